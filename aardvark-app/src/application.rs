@@ -18,19 +18,58 @@
  * SPDX-License-Identifier: GPL-3.0-or-later
  */
 
-use aardvark_doc::{document::DocumentId, service::Service};
+use std::str::FromStr;
+
+use aardvark_doc::{
+    author::Author,
+    checkpoints::Checkpoint,
+    document::{Document, DocumentId, normalize_text},
+    identity::PublicKey,
+    invite::Invite,
+    service::{ColdStorageBackend, Service},
+    tasks::today,
+};
 use adw::prelude::*;
 use adw::subclass::prelude::*;
-use gettextrs::gettext;
-use gtk::{gio, glib, glib::Properties};
-use std::{cell::OnceCell, fs};
-use tracing::error;
+use gettextrs::{gettext, ngettext};
+use gtk::{gio, glib, glib::Properties, glib::clone};
+use std::collections::{HashMap, HashSet};
+use std::{
+    cell::{Cell, OnceCell, RefCell},
+    fs,
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+use tracing::{debug, error, warn};
 
 use crate::AardvarkWindow;
+use crate::background::{self, BACKGROUND_OPTION};
 use crate::config;
+use crate::crash_report;
+use crate::file_transfer;
+use crate::git_mirror::GitMirrors;
+use crate::identity_manager::{self, ANONYMOUS_OPTION};
+use crate::logging;
+use crate::migrations;
+use crate::preferences_dialog::PreferencesDialog;
 use crate::secret;
+use crate::settings::{ColdStorageKind, Settings};
+use crate::shortcuts;
 use crate::system_settings::SystemSettings;
 
+/// Files with these extensions can be opened as new documents.
+const TEXT_FILE_EXTENSIONS: [&str; 3] = ["md", "markdown", "txt"];
+/// How often we look for tasks which became due.
+const TASK_REMINDER_INTERVAL: Duration = Duration::from_secs(60);
+/// How often we look for documents people nearby made discoverable.
+const NEARBY_INVITES_INTERVAL: Duration = Duration::from_secs(30);
+/// How often we check whether we're away, see `update_presence`.
+const PRESENCE_INTERVAL: Duration = Duration::from_secs(15);
+/// We're away after this long without any window focused.
+const AWAY_UNFOCUSED_TIMEOUT: Duration = Duration::from_secs(2 * 60);
+/// We're away after this long without typing, even with a window focused.
+const AWAY_IDLE_TIMEOUT: Duration = Duration::from_secs(15 * 60);
+
 mod imp {
     use super::*;
 
@@ -40,7 +79,35 @@ mod imp {
         #[property(get)]
         pub service: OnceCell<Service>,
         #[property(get)]
+        pub settings: Settings,
+        #[property(get)]
         pub system_settings: SystemSettings,
+        /// This instance runs an anonymous session with a throwaway identity, next to the
+        /// regular one, see [`ANONYMOUS_OPTION`].
+        #[property(get, set, construct_only)]
+        pub anonymous: Cell<bool>,
+        /// Authors of changes which arrived while no window showing the document was focused.
+        pub remote_changes: RefCell<HashMap<DocumentId, Vec<Author>>>,
+        /// Notification ids of the due tasks we already reminded about.
+        pub task_reminders: RefCell<HashSet<String>>,
+        /// Documents we already sent an invite notification for, joined or ignored ones too.
+        pub invites: RefCell<HashSet<DocumentId>>,
+        /// Whether the identity has no data yet, we offer to restore a backup then.
+        pub first_run: Cell<bool>,
+        /// Whether we were started to sync in the background, without opening a window.
+        pub started_in_background: Cell<bool>,
+        /// Keeps the application running without windows while syncing in the background.
+        pub background_hold: RefCell<Option<gio::ApplicationHoldGuard>>,
+        /// Documents we stay subscribed to while syncing in the background.
+        pub background_documents: RefCell<Vec<Document>>,
+        /// When a window was last focused or text typed, see `update_presence`.
+        pub last_activity: Cell<Option<Instant>>,
+        /// Whether the data directory is being migrated during startup.
+        pub migrating: Cell<bool>,
+        /// Whether we were activated while migrating, see `startup`.
+        pub activate_after_migrations: Cell<bool>,
+        /// Files we were asked to open while migrating.
+        pub open_after_migrations: RefCell<Vec<gio::File>>,
     }
 
     #[glib::object_subclass]
@@ -56,8 +123,77 @@ mod imp {
             self.parent_constructed();
             let obj = self.obj();
             obj.setup_gactions();
-            obj.set_accels_for_action("app.quit", &["<primary>q"]);
-            obj.set_accels_for_action("app.new-window", &["<control>n"]);
+            shortcuts::apply(&obj);
+
+            obj.add_main_option(
+                "verbose",
+                glib::Char::from(b'v'),
+                glib::OptionFlags::NONE,
+                glib::OptionArg::None,
+                &gettext("Enable verbose logging"),
+                None,
+            );
+            obj.add_main_option(
+                "log-filter",
+                glib::Char::from(0u8),
+                glib::OptionFlags::NONE,
+                glib::OptionArg::String,
+                &gettext("Set the log filter, uses the same syntax as RUST_LOG"),
+                Some(&gettext("FILTER")),
+            );
+            obj.add_main_option(
+                "verify-store",
+                glib::Char::from(0u8),
+                glib::OptionFlags::NONE,
+                glib::OptionArg::None,
+                &gettext("Check the stored documents for corrupted data and exit"),
+                None,
+            );
+            obj.add_main_option(
+                "repair-store",
+                glib::Char::from(0u8),
+                glib::OptionFlags::NONE,
+                glib::OptionArg::None,
+                &gettext("Move corrupted data out of the way to sync it again and exit"),
+                None,
+            );
+
+            obj.add_main_option(
+                "export-history",
+                glib::Char::from(0u8),
+                glib::OptionFlags::NONE,
+                glib::OptionArg::String,
+                &gettext("Print the history of a document as patch series and exit"),
+                Some(&gettext("DOCUMENT_ID")),
+            );
+
+            obj.add_main_option(
+                BACKGROUND_OPTION,
+                glib::Char::from(0u8),
+                glib::OptionFlags::NONE,
+                glib::OptionArg::None,
+                &gettext("Sync documents in the background without opening a window"),
+                None,
+            );
+
+            obj.add_main_option(
+                ANONYMOUS_OPTION,
+                glib::Char::from(0u8),
+                glib::OptionFlags::NONE,
+                glib::OptionArg::None,
+                &gettext("Join documents with a throwaway identity which is forgotten on exit"),
+                None,
+            );
+
+            if self.anonymous.get() {
+                let (private_key, data_path) = identity_manager::ephemeral_identity()
+                    .expect("Unable to create anonymous identity");
+                let data_dir = gio::File::for_path(data_path);
+                self.service
+                    .set(Service::new_anonymous(&private_key, &data_dir))
+                    .unwrap();
+                return;
+            }
 
             // FIXME: Don't block on loading the identity
             glib::MainContext::new().block_on(async move {
@@ -65,9 +201,8 @@ mod imp {
                     .await
                     .expect("Unable to get or create identity");
 
-                let mut data_path = glib::user_data_dir();
-                data_path.push("Aardvark");
-                data_path.push(private_key.public_key().to_string());
+                let data_path = data_path(&private_key.public_key());
+                self.first_run.set(!data_path.exists());
                 if let Err(error) = fs::create_dir_all(&data_path) {
                     error!("Failed to create data directory: {error}");
                 }
@@ -81,22 +216,303 @@ mod imp {
     }
 
     impl ApplicationImpl for AardvarkApplication {
+        fn handle_local_options(&self, options: &glib::VariantDict) -> glib::ExitCode {
+            let filter = if let Ok(Some(filter)) = options.lookup::<String>("log-filter") {
+                Some(filter)
+            } else if options.contains("verbose") {
+                Some(logging::VERBOSE_FILTER.to_string())
+            } else {
+                None
+            };
+
+            if let Some(filter) = filter {
+                if let Err(error) = logging::set_filter(&filter) {
+                    error!("Invalid log filter \"{filter}\": {error}");
+                    return glib::ExitCode::FAILURE;
+                }
+            }
+
+            if options.contains("verify-store") || options.contains("repair-store") {
+                return self.obj().verify_store(options.contains("repair-store"));
+            }
+
+            if let Ok(Some(document_id)) = options.lookup::<String>("export-history") {
+                return self.obj().export_history(&document_id);
+            }
+
+            if options.contains(BACKGROUND_OPTION) {
+                let obj = self.obj();
+                if let Err(error) = obj.register(gio::Cancellable::NONE) {
+                    error!("Failed to register application: {error}");
+                    return glib::ExitCode::FAILURE;
+                }
+                // An instance which is already running syncs anyway
+                if obj.is_remote() {
+                    return glib::ExitCode::SUCCESS;
+                }
+                self.started_in_background.set(true);
+            }
+
+            self.parent_handle_local_options(options)
+        }
+
         fn startup(&self) {
-            self.obj().service().startup();
             self.parent_startup();
+
+            // Settings and data need to be up to date before the node starts using them
+            let obj = self.obj();
+            let data_dir = obj.service().data_dir().path().expect("Valid file path");
+            match migrations::Migrator::new(&data_dir) {
+                Ok(migrator) if migrator.pending().is_empty() => self.finish_startup(),
+                Ok(_) => {
+                    // Activating and opening files waits till the migrations completed
+                    self.migrating.set(true);
+                    let hold = obj.hold();
+                    glib::spawn_future_local(clone!(
+                        #[weak]
+                        obj,
+                        async move {
+                            let result =
+                                migrations::run_with_progress(obj.upcast_ref(), &data_dir).await;
+                            let imp = obj.imp();
+                            imp.migrating.set(false);
+                            drop(hold);
+                            if let Err(error) = result {
+                                error!("{error}");
+                                obj.quit();
+                                return;
+                            }
+
+                            imp.finish_startup();
+                            if imp.activate_after_migrations.replace(false) {
+                                obj.activate();
+                            }
+                            let files = imp.open_after_migrations.take();
+                            if !files.is_empty() {
+                                obj.open(&files, "");
+                            }
+                        }
+                    ));
+                }
+                Err(error) => {
+                    error!("{error}");
+                    obj.quit();
+                }
+            }
         }
 
         fn shutdown(&self) {
-            self.obj().service().shutdown();
+            let service = self.obj().service();
+            service.shutdown();
+            if self.anonymous.get() {
+                identity_manager::remove_ephemeral_data(&service);
+            }
             self.parent_shutdown();
         }
 
         fn activate(&self) {
-            self.obj().new_window();
+            if self.migrating.get() {
+                self.activate_after_migrations.set(true);
+                return;
+            }
+
+            // Started at login, the hold taken by `update_background` keeps us running
+            if self.started_in_background.replace(false) {
+                return;
+            }
+
+            // Raise the existing window instead of opening another one when the application is
+            // activated a second time, e.g. by launching it again from the desktop
+            if let Some(window) = self.obj().active_window() {
+                window.present();
+            } else {
+                self.obj().new_window();
+            }
+
+            if self.first_run.replace(false) {
+                self.obj().offer_restore_backup();
+            }
+            self.obj().offer_crash_reports();
+        }
+
+        fn open(&self, files: &[gio::File], _hint: &str) {
+            if self.migrating.get() {
+                self.open_after_migrations
+                    .borrow_mut()
+                    .extend_from_slice(files);
+                return;
+            }
+
+            let app = self.obj();
+
+            // Let the launching instance know that we are still busy opening documents
+            app.mark_busy();
+            for file in files {
+                if let Some(document_id) = document_id_from_file(file) {
+                    app.open_document(&document_id);
+                } else if is_text_file(file) {
+                    app.import_file(file);
+                } else {
+                    warn!(
+                        "Can't open {}: neither an aardvark document link nor a text file",
+                        file.uri()
+                    );
+                }
+            }
+            app.unmark_busy();
         }
     }
 
-    impl GtkApplicationImpl for AardvarkApplication {}
+    impl AardvarkApplication {
+        /// Start the node and set up everything depending on it, once the data is migrated.
+        fn finish_startup(&self) {
+            let service = self.obj().service();
+            let settings = self.obj().settings();
+            service.set_gossip_preset(settings.gossip_preset());
+            service.set_network_config(settings.network_config());
+            service.startup();
+            let obj = self.obj();
+            glib::spawn_future_local(clone!(
+                #[weak]
+                obj,
+                #[strong]
+                service,
+                async move {
+                    let linked_documents = identity_manager::add_linked_documents(&service).await;
+                    for (document_id, name) in linked_documents {
+                        obj.notify_invite(&document_id, name.as_deref(), None);
+                    }
+                    identity_manager::restore_backup_documents(&service).await;
+                }
+            ));
+
+            settings.connect_notify_local(
+                None,
+                clone!(
+                    #[weak]
+                    obj,
+                    move |_, pspec| {
+                        if pspec.name().starts_with("cold-storage-") {
+                            obj.setup_cold_storage();
+                            obj.setup_peer_metadata();
+                        } else if pspec.name() == "display-name" {
+                            obj.setup_peer_metadata();
+                        } else if pspec.name() == "run-in-background" && !obj.anonymous() {
+                            obj.update_background();
+                            glib::spawn_future_local(clone!(
+                                #[weak]
+                                obj,
+                                async move { obj.request_background().await }
+                            ));
+                        }
+                    }
+                ),
+            );
+            settings.connect_keybindings_changed(clone!(
+                #[weak]
+                obj,
+                move |_| shortcuts::apply(&obj)
+            ));
+            // The service applies them to the running node
+            settings.connect_gossip_preset_notify(clone!(
+                #[weak]
+                service,
+                move |settings| service.set_gossip_preset(settings.gossip_preset())
+            ));
+            settings.connect_network_config_changed(clone!(
+                #[weak]
+                service,
+                move |settings| service.set_network_config(settings.network_config())
+            ));
+            settings
+                .bind_property("share-presence", &service, "share-presence")
+                .sync_create()
+                .build();
+            obj.setup_cold_storage();
+            obj.update_background();
+
+            let documents = service.documents();
+            for document in documents.iter::<Document>().filter_map(Result::ok) {
+                obj.notify_task_changes(&document);
+                obj.notify_remote_changes(&document);
+                obj.mirror_to_git(&document);
+            }
+            documents.connect_items_changed(clone!(
+                #[weak]
+                obj,
+                move |documents, position, _removed, added| {
+                    for index in position..position + added {
+                        if let Some(document) = documents.item(index).and_downcast::<Document>() {
+                            obj.notify_task_changes(&document);
+                            obj.notify_remote_changes(&document);
+                            obj.mirror_to_git(&document);
+                        }
+                    }
+                }
+            ));
+
+            glib::timeout_add_local(
+                TASK_REMINDER_INTERVAL,
+                clone!(
+                    #[weak]
+                    obj,
+                    #[upgrade_or]
+                    glib::ControlFlow::Break,
+                    move || {
+                        obj.remind_due_tasks();
+                        glib::ControlFlow::Continue
+                    }
+                ),
+            );
+            glib::timeout_add_local(
+                NEARBY_INVITES_INTERVAL,
+                clone!(
+                    #[weak]
+                    obj,
+                    #[upgrade_or]
+                    glib::ControlFlow::Break,
+                    move || {
+                        glib::spawn_future_local(clone!(
+                            #[weak]
+                            obj,
+                            async move { obj.notify_nearby_invites().await }
+                        ));
+                        glib::ControlFlow::Continue
+                    }
+                ),
+            );
+            obj.record_activity();
+            glib::timeout_add_local(
+                PRESENCE_INTERVAL,
+                clone!(
+                    #[weak]
+                    obj,
+                    #[upgrade_or]
+                    glib::ControlFlow::Break,
+                    move || {
+                        obj.update_presence();
+                        glib::ControlFlow::Continue
+                    }
+                ),
+            );
+        }
+    }
+
+    impl GtkApplicationImpl for AardvarkApplication {
+        fn window_added(&self, window: &gtk::Window) {
+            self.parent_window_added(window);
+
+            window.connect_is_active_notify(clone!(
+                #[weak(rename_to = this)]
+                self,
+                move |window| {
+                    if window.is_active() {
+                        this.obj().record_activity();
+                    }
+                }
+            ));
+        }
+    }
     impl AdwApplicationImpl for AardvarkApplication {}
 }
 
@@ -107,13 +523,36 @@ glib::wrapper! {
 }
 
 impl AardvarkApplication {
-    pub fn new(application_id: &str, flags: &gio::ApplicationFlags) -> Self {
+    pub fn new(application_id: &str, flags: &gio::ApplicationFlags, anonymous: bool) -> Self {
         glib::Object::builder()
             .property("application-id", application_id)
             .property("flags", flags)
+            .property("anonymous", anonymous)
             .build()
     }
 
+    /// Open `document_id` in a new instance running an anonymous session.
+    pub fn join_anonymously(&self, document_id: &DocumentId) {
+        let uri = format!("{}:{document_id}", config::URI_SCHEME);
+        let result = std::env::current_exe()
+            .map_err(|error| error.to_string())
+            .and_then(|exe| {
+                gio::Subprocess::newv(
+                    &[
+                        exe.as_os_str(),
+                        format!("--{ANONYMOUS_OPTION}").as_ref(),
+                        uri.as_ref(),
+                    ],
+                    gio::SubprocessFlags::NONE,
+                )
+                .map_err(|error| error.to_string())
+            });
+        if let Err(error) = result {
+            error!("Failed to start anonymous session: {error}");
+            self.show_toast(&gettext("Couldn't join anonymously"));
+        }
+    }
+
     pub fn window_for_document_id(
         &self,
         document_id: &DocumentId,
@@ -121,7 +560,512 @@ impl AardvarkApplication {
         self.windows()
             .into_iter()
             .filter_map(|window| window.downcast::<super::AardvarkWindow>().ok())
-            .find(|window| &window.document().id() == document_id)
+            .find(|window| window.has_document(document_id))
+    }
+
+    /// Open the document with the given id in a new tab of the active window.
+    ///
+    /// If the document is already open in a window, its tab is selected instead to avoid
+    /// subscribing to the same document twice.
+    pub fn open_document(&self, document_id: &DocumentId) {
+        if let Some(window) = self.window_for_document_id(document_id) {
+            window.present_document(document_id);
+            return;
+        }
+
+        let service = self.service();
+        let document = service.document(document_id);
+        if let Some(window) = self.active_window().and_downcast::<AardvarkWindow>() {
+            window.add_document(&document);
+            window.present();
+        } else {
+            let window = AardvarkWindow::new(self, &service, Some(&document));
+            window.present();
+        }
+    }
+
+    /// Open the document with the given id in a new window.
+    ///
+    /// Like [`Self::open_document`] the window already showing the document is raised instead.
+    pub fn open_document_in_new_window(&self, document_id: &DocumentId) {
+        if let Some(window) = self.window_for_document_id(document_id) {
+            window.present_document(document_id);
+            return;
+        }
+
+        let document = self.service().document(document_id);
+        AardvarkWindow::new(self, &self.service(), Some(&document)).present();
+    }
+
+    /// Create a new document with the contents of a text file and open it.
+    pub fn import_file(&self, file: &gio::File) {
+        // Keep the application alive till the file is loaded
+        let guard = self.hold();
+        glib::spawn_future_local(clone!(
+            #[weak(rename_to = app)]
+            self,
+            #[strong]
+            file,
+            async move {
+                let contents = match file_transfer::read(&file).await {
+                    Ok(contents) => contents,
+                    Err(error) => {
+                        error!("Failed to read {}: {error}", file.uri());
+                        app.show_toast(&gettext("Failed to open file"));
+                        drop(guard);
+                        return;
+                    }
+                };
+
+                let text = normalize_text(&contents);
+                let by_paragraph = app.settings().import_by_paragraph();
+                let document = Document::new(&app.service(), None);
+                app.open_document(&document.id());
+                let result = if by_paragraph {
+                    let origin = format!(
+                        "Imported from {}",
+                        file.basename().unwrap_or_default().display()
+                    );
+                    document.insert_paragraphs(0, &text, &origin)
+                } else {
+                    document.insert_text(0, &text)
+                };
+                if let Err(error) = result {
+                    error!("Failed to insert contents of {}: {error}", file.uri());
+                }
+                drop(guard);
+            }
+        ));
+    }
+
+    /// Tell peers on the local network who we are, see `Service::set_peer_metadata`.
+    ///
+    /// Anonymous sessions don't give away the display name.
+    fn setup_peer_metadata(&self) {
+        let display_name = Some(self.settings().display_name().trim().to_owned())
+            .filter(|display_name| !display_name.is_empty() && !self.anonymous());
+
+        glib::spawn_future_local(clone!(
+            #[weak(rename_to = app)]
+            self,
+            async move {
+                app.service()
+                    .set_peer_metadata(display_name, Some(config::VERSION.to_owned()))
+                    .await;
+            }
+        ));
+    }
+
+    /// Start pinning documents to the cold storage configured in the settings.
+    ///
+    /// Documents of anonymous sessions aren't pinned, nothing of them should outlive the session.
+    fn setup_cold_storage(&self) {
+        if self.anonymous() {
+            return;
+        }
+        let settings = self.settings();
+
+        glib::spawn_future_local(clone!(
+            #[weak(rename_to = app)]
+            self,
+            async move {
+                let backend = settings.cold_storage_backend();
+                let url = settings.cold_storage_url();
+                let username = settings.cold_storage_username();
+
+                let secret = if backend == ColdStorageKind::Disabled {
+                    None
+                } else {
+                    match secret::cold_storage_secret().await {
+                        Ok(secret) => secret,
+                        Err(error) => {
+                            error!("Failed to load cold storage secret: {error}");
+                            None
+                        }
+                    }
+                };
+
+                let backend = match backend {
+                    ColdStorageKind::S3 => Some(ColdStorageBackend::S3 {
+                        endpoint: url,
+                        bucket: settings.cold_storage_bucket(),
+                        region: settings.cold_storage_region(),
+                        access_key: username,
+                        secret_key: secret.unwrap_or_default(),
+                    }),
+                    ColdStorageKind::WebDav => Some(ColdStorageBackend::WebDav {
+                        url,
+                        username: (!username.is_empty()).then_some(username),
+                        password: secret,
+                    }),
+                    ColdStorageKind::Disabled => None,
+                };
+                let proxy = settings.cold_storage_proxy().trim().to_owned();
+
+                app.service()
+                    .set_cold_storage(backend, (!proxy.is_empty()).then_some(proxy))
+                    .await;
+            }
+        ));
+    }
+
+    /// Send a notification when someone else toggles a task we created in `document`.
+    fn notify_task_changes(&self, document: &Document) {
+        document.connect_task_toggled(clone!(
+            #[weak(rename_to = app)]
+            self,
+            move |document, author, text, checked| {
+                let title = if checked {
+                    gettext("{author} checked “{task}”")
+                } else {
+                    gettext("{author} unchecked “{task}”")
+                }
+                .replace("{author}", &author.emoji())
+                .replace("{task}", text);
+
+                let notification = gio::Notification::new(&title);
+                if let Some(name) = document.name() {
+                    notification.set_body(Some(&name));
+                }
+                app.send_notification(
+                    Some(&format!("task-{}-{}", document.id(), text)),
+                    &notification,
+                );
+            }
+        ));
+    }
+
+    /// Send an invite notification for every document people nearby made discoverable.
+    async fn notify_nearby_invites(&self) {
+        for document in self.service().nearby_documents().await {
+            let shared_by = document
+                .display_name
+                .clone()
+                .unwrap_or_else(|| Author::new(&document.public_key).name());
+            self.notify_invite(&document.id, document.name.as_deref(), Some(&shared_by));
+        }
+    }
+
+    /// Offer to join the document with `document_id`, once per document.
+    ///
+    /// The buttons of the notification activate `app.join-document` and `app.ignore-document`.
+    fn notify_invite(&self, document_id: &DocumentId, name: Option<&str>, shared_by: Option<&str>) {
+        let enabled = self.settings().notify_invites();
+        if !enabled || !self.imp().invites.borrow_mut().insert(document_id.clone()) {
+            return;
+        }
+
+        let name = name
+            .map(String::from)
+            .unwrap_or_else(|| gettext("Untitled Document"));
+        let title = match shared_by {
+            Some(shared_by) => gettext("{author} shared “{name}”")
+                .replace("{author}", shared_by)
+                .replace("{name}", &name),
+            None => gettext("“{}” was added from a linked device").replace("{}", &name),
+        };
+        let notification = gio::Notification::new(&title);
+        let target = document_id.to_string().to_variant();
+        notification.add_button_with_target_value(
+            &gettext("Join"),
+            "app.join-document",
+            Some(&target),
+        );
+        notification.add_button_with_target_value(
+            &gettext("Ignore"),
+            "app.ignore-document",
+            Some(&target),
+        );
+        self.send_notification(Some(&invite_notification_id(document_id)), &notification);
+    }
+
+    /// Remind about open tasks which are due today or overdue, once per task.
+    ///
+    /// Tasks assigned to someone else are left to them.
+    fn remind_due_tasks(&self) {
+        let service = self.service();
+        let own_public_key = service.private_key().public_key();
+        let today = today();
+
+        for document in service
+            .documents()
+            .iter::<Document>()
+            .filter_map(Result::ok)
+        {
+            for task in document.tasks() {
+                let Some(due_date) = task.due_date.as_ref() else {
+                    continue;
+                };
+                if task.checked
+                    || due_date > &today
+                    || task
+                        .assignee
+                        .as_ref()
+                        .is_some_and(|assignee| assignee != &own_public_key)
+                {
+                    continue;
+                }
+
+                let id = format!(
+                    "due-{}-{}-{}",
+                    document.id(),
+                    due_date.format("%F").unwrap_or_default(),
+                    task.text
+                );
+                if !self.imp().task_reminders.borrow_mut().insert(id.clone()) {
+                    continue;
+                }
+
+                let title = if task.is_overdue() {
+                    gettext("“{task}” is overdue")
+                } else {
+                    gettext("“{task}” is due today")
+                }
+                .replace("{task}", &task.text);
+                let notification = gio::Notification::new(&title);
+                if let Some(name) = document.name() {
+                    notification.set_body(Some(&name));
+                }
+                notification.set_default_action_and_target_value(
+                    "app.focus-document",
+                    Some(&document.id().to_string().to_variant()),
+                );
+                self.send_notification(Some(&id), &notification);
+            }
+        }
+    }
+
+    /// Commit every snapshot and new checkpoint of `document` to its git repository, if it's
+    /// mirrored to one, see [`crate::git_mirror`].
+    fn mirror_to_git(&self, document: &Document) {
+        document.connect_snapshot_stored(clone!(
+            #[weak(rename_to = app)]
+            self,
+            move |document| {
+                let data_dir = app.service().data_dir().path().expect("Valid file path");
+                if let Err(error) = GitMirrors::load(&data_dir).commit_snapshot(document) {
+                    error!("Failed to commit {} to git: {error}", document.id());
+                }
+            }
+        ));
+
+        document.checkpoints().connect_items_changed(clone!(
+            #[weak(rename_to = app)]
+            self,
+            #[weak]
+            document,
+            move |checkpoints, _, _, _| {
+                // Checkpoints are sorted newest first
+                let Some(checkpoint) = checkpoints.item(0).and_downcast::<Checkpoint>() else {
+                    return;
+                };
+                let data_dir = app.service().data_dir().path().expect("Valid file path");
+                if let Err(error) =
+                    GitMirrors::load(&data_dir).commit_checkpoint(&document, &checkpoint)
+                {
+                    error!("Failed to commit {} to git: {error}", document.id());
+                }
+            }
+        ));
+    }
+
+    /// Send a notification when others change `document` while it isn't visible.
+    fn notify_remote_changes(&self, document: &Document) {
+        document.connect_remote_change(clone!(
+            #[weak(rename_to = app)]
+            self,
+            move |document, author| {
+                let enabled = app.settings().notify_remote_changes();
+                let focused = app
+                    .windows()
+                    .into_iter()
+                    .filter_map(|window| window.downcast::<AardvarkWindow>().ok())
+                    .any(|window| window.is_active() && window.has_document(&document.id()));
+                if !enabled || focused {
+                    return;
+                }
+
+                let mut remote_changes = app.imp().remote_changes.borrow_mut();
+                let authors = remote_changes.entry(document.id()).or_default();
+                authors.push(author.clone());
+                let notification = remote_changes_notification(document, authors);
+                drop(remote_changes);
+
+                app.send_notification(
+                    Some(&remote_changes_notification_id(&document.id())),
+                    &notification,
+                );
+            }
+        ));
+    }
+
+    /// Forget about changes to the document which were notified, it's being looked at again.
+    pub fn withdraw_remote_changes(&self, document_id: &DocumentId) {
+        if self
+            .imp()
+            .remote_changes
+            .borrow_mut()
+            .remove(document_id)
+            .is_some()
+        {
+            self.withdraw_notification(&remote_changes_notification_id(document_id));
+        }
+    }
+
+    /// The user focused a window or typed something, they're not away.
+    pub fn record_activity(&self) {
+        self.imp().last_activity.set(Some(Instant::now()));
+        self.service().set_away(false);
+    }
+
+    /// Tell other authors we're away after a while without focus or typing, see
+    /// `Service::away`.
+    fn update_presence(&self) {
+        let Some(last_activity) = self.imp().last_activity.get() else {
+            return;
+        };
+        let focused = self.windows().iter().any(|window| window.is_active());
+        let timeout = if focused {
+            AWAY_IDLE_TIMEOUT
+        } else {
+            AWAY_UNFOCUSED_TIMEOUT
+        };
+        self.service().set_away(last_activity.elapsed() > timeout);
+    }
+
+    /// Start or stop syncing in the background, following the `run-in-background` setting.
+    ///
+    /// See the `background` module.
+    fn update_background(&self) {
+        let enabled = self.settings().run_in_background() && !self.anonymous();
+        let imp = self.imp();
+        if enabled == imp.background_hold.borrow().is_some() {
+            return;
+        }
+
+        if enabled {
+            imp.background_hold.replace(Some(self.hold()));
+            let documents: Vec<Document> = self
+                .service()
+                .documents()
+                .iter::<Document>()
+                .filter_map(Result::ok)
+                .filter(|document| !document.archived() && document.trashed_at().is_none())
+                .collect();
+            for document in &documents {
+                document.add_view();
+            }
+            imp.background_documents.replace(documents);
+        } else {
+            for document in imp.background_documents.take() {
+                document.remove_view();
+            }
+            imp.background_hold.take();
+        }
+    }
+
+    /// Ask the Background portal to start us at login, or to stop doing so.
+    async fn request_background(&self) {
+        let settings = self.settings();
+        let enabled = settings.run_in_background();
+        match background::request(enabled).await {
+            Ok(false) if enabled => {
+                self.show_toast(&gettext("Running in the background isn't allowed"));
+                settings.set_run_in_background(false);
+            }
+            Ok(_) => {}
+            Err(error) => warn!("Failed to request running in the background: {error}"),
+        }
+    }
+
+    /// Check the store from the command line, Aardvark must not be running at the same time.
+    fn verify_store(&self, repair: bool) -> glib::ExitCode {
+        let service = self.service();
+        let data_dir = service.data_dir().path().expect("Valid file path");
+        let migrated = migrations::Migrator::new(&data_dir).and_then(|migrator| {
+            migrator.run(|_, description| {
+                if !description.is_empty() {
+                    println!("{description}");
+                }
+            })
+        });
+        if let Err(error) = migrated {
+            eprintln!("{error}");
+            return glib::ExitCode::FAILURE;
+        }
+
+        service.startup();
+        let report = glib::MainContext::new().block_on(service.verify_store(repair));
+        service.shutdown();
+
+        let report = match report {
+            Ok(report) => report,
+            Err(error) => {
+                eprintln!("Failed to verify the store: {error}");
+                return glib::ExitCode::FAILURE;
+            }
+        };
+        println!(
+            "Checked {} operations in {} logs",
+            report.operations, report.logs
+        );
+        for log in &report.corrupted_logs {
+            println!(
+                "{:?} log of {} in document {}: {}{}",
+                log.log_type,
+                log.public_key,
+                log.document_id,
+                log.reason,
+                if log.quarantined {
+                    " (quarantined)"
+                } else {
+                    ""
+                }
+            );
+        }
+
+        if report.corrupted_logs.is_empty() || repair {
+            glib::ExitCode::SUCCESS
+        } else {
+            println!("Run with --repair-store to sync corrupted logs again");
+            glib::ExitCode::FAILURE
+        }
+    }
+
+    /// Print the history of the document with `document_id` to stdout, see
+    /// [`Document::export_stored_history`].
+    fn export_history(&self, document_id: &str) -> glib::ExitCode {
+        let Ok(document_id) = DocumentId::from_str(document_id) else {
+            eprintln!("Invalid document id: {document_id}");
+            return glib::ExitCode::FAILURE;
+        };
+
+        let service = self.service();
+        service.startup();
+        let Some(document) = service.documents().by_id(&document_id) else {
+            service.shutdown();
+            eprintln!("No document with id {document_id}");
+            return glib::ExitCode::FAILURE;
+        };
+        let patches = glib::MainContext::new().block_on(document.export_stored_history());
+        service.shutdown();
+
+        match patches {
+            Ok(patches) => {
+                print!("{patches}");
+                glib::ExitCode::SUCCESS
+            }
+            Err(error) => {
+                eprintln!("Failed to export history: {error}");
+                glib::ExitCode::FAILURE
+            }
+        }
+    }
+
+    fn show_toast(&self, title: &str) {
+        if let Some(window) = self.active_window().and_downcast::<AardvarkWindow>() {
+            window.add_toast(adw::Toast::new(title));
+        }
     }
 
     fn setup_gactions(&self) {
@@ -134,14 +1078,252 @@ impl AardvarkApplication {
         let new_window_action = gio::ActionEntry::builder("new-window")
             .activate(move |app: &Self, _, _| app.new_window())
             .build();
-        self.add_action_entries([quit_action, about_action, new_window_action]);
+        let preferences_action = gio::ActionEntry::builder("preferences")
+            .activate(move |app: &Self, _, _| app.show_preferences())
+            .build();
+        let restore_backup_action = gio::ActionEntry::builder("restore-backup")
+            .activate(move |app: &Self, _, _| app.restore_backup())
+            .build();
+        let copy_debug_log_action = gio::ActionEntry::builder("copy-debug-log")
+            .activate(move |app: &Self, _, _| app.copy_debug_log())
+            .build();
+        let paste_invite_action = gio::ActionEntry::builder("paste-invite")
+            .activate(move |app: &Self, _, _| {
+                glib::spawn_future_local(clone!(
+                    #[weak]
+                    app,
+                    async move { app.paste_invite().await }
+                ));
+            })
+            .build();
+        let focus_document_action = gio::ActionEntry::builder("focus-document")
+            .parameter_type(Some(&String::static_variant_type()))
+            .activate(move |app: &Self, _, parameter| {
+                let document_id = parameter
+                    .and_then(|parameter| parameter.get::<String>())
+                    .and_then(|document_id| DocumentId::from_str(&document_id).ok());
+                if let Some(document_id) = document_id {
+                    app.open_document(&document_id);
+                }
+            })
+            .build();
+        let join_document_action = gio::ActionEntry::builder("join-document")
+            .parameter_type(Some(&String::static_variant_type()))
+            .activate(move |app: &Self, _, parameter| {
+                let document_id = parameter
+                    .and_then(|parameter| parameter.get::<String>())
+                    .and_then(|document_id| DocumentId::from_str(&document_id).ok());
+                if let Some(document_id) = document_id {
+                    app.withdraw_notification(&invite_notification_id(&document_id));
+                    app.open_document(&document_id);
+                }
+            })
+            .build();
+        let ignore_document_action = gio::ActionEntry::builder("ignore-document")
+            .parameter_type(Some(&String::static_variant_type()))
+            .activate(move |app: &Self, _, parameter| {
+                let document_id = parameter
+                    .and_then(|parameter| parameter.get::<String>())
+                    .and_then(|document_id| DocumentId::from_str(&document_id).ok());
+                if let Some(document_id) = document_id {
+                    // Stays in the set of invites, so we don't notify about it again
+                    app.withdraw_notification(&invite_notification_id(&document_id));
+                }
+            })
+            .build();
+        let verbose_logging_action = gio::ActionEntry::builder("verbose-logging")
+            .state(false.to_variant())
+            .activate(move |_app: &Self, action, _| {
+                let verbose = !action
+                    .state()
+                    .and_then(|s| s.get::<bool>())
+                    .unwrap_or(false);
+                let filter = if verbose {
+                    logging::VERBOSE_FILTER.to_string()
+                } else {
+                    std::env::var("RUST_LOG").unwrap_or_default()
+                };
+
+                match logging::set_filter(&filter) {
+                    Ok(()) => action.set_state(&verbose.to_variant()),
+                    Err(error) => error!("Failed to change log filter: {error}"),
+                }
+            })
+            .build();
+        self.add_action_entries([
+            quit_action,
+            about_action,
+            new_window_action,
+            preferences_action,
+            restore_backup_action,
+            copy_debug_log_action,
+            paste_invite_action,
+            focus_document_action,
+            join_document_action,
+            ignore_document_action,
+            verbose_logging_action,
+        ]);
+
+        let settings = self.settings();
+        self.add_action(&settings.create_action("gossip-preset"));
+        self.add_action(&settings.create_action("notify-remote-changes"));
+        self.add_action(&settings.create_action("notify-invites"));
+        self.add_action(&settings.create_action("hide-frontmatter"));
+        self.add_action(&settings.create_action("import-by-paragraph"));
+        self.add_action(&settings.create_action("author-order"));
+    }
+
+    fn copy_debug_log(&self) {
+        let Some(window) = self.active_window() else {
+            return;
+        };
+
+        let log = format!(
+            "Aardvark {} ({})\n\n{}",
+            config::VERSION,
+            config::APP_ID,
+            logging::recent_records()
+        );
+        window.clipboard().set_text(&log);
+
+        self.show_toast(&gettext("Debug log copied to clipboard"));
+    }
+
+    /// Join the document of the invite in the clipboard, see [`Invite`].
+    async fn paste_invite(&self) {
+        let Some(window) = self.active_window() else {
+            return;
+        };
+        let text = match window.clipboard().read_text_future().await {
+            Ok(text) => text.unwrap_or_default(),
+            Err(error) => {
+                error!("Failed to read clipboard: {error}");
+                return;
+            }
+        };
+
+        match Invite::from_str(&text) {
+            Ok(invite) => {
+                debug!(
+                    "Joining document {} from invite with {} peers",
+                    invite.document_id,
+                    invite.peers.len()
+                );
+                self.open_document(&invite.document_id);
+            }
+            Err(error) => {
+                warn!("Failed to read invite: {error}");
+                self.show_toast(&gettext("No invite in the clipboard"));
+            }
+        }
     }
 
     fn new_window(&self) {
-        let window = AardvarkWindow::new(self, &self.service());
+        let window = AardvarkWindow::new(self, &self.service(), None);
         window.present();
     }
 
+    fn show_preferences(&self) {
+        let window = self.active_window();
+        PreferencesDialog::new().present(window.as_ref());
+    }
+
+    fn restore_backup(&self) {
+        let window = self.active_window();
+        let dialog = PreferencesDialog::new();
+        dialog.present(window.as_ref());
+        dialog.restore_backup();
+    }
+
+    /// Point new users who already used Aardvark elsewhere to their backup.
+    fn offer_restore_backup(&self) {
+        if let Some(window) = self.active_window().and_downcast::<AardvarkWindow>() {
+            let toast = adw::Toast::builder()
+                .title(gettext(
+                    "Used Aardvark before? Restore your documents from a backup",
+                ))
+                .button_label(gettext("_Restore…"))
+                .action_name("app.restore-backup")
+                .timeout(0)
+                .build();
+            window.add_toast(toast);
+        }
+    }
+
+    /// Ask whether to report the crashes since we were asked last, see [`crash_report`].
+    fn offer_crash_reports(&self) {
+        let Some(window) = self.active_window() else {
+            return;
+        };
+        let reports = crash_report::take_reports();
+        if reports.is_empty() {
+            return;
+        }
+        let report = reports.join("\n\n");
+
+        // Let users see what they're about to share
+        let buffer = gtk::TextBuffer::new(None);
+        buffer.set_text(&report);
+        let text_view = gtk::TextView::builder()
+            .buffer(&buffer)
+            .editable(false)
+            .monospace(true)
+            .top_margin(6)
+            .bottom_margin(6)
+            .left_margin(6)
+            .right_margin(6)
+            .build();
+        let scrollview = gtk::ScrolledWindow::builder()
+            .child(&text_view)
+            .min_content_height(200)
+            .css_classes(["card"])
+            .build();
+        let expander = gtk::Expander::builder()
+            .label(gettext("Show Report"))
+            .child(&scrollview)
+            .build();
+
+        let dialog = adw::AlertDialog::builder()
+            .heading(gettext("Aardvark Quit Unexpectedly"))
+            .body(gettext(
+                "Reporting the problem helps fixing it. The report only contains technical details, none of your documents.",
+            ))
+            .extra_child(&expander)
+            .close_response("close")
+            .default_response("report")
+            .build();
+        dialog.add_responses(&[
+            ("close", &gettext("_Don't Report")),
+            ("report", &gettext("_Report Problem")),
+        ]);
+        dialog.set_response_appearance("report", adw::ResponseAppearance::Suggested);
+        dialog.connect_response(
+            Some("report"),
+            clone!(
+                #[weak(rename_to = app)]
+                self,
+                #[weak]
+                window,
+                move |_, _| {
+                    // Too long for the link, the report is pasted into the new issue instead
+                    window.clipboard().set_text(&report);
+                    let url = format!("{}/new", crash_report::ISSUE_URL);
+                    gtk::UriLauncher::new(&url).launch(
+                        Some(&window),
+                        gio::Cancellable::NONE,
+                        |result| {
+                            if let Err(error) = result {
+                                warn!("Failed to open issue tracker: {error}");
+                            }
+                        },
+                    );
+                    app.show_toast(&gettext("Crash report copied to clipboard"));
+                }
+            ),
+        );
+        dialog.present(Some(&window));
+    }
+
     fn show_about(&self) {
         let window = self.active_window().unwrap();
         let about = adw::AboutDialog::builder()
@@ -149,7 +1331,7 @@ impl AardvarkApplication {
             .application_icon(config::APP_ID)
             .license_type(gtk::License::Gpl30)
             .website("https://github.com/p2panda/aardvark")
-            .issue_url("https://github.com/p2panda/aardvark/issues")
+            .issue_url(crash_report::ISSUE_URL)
             .support_url("https://matrix.to/#/#aardvark:gnome.org")
             .version(config::VERSION)
             .copyright(gettext("© 2024-2025 The Aardvark Team"))
@@ -175,6 +1357,95 @@ impl AardvarkApplication {
     }
 }
 
+/// Directory containing the database and other data of an identity.
+pub(crate) fn data_path(public_key: &PublicKey) -> PathBuf {
+    let mut data_path = glib::user_data_dir();
+    data_path.push("Aardvark");
+    data_path.push(public_key.to_string());
+    data_path
+}
+
+/// Extract the document id from an `aardvark:<document-id>` or `aardvark://<document-id>` link.
+pub(crate) fn document_id_from_file(file: &gio::File) -> Option<DocumentId> {
+    if !file.has_uri_scheme(config::URI_SCHEME) {
+        return None;
+    }
+
+    let uri = file.uri();
+    let document_id = uri
+        .strip_prefix(config::URI_SCHEME)?
+        .trim_start_matches(':')
+        .trim_start_matches('/')
+        .trim_end_matches('/');
+
+    DocumentId::from_str(document_id).ok()
+}
+
+fn invite_notification_id(document_id: &DocumentId) -> String {
+    format!("invite-{document_id}")
+}
+
+fn remote_changes_notification_id(document_id: &DocumentId) -> String {
+    format!("remote-changes-{document_id}")
+}
+
+/// "3 new changes from 🦊 Red Fox in “Meeting notes”", clicking it focuses the document.
+fn remote_changes_notification(document: &Document, authors: &[Author]) -> gio::Notification {
+    let count = authors.len() as u32;
+    let name = document
+        .name()
+        .unwrap_or_else(|| gettext("Untitled Document"));
+
+    let mut names: Vec<String> = Vec::new();
+    for author in authors {
+        let author_name = format!("{} {}", author.emoji(), author.name());
+        if !names.contains(&author_name) {
+            names.push(author_name);
+        }
+    }
+
+    let title = if let [author_name] = names.as_slice() {
+        ngettext(
+            "{count} new change from {author} in “{document}”",
+            "{count} new changes from {author} in “{document}”",
+            count,
+        )
+        .replace("{author}", author_name)
+    } else {
+        ngettext(
+            "{count} new change in “{document}”",
+            "{count} new changes in “{document}”",
+            count,
+        )
+    }
+    .replace("{count}", &count.to_string())
+    .replace("{document}", &name);
+
+    let notification = gio::Notification::new(&title);
+    if names.len() > 1 {
+        notification.set_body(Some(&names.join(", ")));
+    }
+    notification.set_default_action_and_target_value(
+        "app.focus-document",
+        Some(&document.id().to_string().to_variant()),
+    );
+    notification
+}
+
+/// Whether the file looks like something we can import as the text of a new document.
+pub(crate) fn is_text_file(file: &gio::File) -> bool {
+    file.path()
+        .and_then(|path| {
+            path.extension()
+                .map(|extension| extension.to_ascii_lowercase())
+        })
+        .is_some_and(|extension| {
+            TEXT_FILE_EXTENSIONS
+                .iter()
+                .any(|supported| extension == *supported)
+        })
+}
+
 impl Default for AardvarkApplication {
     fn default() -> Self {
         gio::Application::default()
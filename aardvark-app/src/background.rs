@@ -0,0 +1,49 @@
+/* background.rs
+ *
+ * Copyright 2025 The Aardvark Developers
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ *
+ * SPDX-License-Identifier: GPL-3.0-or-later
+ */
+
+//! Syncing documents in the background, without any window being open.
+//!
+//! With the `run-in-background` setting enabled, the application keeps running after the last
+//! window was closed and stays subscribed to all documents, so they're up to date once the user
+//! opens one. The Background portal starts it at login with [`BACKGROUND_OPTION`], which syncs
+//! without opening a window.
+
+use ashpd::desktop::background::Background;
+use gettextrs::gettext;
+
+/// Command line option to start syncing in the background without opening a window.
+pub const BACKGROUND_OPTION: &str = "background";
+
+/// Ask the Background portal to let us run without windows, and to start us at login if
+/// `autostart` is set.
+///
+/// Returns whether running in the background is allowed.
+pub async fn request(autostart: bool) -> Result<bool, ashpd::Error> {
+    let response = Background::request()
+        .reason(gettext("Keep documents in sync while no window is open").as_str())
+        .auto_start(autostart)
+        .command(["aardvark", &format!("--{BACKGROUND_OPTION}")])
+        .dbus_activatable(false)
+        .send()
+        .await?
+        .response()?;
+
+    Ok(response.run_in_background())
+}
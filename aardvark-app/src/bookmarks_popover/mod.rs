@@ -0,0 +1,234 @@
+/* Copyright 2025 The Aardvark Developers
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ *
+ * SPDX-License-Identifier: GPL-3.0-or-later
+ */
+
+use std::cell::RefCell;
+
+use adw::prelude::*;
+use adw::subclass::prelude::*;
+use gettextrs::gettext;
+use gtk::{glib, glib::clone};
+use tracing::error;
+
+use aardvark_doc::{bookmarks::Bookmark, document::Document};
+
+mod imp {
+    use super::*;
+
+    #[derive(Debug, Default, glib::Properties)]
+    #[properties(wrapper_type = super::BookmarksPopover)]
+    pub struct BookmarksPopover {
+        bookmark_list_box: gtk::ListBox,
+        stack: gtk::Stack,
+        empty_page: adw::StatusPage,
+        name_entry: gtk::Entry,
+        #[property(get, set = Self::set_document, nullable)]
+        document: RefCell<Option<Document>>,
+        items_changed_handler: RefCell<Option<glib::SignalHandlerId>>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for BookmarksPopover {
+        const NAME: &'static str = "AardvarkBookmarksPopover";
+        type Type = super::BookmarksPopover;
+        type ParentType = gtk::Popover;
+    }
+
+    #[glib::derived_properties]
+    impl ObjectImpl for BookmarksPopover {
+        fn constructed(&self) {
+            self.parent_constructed();
+            let obj = self.obj();
+
+            self.bookmark_list_box
+                .set_selection_mode(gtk::SelectionMode::None);
+            let scrollview = gtk::ScrolledWindow::builder()
+                .child(&self.bookmark_list_box)
+                .hscrollbar_policy(gtk::PolicyType::Never)
+                .propagate_natural_height(true)
+                .propagate_natural_width(true)
+                .max_content_height(400)
+                .build();
+            self.empty_page.set_icon_name(Some("bookmark-new-symbolic"));
+            self.empty_page.set_title(&gettext("No Bookmarks"));
+            self.empty_page.set_description(Some(&gettext(
+                "Bookmarks mark places in the document for everyone editing it",
+            )));
+            self.empty_page.add_css_class("compact");
+            self.stack.add_named(&scrollview, Some("bookmarks"));
+            self.stack.add_named(&self.empty_page, Some("empty"));
+
+            self.name_entry
+                .set_placeholder_text(Some(&gettext("Bookmark Name")));
+            self.name_entry.set_hexpand(true);
+            self.name_entry.connect_activate(clone!(
+                #[weak(rename_to = this)]
+                self,
+                move |_| this.add_bookmark()
+            ));
+            let add_button = gtk::Button::builder()
+                .label(gettext("_Add"))
+                .use_underline(true)
+                .css_classes(["suggested-action"])
+                .build();
+            add_button.connect_clicked(clone!(
+                #[weak(rename_to = this)]
+                self,
+                move |_| this.add_bookmark()
+            ));
+            let add_box = gtk::Box::builder()
+                .spacing(6)
+                .margin_start(6)
+                .margin_end(6)
+                .margin_bottom(6)
+                .build();
+            add_box.append(&self.name_entry);
+            add_box.append(&add_button);
+
+            let content = gtk::Box::new(gtk::Orientation::Vertical, 6);
+            content.append(&self.stack);
+            content.append(&add_box);
+            obj.set_child(Some(&content));
+            obj.add_css_class("bookmarks-popover");
+
+            obj.connect_map(|obj| {
+                obj.imp().name_entry.grab_focus();
+            });
+        }
+
+        fn dispose(&self) {
+            self.set_document(None);
+        }
+    }
+
+    impl BookmarksPopover {
+        fn set_document(&self, document: Option<Document>) {
+            if let Some(handler) = self.items_changed_handler.take() {
+                if let Some(document) = self.document.borrow().as_ref() {
+                    document.bookmarks().disconnect(handler);
+                }
+            }
+
+            let bookmarks = document.as_ref().map(Document::bookmarks);
+            self.bookmark_list_box.bind_model(
+                bookmarks.as_ref(),
+                clone!(
+                    #[weak(rename_to = this)]
+                    self,
+                    #[upgrade_or_panic]
+                    move |bookmark| {
+                        let bookmark = bookmark.downcast_ref::<Bookmark>().unwrap();
+                        this.bookmark_row(bookmark).upcast()
+                    }
+                ),
+            );
+            if let Some(bookmarks) = bookmarks.as_ref() {
+                let handler = bookmarks.connect_items_changed(clone!(
+                    #[weak(rename_to = this)]
+                    self,
+                    move |_, _, _, _| this.update_stack()
+                ));
+                self.items_changed_handler.replace(Some(handler));
+            }
+
+            self.document.replace(document);
+            self.update_stack();
+        }
+
+        fn update_stack(&self) {
+            let is_empty = self
+                .obj()
+                .document()
+                .is_none_or(|document| document.bookmarks().n_items() == 0);
+            self.stack
+                .set_visible_child_name(if is_empty { "empty" } else { "bookmarks" });
+        }
+
+        fn bookmark_row(&self, bookmark: &Bookmark) -> adw::ActionRow {
+            let row = adw::ActionRow::builder().activatable(true).build();
+            bookmark
+                .bind_property("name", &row, "title")
+                .transform_to(|_, name: String| Some(glib::markup_escape_text(&name)))
+                .sync_create()
+                .build();
+            row.connect_activated(clone!(
+                #[weak(rename_to = this)]
+                self,
+                #[weak]
+                bookmark,
+                move |row| {
+                    this.obj().popdown();
+                    if let Err(error) = row.activate_action(
+                        "window.go-to-position",
+                        Some(&bookmark.position().to_variant()),
+                    ) {
+                        error!("Failed to go to bookmark: {error}");
+                    }
+                }
+            ));
+
+            let remove_button = gtk::Button::builder()
+                .icon_name("user-trash-symbolic")
+                .tooltip_text(gettext("Remove Bookmark"))
+                .valign(gtk::Align::Center)
+                .css_classes(["flat"])
+                .build();
+            remove_button.connect_clicked(clone!(
+                #[weak(rename_to = this)]
+                self,
+                #[weak]
+                bookmark,
+                move |_| {
+                    let Some(document) = this.obj().document() else {
+                        return;
+                    };
+                    if let Err(error) = document.remove_bookmark(&bookmark) {
+                        error!("Failed to remove bookmark: {error}");
+                    }
+                }
+            ));
+            row.add_suffix(&remove_button);
+
+            row
+        }
+
+        /// Add a bookmark at the cursor of the document view.
+        fn add_bookmark(&self) {
+            let mut name = self.name_entry.text().trim().to_string();
+            if name.is_empty() {
+                name = gettext("Bookmark");
+            }
+            if let Err(error) = self
+                .obj()
+                .activate_action("window.add-bookmark", Some(&name.to_variant()))
+            {
+                error!("Failed to add bookmark: {error}");
+                return;
+            }
+            self.name_entry.set_text("");
+        }
+    }
+
+    impl WidgetImpl for BookmarksPopover {}
+    impl PopoverImpl for BookmarksPopover {}
+}
+
+glib::wrapper! {
+    /// Bookmarks of a document, to jump to them or add one at the cursor.
+    pub struct BookmarksPopover(ObjectSubclass<imp::BookmarksPopover>)
+        @extends gtk::Widget, gtk::Popover;
+}
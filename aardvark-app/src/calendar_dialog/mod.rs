@@ -0,0 +1,236 @@
+/* Copyright 2025 The Aardvark Developers
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ *
+ * SPDX-License-Identifier: GPL-3.0-or-later
+ */
+
+use std::cell::OnceCell;
+
+use adw::prelude::*;
+use adw::subclass::prelude::*;
+use gettextrs::gettext;
+use gtk::{glib, glib::clone, glib::closure_local};
+
+use aardvark_doc::{document::Document, documents::Documents};
+
+mod imp {
+    use super::*;
+    use glib::subclass::Signal;
+    use std::sync::LazyLock;
+
+    #[derive(Debug, Default, glib::Properties, gtk::CompositeTemplate)]
+    #[properties(wrapper_type = super::CalendarDialog)]
+    #[template(resource = "/org/p2panda/aardvark/calendar_dialog/calendar_dialog.ui")]
+    pub struct CalendarDialog {
+        #[template_child]
+        calendar: TemplateChild<gtk::Calendar>,
+        #[template_child]
+        stack: TemplateChild<gtk::Stack>,
+        #[template_child]
+        listbox: TemplateChild<gtk::ListBox>,
+        #[template_child]
+        document_list_page: TemplateChild<gtk::Widget>,
+        #[template_child]
+        no_documents_page: TemplateChild<gtk::Widget>,
+        #[property(get, construct_only)]
+        documents: OnceCell<Documents>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for CalendarDialog {
+        const NAME: &'static str = "AardvarkCalendarDialog";
+        type Type = super::CalendarDialog;
+        type ParentType = adw::Dialog;
+
+        fn class_init(klass: &mut Self::Class) {
+            klass.bind_template();
+        }
+
+        fn instance_init(obj: &glib::subclass::InitializingObject<Self>) {
+            obj.init_template();
+        }
+    }
+
+    #[glib::derived_properties]
+    impl ObjectImpl for CalendarDialog {
+        fn signals() -> &'static [Signal] {
+            static SIGNALS: LazyLock<Vec<Signal>> = LazyLock::new(|| {
+                vec![
+                    // The user has activated a document of the selected day.
+                    Signal::builder("document-activated")
+                        .param_types([Document::static_type()])
+                        .build(),
+                ]
+            });
+            SIGNALS.as_ref()
+        }
+
+        fn constructed(&self) {
+            self.parent_constructed();
+
+            self.calendar.connect_day_selected(clone!(
+                #[weak(rename_to = this)]
+                self,
+                move |_| {
+                    this.update_documents();
+                }
+            ));
+            for property in ["month", "year"] {
+                self.calendar.connect_notify_local(
+                    Some(property),
+                    clone!(
+                        #[weak(rename_to = this)]
+                        self,
+                        move |_, _| {
+                            this.update_marks();
+                        }
+                    ),
+                );
+            }
+
+            self.listbox.connect_row_activated(clone!(
+                #[weak(rename_to = this)]
+                self,
+                move |_, row| {
+                    let Some(document) = this
+                        .documents_on_selected_day()
+                        .into_iter()
+                        .nth(row.index() as usize)
+                    else {
+                        return;
+                    };
+                    this.obj()
+                        .emit_by_name::<()>("document-activated", &[&document]);
+                    this.obj().close();
+                }
+            ));
+
+            self.update_marks();
+            self.update_documents();
+        }
+    }
+
+    impl CalendarDialog {
+        /// Highlight the days of the shown month on which documents were created or edited.
+        fn update_marks(&self) {
+            let calendar = &self.calendar;
+            calendar.clear_marks();
+
+            let (year, month) = (calendar.year(), calendar.month() + 1);
+            for document in self.obj().documents().iter::<Document>().flatten() {
+                for date in activity_dates(&document) {
+                    if date.year() == year && date.month() == month {
+                        calendar.mark_day(date.day_of_month() as u32);
+                    }
+                }
+            }
+        }
+
+        fn documents_on_selected_day(&self) -> Vec<Document> {
+            let selected = self.calendar.date();
+            self.obj()
+                .documents()
+                .iter::<Document>()
+                .flatten()
+                .filter(|document| {
+                    activity_dates(document)
+                        .iter()
+                        .any(|date| is_same_day(date, &selected))
+                })
+                .collect()
+        }
+
+        fn update_documents(&self) {
+            self.listbox.remove_all();
+
+            let selected = self.calendar.date();
+            let documents = self.documents_on_selected_day();
+            for document in &documents {
+                let created = document
+                    .created_at()
+                    .and_then(|date| date.to_local().ok())
+                    .is_some_and(|date| is_same_day(&date, &selected));
+                let edited = document
+                    .last_edited()
+                    .and_then(|date| date.to_local().ok())
+                    .is_some_and(|date| is_same_day(&date, &selected));
+                let subtitle = match (created, edited) {
+                    (true, true) => gettext("Created and last edited"),
+                    (true, false) => gettext("Created"),
+                    _ => gettext("Last edited"),
+                };
+
+                let row = adw::ActionRow::builder()
+                    .title(glib::markup_escape_text(
+                        &document.name().unwrap_or_else(|| gettext("Empty document")),
+                    ))
+                    .subtitle(subtitle)
+                    .activatable(true)
+                    .build();
+                row.add_suffix(&gtk::Image::from_icon_name("go-next-symbolic"));
+                self.listbox.append(&row);
+            }
+
+            if documents.is_empty() {
+                self.stack.set_visible_child(&*self.no_documents_page);
+            } else {
+                self.stack.set_visible_child(&*self.document_list_page);
+            }
+        }
+    }
+
+    impl WidgetImpl for CalendarDialog {}
+    impl AdwDialogImpl for CalendarDialog {}
+}
+
+glib::wrapper! {
+    pub struct CalendarDialog(ObjectSubclass<imp::CalendarDialog>)
+        @extends gtk::Widget, adw::Dialog;
+}
+
+impl CalendarDialog {
+    pub fn new<P: IsA<Documents>>(documents: &P) -> Self {
+        glib::Object::builder()
+            .property("documents", documents)
+            .build()
+    }
+
+    /// Connect to the signal emitted when a user clicks a document of the selected day.
+    pub fn connect_document_activated<F: Fn(&Self, &Document) + 'static>(
+        &self,
+        f: F,
+    ) -> glib::SignalHandlerId {
+        self.connect_closure(
+            "document-activated",
+            true,
+            closure_local!(move |obj: Self, document: Document| {
+                f(&obj, &document);
+            }),
+        )
+    }
+}
+
+/// Local dates on which the document was created or last edited.
+fn activity_dates(document: &Document) -> Vec<glib::DateTime> {
+    [document.created_at(), document.last_edited()]
+        .into_iter()
+        .flatten()
+        .filter_map(|date| date.to_local().ok())
+        .collect()
+}
+
+fn is_same_day(a: &glib::DateTime, b: &glib::DateTime) -> bool {
+    a.year() == b.year() && a.day_of_year() == b.day_of_year()
+}
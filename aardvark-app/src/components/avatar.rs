@@ -1,5 +1,5 @@
 use adw::{prelude::*, subclass::prelude::*};
-use gtk::{glib, glib::GString};
+use gtk::{gdk, glib, glib::GString};
 
 mod imp {
     use super::*;
@@ -9,6 +9,9 @@ mod imp {
     pub struct Avatar {
         #[property(name = "emoji", get = Self::emoji, set = Self::set_emoji, type = GString)]
         label: gtk::Label,
+        /// Shown instead of the emoji when set.
+        #[property(name = "paintable", get = Self::paintable, set = Self::set_paintable, nullable, type = Option<gdk::Paintable>)]
+        image: gtk::Image,
     }
 
     #[glib::object_subclass]
@@ -37,6 +40,19 @@ mod imp {
         fn set_emoji(&self, emoji: &str) {
             self.label.set_label(emoji);
         }
+
+        fn paintable(&self) -> Option<gdk::Paintable> {
+            self.image.paintable()
+        }
+
+        fn set_paintable(&self, paintable: Option<gdk::Paintable>) {
+            self.image.set_paintable(paintable.as_ref());
+            if paintable.is_some() {
+                self.obj().set_child(Some(&self.image));
+            } else {
+                self.obj().set_child(Some(&self.label));
+            }
+        }
     }
 
     impl WidgetImpl for Avatar {}
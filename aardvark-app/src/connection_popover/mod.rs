@@ -18,18 +18,30 @@
  * SPDX-License-Identifier: GPL-3.0-or-later
  */
 
-use std::cell::RefCell;
+use std::cell::{OnceCell, RefCell};
+use std::time::Duration;
 
-use adw::prelude::ActionRowExt;
+use adw::prelude::*;
 use adw::subclass::prelude::*;
-use gettextrs::gettext;
-use gtk::glib;
+use gettextrs::{gettext, ngettext};
 use gtk::prelude::*;
+use gtk::{gdk, gio, glib, glib::clone};
+use tracing::error;
 
 use crate::AardvarkApplication;
 use crate::components::Avatar;
+use crate::relative_time;
 use crate::system_settings::ClockFormat;
-use aardvark_doc::{author::Author, author::COLORS, authors::Authors};
+use aardvark_doc::{
+    author::Author, author::COLORS, authors::Authors, debug::BandwidthInfo, document::Document,
+    service::Service, sorted_authors::SortedAuthors,
+};
+
+/// How often relative times like "Last seen 2 minutes ago" are updated while the popover is
+/// shown.
+const LAST_SEEN_REFRESH_INTERVAL: Duration = Duration::from_secs(30);
+/// How often the throughput is updated while the popover is shown.
+const BANDWIDTH_REFRESH_INTERVAL: Duration = Duration::from_secs(2);
 
 mod imp {
     use super::*;
@@ -38,8 +50,18 @@ mod imp {
     #[properties(wrapper_type = super::ConnectionPopover)]
     pub struct ConnectionPopover {
         author_list_box: gtk::ListBox,
+        /// Sync traffic of all peers together and its limits.
+        bandwidth_label: gtk::Label,
+        #[property(get, construct_only)]
+        service: OnceCell<Service>,
+        /// The document whose authors are listed, they are renamed within it.
+        #[property(get, construct_only)]
+        document: OnceCell<Document>,
         #[property(get, set = Self::set_model)]
         model: RefCell<Option<Authors>>,
+        sorted_model: RefCell<Option<SortedAuthors>>,
+        refresh_source: RefCell<Option<glib::SourceId>>,
+        bandwidth_source: RefCell<Option<glib::SourceId>>,
     }
 
     #[glib::object_subclass]
@@ -59,7 +81,17 @@ mod imp {
                 .propagate_natural_width(true)
                 .max_content_height(300)
                 .build();
-            self.obj().set_child(Some(&scrollview));
+            self.bandwidth_label.set_xalign(0.0);
+            self.bandwidth_label.set_wrap(true);
+            self.bandwidth_label.set_margin_top(6);
+            self.bandwidth_label.set_margin_start(12);
+            self.bandwidth_label.set_margin_end(12);
+            self.bandwidth_label.add_css_class("dim-label");
+            self.bandwidth_label.add_css_class("caption");
+            let content = gtk::Box::new(gtk::Orientation::Vertical, 0);
+            content.append(&scrollview);
+            content.append(&self.bandwidth_label);
+            self.obj().set_child(Some(&content));
             self.author_list_box
                 .set_selection_mode(gtk::SelectionMode::None);
             self.obj().add_css_class("connection-popover");
@@ -82,59 +114,202 @@ mod imp {
 
     impl ConnectionPopover {
         fn set_model(&self, model: Option<Authors>) {
-            self.author_list_box.bind_model(model.as_ref(), |author| {
-                let author = author.downcast_ref::<Author>().unwrap();
-                let row = adw::ActionRow::builder()
-                    .selectable(false)
-                    .activatable(false)
-                    .can_focus(false)
-                    .can_target(false)
-                    .build();
-                let avatar = Avatar::new();
-                row.add_prefix(&avatar);
-                if author.is_this_device() {
-                    let this_device_label = gtk::Label::builder()
-                        .label("This Device")
-                        .valign(gtk::Align::Start)
-                        .margin_top(6)
-                        .css_classes(["this-device-pill"])
-                        .build();
-                    row.add_suffix(&this_device_label);
-                }
-                author
-                    .bind_property("name", &row, "title")
-                    .sync_create()
-                    .build();
-                // FIXME: format last seen according to the mockups
-                //author.bind_property ("last-seen", row, "subtitle").sync_create().build();
-                author
-                    .bind_property("emoji", &avatar, "emoji")
-                    .sync_create()
-                    .build();
-                author
-                    .bind_property("is-online", &row, "subtitle")
+            let sorted = model.as_ref().map(SortedAuthors::new);
+            if let Some(sorted) = &sorted {
+                AardvarkApplication::default()
+                    .settings()
+                    .bind_property("author-order", sorted, "order")
                     .sync_create()
-                    .transform_to(|binding, is_online: bool| {
-                        let author: Author = binding.source().unwrap().downcast().unwrap();
-                        if is_online {
-                            Some("Online".to_string())
-                        } else if let Some(last_seen) = author.last_seen() {
-                            Some(format_last_seen(&last_seen))
-                        } else {
-                            Some("Never seen".to_string())
-                        }
-                    })
                     .build();
-                avatar.add_css_class(&format!("bg-{}", author.color()));
+            }
 
-                row.upcast()
-            });
+            let service = self.obj().service();
+            let document = self.obj().document();
+            self.author_list_box
+                .bind_model(sorted.as_ref(), move |author| {
+                    let author = author.downcast_ref::<Author>().unwrap();
+                    let row = adw::ActionRow::builder()
+                        .selectable(false)
+                        .activatable(false)
+                        .can_focus(false)
+                        .build();
+                    let avatar = Avatar::new();
+                    row.add_prefix(&avatar);
+                    if author.is_this_device() {
+                        let this_device_label = gtk::Label::builder()
+                            .label("This Device")
+                            .valign(gtk::Align::Start)
+                            .margin_top(6)
+                            .css_classes(["this-device-pill"])
+                            .build();
+                        row.add_suffix(&this_device_label);
+                    }
+                    author
+                        .bind_property("name", &row, "title")
+                        .sync_create()
+                        .build();
+                    author
+                        .bind_property("emoji", &avatar, "emoji")
+                        .sync_create()
+                        .build();
+                    author
+                        .bind_property("avatar", &avatar, "paintable")
+                        .sync_create()
+                        .build();
+                    author
+                        .bind_property("is-online", &row, "subtitle")
+                        .sync_create()
+                        .transform_to(|binding, is_online: bool| {
+                            let author: Author = binding.source().unwrap().downcast().unwrap();
+                            Some(format_status(&author, is_online))
+                        })
+                        .build();
+                    // Peers with an incompatible version are online but we can't edit together
+                    author
+                        .bind_property("incompatible-reason", &row, "subtitle")
+                        .transform_to(|binding, _reason: Option<String>| {
+                            let author: Author = binding.source().unwrap().downcast().unwrap();
+                            Some(format_status(&author, author.is_online()))
+                        })
+                        .build();
+                    author
+                        .bind_property("incompatible-reason", &row, "tooltip-text")
+                        .sync_create()
+                        .build();
+                    author
+                        .bind_property("changes-behind", &row, "subtitle")
+                        .transform_to(|binding, _changes_behind: u32| {
+                            let author: Author = binding.source().unwrap().downcast().unwrap();
+                            Some(format_status(&author, author.is_online()))
+                        })
+                        .build();
+                    author
+                        .bind_property("last-seen", &row, "subtitle")
+                        .transform_to(|binding, _last_seen: Option<glib::DateTime>| {
+                            let author: Author = binding.source().unwrap().downcast().unwrap();
+                            Some(format_status(&author, author.is_online()))
+                        })
+                        .build();
+                    author
+                        .bind_property("away", &row, "subtitle")
+                        .transform_to(|binding, _away: bool| {
+                            let author: Author = binding.source().unwrap().downcast().unwrap();
+                            Some(format_status(&author, author.is_online()))
+                        })
+                        .build();
+                    author
+                        .bind_property("blocked", &row, "subtitle")
+                        .transform_to(|binding, _blocked: bool| {
+                            let author: Author = binding.source().unwrap().downcast().unwrap();
+                            Some(format_status(&author, author.is_online()))
+                        })
+                        .build();
+                    let download_rate_label = gtk::Label::builder()
+                        .valign(gtk::Align::Center)
+                        .css_classes(["dim-label", "caption", "numeric"])
+                        .build();
+                    author
+                        .bind_property("download-rate", &download_rate_label, "label")
+                        .transform_to(|_, rate: u64| Some(format_rate(rate)))
+                        .sync_create()
+                        .build();
+                    author
+                        .bind_property("download-rate", &download_rate_label, "visible")
+                        .transform_to(|_, rate: u64| Some(rate > 0))
+                        .sync_create()
+                        .build();
+                    row.add_suffix(&download_rate_label);
+                    avatar.add_css_class(&format!("bg-{}", author.color()));
+                    if !author.is_this_device() {
+                        add_context_menu(&service, &document, &row, author);
+                        add_jump_to_last_edit(&row, author);
+                    }
 
+                    row.upcast()
+                });
+
+            self.sorted_model.replace(sorted);
             self.model.replace(model);
         }
+
+        /// Format the status of all authors again, so relative times stay correct.
+        fn refresh_status(&self) {
+            let Some(sorted) = self.sorted_model.borrow().clone() else {
+                return;
+            };
+
+            for (index, author) in sorted.iter::<Author>().flatten().enumerate() {
+                if let Some(row) = self
+                    .author_list_box
+                    .row_at_index(index as i32)
+                    .and_downcast::<adw::ActionRow>()
+                {
+                    row.set_subtitle(&format_status(&author, author.is_online()));
+                }
+            }
+        }
+
+        /// Query the current sync traffic, this updates the rates of the authors as well.
+        fn refresh_bandwidth(&self) {
+            glib::spawn_future_local(clone!(
+                #[weak(rename_to = this)]
+                self,
+                async move {
+                    let info = this.obj().service().bandwidth().await;
+                    this.bandwidth_label.set_label(&format_bandwidth(&info));
+                }
+            ));
+        }
     }
 
-    impl WidgetImpl for ConnectionPopover {}
+    impl WidgetImpl for ConnectionPopover {
+        fn map(&self) {
+            self.parent_map();
+
+            self.refresh_status();
+            let source = glib::timeout_add_local(
+                LAST_SEEN_REFRESH_INTERVAL,
+                clone!(
+                    #[weak(rename_to = this)]
+                    self,
+                    #[upgrade_or]
+                    glib::ControlFlow::Break,
+                    move || {
+                        this.refresh_status();
+                        glib::ControlFlow::Continue
+                    }
+                ),
+            );
+            self.refresh_source.replace(Some(source));
+
+            self.refresh_bandwidth();
+            let source = glib::timeout_add_local(
+                BANDWIDTH_REFRESH_INTERVAL,
+                clone!(
+                    #[weak(rename_to = this)]
+                    self,
+                    #[upgrade_or]
+                    glib::ControlFlow::Break,
+                    move || {
+                        this.refresh_bandwidth();
+                        glib::ControlFlow::Continue
+                    }
+                ),
+            );
+            self.bandwidth_source.replace(Some(source));
+        }
+
+        fn unmap(&self) {
+            if let Some(source) = self.refresh_source.take() {
+                source.remove();
+            }
+            if let Some(source) = self.bandwidth_source.take() {
+                source.remove();
+            }
+
+            self.parent_unmap();
+        }
+    }
     impl PopoverImpl for ConnectionPopover {}
 }
 
@@ -144,9 +319,243 @@ glib::wrapper! {
 }
 
 impl ConnectionPopover {
-    pub fn new<P: IsA<Authors>>(model: &P) -> Self {
-        glib::Object::builder().property("model", model).build()
+    pub fn new(service: &Service, document: &Document) -> Self {
+        glib::Object::builder()
+            .property("service", service)
+            .property("document", document)
+            .property("model", document.authors())
+            .build()
+    }
+}
+
+/// Scroll to where the author of `row` changed the document last when the row is activated.
+fn add_jump_to_last_edit(row: &adw::ActionRow, author: &Author) {
+    author
+        .bind_property("last-edit-position", row, "activatable")
+        .transform_to(|_, position: i32| Some(position >= 0))
+        .sync_create()
+        .build();
+    row.connect_activated(clone!(
+        #[weak]
+        author,
+        move |row| {
+            let position = author.last_edit_position();
+            if position < 0 {
+                return;
+            }
+
+            if let Some(popover) = row.ancestor(gtk::Popover::static_type()) {
+                popover.downcast::<gtk::Popover>().unwrap().popdown();
+            }
+            if let Err(error) =
+                row.activate_action("window.scroll-to-position", Some(&position.to_variant()))
+            {
+                error!("Failed to scroll to last edit of author: {error}");
+            }
+        }
+    ));
+}
+
+/// Offer to rename, block or unblock the author of `row`, on right click or long press.
+fn add_context_menu(service: &Service, document: &Document, row: &adw::ActionRow, author: &Author) {
+    let rename = gio::SimpleAction::new("rename", None);
+    rename.connect_activate(clone!(
+        #[weak]
+        document,
+        #[weak]
+        row,
+        #[weak]
+        author,
+        move |_, _| {
+            if let Some(popover) = row.ancestor(gtk::Popover::static_type()) {
+                popover.downcast::<gtk::Popover>().unwrap().popdown();
+            }
+            glib::spawn_future_local(clone!(
+                #[strong]
+                document,
+                #[strong]
+                row,
+                #[strong]
+                author,
+                async move {
+                    let Some(alias) = ask_alias(&row, &author).await else {
+                        return;
+                    };
+                    if let Err(error) = document
+                        .set_author_alias(&author.public_key(), alias.as_deref())
+                        .await
+                    {
+                        error!("Failed to rename author {}: {error}", author.public_key());
+                    }
+                }
+            ));
+        }
+    ));
+    let toggle_blocked = gio::SimpleAction::new("toggle-blocked", None);
+    toggle_blocked.connect_activate(clone!(
+        #[strong]
+        service,
+        #[weak]
+        author,
+        move |_, _| {
+            glib::spawn_future_local(clone!(
+                #[strong]
+                service,
+                async move {
+                    let blocked = !author.blocked();
+                    if let Err(error) = service
+                        .set_author_blocked(&author.public_key(), blocked)
+                        .await
+                    {
+                        error!("Failed to block author {}: {error}", author.public_key());
+                    }
+                }
+            ));
+        }
+    ));
+    let actions = gio::SimpleActionGroup::new();
+    actions.add_action(&rename);
+    actions.add_action(&toggle_blocked);
+    row.insert_action_group("author", Some(&actions));
+
+    let popover = gtk::PopoverMenu::builder()
+        .has_arrow(false)
+        .halign(gtk::Align::Start)
+        .build();
+    popover.set_parent(row);
+    row.connect_destroy(clone!(
+        #[weak]
+        popover,
+        move |_| popover.unparent()
+    ));
+
+    let show_menu = clone!(
+        #[weak]
+        popover,
+        #[weak]
+        author,
+        move |x: f64, y: f64| {
+            let menu = gio::Menu::new();
+            menu.append(Some(&gettext("_Rename…")), Some("author.rename"));
+            let label = if author.blocked() {
+                gettext("_Unblock Author")
+            } else {
+                gettext("_Block Author")
+            };
+            menu.append(Some(&label), Some("author.toggle-blocked"));
+            popover.set_menu_model(Some(&menu));
+            popover.set_pointing_to(Some(&gdk::Rectangle::new(x as i32, y as i32, 1, 1)));
+            popover.popup();
+        }
+    );
+
+    let click = gtk::GestureClick::builder()
+        .button(gdk::BUTTON_SECONDARY)
+        .build();
+    click.connect_pressed(clone!(
+        #[strong]
+        show_menu,
+        move |_, _, x, y| show_menu(x, y)
+    ));
+    row.add_controller(click);
+
+    let long_press = gtk::GestureLongPress::builder().touch_only(true).build();
+    long_press.connect_pressed(move |_, x, y| show_menu(x, y));
+    row.add_controller(long_press);
+}
+
+/// Ask for the name `author` should be shown with on this device.
+///
+/// Returns `None` when cancelled and `Some(None)` when the author should get their regular name
+/// back.
+async fn ask_alias(parent: &impl IsA<gtk::Widget>, author: &Author) -> Option<Option<String>> {
+    let entry = gtk::Entry::builder().activates_default(true).build();
+    match author.alias() {
+        Some(alias) => entry.set_text(&alias),
+        None => entry.set_placeholder_text(Some(&author.name())),
     }
+    let dialog = adw::AlertDialog::builder()
+        .heading(gettext("Rename Author"))
+        .body(gettext(
+            "The name is only shown on this device, other authors aren't told about it",
+        ))
+        .extra_child(&entry)
+        .close_response("cancel")
+        .default_response("rename")
+        .build();
+    dialog.add_response("cancel", &gettext("_Cancel"));
+    if author.alias().is_some() {
+        dialog.add_response("reset", &gettext("_Reset"));
+    }
+    dialog.add_response("rename", &gettext("_Rename"));
+    dialog.set_response_appearance("rename", adw::ResponseAppearance::Suggested);
+
+    match dialog.choose_future(parent).await.as_str() {
+        "rename" => Some(Some(entry.text().to_string())),
+        "reset" => Some(None),
+        _ => None,
+    }
+}
+
+fn format_status(author: &Author, is_online: bool) -> String {
+    if author.blocked() {
+        gettext("Blocked, their changes are ignored")
+    } else if author.incompatible_reason().is_some() {
+        gettext("Incompatible version, ask them to update Aardvark")
+    } else if is_online && author.away() {
+        gettext("Away")
+    } else if is_online && author.changes_behind() > 0 {
+        ngettext(
+            "Online, {} change behind",
+            "Online, {} changes behind",
+            author.changes_behind(),
+        )
+        .replace("{}", &author.changes_behind().to_string())
+    } else if is_online {
+        "Online".to_string()
+    } else if let Some(last_seen) = author.last_seen() {
+        format_relative_last_seen(&last_seen).unwrap_or_else(|| format_last_seen(&last_seen))
+    } else {
+        "Never seen".to_string()
+    }
+}
+
+/// A rate in bytes per second, e.g. "1.2 kB/s".
+fn format_rate(rate: u64) -> String {
+    // Translators: {} is an amount of data, e.g. "1.2 kB"
+    gettext("{}/s").replace("{}", &glib::format_size(rate))
+}
+
+/// Throughput of all sync sessions, with the limits if there are any.
+fn format_bandwidth(info: &BandwidthInfo) -> String {
+    let with_limit = |rate: u64, limit: Option<u64>| match limit.filter(|limit| *limit > 0) {
+        // Translators: The first {} is the current rate, the second the limit, e.g. "1.2 kB/s of 2.0 kB/s"
+        Some(limit) => gettext("{} of {}")
+            .replacen("{}", &format_rate(rate), 1)
+            .replacen("{}", &format_rate(limit), 1),
+        None => format_rate(rate),
+    };
+
+    // Translators: {} are rates, e.g. "Sync: ↑ 1.2 kB/s ↓ 3.4 kB/s"
+    gettext("Sync: ↑ {} ↓ {}")
+        .replacen("{}", &with_limit(info.upload_rate, info.limits.upload), 1)
+        .replacen(
+            "{}",
+            &with_limit(info.download_rate, info.limits.download),
+            1,
+        )
+}
+
+/// When the author was last seen relative to now, e.g. "Last seen 5 minutes ago", if that was
+/// less than a day ago.
+fn format_relative_last_seen(datetime: &glib::DateTime) -> Option<String> {
+    let now = glib::DateTime::now_local().ok()?;
+    if now.difference(datetime).as_hours() >= 24 {
+        return None;
+    }
+
+    // Translators: {} is a relative time, e.g. "5 minutes ago"
+    Some(gettext("Last seen {}").replace("{}", &relative_time::format(datetime, &now)))
 }
 
 // This was copied from Fractal
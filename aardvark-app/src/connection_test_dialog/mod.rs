@@ -0,0 +1,336 @@
+/* Copyright 2025 The Aardvark Developers
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ *
+ * SPDX-License-Identifier: GPL-3.0-or-later
+ */
+
+use std::cell::{OnceCell, RefCell};
+
+use adw::prelude::*;
+use adw::subclass::prelude::*;
+use gettextrs::{gettext, ngettext};
+use gtk::{glib, glib::clone};
+use tracing::error;
+
+use aardvark_doc::service::{Check, CheckResult, Finding, Service, Status};
+
+/// All checks of the self-test, in the order they run.
+const CHECKS: [Check; 4] = [
+    Check::LocalDiscovery,
+    Check::Relay,
+    Check::TestPeer,
+    Check::Clock,
+];
+
+mod imp {
+    use super::*;
+
+    #[derive(Debug, Default, glib::Properties, gtk::CompositeTemplate)]
+    #[properties(wrapper_type = super::ConnectionTestDialog)]
+    #[template(resource = "/org/p2panda/aardvark/connection_test_dialog/connection_test_dialog.ui")]
+    pub struct ConnectionTestDialog {
+        #[template_child]
+        toast_overlay: TemplateChild<adw::ToastOverlay>,
+        #[template_child]
+        checks_group: TemplateChild<adw::PreferencesGroup>,
+        #[template_child]
+        start_button: TemplateChild<gtk::Button>,
+        #[template_child]
+        copy_button: TemplateChild<gtk::Button>,
+        #[property(get, construct_only)]
+        service: OnceCell<Service>,
+        rows: RefCell<Vec<CheckRow>>,
+        results: RefCell<Vec<CheckResult>>,
+        task: RefCell<Option<glib::JoinHandle<()>>>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for ConnectionTestDialog {
+        const NAME: &'static str = "AardvarkConnectionTestDialog";
+        type Type = super::ConnectionTestDialog;
+        type ParentType = adw::Dialog;
+
+        fn class_init(klass: &mut Self::Class) {
+            klass.bind_template();
+        }
+
+        fn instance_init(obj: &glib::subclass::InitializingObject<Self>) {
+            obj.init_template();
+        }
+    }
+
+    #[glib::derived_properties]
+    impl ObjectImpl for ConnectionTestDialog {
+        fn constructed(&self) {
+            self.parent_constructed();
+
+            let rows: Vec<CheckRow> = CHECKS.into_iter().map(CheckRow::new).collect();
+            for row in &rows {
+                self.checks_group.add(&row.row);
+            }
+            self.rows.replace(rows);
+
+            self.start_button.connect_clicked(clone!(
+                #[weak(rename_to = this)]
+                self,
+                move |_| this.start()
+            ));
+            self.copy_button.connect_clicked(clone!(
+                #[weak(rename_to = this)]
+                self,
+                move |button| {
+                    button.clipboard().set_text(&this.report());
+                    this.toast_overlay
+                        .add_toast(adw::Toast::new(&gettext("Report copied to clipboard")));
+                }
+            ));
+        }
+
+        fn dispose(&self) {
+            if let Some(task) = self.task.take() {
+                task.abort();
+            }
+        }
+    }
+
+    impl ConnectionTestDialog {
+        fn start(&self) {
+            self.results.borrow_mut().clear();
+            for row in self.rows.borrow().iter() {
+                row.reset();
+            }
+            if let Some(row) = self.rows.borrow().first() {
+                row.set_running();
+            }
+            self.start_button.set_sensitive(false);
+            self.copy_button.set_visible(false);
+
+            let task = glib::spawn_future_local(clone!(
+                #[weak(rename_to = this)]
+                self,
+                async move {
+                    let service = this.obj().service();
+                    let result = service
+                        .self_test(clone!(
+                            #[weak]
+                            this,
+                            move |result| this.show_result(result)
+                        ))
+                        .await;
+                    if let Err(error) = result {
+                        error!("Connection self-test failed: {error}");
+                        this.toast_overlay
+                            .add_toast(adw::Toast::new(&gettext("Failed to run the test")));
+                        for row in this.rows.borrow().iter() {
+                            row.stop();
+                        }
+                    }
+
+                    this.start_button.set_label(&gettext("Test _Again"));
+                    this.start_button.set_sensitive(true);
+                    this.copy_button
+                        .set_visible(!this.results.borrow().is_empty());
+                    this.task.take();
+                }
+            ));
+            self.task.replace(Some(task));
+        }
+
+        fn show_result(&self, result: CheckResult) {
+            let rows = self.rows.borrow();
+            let Some(index) = rows.iter().position(|row| row.check == result.check) else {
+                return;
+            };
+            rows[index].set_finding(&result.finding);
+            if let Some(next) = rows.get(index + 1) {
+                next.set_running();
+            }
+            self.results.borrow_mut().push(result);
+        }
+
+        /// Plain text summary of the results, e.g. to share when asking for help.
+        fn report(&self) -> String {
+            self.results
+                .borrow()
+                .iter()
+                .map(|result| {
+                    let mut line = format!(
+                        "{}: {} – {}",
+                        check_title(result.check),
+                        status_label(result.finding.status()),
+                        finding_description(&result.finding)
+                    );
+                    if let Some(suggestion) = finding_suggestion(&result.finding) {
+                        line.push(' ');
+                        line.push_str(&suggestion);
+                    }
+                    line
+                })
+                .collect::<Vec<_>>()
+                .join("\n")
+        }
+    }
+
+    impl WidgetImpl for ConnectionTestDialog {}
+    impl AdwDialogImpl for ConnectionTestDialog {}
+}
+
+glib::wrapper! {
+    pub struct ConnectionTestDialog(ObjectSubclass<imp::ConnectionTestDialog>)
+        @extends gtk::Widget, adw::Dialog;
+}
+
+impl ConnectionTestDialog {
+    pub fn new(service: &Service) -> Self {
+        glib::Object::builder().property("service", service).build()
+    }
+}
+
+/// Row showing the state of one check.
+#[derive(Debug)]
+struct CheckRow {
+    check: Check,
+    row: adw::ActionRow,
+    icon: gtk::Image,
+    spinner: adw::Spinner,
+}
+
+impl CheckRow {
+    fn new(check: Check) -> Self {
+        let row = adw::ActionRow::builder()
+            .title(check_title(check))
+            .subtitle_selectable(true)
+            .build();
+        let icon = gtk::Image::new();
+        let spinner = adw::Spinner::new();
+        row.add_prefix(&icon);
+        row.add_prefix(&spinner);
+
+        let check_row = Self {
+            check,
+            row,
+            icon,
+            spinner,
+        };
+        check_row.reset();
+        check_row
+    }
+
+    fn reset(&self) {
+        self.row.set_subtitle("");
+        self.icon.set_icon_name(Some("content-loading-symbolic"));
+        self.icon.set_css_classes(&["dim-label"]);
+        self.stop();
+    }
+
+    fn set_running(&self) {
+        self.icon.set_visible(false);
+        self.spinner.set_visible(true);
+    }
+
+    fn stop(&self) {
+        self.icon.set_visible(true);
+        self.spinner.set_visible(false);
+    }
+
+    fn set_finding(&self, finding: &Finding) {
+        let (icon_name, css_class) = match finding.status() {
+            Status::Passed => ("object-select-symbolic", "success"),
+            Status::Warning => ("dialog-warning-symbolic", "warning"),
+            Status::Failed => ("dialog-error-symbolic", "error"),
+            Status::Skipped => ("action-unavailable-symbolic", "dim-label"),
+        };
+        self.icon.set_icon_name(Some(icon_name));
+        self.icon.set_css_classes(&[css_class]);
+        self.icon
+            .set_tooltip_text(Some(&status_label(finding.status())));
+
+        let mut subtitle = glib::markup_escape_text(&finding_description(finding)).to_string();
+        if let Some(suggestion) = finding_suggestion(finding) {
+            subtitle.push('\n');
+            subtitle.push_str(&glib::markup_escape_text(&suggestion));
+        }
+        self.row.set_subtitle(&subtitle);
+        self.stop();
+    }
+}
+
+fn check_title(check: Check) -> String {
+    match check {
+        Check::LocalDiscovery => gettext("Devices on This Network"),
+        Check::Relay => gettext("Relay Server"),
+        Check::TestPeer => gettext("Connection to Test Device"),
+        Check::Clock => gettext("Clock"),
+    }
+}
+
+fn status_label(status: Status) -> String {
+    match status {
+        Status::Passed => gettext("Passed"),
+        Status::Warning => gettext("Warning"),
+        Status::Failed => gettext("Failed"),
+        Status::Skipped => gettext("Skipped"),
+    }
+}
+
+fn finding_description(finding: &Finding) -> String {
+    match finding {
+        Finding::PeersNearby(count) => {
+            ngettext("Found {} device", "Found {} devices", *count as u32)
+                .replace("{}", &count.to_string())
+        }
+        Finding::NoPeersNearby => gettext("No other devices found"),
+        Finding::NoRelayConfigured => {
+            gettext("No relay server is set up, only devices on the same network can connect")
+        }
+        Finding::TestPeerReached { round_trip } => {
+            gettext("Answered in {} ms").replace("{}", &round_trip.as_millis().to_string())
+        }
+        Finding::NoTestPeer => gettext("No test device answered"),
+        Finding::ClockInSync => gettext("Matches the test device"),
+        Finding::ClockInPast => gettext("The date of this device is wrong"),
+        Finding::ClockOffset(offset) if *offset > 0 => ngettext(
+            "{} second ahead of the test device",
+            "{} seconds ahead of the test device",
+            offset.unsigned_abs() as u32,
+        )
+        .replace("{}", &offset.unsigned_abs().to_string()),
+        Finding::ClockOffset(offset) => ngettext(
+            "{} second behind the test device",
+            "{} seconds behind the test device",
+            offset.unsigned_abs() as u32,
+        )
+        .replace("{}", &offset.unsigned_abs().to_string()),
+        Finding::ClockNotCompared => {
+            gettext("Looks right, there was no test device to compare with")
+        }
+    }
+}
+
+/// What the user can do about a problem a check found.
+fn finding_suggestion(finding: &Finding) -> Option<String> {
+    match finding {
+        Finding::NoPeersNearby => Some(gettext(
+            "Make sure the other devices are connected to the same network. Guest and public networks often don’t let devices see each other.",
+        )),
+        Finding::NoTestPeer => Some(gettext(
+            "Start the test on another device at the same time. If it doesn’t answer, a firewall might block Aardvark on one of the devices.",
+        )),
+        Finding::ClockInPast | Finding::ClockOffset(_) => Some(gettext(
+            "Turn on automatic date and time in the system settings of both devices.",
+        )),
+        _ => None,
+    }
+}
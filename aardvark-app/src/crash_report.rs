@@ -0,0 +1,150 @@
+/* crash_report.rs
+ *
+ * Copyright 2025 The Aardvark Developers
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ *
+ * SPDX-License-Identifier: GPL-3.0-or-later
+ */
+
+//! Reports of crashes, offered to be sent to us on the next start.
+//!
+//! Panics and fatal GLib errors write a report to [`reports_dir`] before the process goes down.
+//! A report has the version, a backtrace and the recent log records, never the text of a
+//! document. They stay on disk until the user was asked about them once.
+
+use std::backtrace::Backtrace;
+use std::fmt::Write as _;
+use std::fs;
+use std::io::Write as _;
+use std::panic;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+use gtk::glib;
+use tracing::warn;
+
+use crate::config;
+use crate::logging;
+
+/// Where to report problems, the reports are pasted into a new issue.
+pub const ISSUE_URL: &str = "https://github.com/p2panda/aardvark/issues";
+
+/// Directory the crash reports are written to, shared by all identities.
+fn reports_dir() -> PathBuf {
+    let mut path = glib::user_data_dir();
+    path.push("Aardvark");
+    path.push("crash-reports");
+    path
+}
+
+/// Write a crash report when the application panics or GLib logs a fatal error.
+///
+/// The previous panic hook still runs afterwards, so the panic is printed as usual.
+pub fn install() {
+    let default_hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        let location = info
+            .location()
+            .map(|location| format!(" at {location}"))
+            .unwrap_or_default();
+        let message = info
+            .payload()
+            .downcast_ref::<&str>()
+            .map(|message| message.to_string())
+            .or_else(|| info.payload().downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "Box<dyn Any>".to_string());
+        let thread = std::thread::current();
+        write_report(&format!(
+            "Panic in thread '{}'{location}: {message}",
+            thread.name().unwrap_or("<unnamed>")
+        ));
+
+        default_hook(info);
+    }));
+
+    // GLib aborts after logging errors, e.g. when an allocation failed
+    glib::log_set_writer_func(|level, fields| {
+        if level == glib::LogLevel::Error {
+            let domain = field(fields, "GLIB_DOMAIN").unwrap_or_default();
+            let message = field(fields, "MESSAGE").unwrap_or_default();
+            write_report(&format!("GLib error in {domain}: {message}"));
+        }
+        glib::log_writer_default(level, fields)
+    });
+}
+
+fn field(fields: &[glib::LogField<'_>], key: &str) -> Option<String> {
+    fields
+        .iter()
+        .find(|field| field.key() == key)
+        .and_then(|field| field.value_str())
+        .map(String::from)
+}
+
+fn write_report(reason: &str) {
+    let timestamp = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+
+    let mut report = String::new();
+    let _ = writeln!(report, "Aardvark {} ({})", config::VERSION, config::APP_ID);
+    let crashed_at = glib::DateTime::now_utc()
+        .and_then(|now| now.format_iso8601())
+        .map(String::from)
+        .unwrap_or_else(|_| timestamp.to_string());
+    let _ = writeln!(report, "Crashed at {crashed_at}");
+    let _ = writeln!(report, "{reason}");
+    let _ = writeln!(report, "\nBacktrace:\n{}", Backtrace::force_capture());
+    // The log might be locked by the code which crashed
+    let records =
+        logging::try_recent_records().unwrap_or_else(|| "Log records unavailable\n".to_string());
+    let _ = write!(report, "\nRecent log records:\n{records}");
+
+    // Nothing to do about errors while crashing
+    let dir = reports_dir();
+    let _ = fs::create_dir_all(&dir);
+    let path = dir.join(format!("crash-{timestamp}-{}.txt", std::process::id()));
+    if let Ok(mut file) = fs::File::create(path) {
+        let _ = file.write_all(report.as_bytes());
+    }
+}
+
+/// Reports written since the user was asked about them last, oldest first.
+///
+/// The reports are removed from disk, so the user is only asked once.
+pub fn take_reports() -> Vec<String> {
+    let Ok(entries) = fs::read_dir(reports_dir()) else {
+        return Vec::new();
+    };
+
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "txt"))
+        .collect();
+    paths.sort();
+
+    let mut reports = Vec::new();
+    for path in paths {
+        if let Ok(report) = fs::read_to_string(&path) {
+            reports.push(report);
+        }
+        if let Err(error) = fs::remove_file(&path) {
+            warn!("Failed to remove crash report {}: {error}", path.display());
+        }
+    }
+    reports
+}
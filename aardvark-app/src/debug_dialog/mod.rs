@@ -0,0 +1,324 @@
+/* Copyright 2025 The Aardvark Developers
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ *
+ * SPDX-License-Identifier: GPL-3.0-or-later
+ */
+
+use std::cell::OnceCell;
+use std::fmt::Write;
+
+use adw::prelude::*;
+use adw::subclass::prelude::*;
+use gtk::prelude::*;
+use gtk::{glib, glib::clone};
+use tracing::warn;
+
+use aardvark_doc::author::Author;
+use aardvark_doc::debug::{IngestStats, SyncDirection, SyncSessionInfo};
+use aardvark_doc::document::Document;
+
+/// Number of operations shown in the operation log tail.
+const OPERATIONS_LIMIT: usize = 100;
+
+mod imp {
+    use super::*;
+
+    #[derive(Debug, Default, glib::Properties, gtk::CompositeTemplate)]
+    #[properties(wrapper_type = super::DebugDialog)]
+    #[template(resource = "/org/p2panda/aardvark/debug_dialog/debug_dialog.ui")]
+    pub struct DebugDialog {
+        #[template_child]
+        refresh_button: TemplateChild<gtk::Button>,
+        #[template_child]
+        crdt_view: TemplateChild<gtk::TextView>,
+        #[template_child]
+        operations_view: TemplateChild<gtk::TextView>,
+        #[template_child]
+        peers_view: TemplateChild<gtk::TextView>,
+        #[template_child]
+        rejected_expander: TemplateChild<gtk::Expander>,
+        #[template_child]
+        rejected_label: TemplateChild<gtk::Label>,
+        #[template_child]
+        sessions_list: TemplateChild<gtk::ListBox>,
+        #[template_child]
+        store_view: TemplateChild<gtk::TextView>,
+        #[property(get, construct_only)]
+        document: OnceCell<Document>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for DebugDialog {
+        const NAME: &'static str = "AardvarkDebugDialog";
+        type Type = super::DebugDialog;
+        type ParentType = adw::Dialog;
+
+        fn class_init(klass: &mut Self::Class) {
+            klass.bind_template();
+        }
+
+        fn instance_init(obj: &glib::subclass::InitializingObject<Self>) {
+            obj.init_template();
+        }
+    }
+
+    #[glib::derived_properties]
+    impl ObjectImpl for DebugDialog {
+        fn constructed(&self) {
+            self.parent_constructed();
+
+            self.refresh_button.connect_clicked(clone!(
+                #[weak(rename_to = this)]
+                self,
+                move |_| {
+                    this.refresh();
+                }
+            ));
+
+            self.refresh();
+        }
+    }
+
+    impl DebugDialog {
+        fn refresh(&self) {
+            let document = self.obj().document();
+            self.crdt_view.buffer().set_text(&document.crdt_state());
+
+            glib::spawn_future_local(clone!(
+                #[weak(rename_to = this)]
+                self,
+                async move {
+                    let document = this.obj().document();
+
+                    let operations = match document.operations(OPERATIONS_LIMIT).await {
+                        Ok(operations) => {
+                            operations
+                                .iter()
+                                .fold(String::new(), |mut output, operation| {
+                                    let _ = writeln!(
+                                        output,
+                                        "{} {} {:?} #{} {} B{} {}",
+                                        operation.timestamp,
+                                        operation.public_key,
+                                        operation.log_type,
+                                        operation.seq_num,
+                                        operation.payload_size,
+                                        if operation.prune_flag { " (prune)" } else { "" },
+                                        operation.hash,
+                                    );
+                                    output
+                                })
+                        }
+                        Err(error) => format!("Failed to load operations: {error}"),
+                    };
+                    this.operations_view.buffer().set_text(&operations);
+
+                    let peers = match document.topic_info().await {
+                        Ok(topic_info) => {
+                            let mut output = format!("Topic: {}\n\nNeighbors:\n", document.id());
+                            for peer in &topic_info.neighbors {
+                                let _ = writeln!(output, "  {peer}");
+                            }
+                            output.push_str("\nAuthors:\n");
+                            for (public_key, last_seen) in &topic_info.authors {
+                                let _ = writeln!(
+                                    output,
+                                    "  {public_key} last seen: {}",
+                                    last_seen
+                                        .map(|last_seen| last_seen.to_rfc3339())
+                                        .unwrap_or_else(|| "never".to_string())
+                                );
+                            }
+
+                            let incompatible = document
+                                .authors()
+                                .iter::<Author>()
+                                .filter_map(Result::ok)
+                                .filter_map(|author| {
+                                    author.incompatible_reason().map(|reason| (author, reason))
+                                })
+                                .collect::<Vec<_>>();
+                            if !incompatible.is_empty() {
+                                output.push_str(
+                                    "\nIncompatible version (they need to update Aardvark):\n",
+                                );
+                                for (author, reason) in &incompatible {
+                                    let _ = writeln!(output, "  {} {reason}", author.public_key());
+                                }
+                            }
+
+                            let gossip = &topic_info.gossip;
+                            let ingest = &topic_info.ingest;
+                            this.show_rejected(ingest);
+                            let tuning = document.service().gossip_tuning().await;
+                            let _ = write!(
+                                output,
+                                "\nGossip ({:?}):\n  sent: {} messages, {}\n  received: {} messages, {}\n  dropped: {} messages\n  max message size: {}\n  resync interval: {}s\n  message TTL: {}s\n\nNot ingested:\n  rate limited: {} operations\n  invalid payload: {} operations\n  unknown author: {} operations\n  blocked author: {} operations\n  rejected: {} operations\n",
+                                document.service().gossip_preset(),
+                                gossip.messages_sent,
+                                glib::format_size(gossip.bytes_sent),
+                                gossip.messages_received,
+                                glib::format_size(gossip.bytes_received),
+                                gossip.messages_dropped,
+                                glib::format_size(tuning.max_message_size as u64),
+                                tuning.resync_interval.as_secs(),
+                                tuning.message_ttl.as_secs(),
+                                ingest.rate_limited,
+                                ingest.invalid_payload,
+                                ingest.unknown_author,
+                                ingest.blocked,
+                                ingest.rejected,
+                            );
+                            output
+                        }
+                        Err(error) => format!("Failed to load peers: {error}"),
+                    };
+                    this.peers_view.buffer().set_text(&peers);
+
+                    this.sessions_list.remove_all();
+                    for session in document.service().sync_sessions().await {
+                        this.sessions_list.append(&this.session_row(&session));
+                    }
+
+                    let store = match document.service().store_stats().await {
+                        Ok(stats) => format!(
+                            "Documents: {}\nAuthors: {}\nOperations: {}\nDatabase size: {}",
+                            stats.documents,
+                            stats.authors,
+                            stats.operations,
+                            glib::format_size(stats.database_size),
+                        ),
+                        Err(error) => format!("Failed to load store statistics: {error}"),
+                    };
+                    this.store_view.buffer().set_text(&store);
+                }
+            ));
+        }
+
+        /// Warn about operations with an invalid signature or which failed validation.
+        fn show_rejected(&self, ingest: &IngestStats) {
+            self.rejected_expander.set_visible(ingest.rejected > 0);
+            self.rejected_expander.set_label(Some(&format!(
+                "{} operations rejected, peers may run an incompatible version",
+                ingest.rejected
+            )));
+
+            let mut rejections =
+                ingest
+                    .rejections
+                    .iter()
+                    .fold(String::new(), |mut output, rejection| {
+                        let _ = writeln!(
+                            output,
+                            "{} ({}x): {}",
+                            rejection.public_key, rejection.count, rejection.reason
+                        );
+                        output
+                    });
+            let listed: u64 = ingest
+                .rejections
+                .iter()
+                .map(|rejection| rejection.count)
+                .sum();
+            if listed < ingest.rejected {
+                let _ = writeln!(rejections, "… and {} more", ingest.rejected - listed);
+            }
+            self.rejected_label.set_label(rejections.trim_end());
+        }
+
+        fn session_row(&self, session: &SyncSessionInfo) -> adw::ActionRow {
+            let title = match (session.direction, session.document) {
+                (SyncDirection::Initiated, Some(document_id)) => format!("Outgoing {document_id}"),
+                _ => "Incoming".to_string(),
+            };
+            let row = adw::ActionRow::builder()
+                .title(title)
+                .subtitle(format!(
+                    "Started {}, sent {}, received {}{}",
+                    session.started.to_rfc3339(),
+                    glib::format_size(session.bytes_sent),
+                    glib::format_size(session.bytes_received),
+                    if session.paused { " (paused)" } else { "" },
+                ))
+                .build();
+
+            let pause_button = gtk::Button::builder()
+                .icon_name(if session.paused {
+                    "media-playback-start-symbolic"
+                } else {
+                    "media-playback-pause-symbolic"
+                })
+                .tooltip_text(if session.paused { "Resume" } else { "Pause" })
+                .valign(gtk::Align::Center)
+                .css_classes(["flat"])
+                .build();
+            let id = session.id;
+            let paused = session.paused;
+            pause_button.connect_clicked(clone!(
+                #[weak(rename_to = this)]
+                self,
+                move |_| {
+                    glib::spawn_future_local(async move {
+                        let service = this.obj().document().service();
+                        if let Err(error) = service.set_sync_session_paused(id, !paused).await {
+                            warn!("Failed to pause sync session {id}: {error}");
+                        }
+                        this.refresh();
+                    });
+                }
+            ));
+            row.add_suffix(&pause_button);
+
+            let cancel_button = gtk::Button::builder()
+                .icon_name("process-stop-symbolic")
+                .tooltip_text("Cancel")
+                .valign(gtk::Align::Center)
+                .css_classes(["flat"])
+                .build();
+            cancel_button.connect_clicked(clone!(
+                #[weak(rename_to = this)]
+                self,
+                move |_| {
+                    glib::spawn_future_local(async move {
+                        let service = this.obj().document().service();
+                        if let Err(error) = service.cancel_sync_session(id).await {
+                            warn!("Failed to cancel sync session {id}: {error}");
+                        }
+                        this.refresh();
+                    });
+                }
+            ));
+            row.add_suffix(&cancel_button);
+
+            row
+        }
+    }
+
+    impl WidgetImpl for DebugDialog {}
+    impl AdwDialogImpl for DebugDialog {}
+}
+
+glib::wrapper! {
+    pub struct DebugDialog(ObjectSubclass<imp::DebugDialog>)
+        @extends gtk::Widget, adw::Dialog;
+}
+
+impl DebugDialog {
+    pub fn new(document: &Document) -> Self {
+        glib::Object::builder()
+            .property("document", document)
+            .build()
+    }
+}
@@ -0,0 +1,743 @@
+/* Copyright 2025 The Aardvark Developers
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ *
+ * SPDX-License-Identifier: GPL-3.0-or-later
+ */
+
+use std::cell::{Cell, OnceCell, RefCell};
+
+use aardvark_doc::document::{Document, normalize_text};
+use aardvark_doc::polls::parse_poll_marker;
+use adw::prelude::*;
+use adw::subclass::prelude::*;
+use gettextrs::gettext;
+use gtk::{gdk, gio, glib, glib::clone};
+use tracing::warn;
+
+use crate::application::document_id_from_file;
+use crate::emoji_completion::EmojiCompletionProvider;
+use crate::poll_widget::PollWidget;
+use crate::profiler_overlay::ProfilerOverlay;
+use crate::{AardvarkApplication, AardvarkTextBuffer};
+
+/// Pastes larger than this (in bytes) are inserted step by step while showing their progress.
+const LARGE_PASTE_THRESHOLD: usize = 1024 * 1024;
+/// How much of a large paste is inserted per main loop iteration.
+const LARGE_PASTE_STEP: usize = 64 * 1024;
+/// Name of the tag dimming all text except the current paragraph in focus mode.
+const DIMMED_TAG: &str = "focus-mode-dimmed";
+/// Name of the tag hiding the YAML frontmatter at the top of the document.
+const FRONTMATTER_TAG: &str = "hidden-frontmatter";
+/// Name of the tag highlighting text edited concurrently by us and another author.
+const CONFLICT_TAG: &str = "conflict";
+/// How long text edited concurrently stays highlighted.
+const CONFLICT_HIGHLIGHT_DURATION: std::time::Duration = std::time::Duration::from_secs(4);
+/// Name of the mark used to scroll to a position, see `DocumentView::scroll_to_position()`.
+const SCROLL_MARK: &str = "scroll-position";
+
+mod imp {
+    use super::*;
+
+    #[derive(Debug, Default, glib::Properties, gtk::CompositeTemplate)]
+    #[properties(wrapper_type = super::DocumentView)]
+    #[template(resource = "/org/p2panda/aardvark/document_view/document_view.ui")]
+    pub struct DocumentView {
+        #[template_child]
+        pub text_view: TemplateChild<sourceview::View>,
+        #[template_child]
+        pub overlay: TemplateChild<gtk::Overlay>,
+        #[template_child]
+        pub paste_progress: TemplateChild<gtk::ProgressBar>,
+        /// Shown while the document is downloaded from peers after joining it.
+        #[template_child]
+        pub sync_progress: TemplateChild<gtk::ProgressBar>,
+        #[template_child]
+        pub clamp: TemplateChild<adw::ClampScrollable>,
+        #[property(get, construct_only)]
+        document: OnceCell<Document>,
+        /// Limit the width of the text column and dim everything but the current paragraph.
+        #[property(get, set = Self::set_focus_mode, explicit_notify)]
+        focus_mode: Cell<bool>,
+        /// Width of the text column in focus mode, in pixels.
+        #[property(get, set = Self::set_measure_width, explicit_notify, default = 680)]
+        measure_width: Cell<i32>,
+        dimmed: Cell<bool>,
+        /// Don't show the YAML frontmatter, it's still part of the text and exported.
+        #[property(get, set = Self::set_hide_frontmatter, explicit_notify)]
+        hide_frontmatter: Cell<bool>,
+        /// Show the latency of local edits and remote patches, for working on the editing
+        /// pipeline.
+        #[property(get, set = Self::set_show_profiler, explicit_notify)]
+        show_profiler: Cell<bool>,
+        profiler_overlay: OnceCell<ProfilerOverlay>,
+        conflict_highlight: RefCell<Option<glib::SourceId>>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for DocumentView {
+        const NAME: &'static str = "AardvarkDocumentView";
+        type Type = super::DocumentView;
+        type ParentType = adw::Bin;
+
+        fn class_init(klass: &mut Self::Class) {
+            klass.bind_template();
+        }
+
+        fn instance_init(obj: &glib::subclass::InitializingObject<Self>) {
+            obj.init_template();
+        }
+    }
+
+    #[glib::derived_properties]
+    impl ObjectImpl for DocumentView {
+        fn constructed(&self) {
+            self.parent_constructed();
+
+            let buffer = AardvarkTextBuffer::new();
+            buffer.set_document(&self.obj().document());
+            self.text_view.set_buffer(Some(&buffer));
+            // Emojis can be picked with Ctrl+. or completed while typing `:shortcode:`
+            self.text_view
+                .completion()
+                .add_provider(&EmojiCompletionProvider::new());
+
+            self.setup_poll_gutter();
+
+            self.measure_width.set(680);
+            self.update_clamp();
+            buffer.create_tag(Some(DIMMED_TAG), &[]);
+            buffer.connect_cursor_position_notify(clone!(
+                #[weak(rename_to = this)]
+                self,
+                move |_| this.update_dimming()
+            ));
+            buffer.connect_changed(clone!(
+                #[weak(rename_to = this)]
+                self,
+                move |_| this.update_dimming()
+            ));
+            buffer.create_tag(Some(FRONTMATTER_TAG), &[("invisible", &true)]);
+            buffer.create_tag(
+                Some(CONFLICT_TAG),
+                &[("background-rgba", &gdk::RGBA::new(0.965, 0.827, 0.176, 0.4))],
+            );
+            self.obj().document().connect_conflict_detected(clone!(
+                #[weak(rename_to = this)]
+                self,
+                move |_, start, end, _| this.highlight_conflict(start, end)
+            ));
+            self.obj().document().connect_frontmatter_notify(clone!(
+                #[weak(rename_to = this)]
+                self,
+                move |_| this.update_frontmatter()
+            ));
+            self.obj().document().connect_sync_progress_notify(clone!(
+                #[weak(rename_to = this)]
+                self,
+                move |_| this.update_sync_progress()
+            ));
+            self.update_sync_progress();
+            AardvarkApplication::default()
+                .settings()
+                .bind_property("hide-frontmatter", &*self.obj(), "hide-frontmatter")
+                .sync_create()
+                .build();
+            // Show the column the authors of the document agreed to wrap lines at
+            let document = self.obj().document();
+            document
+                .bind_property("wrap-column", &*self.text_view, "show-right-margin")
+                .transform_to(|_, column: u32| Some(column > 0))
+                .sync_create()
+                .build();
+            document
+                .bind_property("wrap-column", &*self.text_view, "right-margin-position")
+                .transform_to(|_, column: u32| (column > 0).then_some(column))
+                .sync_create()
+                .build();
+
+            let profiler_overlay = ProfilerOverlay::new(&document);
+            profiler_overlay.set_visible(false);
+            self.overlay.add_overlay(&profiler_overlay);
+            self.profiler_overlay.set(profiler_overlay).unwrap();
+
+            // Links are opened with Ctrl+Click, a plain click places the cursor as usual or
+            // toggles the checkbox of a task
+            let click = gtk::GestureClick::new();
+            click.connect_released(clone!(
+                #[weak(rename_to = this)]
+                self,
+                move |gesture, n_press, x, y| {
+                    if !gesture
+                        .current_event_state()
+                        .contains(gdk::ModifierType::CONTROL_MASK)
+                    {
+                        if n_press == 1 {
+                            this.toggle_task_at(x, y);
+                        }
+                        return;
+                    }
+                    if let Some(link) = this.link_at(x, y) {
+                        this.open_link(&link);
+                    }
+                }
+            ));
+            self.text_view.add_controller(click);
+
+            let motion = gtk::EventControllerMotion::new();
+            motion.connect_motion(clone!(
+                #[weak(rename_to = this)]
+                self,
+                move |controller, x, y| {
+                    let over_link = controller
+                        .current_event_state()
+                        .contains(gdk::ModifierType::CONTROL_MASK)
+                        && this.link_at(x, y).is_some();
+                    this.text_view.set_cursor_from_name(Some(if over_link {
+                        "pointer"
+                    } else {
+                        "text"
+                    }));
+                }
+            ));
+            self.text_view.add_controller(motion);
+
+            // Always paste as plain text, we handle reading the clipboard ourselves to be able to
+            // clean up the text and to not block the UI for very large pastes.
+            self.text_view.connect_paste_clipboard(clone!(
+                #[weak(rename_to = this)]
+                self,
+                move |text_view| {
+                    text_view.stop_signal_emission_by_name("paste-clipboard");
+                    glib::spawn_future_local(clone!(
+                        #[weak]
+                        this,
+                        async move {
+                            this.paste_clipboard().await;
+                        }
+                    ));
+                }
+            ));
+        }
+    }
+
+    impl DocumentView {
+        fn set_focus_mode(&self, focus_mode: bool) {
+            if self.focus_mode.replace(focus_mode) == focus_mode {
+                return;
+            }
+
+            self.update_clamp();
+            self.update_dimming();
+            self.obj().notify_focus_mode();
+        }
+
+        fn set_measure_width(&self, measure_width: i32) {
+            if self.measure_width.replace(measure_width) == measure_width {
+                return;
+            }
+
+            self.update_clamp();
+            self.obj().notify_measure_width();
+        }
+
+        fn set_show_profiler(&self, show_profiler: bool) {
+            if self.show_profiler.replace(show_profiler) == show_profiler {
+                return;
+            }
+
+            self.profiler_overlay
+                .get()
+                .unwrap()
+                .set_visible(show_profiler);
+            self.obj().notify_show_profiler();
+        }
+
+        fn set_hide_frontmatter(&self, hide_frontmatter: bool) {
+            if self.hide_frontmatter.replace(hide_frontmatter) == hide_frontmatter {
+                return;
+            }
+
+            self.update_frontmatter();
+            self.obj().notify_hide_frontmatter();
+        }
+
+        fn update_frontmatter(&self) {
+            let buffer = self.text_view.buffer();
+            let Some(tag) = buffer.tag_table().lookup(FRONTMATTER_TAG) else {
+                return;
+            };
+
+            buffer.remove_tag(&tag, &buffer.start_iter(), &buffer.end_iter());
+            if !self.hide_frontmatter.get() {
+                return;
+            }
+            if let Some(frontmatter) = self.obj().document().frontmatter() {
+                let end = buffer.iter_at_offset(frontmatter.block_len() as i32);
+                buffer.apply_tag(&tag, &buffer.start_iter(), &end);
+            }
+        }
+
+        fn update_sync_progress(&self) {
+            let Some(progress) = self.obj().document().sync_progress() else {
+                self.sync_progress.set_visible(false);
+                return;
+            };
+            self.sync_progress
+                .set_visible(!progress.is_done() && progress.operations_received() > 0);
+
+            let size = glib::format_size(progress.bytes_received());
+            let tooltip = match progress.operations_expected() {
+                Some(expected) => gettext("Downloaded {received} of {expected} changes ({size})")
+                    .replace("{expected}", &expected.to_string()),
+                None => gettext("Downloaded {received} changes ({size})"),
+            };
+            self.sync_progress.set_tooltip_text(Some(
+                &tooltip
+                    .replace("{received}", &progress.operations_received().to_string())
+                    .replace("{size}", &size),
+            ));
+            // We don't know how much there is most of the time
+            match progress.fraction() {
+                Some(fraction) => self.sync_progress.set_fraction(fraction),
+                None => self.sync_progress.pulse(),
+            }
+        }
+
+        fn update_clamp(&self) {
+            let width = if self.focus_mode.get() {
+                self.measure_width.get()
+            } else {
+                i32::MAX
+            };
+            self.clamp.set_maximum_size(width);
+            self.clamp.set_tightening_threshold(width);
+        }
+
+        /// Briefly highlight text which was edited concurrently by us and another author.
+        fn highlight_conflict(&self, start: i32, end: i32) {
+            let buffer = self.text_view.buffer();
+            let Some(tag) = buffer.tag_table().lookup(CONFLICT_TAG) else {
+                return;
+            };
+
+            // Deletions leave nothing to highlight, mark the character next to them
+            let end = if start == end { end + 1 } else { end };
+            buffer.apply_tag(
+                &tag,
+                &buffer.iter_at_offset(start),
+                &buffer.iter_at_offset(end),
+            );
+
+            if let Some(source) = self.conflict_highlight.take() {
+                source.remove();
+            }
+            let source = glib::timeout_add_local_once(
+                CONFLICT_HIGHLIGHT_DURATION,
+                clone!(
+                    #[weak(rename_to = this)]
+                    self,
+                    #[weak]
+                    buffer,
+                    #[weak]
+                    tag,
+                    move || {
+                        this.conflict_highlight.take();
+                        buffer.remove_tag(&tag, &buffer.start_iter(), &buffer.end_iter());
+                    }
+                ),
+            );
+            self.conflict_highlight.replace(Some(source));
+        }
+
+        /// Dim all text except the paragraph containing the cursor while in focus mode.
+        fn update_dimming(&self) {
+            let buffer = self.text_view.buffer();
+            let Some(tag) = buffer.tag_table().lookup(DIMMED_TAG) else {
+                return;
+            };
+
+            if !self.focus_mode.get() {
+                if self.dimmed.replace(false) {
+                    buffer.remove_tag(&tag, &buffer.start_iter(), &buffer.end_iter());
+                }
+                return;
+            }
+
+            buffer.remove_tag(&tag, &buffer.start_iter(), &buffer.end_iter());
+            self.dimmed.set(true);
+
+            let color = self.text_view.color();
+            tag.set_foreground_rgba(Some(&color.with_alpha(color.alpha() * 0.35)));
+
+            let (start, end) = current_paragraph(&buffer);
+            buffer.apply_tag(&tag, &buffer.start_iter(), &start);
+            buffer.apply_tag(&tag, &end, &buffer.end_iter());
+        }
+
+        /// Show an icon next to lines referencing a poll, clicking it shows the poll.
+        fn setup_poll_gutter(&self) {
+            let renderer = sourceview::GutterRendererText::new();
+            renderer.set_tooltip_text(Some(&gettext("Show Poll")));
+            renderer.connect_query_data(clone!(
+                #[weak(rename_to = this)]
+                self,
+                move |renderer, _, line| {
+                    let is_poll = this.poll_id_at_line(line as i32).is_some();
+                    renderer.set_text(if is_poll { "🗳" } else { "" }, -1);
+                }
+            ));
+            renderer.connect_query_activatable(clone!(
+                #[weak(rename_to = this)]
+                self,
+                #[upgrade_or]
+                false,
+                move |_, iter, _| this.poll_id_at_line(iter.line()).is_some()
+            ));
+            renderer.connect_activate(clone!(
+                #[weak(rename_to = this)]
+                self,
+                move |renderer, iter, area, _, _, _| {
+                    let Some(poll_id) = this.poll_id_at_line(iter.line()) else {
+                        return;
+                    };
+                    let poll_widget = PollWidget::new(&this.obj().document(), &poll_id);
+                    poll_widget.set_width_request(300);
+                    let popover = gtk::Popover::builder()
+                        .child(&poll_widget)
+                        .pointing_to(area)
+                        .position(gtk::PositionType::Right)
+                        .build();
+                    popover.set_parent(renderer);
+                    popover.connect_closed(|popover| popover.unparent());
+                    popover.popup();
+                }
+            ));
+
+            self.text_view
+                .gutter(gtk::TextWindowType::Left)
+                .insert(&renderer, 0);
+        }
+
+        /// Id of the poll referenced by the given line of the text.
+        fn poll_id_at_line(&self, line: i32) -> Option<String> {
+            let buffer = self.text_view.buffer();
+            let start = buffer.iter_at_line(line)?;
+            let mut end = start;
+            if !end.ends_line() {
+                end.forward_to_line_end();
+            }
+            parse_poll_marker(&buffer.text(&start, &end, false)).map(ToOwned::to_owned)
+        }
+
+        /// Ask for question and options of a new poll and insert it above the cursor.
+        pub(super) async fn insert_poll(&self) {
+            let question_entry = gtk::Entry::builder()
+                .placeholder_text(gettext("Question"))
+                .build();
+            let options_view = gtk::TextView::builder()
+                .wrap_mode(gtk::WrapMode::WordChar)
+                .height_request(96)
+                .top_margin(6)
+                .bottom_margin(6)
+                .left_margin(6)
+                .right_margin(6)
+                .build();
+            options_view.add_css_class("card");
+            let options_label = gtk::Label::builder()
+                .label(gettext("Options, one per line"))
+                .xalign(0.0)
+                .build();
+            options_label.add_css_class("dim-label");
+            let content = gtk::Box::new(gtk::Orientation::Vertical, 6);
+            content.append(&question_entry);
+            content.append(&options_label);
+            content.append(&options_view);
+
+            let dialog = adw::AlertDialog::builder()
+                .heading(gettext("New Poll"))
+                .extra_child(&content)
+                .default_response("create")
+                .close_response("cancel")
+                .build();
+            dialog.add_responses(&[
+                ("cancel", &gettext("_Cancel")),
+                ("create", &gettext("_Create")),
+            ]);
+            dialog.set_response_appearance("create", adw::ResponseAppearance::Suggested);
+
+            if dialog.choose_future(&*self.obj()).await != "create" {
+                return;
+            }
+
+            let question = question_entry.text().trim().to_owned();
+            let buffer = options_view.buffer();
+            let options: Vec<String> = buffer
+                .text(&buffer.start_iter(), &buffer.end_iter(), false)
+                .lines()
+                .map(str::trim)
+                .filter(|option| !option.is_empty())
+                .map(ToOwned::to_owned)
+                .collect();
+            if question.is_empty() || options.is_empty() {
+                return;
+            }
+
+            let buffer = self.text_view.buffer();
+            let cursor = buffer.iter_at_mark(&buffer.get_insert());
+            let line_start = buffer
+                .iter_at_line(cursor.line())
+                .unwrap_or_else(|| buffer.start_iter());
+            if let Err(error) =
+                self.obj()
+                    .document()
+                    .create_poll(line_start.offset(), &question, &options)
+            {
+                warn!("Failed to create poll: {error}");
+            }
+        }
+
+        /// The link at the given widget coordinates of the text view.
+        fn link_at(&self, x: f64, y: f64) -> Option<String> {
+            let (x, y) = self.text_view.window_to_buffer_coords(
+                gtk::TextWindowType::Widget,
+                x as i32,
+                y as i32,
+            );
+            let iter = self.text_view.iter_at_location(x, y)?;
+            self.text_view
+                .buffer()
+                .downcast::<AardvarkTextBuffer>()
+                .ok()?
+                .link_at_iter(&iter)
+        }
+
+        /// Check or uncheck the task whose checkbox, e.g. `[ ]`, is at `x`, `y`.
+        fn toggle_task_at(&self, x: f64, y: f64) {
+            let buffer = self.text_view.buffer();
+            if buffer.has_selection() || !self.text_view.is_editable() {
+                return;
+            }
+            let (x, y) = self.text_view.window_to_buffer_coords(
+                gtk::TextWindowType::Widget,
+                x as i32,
+                y as i32,
+            );
+            let Some(iter) = self.text_view.iter_at_location(x, y) else {
+                return;
+            };
+
+            let offset = iter.offset() as usize;
+            let document = self.obj().document();
+            let Some(task) = document
+                .tasks()
+                .into_iter()
+                .find(|task| (task.marker_offset - 1..=task.marker_offset + 1).contains(&offset))
+            else {
+                return;
+            };
+            if let Err(error) = document.set_task_checked(&task, !task.checked) {
+                warn!("Failed to toggle task: {error}");
+            }
+        }
+
+        /// Open web links in the browser and links to other documents in a new window.
+        fn open_link(&self, link: &str) {
+            let file = gio::File::for_uri(link);
+            if let Some(document_id) = document_id_from_file(&file) {
+                AardvarkApplication::default().open_document_in_new_window(&document_id);
+                return;
+            }
+
+            let window = self.obj().root().and_downcast::<gtk::Window>();
+            gtk::UriLauncher::new(link).launch(window.as_ref(), gio::Cancellable::NONE, |result| {
+                if let Err(error) = result {
+                    warn!("Failed to open link: {error}");
+                }
+            });
+        }
+
+        async fn paste_clipboard(&self) {
+            let clipboard = self.text_view.clipboard();
+            let bytes = match read_clipboard(&clipboard).await {
+                Ok(bytes) => bytes,
+                Err(error) => {
+                    warn!("Failed to read clipboard: {error}");
+                    return;
+                }
+            };
+            let text = normalize_text(&bytes);
+            if text.is_empty() {
+                return;
+            }
+
+            let buffer = self.text_view.buffer();
+            let editable = self.text_view.is_editable();
+            buffer.begin_user_action();
+            buffer.delete_selection(true, editable);
+
+            if text.len() <= LARGE_PASTE_THRESHOLD {
+                buffer.insert_interactive_at_cursor(&text, editable);
+                buffer.end_user_action();
+                self.text_view.scroll_mark_onscreen(&buffer.get_insert());
+                return;
+            }
+
+            // Don't let the user edit the document while we are still inserting the paste
+            self.text_view.set_editable(false);
+            self.paste_progress.set_fraction(0.0);
+            self.paste_progress.set_visible(true);
+
+            let mark = buffer.create_mark(None, &buffer.iter_at_mark(&buffer.get_insert()), false);
+            let mut inserted = 0;
+            while inserted < text.len() {
+                let mut end = (inserted + LARGE_PASTE_STEP).min(text.len());
+                while !text.is_char_boundary(end) {
+                    end -= 1;
+                }
+
+                buffer.insert(&mut buffer.iter_at_mark(&mark), &text[inserted..end]);
+                inserted = end;
+
+                self.paste_progress
+                    .set_fraction(inserted as f64 / text.len() as f64);
+                // Give GTK the chance to draw the progress
+                glib::timeout_future(std::time::Duration::ZERO).await;
+            }
+
+            buffer.place_cursor(&buffer.iter_at_mark(&mark));
+            buffer.delete_mark(&mark);
+            buffer.end_user_action();
+
+            self.paste_progress.set_visible(false);
+            self.text_view.set_editable(editable);
+            self.text_view.scroll_mark_onscreen(&buffer.get_insert());
+        }
+    }
+
+    impl WidgetImpl for DocumentView {
+        fn grab_focus(&self) -> bool {
+            self.text_view.grab_focus()
+        }
+    }
+
+    impl BinImpl for DocumentView {}
+}
+
+glib::wrapper! {
+    /// Editor for a single document, shown as a tab in `AardvarkWindow`.
+    pub struct DocumentView(ObjectSubclass<imp::DocumentView>)
+        @extends gtk::Widget, adw::Bin;
+}
+
+/// Start and end of the paragraph containing the cursor, paragraphs are separated by empty lines.
+fn current_paragraph(buffer: &gtk::TextBuffer) -> (gtk::TextIter, gtk::TextIter) {
+    let cursor = buffer.iter_at_mark(&buffer.get_insert());
+    let is_blank = |line: i32| {
+        let Some(start) = buffer.iter_at_line(line) else {
+            return true;
+        };
+        let mut end = start;
+        end.forward_to_line_end();
+        start.ends_line() || buffer.text(&start, &end, false).trim().is_empty()
+    };
+
+    let mut first = cursor.line();
+    while first > 0 && !is_blank(first - 1) {
+        first -= 1;
+    }
+    let mut last = cursor.line();
+    while last + 1 < buffer.line_count() && !is_blank(last + 1) {
+        last += 1;
+    }
+
+    let start = buffer
+        .iter_at_line(first)
+        .unwrap_or_else(|| buffer.start_iter());
+    let mut end = buffer
+        .iter_at_line(last)
+        .unwrap_or_else(|| buffer.end_iter());
+    if !end.ends_line() {
+        end.forward_to_line_end();
+    }
+    (start, end)
+}
+
+async fn read_clipboard(clipboard: &gtk::gdk::Clipboard) -> Result<glib::Bytes, glib::Error> {
+    let (stream, _mime_type) = clipboard
+        .read_future(
+            &["text/plain;charset=utf-8", "text/plain"],
+            glib::Priority::DEFAULT,
+        )
+        .await?;
+
+    let output = gio::MemoryOutputStream::new_resizable();
+    output
+        .splice_future(
+            &stream,
+            gio::OutputStreamSpliceFlags::CLOSE_SOURCE | gio::OutputStreamSpliceFlags::CLOSE_TARGET,
+            glib::Priority::DEFAULT,
+        )
+        .await?;
+
+    Ok(output.steal_as_bytes())
+}
+
+impl DocumentView {
+    pub fn new(document: &Document) -> Self {
+        glib::Object::builder()
+            .property("document", document)
+            .build()
+    }
+
+    /// Ask for question and options of a new poll and insert it above the cursor.
+    pub async fn insert_poll(&self) {
+        self.imp().insert_poll().await;
+    }
+
+    pub fn buffer(&self) -> AardvarkTextBuffer {
+        self.imp()
+            .text_view
+            .buffer()
+            .downcast()
+            .expect("Text view to have an AardvarkTextBuffer")
+    }
+
+    /// Position of the cursor, in characters.
+    pub fn cursor_position(&self) -> i32 {
+        self.imp().text_view.buffer().cursor_position()
+    }
+
+    /// Move the cursor to the character at `position` and scroll to it.
+    pub fn go_to_position(&self, position: i32) {
+        let text_view = &self.imp().text_view;
+        let buffer = text_view.buffer();
+        buffer.place_cursor(&buffer.iter_at_offset(position));
+        text_view.scroll_mark_onscreen(&buffer.get_insert());
+        text_view.grab_focus();
+    }
+
+    /// Scroll to the character at `position`, without moving the cursor.
+    pub fn scroll_to_position(&self, position: i32) {
+        let text_view = &self.imp().text_view;
+        let buffer = text_view.buffer();
+        let iter = buffer.iter_at_offset(position);
+        // Scrolling to a mark waits until the lines before it were measured
+        let mark = buffer
+            .mark(SCROLL_MARK)
+            .unwrap_or_else(|| buffer.create_mark(Some(SCROLL_MARK), &iter, true));
+        buffer.move_mark(&mark, &iter);
+        text_view.scroll_to_mark(&mark, 0.0, true, 0.0, 0.5);
+    }
+}
@@ -0,0 +1,202 @@
+/* Copyright 2025 The Aardvark Developers
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ *
+ * SPDX-License-Identifier: GPL-3.0-or-later
+ */
+
+//! Completion of `:shortcode:` emojis, e.g. `:tada` becomes 🎉.
+
+use std::cell::OnceCell;
+use std::future::Future;
+use std::pin::Pin;
+
+use gettextrs::gettext;
+use gtk::prelude::*;
+use gtk::subclass::prelude::*;
+use gtk::{gio, glib};
+use sourceview::prelude::*;
+use sourceview::subclass::prelude::*;
+
+/// Shortcodes need to be at least this long before we propose emojis.
+const MIN_SHORTCODE_LENGTH: usize = 2;
+/// Showing more proposals isn't useful, the user should keep typing instead.
+const MAX_PROPOSALS: usize = 50;
+
+mod imp {
+    use super::*;
+
+    #[derive(Debug, Default)]
+    pub struct EmojiProposal {
+        pub(super) emoji: OnceCell<&'static emojis::Emoji>,
+        pub(super) shortcode: OnceCell<&'static str>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for EmojiProposal {
+        const NAME: &'static str = "AardvarkEmojiProposal";
+        type Type = super::EmojiProposal;
+        type Interfaces = (sourceview::CompletionProposal,);
+    }
+
+    impl ObjectImpl for EmojiProposal {}
+    impl CompletionProposalImpl for EmojiProposal {}
+
+    #[derive(Debug, Default)]
+    pub struct EmojiCompletionProvider {}
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for EmojiCompletionProvider {
+        const NAME: &'static str = "AardvarkEmojiCompletionProvider";
+        type Type = super::EmojiCompletionProvider;
+        type Interfaces = (sourceview::CompletionProvider,);
+    }
+
+    impl ObjectImpl for EmojiCompletionProvider {}
+
+    impl CompletionProviderImpl for EmojiCompletionProvider {
+        fn title(&self) -> Option<glib::GString> {
+            Some(gettext("Emoji").into())
+        }
+
+        fn is_trigger(&self, _iter: &gtk::TextIter, c: char) -> bool {
+            c == ':'
+        }
+
+        fn populate_future(
+            &self,
+            context: &sourceview::CompletionContext,
+        ) -> Pin<Box<dyn Future<Output = Result<gio::ListModel, glib::Error>> + 'static>> {
+            let proposals = proposals(context);
+            Box::pin(async move { Ok(proposals.upcast()) })
+        }
+
+        fn refilter(&self, context: &sourceview::CompletionContext, _model: &gio::ListModel) {
+            context.set_proposals_for_provider(&*self.obj(), Some(&proposals(context)));
+        }
+
+        fn display(
+            &self,
+            _context: &sourceview::CompletionContext,
+            proposal: &sourceview::CompletionProposal,
+            cell: &sourceview::CompletionCell,
+        ) {
+            let Some(proposal) = proposal.downcast_ref::<super::EmojiProposal>() else {
+                return;
+            };
+
+            match cell.column() {
+                sourceview::CompletionColumn::Icon => {
+                    cell.set_text(Some(proposal.emoji().as_str()))
+                }
+                sourceview::CompletionColumn::TypedText => {
+                    cell.set_text(Some(&format!(":{}:", proposal.shortcode())))
+                }
+                sourceview::CompletionColumn::Comment => {
+                    cell.set_text(Some(proposal.emoji().name()))
+                }
+                _ => cell.set_text(None),
+            }
+        }
+
+        fn activate(
+            &self,
+            context: &sourceview::CompletionContext,
+            proposal: &sourceview::CompletionProposal,
+        ) {
+            let Some(proposal) = proposal.downcast_ref::<super::EmojiProposal>() else {
+                return;
+            };
+            let Some((mut start, mut end)) = shortcode_bounds(context) else {
+                return;
+            };
+
+            // Replace the shortcode through the regular buffer path, the buffer forwards the
+            // changes to the document
+            let buffer = start.buffer();
+            buffer.begin_user_action();
+            buffer.delete(&mut start, &mut end);
+            buffer.insert(&mut start, proposal.emoji().as_str());
+            buffer.end_user_action();
+        }
+    }
+}
+
+glib::wrapper! {
+    pub struct EmojiProposal(ObjectSubclass<imp::EmojiProposal>)
+        @implements sourceview::CompletionProposal;
+}
+
+impl EmojiProposal {
+    fn new(emoji: &'static emojis::Emoji, shortcode: &'static str) -> Self {
+        let obj: Self = glib::Object::new();
+        obj.imp().emoji.set(emoji).unwrap();
+        obj.imp().shortcode.set(shortcode).unwrap();
+        obj
+    }
+
+    fn emoji(&self) -> &'static emojis::Emoji {
+        self.imp().emoji.get().unwrap()
+    }
+
+    fn shortcode(&self) -> &'static str {
+        self.imp().shortcode.get().unwrap()
+    }
+}
+
+glib::wrapper! {
+    pub struct EmojiCompletionProvider(ObjectSubclass<imp::EmojiCompletionProvider>)
+        @implements sourceview::CompletionProvider;
+}
+
+impl EmojiCompletionProvider {
+    pub fn new() -> Self {
+        glib::Object::new()
+    }
+}
+
+/// Range of the shortcode being typed including the leading colon, e.g. `:tad`.
+fn shortcode_bounds(
+    context: &sourceview::CompletionContext,
+) -> Option<(gtk::TextIter, gtk::TextIter)> {
+    let (mut start, end) = context.bounds()?;
+    let mut colon = start;
+    if !colon.backward_char() || colon.char() != ':' {
+        return None;
+    }
+    start.backward_char();
+
+    Some((start, end))
+}
+
+fn proposals(context: &sourceview::CompletionContext) -> gio::ListStore {
+    let proposals = gio::ListStore::new::<EmojiProposal>();
+
+    if shortcode_bounds(context).is_none() {
+        return proposals;
+    }
+    let word = context.word().to_lowercase();
+    if word.chars().count() < MIN_SHORTCODE_LENGTH {
+        return proposals;
+    }
+
+    let matches = emojis::iter()
+        .flat_map(|emoji| emoji.shortcodes().map(move |shortcode| (emoji, shortcode)))
+        .filter(|(_, shortcode)| shortcode.starts_with(&word))
+        .take(MAX_PROPOSALS)
+        .map(|(emoji, shortcode)| EmojiProposal::new(emoji, shortcode));
+    proposals.extend(matches);
+
+    proposals
+}
@@ -0,0 +1,174 @@
+/* file_transfer.rs
+ *
+ * Copyright 2025 The Aardvark Developers
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ *
+ * SPDX-License-Identifier: GPL-3.0-or-later
+ */
+
+//! Reading and writing files the user chose, for export, import and backups.
+//!
+//! Files are chosen with `gtk::FileDialog`, which goes through the FileChooser portal inside
+//! Flatpak. The sandbox only grants access to the chosen file itself, e.g. via the document
+//! portal, so it's always accessed through the returned `gio::File` and never through paths
+//! derived from it.
+//!
+//! Contents are streamed in chunks of [`CHUNK_SIZE`], which keeps the main loop responsive
+//! while large backups are transferred. Writes go to a temporary file first, if one fails the
+//! destination keeps its previous contents.
+
+use gtk::prelude::*;
+use gtk::{gio, glib};
+
+/// Bytes read or written at once.
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// A file dialog for choosing the file to export to or to import from.
+pub struct FileTransfer {
+    dialog: gtk::FileDialog,
+}
+
+impl FileTransfer {
+    pub fn new(title: &str) -> Self {
+        Self {
+            dialog: gtk::FileDialog::builder().title(title).modal(true).build(),
+        }
+    }
+
+    pub fn accept_label(self, label: &str) -> Self {
+        self.dialog.set_accept_label(Some(label));
+        self
+    }
+
+    pub fn initial_name(self, name: &str) -> Self {
+        self.dialog.set_initial_name(Some(name));
+        self
+    }
+
+    pub fn initial_file(self, file: Option<&gio::File>) -> Self {
+        self.dialog.set_initial_file(file);
+        self
+    }
+
+    pub fn initial_folder(self, folder: Option<&gio::File>) -> Self {
+        self.dialog.set_initial_folder(folder);
+        self
+    }
+
+    pub fn filters(self, filters: &gio::ListStore) -> Self {
+        self.dialog.set_filters(Some(filters));
+        self
+    }
+
+    /// Ask for a file to export to, `None` if the user dismissed the dialog.
+    pub async fn save(
+        &self,
+        parent: Option<&impl IsA<gtk::Window>>,
+    ) -> Result<Option<gio::File>, glib::Error> {
+        dismissed_as_none(self.dialog.save_future(parent).await)
+    }
+
+    /// Ask for a file to import, `None` if the user dismissed the dialog.
+    pub async fn open(
+        &self,
+        parent: Option<&impl IsA<gtk::Window>>,
+    ) -> Result<Option<gio::File>, glib::Error> {
+        dismissed_as_none(self.dialog.open_future(parent).await)
+    }
+
+    /// Ask for a folder, `None` if the user dismissed the dialog.
+    pub async fn select_folder(
+        &self,
+        parent: Option<&impl IsA<gtk::Window>>,
+    ) -> Result<Option<gio::File>, glib::Error> {
+        dismissed_as_none(self.dialog.select_folder_future(parent).await)
+    }
+}
+
+/// Dismissing the dialog isn't an error, but e.g. a missing portal is.
+fn dismissed_as_none(
+    result: Result<gio::File, glib::Error>,
+) -> Result<Option<gio::File>, glib::Error> {
+    match result {
+        Ok(file) => Ok(Some(file)),
+        Err(error)
+            if error.matches(gtk::DialogError::Dismissed)
+                || error.matches(gtk::DialogError::Cancelled) =>
+        {
+            Ok(None)
+        }
+        Err(error) => Err(error),
+    }
+}
+
+/// Read all of `file`.
+pub async fn read(file: &gio::File) -> Result<Vec<u8>, glib::Error> {
+    let stream = file.read_future(glib::Priority::DEFAULT).await?;
+    let mut contents = Vec::new();
+    loop {
+        let chunk = stream
+            .read_bytes_future(CHUNK_SIZE, glib::Priority::DEFAULT)
+            .await?;
+        if chunk.is_empty() {
+            break;
+        }
+        contents.extend_from_slice(&chunk);
+    }
+    stream.close_future(glib::Priority::DEFAULT).await?;
+
+    Ok(contents)
+}
+
+/// Replace the contents of `file`, only readable by the user if `private` is set.
+///
+/// The file isn't changed if writing fails.
+pub async fn write(
+    file: &gio::File,
+    contents: impl Into<glib::Bytes>,
+    private: bool,
+) -> Result<(), glib::Error> {
+    let mut flags = gio::FileCreateFlags::REPLACE_DESTINATION;
+    if private {
+        flags |= gio::FileCreateFlags::PRIVATE;
+    }
+    let stream = file
+        .replace_future(None, false, flags, glib::Priority::DEFAULT)
+        .await?;
+
+    let contents = contents.into();
+    let mut start = 0;
+    while start < contents.len() {
+        let end = (start + CHUNK_SIZE).min(contents.len());
+        let chunk = glib::Bytes::from_bytes(&contents, start..end);
+        let result = match stream
+            .write_all_future(chunk, glib::Priority::DEFAULT)
+            .await
+        {
+            Ok((_, _, Some(error))) | Err((_, error)) => Err(error),
+            Ok(_) => Ok(()),
+        };
+        if let Err(error) = result {
+            // Closing a cancelled stream drops the temporary file instead of replacing `file`
+            let cancellable = gio::Cancellable::new();
+            cancellable.cancel();
+            let _ = stream.close(Some(&cancellable));
+            return Err(error);
+        }
+        start = end;
+    }
+    stream.close_future(glib::Priority::DEFAULT).await?;
+
+    Ok(())
+}
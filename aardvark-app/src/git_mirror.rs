@@ -0,0 +1,260 @@
+/* git_mirror.rs
+ *
+ * Copyright 2025 The Aardvark Developers
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ *
+ * SPDX-License-Identifier: GPL-3.0-or-later
+ */
+
+//! Mirroring documents to local git repositories.
+//!
+//! Every stored snapshot and every named checkpoint of a mirrored document is committed to the
+//! repository, so teams keep an auditable trail of e.g. their meeting notes with the tools they
+//! already know. The repository of each document and the version committed last are remembered
+//! in `git-mirrors.ini` inside the data directory.
+//!
+//! Authors are mapped to git identities in the `[authors]` group of the same file, with their
+//! public key as key and `Name <email>` as value. Authors without an entry are committed with
+//! their name in Aardvark and their public key as email address.
+
+use std::path::{Path, PathBuf};
+
+use aardvark_doc::author::Author;
+use aardvark_doc::checkpoints::Checkpoint;
+use aardvark_doc::document::{Document, DocumentId};
+use aardvark_doc::history;
+use gtk::glib;
+use gtk::prelude::*;
+use thiserror::Error;
+
+const GIT_MIRRORS_FILE: &str = "git-mirrors.ini";
+const REPOSITORY_KEY: &str = "repository";
+/// Version of the document committed last, base64 encoded.
+const VERSION_KEY: &str = "version";
+/// Id of the checkpoint committed last.
+const CHECKPOINT_KEY: &str = "checkpoint";
+const AUTHORS_GROUP: &str = "authors";
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("Git error: {0}")]
+    Git(#[from] git2::Error),
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("File error: {0}")]
+    File(#[from] glib::Error),
+    #[error("Failed to restore checkpoint: {0}")]
+    Checkpoint(String),
+}
+
+pub struct GitMirrors {
+    path: PathBuf,
+    key_file: glib::KeyFile,
+}
+
+impl GitMirrors {
+    pub fn load(data_dir: &Path) -> Self {
+        let key_file = glib::KeyFile::new();
+        let path = data_dir.join(GIT_MIRRORS_FILE);
+        // A missing or broken file means that no document is mirrored yet
+        let _ = key_file.load_from_file(&path, glib::KeyFileFlags::NONE);
+
+        Self { path, key_file }
+    }
+
+    /// The repository `document_id` is mirrored to.
+    pub fn get(&self, document_id: &DocumentId) -> Option<PathBuf> {
+        self.key_file
+            .string(&document_id.to_string(), REPOSITORY_KEY)
+            .ok()
+            .map(|path| PathBuf::from(path.as_str()))
+    }
+
+    /// Mirror `document_id` to `repository` from now on, or stop mirroring it.
+    pub fn set(
+        &self,
+        document_id: &DocumentId,
+        repository: Option<&Path>,
+    ) -> Result<(), glib::Error> {
+        let group = document_id.to_string();
+        match repository {
+            Some(repository) => {
+                self.key_file
+                    .set_string(&group, REPOSITORY_KEY, &repository.to_string_lossy());
+                // Start over, the new repository doesn't contain anything yet
+                let _ = self.key_file.remove_key(&group, VERSION_KEY);
+            }
+            None => {
+                let _ = self.key_file.remove_group(&group);
+            }
+        }
+        self.key_file.save_to_file(&self.path)
+    }
+
+    /// Commit the current text of `document` to its repository.
+    ///
+    /// Does nothing if the document isn't mirrored or the text didn't change since the last
+    /// commit.
+    pub fn commit_snapshot(&self, document: &Document) -> Result<(), Error> {
+        self.commit(document, &document.text(), "Update document")
+    }
+
+    /// Commit the text of `document` at `checkpoint` to its repository, unless it was
+    /// committed already.
+    pub fn commit_checkpoint(
+        &self,
+        document: &Document,
+        checkpoint: &Checkpoint,
+    ) -> Result<(), Error> {
+        let group = document.id().to_string();
+        if self.get(&document.id()).is_none()
+            || self
+                .key_file
+                .string(&group, CHECKPOINT_KEY)
+                .is_ok_and(|id| id == checkpoint.id())
+        {
+            return Ok(());
+        }
+
+        let text = document
+            .checkpoint_text(checkpoint)
+            .map_err(|error| Error::Checkpoint(error.to_string()))?;
+        self.commit(
+            document,
+            &text,
+            &format!("Checkpoint \"{}\"", checkpoint.name()),
+        )?;
+        self.key_file
+            .set_string(&group, CHECKPOINT_KEY, &checkpoint.id());
+        self.key_file.save_to_file(&self.path)?;
+        Ok(())
+    }
+
+    fn commit(&self, document: &Document, text: &str, message: &str) -> Result<(), Error> {
+        let group = document.id().to_string();
+        let Some(repository) = self.get(&document.id()) else {
+            return Ok(());
+        };
+        let last_version = self
+            .key_file
+            .string(&group, VERSION_KEY)
+            .map(|version| glib::base64_decode(&version))
+            .unwrap_or_default();
+
+        // The first author who changed the document since the last commit is the author of
+        // the commit, the others are credited as co-authors
+        let mut authors = document.authors_changed_since(&last_version).into_iter();
+        let this_device = document
+            .authors()
+            .iter::<Author>()
+            .filter_map(Result::ok)
+            .find(Author::is_this_device);
+        let this_device = self.signature(this_device.as_ref());
+        let author = authors.next().map_or_else(
+            || this_device.clone(),
+            |author| self.signature(Some(&author)),
+        );
+        let mut message = message.to_string();
+        let co_authors: Vec<String> = authors
+            .map(|author| {
+                let (name, email) = self.signature(Some(&author));
+                format!("Co-authored-by: {name} <{email}>")
+            })
+            .collect();
+        if !co_authors.is_empty() {
+            message = format!("{message}\n\n{}", co_authors.join("\n"));
+        }
+
+        let file_name = history::file_name(document.name().as_deref());
+        let committed = commit_file(
+            &repository,
+            &file_name,
+            text,
+            &message,
+            &git2::Signature::now(&author.0, &author.1)?,
+            &git2::Signature::now(&this_device.0, &this_device.1)?,
+        )?;
+
+        if committed {
+            self.key_file.set_string(
+                &group,
+                VERSION_KEY,
+                &glib::base64_encode(&document.version()),
+            );
+            self.key_file.save_to_file(&self.path)?;
+        }
+        Ok(())
+    }
+
+    /// Name and email address of `author` in commits, see the module documentation.
+    fn signature(&self, author: Option<&Author>) -> (String, String) {
+        let mapped = author
+            .and_then(|author| {
+                self.key_file
+                    .string(AUTHORS_GROUP, &author.public_key().to_string())
+                    .ok()
+            })
+            .and_then(|identity| {
+                let (name, email) = identity.split_once('<')?;
+                Some((
+                    name.trim().to_string(),
+                    email.trim().trim_end_matches('>').to_string(),
+                ))
+            });
+        mapped.unwrap_or_else(|| history::signature(author))
+    }
+}
+
+/// Write `text` to `file_name` in `repository` and commit it, the repository is created if it
+/// doesn't exist yet.
+///
+/// Returns `false` if the file didn't change and nothing was committed.
+fn commit_file(
+    repository: &Path,
+    file_name: &str,
+    text: &str,
+    message: &str,
+    author: &git2::Signature,
+    committer: &git2::Signature,
+) -> Result<bool, Error> {
+    let repository = match git2::Repository::open(repository) {
+        Ok(repository) => repository,
+        Err(_) => git2::Repository::init(repository)?,
+    };
+    let workdir = repository
+        .workdir()
+        .ok_or_else(|| git2::Error::from_str("bare repositories aren't supported"))?;
+    std::fs::write(workdir.join(file_name), text)?;
+
+    let mut index = repository.index()?;
+    index.add_path(Path::new(file_name))?;
+    index.write()?;
+    let tree = repository.find_tree(index.write_tree()?)?;
+
+    let parent = repository
+        .head()
+        .ok()
+        .and_then(|head| head.peel_to_commit().ok());
+    if parent
+        .as_ref()
+        .is_some_and(|parent| parent.tree_id() == tree.id())
+    {
+        return Ok(false);
+    }
+
+    let parents: Vec<&git2::Commit> = parent.iter().collect();
+    repository.commit(Some("HEAD"), author, committer, message, &tree, &parents)?;
+    Ok(true)
+}
@@ -0,0 +1,660 @@
+/* Copyright 2025 The Aardvark Developers
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ *
+ * SPDX-License-Identifier: GPL-3.0-or-later
+ */
+
+use std::cell::RefCell;
+use std::str::FromStr;
+
+use aardvark_doc::author::Author;
+use aardvark_doc::checkpoints::{Checkpoint, DiffLine};
+use aardvark_doc::debug::SnapshotInfo;
+use aardvark_doc::document::Document;
+use aardvark_doc::history::ChangeBatch;
+use aardvark_doc::identity::PublicKey;
+use aardvark_doc::summary::ChangeSummary;
+use adw::prelude::*;
+use adw::subclass::prelude::*;
+use gettextrs::{gettext, ngettext};
+use gtk::{glib, glib::clone};
+use tracing::error;
+
+use crate::AardvarkWindow;
+use crate::components::Avatar;
+use crate::relative_time::RelativeTime;
+
+/// Name of the tag marking lines added since a checkpoint.
+const ADDED_TAG: &str = "added";
+/// Name of the tag marking lines removed since a checkpoint.
+const REMOVED_TAG: &str = "removed";
+
+mod imp {
+    use super::*;
+
+    #[derive(Debug, Default, glib::Properties)]
+    #[properties(wrapper_type = super::HistorySidebar)]
+    pub struct HistorySidebar {
+        checkpoint_list_box: gtk::ListBox,
+        changes_list_box: gtk::ListBox,
+        snapshot_list_box: gtk::ListBox,
+        stack: gtk::Stack,
+        empty_page: adw::StatusPage,
+        name_entry: gtk::Entry,
+        #[property(get, set = Self::set_document, nullable)]
+        document: RefCell<Option<Document>>,
+        items_changed_handler: RefCell<Option<glib::SignalHandlerId>>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for HistorySidebar {
+        const NAME: &'static str = "AardvarkHistorySidebar";
+        type Type = super::HistorySidebar;
+        type ParentType = adw::Bin;
+    }
+
+    #[glib::derived_properties]
+    impl ObjectImpl for HistorySidebar {
+        fn constructed(&self) {
+            self.parent_constructed();
+            let obj = self.obj();
+
+            self.checkpoint_list_box
+                .set_selection_mode(gtk::SelectionMode::None);
+            self.checkpoint_list_box.add_css_class("navigation-sidebar");
+            let scrollview = gtk::ScrolledWindow::builder()
+                .child(&self.checkpoint_list_box)
+                .hscrollbar_policy(gtk::PolicyType::Never)
+                .vexpand(true)
+                .build();
+            self.empty_page
+                .set_icon_name(Some("document-open-recent-symbolic"));
+            self.empty_page.set_title(&gettext("No Checkpoints"));
+            self.empty_page.set_description(Some(&gettext(
+                "Checkpoints name a version of the document to compare the text with later",
+            )));
+            self.empty_page.add_css_class("compact");
+            self.empty_page.set_vexpand(true);
+            self.stack.add_named(&scrollview, Some("checkpoints"));
+            self.stack.add_named(&self.empty_page, Some("empty"));
+
+            self.name_entry
+                .set_placeholder_text(Some(&gettext("Checkpoint Name")));
+            self.name_entry.set_hexpand(true);
+            self.name_entry.connect_activate(clone!(
+                #[weak(rename_to = this)]
+                self,
+                move |_| this.create_checkpoint()
+            ));
+            let create_button = gtk::Button::builder()
+                .label(gettext("_Create"))
+                .use_underline(true)
+                .css_classes(["suggested-action"])
+                .build();
+            create_button.connect_clicked(clone!(
+                #[weak(rename_to = this)]
+                self,
+                move |_| this.create_checkpoint()
+            ));
+            let create_box = gtk::Box::builder()
+                .spacing(6)
+                .margin_start(6)
+                .margin_end(6)
+                .margin_bottom(6)
+                .build();
+            create_box.append(&self.name_entry);
+            create_box.append(&create_button);
+
+            // Reverting the changes of one author keeps everyone else's
+            self.changes_list_box
+                .set_selection_mode(gtk::SelectionMode::None);
+            self.changes_list_box.add_css_class("navigation-sidebar");
+            let changes_label = gtk::Label::builder()
+                .label(gettext("Changes by Author"))
+                .xalign(0.0)
+                .margin_start(12)
+                .margin_top(6)
+                .css_classes(["heading"])
+                .build();
+            let changes_scrollview = gtk::ScrolledWindow::builder()
+                .child(&self.changes_list_box)
+                .hscrollbar_policy(gtk::PolicyType::Never)
+                .max_content_height(240)
+                .propagate_natural_height(true)
+                .build();
+
+            // Stored snapshots allow recovering the text, e.g. if another author deleted it
+            self.snapshot_list_box
+                .set_selection_mode(gtk::SelectionMode::None);
+            self.snapshot_list_box.add_css_class("navigation-sidebar");
+            let snapshots_label = gtk::Label::builder()
+                .label(gettext("Stored Snapshots"))
+                .xalign(0.0)
+                .margin_start(12)
+                .margin_top(6)
+                .css_classes(["heading"])
+                .build();
+            let content = gtk::Box::new(gtk::Orientation::Vertical, 0);
+            content.append(&self.stack);
+            content.append(&changes_label);
+            content.append(&changes_scrollview);
+            content.append(&snapshots_label);
+            content.append(&self.snapshot_list_box);
+            obj.connect_map(|obj| {
+                obj.imp().update_changes();
+                obj.imp().update_snapshots();
+            });
+
+            let header_bar = adw::HeaderBar::builder()
+                .title_widget(&adw::WindowTitle::new(&gettext("History"), ""))
+                .show_start_title_buttons(false)
+                .show_end_title_buttons(false)
+                .build();
+            let toolbar_view = adw::ToolbarView::new();
+            toolbar_view.add_top_bar(&header_bar);
+            toolbar_view.set_content(Some(&content));
+            toolbar_view.add_bottom_bar(&create_box);
+            obj.set_child(Some(&toolbar_view));
+            obj.add_css_class("history-sidebar");
+        }
+
+        fn dispose(&self) {
+            self.set_document(None);
+        }
+    }
+
+    impl HistorySidebar {
+        fn set_document(&self, document: Option<Document>) {
+            if let Some(handler) = self.items_changed_handler.take() {
+                if let Some(document) = self.document.borrow().as_ref() {
+                    document.checkpoints().disconnect(handler);
+                }
+            }
+
+            let checkpoints = document.as_ref().map(Document::checkpoints);
+            self.checkpoint_list_box.bind_model(
+                checkpoints.as_ref(),
+                clone!(
+                    #[weak(rename_to = this)]
+                    self,
+                    #[upgrade_or_panic]
+                    move |checkpoint| {
+                        let checkpoint = checkpoint.downcast_ref::<Checkpoint>().unwrap();
+                        this.checkpoint_row(checkpoint).upcast()
+                    }
+                ),
+            );
+            if let Some(checkpoints) = checkpoints.as_ref() {
+                let handler = checkpoints.connect_items_changed(clone!(
+                    #[weak(rename_to = this)]
+                    self,
+                    move |_, _, _, _| this.update_stack()
+                ));
+                self.items_changed_handler.replace(Some(handler));
+            }
+
+            self.document.replace(document);
+            self.update_stack();
+            self.update_changes();
+            self.update_snapshots();
+        }
+
+        fn update_stack(&self) {
+            let is_empty = self
+                .obj()
+                .document()
+                .is_none_or(|document| document.checkpoints().n_items() == 0);
+            self.stack
+                .set_visible_child_name(if is_empty { "empty" } else { "checkpoints" });
+        }
+
+        fn checkpoint_row(&self, checkpoint: &Checkpoint) -> adw::ActionRow {
+            let row = adw::ActionRow::builder()
+                .activatable(true)
+                .tooltip_text(gettext("Compare With Current Text"))
+                .build();
+            checkpoint
+                .bind_property("name", &row, "title")
+                .transform_to(|_, name: String| Some(glib::markup_escape_text(&name)))
+                .sync_create()
+                .build();
+            let created_at = RelativeTime::new();
+            checkpoint
+                .bind_property("created-at", &created_at, "datetime")
+                .sync_create()
+                .build();
+            created_at
+                .bind_property("display-string", &row, "subtitle")
+                .sync_create()
+                .build();
+            // Keep the relative time up to date as long as the row exists
+            row.connect_destroy(move |_| created_at.set_datetime(None::<&glib::DateTime>));
+            row.connect_activated(clone!(
+                #[weak(rename_to = this)]
+                self,
+                #[weak]
+                checkpoint,
+                move |_| this.show_diff(&checkpoint)
+            ));
+
+            row
+        }
+
+        /// List the changes of the document grouped by author, while the sidebar is shown.
+        ///
+        /// Authors who changed the text most recently are listed first.
+        fn update_changes(&self) {
+            self.changes_list_box.remove_all();
+            let Some(document) = self.obj().document() else {
+                return;
+            };
+            if !self.obj().is_mapped() {
+                return;
+            }
+
+            let batches = match document.change_batches() {
+                Ok(batches) => batches,
+                Err(error) => {
+                    error!("Failed to list changes: {error}");
+                    return;
+                }
+            };
+            let mut groups: Vec<(Option<Author>, Vec<ChangeBatch>)> = Vec::new();
+            for batch in batches.into_iter().rev() {
+                match groups
+                    .iter_mut()
+                    .find(|(author, _)| *author == batch.author)
+                {
+                    Some((_, group)) => group.push(batch),
+                    None => groups.push((batch.author.clone(), vec![batch])),
+                }
+            }
+            for (author, batches) in groups {
+                self.changes_list_box
+                    .append(&self.author_row(&document, author.as_ref(), batches));
+            }
+        }
+
+        fn author_row(
+            &self,
+            document: &Document,
+            author: Option<&Author>,
+            batches: Vec<ChangeBatch>,
+        ) -> adw::ExpanderRow {
+            let title = author.map_or_else(|| gettext("Unknown Author"), Author::name);
+            let row = adw::ExpanderRow::builder()
+                .title(glib::markup_escape_text(&title))
+                .subtitle(
+                    ngettext("{} edit", "{} edits", batches.len() as u32)
+                        .replace("{}", &batches.len().to_string()),
+                )
+                .build();
+            if let Some(author) = author {
+                let avatar = Avatar::new();
+                avatar.set_emoji(&author.emoji());
+                avatar.set_paintable(Some(&author.avatar()));
+                avatar.add_css_class(&format!("bg-{}", author.color()));
+                row.add_prefix(&avatar);
+            }
+            for batch in batches {
+                row.add_row(&self.batch_row(document, batch));
+            }
+
+            row
+        }
+
+        fn batch_row(&self, document: &Document, batch: ChangeBatch) -> adw::ActionRow {
+            let changes = ngettext("{} change", "{} changes", batch.changes)
+                .replace("{}", &batch.changes.to_string());
+            let subtitle = match document.change_summary(&batch) {
+                Ok(summary) if !summary.is_empty() => {
+                    // Translators: The number of changes and what they changed, e.g.
+                    // "3 changes · +120 chars in §Introduction, −2 lines"
+                    gettext("{changes} · {summary}")
+                        .replace("{changes}", &changes)
+                        .replace("{summary}", &format_summary(&summary))
+                }
+                Ok(_) => changes,
+                Err(error) => {
+                    error!("Failed to summarize changes: {error}");
+                    changes
+                }
+            };
+            let row = adw::ActionRow::builder()
+                .title(gettext("Unknown Time"))
+                .subtitle(glib::markup_escape_text(&subtitle))
+                .build();
+            if let Some(timestamp) = batch.timestamp {
+                let made_at = RelativeTime::new();
+                made_at.set_datetime(glib::DateTime::from_unix_utc(timestamp).ok().as_ref());
+                made_at
+                    .bind_property("display-string", &row, "title")
+                    .sync_create()
+                    .build();
+                // Keep the relative time up to date as long as the row exists
+                row.connect_destroy(move |_| made_at.set_datetime(None::<&glib::DateTime>));
+            }
+
+            let revert_button = gtk::Button::builder()
+                .icon_name("edit-undo-symbolic")
+                .tooltip_text(gettext("Revert Changes"))
+                .valign(gtk::Align::Center)
+                .css_classes(["flat"])
+                .build();
+            revert_button.connect_clicked(clone!(
+                #[weak(rename_to = this)]
+                self,
+                #[weak]
+                document,
+                move |_| this.confirm_revert(&document, batch.clone())
+            ));
+            row.add_suffix(&revert_button);
+
+            row
+        }
+
+        /// Ask whether to revert `batch`, showing how the text would change.
+        fn confirm_revert(&self, document: &Document, batch: ChangeBatch) {
+            let diff = match document.revert_preview(&batch) {
+                Ok(diff) => diff,
+                Err(error) => {
+                    error!("Failed to preview reverting changes: {error}");
+                    return;
+                }
+            };
+
+            let name = batch
+                .author
+                .as_ref()
+                .map_or_else(|| gettext("an unknown author"), Author::name);
+            let diff_view = diff_view(diff);
+            diff_view.set_min_content_height(240);
+            diff_view.add_css_class("card");
+            let dialog = adw::AlertDialog::builder()
+                .heading(gettext("Revert Changes?"))
+                .body(
+                    gettext("The changes by {} are undone for everyone, later changes are kept")
+                        .replace("{}", &name),
+                )
+                .extra_child(&diff_view)
+                .prefer_wide_layout(true)
+                .close_response("cancel")
+                .default_response("cancel")
+                .build();
+            dialog.add_responses(&[
+                ("cancel", &gettext("_Cancel")),
+                ("revert", &gettext("_Revert")),
+            ]);
+            dialog.set_response_appearance("revert", adw::ResponseAppearance::Destructive);
+            dialog.connect_response(
+                Some("revert"),
+                clone!(
+                    #[weak(rename_to = this)]
+                    self,
+                    #[weak]
+                    document,
+                    move |_, _| {
+                        if let Err(error) = document.revert_changes(&batch) {
+                            error!("Failed to revert changes: {error}");
+                        }
+                        this.update_changes();
+                    }
+                ),
+            );
+            dialog.present(Some(&*self.obj()));
+        }
+
+        /// Load the snapshots stored for the document, while the sidebar is shown.
+        fn update_snapshots(&self) {
+            self.snapshot_list_box.remove_all();
+            let Some(document) = self.obj().document() else {
+                return;
+            };
+            if !self.obj().is_mapped() {
+                return;
+            }
+
+            glib::spawn_future_local(clone!(
+                #[weak(rename_to = this)]
+                self,
+                async move {
+                    let snapshots = match document.snapshots().await {
+                        Ok(snapshots) => snapshots,
+                        Err(error) => {
+                            error!("Failed to list stored snapshots: {error}");
+                            return;
+                        }
+                    };
+                    // The document might have changed in the meantime
+                    if this.obj().document().as_ref() != Some(&document) {
+                        return;
+                    }
+
+                    this.snapshot_list_box.remove_all();
+                    for snapshot in snapshots {
+                        this.snapshot_list_box
+                            .append(&this.snapshot_row(&document, snapshot));
+                    }
+                }
+            ));
+        }
+
+        fn snapshot_row(&self, document: &Document, snapshot: SnapshotInfo) -> adw::ActionRow {
+            let author = PublicKey::from_str(&snapshot.public_key.to_string())
+                .ok()
+                .and_then(|public_key| document.authors().by_public_key(&public_key));
+            let title = author
+                .as_ref()
+                .map_or_else(|| snapshot.public_key.to_string(), |author| author.name());
+            let created_at = glib::DateTime::from_unix_utc(snapshot.timestamp as i64)
+                .and_then(|created_at| created_at.to_local())
+                .and_then(|created_at| created_at.format("%x %X"))
+                .map(String::from)
+                .unwrap_or_default();
+            let row = adw::ActionRow::builder()
+                .title(glib::markup_escape_text(&title))
+                .subtitle(format!(
+                    "{created_at} · {}",
+                    glib::format_size(snapshot.size)
+                ))
+                .build();
+            if let Some(author) = author {
+                let avatar = Avatar::new();
+                avatar.set_emoji(&author.emoji());
+                avatar.set_paintable(Some(&author.avatar()));
+                avatar.add_css_class(&format!("bg-{}", author.color()));
+                row.add_prefix(&avatar);
+            }
+
+            let restore_button = gtk::Button::builder()
+                .icon_name("document-revert-symbolic")
+                .tooltip_text(gettext("Restore as New Document"))
+                .valign(gtk::Align::Center)
+                .css_classes(["flat"])
+                .build();
+            restore_button.connect_clicked(clone!(
+                #[weak(rename_to = this)]
+                self,
+                #[weak]
+                document,
+                move |_| {
+                    let hash = snapshot.hash;
+                    glib::spawn_future_local(clone!(
+                        #[weak]
+                        this,
+                        #[weak]
+                        document,
+                        async move {
+                            let restored = match document.restore_snapshot(&hash).await {
+                                Ok(restored) => restored,
+                                Err(error) => {
+                                    error!("Failed to restore snapshot: {error}");
+                                    return;
+                                }
+                            };
+                            if let Some(window) = this.obj().root().and_downcast::<AardvarkWindow>()
+                            {
+                                window.add_document(&restored);
+                            }
+                        }
+                    ));
+                }
+            ));
+            row.add_suffix(&restore_button);
+
+            row
+        }
+
+        /// Create a checkpoint of the current version of the document.
+        fn create_checkpoint(&self) {
+            let Some(document) = self.obj().document() else {
+                return;
+            };
+            let mut name = self.name_entry.text().trim().to_string();
+            if name.is_empty() {
+                name = gettext("Checkpoint");
+            }
+            if let Err(error) = document.create_checkpoint(&name) {
+                error!("Failed to create checkpoint: {error}");
+                return;
+            }
+            self.name_entry.set_text("");
+        }
+
+        /// Show what changed in the text since `checkpoint`.
+        fn show_diff(&self, checkpoint: &Checkpoint) {
+            let Some(document) = self.obj().document() else {
+                return;
+            };
+            let diff = match document.diff_against(checkpoint) {
+                Ok(diff) => diff,
+                Err(error) => {
+                    error!("Failed to compare with checkpoint: {error}");
+                    return;
+                }
+            };
+
+            let scrollview = diff_view(diff);
+            let toolbar_view = adw::ToolbarView::new();
+            toolbar_view.add_top_bar(&adw::HeaderBar::new());
+            toolbar_view.set_content(Some(&scrollview));
+
+            let dialog = adw::Dialog::builder()
+                .title(gettext("Changes Since “{}”").replace("{}", &checkpoint.name()))
+                .content_width(640)
+                .content_height(560)
+                .child(&toolbar_view)
+                .build();
+            dialog.present(Some(&*self.obj()));
+        }
+    }
+
+    impl WidgetImpl for HistorySidebar {}
+    impl BinImpl for HistorySidebar {}
+}
+
+glib::wrapper! {
+    /// Checkpoints of a document, to create one or compare the text with it, and the changes of
+    /// every author, to revert them.
+    pub struct HistorySidebar(ObjectSubclass<imp::HistorySidebar>)
+        @extends gtk::Widget, adw::Bin;
+}
+
+/// "+120 chars in §Introduction, −2 lines"
+fn format_summary(summary: &ChangeSummary) -> String {
+    let mut chars = Vec::new();
+    if summary.inserted > 0 {
+        chars.push(
+            ngettext("+{} char", "+{} chars", summary.inserted as u32)
+                .replace("{}", &summary.inserted.to_string()),
+        );
+    }
+    if summary.deleted > 0 {
+        chars.push(
+            ngettext("−{} char", "−{} chars", summary.deleted as u32)
+                .replace("{}", &summary.deleted.to_string()),
+        );
+    }
+    let chars = chars.join(" ");
+
+    let mut text = match (summary.section(), summary.sections.len()) {
+        (Some(section), _) => gettext("{chars} in §{section}")
+            .replace("{chars}", &chars)
+            .replace("{section}", section),
+        (None, 0) => chars,
+        (None, count) => ngettext(
+            "{chars} in {count} section",
+            "{chars} in {count} sections",
+            count as u32,
+        )
+        .replace("{chars}", &chars)
+        .replace("{count}", &count.to_string()),
+    };
+
+    let lines = summary.lines();
+    if lines != 0 {
+        let count = lines.unsigned_abs();
+        let lines = if lines > 0 {
+            ngettext("+{} line", "+{} lines", count as u32)
+        } else {
+            ngettext("−{} line", "−{} lines", count as u32)
+        }
+        .replace("{}", &count.to_string());
+        text = gettext("{summary}, {lines}")
+            .replace("{summary}", &text)
+            .replace("{lines}", &lines);
+    }
+
+    text
+}
+
+/// A read-only view of `diff`, marking added and removed lines.
+fn diff_view(diff: Vec<DiffLine>) -> gtk::ScrolledWindow {
+    let buffer = gtk::TextBuffer::new(None);
+    let added_tag = buffer.create_tag(Some(ADDED_TAG), &[]).unwrap();
+    added_tag.set_paragraph_background_rgba(Some(&gtk::gdk::RGBA::new(0.2, 0.82, 0.478, 0.25)));
+    let removed_tag = buffer.create_tag(Some(REMOVED_TAG), &[]).unwrap();
+    removed_tag
+        .set_paragraph_background_rgba(Some(&gtk::gdk::RGBA::new(0.878, 0.106, 0.141, 0.25)));
+    removed_tag.set_strikethrough(true);
+    for line in diff {
+        let mut end = buffer.end_iter();
+        match line {
+            DiffLine::Unchanged(line) => buffer.insert(&mut end, &format!("{line}\n")),
+            DiffLine::Added(line) => {
+                buffer.insert_with_tags(&mut end, &format!("{line}\n"), &[&added_tag])
+            }
+            DiffLine::Removed(line) => {
+                buffer.insert_with_tags(&mut end, &format!("{line}\n"), &[&removed_tag])
+            }
+        }
+    }
+
+    let text_view = gtk::TextView::builder()
+        .buffer(&buffer)
+        .editable(false)
+        .cursor_visible(false)
+        .monospace(true)
+        .wrap_mode(gtk::WrapMode::WordChar)
+        .top_margin(12)
+        .bottom_margin(12)
+        .left_margin(12)
+        .right_margin(12)
+        .build();
+    gtk::ScrolledWindow::builder()
+        .child(&text_view)
+        .hscrollbar_policy(gtk::PolicyType::Never)
+        .build()
+}
@@ -0,0 +1,279 @@
+/* Copyright 2025 The Aardvark Developers
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ *
+ * SPDX-License-Identifier: GPL-3.0-or-later
+ */
+
+use std::fs;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use gtk::prelude::*;
+use gtk::{gio, glib};
+use thiserror::Error;
+use tracing::error;
+
+use aardvark_doc::document::DocumentId;
+use aardvark_doc::identity::{KeyfileError, PrivateKey, PublicKey};
+use aardvark_doc::service::{AccountBackup, DeviceLink, Service};
+
+use crate::application::data_path;
+use crate::file_transfer;
+use crate::secret;
+
+/// Command line option to start an anonymous session, see [`ephemeral_identity`].
+pub const ANONYMOUS_OPTION: &str = "anonymous";
+/// File extension of exported identities.
+pub const KEYFILE_EXTENSION: &str = "aardvark-key";
+/// File extension of account backups.
+pub const BACKUP_EXTENSION: &str = "aardvark-backup";
+/// Documents handed over by a linked device, they are added to the store on the next start.
+const LINKED_DOCUMENTS_FILE: &str = "linked-documents.ini";
+/// Documents of a restored backup, they are added to the store on the next start.
+const RESTORED_DOCUMENTS_FILE: &str = "restored-documents.cbor";
+/// Directory in the runtime directory with the data of anonymous sessions.
+const ANONYMOUS_DIR: &str = "aardvark-anonymous";
+const NAME_KEY: &str = "name";
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("File error: {0}")]
+    File(#[from] glib::Error),
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Keyfile error: {0}")]
+    Keyfile(#[from] KeyfileError),
+    #[error(transparent)]
+    Secret(#[from] secret::Error),
+    #[error("Backup error: {0}")]
+    Backup(String),
+}
+
+/// Moves the author identity of this device to and from passphrase protected keyfiles, so the
+/// same identity can be used on multiple devices.
+#[derive(Debug, Clone)]
+pub struct IdentityManager {
+    service: Service,
+}
+
+impl IdentityManager {
+    pub fn new(service: &Service) -> Self {
+        Self {
+            service: service.clone(),
+        }
+    }
+
+    /// The identity currently in use.
+    pub fn public_key(&self) -> PublicKey {
+        self.service.private_key().public_key()
+    }
+
+    /// Whether the identity in use is a throwaway one, see [`ephemeral_identity`].
+    pub fn is_ephemeral(&self) -> bool {
+        self.service.anonymous()
+    }
+
+    /// Write the identity of this device to `file`, encrypted with `passphrase`.
+    pub async fn export(&self, file: &gio::File, passphrase: &str) -> Result<(), Error> {
+        let private_key = self.service.private_key();
+        let passphrase = passphrase.to_owned();
+        // Deriving the key from the passphrase is deliberately slow
+        let keyfile = gio::spawn_blocking(move || private_key.to_keyfile(&passphrase))
+            .await
+            .expect("Keyfile encryption doesn't panic")?;
+
+        file_transfer::write(file, keyfile, true).await?;
+
+        Ok(())
+    }
+
+    /// Read the identity stored in `file` without using it yet.
+    pub async fn read(&self, file: &gio::File, passphrase: &str) -> Result<PrivateKey, Error> {
+        let keyfile = file_transfer::read(file).await?;
+        let passphrase = passphrase.to_owned();
+        let private_key =
+            gio::spawn_blocking(move || PrivateKey::from_keyfile(&keyfile, &passphrase))
+                .await
+                .expect("Keyfile decryption doesn't panic")?;
+
+        Ok(private_key)
+    }
+
+    /// Replace the identity of this device, it's used after the next start.
+    ///
+    /// The current identity is lost unless it was exported before.
+    pub async fn import(&self, private_key: &PrivateKey) -> Result<(), Error> {
+        secret::replace_identity(private_key).await?;
+        Ok(())
+    }
+
+    /// Replace the identity of this device with the one of a linked device.
+    ///
+    /// Like [`Self::import`] it's used after the next start, the documents of the linked device
+    /// are added then as well.
+    pub async fn link(&self, device_link: &DeviceLink) -> Result<(), Error> {
+        let data_path = data_path(&device_link.private_key.public_key());
+        fs::create_dir_all(&data_path)?;
+
+        let key_file = glib::KeyFile::new();
+        for (document_id, name) in &device_link.documents {
+            key_file.set_string(
+                &document_id.to_string(),
+                NAME_KEY,
+                name.as_deref().unwrap_or_default(),
+            );
+        }
+        key_file.save_to_file(data_path.join(LINKED_DOCUMENTS_FILE))?;
+
+        self.import(&device_link.private_key).await
+    }
+
+    /// Write the identity and all documents of this device to `file`, encrypted with
+    /// `passphrase`.
+    pub async fn export_backup(&self, file: &gio::File, passphrase: &str) -> Result<(), Error> {
+        let backup = self
+            .service
+            .export_backup(passphrase)
+            .await
+            .map_err(|error| Error::Backup(error.to_string()))?;
+
+        file_transfer::write(file, backup, true).await?;
+
+        Ok(())
+    }
+
+    /// Read the backup stored in `file` without restoring it yet.
+    pub async fn read_backup(
+        &self,
+        file: &gio::File,
+        passphrase: &str,
+    ) -> Result<AccountBackup, Error> {
+        let bytes = file_transfer::read(file).await?;
+        let passphrase = passphrase.to_owned();
+        gio::spawn_blocking(move || AccountBackup::open(&bytes, &passphrase))
+            .await
+            .expect("Backup decryption doesn't panic")
+            .map_err(|error| match error.downcast_ref::<KeyfileError>() {
+                Some(KeyfileError::Decryption) => Error::Keyfile(KeyfileError::Decryption),
+                _ => Error::Backup(error.to_string()),
+            })
+    }
+
+    /// Replace the identity of this device with the one of a backup.
+    ///
+    /// Like [`Self::import`] it's used after the next start, the documents of the backup are
+    /// restored then as well.
+    pub async fn restore_backup(&self, backup: &AccountBackup) -> Result<(), Error> {
+        let data_path = data_path(&backup.private_key.public_key());
+        fs::create_dir_all(&data_path)?;
+
+        let documents = backup
+            .documents_to_bytes()
+            .map_err(|error| Error::Backup(error.to_string()))?;
+        fs::write(data_path.join(RESTORED_DOCUMENTS_FILE), documents)?;
+
+        self.import(&backup.private_key).await
+    }
+}
+
+/// A throwaway identity for an anonymous session and an empty data directory for it.
+///
+/// The identity is never stored in the keyring and the data directory is in the runtime
+/// directory, remove it with [`remove_ephemeral_data`] when the session ends.
+pub fn ephemeral_identity() -> Result<(PrivateKey, PathBuf), Error> {
+    let private_key = PrivateKey::new();
+    let data_path = glib::user_runtime_dir()
+        .join(ANONYMOUS_DIR)
+        .join(private_key.public_key().to_string());
+    fs::create_dir_all(&data_path)?;
+
+    Ok((private_key, data_path))
+}
+
+/// Remove everything an anonymous session stored, including who wrote what.
+pub fn remove_ephemeral_data(service: &Service) {
+    let Some(data_path) = service.data_dir().path() else {
+        return;
+    };
+    if let Err(error) = fs::remove_dir_all(&data_path) {
+        error!("Failed to remove data of anonymous session: {error}");
+    }
+}
+
+/// Add the documents handed over by a linked device to `service`, if there are any.
+///
+/// Returns the added documents.
+pub async fn add_linked_documents(service: &Service) -> Vec<(DocumentId, Option<String>)> {
+    let Some(path) = service
+        .data_dir()
+        .path()
+        .map(|data_dir| data_dir.join(LINKED_DOCUMENTS_FILE))
+    else {
+        return Vec::new();
+    };
+    let key_file = glib::KeyFile::new();
+    if key_file
+        .load_from_file(&path, glib::KeyFileFlags::NONE)
+        .is_err()
+    {
+        return Vec::new();
+    }
+
+    let documents: Vec<(DocumentId, Option<String>)> = key_file
+        .groups()
+        .iter()
+        .filter_map(|group| {
+            let group = group.to_string();
+            let document_id = DocumentId::from_str(&group).ok()?;
+            let name = key_file
+                .string(&group, NAME_KEY)
+                .ok()
+                .filter(|name| !name.is_empty())
+                .map(String::from);
+            Some((document_id, name))
+        })
+        .collect();
+
+    if let Err(error) = service.add_linked_documents(&documents).await {
+        error!("Failed to add documents of linked device: {error}");
+        return Vec::new();
+    }
+    if let Err(error) = fs::remove_file(&path) {
+        error!("Failed to remove list of linked documents: {error}");
+    }
+    documents
+}
+
+/// Add the documents of a restored backup to `service`, if there are any.
+pub async fn restore_backup_documents(service: &Service) {
+    let Some(path) = service
+        .data_dir()
+        .path()
+        .map(|data_dir| data_dir.join(RESTORED_DOCUMENTS_FILE))
+    else {
+        return;
+    };
+    let Ok(documents) = fs::read(&path) else {
+        return;
+    };
+
+    if let Err(error) = service.restore_documents(&documents).await {
+        error!("Failed to restore documents of backup: {error}");
+        return;
+    }
+    if let Err(error) = fs::remove_file(&path) {
+        error!("Failed to remove documents of restored backup: {error}");
+    }
+}
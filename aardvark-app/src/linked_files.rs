@@ -0,0 +1,69 @@
+/* publish.rs
+ *
+ * Copyright 2025 The Aardvark Developers
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ *
+ * SPDX-License-Identifier: GPL-3.0-or-later
+ */
+
+//! Local files documents were exported to with "Save As…".
+//!
+//! The association is remembered in `linked-files.ini` inside the data directory, so saving
+//! again later writes the latest state of the document to the same file.
+
+use std::path::{Path, PathBuf};
+
+use aardvark_doc::document::{Document, DocumentId};
+use gtk::prelude::*;
+use gtk::{gio, glib};
+
+use crate::file_transfer;
+
+const LINKED_FILES_FILE: &str = "linked-files.ini";
+const URI_KEY: &str = "uri";
+
+pub struct LinkedFiles {
+    path: PathBuf,
+    key_file: glib::KeyFile,
+}
+
+impl LinkedFiles {
+    pub fn load(data_dir: &Path) -> Self {
+        let key_file = glib::KeyFile::new();
+        let path = data_dir.join(LINKED_FILES_FILE);
+        // A missing or broken file means that no document was saved yet
+        let _ = key_file.load_from_file(&path, glib::KeyFileFlags::NONE);
+
+        Self { path, key_file }
+    }
+
+    pub fn get(&self, document_id: &DocumentId) -> Option<gio::File> {
+        self.key_file
+            .string(&document_id.to_string(), URI_KEY)
+            .ok()
+            .map(|uri| gio::File::for_uri(&uri))
+    }
+
+    pub fn set(&self, document_id: &DocumentId, file: &gio::File) -> Result<(), glib::Error> {
+        self.key_file
+            .set_string(&document_id.to_string(), URI_KEY, &file.uri());
+        self.key_file.save_to_file(&self.path)
+    }
+}
+
+/// Write the current text of the document to `file`, replacing its contents.
+pub async fn save(document: &Document, file: &gio::File) -> Result<(), glib::Error> {
+    file_transfer::write(file, document.text().into_bytes(), false).await
+}
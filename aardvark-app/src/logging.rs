@@ -0,0 +1,147 @@
+/* logging.rs
+ *
+ * Copyright 2025 The Aardvark Developers
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ *
+ * SPDX-License-Identifier: GPL-3.0-or-later
+ */
+
+use std::collections::VecDeque;
+use std::io;
+use std::sync::{Mutex, OnceLock};
+
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::prelude::*;
+use tracing_subscriber::{EnvFilter, Registry, reload};
+
+/// Number of log lines kept in memory for bug reports.
+const LOG_BUFFER_CAPACITY: usize = 2000;
+
+/// Filter used when `--verbose` is passed.
+pub const VERBOSE_FILTER: &str = "aardvark=debug,aardvark_doc=debug,aardvark_core=debug,info";
+
+static FILTER_HANDLE: OnceLock<reload::Handle<EnvFilter, Registry>> = OnceLock::new();
+static LOG_BUFFER: LogBuffer = LogBuffer::new();
+
+/// Ring buffer holding the most recent formatted log records.
+struct LogBuffer {
+    lines: Mutex<VecDeque<String>>,
+}
+
+impl LogBuffer {
+    const fn new() -> Self {
+        Self {
+            lines: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    fn push(&self, line: String) {
+        let mut lines = self.lines.lock().unwrap();
+        if lines.len() == LOG_BUFFER_CAPACITY {
+            lines.pop_front();
+        }
+        lines.push_back(line);
+    }
+}
+
+/// Writer handed out to the fmt layer, one is created per log record.
+struct LogBufferWriter {
+    buf: Vec<u8>,
+}
+
+impl io::Write for LogBufferWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buf.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for LogBufferWriter {
+    fn drop(&mut self) {
+        let line = String::from_utf8_lossy(&self.buf);
+        LOG_BUFFER.push(line.trim_end().to_owned());
+    }
+}
+
+struct MakeLogBufferWriter;
+
+impl<'a> MakeWriter<'a> for MakeLogBufferWriter {
+    type Writer = LogBufferWriter;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        LogBufferWriter { buf: Vec::new() }
+    }
+}
+
+/// Install the global tracing subscriber.
+///
+/// Log records are written to stderr and kept in an in-memory buffer, see [`recent_records`].
+/// The filter is taken from `RUST_LOG` and can be changed at runtime with [`set_filter`].
+pub fn setup_logging() {
+    let (filter, handle) = reload::Layer::new(EnvFilter::from_default_env());
+
+    let result = tracing_subscriber::registry()
+        .with(filter)
+        .with(tracing_subscriber::fmt::layer().with_writer(std::io::stderr))
+        .with(
+            tracing_subscriber::fmt::layer()
+                .with_ansi(false)
+                .with_writer(MakeLogBufferWriter),
+        )
+        .try_init();
+
+    if result.is_ok() {
+        FILTER_HANDLE.set(handle).ok();
+    }
+}
+
+/// Replace the active log filter, uses the same syntax as `RUST_LOG`.
+pub fn set_filter(directives: &str) -> Result<(), String> {
+    let filter = EnvFilter::try_new(directives).map_err(|error| error.to_string())?;
+    let Some(handle) = FILTER_HANDLE.get() else {
+        return Err("Logging isn't initialized".to_string());
+    };
+
+    handle.reload(filter).map_err(|error| error.to_string())
+}
+
+/// Returns the recent log records, oldest first, one record per line.
+pub fn recent_records() -> String {
+    format_records(&LOG_BUFFER.lines.lock().unwrap())
+}
+
+/// Same as [`recent_records`], but `None` instead of waiting while a record is written.
+///
+/// Used while crashing, the crash might have happened while writing a record.
+pub fn try_recent_records() -> Option<String> {
+    LOG_BUFFER
+        .lines
+        .try_lock()
+        .ok()
+        .map(|lines| format_records(&lines))
+}
+
+fn format_records(lines: &VecDeque<String>) -> String {
+    let mut records = String::new();
+    for line in lines.iter() {
+        records.push_str(line);
+        records.push('\n');
+    }
+    records
+}
@@ -19,13 +19,37 @@
  */
 
 mod application;
+mod background;
+mod bookmarks_popover;
+mod calendar_dialog;
 mod components;
 mod config;
 mod connection_popover;
+mod connection_test_dialog;
+mod crash_report;
+mod debug_dialog;
+mod document_view;
+mod emoji_completion;
+mod file_transfer;
+mod git_mirror;
+mod history_sidebar;
+mod identity_manager;
+mod linked_files;
+mod logging;
+mod migrations;
 mod open_dialog;
 mod open_popover;
+mod poll_widget;
+mod preferences_dialog;
+mod profiler_overlay;
+mod publish;
+mod relative_time;
 mod secret;
+mod settings;
+mod shortcuts;
+mod status_bar;
 mod system_settings;
+mod tasks_popover;
 mod textbuffer;
 mod window;
 
@@ -33,20 +57,23 @@ use gettextrs::{bind_textdomain_codeset, bindtextdomain, textdomain};
 use gtk::prelude::*;
 use gtk::{gio, glib};
 use tracing::info;
-use tracing_subscriber::EnvFilter;
-use tracing_subscriber::prelude::*;
 
 use self::application::AardvarkApplication;
+use self::bookmarks_popover::BookmarksPopover;
 use self::config::*;
 use self::connection_popover::ConnectionPopover;
+use self::history_sidebar::HistorySidebar;
+use self::identity_manager::ANONYMOUS_OPTION;
 use self::open_popover::OpenPopover;
+use self::tasks_popover::TasksPopover;
 use self::textbuffer::AardvarkTextBuffer;
 use self::window::AardvarkWindow;
 
 pub use self::config::APP_ID;
 
 fn main() -> glib::ExitCode {
-    setup_logging();
+    logging::setup_logging();
+    crash_report::install();
 
     // Set up gettext translations
     bindtextdomain(GETTEXT_PACKAGE, LOCALEDIR).expect("Unable to bind the text domain");
@@ -63,7 +90,18 @@ fn main() -> glib::ExitCode {
     // Create a new GtkApplication. The application manages our main loop,
     // application windows, integration with the window manager/compositor, and
     // desktop features such as file opening and single-instance applications.
-    let app = AardvarkApplication::new("org.p2panda.aardvark", &gio::ApplicationFlags::empty());
+    //
+    // Documents can be passed as `aardvark:<document-id>` links, a running instance will receive
+    // them through `gio::Application::open()` via D-Bus.
+    //
+    // Anonymous sessions run in their own instance next to the regular one, the identity is
+    // chosen before the command line is parsed by the application.
+    let anonymous = std::env::args().any(|arg| arg == format!("--{ANONYMOUS_OPTION}"));
+    let mut flags = gio::ApplicationFlags::HANDLES_OPEN;
+    if anonymous {
+        flags |= gio::ApplicationFlags::NON_UNIQUE;
+    }
+    let app = AardvarkApplication::new("org.p2panda.aardvark", &flags, anonymous);
 
     info!("Aardvark ({})", APP_ID);
     info!("Version: {}", VERSION);
@@ -75,11 +113,3 @@ fn main() -> glib::ExitCode {
     // terminal.
     app.run()
 }
-
-fn setup_logging() {
-    tracing_subscriber::registry()
-        .with(tracing_subscriber::fmt::layer().with_writer(std::io::stderr))
-        .with(EnvFilter::from_default_env())
-        .try_init()
-        .ok();
-}
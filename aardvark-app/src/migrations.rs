@@ -0,0 +1,201 @@
+/* migrations.rs
+ *
+ * Copyright 2025 The Aardvark Developers
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ *
+ * SPDX-License-Identifier: GPL-3.0-or-later
+ */
+
+//! Versioned migrations of persisted settings and data.
+//!
+//! The version of the data directory is stored in `versions.ini` inside of it. On startup all
+//! migrations with a version greater than the stored one are run in order, after every successful
+//! step the new version is written to disk. A failing migration therefore leaves the data at the
+//! last good version and is retried on the next start.
+//!
+//! The SQL schema of the store is migrated by the node itself (see `aardvark-core/migrations`),
+//! migrations here are meant for changes which can't be expressed in SQL, e.g. moving the
+//! database file or renaming settings. Files we export, like backups, carry their format version
+//! in their magic bytes and aren't migrated.
+
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use adw::prelude::*;
+use gettextrs::gettext;
+use gtk::{gio, glib, glib::clone};
+use thiserror::Error;
+use tracing::info;
+
+const VERSIONS_FILE: &str = "versions.ini";
+const VERSIONS_GROUP: &str = "versions";
+const VERSION_KEY: &str = "data";
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("Failed to access versions file: {0}")]
+    Versions(glib::Error),
+    #[error("Migration \"{description}\" to version {version} failed: {reason}")]
+    Migration {
+        version: u32,
+        description: &'static str,
+        reason: String,
+    },
+}
+
+pub struct Migration {
+    /// The version the data directory is at after running this migration.
+    pub version: u32,
+    pub description: &'static str,
+    pub run: fn(&Path) -> Result<(), String>,
+}
+
+/// All known migrations, they have to be sorted by version.
+const MIGRATIONS: &[Migration] = &[];
+
+pub struct Migrator {
+    data_dir: PathBuf,
+    versions: glib::KeyFile,
+}
+
+impl Migrator {
+    pub fn new(data_dir: &Path) -> Result<Self, Error> {
+        let versions = glib::KeyFile::new();
+        let path = data_dir.join(VERSIONS_FILE);
+        if path.exists() {
+            versions
+                .load_from_file(&path, glib::KeyFileFlags::NONE)
+                .map_err(Error::Versions)?;
+        }
+
+        Ok(Self {
+            data_dir: data_dir.to_owned(),
+            versions,
+        })
+    }
+
+    pub fn version(&self) -> u32 {
+        self.versions
+            .uint64(VERSIONS_GROUP, VERSION_KEY)
+            .unwrap_or(0) as u32
+    }
+
+    fn set_version(&self, version: u32) -> Result<(), Error> {
+        self.versions
+            .set_uint64(VERSIONS_GROUP, VERSION_KEY, version as u64);
+        self.versions
+            .save_to_file(self.data_dir.join(VERSIONS_FILE))
+            .map_err(Error::Versions)
+    }
+
+    pub fn pending(&self) -> Vec<&'static Migration> {
+        MIGRATIONS
+            .iter()
+            .filter(|migration| migration.version > self.version())
+            .collect()
+    }
+
+    /// Run all pending migrations, `progress` is called before every step with the fraction of
+    /// completed migrations and the description of the next one.
+    pub fn run(&self, progress: impl Fn(f64, &str)) -> Result<(), Error> {
+        let pending = self.pending();
+        let total = pending.len() as f64;
+
+        for (index, migration) in pending.into_iter().enumerate() {
+            progress(index as f64 / total, migration.description);
+            info!(
+                "Migrating to version {}: {}",
+                migration.version, migration.description
+            );
+
+            (migration.run)(&self.data_dir).map_err(|reason| Error::Migration {
+                version: migration.version,
+                description: migration.description,
+                reason,
+            })?;
+
+            self.set_version(migration.version)?;
+        }
+
+        progress(1.0, "");
+
+        Ok(())
+    }
+}
+
+/// Run pending migrations for the data directory while showing a progress window.
+///
+/// The migrations run in a thread, the progress window is only shown when there is actually
+/// something to do.
+pub async fn run_with_progress(
+    application: &gtk::Application,
+    data_dir: &Path,
+) -> Result<(), Error> {
+    if Migrator::new(data_dir)?.pending().is_empty() {
+        return Ok(());
+    }
+
+    let progress_bar = gtk::ProgressBar::builder()
+        .show_text(true)
+        .margin_start(24)
+        .margin_end(24)
+        .build();
+    let status_page = adw::StatusPage::builder()
+        .icon_name("emblem-synchronizing-symbolic")
+        .title(gettext("Updating Documents"))
+        .description(gettext(
+            "This can take a moment, please don’t close Aardvark",
+        ))
+        .child(&progress_bar)
+        .build();
+    let window = adw::Window::builder()
+        .application(application)
+        .title("Aardvark")
+        .default_width(400)
+        .default_height(300)
+        .deletable(false)
+        .content(&status_page)
+        .build();
+    window.present();
+
+    let progress = Arc::new(Mutex::new((0.0, String::new())));
+    let update_source = glib::timeout_add_local(
+        Duration::from_millis(100),
+        clone!(
+            #[strong]
+            progress,
+            move || {
+                let (fraction, description) = progress.lock().unwrap().clone();
+                progress_bar.set_fraction(fraction);
+                progress_bar.set_text(Some(&description));
+                glib::ControlFlow::Continue
+            }
+        ),
+    );
+
+    let data_dir = data_dir.to_owned();
+    let result = gio::spawn_blocking(move || {
+        Migrator::new(&data_dir)?.run(|fraction, description| {
+            *progress.lock().unwrap() = (fraction, description.to_string());
+        })
+    })
+    .await;
+
+    update_source.remove();
+    window.destroy();
+
+    result.expect("Migration thread panicked")
+}
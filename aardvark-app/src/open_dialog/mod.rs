@@ -16,17 +16,24 @@
  * SPDX-License-Identifier: GPL-3.0-or-later
  */
 
+use std::cell::RefCell;
 use std::str::FromStr;
+use std::time::Duration;
 
+use adw::prelude::ActionRowExt;
 use adw::subclass::prelude::*;
 use gettextrs::gettext;
 use gtk::prelude::*;
 use gtk::{glib, glib::clone, glib::closure_local};
 
 use aardvark_doc::document::DocumentId;
+use aardvark_doc::service::NearbyDocument;
 
 use crate::AardvarkApplication;
 
+/// How often the documents advertised by nearby peers are updated while the dialog is shown.
+const NEARBY_REFRESH_INTERVAL: Duration = Duration::from_secs(5);
+
 mod imp {
     use super::*;
     use adw::prelude::AdwDialogExt;
@@ -39,7 +46,14 @@ mod imp {
         #[template_child]
         pub open_document_button: TemplateChild<gtk::Button>,
         #[template_child]
+        join_anonymously_button: TemplateChild<gtk::Button>,
+        #[template_child]
         pub open_document_entry: TemplateChild<gtk::TextView>,
+        #[template_child]
+        nearby_stack: TemplateChild<gtk::Stack>,
+        #[template_child]
+        nearby_list_box: TemplateChild<gtk::ListBox>,
+        refresh_source: RefCell<Option<glib::SourceId>>,
     }
 
     #[glib::object_subclass]
@@ -77,25 +91,27 @@ mod imp {
                 #[weak(rename_to = this)]
                 self,
                 move |_| {
-                    let open_document_buffer = this.open_document_entry.buffer();
-                    let document_id = DocumentId::from_str(
-                        &open_document_buffer
-                            .text(
-                                &open_document_buffer.start_iter(),
-                                &open_document_buffer.end_iter(),
-                                false,
-                            )
-                            .chars()
-                            .filter(|c| c.is_digit(16))
-                            .collect::<String>(),
-                    )
-                    .expect("valid document id");
+                    let document_id = this.entered_document_id().expect("valid document id");
 
                     this.obj().emit_by_name::<()>("open", &[&document_id]);
                     this.obj().close();
                 }
             ));
 
+            // Sessions which are anonymous already open documents anonymously
+            let app = AardvarkApplication::default();
+            self.join_anonymously_button.set_visible(!app.anonymous());
+            self.join_anonymously_button.connect_clicked(clone!(
+                #[weak(rename_to = this)]
+                self,
+                move |_| {
+                    let document_id = this.entered_document_id().expect("valid document id");
+
+                    AardvarkApplication::default().join_anonymously(&document_id);
+                    this.obj().close();
+                }
+            ));
+
             self.open_document_entry.buffer().connect_changed(clone!(
                 #[weak(rename_to = this)]
                 self,
@@ -185,7 +201,106 @@ mod imp {
         }
     }
 
-    impl WidgetImpl for OpenDialog {}
+    impl OpenDialog {
+        fn entered_document_id(&self) -> Option<DocumentId> {
+            let buffer = self.open_document_entry.buffer();
+            let input: String = buffer
+                .text(&buffer.start_iter(), &buffer.end_iter(), false)
+                .chars()
+                .filter(|c| c.is_digit(16))
+                .collect();
+            DocumentId::from_str(&input).ok()
+        }
+
+        /// Show the documents nearby peers currently advertise.
+        async fn refresh_nearby(&self) {
+            let service = AardvarkApplication::default().service();
+            let documents = service.nearby_documents().await;
+
+            self.nearby_list_box.remove_all();
+            for document in &documents {
+                self.nearby_list_box.append(&self.nearby_row(document));
+            }
+            self.nearby_stack
+                .set_visible_child_name(if documents.is_empty() {
+                    "empty"
+                } else {
+                    "documents"
+                });
+        }
+
+        fn nearby_row(&self, document: &NearbyDocument) -> adw::ActionRow {
+            let shared_by = document
+                .display_name
+                .clone()
+                .unwrap_or_else(|| document.public_key.to_string()[..8].to_owned());
+            let row = adw::ActionRow::builder()
+                .title(
+                    document
+                        .name
+                        .clone()
+                        .unwrap_or_else(|| gettext("Untitled Document")),
+                )
+                .subtitle(gettext("Shared by {}").replace("{}", &shared_by))
+                .build();
+
+            let join_button = gtk::Button::builder()
+                .label(gettext("Join"))
+                .valign(gtk::Align::Center)
+                .build();
+            let document_id = document.id.clone();
+            join_button.connect_clicked(clone!(
+                #[weak(rename_to = this)]
+                self,
+                move |_| {
+                    this.obj().emit_by_name::<()>("open", &[&document_id]);
+                    this.obj().close();
+                }
+            ));
+            row.add_suffix(&join_button);
+            row.set_activatable_widget(Some(&join_button));
+
+            row
+        }
+    }
+
+    impl WidgetImpl for OpenDialog {
+        fn map(&self) {
+            self.parent_map();
+
+            glib::spawn_future_local(clone!(
+                #[weak(rename_to = this)]
+                self,
+                async move {
+                    this.refresh_nearby().await;
+                }
+            ));
+            let source = glib::timeout_add_local(
+                NEARBY_REFRESH_INTERVAL,
+                clone!(
+                    #[weak(rename_to = this)]
+                    self,
+                    #[upgrade_or]
+                    glib::ControlFlow::Break,
+                    move || {
+                        glib::spawn_future_local(async move {
+                            this.refresh_nearby().await;
+                        });
+                        glib::ControlFlow::Continue
+                    }
+                ),
+            );
+            self.refresh_source.replace(Some(source));
+        }
+
+        fn unmap(&self) {
+            if let Some(source) = self.refresh_source.take() {
+                source.remove();
+            }
+
+            self.parent_unmap();
+        }
+    }
     impl DialogImpl for OpenDialog {}
     impl WindowImpl for OpenDialog {}
     impl AdwDialogImpl for OpenDialog {}
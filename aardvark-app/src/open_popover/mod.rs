@@ -16,10 +16,11 @@
  * SPDX-License-Identifier: GPL-3.0-or-later
  */
 
+use adw::prelude::*;
 use adw::subclass::prelude::*;
-use gettextrs::gettext;
-use gtk::prelude::*;
-use gtk::{glib, glib::clone, glib::closure_local};
+use gettextrs::{gettext, ngettext};
+use gtk::{gdk, glib, glib::clone, glib::closure, glib::closure_local};
+use tracing::error;
 
 use crate::system_settings::ClockFormat;
 use crate::{AardvarkApplication, AardvarkWindow, open_dialog::OpenDialog};
@@ -27,7 +28,6 @@ use aardvark_doc::{document::Document, documents::Documents};
 
 mod imp {
     use super::*;
-    use adw::prelude::AdwDialogExt;
     use glib::subclass::Signal;
     use std::sync::LazyLock;
 
@@ -40,6 +40,16 @@ mod imp {
         #[template_child]
         listbox: TemplateChild<gtk::ListBox>,
         #[template_child]
+        archived_label: TemplateChild<gtk::Label>,
+        #[template_child]
+        archived_listbox: TemplateChild<gtk::ListBox>,
+        #[template_child]
+        trash_header: TemplateChild<gtk::Widget>,
+        #[template_child]
+        trash_listbox: TemplateChild<gtk::ListBox>,
+        #[template_child]
+        empty_trash_button: TemplateChild<gtk::Button>,
+        #[template_child]
         stack: TemplateChild<gtk::Stack>,
         #[template_child]
         no_results_page: TemplateChild<gtk::Widget>,
@@ -49,6 +59,10 @@ mod imp {
         open_document_button: TemplateChild<gtk::Button>,
         #[property(get = Self::model, set = Self::set_model, type = Option<Documents>)]
         model: gtk::FilterListModel,
+        /// The archived documents of `model`, listed in their own section.
+        archived_model: gtk::FilterListModel,
+        /// The documents of `model` in the trash.
+        trash_model: gtk::FilterListModel,
     }
 
     #[glib::object_subclass]
@@ -93,7 +107,22 @@ mod imp {
                 .ignore_case(true)
                 .match_mode(gtk::StringFilterMatchMode::Substring)
                 .build();
-            self.model.set_filter(Some(&filter));
+            let model_filter = gtk::EveryFilter::new();
+            model_filter.append(filter.clone());
+            model_filter.append(archived_filter(true));
+            model_filter.append(trashed_filter(true));
+            self.model.set_filter(Some(&model_filter));
+
+            let archived_model_filter = gtk::EveryFilter::new();
+            archived_model_filter.append(filter.clone());
+            archived_model_filter.append(archived_filter(false));
+            archived_model_filter.append(trashed_filter(true));
+            self.archived_model.set_filter(Some(&archived_model_filter));
+
+            let trash_model_filter = gtk::EveryFilter::new();
+            trash_model_filter.append(filter.clone());
+            trash_model_filter.append(trashed_filter(false));
+            self.trash_model.set_filter(Some(&trash_model_filter));
 
             self.search_entry
                 .connect_search_changed(move |search_entry| {
@@ -103,12 +132,22 @@ mod imp {
             self.model.connect_items_changed(clone!(
                 #[weak(rename_to = this)]
                 self,
-                move |model, _, _, _| {
-                    if model.n_items() > 0 {
-                        this.stack.set_visible_child(&*this.document_list_page);
-                    } else {
-                        this.stack.set_visible_child(&*this.no_results_page);
-                    }
+                move |_, _, _, _| {
+                    this.update_visible_page();
+                }
+            ));
+            self.archived_model.connect_items_changed(clone!(
+                #[weak(rename_to = this)]
+                self,
+                move |_, _, _, _| {
+                    this.update_visible_page();
+                }
+            ));
+            self.trash_model.connect_items_changed(clone!(
+                #[weak(rename_to = this)]
+                self,
+                move |_, _, _, _| {
+                    this.update_visible_page();
                 }
             ));
 
@@ -129,43 +168,92 @@ mod imp {
                 }
             ));
 
+            self.archived_listbox.connect_row_activated(clone!(
+                #[weak(rename_to = this)]
+                self,
+                move |_, row| {
+                    let document: Document = this
+                        .archived_model
+                        .item(row.index() as u32)
+                        .unwrap()
+                        .downcast()
+                        .unwrap();
+                    // Opening an archived document restores it
+                    document.set_archived(false);
+                    this.obj()
+                        .emit_by_name::<()>("document-activated", &[&document]);
+                    this.search_entry.set_text("");
+                    this.obj().popdown();
+                }
+            ));
+
+            self.trash_listbox.connect_row_activated(clone!(
+                #[weak(rename_to = this)]
+                self,
+                move |_, row| {
+                    let document: Document = this
+                        .trash_model
+                        .item(row.index() as u32)
+                        .unwrap()
+                        .downcast()
+                        .unwrap();
+                    // Opening a document in the trash restores it
+                    document.set_trashed(false);
+                    this.obj()
+                        .emit_by_name::<()>("document-activated", &[&document]);
+                    this.search_entry.set_text("");
+                    this.obj().popdown();
+                }
+            ));
+
             self.listbox.bind_model(Some(&self.model), |document| {
+                let row = create_row(document);
                 let document = document.downcast_ref::<Document>().unwrap();
-                let row = adw::ActionRow::builder()
-                    .selectable(false)
-                    .activatable(true)
-                    .build();
-
-                document
-                    .bind_property("name", &row, "title")
-                    .sync_create()
-                    .transform_to(|_, title: Option<String>| {
-                        if let Some(title) = title {
-                            Some(title)
-                        } else {
-                            Some(gettext("Empty document"))
-                        }
-                    })
-                    .build();
-
-                document
-                    .bind_property("last-accessed", &row, "subtitle")
-                    .sync_create()
-                    .transform_to(|binding, last_accessed: Option<glib::DateTime>| {
-                        let document: Document = binding.source().unwrap().downcast().unwrap();
-                        if let Some(last_accessed) = last_accessed {
-                            Some(format_last_accessed(&last_accessed))
-                        } else if document.subscribed() {
-                            Some(gettext("Currently open"))
-                        } else {
-                            Some(gettext("Never accessed"))
-                        }
-                    })
-                    .build();
-
+                add_pin_button(&row, document);
+                add_drag_and_drop(&row, document);
                 row.upcast()
             });
 
+            self.archived_listbox
+                .bind_model(Some(&self.archived_model), |document| {
+                    let row = create_row(document);
+                    let document = document.downcast_ref::<Document>().unwrap().clone();
+                    add_restore_button(&row, move || document.set_archived(false));
+                    row.upcast()
+                });
+
+            self.trash_listbox
+                .bind_model(Some(&self.trash_model), |document| {
+                    let row = create_trash_row(document);
+                    let document = document.downcast_ref::<Document>().unwrap().clone();
+                    add_restore_button(&row, move || document.set_trashed(false));
+                    row.upcast()
+                });
+
+            self.empty_trash_button.connect_clicked(clone!(
+                #[weak(rename_to = this)]
+                self,
+                move |_| {
+                    let window = this
+                        .obj()
+                        .root()
+                        .and_then(|w| w.downcast::<AardvarkWindow>().ok())
+                        .expect("Toplevel window needs to be a AardvarkWindow");
+                    this.obj().popdown();
+
+                    glib::spawn_future_local(async move {
+                        if !confirm_empty_trash(&window).await {
+                            return;
+                        }
+                        if let Err(error) = window.service().empty_trash().await {
+                            error!("Failed to empty the trash: {error}");
+                            window
+                                .add_toast(adw::Toast::new(&gettext("Failed to empty the trash")));
+                        }
+                    });
+                }
+            ));
+
             self.open_document_button.connect_clicked(clone!(
                 #[weak(rename_to = this)]
                 self,
@@ -187,10 +275,7 @@ mod imp {
                         #[weak]
                         service,
                         move |_, document_id| {
-                            let document = service
-                                .documents()
-                                .by_id(document_id)
-                                .unwrap_or_else(|| Document::new(&service, Some(document_id)));
+                            let document = service.document(document_id);
 
                             this.obj()
                                 .emit_by_name::<()>("document-activated", &[&document]);
@@ -212,6 +297,21 @@ mod imp {
 
         fn set_model(&self, model: Option<&Documents>) {
             self.model.set_model(model);
+            self.archived_model.set_model(model);
+            self.trash_model.set_model(model);
+        }
+
+        fn update_visible_page(&self) {
+            let has_archived = self.archived_model.n_items() > 0;
+            self.archived_label.set_visible(has_archived);
+            let has_trash = self.trash_model.n_items() > 0;
+            self.trash_header.set_visible(has_trash);
+
+            if self.model.n_items() > 0 || has_archived || has_trash {
+                self.stack.set_visible_child(&*self.document_list_page);
+            } else {
+                self.stack.set_visible_child(&*self.no_results_page);
+            }
         }
     }
 
@@ -244,6 +344,168 @@ impl OpenPopover {
     }
 }
 
+/// A row showing the name of `document`.
+fn document_row(document: &Document) -> adw::ActionRow {
+    let row = adw::ActionRow::builder()
+        .selectable(false)
+        .activatable(true)
+        .build();
+
+    document
+        .bind_property("name", &row, "title")
+        .sync_create()
+        .transform_to(|_, title: Option<String>| {
+            if let Some(title) = title {
+                Some(title)
+            } else {
+                Some(gettext("Empty document"))
+            }
+        })
+        .build();
+
+    row
+}
+
+/// A row showing the name of `document` and when it was last accessed.
+fn create_row(document: &glib::Object) -> adw::ActionRow {
+    let document = document.downcast_ref::<Document>().unwrap();
+    let row = document_row(document);
+
+    document
+        .bind_property("last-accessed", &row, "subtitle")
+        .sync_create()
+        .transform_to(|binding, last_accessed: Option<glib::DateTime>| {
+            let document: Document = binding.source().unwrap().downcast().unwrap();
+            if let Some(last_accessed) = last_accessed {
+                Some(format_last_accessed(&last_accessed))
+            } else if document.subscribed() {
+                Some(gettext("Currently open"))
+            } else {
+                Some(gettext("Never accessed"))
+            }
+        })
+        .build();
+
+    row
+}
+
+/// A row showing the name of `document` in the trash and when it's deleted.
+fn create_trash_row(document: &glib::Object) -> adw::ActionRow {
+    let document = document.downcast_ref::<Document>().unwrap();
+    let row = document_row(document);
+
+    if let Some(deletion_date) = document.deletion_date() {
+        let now = glib::DateTime::now_utc().unwrap();
+        let days = deletion_date.difference(&now).as_days().max(0);
+        let subtitle = if days == 0 {
+            gettext("Deleted today")
+        } else {
+            ngettext("Deleted in {} day", "Deleted in {} days", days as u32)
+                .replace("{}", &days.to_string())
+        };
+        row.set_subtitle(&subtitle);
+    }
+
+    row
+}
+
+fn add_restore_button(row: &adw::ActionRow, restore: impl Fn() + 'static) {
+    let restore_button = gtk::Button::builder()
+        .icon_name("edit-undo-symbolic")
+        .tooltip_text(gettext("Restore Document"))
+        .valign(gtk::Align::Center)
+        .css_classes(["flat"])
+        .build();
+    restore_button.connect_clicked(move |_| restore());
+    row.add_suffix(&restore_button);
+}
+
+fn add_pin_button(row: &adw::ActionRow, document: &Document) {
+    let pin_button = gtk::ToggleButton::builder()
+        .icon_name("view-pin-symbolic")
+        .tooltip_text(gettext("Pin to Top"))
+        .valign(gtk::Align::Center)
+        .css_classes(["flat"])
+        .build();
+    document
+        .bind_property("pinned", &pin_button, "active")
+        .sync_create()
+        .bidirectional()
+        .build();
+    row.add_suffix(&pin_button);
+}
+
+/// Let the user reorder the document list by dragging `row` onto another row.
+fn add_drag_and_drop(row: &adw::ActionRow, document: &Document) {
+    let drag_source = gtk::DragSource::builder()
+        .actions(gdk::DragAction::MOVE)
+        .content(&gdk::ContentProvider::for_value(&document.to_value()))
+        .build();
+    drag_source.connect_drag_begin(|drag_source, _| {
+        if let Some(row) = drag_source.widget() {
+            let icon = gtk::WidgetPaintable::new(Some(&row));
+            drag_source.set_icon(Some(&icon), 0, 0);
+        }
+    });
+    row.add_controller(drag_source);
+
+    let drop_target = gtk::DropTarget::new(Document::static_type(), gdk::DragAction::MOVE);
+    let target = document.clone();
+    drop_target.connect_drop(move |_, value, _, _| {
+        let Ok(document) = value.get::<Document>() else {
+            return false;
+        };
+        target
+            .service()
+            .documents()
+            .move_document(&document, &target);
+        true
+    });
+    row.add_controller(drop_target);
+}
+
+/// Filter archived documents, or documents which aren't archived if `invert` is set.
+fn archived_filter(invert: bool) -> gtk::BoolFilter {
+    let filter = gtk::BoolFilter::new(Some(gtk::PropertyExpression::new(
+        Document::static_type(),
+        gtk::Expression::NONE,
+        "archived",
+    )));
+    filter.set_invert(invert);
+    filter
+}
+
+/// Filter documents in the trash, or documents which aren't if `invert` is set.
+fn trashed_filter(invert: bool) -> gtk::BoolFilter {
+    let trashed_at =
+        gtk::PropertyExpression::new(Document::static_type(), gtk::Expression::NONE, "trashed-at");
+    let filter = gtk::BoolFilter::new(Some(gtk::ClosureExpression::new::<bool>(
+        [trashed_at],
+        closure!(
+            |_: Option<glib::Object>, trashed_at: Option<glib::DateTime>| trashed_at.is_some()
+        ),
+    )));
+    filter.set_invert(invert);
+    filter
+}
+
+/// Ask whether all documents in the trash should be deleted right away.
+async fn confirm_empty_trash(window: &AardvarkWindow) -> bool {
+    let dialog = adw::AlertDialog::builder()
+        .heading(gettext("Empty Trash?"))
+        .body(gettext(
+            "All documents in the trash are permanently deleted from this device",
+        ))
+        .close_response("cancel")
+        .default_response("cancel")
+        .build();
+    dialog.add_response("cancel", &gettext("_Cancel"));
+    dialog.add_response("empty", &gettext("_Empty Trash"));
+    dialog.set_response_appearance("empty", adw::ResponseAppearance::Destructive);
+
+    dialog.choose_future(window).await == "empty"
+}
+
 // This was copied from Fractal
 // See: https://gitlab.gnome.org/World/fractal/-/blob/main/src/session/model/user_sessions_list/user_session.rs#L258
 fn format_last_accessed(datetime: &glib::DateTime) -> String {
@@ -0,0 +1,205 @@
+/* Copyright 2025 The Aardvark Developers
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ *
+ * SPDX-License-Identifier: GPL-3.0-or-later
+ */
+
+use std::cell::{OnceCell, RefCell};
+
+use adw::prelude::*;
+use adw::subclass::prelude::*;
+use gettextrs::{gettext, ngettext};
+use gtk::{glib, glib::clone};
+use tracing::error;
+
+use aardvark_doc::{document::Document, polls::Poll};
+
+mod imp {
+    use super::*;
+
+    #[derive(Debug, Default, glib::Properties)]
+    #[properties(wrapper_type = super::PollWidget)]
+    pub struct PollWidget {
+        question_label: gtk::Label,
+        options_box: gtk::ListBox,
+        withdraw_button: gtk::Button,
+        #[property(get, construct_only)]
+        document: OnceCell<Document>,
+        #[property(get, construct_only)]
+        poll_id: OnceCell<String>,
+        polls_handler: RefCell<Option<glib::SignalHandlerId>>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for PollWidget {
+        const NAME: &'static str = "AardvarkPollWidget";
+        type Type = super::PollWidget;
+        type ParentType = gtk::Box;
+    }
+
+    #[glib::derived_properties]
+    impl ObjectImpl for PollWidget {
+        fn constructed(&self) {
+            self.parent_constructed();
+            let obj = self.obj();
+
+            obj.set_orientation(gtk::Orientation::Vertical);
+            obj.set_spacing(12);
+            obj.add_css_class("poll");
+
+            self.question_label.set_wrap(true);
+            self.question_label.set_xalign(0.0);
+            self.question_label.add_css_class("heading");
+            self.options_box
+                .set_selection_mode(gtk::SelectionMode::None);
+            self.options_box.add_css_class("boxed-list");
+            self.withdraw_button.set_label(&gettext("_Withdraw Vote"));
+            self.withdraw_button.set_use_underline(true);
+            self.withdraw_button.set_halign(gtk::Align::End);
+            self.withdraw_button.add_css_class("flat");
+            self.withdraw_button.connect_clicked(clone!(
+                #[weak(rename_to = this)]
+                self,
+                move |_| {
+                    let document = this.obj().document();
+                    let Some(poll) = document.poll(&this.obj().poll_id()) else {
+                        return;
+                    };
+                    if let Err(error) = document.vote(&poll, None) {
+                        error!("Failed to withdraw vote: {}", error);
+                    }
+                }
+            ));
+            obj.append(&self.question_label);
+            obj.append(&self.options_box);
+            obj.append(&self.withdraw_button);
+
+            // Results update live while other authors vote
+            let handler = obj.document().connect_polls_changed(clone!(
+                #[weak(rename_to = this)]
+                self,
+                move |_| this.update()
+            ));
+            self.polls_handler.replace(Some(handler));
+
+            self.update();
+        }
+
+        fn dispose(&self) {
+            if let Some(handler) = self.polls_handler.take() {
+                self.obj().document().disconnect(handler);
+            }
+        }
+    }
+
+    impl PollWidget {
+        fn update(&self) {
+            self.options_box.remove_all();
+
+            let document = self.obj().document();
+            let Some(poll) = document.poll(&self.obj().poll_id()) else {
+                return;
+            };
+            self.question_label.set_label(&poll.question);
+
+            let own_vote = poll.vote_of(&document.service().private_key().public_key());
+            self.withdraw_button.set_sensitive(own_vote.is_some());
+            let results = poll.results();
+            let total = poll.votes.len().max(1);
+            let mut group: Option<gtk::CheckButton> = None;
+            for (index, (option, votes)) in poll.options.iter().zip(results).enumerate() {
+                let (check_button, level_bar, count_label) =
+                    option_row(&document, &poll, index, own_vote, group.as_ref());
+                level_bar.set_value(votes as f64 / total as f64);
+                count_label.set_label(
+                    &ngettext("{} vote", "{} votes", votes as u32)
+                        .replace("{}", &votes.to_string()),
+                );
+
+                let row = adw::ActionRow::builder()
+                    .title(glib::markup_escape_text(option))
+                    .activatable_widget(&check_button)
+                    .build();
+                row.add_prefix(&check_button);
+                row.add_suffix(&count_label);
+                let content = gtk::Box::new(gtk::Orientation::Vertical, 0);
+                content.append(&row);
+                content.append(&level_bar);
+                self.options_box.append(&content);
+
+                group.get_or_insert(check_button);
+            }
+        }
+    }
+
+    impl WidgetImpl for PollWidget {}
+    impl BoxImpl for PollWidget {}
+}
+
+glib::wrapper! {
+    /// Question, options and live results of a poll, where the user can cast their vote.
+    pub struct PollWidget(ObjectSubclass<imp::PollWidget>)
+        @extends gtk::Widget, gtk::Box;
+}
+
+impl PollWidget {
+    pub fn new(document: &Document, poll_id: &str) -> Self {
+        glib::Object::builder()
+            .property("document", document)
+            .property("poll-id", poll_id)
+            .build()
+    }
+}
+
+/// Radio button to vote for an option, and the widgets showing its result.
+fn option_row(
+    document: &Document,
+    poll: &Poll,
+    index: usize,
+    own_vote: Option<usize>,
+    group: Option<&gtk::CheckButton>,
+) -> (gtk::CheckButton, gtk::LevelBar, gtk::Label) {
+    let check_button = gtk::CheckButton::builder()
+        .active(own_vote == Some(index))
+        .valign(gtk::Align::Center)
+        .build();
+    check_button.set_group(group);
+    check_button.connect_toggled(clone!(
+        #[weak]
+        document,
+        #[strong]
+        poll,
+        move |check_button| {
+            if !check_button.is_active() {
+                return;
+            }
+            if let Err(error) = document.vote(&poll, Some(index)) {
+                error!("Failed to vote: {}", error);
+            }
+        }
+    ));
+
+    let level_bar = gtk::LevelBar::builder()
+        .mode(gtk::LevelBarMode::Continuous)
+        .margin_start(12)
+        .margin_end(12)
+        .margin_bottom(6)
+        .build();
+    let count_label = gtk::Label::new(None);
+    count_label.add_css_class("dim-label");
+    count_label.add_css_class("numeric");
+
+    (check_button, level_bar, count_label)
+}
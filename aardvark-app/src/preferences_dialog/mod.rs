@@ -0,0 +1,1058 @@
+/* Copyright 2025 The Aardvark Developers
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ *
+ * SPDX-License-Identifier: GPL-3.0-or-later
+ */
+
+use std::cell::RefCell;
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::pin::pin;
+use std::rc::Rc;
+use std::str::FromStr;
+
+use adw::prelude::*;
+use adw::subclass::prelude::*;
+use futures_util::future::{Either, select};
+use gettextrs::{gettext, ngettext};
+use gtk::{gdk, gio, glib, glib::clone};
+use tracing::{error, warn};
+
+use aardvark_doc::identity::KeyfileError;
+use aardvark_doc::service::PairingCode;
+
+use crate::AardvarkApplication;
+use crate::file_transfer::FileTransfer;
+use crate::identity_manager::{self, BACKUP_EXTENSION, IdentityManager, KEYFILE_EXTENSION};
+use crate::shortcuts::{self, SHORTCUTS, Shortcut};
+
+/// Close response of the capture dialog once the user pressed a new shortcut.
+const CAPTURED_RESPONSE: &str = "captured";
+
+mod imp {
+    use super::*;
+
+    #[derive(Debug, Default, gtk::CompositeTemplate)]
+    #[template(resource = "/org/p2panda/aardvark/preferences_dialog/preferences_dialog.ui")]
+    pub struct PreferencesDialog {
+        #[template_child]
+        public_key_row: TemplateChild<adw::ActionRow>,
+        #[template_child]
+        copy_public_key_button: TemplateChild<gtk::Button>,
+        #[template_child]
+        display_name_row: TemplateChild<adw::EntryRow>,
+        #[template_child]
+        share_presence_row: TemplateChild<adw::SwitchRow>,
+        #[template_child]
+        export_identity_row: TemplateChild<adw::ButtonRow>,
+        #[template_child]
+        import_identity_row: TemplateChild<adw::ButtonRow>,
+        #[template_child]
+        link_device_row: TemplateChild<adw::ButtonRow>,
+        #[template_child]
+        join_device_row: TemplateChild<adw::ButtonRow>,
+        #[template_child]
+        export_backup_row: TemplateChild<adw::ButtonRow>,
+        #[template_child]
+        restore_backup_row: TemplateChild<adw::ButtonRow>,
+        #[template_child]
+        troubleshooting_group: TemplateChild<adw::PreferencesGroup>,
+        #[template_child]
+        repair_database_row: TemplateChild<adw::ButtonRow>,
+        #[template_child]
+        connections_group: TemplateChild<adw::PreferencesGroup>,
+        #[template_child]
+        ipv4_row: TemplateChild<adw::ExpanderRow>,
+        #[template_child]
+        bind_address_v4_row: TemplateChild<adw::EntryRow>,
+        #[template_child]
+        ipv6_row: TemplateChild<adw::ExpanderRow>,
+        #[template_child]
+        bind_address_v6_row: TemplateChild<adw::EntryRow>,
+        #[template_child]
+        port_row: TemplateChild<adw::SpinRow>,
+        #[template_child]
+        proxy_row: TemplateChild<adw::EntryRow>,
+        #[template_child]
+        background_row: TemplateChild<adw::SwitchRow>,
+        #[template_child]
+        upload_limit_row: TemplateChild<adw::SpinRow>,
+        #[template_child]
+        download_limit_row: TemplateChild<adw::SpinRow>,
+        #[template_child]
+        peer_upload_limit_row: TemplateChild<adw::SpinRow>,
+        #[template_child]
+        peer_download_limit_row: TemplateChild<adw::SpinRow>,
+        #[template_child]
+        shortcuts_group: TemplateChild<adw::PreferencesGroup>,
+        #[template_child]
+        reset_all_button: TemplateChild<gtk::Button>,
+        /// Shortcut label and reset button of every row, in the order of `SHORTCUTS`.
+        shortcut_rows: RefCell<Vec<(gtk::ShortcutLabel, gtk::Button)>>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for PreferencesDialog {
+        const NAME: &'static str = "AardvarkPreferencesDialog";
+        type Type = super::PreferencesDialog;
+        type ParentType = adw::PreferencesDialog;
+
+        fn class_init(klass: &mut Self::Class) {
+            klass.bind_template();
+        }
+
+        fn instance_init(obj: &glib::subclass::InitializingObject<Self>) {
+            obj.init_template();
+        }
+    }
+
+    impl ObjectImpl for PreferencesDialog {
+        fn constructed(&self) {
+            self.parent_constructed();
+
+            self.setup_identity();
+            self.setup_backup();
+            self.setup_troubleshooting();
+            self.setup_network();
+
+            for shortcut in SHORTCUTS {
+                self.add_shortcut_row(shortcut);
+            }
+
+            self.reset_all_button.connect_clicked(clone!(
+                #[weak(rename_to = this)]
+                self,
+                move |_| {
+                    shortcuts::reset_all();
+                    this.update_shortcuts();
+                }
+            ));
+
+            self.update_shortcuts();
+        }
+    }
+
+    impl PreferencesDialog {
+        fn identity_manager(&self) -> IdentityManager {
+            IdentityManager::new(&AardvarkApplication::default().service())
+        }
+
+        fn setup_identity(&self) {
+            let public_key = self.identity_manager().public_key().to_string();
+            self.public_key_row.set_subtitle(&public_key);
+            self.copy_public_key_button.connect_clicked(clone!(
+                #[weak(rename_to = this)]
+                self,
+                move |button| {
+                    button.clipboard().set_text(&public_key);
+                    this.obj()
+                        .add_toast(adw::Toast::new(&gettext("Public key copied to clipboard")));
+                }
+            ));
+
+            let settings = AardvarkApplication::default().settings();
+            settings
+                .bind_property("display-name", &*self.display_name_row, "text")
+                .sync_create()
+                .bidirectional()
+                .build();
+            settings
+                .bind_property("share-presence", &*self.share_presence_row, "active")
+                .sync_create()
+                .bidirectional()
+                .build();
+
+            self.export_identity_row.connect_activated(clone!(
+                #[weak(rename_to = this)]
+                self,
+                move |_| {
+                    glib::spawn_future_local(async move {
+                        this.export_identity().await;
+                    });
+                }
+            ));
+            self.import_identity_row.connect_activated(clone!(
+                #[weak(rename_to = this)]
+                self,
+                move |_| {
+                    glib::spawn_future_local(async move {
+                        this.import_identity().await;
+                    });
+                }
+            ));
+            self.link_device_row.connect_activated(clone!(
+                #[weak(rename_to = this)]
+                self,
+                move |_| {
+                    glib::spawn_future_local(async move {
+                        this.link_device().await;
+                    });
+                }
+            ));
+            self.join_device_row.connect_activated(clone!(
+                #[weak(rename_to = this)]
+                self,
+                move |_| {
+                    glib::spawn_future_local(async move {
+                        this.join_device().await;
+                    });
+                }
+            ));
+
+            // The throwaway identity of an anonymous session isn't worth keeping, and replacing
+            // the regular identity from it would be confusing
+            if self.identity_manager().is_ephemeral() {
+                for row in [
+                    &*self.export_identity_row,
+                    &*self.import_identity_row,
+                    &*self.link_device_row,
+                    &*self.join_device_row,
+                    &*self.export_backup_row,
+                    &*self.restore_backup_row,
+                ] {
+                    row.set_sensitive(false);
+                }
+            }
+        }
+
+        fn setup_backup(&self) {
+            self.export_backup_row.connect_activated(clone!(
+                #[weak(rename_to = this)]
+                self,
+                move |_| {
+                    glib::spawn_future_local(async move {
+                        this.export_backup().await;
+                    });
+                }
+            ));
+            self.restore_backup_row.connect_activated(clone!(
+                #[weak(rename_to = this)]
+                self,
+                move |_| {
+                    glib::spawn_future_local(async move {
+                        this.restore_backup().await;
+                    });
+                }
+            ));
+        }
+
+        fn setup_network(&self) {
+            let settings = AardvarkApplication::default().settings();
+            settings
+                .bind_property("network-ipv4", &*self.ipv4_row, "enable-expansion")
+                .sync_create()
+                .bidirectional()
+                .build();
+            settings
+                .bind_property("network-ipv6", &*self.ipv6_row, "enable-expansion")
+                .sync_create()
+                .bidirectional()
+                .build();
+            settings
+                .bind_property(
+                    "network-bind-address-v4",
+                    &*self.bind_address_v4_row,
+                    "text",
+                )
+                .sync_create()
+                .bidirectional()
+                .build();
+            settings
+                .bind_property(
+                    "network-bind-address-v6",
+                    &*self.bind_address_v6_row,
+                    "text",
+                )
+                .sync_create()
+                .bidirectional()
+                .build();
+            settings
+                .bind_property("network-port", &*self.port_row, "value")
+                .sync_create()
+                .bidirectional()
+                .build();
+            settings
+                .bind_property("cold-storage-proxy", &*self.proxy_row, "text")
+                .sync_create()
+                .bidirectional()
+                .build();
+            settings
+                .bind_property("run-in-background", &*self.background_row, "active")
+                .sync_create()
+                .bidirectional()
+                .build();
+            settings
+                .bind_property("bandwidth-upload-limit", &*self.upload_limit_row, "value")
+                .sync_create()
+                .bidirectional()
+                .build();
+            settings
+                .bind_property(
+                    "bandwidth-download-limit",
+                    &*self.download_limit_row,
+                    "value",
+                )
+                .sync_create()
+                .bidirectional()
+                .build();
+            settings
+                .bind_property(
+                    "bandwidth-peer-upload-limit",
+                    &*self.peer_upload_limit_row,
+                    "value",
+                )
+                .sync_create()
+                .bidirectional()
+                .build();
+            settings
+                .bind_property(
+                    "bandwidth-peer-download-limit",
+                    &*self.peer_download_limit_row,
+                    "value",
+                )
+                .sync_create()
+                .bidirectional()
+                .build();
+
+            // Where and how we listen for peers can't be changed while the node is running
+            AardvarkApplication::default()
+                .service()
+                .bind_property("restart-required", &*self.connections_group, "description")
+                .transform_to(|_, restart_required: bool| {
+                    Some(if restart_required {
+                        gettext("Changes are applied when Aardvark is started the next time")
+                    } else {
+                        String::new()
+                    })
+                })
+                .sync_create()
+                .build();
+
+            // At least one of them is needed to reach any peer
+            self.ipv4_row
+                .bind_property("enable-expansion", &*self.ipv6_row, "sensitive")
+                .sync_create()
+                .build();
+            self.ipv6_row
+                .bind_property("enable-expansion", &*self.ipv4_row, "sensitive")
+                .sync_create()
+                .build();
+
+            self.bind_address_v4_row
+                .connect_changed(update_address_error::<Ipv4Addr>);
+            self.bind_address_v6_row
+                .connect_changed(update_address_error::<Ipv6Addr>);
+            self.proxy_row.connect_changed(update_proxy_error);
+        }
+
+        fn setup_troubleshooting(&self) {
+            AardvarkApplication::default()
+                .settings()
+                .bind_property(
+                    "show-troubleshooting",
+                    &*self.troubleshooting_group,
+                    "visible",
+                )
+                .sync_create()
+                .build();
+
+            self.repair_database_row.connect_activated(clone!(
+                #[weak(rename_to = this)]
+                self,
+                move |_| {
+                    glib::spawn_future_local(async move {
+                        this.repair_database().await;
+                    });
+                }
+            ));
+        }
+
+        async fn repair_database(&self) {
+            let obj = self.obj();
+            if !confirm_repair(obj.upcast_ref()).await {
+                return;
+            }
+
+            let service = AardvarkApplication::default().service();
+            let message = match service.verify_store(true).await {
+                Ok(report) if report.corrupted_logs.is_empty() => {
+                    gettext("No corrupted data found")
+                }
+                Ok(report) => {
+                    let count = report.corrupted_logs.len();
+                    ngettext(
+                        "Removed {} corrupted log, it will be synced again",
+                        "Removed {} corrupted logs, they will be synced again",
+                        count as u32,
+                    )
+                    .replace("{}", &count.to_string())
+                }
+                Err(error) => {
+                    error!("Failed to repair database: {error}");
+                    gettext("Failed to repair database")
+                }
+            };
+            obj.add_toast(adw::Toast::new(&message));
+        }
+
+        /// The file chosen in a file dialog, failures to show the dialog are reported to the user.
+        fn choose_file(&self, result: Result<Option<gio::File>, glib::Error>) -> Option<gio::File> {
+            result.unwrap_or_else(|error| {
+                error!("Failed to choose file: {error}");
+                self.obj()
+                    .add_toast(adw::Toast::new(&gettext("Failed to open file chooser")));
+                None
+            })
+        }
+
+        async fn export_identity(&self) {
+            let obj = self.obj();
+            let Some(passphrase) = ask_passphrase(
+                obj.upcast_ref(),
+                &gettext("Export Identity"),
+                &gettext(
+                    "Anyone with the exported file and its passphrase can edit as you. Choose a strong passphrase.",
+                ),
+                true,
+            )
+            .await
+            else {
+                return;
+            };
+
+            let transfer = FileTransfer::new(&gettext("Export Identity"))
+                .initial_name(&format!("identity.{KEYFILE_EXTENSION}"))
+                .filters(&keyfile_filters());
+            let root = obj.root().and_downcast::<gtk::Window>();
+            let Some(file) = self.choose_file(transfer.save(root.as_ref()).await) else {
+                return;
+            };
+
+            let message = match self.identity_manager().export(&file, &passphrase).await {
+                Ok(()) => gettext("Identity exported"),
+                Err(error) => {
+                    error!("Failed to export identity: {error}");
+                    gettext("Failed to export identity")
+                }
+            };
+            obj.add_toast(adw::Toast::new(&message));
+        }
+
+        async fn import_identity(&self) {
+            let obj = self.obj();
+            let transfer =
+                FileTransfer::new(&gettext("Import Identity")).filters(&keyfile_filters());
+            let root = obj.root().and_downcast::<gtk::Window>();
+            let Some(file) = self.choose_file(transfer.open(root.as_ref()).await) else {
+                return;
+            };
+            let Some(passphrase) = ask_passphrase(
+                obj.upcast_ref(),
+                &gettext("Import Identity"),
+                &gettext("Enter the passphrase the identity was exported with"),
+                false,
+            )
+            .await
+            else {
+                return;
+            };
+
+            let identity_manager = self.identity_manager();
+            let private_key = match identity_manager.read(&file, &passphrase).await {
+                Ok(private_key) => private_key,
+                Err(identity_manager::Error::Keyfile(KeyfileError::Decryption)) => {
+                    obj.add_toast(adw::Toast::new(&gettext("Wrong passphrase")));
+                    return;
+                }
+                Err(error) => {
+                    error!("Failed to read identity: {error}");
+                    obj.add_toast(adw::Toast::new(&gettext("Not a valid identity file")));
+                    return;
+                }
+            };
+            if private_key.public_key() == identity_manager.public_key() {
+                obj.add_toast(adw::Toast::new(&gettext(
+                    "This identity is already used on this device",
+                )));
+                return;
+            }
+
+            if !confirm_import(obj.upcast_ref()).await {
+                return;
+            }
+            if let Err(error) = identity_manager.import(&private_key).await {
+                error!("Failed to import identity: {error}");
+                obj.add_toast(adw::Toast::new(&gettext("Failed to import identity")));
+                return;
+            }
+
+            // The identity is only loaded on startup
+            AardvarkApplication::default().quit();
+        }
+
+        async fn export_backup(&self) {
+            let obj = self.obj();
+            let Some(passphrase) = ask_passphrase(
+                obj.upcast_ref(),
+                &gettext("Back Up Account"),
+                &gettext(
+                    "Anyone with the backup and its passphrase can read all documents and edit as you. Choose a strong passphrase.",
+                ),
+                true,
+            )
+            .await
+            else {
+                return;
+            };
+
+            let date = glib::DateTime::now_local()
+                .and_then(|date| date.format("%Y-%m-%d"))
+                .map(|date| date.to_string())
+                .unwrap_or_default();
+            let transfer = FileTransfer::new(&gettext("Back Up Account"))
+                .initial_name(&format!("aardvark-{date}.{BACKUP_EXTENSION}"))
+                .filters(&backup_filters());
+            let root = obj.root().and_downcast::<gtk::Window>();
+            let Some(file) = self.choose_file(transfer.save(root.as_ref()).await) else {
+                return;
+            };
+
+            let message = match self
+                .identity_manager()
+                .export_backup(&file, &passphrase)
+                .await
+            {
+                Ok(()) => gettext("Backup saved"),
+                Err(error) => {
+                    error!("Failed to back up account: {error}");
+                    gettext("Failed to back up account")
+                }
+            };
+            obj.add_toast(adw::Toast::new(&message));
+        }
+
+        pub(super) async fn restore_backup(&self) {
+            let obj = self.obj();
+            let transfer = FileTransfer::new(&gettext("Restore Backup")).filters(&backup_filters());
+            let root = obj.root().and_downcast::<gtk::Window>();
+            let Some(file) = self.choose_file(transfer.open(root.as_ref()).await) else {
+                return;
+            };
+            let Some(passphrase) = ask_passphrase(
+                obj.upcast_ref(),
+                &gettext("Restore Backup"),
+                &gettext("Enter the passphrase the backup was saved with"),
+                false,
+            )
+            .await
+            else {
+                return;
+            };
+
+            let identity_manager = self.identity_manager();
+            let backup = match identity_manager.read_backup(&file, &passphrase).await {
+                Ok(backup) => backup,
+                Err(identity_manager::Error::Keyfile(KeyfileError::Decryption)) => {
+                    obj.add_toast(adw::Toast::new(&gettext("Wrong passphrase")));
+                    return;
+                }
+                Err(error) => {
+                    error!("Failed to read backup: {error}");
+                    obj.add_toast(adw::Toast::new(&gettext("Not a valid backup file")));
+                    return;
+                }
+            };
+
+            // Documents of the identity in use can be restored right away
+            if backup.private_key.public_key() == identity_manager.public_key() {
+                let service = AardvarkApplication::default().service();
+                let result = match backup.documents_to_bytes() {
+                    Ok(documents) => service.restore_documents(&documents).await,
+                    Err(error) => Err(error),
+                };
+                let message = match result {
+                    Ok(()) => gettext("Backup restored"),
+                    Err(error) => {
+                        error!("Failed to restore backup: {error}");
+                        gettext("Failed to restore backup")
+                    }
+                };
+                obj.add_toast(adw::Toast::new(&message));
+                return;
+            }
+
+            if !confirm_import(obj.upcast_ref()).await {
+                return;
+            }
+            if let Err(error) = identity_manager.restore_backup(&backup).await {
+                error!("Failed to restore backup: {error}");
+                obj.add_toast(adw::Toast::new(&gettext("Failed to restore backup")));
+                return;
+            }
+
+            // The identity is only loaded on startup
+            AardvarkApplication::default().quit();
+        }
+
+        async fn link_device(&self) {
+            let obj = self.obj();
+            let code = PairingCode::generate();
+            let code_label = gtk::Label::builder()
+                .label(code.to_string())
+                .selectable(true)
+                .css_classes(["title-1", "monospace"])
+                .build();
+            let dialog = adw::AlertDialog::builder()
+                .heading(gettext("Link Another Device"))
+                .body(gettext("On the other device, open the preferences, choose “Use Identity of Another Device” and enter this code. The code is valid for 5 minutes."))
+                .extra_child(&code_label)
+                .close_response("cancel")
+                .build();
+            dialog.add_response("cancel", &gettext("_Cancel"));
+
+            let service = AardvarkApplication::default().service();
+            let response = pin!(dialog.clone().choose_future(&*obj));
+            let linking = pin!(service.link_device(&code));
+            // Closing the dialog drops the linking future, which stops offering the identity
+            let Either::Right((result, _)) = select(response, linking).await else {
+                return;
+            };
+            dialog.force_close();
+
+            let message = match result {
+                Ok(()) => gettext("Device linked"),
+                Err(error) => {
+                    warn!("Failed to link device: {error}");
+                    gettext("Linking device failed")
+                }
+            };
+            obj.add_toast(adw::Toast::new(&message));
+        }
+
+        async fn join_device(&self) {
+            let obj = self.obj();
+            if !confirm_import(obj.upcast_ref()).await {
+                return;
+            }
+            let Some(code) = ask_pairing_code(obj.upcast_ref()).await else {
+                return;
+            };
+
+            let dialog = adw::AlertDialog::builder()
+                .heading(gettext("Waiting for Other Device…"))
+                .body(gettext(
+                    "Keep the code open on the other device until linking completes",
+                ))
+                .extra_child(&adw::Spinner::new())
+                .close_response("cancel")
+                .build();
+            dialog.add_response("cancel", &gettext("_Cancel"));
+
+            let service = AardvarkApplication::default().service();
+            let response = pin!(dialog.clone().choose_future(&*obj));
+            let linking = pin!(service.accept_device_link(&code));
+            let Either::Right((result, _)) = select(response, linking).await else {
+                return;
+            };
+            dialog.force_close();
+
+            let result = match result {
+                Ok(device_link) => self.identity_manager().link(&device_link).await,
+                Err(error) => {
+                    warn!("Failed to link with other device: {error}");
+                    obj.add_toast(adw::Toast::new(&gettext(
+                        "Linking failed, make sure both devices are on the same network",
+                    )));
+                    return;
+                }
+            };
+            if let Err(error) = result {
+                error!("Failed to use identity of linked device: {error}");
+                obj.add_toast(adw::Toast::new(&gettext("Failed to import identity")));
+                return;
+            }
+
+            // The identity is only loaded on startup
+            AardvarkApplication::default().quit();
+        }
+
+        fn add_shortcut_row(&self, shortcut: &'static Shortcut) {
+            let shortcut_label = gtk::ShortcutLabel::builder()
+                .disabled_text(gettext("Disabled"))
+                .valign(gtk::Align::Center)
+                .build();
+            let reset_button = gtk::Button::builder()
+                .icon_name("edit-undo-symbolic")
+                .tooltip_text(gettext("Reset to Default"))
+                .valign(gtk::Align::Center)
+                .css_classes(["flat"])
+                .build();
+            reset_button.connect_clicked(clone!(
+                #[weak(rename_to = this)]
+                self,
+                move |_| {
+                    shortcut.reset();
+                    this.update_shortcuts();
+                }
+            ));
+
+            let row = adw::ActionRow::builder()
+                .title(gettext(shortcut.title))
+                .activatable(true)
+                .build();
+            row.add_suffix(&shortcut_label);
+            row.add_suffix(&reset_button);
+            row.connect_activated(clone!(
+                #[weak(rename_to = this)]
+                self,
+                move |_| {
+                    glib::spawn_future_local(async move {
+                        this.edit_shortcut(shortcut).await;
+                    });
+                }
+            ));
+
+            self.shortcuts_group.add(&row);
+            self.shortcut_rows
+                .borrow_mut()
+                .push((shortcut_label, reset_button));
+        }
+
+        fn update_shortcuts(&self) {
+            let mut all_default = true;
+            for (shortcut, (shortcut_label, reset_button)) in
+                SHORTCUTS.iter().zip(self.shortcut_rows.borrow().iter())
+            {
+                // `ShortcutLabel` shows multiple accelerators when they are separated by spaces
+                shortcut_label.set_accelerator(&shortcut.accels().join(" "));
+                let is_default = shortcut.is_default();
+                reset_button.set_visible(!is_default);
+                all_default &= is_default;
+            }
+            self.reset_all_button.set_sensitive(!all_default);
+        }
+
+        async fn edit_shortcut(&self, shortcut: &'static Shortcut) {
+            let obj = self.obj();
+            let Some(accel) = capture_accel(obj.upcast_ref(), shortcut).await else {
+                return;
+            };
+
+            let Some(accel) = accel else {
+                shortcut.set_accels(&[]);
+                self.update_shortcuts();
+                return;
+            };
+
+            if let Some(other) = shortcuts::conflict(&accel, shortcut) {
+                if !confirm_replace(obj.upcast_ref(), &accel, other).await {
+                    return;
+                }
+                let key = gtk::accelerator_parse(&accel);
+                let remaining: Vec<String> = other
+                    .accels()
+                    .into_iter()
+                    .filter(|other_accel| gtk::accelerator_parse(other_accel) != key)
+                    .collect();
+                other.set_accels(&remaining);
+            }
+
+            shortcut.set_accels(&[accel]);
+            self.update_shortcuts();
+        }
+    }
+
+    impl WidgetImpl for PreferencesDialog {}
+    impl AdwDialogImpl for PreferencesDialog {}
+    impl PreferencesDialogImpl for PreferencesDialog {}
+}
+
+glib::wrapper! {
+    pub struct PreferencesDialog(ObjectSubclass<imp::PreferencesDialog>)
+        @extends gtk::Widget, adw::Dialog, adw::PreferencesDialog;
+}
+
+impl PreferencesDialog {
+    pub fn new() -> Self {
+        glib::Object::new()
+    }
+
+    /// Ask for a backup and restore it, the dialog has to be presented already.
+    pub fn restore_backup(&self) {
+        glib::spawn_future_local(clone!(
+            #[weak(rename_to = this)]
+            self,
+            async move {
+                this.imp().restore_backup().await;
+            }
+        ));
+    }
+}
+
+impl Default for PreferencesDialog {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Ask the user to press a new shortcut for an action.
+///
+/// Returns `None` when cancelled and `Some(None)` when the shortcut should be disabled.
+async fn capture_accel(parent: &gtk::Widget, shortcut: &Shortcut) -> Option<Option<String>> {
+    let dialog = adw::AlertDialog::builder()
+        .heading(gettext("Set Shortcut"))
+        .body(
+            gettext("Press the new shortcut for “{}”, or Backspace to disable it")
+                .replace("{}", &gettext(shortcut.title)),
+        )
+        .close_response("cancel")
+        .build();
+    dialog.add_response("cancel", &gettext("_Cancel"));
+    dialog.add_response("disable", &gettext("_Disable"));
+    dialog.set_response_appearance("disable", adw::ResponseAppearance::Destructive);
+
+    let captured = Rc::new(RefCell::new(None));
+    let key_controller = gtk::EventControllerKey::new();
+    key_controller.set_propagation_phase(gtk::PropagationPhase::Capture);
+    key_controller.connect_key_pressed(clone!(
+        #[weak]
+        dialog,
+        #[strong]
+        captured,
+        #[upgrade_or]
+        glib::Propagation::Proceed,
+        move |_, key, _, state| {
+            let modifiers = state & gtk::accelerator_get_default_mod_mask();
+            if modifiers.is_empty() && key == gdk::Key::Escape {
+                // The dialog handles closing itself
+                return glib::Propagation::Proceed;
+            }
+            if modifiers.is_empty() && key == gdk::Key::BackSpace {
+                dialog.set_close_response("disable");
+                dialog.close();
+                return glib::Propagation::Stop;
+            }
+
+            // Modifiers on their own aren't a shortcut, wait for the rest of it
+            let Some(accel) = shortcuts::accel_for_key_press(key, state) else {
+                return glib::Propagation::Proceed;
+            };
+            captured.replace(Some(accel));
+            dialog.set_close_response(CAPTURED_RESPONSE);
+            dialog.close();
+            glib::Propagation::Stop
+        }
+    ));
+    dialog.add_controller(key_controller);
+
+    match dialog.choose_future(parent).await.as_str() {
+        CAPTURED_RESPONSE => captured.take().map(Some),
+        "disable" => Some(None),
+        _ => None,
+    }
+}
+
+/// Ask whether corrupted data should be moved out of the store.
+async fn confirm_repair(parent: &gtk::Widget) -> bool {
+    let dialog = adw::AlertDialog::builder()
+        .heading(gettext("Repair Database?"))
+        .body(gettext(
+            "All stored documents are checked. Corrupted data is removed and synced again from other devices.",
+        ))
+        .close_response("cancel")
+        .default_response("repair")
+        .build();
+    dialog.add_response("cancel", &gettext("_Cancel"));
+    dialog.add_response("repair", &gettext("_Repair"));
+    dialog.set_response_appearance("repair", adw::ResponseAppearance::Destructive);
+
+    dialog.choose_future(parent).await == "repair"
+}
+
+/// Ask whether the shortcut should be taken away from the action already using it.
+async fn confirm_replace(parent: &gtk::Widget, accel: &str, other: &Shortcut) -> bool {
+    let label = gtk::accelerator_parse(accel)
+        .map(|(key, modifiers)| gtk::accelerator_get_label(key, modifiers).to_string())
+        .unwrap_or_else(|| accel.to_owned());
+    let dialog = adw::AlertDialog::builder()
+        .heading(gettext("Replace Shortcut?"))
+        .body(
+            gettext("“{shortcut}” is already used for “{action}”")
+                .replace("{shortcut}", &label)
+                .replace("{action}", &gettext(other.title)),
+        )
+        .close_response("cancel")
+        .default_response("replace")
+        .build();
+    dialog.add_response("cancel", &gettext("_Cancel"));
+    dialog.add_response("replace", &gettext("_Replace"));
+    dialog.set_response_appearance("replace", adw::ResponseAppearance::Destructive);
+
+    dialog.choose_future(parent).await == "replace"
+}
+
+fn backup_filters() -> gio::ListStore {
+    let filter = gtk::FileFilter::new();
+    filter.set_name(Some(&gettext("Aardvark Backup")));
+    filter.add_suffix(BACKUP_EXTENSION);
+
+    let filters = gio::ListStore::new::<gtk::FileFilter>();
+    filters.append(&filter);
+    filters
+}
+
+fn keyfile_filters() -> gio::ListStore {
+    let filter = gtk::FileFilter::new();
+    filter.set_name(Some(&gettext("Aardvark Identity")));
+    filter.add_suffix(KEYFILE_EXTENSION);
+
+    let filters = gio::ListStore::new::<gtk::FileFilter>();
+    filters.append(&filter);
+    filters
+}
+
+/// Ask for the passphrase protecting an exported identity or backup.
+///
+/// When `confirm` is set, the passphrase has to be entered twice since it's a new one.
+async fn ask_passphrase(
+    parent: &gtk::Widget,
+    heading: &str,
+    body: &str,
+    confirm: bool,
+) -> Option<String> {
+    let passphrase_row = adw::PasswordEntryRow::builder()
+        .title(gettext("Passphrase"))
+        .build();
+    let confirm_row = adw::PasswordEntryRow::builder()
+        .title(gettext("Confirm Passphrase"))
+        .visible(confirm)
+        .build();
+    let list_box = gtk::ListBox::builder()
+        .selection_mode(gtk::SelectionMode::None)
+        .css_classes(["boxed-list"])
+        .build();
+    list_box.append(&passphrase_row);
+    list_box.append(&confirm_row);
+
+    let dialog = adw::AlertDialog::builder()
+        .heading(heading)
+        .body(body)
+        .extra_child(&list_box)
+        .close_response("cancel")
+        .default_response("continue")
+        .build();
+    dialog.add_response("cancel", &gettext("_Cancel"));
+    dialog.add_response("continue", &gettext("C_ontinue"));
+    dialog.set_response_appearance("continue", adw::ResponseAppearance::Suggested);
+    dialog.set_response_enabled("continue", false);
+
+    let update = clone!(
+        #[weak]
+        dialog,
+        #[weak]
+        passphrase_row,
+        #[weak]
+        confirm_row,
+        move |_: &adw::PasswordEntryRow| {
+            let passphrase = passphrase_row.text();
+            let valid = !passphrase.is_empty() && (!confirm || confirm_row.text() == passphrase);
+            dialog.set_response_enabled("continue", valid);
+        }
+    );
+    passphrase_row.connect_changed(update.clone());
+    confirm_row.connect_changed(update);
+
+    if dialog.choose_future(parent).await != "continue" {
+        return None;
+    }
+
+    Some(passphrase_row.text().to_string())
+}
+
+/// Ask for the pairing code shown on the device to link with.
+async fn ask_pairing_code(parent: &gtk::Widget) -> Option<PairingCode> {
+    let code_row = adw::EntryRow::builder()
+        .title(gettext("Pairing Code"))
+        .build();
+    let list_box = gtk::ListBox::builder()
+        .selection_mode(gtk::SelectionMode::None)
+        .css_classes(["boxed-list"])
+        .build();
+    list_box.append(&code_row);
+
+    let dialog = adw::AlertDialog::builder()
+        .heading(gettext("Use Identity of Another Device"))
+        .body(gettext("On the other device, open the preferences and choose “Link Another Device” to show a pairing code"))
+        .extra_child(&list_box)
+        .close_response("cancel")
+        .default_response("link")
+        .build();
+    dialog.add_response("cancel", &gettext("_Cancel"));
+    dialog.add_response("link", &gettext("_Link"));
+    dialog.set_response_appearance("link", adw::ResponseAppearance::Suggested);
+    dialog.set_response_enabled("link", false);
+    code_row.connect_changed(clone!(
+        #[weak]
+        dialog,
+        move |code_row| {
+            let valid = PairingCode::from_str(&code_row.text()).is_ok();
+            dialog.set_response_enabled("link", valid);
+        }
+    ));
+
+    if dialog.choose_future(parent).await != "link" {
+        return None;
+    }
+
+    PairingCode::from_str(&code_row.text()).ok()
+}
+
+/// Ask whether the identity of this device should really be replaced.
+async fn confirm_import(parent: &gtk::Widget) -> bool {
+    let dialog = adw::AlertDialog::builder()
+        .heading(gettext("Replace Identity?"))
+        .body(gettext("The current identity is lost unless you exported it before. Documents of the current identity stay on this device but aren’t shown anymore. Aardvark quits to start using the new identity."))
+        .close_response("cancel")
+        .default_response("cancel")
+        .build();
+    dialog.add_response("cancel", &gettext("_Cancel"));
+    dialog.add_response("replace", &gettext("_Replace and Quit"));
+    dialog.set_response_appearance("replace", adw::ResponseAppearance::Destructive);
+
+    dialog.choose_future(parent).await == "replace"
+}
+
+/// Mark the proxy of `row` as invalid unless it's empty or a host with a port.
+fn update_proxy_error(row: &adw::EntryRow) {
+    let text = row.text();
+    let valid = text
+        .trim()
+        .rsplit_once(':')
+        .is_some_and(|(host, port)| !host.is_empty() && port.parse::<u16>().is_ok());
+    if text.trim().is_empty() || valid {
+        row.remove_css_class("error");
+    } else {
+        row.add_css_class("error");
+    }
+}
+
+/// Mark the text of `row` as invalid unless it's empty or an address of type `A`.
+fn update_address_error<A: FromStr>(row: &adw::EntryRow) {
+    let text = row.text();
+    if text.is_empty() || text.parse::<A>().is_ok() {
+        row.remove_css_class("error");
+    } else {
+        row.add_css_class("error");
+    }
+}
@@ -0,0 +1,240 @@
+/* Copyright 2025 The Aardvark Developers
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ *
+ * SPDX-License-Identifier: GPL-3.0-or-later
+ */
+
+use std::cell::{OnceCell, RefCell};
+use std::time::Duration;
+
+use adw::prelude::*;
+use adw::subclass::prelude::*;
+use gtk::{gdk, glib, glib::clone};
+
+use aardvark_doc::document::Document;
+use aardvark_doc::profiler::{EditLatency, PatchLatency};
+
+/// Stages of a local edit, as shown in the overlay.
+const EDIT_STAGES: [(&str, fn(&EditLatency) -> Duration); 5] = [
+    ("Buffer hook", |latency| latency.buffer_hook),
+    ("CRDT apply", |latency| latency.crdt_apply),
+    ("Delta encode", |latency| latency.delta_encode),
+    ("Channel send", |latency| latency.channel_send),
+    ("Total", EditLatency::total),
+];
+/// Stages of a remote patch, as shown in the overlay.
+const PATCH_STAGES: [(&str, fn(&PatchLatency) -> Duration); 4] = [
+    ("Receive", |latency| latency.receive),
+    ("Apply", |latency| latency.apply),
+    ("Render", |latency| latency.render),
+    ("Total", PatchLatency::total),
+];
+
+mod imp {
+    use super::*;
+
+    #[derive(Debug, Default, glib::Properties)]
+    #[properties(wrapper_type = super::ProfilerOverlay)]
+    pub struct ProfilerOverlay {
+        grid: gtk::Grid,
+        edits_header: gtk::Label,
+        patches_header: gtk::Label,
+        /// Last, average and maximum duration of every stage.
+        edit_labels: RefCell<Vec<[gtk::Label; 3]>>,
+        patch_labels: RefCell<Vec<[gtk::Label; 3]>>,
+        #[property(get, construct_only)]
+        document: OnceCell<Document>,
+        updated_handler: RefCell<Option<glib::SignalHandlerId>>,
+        remote_change_handler: RefCell<Option<glib::SignalHandlerId>>,
+        after_paint_handler: RefCell<Option<(gdk::FrameClock, glib::SignalHandlerId)>>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for ProfilerOverlay {
+        const NAME: &'static str = "AardvarkProfilerOverlay";
+        type Type = super::ProfilerOverlay;
+        type ParentType = gtk::Box;
+    }
+
+    #[glib::derived_properties]
+    impl ObjectImpl for ProfilerOverlay {
+        fn constructed(&self) {
+            self.parent_constructed();
+            let obj = self.obj();
+
+            obj.add_css_class("osd");
+            obj.add_css_class("profiler-overlay");
+            obj.set_halign(gtk::Align::End);
+            obj.set_valign(gtk::Align::End);
+            obj.set_can_target(false);
+
+            self.grid.set_column_spacing(12);
+            self.grid.set_row_spacing(3);
+            let mut row = 0;
+            let edit_labels = self.add_section(&self.edits_header, &EDIT_STAGES, &mut row);
+            let patch_labels = self.add_section(&self.patches_header, &PATCH_STAGES, &mut row);
+            self.edit_labels.replace(edit_labels);
+            self.patch_labels.replace(patch_labels);
+            obj.append(&self.grid);
+
+            let document = obj.document();
+            let handler = document.profiler().connect_updated(clone!(
+                #[weak(rename_to = this)]
+                self,
+                move |_| this.update()
+            ));
+            self.updated_handler.replace(Some(handler));
+            // Make sure a frame is drawn after remote patches, even when they don't change the
+            // visible text, so their render time is known
+            let handler = document.connect_remote_change(clone!(
+                #[weak]
+                obj,
+                move |_, _| obj.queue_draw()
+            ));
+            self.remote_change_handler.replace(Some(handler));
+
+            self.update();
+        }
+
+        fn dispose(&self) {
+            let document = self.obj().document();
+            if let Some(handler) = self.updated_handler.take() {
+                document.profiler().disconnect(handler);
+            }
+            if let Some(handler) = self.remote_change_handler.take() {
+                document.disconnect(handler);
+            }
+        }
+    }
+
+    impl ProfilerOverlay {
+        fn add_section<T>(
+            &self,
+            header: &gtk::Label,
+            stages: &[(&str, fn(&T) -> Duration)],
+            row: &mut i32,
+        ) -> Vec<[gtk::Label; 3]> {
+            header.set_xalign(0.0);
+            header.add_css_class("heading");
+            self.grid.attach(header, 0, *row, 1, 1);
+            for (column, title) in ["Last", "Average", "Max"].into_iter().enumerate() {
+                let label = gtk::Label::builder()
+                    .label(title)
+                    .xalign(1.0)
+                    .css_classes(["dim-label"])
+                    .build();
+                self.grid.attach(&label, column as i32 + 1, *row, 1, 1);
+            }
+            *row += 1;
+
+            let mut labels = Vec::new();
+            for (title, _) in stages {
+                let title = gtk::Label::builder().label(*title).xalign(0.0).build();
+                self.grid.attach(&title, 0, *row, 1, 1);
+                let values = [(); 3].map(|_| {
+                    gtk::Label::builder()
+                        .xalign(1.0)
+                        .css_classes(["numeric"])
+                        .build()
+                });
+                for (column, label) in values.iter().enumerate() {
+                    self.grid.attach(label, column as i32 + 1, *row, 1, 1);
+                }
+                labels.push(values);
+                *row += 1;
+            }
+
+            labels
+        }
+
+        fn update(&self) {
+            let profiler = self.obj().document().profiler();
+
+            let edits = profiler.edits();
+            self.edits_header
+                .set_label(&format!("Keystroke ({})", edits.len()));
+            for ((_, duration), labels) in EDIT_STAGES.iter().zip(self.edit_labels.borrow().iter())
+            {
+                show_durations(labels, edits.iter().map(duration));
+            }
+
+            let patches = profiler.patches();
+            self.patches_header
+                .set_label(&format!("Remote Patch ({})", patches.len()));
+            for ((_, duration), labels) in
+                PATCH_STAGES.iter().zip(self.patch_labels.borrow().iter())
+            {
+                show_durations(labels, patches.iter().map(duration));
+            }
+        }
+    }
+
+    // Timing is only recorded while the overlay is shown, e.g. not for documents in other tabs
+    impl WidgetImpl for ProfilerOverlay {
+        fn map(&self) {
+            self.parent_map();
+
+            let profiler = self.obj().document().profiler();
+            profiler.set_enabled(true);
+            let Some(frame_clock) = self.obj().frame_clock() else {
+                return;
+            };
+            let handler = frame_clock.connect_after_paint(move |_| profiler.frame_drawn());
+            self.after_paint_handler
+                .replace(Some((frame_clock, handler)));
+        }
+
+        fn unmap(&self) {
+            if let Some((frame_clock, handler)) = self.after_paint_handler.take() {
+                frame_clock.disconnect(handler);
+            }
+            self.obj().document().profiler().set_enabled(false);
+
+            self.parent_unmap();
+        }
+    }
+
+    impl BoxImpl for ProfilerOverlay {}
+}
+
+glib::wrapper! {
+    pub struct ProfilerOverlay(ObjectSubclass<imp::ProfilerOverlay>)
+        @extends gtk::Widget, gtk::Box;
+}
+
+impl ProfilerOverlay {
+    pub fn new(document: &Document) -> Self {
+        glib::Object::builder()
+            .property("document", document)
+            .build()
+    }
+}
+
+/// Show the last, average and maximum of `durations`, oldest first.
+fn show_durations(labels: &[gtk::Label; 3], durations: impl Iterator<Item = Duration>) {
+    let durations: Vec<Duration> = durations.collect();
+    let last = durations.last().copied();
+    let average = (!durations.is_empty())
+        .then(|| durations.iter().sum::<Duration>() / durations.len() as u32);
+    let max = durations.iter().max().copied();
+
+    for (label, duration) in labels.iter().zip([last, average, max]) {
+        label.set_label(&duration.map_or_else(|| "–".to_string(), format_duration));
+    }
+}
+
+fn format_duration(duration: Duration) -> String {
+    format!("{:.2} ms", duration.as_secs_f64() * 1000.0)
+}
@@ -0,0 +1,181 @@
+/* publish.rs
+ *
+ * Copyright 2025 The Aardvark Developers
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ *
+ * SPDX-License-Identifier: GPL-3.0-or-later
+ */
+
+//! Publishing read-only snapshots of documents as static HTML pages.
+//!
+//! A page is written to any location gio can write to, e.g. a local folder served by a web
+//! server or a remote folder mounted via sftp:// or dav://. The location and the number of
+//! changes the published version contains are remembered in `publications.ini` inside the data
+//! directory, so the page can be updated later and we can tell how far it is behind.
+
+use std::path::{Path, PathBuf};
+
+use aardvark_doc::document::{Document, DocumentId};
+use aardvark_doc::frontmatter::parse_frontmatter;
+use aardvark_doc::polls::parse_poll_marker;
+use gtk::prelude::*;
+use gtk::{gio, glib};
+use pulldown_cmark::{Event, Options, Parser, html};
+
+use crate::file_transfer;
+
+const PUBLICATIONS_FILE: &str = "publications.ini";
+const LOCATION_KEY: &str = "location";
+const CHANGES_KEY: &str = "changes";
+
+/// Where a document was published to and which state of it.
+#[derive(Clone, Debug)]
+pub struct Publication {
+    pub location: gio::File,
+    /// Number of changes of the document contained in the published version.
+    pub changes: u64,
+}
+
+impl Publication {
+    /// How many changes were made to the document since it was published.
+    pub fn behind(&self, document: &Document) -> u64 {
+        document.changes().saturating_sub(self.changes)
+    }
+}
+
+pub struct Publications {
+    path: PathBuf,
+    key_file: glib::KeyFile,
+}
+
+impl Publications {
+    pub fn load(data_dir: &Path) -> Self {
+        let key_file = glib::KeyFile::new();
+        let path = data_dir.join(PUBLICATIONS_FILE);
+        // A missing or broken file means that nothing was published yet
+        let _ = key_file.load_from_file(&path, glib::KeyFileFlags::NONE);
+
+        Self { path, key_file }
+    }
+
+    pub fn get(&self, document_id: &DocumentId) -> Option<Publication> {
+        let group = document_id.to_string();
+        let location = self.key_file.string(&group, LOCATION_KEY).ok()?;
+
+        Some(Publication {
+            location: gio::File::for_uri(&location),
+            changes: self.key_file.uint64(&group, CHANGES_KEY).unwrap_or(0),
+        })
+    }
+
+    pub fn set(
+        &self,
+        document_id: &DocumentId,
+        publication: &Publication,
+    ) -> Result<(), glib::Error> {
+        let group = document_id.to_string();
+        self.key_file
+            .set_string(&group, LOCATION_KEY, &publication.location.uri());
+        self.key_file
+            .set_uint64(&group, CHANGES_KEY, publication.changes);
+        self.key_file.save_to_file(&self.path)
+    }
+}
+
+/// Render the current state of the document and write it to `location`.
+pub async fn publish(
+    document: &Document,
+    location: &gio::File,
+) -> Result<Publication, glib::Error> {
+    let changes = document.changes();
+    let title = document.name().unwrap_or_else(|| document.id().to_string());
+    let page = render_html(&title, &document.text());
+
+    file_transfer::write(location, page.into_bytes(), false).await?;
+
+    Ok(Publication {
+        location: location.clone(),
+        changes,
+    })
+}
+
+/// Render markdown as a self-contained HTML page.
+///
+/// Documents are written by everyone they are shared with, raw HTML is therefore escaped
+/// instead of ending up on the page.
+///
+/// The YAML frontmatter isn't rendered, its `description` and `author` end up in the head of the
+/// page.
+pub fn render_html(title: &str, markdown: &str) -> String {
+    let options = Options::ENABLE_TABLES
+        | Options::ENABLE_STRIKETHROUGH
+        | Options::ENABLE_TASKLISTS
+        | Options::ENABLE_YAML_STYLE_METADATA_BLOCKS;
+    let parser = Parser::new_ext(markdown, options).filter_map(|event| match event {
+        // Polls only exist inside Aardvark, leave out the lines referencing them
+        Event::Html(html) if parse_poll_marker(&html).is_some() => None,
+        Event::Html(html) | Event::InlineHtml(html) => Some(Event::Text(html)),
+        event => Some(event),
+    });
+
+    let mut body = String::new();
+    html::push_html(&mut body, parser);
+
+    let mut meta = String::new();
+    if let Some(frontmatter) = parse_frontmatter(markdown) {
+        for key in ["description", "author"] {
+            if let Some(value) = frontmatter.get(key) {
+                meta.push_str(&format!(
+                    "<meta name=\"{key}\" content=\"{}\">\n",
+                    escape_html(value)
+                ));
+            }
+        }
+    }
+
+    let published = glib::DateTime::now_utc()
+        .and_then(|now| now.format_iso8601())
+        .map(|now| now.to_string())
+        .unwrap_or_default();
+
+    format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<meta name="generator" content="Aardvark">
+{meta}<title>{title}</title>
+<style>
+body {{ max-width: 42em; margin: 2em auto; padding: 0 1em; font-family: sans-serif; line-height: 1.5; }}
+pre, code {{ background: #f6f5f4; }}
+footer {{ margin-top: 3em; color: #77767b; font-size: small; }}
+</style>
+</head>
+<body>
+{body}
+<footer>Published with Aardvark on <time>{published}</time></footer>
+</body>
+</html>
+"#,
+        title = escape_html(title),
+    )
+}
+
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    html::push_html(&mut escaped, std::iter::once(Event::Text(text.into())));
+    escaped
+}
@@ -0,0 +1,152 @@
+/* relative_time.rs
+ *
+ * Copyright 2025 The Aardvark Developers
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ *
+ * SPDX-License-Identifier: GPL-3.0-or-later
+ */
+
+use std::cell::RefCell;
+use std::time::Duration;
+
+use gettextrs::{gettext, ngettext};
+use gtk::prelude::*;
+use gtk::subclass::prelude::*;
+use gtk::{glib, glib::clone};
+
+/// How often the display string is formatted again.
+const TICK_INTERVAL: Duration = Duration::from_secs(30);
+
+mod imp {
+    use super::*;
+
+    #[derive(Debug, Default, glib::Properties)]
+    #[properties(wrapper_type = super::RelativeTime)]
+    pub struct RelativeTime {
+        #[property(get, set = Self::set_datetime, nullable)]
+        datetime: RefCell<Option<glib::DateTime>>,
+        /// `datetime` relative to now, e.g. "5 minutes ago", empty without `datetime`.
+        #[property(get)]
+        display_string: RefCell<String>,
+        tick_source: RefCell<Option<glib::SourceId>>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for RelativeTime {
+        const NAME: &'static str = "AardvarkRelativeTime";
+        type Type = super::RelativeTime;
+    }
+
+    #[glib::derived_properties]
+    impl ObjectImpl for RelativeTime {
+        fn dispose(&self) {
+            if let Some(source) = self.tick_source.take() {
+                source.remove();
+            }
+        }
+    }
+
+    impl RelativeTime {
+        fn set_datetime(&self, datetime: Option<glib::DateTime>) {
+            let ticking = datetime.is_some();
+            self.datetime.replace(datetime);
+            self.obj().notify_datetime();
+            self.update();
+
+            if !ticking {
+                if let Some(source) = self.tick_source.take() {
+                    source.remove();
+                }
+            } else if self.tick_source.borrow().is_none() {
+                let source = glib::timeout_add_local(
+                    TICK_INTERVAL,
+                    clone!(
+                        #[weak(rename_to = this)]
+                        self,
+                        #[upgrade_or]
+                        glib::ControlFlow::Break,
+                        move || {
+                            this.update();
+                            glib::ControlFlow::Continue
+                        }
+                    ),
+                );
+                self.tick_source.replace(Some(source));
+            }
+        }
+
+        fn update(&self) {
+            let display_string = self
+                .datetime
+                .borrow()
+                .as_ref()
+                .and_then(|datetime| {
+                    let now = glib::DateTime::now_local().ok()?;
+                    Some(format(datetime, &now))
+                })
+                .unwrap_or_default();
+            if *self.display_string.borrow() != display_string {
+                self.display_string.replace(display_string);
+                self.obj().notify_display_string();
+            }
+        }
+    }
+}
+
+glib::wrapper! {
+    /// A point in time formatted relative to now, which stays up to date.
+    ///
+    /// Bind `display-string` to a label to show e.g. "5 minutes ago".
+    pub struct RelativeTime(ObjectSubclass<imp::RelativeTime>);
+}
+
+impl RelativeTime {
+    pub fn new() -> Self {
+        glib::Object::new()
+    }
+}
+
+impl Default for RelativeTime {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// `datetime` relative to `now`, e.g. "5 minutes ago", or its date if it was more than a week
+/// ago.
+pub fn format(datetime: &glib::DateTime, now: &glib::DateTime) -> String {
+    let elapsed = now.difference(datetime);
+    let minutes = elapsed.as_minutes();
+    let hours = elapsed.as_hours();
+    let days = elapsed.as_days();
+
+    // Clocks of peers might be ahead of ours
+    if minutes < 1 {
+        gettext("just now")
+    } else if hours < 1 {
+        ngettext("{} minute ago", "{} minutes ago", minutes as u32)
+            .replace("{}", &minutes.to_string())
+    } else if days < 1 {
+        ngettext("{} hour ago", "{} hours ago", hours as u32).replace("{}", &hours.to_string())
+    } else if days < 7 {
+        ngettext("{} day ago", "{} days ago", days as u32).replace("{}", &days.to_string())
+    } else {
+        datetime
+            .to_local()
+            .and_then(|datetime| datetime.format("%x"))
+            .map(String::from)
+            .unwrap_or_default()
+    }
+}
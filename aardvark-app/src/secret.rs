@@ -49,6 +49,24 @@ impl From<oo7::Error> for Error {
     }
 }
 
+/// Password of the WebDAV user or secret key of the S3 access key used for cold storage.
+///
+/// Stored with its own schema so it can never be mistaken for the identity.
+pub async fn cold_storage_secret() -> Result<Option<String>, Error> {
+    let keyring = oo7::Keyring::new().await?;
+    let attributes = HashMap::from([(XDG_SCHEMA, format!("{APP_ID}.ColdStorage"))]);
+
+    keyring.unlock().await?;
+
+    let Some(item) = keyring.search_items(&attributes).await?.into_iter().next() else {
+        return Ok(None);
+    };
+    item.unlock().await?;
+    let secret = item.secret().await?;
+
+    Ok(Some(String::from_utf8_lossy(&secret).into_owned()))
+}
+
 pub async fn get_or_create_identity() -> Result<PrivateKey, Error> {
     let keyring = oo7::Keyring::new().await?;
 
@@ -76,3 +94,16 @@ pub async fn get_or_create_identity() -> Result<PrivateKey, Error> {
 
     Ok(private_key)
 }
+
+/// Store a different identity, replacing the one of this device.
+pub async fn replace_identity(private_key: &PrivateKey) -> Result<(), Error> {
+    let keyring = oo7::Keyring::new().await?;
+
+    keyring.unlock().await?;
+    keyring
+        .create_item("Aardvark", &attributes(), private_key.as_bytes(), true)
+        .await?;
+
+    info!("Replaced identity: {}", private_key.public_key());
+    Ok(())
+}
@@ -0,0 +1,334 @@
+/* settings.rs
+ *
+ * Copyright 2025 The Aardvark Developers
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ *
+ * SPDX-License-Identifier: GPL-3.0-or-later
+ */
+
+//! Typed access to the settings of the application.
+//!
+//! Every key of the `org.p2panda.aardvark` schema is a property of [`Settings`] with the same
+//! name, bound to GSettings in both directions. Widgets bind to the properties and everything
+//! else connects to their notifications, so changes apply while the application is running, no
+//! matter whether they come from the preferences or from `gsettings set`. Without the schema
+//! installed, e.g. when running from the build directory, the properties hold the defaults of
+//! the schema and changes are kept in memory only.
+
+use std::cell::{Cell, OnceCell, RefCell};
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::sync::LazyLock;
+use std::time::Duration;
+
+use aardvark_doc::service::{BandwidthLimits, GossipPreset, NetworkConfig};
+use aardvark_doc::sorted_authors::AuthorOrder;
+use gtk::prelude::*;
+use gtk::subclass::prelude::*;
+use gtk::{gio, glib, glib::Properties, glib::clone, glib::closure_local, glib::subclass::Signal};
+use tracing::error;
+
+use crate::config;
+
+const KEYBINDINGS_KEY: &str = "keybindings";
+
+/// Where encrypted snapshots of all documents are pinned.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, glib::Enum)]
+#[enum_type(name = "AardvarkColdStorageKind")]
+pub enum ColdStorageKind {
+    #[default]
+    #[enum_value(nick = "none")]
+    Disabled,
+    S3,
+    #[enum_value(nick = "webdav")]
+    WebDav,
+}
+
+mod imp {
+    use super::*;
+
+    #[derive(Properties)]
+    #[properties(wrapper_type = super::Settings)]
+    pub struct Settings {
+        /// `None` when the schema isn't installed.
+        pub gsettings: OnceCell<Option<gio::Settings>>,
+        /// Shortcuts which differ from the defaults, by action.
+        pub keybindings: RefCell<HashMap<String, Vec<String>>>,
+
+        #[property(get, set, builder(GossipPreset::default()))]
+        gossip_preset: Cell<GossipPreset>,
+        #[property(get, set, default = true)]
+        network_ipv4: Cell<bool>,
+        #[property(get, set, default = true)]
+        network_ipv6: Cell<bool>,
+        #[property(get, set)]
+        network_bind_address_v4: RefCell<String>,
+        #[property(get, set)]
+        network_bind_address_v6: RefCell<String>,
+        #[property(get, set, maximum = 65535)]
+        network_port: Cell<u32>,
+        #[property(get, set, minimum = 5, maximum = 3600, default = 60)]
+        sync_session_timeout: Cell<u32>,
+        #[property(get, set, minimum = 1, maximum = 1024, default = 128)]
+        sync_max_sessions: Cell<u32>,
+        #[property(get, set, maximum = 255, default = 5)]
+        sync_max_retries: Cell<u32>,
+        /// In KiB per second, `0` for no limit.
+        #[property(get, set)]
+        bandwidth_upload_limit: Cell<u32>,
+        #[property(get, set)]
+        bandwidth_download_limit: Cell<u32>,
+        #[property(get, set)]
+        bandwidth_peer_upload_limit: Cell<u32>,
+        #[property(get, set)]
+        bandwidth_peer_download_limit: Cell<u32>,
+        #[property(get, set, default = true)]
+        notify_remote_changes: Cell<bool>,
+        #[property(get, set, default = true)]
+        share_presence: Cell<bool>,
+        #[property(get, set)]
+        run_in_background: Cell<bool>,
+        #[property(get, set, default = true)]
+        notify_invites: Cell<bool>,
+        #[property(get, set, minimum = 320, maximum = 2000, default = 680)]
+        focus_mode_measure_width: Cell<i32>,
+        #[property(get, set)]
+        hide_frontmatter: Cell<bool>,
+        #[property(get, set)]
+        import_by_paragraph: Cell<bool>,
+        #[property(get, set, builder(AuthorOrder::default()))]
+        author_order: Cell<AuthorOrder>,
+        #[property(get, set, builder(ColdStorageKind::default()))]
+        cold_storage_backend: Cell<ColdStorageKind>,
+        #[property(get, set)]
+        cold_storage_url: RefCell<String>,
+        #[property(get, set)]
+        cold_storage_bucket: RefCell<String>,
+        #[property(get, set)]
+        cold_storage_region: RefCell<String>,
+        #[property(get, set)]
+        cold_storage_username: RefCell<String>,
+        #[property(get, set)]
+        cold_storage_proxy: RefCell<String>,
+        #[property(get, set)]
+        display_name: RefCell<String>,
+        #[property(get, set)]
+        publish_location: RefCell<String>,
+        #[property(get, set)]
+        show_troubleshooting: Cell<bool>,
+    }
+
+    /// The defaults of the schema, for running without it.
+    impl Default for Settings {
+        fn default() -> Self {
+            Self {
+                gsettings: OnceCell::new(),
+                keybindings: RefCell::default(),
+                gossip_preset: Cell::default(),
+                network_ipv4: Cell::new(true),
+                network_ipv6: Cell::new(true),
+                network_bind_address_v4: RefCell::default(),
+                network_bind_address_v6: RefCell::default(),
+                network_port: Cell::new(0),
+                sync_session_timeout: Cell::new(60),
+                sync_max_sessions: Cell::new(128),
+                sync_max_retries: Cell::new(5),
+                bandwidth_upload_limit: Cell::new(0),
+                bandwidth_download_limit: Cell::new(0),
+                bandwidth_peer_upload_limit: Cell::new(0),
+                bandwidth_peer_download_limit: Cell::new(0),
+                notify_remote_changes: Cell::new(true),
+                share_presence: Cell::new(true),
+                run_in_background: Cell::new(false),
+                notify_invites: Cell::new(true),
+                focus_mode_measure_width: Cell::new(680),
+                hide_frontmatter: Cell::new(false),
+                import_by_paragraph: Cell::new(false),
+                author_order: Cell::default(),
+                cold_storage_backend: Cell::default(),
+                cold_storage_url: RefCell::default(),
+                cold_storage_bucket: RefCell::default(),
+                cold_storage_region: RefCell::new("us-east-1".to_owned()),
+                cold_storage_username: RefCell::default(),
+                cold_storage_proxy: RefCell::default(),
+                display_name: RefCell::default(),
+                publish_location: RefCell::default(),
+                show_troubleshooting: Cell::new(false),
+            }
+        }
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for Settings {
+        const NAME: &'static str = "AardvarkSettings";
+        type Type = super::Settings;
+    }
+
+    #[glib::derived_properties]
+    impl ObjectImpl for Settings {
+        fn signals() -> &'static [Signal] {
+            static SIGNALS: LazyLock<Vec<Signal>> = LazyLock::new(|| {
+                vec![
+                    // The shortcuts differing from the defaults changed.
+                    Signal::builder("keybindings-changed").build(),
+                ]
+            });
+            SIGNALS.as_ref()
+        }
+
+        fn constructed(&self) {
+            self.parent_constructed();
+
+            let gsettings = gio::SettingsSchemaSource::default()
+                .and_then(|source| source.lookup(config::APP_ID, true))
+                .map(|_| gio::Settings::new(config::APP_ID));
+            let Some(gsettings) = self.gsettings.get_or_init(|| gsettings) else {
+                return;
+            };
+
+            let obj = self.obj();
+            // The names of the properties are the keys of the schema
+            for pspec in obj.list_properties() {
+                gsettings.bind(pspec.name(), &*obj, pspec.name()).build();
+            }
+
+            self.load_keybindings(gsettings);
+            gsettings.connect_changed(
+                Some(KEYBINDINGS_KEY),
+                clone!(
+                    #[weak(rename_to = this)]
+                    self,
+                    move |gsettings, _| {
+                        this.load_keybindings(gsettings);
+                        this.obj().emit_by_name::<()>("keybindings-changed", &[]);
+                    }
+                ),
+            );
+        }
+    }
+
+    impl Settings {
+        fn load_keybindings(&self, gsettings: &gio::Settings) {
+            let keybindings = gsettings.value(KEYBINDINGS_KEY).get().unwrap_or_default();
+            self.keybindings.replace(keybindings);
+        }
+    }
+}
+
+glib::wrapper! {
+    /// The settings of the application, see the module documentation.
+    pub struct Settings(ObjectSubclass<imp::Settings>);
+}
+
+impl Settings {
+    pub fn new() -> Self {
+        glib::Object::new()
+    }
+
+    /// Configuration of the network layer, following the `network-*`, `sync-*` and
+    /// `bandwidth-*` settings.
+    pub fn network_config(&self) -> NetworkConfig {
+        // Zero means no limit, the node treats it the same
+        let limit = |kib: u32| Some(u64::from(kib) * 1024);
+        // Invalid addresses are ignored, listening on all interfaces
+        NetworkConfig {
+            ipv4: self.network_ipv4(),
+            ipv6: self.network_ipv6(),
+            bind_ip_v4: self.network_bind_address_v4().parse().ok(),
+            bind_ip_v6: self.network_bind_address_v6().parse().ok(),
+            port: self.network_port().min(u16::MAX.into()) as u16,
+            session_timeout: Duration::from_secs(self.sync_session_timeout().into()),
+            max_concurrent_sessions: self.sync_max_sessions() as usize,
+            max_retry_attempts: self.sync_max_retries().min(u8::MAX.into()) as u8,
+            bandwidth: BandwidthLimits {
+                upload: limit(self.bandwidth_upload_limit()),
+                download: limit(self.bandwidth_download_limit()),
+                peer_upload: limit(self.bandwidth_peer_upload_limit()),
+                peer_download: limit(self.bandwidth_peer_download_limit()),
+            },
+        }
+    }
+
+    /// Connect to changes of any setting [`Self::network_config`] depends on.
+    pub fn connect_network_config_changed<F: Fn(&Self) + 'static>(
+        &self,
+        f: F,
+    ) -> Vec<glib::SignalHandlerId> {
+        let f = Rc::new(f);
+        [
+            "network-ipv4",
+            "network-ipv6",
+            "network-bind-address-v4",
+            "network-bind-address-v6",
+            "network-port",
+            "sync-session-timeout",
+            "sync-max-sessions",
+            "sync-max-retries",
+            "bandwidth-upload-limit",
+            "bandwidth-download-limit",
+            "bandwidth-peer-upload-limit",
+            "bandwidth-peer-download-limit",
+        ]
+        .into_iter()
+        .map(|name| {
+            let f = f.clone();
+            self.connect_notify_local(Some(name), move |settings, _| f(settings))
+        })
+        .collect()
+    }
+
+    /// Stateful action changing the setting `name`, e.g. for a check button in a menu.
+    pub fn create_action(&self, name: &str) -> gio::Action {
+        gio::PropertyAction::new(name, self, name).upcast()
+    }
+
+    /// Shortcuts which differ from the defaults, by action.
+    pub fn keybindings(&self) -> HashMap<String, Vec<String>> {
+        self.imp().keybindings.borrow().clone()
+    }
+
+    /// Replace the shortcuts which differ from the defaults.
+    pub fn set_keybindings(&self, keybindings: HashMap<String, Vec<String>>) {
+        let Some(Some(gsettings)) = self.imp().gsettings.get() else {
+            self.imp().keybindings.replace(keybindings);
+            self.emit_by_name::<()>("keybindings-changed", &[]);
+            return;
+        };
+        // Emits `keybindings-changed` once GSettings reports the change
+        if let Err(error) = gsettings.set_value(KEYBINDINGS_KEY, &keybindings.to_variant()) {
+            error!("Failed to store keyboard shortcuts: {error}");
+        }
+    }
+
+    /// Connect to changes of [`Self::keybindings`].
+    pub fn connect_keybindings_changed<F: Fn(&Self) + 'static>(
+        &self,
+        f: F,
+    ) -> glib::SignalHandlerId {
+        self.connect_closure(
+            "keybindings-changed",
+            false,
+            closure_local!(move |obj: Self| {
+                f(&obj);
+            }),
+        )
+    }
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self::new()
+    }
+}
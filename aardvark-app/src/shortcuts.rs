@@ -0,0 +1,232 @@
+/* Copyright 2025 The Aardvark Developers
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ *
+ * SPDX-License-Identifier: GPL-3.0-or-later
+ */
+
+//! Keyboard shortcuts of app and window actions.
+//!
+//! Users can change the shortcut of every action in the preferences. Only changed shortcuts are
+//! stored in the `keybindings` setting, so updated defaults reach everyone else.
+
+use std::collections::HashMap;
+
+use gtk::gdk;
+use gtk::prelude::*;
+
+use crate::AardvarkApplication;
+
+/// An action which can be triggered with a keyboard shortcut.
+#[derive(Debug)]
+pub struct Shortcut {
+    pub action: &'static str,
+    /// Untranslated description, shown in the preferences.
+    pub title: &'static str,
+    pub defaults: &'static [&'static str],
+}
+
+pub const SHORTCUTS: &[Shortcut] = &[
+    Shortcut {
+        action: "app.new-window",
+        title: "New Window",
+        defaults: &["<Control>n"],
+    },
+    Shortcut {
+        action: "window.new-tab",
+        title: "New Tab",
+        defaults: &["<Control>t"],
+    },
+    Shortcut {
+        action: "window.close-tab",
+        title: "Close Tab",
+        defaults: &["<Control>w"],
+    },
+    Shortcut {
+        action: "window.save",
+        title: "Save",
+        defaults: &["<Control>s"],
+    },
+    Shortcut {
+        action: "window.save-as",
+        title: "Save As",
+        defaults: &["<Control><Shift>s"],
+    },
+    Shortcut {
+        action: "window.publish",
+        title: "Publish",
+        defaults: &[],
+    },
+    Shortcut {
+        action: "window.insert-poll",
+        title: "Insert Poll",
+        defaults: &[],
+    },
+    Shortcut {
+        action: "window.show-calendar",
+        title: "Calendar",
+        defaults: &[],
+    },
+    Shortcut {
+        action: "window.show-bookmarks",
+        title: "Bookmarks",
+        defaults: &["<Control>b"],
+    },
+    Shortcut {
+        action: "window.next-bookmark",
+        title: "Next Bookmark",
+        defaults: &["<Alt>Page_Down"],
+    },
+    Shortcut {
+        action: "window.previous-bookmark",
+        title: "Previous Bookmark",
+        defaults: &["<Alt>Page_Up"],
+    },
+    Shortcut {
+        action: "window.focus-mode",
+        title: "Focus Mode",
+        defaults: &["F11"],
+    },
+    Shortcut {
+        action: "window.zoom-in",
+        title: "Zoom In",
+        defaults: &["<Control>plus", "<Control>KP_Add"],
+    },
+    Shortcut {
+        action: "window.zoom-out",
+        title: "Zoom Out",
+        // gnome-text-editor uses `equal` as well: probably to make it nicer for the US keyboard
+        // layout
+        defaults: &["<Control>minus", "<Control>equal", "<Control>KP_Subtract"],
+    },
+    Shortcut {
+        action: "window.zoom-one",
+        title: "Reset Zoom",
+        defaults: &["<Control>0", "<Control>KP_0"],
+    },
+    Shortcut {
+        action: "window.test-connection",
+        title: "Test Connection",
+        defaults: &[],
+    },
+    Shortcut {
+        action: "window.show-profiler",
+        title: "Profiler Overlay",
+        defaults: &["<Control><Shift>p"],
+    },
+    Shortcut {
+        action: "window.show-debug-dialog",
+        title: "Debug Information",
+        defaults: &["<Control><Shift>d"],
+    },
+    Shortcut {
+        action: "app.preferences",
+        title: "Preferences",
+        defaults: &["<Control>comma"],
+    },
+    Shortcut {
+        action: "app.quit",
+        title: "Quit",
+        defaults: &["<Control>q"],
+    },
+];
+
+impl Shortcut {
+    /// The shortcuts currently used for this action.
+    pub fn accels(&self) -> Vec<String> {
+        overrides().remove(self.action).unwrap_or_else(|| {
+            self.defaults
+                .iter()
+                .map(|accel| accel.to_string())
+                .collect()
+        })
+    }
+
+    pub fn is_default(&self) -> bool {
+        !overrides().contains_key(self.action)
+    }
+
+    /// Use `accels` for this action from now on, an empty list disables the shortcut.
+    pub fn set_accels(&self, accels: &[String]) {
+        let mut overrides = overrides();
+        if accels
+            .iter()
+            .map(String::as_str)
+            .eq(self.defaults.iter().copied())
+        {
+            overrides.remove(self.action);
+        } else {
+            overrides.insert(self.action.to_owned(), accels.to_vec());
+        }
+        store_overrides(overrides);
+    }
+
+    pub fn reset(&self) {
+        let mut overrides = overrides();
+        if overrides.remove(self.action).is_some() {
+            store_overrides(overrides);
+        }
+    }
+}
+
+/// Apply the shortcuts of all actions, called on startup and whenever they were changed.
+pub fn apply(application: &AardvarkApplication) {
+    for shortcut in SHORTCUTS {
+        let accels = shortcut.accels();
+        let accels: Vec<&str> = accels.iter().map(String::as_str).collect();
+        application.set_accels_for_action(shortcut.action, &accels);
+    }
+}
+
+/// Go back to the default shortcuts for all actions.
+pub fn reset_all() {
+    store_overrides(HashMap::new());
+}
+
+/// The action already using `accel`, ignoring `except`.
+pub fn conflict(accel: &str, except: &Shortcut) -> Option<&'static Shortcut> {
+    let key = gtk::accelerator_parse(accel)?;
+    SHORTCUTS
+        .iter()
+        .filter(|shortcut| shortcut.action != except.action)
+        .find(|shortcut| {
+            shortcut
+                .accels()
+                .iter()
+                .any(|other| gtk::accelerator_parse(other) == Some(key))
+        })
+}
+
+/// The accelerator for a key press, if it's usable as a shortcut.
+pub fn accel_for_key_press(key: gdk::Key, state: gdk::ModifierType) -> Option<String> {
+    let state = state & gtk::accelerator_get_default_mod_mask();
+    let key = key.to_lower();
+    if !gtk::accelerator_valid(key, state) {
+        return None;
+    }
+
+    Some(gtk::accelerator_name(key, state).to_string())
+}
+
+/// Shortcuts which differ from the defaults, by action.
+fn overrides() -> HashMap<String, Vec<String>> {
+    AardvarkApplication::default().settings().keybindings()
+}
+
+fn store_overrides(overrides: HashMap<String, Vec<String>>) {
+    // Applied once the settings report the change
+    AardvarkApplication::default()
+        .settings()
+        .set_keybindings(overrides);
+}
@@ -0,0 +1,358 @@
+/* Copyright 2025 The Aardvark Developers
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ *
+ * SPDX-License-Identifier: GPL-3.0-or-later
+ */
+
+use std::cell::{OnceCell, RefCell};
+use std::time::Duration;
+
+use aardvark_doc::document::Document;
+use adw::prelude::*;
+use adw::subclass::prelude::*;
+use gettextrs::{gettext, ngettext};
+use gtk::{glib, glib::clone};
+use sourceview::prelude::BufferExt;
+
+use crate::document_view::DocumentView;
+
+/// How long the sync indicator pulses after the text changed while peers are connected.
+const SYNC_PULSE: Duration = Duration::from_secs(1);
+/// Languages to highlight the text as, by their GtkSourceView id.
+const LANGUAGES: [(&str, &str); 2] = [("markdown", "Markdown"), ("", "Plain Text")];
+
+/// State of the sync indicator dot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SyncState {
+    /// Connected to peers, nothing to exchange right now.
+    Idle,
+    /// Changes are being exchanged with peers.
+    Syncing,
+    /// No peers to sync with.
+    Offline,
+}
+
+impl SyncState {
+    fn css_class(self) -> &'static str {
+        match self {
+            SyncState::Idle => "idle",
+            SyncState::Syncing => "syncing",
+            SyncState::Offline => "offline",
+        }
+    }
+}
+
+mod imp {
+    use super::*;
+
+    #[derive(Debug, Default, glib::Properties)]
+    #[properties(wrapper_type = super::StatusBar)]
+    pub struct StatusBar {
+        sync_indicator: gtk::Box,
+        position_label: gtk::Label,
+        words_label: gtk::Label,
+        language_dropdown: OnceCell<gtk::DropDown>,
+        /// The view of the selected tab.
+        #[property(get, set = Self::set_view, nullable)]
+        view: RefCell<Option<DocumentView>>,
+        view_handlers: RefCell<Vec<(glib::Object, glib::SignalHandlerId)>>,
+        /// The indicator pulses until this source runs.
+        sync_pulse: RefCell<Option<glib::SourceId>>,
+        /// Pending count of the words after the text changed.
+        word_count: RefCell<Option<glib::SourceId>>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for StatusBar {
+        const NAME: &'static str = "AardvarkStatusBar";
+        type Type = super::StatusBar;
+        type ParentType = gtk::Box;
+    }
+
+    #[glib::derived_properties]
+    impl ObjectImpl for StatusBar {
+        fn constructed(&self) {
+            self.parent_constructed();
+            let obj = self.obj();
+
+            obj.add_css_class("status-bar");
+            obj.set_spacing(12);
+
+            self.sync_indicator.add_css_class("sync-indicator");
+            self.sync_indicator.set_valign(gtk::Align::Center);
+            obj.append(&self.sync_indicator);
+
+            self.position_label.add_css_class("numeric");
+            self.position_label.set_hexpand(true);
+            self.position_label.set_xalign(0.0);
+            obj.append(&self.position_label);
+
+            self.words_label.add_css_class("numeric");
+            obj.append(&self.words_label);
+
+            let names: Vec<String> = LANGUAGES.iter().map(|(_, name)| gettext(*name)).collect();
+            let names: Vec<&str> = names.iter().map(String::as_str).collect();
+            let language_dropdown = gtk::DropDown::from_strings(&names);
+            language_dropdown.add_css_class("flat");
+            language_dropdown.set_tooltip_text(Some(&gettext("Highlighting")));
+            language_dropdown.connect_selected_notify(clone!(
+                #[weak(rename_to = this)]
+                self,
+                move |dropdown| this.set_language(dropdown.selected())
+            ));
+            obj.append(&language_dropdown);
+            self.language_dropdown.set(language_dropdown).unwrap();
+        }
+
+        fn dispose(&self) {
+            self.disconnect_view();
+        }
+    }
+
+    impl StatusBar {
+        fn set_view(&self, view: Option<DocumentView>) {
+            if *self.view.borrow() == view {
+                return;
+            }
+            self.disconnect_view();
+            self.view.replace(view.clone());
+
+            if let Some(view) = view {
+                self.connect_view(&view);
+            }
+            self.update_position();
+            self.update_words();
+            self.update_sync_state();
+            self.update_language();
+            self.obj().notify_view();
+        }
+
+        fn connect_view(&self, view: &DocumentView) {
+            let buffer = view.buffer();
+            let document = view.document();
+            let mut handlers: Vec<(glib::Object, glib::SignalHandlerId)> = Vec::new();
+
+            let handler = buffer.connect_cursor_position_notify(clone!(
+                #[weak(rename_to = this)]
+                self,
+                move |_| this.update_position()
+            ));
+            handlers.push((buffer.clone().upcast(), handler));
+            let handler = buffer.connect_language_notify(clone!(
+                #[weak(rename_to = this)]
+                self,
+                move |_| this.update_language()
+            ));
+            handlers.push((buffer.clone().upcast(), handler));
+            // Counting the words is delayed till the main loop is idle, like detecting links
+            let handler = buffer.connect_changed(clone!(
+                #[weak(rename_to = this)]
+                self,
+                move |_| {
+                    if this.word_count.borrow().is_some() {
+                        return;
+                    }
+                    let obj = this.obj();
+                    let source_id = glib::idle_add_local_once(clone!(
+                        #[weak]
+                        obj,
+                        move || {
+                            obj.imp().word_count.take();
+                            obj.imp().update_words();
+                        }
+                    ));
+                    this.word_count.replace(Some(source_id));
+                }
+            ));
+            handlers.push((buffer.upcast(), handler));
+
+            let handler = document.connect_text_notify(clone!(
+                #[weak(rename_to = this)]
+                self,
+                move |_| this.pulse_sync_indicator()
+            ));
+            handlers.push((document.clone().upcast(), handler));
+            for property in ["subscribed", "reconnect-attempt"] {
+                let handler = document.connect_notify_local(
+                    Some(property),
+                    clone!(
+                        #[weak(rename_to = this)]
+                        self,
+                        move |_, _| this.update_sync_state()
+                    ),
+                );
+                handlers.push((document.clone().upcast(), handler));
+            }
+            let peers = document.peers();
+            let handler = peers.connect_items_changed(clone!(
+                #[weak(rename_to = this)]
+                self,
+                move |_, _, _, _| this.update_sync_state()
+            ));
+            handlers.push((peers.upcast(), handler));
+
+            self.view_handlers.replace(handlers);
+        }
+
+        fn disconnect_view(&self) {
+            for (object, handler) in self.view_handlers.take() {
+                object.disconnect(handler);
+            }
+            if let Some(source_id) = self.sync_pulse.take() {
+                source_id.remove();
+            }
+            if let Some(source_id) = self.word_count.take() {
+                source_id.remove();
+            }
+        }
+
+        fn update_position(&self) {
+            let Some(view) = self.view.borrow().clone() else {
+                return;
+            };
+            let buffer = view.buffer();
+            let iter = buffer.iter_at_mark(&buffer.get_insert());
+            self.position_label.set_label(
+                &gettext("Ln {line}, Col {column}")
+                    .replace("{line}", &(iter.line() + 1).to_string())
+                    .replace("{column}", &(iter.line_offset() + 1).to_string()),
+            );
+        }
+
+        fn update_words(&self) {
+            let Some(view) = self.view.borrow().clone() else {
+                return;
+            };
+            let words = view.buffer().full_text().split_whitespace().count() as u32;
+            self.words_label.set_label(
+                &ngettext("{} Word", "{} Words", words).replace("{}", &words.to_string()),
+            );
+        }
+
+        /// Show that changes are being exchanged for a moment.
+        fn pulse_sync_indicator(&self) {
+            if let Some(source_id) = self.sync_pulse.take() {
+                source_id.remove();
+            }
+            let source_id = glib::timeout_add_local_once(
+                SYNC_PULSE,
+                clone!(
+                    #[weak(rename_to = this)]
+                    self,
+                    move || {
+                        this.sync_pulse.take();
+                        this.update_sync_state();
+                    }
+                ),
+            );
+            self.sync_pulse.replace(Some(source_id));
+            self.update_sync_state();
+        }
+
+        fn update_sync_state(&self) {
+            let Some(document) = self.view.borrow().as_ref().map(DocumentView::document) else {
+                return;
+            };
+            let state = if !document.subscribed()
+                || document.reconnect_attempt() > 0
+                || document.peers().n_items() == 0
+            {
+                SyncState::Offline
+            } else if self.sync_pulse.borrow().is_some() {
+                SyncState::Syncing
+            } else {
+                SyncState::Idle
+            };
+
+            for other in [SyncState::Idle, SyncState::Syncing, SyncState::Offline] {
+                self.sync_indicator.remove_css_class(other.css_class());
+            }
+            self.sync_indicator.add_css_class(state.css_class());
+            self.sync_indicator
+                .set_tooltip_text(Some(&sync_state_tooltip(&document)));
+        }
+
+        fn update_language(&self) {
+            let Some(view) = self.view.borrow().clone() else {
+                return;
+            };
+            let id = view
+                .buffer()
+                .language()
+                .map(|language| language.id().to_string())
+                .unwrap_or_default();
+            if let Some(position) = LANGUAGES.iter().position(|(other, _)| *other == id) {
+                self.language_dropdown
+                    .get()
+                    .unwrap()
+                    .set_selected(position as u32);
+            }
+        }
+
+        fn set_language(&self, position: u32) {
+            let Some(view) = self.view.borrow().clone() else {
+                return;
+            };
+            let Some((id, _)) = LANGUAGES.get(position as usize) else {
+                return;
+            };
+            let language = sourceview::LanguageManager::default().language(id);
+            let buffer = view.buffer();
+            if buffer.language() != language {
+                buffer.set_language(language.as_ref());
+            }
+        }
+    }
+
+    impl WidgetImpl for StatusBar {}
+    impl BoxImpl for StatusBar {}
+}
+
+glib::wrapper! {
+    /// Position of the cursor, number of words, highlighting and sync state of the selected
+    /// document.
+    pub struct StatusBar(ObjectSubclass<imp::StatusBar>)
+        @extends gtk::Widget, gtk::Box,
+        @implements gtk::Orientable;
+}
+
+impl StatusBar {
+    pub fn new() -> Self {
+        glib::Object::new()
+    }
+}
+
+impl Default for StatusBar {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Description of the sync state of `document`.
+pub fn sync_state_tooltip(document: &Document) -> String {
+    if !document.subscribed() {
+        return gettext("Not syncing");
+    }
+
+    let online = document.peers().n_items();
+    if document.reconnect_attempt() > 0 {
+        gettext("Connection lost, reconnecting")
+    } else if online == 0 {
+        gettext("Waiting for peers")
+    } else {
+        ngettext("Syncing with {} peer", "Syncing with {} peers", online)
+            .replace("{}", &online.to_string())
+    }
+}
@@ -0,0 +1,400 @@
+/* Copyright 2025 The Aardvark Developers
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ *
+ * SPDX-License-Identifier: GPL-3.0-or-later
+ */
+
+use std::cell::{Cell, RefCell};
+
+use adw::prelude::*;
+use adw::subclass::prelude::*;
+use gettextrs::gettext;
+use gtk::{glib, glib::clone};
+use tracing::error;
+
+use crate::components::Avatar;
+use aardvark_doc::{
+    author::Author,
+    document::Document,
+    identity::PublicKey,
+    tasks::{Task, today},
+};
+
+mod imp {
+    use super::*;
+
+    #[derive(Debug, Default, glib::Properties)]
+    #[properties(wrapper_type = super::TasksPopover)]
+    pub struct TasksPopover {
+        task_list_box: gtk::ListBox,
+        stack: gtk::Stack,
+        empty_page: adw::StatusPage,
+        #[property(get, set = Self::set_document, nullable)]
+        document: RefCell<Option<Document>>,
+        /// Only list tasks assigned to us.
+        #[property(get, set = Self::set_only_mine)]
+        only_mine: Cell<bool>,
+        /// Only list open tasks past their due date.
+        #[property(get, set = Self::set_only_overdue)]
+        only_overdue: Cell<bool>,
+        text_handler: RefCell<Option<glib::SignalHandlerId>>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for TasksPopover {
+        const NAME: &'static str = "AardvarkTasksPopover";
+        type Type = super::TasksPopover;
+        type ParentType = gtk::Popover;
+    }
+
+    #[glib::derived_properties]
+    impl ObjectImpl for TasksPopover {
+        fn constructed(&self) {
+            self.parent_constructed();
+            let obj = self.obj();
+
+            let only_mine_button = gtk::ToggleButton::builder()
+                .label(gettext("_My Tasks"))
+                .use_underline(true)
+                .build();
+            obj.bind_property("only-mine", &only_mine_button, "active")
+                .bidirectional()
+                .sync_create()
+                .build();
+            let only_overdue_button = gtk::ToggleButton::builder()
+                .label(gettext("_Overdue"))
+                .use_underline(true)
+                .build();
+            obj.bind_property("only-overdue", &only_overdue_button, "active")
+                .bidirectional()
+                .sync_create()
+                .build();
+            let filter_box = gtk::Box::builder()
+                .halign(gtk::Align::Center)
+                .margin_top(6)
+                .css_classes(["linked"])
+                .build();
+            filter_box.append(&only_mine_button);
+            filter_box.append(&only_overdue_button);
+
+            self.task_list_box
+                .set_selection_mode(gtk::SelectionMode::None);
+            let scrollview = gtk::ScrolledWindow::builder()
+                .child(&self.task_list_box)
+                .hscrollbar_policy(gtk::PolicyType::Never)
+                .propagate_natural_height(true)
+                .propagate_natural_width(true)
+                .max_content_height(400)
+                .build();
+            self.empty_page
+                .set_icon_name(Some("checkbox-checked-symbolic"));
+            self.empty_page.set_title(&gettext("No Tasks"));
+            self.empty_page.add_css_class("compact");
+            self.stack.add_named(&scrollview, Some("tasks"));
+            self.stack.add_named(&self.empty_page, Some("empty"));
+
+            let content = gtk::Box::new(gtk::Orientation::Vertical, 6);
+            content.append(&filter_box);
+            content.append(&self.stack);
+            obj.set_child(Some(&content));
+            obj.add_css_class("tasks-popover");
+
+            // Tasks are only listed while the popover is shown
+            obj.connect_map(|obj| obj.imp().update_tasks());
+        }
+
+        fn dispose(&self) {
+            self.set_document(None);
+        }
+    }
+
+    impl TasksPopover {
+        fn set_document(&self, document: Option<Document>) {
+            if let Some(handler) = self.text_handler.take() {
+                if let Some(document) = self.document.borrow().as_ref() {
+                    document.disconnect(handler);
+                }
+            }
+
+            if let Some(document) = document.as_ref() {
+                let handler = document.connect_text_notify(clone!(
+                    #[weak(rename_to = this)]
+                    self,
+                    move |_| {
+                        if this.obj().is_mapped() {
+                            this.update_tasks();
+                        }
+                    }
+                ));
+                self.text_handler.replace(Some(handler));
+            }
+
+            self.document.replace(document);
+            self.update_tasks();
+        }
+
+        fn set_only_mine(&self, only_mine: bool) {
+            self.only_mine.set(only_mine);
+            self.update_tasks();
+        }
+
+        fn set_only_overdue(&self, only_overdue: bool) {
+            self.only_overdue.set(only_overdue);
+            self.update_tasks();
+        }
+
+        fn update_tasks(&self) {
+            while let Some(row) = self.task_list_box.first_child() {
+                self.task_list_box.remove(&row);
+            }
+
+            let Some(document) = self.obj().document() else {
+                return;
+            };
+            let own_public_key = document.service().private_key().public_key();
+
+            let mut has_tasks = false;
+            for task in document.tasks() {
+                if self.only_mine.get() && task.assignee.as_ref() != Some(&own_public_key) {
+                    continue;
+                }
+                if self.only_overdue.get() && !task.is_overdue() {
+                    continue;
+                }
+                self.task_list_box.append(&task_row(&document, task));
+                has_tasks = true;
+            }
+
+            // Suggest the list marker the authors of the document agreed on
+            let marker = document.list_marker().format("[ ]");
+            self.empty_page.set_description(Some(
+                &gettext("Add a task by starting a line with “{}”").replace("{}", &marker),
+            ));
+            self.stack
+                .set_visible_child_name(if has_tasks { "tasks" } else { "empty" });
+        }
+    }
+
+    impl WidgetImpl for TasksPopover {}
+    impl PopoverImpl for TasksPopover {}
+}
+
+glib::wrapper! {
+    pub struct TasksPopover(ObjectSubclass<imp::TasksPopover>)
+        @extends gtk::Widget, gtk::Popover;
+}
+
+fn task_row(document: &Document, task: Task) -> adw::ActionRow {
+    let row = adw::ActionRow::builder()
+        .title(glib::markup_escape_text(&task.text))
+        .activatable(false)
+        .build();
+
+    let check_button = gtk::CheckButton::builder()
+        .active(task.checked)
+        .valign(gtk::Align::Center)
+        .build();
+    check_button.connect_toggled(clone!(
+        #[weak]
+        document,
+        #[strong]
+        task,
+        move |check_button| {
+            if let Err(error) = document.set_task_checked(&task, check_button.is_active()) {
+                error!("Failed to toggle task: {}", error);
+            }
+        }
+    ));
+    row.add_prefix(&check_button);
+
+    if let Some(due_date) = task.due_date.as_ref() {
+        row.set_subtitle(&format_due_date(due_date));
+        if task.is_overdue() {
+            row.add_css_class("overdue");
+        }
+    }
+
+    if let Some(assignee) = task.assignee.as_ref() {
+        let author = author_for(document, assignee);
+        let avatar = Avatar::new();
+        avatar.set_emoji(&author.emoji());
+        avatar.set_paintable(Some(&author.avatar()));
+        avatar.add_css_class(&format!("bg-{}", author.color()));
+        avatar.set_tooltip_text(Some(&author.name()));
+        row.add_suffix(&avatar);
+    }
+
+    let due_date_button = gtk::MenuButton::builder()
+        .icon_name("x-office-calendar-symbolic")
+        .tooltip_text(gettext("Set Due Date"))
+        .valign(gtk::Align::Center)
+        .popover(&due_date_popover(document, &task))
+        .build();
+    due_date_button.add_css_class("flat");
+    row.add_suffix(&due_date_button);
+
+    let assign_button = gtk::MenuButton::builder()
+        .icon_name("avatar-default-symbolic")
+        .tooltip_text(gettext("Assign Task"))
+        .valign(gtk::Align::Center)
+        .popover(&assign_popover(document, &task))
+        .build();
+    assign_button.add_css_class("flat");
+    row.add_suffix(&assign_button);
+
+    row
+}
+
+/// List of all authors of the document to choose the assignee of a task from.
+fn assign_popover(document: &Document, task: &Task) -> gtk::Popover {
+    let list_box = gtk::ListBox::builder()
+        .selection_mode(gtk::SelectionMode::None)
+        .build();
+    list_box.add_css_class("navigation-sidebar");
+
+    let nobody_row = adw::ActionRow::builder()
+        .title(gettext("Nobody"))
+        .activatable(true)
+        .build();
+    list_box.append(&nobody_row);
+
+    // Assignee of every row, by index
+    let mut assignees = vec![None];
+    for author in document.authors().iter::<Author>().flatten() {
+        let row = adw::ActionRow::builder()
+            .title(author.name())
+            .activatable(true)
+            .build();
+        let avatar = Avatar::new();
+        avatar.set_emoji(&author.emoji());
+        avatar.set_paintable(Some(&author.avatar()));
+        avatar.add_css_class(&format!("bg-{}", author.color()));
+        row.add_prefix(&avatar);
+        if task.assignee.as_ref() == Some(&author.public_key()) {
+            row.add_suffix(&gtk::Image::from_icon_name("object-select-symbolic"));
+        }
+        list_box.append(&row);
+        assignees.push(Some(author.public_key()));
+    }
+
+    let popover = gtk::Popover::builder().child(&list_box).build();
+    list_box.connect_row_activated(clone!(
+        #[weak]
+        document,
+        #[weak]
+        popover,
+        #[strong]
+        task,
+        move |_, row| {
+            popover.popdown();
+            let assignee = assignees[row.index() as usize].as_ref();
+            if let Err(error) = document.assign_task(&task, assignee) {
+                error!("Failed to assign task: {}", error);
+            }
+        }
+    ));
+
+    popover
+}
+
+/// Calendar to pick the due date of a task from.
+fn due_date_popover(document: &Document, task: &Task) -> gtk::Popover {
+    let calendar = gtk::Calendar::new();
+    if let Some(due_date) = task.due_date.as_ref() {
+        calendar.select_day(due_date);
+    }
+    let remove_button = gtk::Button::builder()
+        .label(gettext("_Remove Due Date"))
+        .use_underline(true)
+        .sensitive(task.due_date.is_some())
+        .build();
+
+    let content = gtk::Box::new(gtk::Orientation::Vertical, 6);
+    content.append(&calendar);
+    content.append(&remove_button);
+    let popover = gtk::Popover::builder().child(&content).build();
+
+    calendar.connect_day_selected(clone!(
+        #[weak]
+        document,
+        #[weak]
+        popover,
+        #[strong]
+        task,
+        move |calendar| {
+            popover.popdown();
+            // The calendar keeps the time of day, due dates start at midnight
+            let date = calendar.date();
+            let Ok(due_date) = glib::DateTime::from_local(
+                date.year(),
+                date.month(),
+                date.day_of_month(),
+                0,
+                0,
+                0.0,
+            ) else {
+                return;
+            };
+            if let Err(error) = document.set_task_due_date(&task, Some(&due_date)) {
+                error!("Failed to set due date of task: {}", error);
+            }
+        }
+    ));
+    remove_button.connect_clicked(clone!(
+        #[weak]
+        document,
+        #[weak]
+        popover,
+        #[strong]
+        task,
+        move |_| {
+            popover.popdown();
+            if let Err(error) = document.set_task_due_date(&task, None) {
+                error!("Failed to remove due date of task: {}", error);
+            }
+        }
+    ));
+
+    popover
+}
+
+/// "Due Tomorrow", "Due October 20"
+fn format_due_date(due_date: &glib::DateTime) -> String {
+    let days = due_date.difference(&today()).as_days();
+    let format = match days {
+        0 => gettext("Due Today"),
+        1 => gettext("Due Tomorrow"),
+        -1 => gettext("Due Yesterday"),
+        _ if due_date.year() == today().year() => {
+            // Translators: Month and day, see `man strftime` for the available specifiers
+            gettext("Due %B %-e")
+        }
+        // Translators: Month, day and year, see `man strftime` for the available specifiers
+        _ => gettext("Due %B %-e %Y"),
+    };
+
+    due_date
+        .format(&format)
+        .expect("formatting GDateTime works")
+        .into()
+}
+
+/// Assignees might have never joined the document while we were online.
+fn author_for(document: &Document, public_key: &PublicKey) -> Author {
+    document
+        .authors()
+        .by_public_key(public_key)
+        .unwrap_or_else(|| Author::new(public_key))
+}
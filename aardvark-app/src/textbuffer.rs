@@ -18,16 +18,31 @@
  * SPDX-License-Identifier: GPL-3.0-or-later
  */
 
+use std::borrow::Cow;
 use std::cell::{Cell, OnceCell, RefCell};
+use std::ops::Range;
 
+use aardvark_doc::assists::{
+    Continuation, closing_char, continuation, is_closing_char, should_close_before,
+};
 use aardvark_doc::document::Document;
 use gtk::prelude::*;
 use gtk::subclass::prelude::*;
-use gtk::{glib, glib::clone};
+use gtk::{glib, glib::clone, pango};
 use sourceview::prelude::BufferExt;
 use sourceview::subclass::prelude::*;
 use sourceview::*;
-use tracing::{error, info};
+use tracing::{debug, error};
+
+use crate::AardvarkApplication;
+
+/// Name of the tag underlining detected links.
+const LINK_TAG: &str = "link";
+/// Text starting with one of these is detected as a link.
+const LINK_PREFIXES: [&str; 3] = ["https://", "http://", "aardvark:"];
+/// Characters which are most likely part of the surrounding text rather than the end of a link.
+const LINK_TRAILING_PUNCTUATION: &[char] =
+    &['.', ',', ';', ':', '!', '?', ')', ']', '>', '"', '\''];
 
 mod imp {
     use super::*;
@@ -39,14 +54,37 @@ mod imp {
         pub document_handlers: OnceCell<glib::SignalGroup>,
         #[property(get, set = Self::set_document)]
         pub document: RefCell<Option<Document>>,
+        /// Changes of the document up to this version are already part of the text.
+        pub text_version: Cell<u64>,
+        /// Pending detection of links after the text changed.
+        pub link_detection: RefCell<Option<glib::SourceId>>,
+        /// Nesting depth of user actions, assists only apply to text typed by the user.
+        pub user_action_depth: Cell<u32>,
+        /// Whether a change is being undone or redone.
+        pub undoing: Cell<bool>,
+    }
+
+    /// An assist replacing what the user typed.
+    enum Assist {
+        /// Insert this text instead.
+        Insert(String),
+        /// Insert an opening and a closing character, with the cursor between them.
+        InsertPair(String),
+        /// Don't insert the closing character typed in front of the same one, move over it.
+        SkipClosing,
+        /// Remove the marker of an empty list item instead of starting a new line, its length
+        /// in characters.
+        EndList(usize),
     }
 
     impl AardvarkTextBuffer {
         fn set_document(&self, document: Option<&Document>) {
             if let Some(document) = document.as_ref() {
+                let (text, version) = document.text_with_version();
                 self.obj().set_inhibit_text_change(true);
-                self.obj().set_text(&document.text());
+                self.obj().set_text(&text);
                 self.obj().set_inhibit_text_change(false);
+                self.text_version.set(version);
             }
 
             self.document_handlers.get().unwrap().set_target(document);
@@ -97,7 +135,8 @@ mod imp {
                     move |values| {
                         let pos: i32 = values.get(1).unwrap().get().unwrap();
                         let text: &str = values.get(2).unwrap().get().unwrap();
-                        if buffer.inhibit_text_change() {
+                        let version: u64 = values.get(3).unwrap().get().unwrap();
+                        if buffer.inhibit_text_change() || !buffer.advance_text_version(version) {
                             return None;
                         }
 
@@ -122,7 +161,8 @@ mod imp {
                     move |values| {
                         let start: i32 = values.get(1).unwrap().get().unwrap();
                         let end: i32 = values.get(2).unwrap().get().unwrap();
-                        if buffer.inhibit_text_change() {
+                        let version: u64 = values.get(3).unwrap().get().unwrap();
+                        if buffer.inhibit_text_change() || !buffer.advance_text_version(version) {
                             return None;
                         }
 
@@ -138,10 +178,97 @@ mod imp {
             );
 
             self.document_handlers.set(document_handlers).unwrap();
+
+            buffer.create_tag(Some(LINK_TAG), &[("underline", &pango::Underline::Single)]);
+            // Detecting links is delayed till the main loop is idle, so remote changes arriving
+            // in many small deltas only cause a single pass over the text
+            buffer.connect_changed(|buffer| {
+                let imp = buffer.imp();
+                if imp.link_detection.borrow().is_some() {
+                    return;
+                }
+                let source_id = glib::idle_add_local_once(clone!(
+                    #[weak]
+                    buffer,
+                    move || {
+                        buffer.imp().link_detection.take();
+                        buffer.update_links();
+                    }
+                ));
+                imp.link_detection.replace(Some(source_id));
+            });
+        }
+
+        fn dispose(&self) {
+            if let Some(source_id) = self.link_detection.take() {
+                source_id.remove();
+            }
+        }
+    }
+
+    impl AardvarkTextBuffer {
+        /// The assist for typing `new_text` at `iter`, if any.
+        fn assist(&self, iter: &gtk::TextIter, new_text: &str) -> Option<Assist> {
+            if self.user_action_depth.get() == 0 || self.undoing.get() {
+                return None;
+            }
+            let mut chars = new_text.chars();
+            let (Some(c), None) = (chars.next(), chars.next()) else {
+                return None;
+            };
+
+            if c == '\n' {
+                let mut line_start = *iter;
+                line_start.set_line_offset(0);
+                let line = self.obj().text(&line_start, iter, false);
+                return match continuation(&line)? {
+                    Continuation::Prefix(prefix) => Some(Assist::Insert(format!("\n{prefix}"))),
+                    Continuation::End(len) if iter.ends_line() => Some(Assist::EndList(len)),
+                    Continuation::End(_) => None,
+                };
+            }
+
+            let next = Some(iter.char()).filter(|_| !iter.is_end());
+            if next == Some(c) && is_closing_char(c) {
+                return Some(Assist::SkipClosing);
+            }
+
+            let close = closing_char(c)?;
+            // Quotes directly after a word are most likely apostrophes or closing quotes
+            let mut previous = *iter;
+            let after_word =
+                close == c && previous.backward_char() && previous.char().is_alphanumeric();
+            if after_word || !should_close_before(next) {
+                return None;
+            }
+            Some(Assist::InsertPair(format!("{c}{close}")))
         }
     }
 
     impl TextBufferImpl for AardvarkTextBuffer {
+        fn begin_user_action(&self) {
+            self.user_action_depth.set(self.user_action_depth.get() + 1);
+            self.parent_begin_user_action();
+        }
+
+        fn end_user_action(&self) {
+            self.user_action_depth
+                .set(self.user_action_depth.get().saturating_sub(1));
+            self.parent_end_user_action();
+        }
+
+        fn undo(&self) {
+            self.undoing.set(true);
+            self.parent_undo();
+            self.undoing.set(false);
+        }
+
+        fn redo(&self) {
+            self.undoing.set(true);
+            self.parent_redo();
+            self.undoing.set(false);
+        }
+
         fn insert_text(&self, iter: &mut gtk::TextIter, new_text: &str) {
             if self.obj().inhibit_text_change() {
                 self.parent_insert_text(iter, new_text);
@@ -152,6 +279,37 @@ mod imp {
                 return;
             };
 
+            // Assists replace the typed text, so they're a single change of the document
+            let mut new_text = Cow::Borrowed(new_text);
+            let mut inside_pair = false;
+            match self.assist(iter, &new_text) {
+                Some(Assist::Insert(text)) => new_text = Cow::Owned(text),
+                Some(Assist::InsertPair(text)) => {
+                    new_text = Cow::Owned(text);
+                    inside_pair = true;
+                }
+                Some(Assist::SkipClosing) => {
+                    iter.forward_char();
+                    self.obj().place_cursor(iter);
+                    return;
+                }
+                Some(Assist::EndList(len)) => {
+                    let mut start = *iter;
+                    start.set_line_offset(0);
+                    let mut end = start;
+                    end.forward_chars(len as i32);
+                    self.obj().delete(&mut start, &mut end);
+                    *iter = start;
+                    return;
+                }
+                None => {}
+            }
+            let new_text = new_text.as_ref();
+
+            // Typing means we're around, see `AardvarkApplication::update_presence`
+            AardvarkApplication::default().record_activity();
+            let profiler = document.profiler();
+            profiler.begin_edit();
             let offset = iter.offset();
             self.obj().set_inhibit_text_change(true);
             let result = document.insert_text(offset, new_text);
@@ -161,9 +319,15 @@ mod imp {
             if let Err(error) = result {
                 error!("Failed to submit changes to the document: {error}");
             } else {
-                info!("inserting new text {} at pos {}", new_text, offset);
+                debug!(offset, len = new_text.len(), "inserting text");
                 self.parent_insert_text(iter, new_text);
+                if inside_pair {
+                    let mut cursor = *iter;
+                    cursor.backward_char();
+                    self.obj().place_cursor(&cursor);
+                }
             }
+            profiler.end_edit();
         }
 
         fn delete_range(&self, start: &mut gtk::TextIter, end: &mut gtk::TextIter) {
@@ -176,6 +340,9 @@ mod imp {
                 return;
             };
 
+            AardvarkApplication::default().record_activity();
+            let profiler = document.profiler();
+            profiler.begin_edit();
             let offset_start = start.offset();
             let offset_end = end.offset();
             self.obj().set_inhibit_text_change(true);
@@ -183,16 +350,18 @@ mod imp {
             self.obj().set_inhibit_text_change(false);
 
             // Only delete text from the buffer when the document was successfully updated
-            if let Err(error) = result {
-                error!("Failed to submit changes to the document: {error}");
-            } else {
-                info!(
-                    "deleting range at start {} end {}",
-                    offset_start, offset_end
-                );
-
-                self.parent_delete_range(start, end);
+            match result {
+                Err(error) => error!("Failed to submit changes to the document: {error}"),
+                Ok((offset_start, offset_end)) => {
+                    debug!(start = offset_start, end = offset_end, "deleting range");
+
+                    // The document never deletes parts of a character, keep the buffer the same
+                    start.set_offset(offset_start);
+                    end.set_offset(offset_end);
+                    self.parent_delete_range(start, end);
+                }
             }
+            profiler.end_edit();
         }
     }
 
@@ -217,9 +386,77 @@ impl AardvarkTextBuffer {
         self.imp().inhibit_text_change.set(inhibit_text_change);
     }
 
+    /// Whether the change with `version` still needs to be applied to the buffer, it isn't part
+    /// of the text we copied from the document.
+    fn advance_text_version(&self, version: u64) -> bool {
+        if version <= self.imp().text_version.get() {
+            return false;
+        }
+        self.imp().text_version.set(version);
+        true
+    }
+
     pub fn full_text(&self) -> String {
         self.text(&self.start_iter(), &self.end_iter(), true).into()
     }
+
+    /// The link under `iter`, if there is one.
+    pub fn link_at_iter(&self, iter: &gtk::TextIter) -> Option<String> {
+        let tag = self.tag_table().lookup(LINK_TAG)?;
+        if !iter.has_tag(&tag) {
+            return None;
+        }
+
+        let mut start = *iter;
+        if !start.starts_tag(Some(&tag)) {
+            start.backward_to_tag_toggle(Some(&tag));
+        }
+        let mut end = *iter;
+        end.forward_to_tag_toggle(Some(&tag));
+
+        Some(self.text(&start, &end, false).into())
+    }
+
+    fn update_links(&self) {
+        let Some(tag) = self.tag_table().lookup(LINK_TAG) else {
+            return;
+        };
+
+        self.remove_tag(&tag, &self.start_iter(), &self.end_iter());
+        for link in find_links(&self.full_text()) {
+            let start = self.iter_at_offset(link.start as i32);
+            let end = self.iter_at_offset(link.end as i32);
+            self.apply_tag(&tag, &start, &end);
+        }
+    }
+}
+
+/// Ranges of all links in `text`, in unicode characters.
+fn find_links(text: &str) -> Vec<Range<usize>> {
+    let mut links = Vec::new();
+    let mut offset = 0;
+
+    for segment in text.split_inclusive(char::is_whitespace) {
+        let word = segment.trim_end_matches(char::is_whitespace);
+        // Links may be wrapped in brackets or quotes, e.g. `(https://p2panda.org)`
+        if let Some(start) = LINK_PREFIXES
+            .iter()
+            .filter_map(|prefix| word.find(prefix))
+            .min()
+        {
+            let link = word[start..].trim_end_matches(LINK_TRAILING_PUNCTUATION);
+            if LINK_PREFIXES
+                .iter()
+                .any(|prefix| link.starts_with(prefix) && link.len() > prefix.len())
+            {
+                let start = offset + word[..start].chars().count();
+                links.push(start..start + link.chars().count());
+            }
+        }
+        offset += segment.chars().count();
+    }
+
+    links
 }
 
 fn style_scheme() -> Option<sourceview::StyleScheme> {
@@ -19,18 +19,36 @@
  */
 
 use std::cell::{Cell, OnceCell, RefCell};
+use std::collections::HashMap;
 
 use aardvark_doc::{
+    author::Author,
+    authors::Authors,
     document::{Document, DocumentId},
+    identity::PublicKey,
+    invite::Invite,
     service::Service,
 };
 
 use adw::{prelude::*, subclass::prelude::*};
+use gettextrs::{gettext, ngettext};
 use gtk::{gdk, gio, glib, glib::clone};
+use tracing::error;
 
 use crate::{
-    AardvarkApplication, AardvarkTextBuffer, ConnectionPopover, OpenPopover,
+    AardvarkApplication, BookmarksPopover, ConnectionPopover, HistorySidebar, OpenPopover,
+    TasksPopover,
+    application::is_text_file,
+    calendar_dialog::CalendarDialog,
     components::{MultilineEntry, ZoomLevelSelector},
+    connection_test_dialog::ConnectionTestDialog,
+    debug_dialog::DebugDialog,
+    document_view::DocumentView,
+    file_transfer::{self, FileTransfer},
+    git_mirror::GitMirrors,
+    linked_files::{self, LinkedFiles},
+    publish::{self, Publication, Publications},
+    status_bar::{StatusBar, sync_state_tooltip},
 };
 
 const BASE_TEXT_FONT_SIZE: f64 = 24.0;
@@ -44,7 +62,7 @@ mod imp {
     pub struct AardvarkWindow {
         // Template widgets
         #[template_child]
-        pub text_view: TemplateChild<sourceview::View>,
+        pub tab_view: TemplateChild<adw::TabView>,
         #[template_child]
         pub open_popover_button: TemplateChild<gtk::MenuButton>,
         #[template_child]
@@ -61,6 +79,40 @@ mod imp {
         pub connection_button: TemplateChild<gtk::MenuButton>,
         #[template_child]
         pub connection_button_label: TemplateChild<gtk::Label>,
+        #[template_child]
+        pub pending_changes_label: TemplateChild<gtk::Label>,
+        #[template_child]
+        pub publish_banner: TemplateChild<adw::Banner>,
+        #[template_child]
+        pub history_banner: TemplateChild<adw::Banner>,
+        #[template_child]
+        pub title_entry: TemplateChild<gtk::Entry>,
+        #[template_child]
+        pub subtitle_label: TemplateChild<gtk::Label>,
+        #[template_child]
+        pub toolbar_view: TemplateChild<adw::ToolbarView>,
+        #[template_child]
+        pub tasks_popover: TemplateChild<TasksPopover>,
+        #[template_child]
+        pub bookmarks_button: TemplateChild<gtk::MenuButton>,
+        #[template_child]
+        pub tasks_button: TemplateChild<gtk::MenuButton>,
+        #[template_child(id = "ShareButton")]
+        pub share_button: TemplateChild<gtk::MenuButton>,
+        #[template_child]
+        pub tasks_bottom_button: TemplateChild<gtk::MenuButton>,
+        #[template_child]
+        pub bookmarks_bottom_button: TemplateChild<gtk::MenuButton>,
+        #[template_child]
+        pub share_bottom_button: TemplateChild<gtk::MenuButton>,
+        #[template_child]
+        pub narrow_breakpoint: TemplateChild<adw::Breakpoint>,
+        #[template_child]
+        pub bookmarks_popover: TemplateChild<BookmarksPopover>,
+        #[template_child]
+        pub history_sidebar: TemplateChild<HistorySidebar>,
+        #[template_child]
+        pub status_bar: TemplateChild<StatusBar>,
         pub css_provider: gtk::CssProvider,
         pub font_size: Cell<f64>,
         #[property(get, set = Self::set_font_scale, default = 0.0)]
@@ -69,8 +121,38 @@ mod imp {
         pub zoom_level: Cell<f64>,
         #[property(get, construct_only)]
         pub service: OnceCell<Service>,
-        #[property(get, type = Document)]
-        document: RefCell<Option<Document>>,
+        /// Distraction-free fullscreen view of the selected document.
+        #[property(get, set = Self::set_focus_mode, explicit_notify)]
+        pub focus_mode: Cell<bool>,
+        /// Width of the text column in focus mode, in pixels.
+        #[property(get, set, default = 680)]
+        pub measure_width: Cell<i32>,
+        /// Show the latency of the editing pipeline on top of the documents.
+        #[property(get, set)]
+        pub show_profiler: Cell<bool>,
+        /// The document of the selected tab, `None` while the window has no tabs.
+        #[property(
+            name = "document",
+            get = Self::document,
+            set = Self::set_initial_document,
+            construct_only,
+            nullable,
+            type = Option<Document>
+        )]
+        initial_document: RefCell<Option<Document>>,
+        /// Whether to open a new document when the window isn't constructed for a specific one.
+        #[property(get, construct_only, default = true)]
+        create_document: Cell<bool>,
+        authors_handler: RefCell<Option<(Authors, glib::SignalHandlerId)>>,
+        text_handler: RefCell<Option<(Document, glib::SignalHandlerId)>>,
+        /// Handlers on the document of every page, the document outlives the page.
+        page_handlers: RefCell<HashMap<adw::TabPage, Vec<(glib::Object, glib::SignalHandlerId)>>>,
+        /// Bindings to the document of the selected tab.
+        document_bindings: RefCell<Vec<glib::Binding>>,
+        /// Where the document of the selected tab was published to, if it was.
+        publication: RefCell<Option<Publication>>,
+        /// Local file the document of the selected tab is saved to.
+        linked_file: RefCell<Option<gio::File>>,
     }
 
     #[glib::object_subclass]
@@ -83,6 +165,10 @@ mod imp {
             ZoomLevelSelector::static_type();
             MultilineEntry::static_type();
             OpenPopover::static_type();
+            TasksPopover::static_type();
+            BookmarksPopover::static_type();
+            HistorySidebar::static_type();
+            StatusBar::static_type();
 
             klass.bind_template();
 
@@ -96,43 +182,204 @@ mod imp {
                 window.set_font_scale(0.0);
             });
 
-            klass.add_binding_action(
-                gdk::Key::plus,
-                gdk::ModifierType::CONTROL_MASK,
-                "window.zoom-in",
-            );
-            klass.add_binding_action(
-                gdk::Key::KP_Add,
-                gdk::ModifierType::CONTROL_MASK,
-                "window.zoom-in",
-            );
-            klass.add_binding_action(
-                gdk::Key::minus,
-                gdk::ModifierType::CONTROL_MASK,
-                "window.zoom-out",
-            );
-            // gnome-text-editor uses this as well: probably to make it
-            // nicer for the US keyboard layout
-            klass.add_binding_action(
-                gdk::Key::equal,
-                gdk::ModifierType::CONTROL_MASK,
-                "window.zoom-out",
+            klass.install_action("window.new-tab", None, |window, _, _| {
+                let document = Document::new(&window.service(), None);
+                window.add_document(&document);
+            });
+            klass.install_action("window.close-tab", None, |window, _, _| {
+                let tab_view = &window.imp().tab_view;
+                if let Some(page) = tab_view.selected_page() {
+                    tab_view.close_page(&page);
+                }
+            });
+
+            klass.install_action("window.archive-document", None, |window, _, _| {
+                let Some(document) = window.document() else {
+                    return;
+                };
+                // Archiving closes the tab, keep the window open with a new document instead
+                if window.imp().tab_view.n_pages() == 1 {
+                    window.add_document(&Document::new(&window.service(), None));
+                }
+                document.set_archived(true);
+                window.add_toast(adw::Toast::new(&gettext(
+                    "Document archived, restore it from the list of documents",
+                )));
+            });
+
+            klass.install_action("window.trash-document", None, |window, _, _| {
+                let Some(document) = window.document() else {
+                    return;
+                };
+                if window.imp().tab_view.n_pages() == 1 {
+                    window.add_document(&Document::new(&window.service(), None));
+                }
+                document.set_trashed(true);
+                window.add_toast(adw::Toast::new(&gettext("Document moved to the trash")));
+            });
+
+            klass.install_action_async("window.save", None, |window, _, _| async move {
+                let file = window.imp().linked_file.borrow().clone();
+                match file {
+                    Some(file) => window.imp().save(&file).await,
+                    None => window.imp().save_as().await,
+                }
+            });
+            klass.install_action_async("window.save-as", None, |window, _, _| async move {
+                window.imp().save_as().await;
+            });
+
+            klass.install_property_action("window.focus-mode", "focus-mode");
+            klass.add_binding(gdk::Key::Escape, gdk::ModifierType::empty(), |window| {
+                if window.focus_mode() {
+                    window.set_focus_mode(false);
+                    glib::Propagation::Stop
+                } else {
+                    glib::Propagation::Proceed
+                }
+            });
+
+            klass.install_action("window.open-in-new-window", None, |window, _, _| {
+                let (Some(application), Some(document)) = (window.application(), window.document())
+                else {
+                    return;
+                };
+                // Both windows share the same document instance and therefore the same CRDT
+                let new_window =
+                    super::AardvarkWindow::new(&application, &window.service(), Some(&document));
+                new_window.present();
+            });
+
+            klass.install_action_async("window.export-history", None, |window, _, _| async move {
+                window.imp().export_history().await;
+            });
+            klass.install_action_async("window.mirror-to-git", None, |window, _, _| async move {
+                window.imp().mirror_to_git().await;
+            });
+            klass.install_action("window.copy-invite", None, |window, _, _| {
+                window.imp().copy_invite();
+            });
+            klass.install_action("window.stop-mirroring-to-git", None, |window, _, _| {
+                window.imp().stop_mirroring_to_git();
+            });
+            klass.install_action_async("window.publish", None, |window, _, _| async move {
+                window.imp().publish().await;
+            });
+
+            klass.install_action_async(
+                "window.restore-from-cold-storage",
+                None,
+                |window, _, _| async move {
+                    let Some(document) = window.document() else {
+                        return;
+                    };
+                    let message = match document.restore_from_cold_storage().await {
+                        Ok(true) => gettext("Document restored from cold storage"),
+                        Ok(false) => gettext("This document isn’t pinned in cold storage"),
+                        Err(error) => {
+                            error!("Failed to restore document from cold storage: {error}");
+                            gettext("Failed to restore from cold storage")
+                        }
+                    };
+                    window.add_toast(adw::Toast::new(&message));
+                },
             );
-            klass.add_binding_action(
-                gdk::Key::KP_Subtract,
-                gdk::ModifierType::CONTROL_MASK,
-                "window.zoom-out",
+
+            klass.install_action("window.show-calendar", None, |window, _, _| {
+                let dialog = CalendarDialog::new(&window.service().documents());
+                dialog.connect_document_activated(clone!(
+                    #[weak]
+                    window,
+                    move |_, document| {
+                        window.imp().show_document(document);
+                    }
+                ));
+                dialog.present(Some(window));
+            });
+
+            klass.install_action_async("window.insert-poll", None, |window, _, _| async move {
+                let view = window
+                    .imp()
+                    .tab_view
+                    .selected_page()
+                    .and_then(|page| page.child().downcast::<DocumentView>().ok());
+                if let Some(view) = view {
+                    view.insert_poll().await;
+                }
+            });
+
+            klass.install_action(
+                "window.scroll-to-position",
+                Some(glib::VariantTy::INT32),
+                |window, _, parameter| {
+                    let Some(position) = parameter.and_then(i32::from_variant) else {
+                        return;
+                    };
+                    if let Some(view) = window.imp().selected_view() {
+                        view.scroll_to_position(position);
+                    }
+                },
             );
-            klass.add_binding_action(
-                gdk::Key::_0,
-                gdk::ModifierType::CONTROL_MASK,
-                "window.zoom-one",
+
+            klass.install_action(
+                "window.go-to-position",
+                Some(glib::VariantTy::INT32),
+                |window, _, parameter| {
+                    let Some(position) = parameter.and_then(i32::from_variant) else {
+                        return;
+                    };
+                    if let Some(view) = window.imp().selected_view() {
+                        view.go_to_position(position);
+                    }
+                },
             );
-            klass.add_binding_action(
-                gdk::Key::KP_0,
-                gdk::ModifierType::CONTROL_MASK,
-                "window.zoom-one",
+
+            klass.install_action("window.show-bookmarks", None, |window, _, _| {
+                let imp = window.imp();
+                if imp.bookmarks_button.is_visible() {
+                    imp.bookmarks_button.popup();
+                } else {
+                    imp.bookmarks_bottom_button.popup();
+                }
+            });
+
+            klass.install_action(
+                "window.add-bookmark",
+                Some(glib::VariantTy::STRING),
+                |window, _, parameter| {
+                    let Some(name) = parameter.and_then(String::from_variant) else {
+                        return;
+                    };
+                    let Some(view) = window.imp().selected_view() else {
+                        return;
+                    };
+                    if let Err(error) = view.document().add_bookmark(view.cursor_position(), &name)
+                    {
+                        error!("Failed to add bookmark: {}", error);
+                    }
+                },
             );
+
+            klass.install_action("window.next-bookmark", None, |window, _, _| {
+                window.imp().go_to_bookmark(true);
+            });
+
+            klass.install_action("window.previous-bookmark", None, |window, _, _| {
+                window.imp().go_to_bookmark(false);
+            });
+
+            klass.install_action("window.test-connection", None, |window, _, _| {
+                let dialog = ConnectionTestDialog::new(&window.service());
+                dialog.present(Some(window));
+            });
+
+            klass.install_property_action("window.show-profiler", "show-profiler");
+
+            klass.install_action("window.show-debug-dialog", None, |window, _, _| {
+                if let Some(document) = window.document() {
+                    DebugDialog::new(&document).present(Some(window));
+                }
+            });
         }
 
         fn instance_init(obj: &glib::subclass::InitializingObject<Self>) {
@@ -145,9 +392,6 @@ mod imp {
         fn constructed(&self) {
             self.parent_constructed();
 
-            let buffer = AardvarkTextBuffer::new();
-            self.text_view.set_buffer(Some(&buffer));
-
             self.font_size.set(BASE_TEXT_FONT_SIZE);
             self.obj().set_font_scale(0.0);
             gtk::style_context_add_provider_for_display(
@@ -203,6 +447,78 @@ mod imp {
             ));
             self.obj().add_controller(zoom_gesture);
 
+            // Dropping text files onto the window opens them as new documents. Capture the drop
+            // before the text view, which would otherwise insert the file's uri.
+            let drop_target =
+                gtk::DropTarget::new(gdk::FileList::static_type(), gdk::DragAction::COPY);
+            drop_target.set_propagation_phase(gtk::PropagationPhase::Capture);
+            drop_target.connect_drop(|_, value, _, _| {
+                let Ok(file_list) = value.get::<gdk::FileList>() else {
+                    return false;
+                };
+
+                let app = AardvarkApplication::default();
+                let files: Vec<_> = file_list.files().into_iter().filter(is_text_file).collect();
+                for file in &files {
+                    app.import_file(file);
+                }
+
+                !files.is_empty()
+            });
+            self.obj().add_controller(drop_target);
+
+            self.title_entry.connect_changed(clone!(
+                #[weak]
+                window,
+                move |entry| {
+                    if let Some(document) = window.document() {
+                        document.set_title(entry.text().as_str());
+                    }
+                }
+            ));
+            // Return to the text once the title is entered
+            self.title_entry.connect_activate(clone!(
+                #[weak]
+                window,
+                move |_| {
+                    if let Some(view) = window.imp().selected_view() {
+                        view.grab_focus();
+                    }
+                }
+            ));
+
+            // Changes which were notified while the window was in the background are seen now
+            self.obj().connect_is_active_notify(|window| {
+                if !window.is_active() {
+                    return;
+                }
+                let app = AardvarkApplication::default();
+                for page in window
+                    .imp()
+                    .tab_view
+                    .pages()
+                    .iter::<adw::TabPage>()
+                    .flatten()
+                {
+                    if let Ok(view) = page.child().downcast::<DocumentView>() {
+                        app.withdraw_remote_changes(&view.document().id());
+                    }
+                }
+            });
+
+            self.measure_width.set(680);
+            // Leave focus mode when fullscreen is left some other way, e.g. by the window manager
+            self.obj().connect_fullscreened_notify(|window| {
+                if !window.is_fullscreen() {
+                    window.set_focus_mode(false);
+                }
+            });
+            AardvarkApplication::default()
+                .settings()
+                .bind_property("focus-mode-measure-width", &*self.obj(), "measure-width")
+                .sync_create()
+                .build();
+
             self.open_popover
                 .set_model(self.obj().service().documents());
 
@@ -210,12 +526,7 @@ mod imp {
                 #[weak(rename_to = this)]
                 self,
                 move |_, document| {
-                    let app = AardvarkApplication::default();
-                    if let Some(window) = app.window_for_document_id(&document.id()) {
-                        window.present();
-                    } else {
-                        this.set_document(document.to_owned());
-                    }
+                    this.show_document(document);
                 }
             ));
 
@@ -223,18 +534,103 @@ mod imp {
                 #[weak(rename_to = this)]
                 self,
                 move |button| {
-                    let document_id = Self::format_document_id(&this.obj().document().id());
+                    let Some(document) = this.obj().document() else {
+                        return;
+                    };
+                    let document_id = Self::format_document_id(&document.id());
                     let clipboard = button.display().clipboard();
                     clipboard.set(&document_id);
                     this.share_popover.popdown();
                 }
             ));
 
-            let document = Document::new(self.service.get().unwrap(), None);
-            self.set_document(document);
+            // Popovers can only have one parent, they move along with the buttons
+            self.narrow_breakpoint.connect_apply(clone!(
+                #[weak(rename_to = this)]
+                self,
+                move |_| this.move_popovers_to_bottom_bar(true)
+            ));
+            self.narrow_breakpoint.connect_unapply(clone!(
+                #[weak(rename_to = this)]
+                self,
+                move |_| this.move_popovers_to_bottom_bar(false)
+            ));
+
+            self.tab_view.connect_selected_page_notify(clone!(
+                #[weak(rename_to = this)]
+                self,
+                move |_| {
+                    this.update_selected_document();
+                }
+            ));
+
+            // Pages dragged in from another window are attached as well
+            self.tab_view.connect_page_attached(clone!(
+                #[weak(rename_to = this)]
+                self,
+                move |_, page, _| this.connect_page(page)
+            ));
+
+            self.tab_view.connect_close_page(clone!(
+                #[weak(rename_to = this)]
+                self,
+                #[upgrade_or]
+                glib::Propagation::Proceed,
+                move |tab_view, page| {
+                    this.disconnect_page(page);
+                    if let Ok(view) = page.child().downcast::<DocumentView>() {
+                        view.document().remove_view();
+                    }
+                    tab_view.close_page_finish(page, true);
+                    glib::Propagation::Stop
+                }
+            ));
+
+            // Close the window with the last tab, except when the tab was dragged to another window
+            self.tab_view.connect_page_detached(clone!(
+                #[weak(rename_to = this)]
+                self,
+                move |tab_view, page, _| {
+                    // Dragged to another window, which connects to the page again
+                    this.disconnect_page(page);
+                    if tab_view.n_pages() == 0 {
+                        this.obj().close();
+                    }
+                }
+            ));
+
+            self.tab_view.connect_create_window(clone!(
+                #[weak(rename_to = this)]
+                self,
+                #[upgrade_or]
+                None,
+                move |_| {
+                    let application = this.obj().application()?;
+                    let window =
+                        super::AardvarkWindow::new_empty(&application, &this.obj().service());
+                    window.present();
+                    Some(window.imp().tab_view.get())
+                }
+            ));
+
+            // Open a new empty document if the window wasn't constructed for a specific one
+            let document = self.initial_document.take().or_else(|| {
+                self.create_document
+                    .get()
+                    .then(|| Document::new(self.service.get().unwrap(), None))
+            });
+            if let Some(document) = document {
+                self.obj().add_document(&document);
+            }
 
             self.obj().connect_close_request(|window| {
-                window.document().set_subscribed(false);
+                let pages = window.imp().tab_view.pages();
+                for page in pages.iter::<adw::TabPage>().flatten() {
+                    window.imp().disconnect_page(&page);
+                    if let Ok(view) = page.child().downcast::<DocumentView>() {
+                        view.document().remove_view();
+                    }
+                }
                 glib::Propagation::Proceed
             });
         }
@@ -254,20 +650,120 @@ mod imp {
             self.obj().action_set_enabled("window.zoom-out", size > 1.0);
         }
 
-        fn set_document(&self, document: Document) {
+        fn set_focus_mode(&self, focus_mode: bool) {
+            if self.focus_mode.replace(focus_mode) == focus_mode {
+                return;
+            }
+
+            let obj = self.obj();
+            self.toolbar_view.set_reveal_top_bars(!focus_mode);
+            self.toolbar_view.set_reveal_bottom_bars(!focus_mode);
+            if focus_mode {
+                obj.fullscreen();
+            } else {
+                obj.unfullscreen();
+            }
+            obj.notify_focus_mode();
+        }
+
+        fn set_initial_document(&self, document: Option<Document>) {
+            // The tab view isn't ready yet during construction, `constructed()` takes care of
+            // actually opening the document
+            self.initial_document.replace(document);
+        }
+
+        fn document(&self) -> Option<Document> {
+            self.selected_view().map(|view| view.document())
+        }
+
+        pub(super) fn page_for_document_id(
+            &self,
+            document_id: &DocumentId,
+        ) -> Option<adw::TabPage> {
+            self.tab_view
+                .pages()
+                .iter::<adw::TabPage>()
+                .flatten()
+                .find(|page| {
+                    page.child()
+                        .downcast::<DocumentView>()
+                        .is_ok_and(|view| &view.document().id() == document_id)
+                })
+        }
+
+        /// Attach the popovers of the header bar to the buttons of the bottom bar, or back.
+        fn move_popovers_to_bottom_bar(&self, bottom: bool) {
+            for (header_button, bottom_button) in [
+                (&self.tasks_button, &self.tasks_bottom_button),
+                (&self.bookmarks_button, &self.bookmarks_bottom_button),
+                (&self.share_button, &self.share_bottom_button),
+            ] {
+                let (from, to) = if bottom {
+                    (header_button, bottom_button)
+                } else {
+                    (bottom_button, header_button)
+                };
+                let popover = from.popover();
+                from.set_popover(None::<&gtk::Widget>);
+                to.set_popover(popover.as_ref());
+            }
+        }
+
+        /// The view of the selected tab.
+        fn selected_view(&self) -> Option<DocumentView> {
+            self.tab_view
+                .selected_page()
+                .and_then(|page| page.child().downcast::<DocumentView>().ok())
+        }
+
+        /// Move the cursor to the next or previous bookmark of the selected document.
+        fn go_to_bookmark(&self, next: bool) {
+            let Some(view) = self.selected_view() else {
+                return;
+            };
+            let bookmarks = view.document().bookmarks();
+            let position = view.cursor_position();
+            let bookmark = if next {
+                bookmarks.next(position)
+            } else {
+                bookmarks.previous(position)
+            };
+            if let Some(bookmark) = bookmark {
+                view.go_to_position(bookmark.position());
+            }
+        }
+
+        /// Update the header bar for the document of the selected tab.
+        fn update_selected_document(&self) {
+            let Some(document) = self.document() else {
+                return;
+            };
             let document_id = Self::format_document_id(&document.id());
             self.share_code_label.set_text(&document_id);
-            self.text_view
-                .buffer()
-                .downcast::<AardvarkTextBuffer>()
-                .unwrap()
-                .set_document(&document);
+            self.tasks_popover.set_document(Some(&document));
+            self.bookmarks_popover.set_document(Some(&document));
+            self.history_sidebar.set_document(Some(&document));
+            self.status_bar.set_view(self.selected_view());
+            for property in [
+                "meeting-notes",
+                "heading-style",
+                "list-marker",
+                "wrap-column",
+                "discoverable",
+            ] {
+                self.obj()
+                    .add_action(&gio::PropertyAction::new(property, &document, property));
+            }
+
             let authors = document.authors();
             self.connection_button
-                .set_popover(Some(&ConnectionPopover::new(&authors)));
+                .set_popover(Some(&ConnectionPopover::new(
+                    &self.obj().service(),
+                    &document,
+                )));
             // TODO: we need to do the same as fractal to allow gettext string substitution
             //self.connection_button.set_tooltip_text(gettext!("{} People Connected", authors.n_items()));
-            authors.connect_items_changed(clone!(
+            let handler = authors.connect_items_changed(clone!(
                 #[weak(rename_to = this)]
                 self,
                 move |authors, _, _, _| {
@@ -278,16 +774,518 @@ mod imp {
             self.connection_button_label
                 .set_label(&format!("{}", authors.n_items()));
 
-            document.set_subscribed(true);
-            let old_document = self.document.replace(Some(document));
+            if let Some((authors, handler)) = self.authors_handler.replace(Some((authors, handler)))
+            {
+                authors.disconnect(handler);
+            }
 
-            if let Some(old_document) = old_document {
-                old_document.set_subscribed(false);
+            let data_dir = self
+                .obj()
+                .service()
+                .data_dir()
+                .path()
+                .expect("Valid file path");
+            self.publication
+                .replace(Publications::load(&data_dir).get(&document.id()));
+            self.set_linked_file(LinkedFiles::load(&data_dir).get(&document.id()));
+            self.update_git_mirror_action();
+            let handler = document.connect_text_notify(clone!(
+                #[weak(rename_to = this)]
+                self,
+                move |_| {
+                    this.update_publish_banner();
+                }
+            ));
+            if let Some((document, handler)) =
+                self.text_handler.replace(Some((document.clone(), handler)))
+            {
+                document.disconnect(handler);
+            }
+            self.update_publish_banner();
+
+            // The title entry follows remote changes of the title, changes in the entry are
+            // applied to the selected document by its `changed` handler
+            for binding in self.document_bindings.take() {
+                binding.unbind();
             }
+            let bindings = vec![
+                document
+                    .bind_property("title", &*self.title_entry, "text")
+                    .sync_create()
+                    .build(),
+                // The most recent changes are shown while older ones are still on their way
+                document
+                    .bind_property("loading-history", &*self.history_banner, "revealed")
+                    .sync_create()
+                    .build(),
+                document
+                    .bind_property("name", &*self.title_entry, "placeholder-text")
+                    .transform_to(|_, name: Option<String>| {
+                        Some(name.unwrap_or_else(|| gettext("Untitled Document")))
+                    })
+                    .sync_create()
+                    .build(),
+                document
+                    .bind_property("name", &*self.obj(), "title")
+                    .transform_to(|_, name: Option<String>| {
+                        Some(name.unwrap_or_else(|| gettext("Aardvark")))
+                    })
+                    .sync_create()
+                    .build(),
+                document
+                    .bind_property(
+                        "pending-local-changes",
+                        &*self.pending_changes_label,
+                        "label",
+                    )
+                    .transform_to(|_, pending: u32| Some(pending.to_string()))
+                    .sync_create()
+                    .build(),
+                document
+                    .bind_property(
+                        "pending-local-changes",
+                        &*self.pending_changes_label,
+                        "visible",
+                    )
+                    .transform_to(|_, pending: u32| Some(pending > 0))
+                    .sync_create()
+                    .build(),
+                document
+                    .bind_property(
+                        "pending-local-changes",
+                        &*self.connection_button,
+                        "tooltip-text",
+                    )
+                    .transform_to(|_, pending: u32| {
+                        let tooltip = (pending > 0).then(|| {
+                            ngettext(
+                                "Offline, {} change will sync later",
+                                "Offline, {} changes will sync later",
+                                pending,
+                            )
+                            .replace("{}", &pending.to_string())
+                        });
+                        Some(tooltip)
+                    })
+                    .sync_create()
+                    .build(),
+            ];
+            self.document_bindings.replace(bindings);
 
             self.obj().notify("document");
         }
 
+        /// Show the name of the linked file below the title, or that the session is anonymous.
+        fn set_linked_file(&self, file: Option<gio::File>) {
+            let subtitle = file
+                .as_ref()
+                .and_then(|file| file.basename())
+                .map(|name| name.display().to_string())
+                .unwrap_or_else(|| {
+                    if self.obj().service().anonymous() {
+                        gettext("Anonymous Session")
+                    } else {
+                        String::new()
+                    }
+                });
+            self.subtitle_label.set_visible(!subtitle.is_empty());
+            self.subtitle_label.set_label(&subtitle);
+            self.linked_file.replace(file);
+        }
+
+        /// Ask for a file to save the selected document to and link the document with it.
+        async fn save_as(&self) {
+            let Some(document) = self.document() else {
+                return;
+            };
+            let name = document
+                .name()
+                .unwrap_or_else(|| gettext("Untitled Document"));
+            let transfer = FileTransfer::new(&gettext("Save As"))
+                .initial_name(&format!("{name}.md"))
+                .initial_file(self.linked_file.borrow().as_ref());
+            let Some(file) = self.choose_file(transfer.save(Some(&*self.obj())).await) else {
+                return;
+            };
+
+            let data_dir = self
+                .obj()
+                .service()
+                .data_dir()
+                .path()
+                .expect("Valid file path");
+            if let Err(error) = LinkedFiles::load(&data_dir).set(&document.id(), &file) {
+                error!("Failed to remember linked file: {error}");
+            }
+            if self.document().as_ref() == Some(&document) {
+                self.set_linked_file(Some(file.clone()));
+            }
+
+            self.save(&file).await;
+        }
+
+        /// The file chosen in a file dialog, failures to show the dialog are reported to the user.
+        fn choose_file(&self, result: Result<Option<gio::File>, glib::Error>) -> Option<gio::File> {
+            result.unwrap_or_else(|error| {
+                error!("Failed to choose file: {error}");
+                self.obj()
+                    .add_toast(adw::Toast::new(&gettext("Failed to open file chooser")));
+                None
+            })
+        }
+
+        /// Export the latest state of the selected document to `file`.
+        async fn save(&self, file: &gio::File) {
+            let Some(document) = self.document() else {
+                return;
+            };
+            if let Err(error) = linked_files::save(&document, file).await {
+                error!("Failed to save to {}: {error}", file.uri());
+                self.obj()
+                    .add_toast(adw::Toast::new(&gettext("Failed to save document")));
+            }
+        }
+
+        /// Ask for a file to save the history of the selected document to as patch series.
+        async fn export_history(&self) {
+            let Some(document) = self.document() else {
+                return;
+            };
+            let name = document
+                .name()
+                .unwrap_or_else(|| gettext("Untitled Document"));
+            let transfer = FileTransfer::new(&gettext("Export History"))
+                .accept_label(&gettext("_Export"))
+                .initial_name(&format!("{name}.patch"));
+            let Some(file) = self.choose_file(transfer.save(Some(&*self.obj())).await) else {
+                return;
+            };
+
+            let patches = match document.export_history() {
+                Ok(patches) => patches,
+                Err(error) => {
+                    error!("Failed to export history: {error}");
+                    self.obj()
+                        .add_toast(adw::Toast::new(&gettext("Failed to export history")));
+                    return;
+                }
+            };
+            if let Err(error) = file_transfer::write(&file, patches.into_bytes(), false).await {
+                error!("Failed to export history to {}: {error}", file.uri());
+                self.obj()
+                    .add_toast(adw::Toast::new(&gettext("Failed to export history")));
+            }
+        }
+
+        /// Copy an invite to the selected document, with ourselves and the authors currently
+        /// online as peers to connect to.
+        fn copy_invite(&self) {
+            let Some(document) = self.document() else {
+                return;
+            };
+            let mut authors: Vec<Author> = document
+                .authors()
+                .iter::<Author>()
+                .filter_map(Result::ok)
+                .filter(|author| author.is_this_device() || author.is_online())
+                .collect();
+            authors.sort_by_key(|author| !author.is_this_device());
+            let peers: Vec<PublicKey> = authors.iter().map(Author::public_key).collect();
+
+            let invite = Invite::new(&document.id(), &peers);
+            self.obj().clipboard().set_text(&invite.to_string());
+            self.share_popover.popdown();
+            self.obj()
+                .add_toast(adw::Toast::new(&gettext("Invite copied to clipboard")));
+        }
+
+        /// Ask for a git repository to commit the selected document to from now on.
+        async fn mirror_to_git(&self) {
+            let Some(document) = self.document() else {
+                return;
+            };
+            let data_dir = self
+                .obj()
+                .service()
+                .data_dir()
+                .path()
+                .expect("Valid file path");
+            let repository = GitMirrors::load(&data_dir)
+                .get(&document.id())
+                .map(gio::File::for_path);
+            let transfer = FileTransfer::new(&gettext("Mirror to Git Repository"))
+                .accept_label(&gettext("_Mirror"))
+                .initial_folder(repository.as_ref());
+            let Some(repository) = self
+                .choose_file(transfer.select_folder(Some(&*self.obj())).await)
+                .and_then(|folder| folder.path())
+            else {
+                return;
+            };
+
+            let git_mirrors = GitMirrors::load(&data_dir);
+            if let Err(error) = git_mirrors.set(&document.id(), Some(&repository)) {
+                error!("Failed to remember git mirror: {error}");
+                return;
+            }
+            if let Err(error) = git_mirrors.commit_snapshot(&document) {
+                error!("Failed to commit {} to git: {error}", document.id());
+                self.obj().add_toast(adw::Toast::new(&gettext(
+                    "Failed to commit to git repository",
+                )));
+            }
+            self.update_git_mirror_action();
+        }
+
+        fn stop_mirroring_to_git(&self) {
+            let Some(document) = self.document() else {
+                return;
+            };
+            let data_dir = self
+                .obj()
+                .service()
+                .data_dir()
+                .path()
+                .expect("Valid file path");
+            if let Err(error) = GitMirrors::load(&data_dir).set(&document.id(), None) {
+                error!("Failed to forget git mirror: {error}");
+            }
+            self.update_git_mirror_action();
+        }
+
+        fn update_git_mirror_action(&self) {
+            let data_dir = self
+                .obj()
+                .service()
+                .data_dir()
+                .path()
+                .expect("Valid file path");
+            let mirrored = self
+                .document()
+                .is_some_and(|document| GitMirrors::load(&data_dir).get(&document.id()).is_some());
+            self.obj()
+                .action_set_enabled("window.stop-mirroring-to-git", mirrored);
+        }
+
+        /// Show how far the published snapshot of the selected document is behind.
+        fn update_publish_banner(&self) {
+            let behind = match (self.publication.borrow().as_ref(), self.document()) {
+                (Some(publication), Some(document)) => publication.behind(&document),
+                _ => 0,
+            };
+
+            if behind > 0 {
+                self.publish_banner.set_title(
+                    &ngettext(
+                        "Published version is behind by {} change",
+                        "Published version is behind by {} changes",
+                        behind as u32,
+                    )
+                    .replace("{}", &behind.to_string()),
+                );
+            }
+            self.publish_banner.set_revealed(behind > 0);
+        }
+
+        /// Publish the selected document to the web, or update the published version.
+        async fn publish(&self) {
+            let Some(document) = self.document() else {
+                return;
+            };
+            let location = self
+                .publication
+                .borrow()
+                .as_ref()
+                .map(|publication| publication.location.clone());
+            let location = match location {
+                Some(location) => location,
+                None => match self.choose_publish_location(&document).await {
+                    Some(location) => location,
+                    None => return,
+                },
+            };
+
+            let obj = self.obj();
+            match publish::publish(&document, &location).await {
+                Ok(publication) => {
+                    let data_dir = obj.service().data_dir().path().expect("Valid file path");
+                    if let Err(error) =
+                        Publications::load(&data_dir).set(&document.id(), &publication)
+                    {
+                        error!("Failed to remember publication: {error}");
+                    }
+                    // The selected tab could have changed in the meantime
+                    if self.document().as_ref() == Some(&document) {
+                        self.publication.replace(Some(publication));
+                        self.update_publish_banner();
+                    }
+                    obj.add_toast(adw::Toast::new(&gettext("Snapshot published")));
+                }
+                Err(error) => {
+                    error!("Failed to publish {}: {error}", location.uri());
+                    obj.add_toast(adw::Toast::new(&gettext("Failed to publish snapshot")));
+                }
+            }
+        }
+
+        /// Page in the configured publishing folder, or ask the user where to save the page.
+        async fn choose_publish_location(&self, document: &Document) -> Option<gio::File> {
+            let base = Some(AardvarkApplication::default().settings().publish_location())
+                .filter(|base| !base.is_empty());
+            if let Some(base) = base {
+                return Some(gio::File::for_uri(&base).child(format!("{}.html", document.id())));
+            }
+
+            let name = document
+                .name()
+                .unwrap_or_else(|| gettext("Untitled Document"));
+            let transfer = FileTransfer::new(&gettext("Publish Snapshot"))
+                .accept_label(&gettext("_Publish"))
+                .initial_name(&format!("{name}.html"));
+            self.choose_file(transfer.save(Some(&*self.obj())).await)
+        }
+
+        pub(super) fn add_page(&self, document: &Document) -> adw::TabPage {
+            let view = DocumentView::new(document);
+            let page = self.tab_view.append(&view);
+
+            let obj = self.obj();
+            obj.bind_property("focus-mode", &view, "focus-mode")
+                .sync_create()
+                .build();
+            obj.bind_property("measure-width", &view, "measure-width")
+                .sync_create()
+                .build();
+            obj.bind_property("show-profiler", &view, "show-profiler")
+                .sync_create()
+                .build();
+
+            document
+                .bind_property("name", &page, "title")
+                .transform_to(|_, name: Option<String>| {
+                    Some(name.unwrap_or_else(|| gettext("Untitled Document")))
+                })
+                .sync_create()
+                .build();
+
+            // Show a spinner in the tab while we try to find peers again
+            document
+                .bind_property("reconnect-attempt", &page, "loading")
+                .transform_to(|_, attempt: u32| Some(attempt > 0))
+                .sync_create()
+                .build();
+
+            document.add_view();
+            page
+        }
+
+        /// Follow the shared document of a page, see `disconnect_page`.
+        fn connect_page(&self, page: &adw::TabPage) {
+            let Ok(view) = page.child().downcast::<DocumentView>() else {
+                return;
+            };
+            let document = view.document();
+
+            page.set_tooltip(&sync_state_tooltip(&document));
+            let update_tooltip = clone!(
+                #[weak]
+                page,
+                #[weak]
+                document,
+                move || {
+                    page.set_tooltip(&sync_state_tooltip(&document));
+                }
+            );
+            let peers = document.peers();
+            let peers_handler = peers.connect_items_changed(clone!(
+                #[strong]
+                update_tooltip,
+                move |_, _, _, _| update_tooltip()
+            ));
+            let reconnect_handler = document.connect_reconnect_attempt_notify(clone!(
+                #[strong]
+                update_tooltip,
+                move |_| update_tooltip()
+            ));
+            let subscribed_handler = document.connect_subscribed_notify(move |_| update_tooltip());
+
+            let conflict_handler = document.connect_conflict_detected(clone!(
+                #[weak(rename_to = this)]
+                self,
+                #[weak]
+                page,
+                move |_, start, _, author| {
+                    if this.tab_view.selected_page().as_ref() != Some(&page) {
+                        return;
+                    }
+                    let toast = adw::Toast::builder()
+                        .title(
+                            gettext("Edited at the same time as {}").replace("{}", &author.name()),
+                        )
+                        .button_label(gettext("_Review"))
+                        .action_name("window.go-to-position")
+                        .action_target(&start.to_variant())
+                        .build();
+                    this.obj().add_toast(toast);
+                }
+            ));
+
+            // Archived documents aren't synced, the page goes away with the subscription
+            let archived_handler = document.connect_archived_notify(clone!(
+                #[weak(rename_to = this)]
+                self,
+                #[weak]
+                page,
+                move |document| {
+                    if document.archived() {
+                        this.tab_view.close_page(&page);
+                    }
+                }
+            ));
+            let trashed_handler = document.connect_trashed_at_notify(clone!(
+                #[weak(rename_to = this)]
+                self,
+                #[weak]
+                page,
+                move |document| {
+                    if document.trashed_at().is_some() {
+                        this.tab_view.close_page(&page);
+                    }
+                }
+            ));
+
+            let document = document.upcast::<glib::Object>();
+            self.page_handlers.borrow_mut().insert(
+                page.clone(),
+                vec![
+                    (peers.upcast(), peers_handler),
+                    (document.clone(), reconnect_handler),
+                    (document.clone(), subscribed_handler),
+                    (document.clone(), conflict_handler),
+                    (document.clone(), archived_handler),
+                    (document, trashed_handler),
+                ],
+            );
+        }
+
+        /// Disconnect the handlers `connect_page` connected to the document of a closed page.
+        fn disconnect_page(&self, page: &adw::TabPage) {
+            let handlers = self.page_handlers.borrow_mut().remove(page);
+            for (object, handler) in handlers.into_iter().flatten() {
+                object.disconnect(handler);
+            }
+        }
+
+        /// Raise the window already showing the document or open it in a new tab.
+        fn show_document(&self, document: &Document) {
+            let app = AardvarkApplication::default();
+            if let Some(window) = app.window_for_document_id(&document.id()) {
+                window.present_document(&document.id());
+            } else {
+                self.obj().add_document(document);
+            }
+        }
+
         fn format_document_id(document_id: &DocumentId) -> String {
             document_id
                 .to_string()
@@ -319,13 +1317,49 @@ glib::wrapper! {
 }
 
 impl AardvarkWindow {
-    pub fn new<P: IsA<gtk::Application>>(application: &P, service: &Service) -> Self {
+    pub fn new<P: IsA<gtk::Application>>(
+        application: &P,
+        service: &Service,
+        document: Option<&Document>,
+    ) -> Self {
         glib::Object::builder()
             .property("application", application)
             .property("service", service)
+            .property("document", document)
             .build()
     }
 
+    /// Window used as target when a tab is dragged out of another window.
+    fn new_empty<P: IsA<gtk::Application>>(application: &P, service: &Service) -> Self {
+        glib::Object::builder()
+            .property("application", application)
+            .property("service", service)
+            .property("create-document", false)
+            .build()
+    }
+
+    /// Open a document in a new tab, or select its tab if it's already open in this window.
+    pub fn add_document(&self, document: &Document) {
+        let imp = self.imp();
+        let page = imp
+            .page_for_document_id(&document.id())
+            .unwrap_or_else(|| imp.add_page(document));
+        imp.tab_view.set_selected_page(&page);
+    }
+
+    pub fn has_document(&self, document_id: &DocumentId) -> bool {
+        self.imp().page_for_document_id(document_id).is_some()
+    }
+
+    /// Select the tab of the given document and raise the window.
+    pub fn present_document(&self, document_id: &DocumentId) {
+        let imp = self.imp();
+        if let Some(page) = imp.page_for_document_id(document_id) {
+            imp.tab_view.set_selected_page(&page);
+        }
+        self.present();
+    }
+
     pub fn add_toast(&self, toast: adw::Toast) {
         self.imp().toast_overlay.add_toast(toast);
     }
@@ -0,0 +1,2 @@
+ALTER TABLE documents ADD COLUMN created_at INTEGER;
+ALTER TABLE documents ADD COLUMN last_edited INTEGER;
@@ -0,0 +1,4 @@
+CREATE TABLE IF NOT EXISTS blocked_authors (
+    public_key          TEXT NOT NULL PRIMARY KEY,
+    blocked_at		INTEGER
+);
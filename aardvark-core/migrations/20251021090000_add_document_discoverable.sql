@@ -0,0 +1 @@
+ALTER TABLE documents ADD COLUMN discoverable INTEGER NOT NULL DEFAULT 0;
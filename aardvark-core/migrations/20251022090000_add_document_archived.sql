@@ -0,0 +1 @@
+ALTER TABLE documents ADD COLUMN archived INTEGER NOT NULL DEFAULT 0;
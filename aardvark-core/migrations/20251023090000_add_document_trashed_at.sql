@@ -0,0 +1 @@
+ALTER TABLE documents ADD COLUMN trashed_at INTEGER;
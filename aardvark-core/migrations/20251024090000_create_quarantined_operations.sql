@@ -0,0 +1,8 @@
+CREATE TABLE IF NOT EXISTS quarantined_operations (
+    document_id		TEXT NOT NULL,
+    log_type		TEXT NOT NULL,
+    header		BLOB NOT NULL,
+    body		BLOB,
+    reason		TEXT NOT NULL,
+    quarantined_at	INTEGER
+);
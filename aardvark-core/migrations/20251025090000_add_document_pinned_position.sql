@@ -0,0 +1,2 @@
+ALTER TABLE documents ADD COLUMN pinned INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE documents ADD COLUMN position INTEGER;
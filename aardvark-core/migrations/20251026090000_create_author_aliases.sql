@@ -0,0 +1,7 @@
+CREATE TABLE IF NOT EXISTS author_aliases (
+    public_key          TEXT NOT NULL,
+    document_id 	TEXT NOT NULL,
+    alias		TEXT NOT NULL,
+    UNIQUE(public_key, document_id),
+    FOREIGN KEY(document_id) REFERENCES documents(document_id)
+);
@@ -0,0 +1,31 @@
+//! Anonymous sessions, joining documents with a throwaway identity.
+//!
+//! The identity of an anonymous session is never stored, e.g. for a workshop where people
+//! shouldn't be recognizable afterwards. Authors in anonymous sessions announce it with an
+//! ephemeral message whenever peers join a document, so the peers call them "Anonymous" instead
+//! of the name derived from their public key.
+
+/// The whole ephemeral message announcing an anonymous session.
+const ANONYMOUS_ANNOUNCEMENT: &[u8] = b"aardvark-anonymous";
+
+pub fn encode_anonymous_announcement() -> Vec<u8> {
+    ANONYMOUS_ANNOUNCEMENT.to_vec()
+}
+
+/// Whether the ephemeral message `bytes` announces an anonymous session.
+pub fn is_anonymous_announcement(bytes: &[u8]) -> bool {
+    bytes == ANONYMOUS_ANNOUNCEMENT
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::presence::{Presence, encode_presence};
+
+    #[test]
+    fn anonymous_announcement() {
+        assert!(is_anonymous_announcement(&encode_anonymous_announcement()));
+        assert!(!is_anonymous_announcement(&encode_presence(Presence::Away)));
+        assert!(!is_anonymous_announcement(b"aardvark-anonymous-not"));
+    }
+}
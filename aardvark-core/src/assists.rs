@@ -0,0 +1,69 @@
+//! Assists for typing markdown, like continuing a list when starting a new line.
+
+/// Brackets and quotes which are closed automatically, with their closing character.
+const PAIRS: [(char, char); 5] = [('(', ')'), ('[', ']'), ('{', '}'), ('"', '"'), ('`', '`')];
+
+/// What to do when starting a new line after `line`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Continuation {
+    /// Start the new line with this prefix, e.g. the marker of the next list item.
+    Prefix(String),
+    /// The line is an empty list item or quote, remove the first `n` characters of the line
+    /// instead of starting a new one, which ends the list.
+    End(usize),
+}
+
+/// The closing character for `c`, if `c` opens a pair which is closed automatically.
+pub fn closing_char(c: char) -> Option<char> {
+    PAIRS
+        .iter()
+        .find(|(open, _)| *open == c)
+        .map(|(_, close)| *close)
+}
+
+/// Whether `c` closes a pair which is closed automatically.
+pub fn is_closing_char(c: char) -> bool {
+    PAIRS.iter().any(|(_, close)| *close == c)
+}
+
+/// Whether to close a pair opened in front of `next`, the character after the cursor.
+///
+/// Pairs aren't closed in the middle of a word, where the user most likely wraps existing text.
+pub fn should_close_before(next: Option<char>) -> bool {
+    next.is_none_or(|next| next.is_whitespace() || is_closing_char(next))
+}
+
+/// How to continue `line`, the text from the start of the line to the cursor, on a new line.
+///
+/// Lists, task lists and block quotes are continued, keeping the indentation. Ordered lists
+/// continue with the next number and new tasks are always unchecked.
+pub fn continuation(line: &str) -> Option<Continuation> {
+    let trimmed = line.trim_start();
+    let indent = &line[..line.len() - trimmed.len()];
+
+    let (prefix, rest) = if let Some(rest) = trimmed.strip_prefix("> ") {
+        (">".to_owned(), rest)
+    } else if let Some(marker) = ['-', '*', '+']
+        .into_iter()
+        .find(|marker| trimmed.starts_with(*marker) && trimmed[1..].starts_with(' '))
+    {
+        let rest = &trimmed[2..];
+        match rest.get(..4) {
+            Some("[ ] " | "[x] " | "[X] ") => (format!("{marker} [ ]"), &rest[4..]),
+            _ => (marker.to_string(), rest),
+        }
+    } else {
+        let digits = trimmed.chars().take_while(char::is_ascii_digit).count();
+        let rest = trimmed[digits..].strip_prefix(". ")?;
+        let number: u64 = trimmed[..digits].parse().ok()?;
+        if rest.trim().is_empty() {
+            return Some(Continuation::End(line.chars().count()));
+        }
+        return Some(Continuation::Prefix(format!("{indent}{}. ", number + 1)));
+    };
+
+    if rest.trim().is_empty() {
+        return Some(Continuation::End(line.chars().count()));
+    }
+    Some(Continuation::Prefix(format!("{indent}{prefix} ")))
+}
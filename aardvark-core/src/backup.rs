@@ -0,0 +1,317 @@
+//! Backing up an identity together with all its documents into a single file.
+//!
+//! A backup contains the private key, the list of documents and every operation we stored for
+//! them, encrypted with a passphrase like a keyfile. Restoring it doesn't need any peer to be
+//! online, the operations are validated and stored as if they were synced.
+//!
+//! ```text
+//! magic (16 bytes) | salt (16 bytes) | nonce (24 bytes) | encrypted CBOR of `Backup`
+//! ```
+use anyhow::{Context, Result};
+use p2panda_core::cbor::{decode_cbor, encode_cbor};
+use p2panda_core::{Body, Extension, Header, PrivateKey, PublicKey};
+use p2panda_store::LogStore;
+use p2panda_store::OperationStore as TraitOperationStore;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::document::DocumentId;
+use crate::integrity::verify_log;
+use crate::keyfile;
+use crate::operation::{AardvarkExtensions, LogType};
+use crate::store::{DocumentStore, LogId, OperationStore};
+
+/// Identifies the format of a backup, the last byte is the version.
+const BACKUP_MAGIC: &[u8] = b"aardvark-backup\x01";
+
+/// A document with all stored operations of all its authors.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BackupDocument {
+    #[serde(rename = "i")]
+    pub id: DocumentId,
+    #[serde(rename = "n")]
+    pub name: Option<String>,
+    #[serde(rename = "ar", default)]
+    pub archived: bool,
+    #[serde(rename = "p", default)]
+    pub pinned: bool,
+    #[serde(rename = "a")]
+    pub authors: Vec<PublicKey>,
+    /// Encoded header and body of every operation, ordered by log and sequence number.
+    #[serde(rename = "o")]
+    operations: Vec<(Vec<u8>, Option<Vec<u8>>)>,
+}
+
+/// Identity and documents of a backup.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Backup {
+    #[serde(rename = "k")]
+    private_key: Vec<u8>,
+    #[serde(rename = "d")]
+    pub documents: Vec<BackupDocument>,
+}
+
+impl Backup {
+    pub fn private_key(&self) -> Result<PrivateKey> {
+        Ok(PrivateKey::try_from(self.private_key.as_slice())?)
+    }
+
+    /// Encrypt the backup with `passphrase`.
+    ///
+    /// Deriving the key from the passphrase is deliberately slow, don't call this from an async
+    /// context.
+    pub fn seal(&self, passphrase: &str) -> Result<Vec<u8>> {
+        let bytes = encode_cbor(self)?;
+        Ok(keyfile::seal(BACKUP_MAGIC, &bytes, passphrase)?)
+    }
+
+    /// Decrypt a backup written by [`Self::seal`].
+    pub fn open(sealed: &[u8], passphrase: &str) -> Result<Self> {
+        let bytes = keyfile::open(BACKUP_MAGIC, sealed, passphrase)?;
+        let backup: Self = decode_cbor(bytes.as_slice())?;
+        backup
+            .private_key()
+            .context("backup contains invalid private key")?;
+        Ok(backup)
+    }
+
+    /// Encode the documents without the identity, to restore them once the identity is in use.
+    pub fn encode_documents(&self) -> Result<Vec<u8>> {
+        Ok(encode_cbor(&self.documents)?)
+    }
+
+    pub fn decode_documents(bytes: &[u8]) -> Result<Vec<BackupDocument>> {
+        Ok(decode_cbor(bytes)?)
+    }
+}
+
+/// All documents in the store, including the ones in the trash.
+pub(crate) async fn collect(
+    document_store: &DocumentStore,
+    operation_store: &OperationStore,
+    private_key: &PrivateKey,
+) -> Result<Backup> {
+    let mut documents = Vec::new();
+    for document in document_store.documents().await? {
+        let mut operations = Vec::new();
+        for author in &document.authors {
+            for log_type in [LogType::Delta, LogType::Snapshot] {
+                let log_id = LogId::new(log_type, &document.id);
+                let log = operation_store
+                    .get_log(&author.public_key, &log_id, None)
+                    .await?
+                    .unwrap_or_default();
+                operations.extend(
+                    log.into_iter().map(|(header, body)| {
+                        (header.to_bytes(), body.as_ref().map(Body::to_bytes))
+                    }),
+                );
+            }
+        }
+
+        documents.push(BackupDocument {
+            id: document.id,
+            name: document.name,
+            archived: document.archived,
+            pinned: document.pinned,
+            authors: document
+                .authors
+                .iter()
+                .map(|author| author.public_key)
+                .collect(),
+            operations,
+        });
+    }
+
+    Ok(Backup {
+        private_key: private_key.as_bytes().to_vec(),
+        documents,
+    })
+}
+
+/// Add the documents of a backup and their operations to the store.
+///
+/// Operations we already have are skipped. Logs which don't pass verification aren't restored,
+/// they are synced from peers instead. Returns the number of stored operations.
+pub(crate) async fn restore(
+    document_store: &DocumentStore,
+    operation_store: &mut OperationStore,
+    documents: &[BackupDocument],
+) -> Result<u64> {
+    let mut restored = 0;
+    for document in documents {
+        document_store.add_document(&document.id).await?;
+        if document.name.is_some() {
+            document_store
+                .set_name_for_document(&document.id, document.name.clone())
+                .await?;
+        }
+        if document.archived {
+            document_store
+                .set_archived_for_document(&document.id, true)
+                .await?;
+        }
+        if document.pinned {
+            document_store
+                .set_pinned_for_document(&document.id, true)
+                .await?;
+        }
+        for author in &document.authors {
+            document_store.add_author(&document.id, author).await?;
+        }
+
+        for author in &document.authors {
+            for log_type in [LogType::Delta, LogType::Snapshot] {
+                let log = match decode_log(&document.operations, author, log_type) {
+                    Ok(log) => log,
+                    Err(error) => {
+                        warn!(document = %document.id, %author, "Skipping log of backup: {error}");
+                        continue;
+                    }
+                };
+                if let Err(reason) = verify_log(&document.id, author, log_type, &log) {
+                    warn!(document = %document.id, %author, "Skipping log of backup: {reason}");
+                    continue;
+                }
+
+                let log_id = LogId::new(log_type, &document.id);
+                for (header, body) in log {
+                    let hash = header.hash();
+                    if operation_store.has_operation(hash).await? {
+                        continue;
+                    }
+                    operation_store
+                        .insert_operation(
+                            hash,
+                            &header,
+                            body.as_ref(),
+                            header.to_bytes().as_slice(),
+                            &log_id,
+                        )
+                        .await?;
+                    restored += 1;
+                }
+            }
+        }
+    }
+    info!(
+        documents = documents.len(),
+        operations = restored,
+        "Restored backup"
+    );
+
+    Ok(restored)
+}
+
+/// Decode the operations of one log of a backup document.
+fn decode_log(
+    operations: &[(Vec<u8>, Option<Vec<u8>>)],
+    public_key: &PublicKey,
+    log_type: LogType,
+) -> Result<Vec<(Header<AardvarkExtensions>, Option<Body>)>> {
+    let mut log = Vec::new();
+    for (header, body) in operations {
+        let header: Header<AardvarkExtensions> = decode_cbor(header.as_slice())?;
+        let given_log_type: Option<LogType> = header.extension();
+        if &header.public_key != public_key || given_log_type != Some(log_type) {
+            continue;
+        }
+        log.push((header, body.as_deref().map(Body::new)));
+    }
+    log.sort_by_key(|(header, _)| header.seq_num);
+
+    Ok(log)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::operation::create_operation;
+    use crate::store::connect_database;
+
+    #[tokio::test]
+    async fn backup_and_restore() {
+        let pool = connect_database(None).await.unwrap();
+        let document_store = DocumentStore::new(pool.clone());
+        let mut operation_store = OperationStore::new(pool);
+        let private_key = PrivateKey::new();
+        let public_key = private_key.public_key();
+
+        let operation = create_operation(
+            &mut operation_store,
+            &private_key,
+            LogType::Snapshot,
+            None,
+            None,
+            false,
+        )
+        .await
+        .unwrap();
+        let document_id: DocumentId = operation.header.extension().unwrap();
+        document_store.add_document(&document_id).await.unwrap();
+        document_store
+            .add_author(&document_id, &public_key)
+            .await
+            .unwrap();
+        document_store
+            .set_name_for_document(&document_id, Some("Notes".to_owned()))
+            .await
+            .unwrap();
+        for text in ["a", "b"] {
+            create_operation(
+                &mut operation_store,
+                &private_key,
+                LogType::Delta,
+                Some(document_id),
+                Some(text.as_bytes()),
+                false,
+            )
+            .await
+            .unwrap();
+        }
+
+        let backup = collect(&document_store, &operation_store, &private_key)
+            .await
+            .unwrap();
+        let sealed = backup.seal("passphrase").unwrap();
+        assert!(Backup::open(&sealed, "wrong").is_err());
+        let backup = Backup::open(&sealed, "passphrase").unwrap();
+        assert_eq!(
+            backup.private_key().unwrap().public_key(),
+            private_key.public_key()
+        );
+        let documents = Backup::decode_documents(&backup.encode_documents().unwrap()).unwrap();
+
+        let pool = connect_database(None).await.unwrap();
+        let restored_document_store = DocumentStore::new(pool.clone());
+        let mut restored_operation_store = OperationStore::new(pool);
+        let restored = restore(
+            &restored_document_store,
+            &mut restored_operation_store,
+            &documents,
+        )
+        .await
+        .unwrap();
+        assert_eq!(restored, 3);
+
+        let restored_documents = restored_document_store.documents().await.unwrap();
+        assert_eq!(restored_documents.len(), 1);
+        assert_eq!(restored_documents[0].name.as_deref(), Some("Notes"));
+        let log = restored_operation_store
+            .get_log(&public_key, &LogId::new(LogType::Delta, &document_id), None)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(log.len(), 2);
+
+        // Restoring again doesn't store anything twice
+        let restored = restore(
+            &restored_document_store,
+            &mut restored_operation_store,
+            &documents,
+        )
+        .await
+        .unwrap();
+        assert_eq!(restored, 0);
+    }
+}
@@ -0,0 +1,198 @@
+//! Limiting and measuring the bandwidth of sync sessions.
+//!
+//! Users on metered connections can cap how fast we upload and download, for all sync sessions
+//! together and for every single one. A session syncs one document with one peer, so the limit
+//! of a session caps what a single peer can make us transfer at a time. Limits are token buckets
+//! which may go into debt: a read or write is never split, instead the next one waits until the
+//! debt is paid off. Gossip isn't limited, its messages are small and capped in size by the tuning
+//! of the overlay.
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// How long the measured throughput is averaged over.
+const THROUGHPUT_WINDOW: Duration = Duration::from_secs(5);
+/// Transfers closer together than this are counted as one sample.
+const THROUGHPUT_RESOLUTION: Duration = Duration::from_millis(100);
+
+/// Caps of sync traffic in bytes per second, `None` doesn't limit it.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct BandwidthLimits {
+    /// All sync sessions together.
+    pub upload: Option<u64>,
+    pub download: Option<u64>,
+    /// Every single sync session, i.e. with one peer for one document.
+    pub peer_upload: Option<u64>,
+    pub peer_download: Option<u64>,
+}
+
+/// Limits the rate of transfers, see the module documentation.
+#[derive(Debug)]
+pub struct TokenBucket {
+    rate: Option<u64>,
+    /// Bytes which may be transferred right away, negative while in debt.
+    available: f64,
+    updated: Instant,
+}
+
+impl TokenBucket {
+    pub fn new(rate: Option<u64>) -> Self {
+        let rate = rate.filter(|rate| *rate > 0);
+        Self {
+            rate,
+            available: rate.unwrap_or_default() as f64,
+            updated: Instant::now(),
+        }
+    }
+
+    pub fn set_rate(&mut self, rate: Option<u64>) {
+        let rate = rate.filter(|rate| *rate > 0);
+        if rate == self.rate {
+            return;
+        }
+        self.refill(Instant::now());
+        self.rate = rate;
+        if let Some(rate) = rate {
+            self.available = self.available.min(rate as f64);
+        }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        if let Some(rate) = self.rate {
+            let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+            // At most a second worth of transfers accumulates while idle
+            self.available = (self.available + elapsed * rate as f64).min(rate as f64);
+        }
+        self.updated = now;
+    }
+
+    /// Account for `bytes` transferred at `now`.
+    pub fn consume(&mut self, bytes: usize, now: Instant) {
+        self.refill(now);
+        if self.rate.is_some() {
+            self.available -= bytes as f64;
+        }
+    }
+
+    /// How long the next transfer has to wait at `now`, `None` if it may start right away.
+    pub fn delay(&mut self, now: Instant) -> Option<Duration> {
+        self.refill(now);
+        let rate = self.rate?;
+        // Less than a byte is rounding
+        (self.available <= -1.0).then(|| Duration::from_secs_f64(-self.available / rate as f64))
+    }
+}
+
+impl Default for TokenBucket {
+    fn default() -> Self {
+        Self::new(None)
+    }
+}
+
+/// Bytes transferred per second, averaged over the last few seconds.
+#[derive(Debug, Default)]
+pub struct Throughput {
+    samples: VecDeque<(Instant, u64)>,
+}
+
+impl Throughput {
+    pub fn record(&mut self, bytes: usize, now: Instant) {
+        self.trim(now);
+        match self.samples.back_mut() {
+            Some((time, sample))
+                if now.saturating_duration_since(*time) < THROUGHPUT_RESOLUTION =>
+            {
+                *sample += bytes as u64;
+            }
+            _ => self.samples.push_back((now, bytes as u64)),
+        }
+    }
+
+    /// Bytes per second at `now`.
+    pub fn rate(&mut self, now: Instant) -> u64 {
+        self.trim(now);
+        let total: u64 = self.samples.iter().map(|(_, bytes)| bytes).sum();
+        total / THROUGHPUT_WINDOW.as_secs()
+    }
+
+    fn trim(&mut self, now: Instant) {
+        while self
+            .samples
+            .front()
+            .is_some_and(|(time, _)| now.saturating_duration_since(*time) > THROUGHPUT_WINDOW)
+        {
+            self.samples.pop_front();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn millis(delay: Option<Duration>) -> Option<u64> {
+        delay.map(|delay| (delay.as_secs_f64() * 1000.0).round() as u64)
+    }
+
+    #[test]
+    fn unlimited() {
+        let now = Instant::now();
+        let mut bucket = TokenBucket::new(None);
+        bucket.consume(1_000_000_000, now);
+        assert_eq!(bucket.delay(now), None);
+
+        // Zero means no limit as well
+        let mut bucket = TokenBucket::new(Some(0));
+        bucket.consume(1_000_000_000, now);
+        assert_eq!(bucket.delay(now), None);
+    }
+
+    #[test]
+    fn pay_off_debt() {
+        let mut bucket = TokenBucket::new(Some(1000));
+        let now = bucket.updated;
+
+        // A second worth of transfers is available right away
+        bucket.consume(1000, now);
+        assert_eq!(bucket.delay(now), None);
+
+        bucket.consume(500, now);
+        assert_eq!(millis(bucket.delay(now)), Some(500));
+        assert_eq!(
+            millis(bucket.delay(now + Duration::from_millis(200))),
+            Some(300)
+        );
+        assert_eq!(bucket.delay(now + Duration::from_millis(500)), None);
+
+        // Idle time doesn't allow bursts larger than a second worth
+        let later = now + Duration::from_secs(60);
+        bucket.consume(1500, later);
+        assert_eq!(millis(bucket.delay(later)), Some(500));
+    }
+
+    #[test]
+    fn change_rate() {
+        let mut bucket = TokenBucket::new(None);
+        let now = Instant::now();
+        bucket.set_rate(Some(100));
+        bucket.consume(300, now);
+        assert!(bucket.delay(now).is_some());
+
+        bucket.set_rate(None);
+        assert_eq!(bucket.delay(now), None);
+    }
+
+    #[test]
+    fn measure_throughput() {
+        let mut throughput = Throughput::default();
+        let now = Instant::now();
+        for second in 0..5 {
+            throughput.record(1000, now + Duration::from_secs(second));
+        }
+        assert_eq!(throughput.rate(now + Duration::from_secs(4)), 1000);
+
+        // Old transfers don't count anymore
+        assert_eq!(throughput.rate(now + Duration::from_secs(7)), 600);
+        assert_eq!(throughput.rate(now + Duration::from_secs(60)), 0);
+    }
+}
@@ -0,0 +1,153 @@
+//! Keep documents available while the laptops of their authors are asleep.
+//!
+//! ```sh
+//! cargo run -p aardvark-core --bin aardvark-pin-server -- \
+//!     --data-dir /var/lib/aardvark --document <INVITE CODE> --metrics 127.0.0.1:9184
+//! ```
+//!
+//! Joins the given documents and all documents it joined before, then stores and serves their
+//! operations until it's stopped. Documents are never edited. The identity of the pin server is
+//! kept in the data directory.
+
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::net::SocketAddr;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::str::FromStr;
+
+use aardvark_core::document::DocumentId;
+use aardvark_core::replica::replicate;
+use aardvark_core::{DEFAULT_NETWORK_ID, GossipPreset, NetworkConfig, Node};
+use anyhow::Context;
+use p2panda_core::{Hash, PrivateKey};
+
+const PRIVATE_KEY_FILE: &str = "private-key";
+
+struct Options {
+    data_dir: PathBuf,
+    documents: Vec<DocumentId>,
+    metrics: Option<SocketAddr>,
+    preset: GossipPreset,
+}
+
+fn usage() -> &'static str {
+    "Usage: aardvark-pin-server --data-dir <DIR> [--document <ID>]... \
+     [--metrics <ADDRESS:PORT>] [--preset small-group|classroom|large-event]"
+}
+
+fn parse_options() -> Result<Options, String> {
+    let mut options = Options {
+        data_dir: PathBuf::new(),
+        documents: Vec::new(),
+        metrics: None,
+        preset: GossipPreset::default(),
+    };
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .ok_or_else(|| format!("Missing value for {arg}"))
+        };
+        match arg.as_str() {
+            "--data-dir" => options.data_dir = PathBuf::from(value()?),
+            "--document" => {
+                // Invite codes are shown in groups separated by spaces
+                let id: String = value()?
+                    .chars()
+                    .filter(|char| !char.is_whitespace())
+                    .collect();
+                let id = DocumentId::from_str(&id).map_err(|_| format!("Invalid document {id}"))?;
+                options.documents.push(id);
+            }
+            "--metrics" => {
+                options.metrics = Some(value()?.parse().map_err(|_| "Invalid --metrics")?)
+            }
+            "--preset" => {
+                options.preset = value()?.parse().map_err(|_| "Invalid --preset")?;
+            }
+            _ => return Err(format!("Unknown argument {arg}")),
+        }
+    }
+
+    if options.data_dir.as_os_str().is_empty() {
+        return Err("--data-dir is required".to_string());
+    }
+
+    Ok(options)
+}
+
+/// The identity stored in `data_dir`, created on the first start.
+fn load_private_key(data_dir: &Path) -> anyhow::Result<PrivateKey> {
+    let path = data_dir.join(PRIVATE_KEY_FILE);
+    if path.exists() {
+        let bytes = fs::read(&path)?;
+        return PrivateKey::try_from(bytes.as_slice())
+            .with_context(|| format!("Invalid private key in {}", path.display()));
+    }
+
+    let private_key = PrivateKey::new();
+    OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(&path)?
+        .write_all(private_key.as_bytes())?;
+    Ok(private_key)
+}
+
+async fn run(options: Options) -> anyhow::Result<()> {
+    fs::create_dir_all(&options.data_dir)?;
+    let private_key = load_private_key(&options.data_dir)?;
+    println!("Public key: {}", private_key.public_key());
+
+    let node = Node::new();
+    // Same network as the app
+    node.run(
+        private_key,
+        Hash::new(DEFAULT_NETWORK_ID),
+        Some(&options.data_dir),
+        options.preset.tuning(),
+        NetworkConfig::default(),
+    )
+    .await?;
+
+    if let Some(address) = options.metrics {
+        let address = node.serve_metrics(address).await?;
+        println!("Metrics: http://{address}/metrics");
+    }
+
+    let subscriptions = replicate(&node, &options.documents).await?;
+    if subscriptions.is_empty() {
+        println!("No documents to pin yet, pass them with --document");
+    }
+    for subscription in &subscriptions {
+        println!("Pinning {}", subscription.document_id());
+    }
+
+    // Everything happens on the node's runtime from here on, until the process is stopped
+    std::future::pending::<()>().await;
+
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    let options = match parse_options() {
+        Ok(options) => options,
+        Err(error) => {
+            eprintln!("{error}\n{}", usage());
+            return ExitCode::FAILURE;
+        }
+    };
+
+    // The node brings its own tokio runtime and must not be run from within another one
+    match futures::executor::block_on(run(options)) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("{error:#}");
+            ExitCode::FAILURE
+        }
+    }
+}
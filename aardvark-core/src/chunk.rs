@@ -0,0 +1,287 @@
+//! Splitting large payloads over multiple operations and putting them back together.
+//!
+//! Snapshots of large documents easily exceed what a single gossip message or sync frame can
+//! carry. Instead of relying on transport limits every operation body is at most
+//! [`MAX_PAYLOAD_SIZE`] bytes, larger payloads are appended to the log as a run of chunk
+//! operations which all mention the hash of the full payload. Peers buffer chunks until they
+//! received all of them and only forward the reassembled payload to the application.
+use std::collections::HashMap;
+
+use anyhow::{Result, bail};
+use p2panda_core::{Hash, PublicKey};
+use serde::{Deserialize, Serialize};
+
+/// Maximum size of the body of a single operation in bytes.
+///
+/// This fits into a gossip message even with the most conservative tuning preset.
+pub const MAX_PAYLOAD_SIZE: usize = 48 * 1024;
+
+/// Maximum number of chunks a payload may be split into, this limits how much memory we spend on
+/// buffering chunks of a single payload.
+pub const MAX_CHUNK_COUNT: u32 = 2048;
+
+/// Position of an operation body in a payload which was split over multiple operations.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Chunk {
+    /// Hash of the full payload, shared by all chunks of it.
+    #[serde(rename = "h")]
+    pub payload_hash: Hash,
+    #[serde(rename = "i")]
+    pub index: u32,
+    #[serde(rename = "n")]
+    pub count: u32,
+}
+
+impl Chunk {
+    pub fn validate(&self) -> Result<()> {
+        if self.count < 2 {
+            bail!("chunked payload needs at least two chunks");
+        }
+        if self.count > MAX_CHUNK_COUNT {
+            bail!(
+                "payload split into too many chunks ({} > {MAX_CHUNK_COUNT})",
+                self.count
+            );
+        }
+        if self.index >= self.count {
+            bail!(
+                "chunk index {} out of range (count: {})",
+                self.index,
+                self.count
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Split a payload into bodies of at most [`MAX_PAYLOAD_SIZE`] bytes.
+///
+/// Payloads which fit into a single operation are returned as they are, without chunk
+/// information.
+pub fn split_payload(payload: &[u8]) -> Result<Vec<(Option<Chunk>, &[u8])>> {
+    if payload.len() <= MAX_PAYLOAD_SIZE {
+        return Ok(vec![(None, payload)]);
+    }
+
+    let count = payload.len().div_ceil(MAX_PAYLOAD_SIZE);
+    if count > MAX_CHUNK_COUNT as usize {
+        bail!(
+            "payload of {} bytes exceeds the maximum size",
+            payload.len()
+        );
+    }
+
+    let payload_hash = Hash::new(payload);
+    Ok(payload
+        .chunks(MAX_PAYLOAD_SIZE)
+        .enumerate()
+        .map(|(index, bytes)| {
+            let chunk = Chunk {
+                payload_hash,
+                index: index as u32,
+                count: count as u32,
+            };
+            (Some(chunk), bytes)
+        })
+        .collect())
+}
+
+/// Chunks of payloads we didn't receive completely yet.
+#[derive(Debug, Default)]
+pub struct ChunkBuffer {
+    pending: HashMap<(PublicKey, Hash), Vec<Option<Vec<u8>>>>,
+}
+
+impl ChunkBuffer {
+    /// Add a received chunk, returns the full payload once all chunks of it arrived.
+    ///
+    /// Receiving the same chunk more than once is fine, e.g. when it arrives via gossip and sync.
+    pub fn insert(
+        &mut self,
+        public_key: PublicKey,
+        chunk: &Chunk,
+        bytes: Vec<u8>,
+    ) -> Result<Option<Vec<u8>>> {
+        chunk.validate()?;
+        if bytes.len() > MAX_PAYLOAD_SIZE {
+            bail!("chunk of {} bytes exceeds the maximum size", bytes.len());
+        }
+
+        let key = (public_key, chunk.payload_hash);
+        let chunks = self
+            .pending
+            .entry(key)
+            .or_insert_with(|| vec![None; chunk.count as usize]);
+        if chunks.len() != chunk.count as usize {
+            bail!("chunk count doesn't match previous chunks of the payload");
+        }
+        chunks[chunk.index as usize] = Some(bytes);
+
+        if chunks.iter().any(Option::is_none) {
+            return Ok(None);
+        }
+
+        let payload: Vec<u8> = self
+            .pending
+            .remove(&key)
+            .expect("chunks of payload are pending")
+            .into_iter()
+            .flatten()
+            .flatten()
+            .collect();
+        if Hash::new(&payload) != chunk.payload_hash {
+            bail!("reassembled payload doesn't match its hash");
+        }
+
+        Ok(Some(payload))
+    }
+
+    /// Chunks received and expected of all payloads we are still waiting for chunks of, `None`
+    /// if we aren't waiting for any.
+    pub fn progress(&self) -> Option<(u32, u32)> {
+        if self.pending.is_empty() {
+            return None;
+        }
+
+        let received = self
+            .pending
+            .values()
+            .flatten()
+            .filter(|chunk| chunk.is_some())
+            .count();
+        let expected = self.pending.values().map(Vec::len).sum::<usize>();
+        Some((received as u32, expected as u32))
+    }
+
+    /// Number of payloads we are still waiting for chunks of.
+    #[cfg(test)]
+    fn pending(&self) -> usize {
+        self.pending.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use p2panda_core::PrivateKey;
+
+    use super::*;
+
+    fn payload(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i % 251) as u8).collect()
+    }
+
+    #[test]
+    fn small_payload_is_not_chunked() {
+        let bytes = payload(MAX_PAYLOAD_SIZE);
+        let chunks = split_payload(&bytes).unwrap();
+        assert_eq!(chunks, vec![(None, bytes.as_slice())]);
+    }
+
+    #[test]
+    fn split_and_reassemble() {
+        let public_key = PrivateKey::new().public_key();
+        let bytes = payload(MAX_PAYLOAD_SIZE * 3 + 17);
+        let chunks = split_payload(&bytes).unwrap();
+        assert_eq!(chunks.len(), 4);
+        assert!(
+            chunks
+                .iter()
+                .all(|(_, body)| body.len() <= MAX_PAYLOAD_SIZE)
+        );
+
+        let mut buffer = ChunkBuffer::default();
+        // Chunks might arrive out of order and more than once
+        for index in [2, 0, 3, 0] {
+            let (chunk, body) = &chunks[index];
+            let result = buffer
+                .insert(public_key, chunk.as_ref().unwrap(), body.to_vec())
+                .unwrap();
+            assert_eq!(result, None);
+        }
+        assert_eq!(buffer.pending(), 1);
+        assert_eq!(buffer.progress(), Some((3, 4)));
+
+        let (chunk, body) = &chunks[1];
+        let result = buffer
+            .insert(public_key, chunk.as_ref().unwrap(), body.to_vec())
+            .unwrap();
+        assert_eq!(result, Some(bytes));
+        assert_eq!(buffer.pending(), 0);
+        assert_eq!(buffer.progress(), None);
+    }
+
+    #[test]
+    fn chunks_of_different_authors_are_kept_apart() {
+        let alice = PrivateKey::new().public_key();
+        let bob = PrivateKey::new().public_key();
+        let bytes = payload(MAX_PAYLOAD_SIZE + 1);
+        let chunks = split_payload(&bytes).unwrap();
+
+        let mut buffer = ChunkBuffer::default();
+        let (chunk, body) = &chunks[0];
+        assert_eq!(
+            buffer
+                .insert(alice, chunk.as_ref().unwrap(), body.to_vec())
+                .unwrap(),
+            None
+        );
+        let (chunk, body) = &chunks[1];
+        assert_eq!(
+            buffer
+                .insert(bob, chunk.as_ref().unwrap(), body.to_vec())
+                .unwrap(),
+            None
+        );
+        assert_eq!(buffer.pending(), 2);
+    }
+
+    #[test]
+    fn tampered_chunk_is_rejected() {
+        let public_key = PrivateKey::new().public_key();
+        let bytes = payload(MAX_PAYLOAD_SIZE + 1);
+        let chunks = split_payload(&bytes).unwrap();
+
+        let mut buffer = ChunkBuffer::default();
+        let (chunk, body) = &chunks[0];
+        buffer
+            .insert(public_key, chunk.as_ref().unwrap(), body.to_vec())
+            .unwrap();
+        let (chunk, _) = &chunks[1];
+        assert!(
+            buffer
+                .insert(public_key, chunk.as_ref().unwrap(), vec![0])
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn invalid_chunks_are_rejected() {
+        let public_key = PrivateKey::new().public_key();
+        let payload_hash = Hash::new(b"payload");
+        let mut buffer = ChunkBuffer::default();
+
+        for (index, count) in [(0, 1), (2, 2), (0, MAX_CHUNK_COUNT + 1)] {
+            let chunk = Chunk {
+                payload_hash,
+                index,
+                count,
+            };
+            assert!(chunk.validate().is_err());
+            assert!(buffer.insert(public_key, &chunk, vec![1]).is_err());
+        }
+
+        let chunk = Chunk {
+            payload_hash,
+            index: 0,
+            count: 2,
+        };
+        let oversized = vec![0; MAX_PAYLOAD_SIZE + 1];
+        assert!(buffer.insert(public_key, &chunk, oversized).is_err());
+    }
+
+    #[test]
+    fn oversized_payload_is_rejected() {
+        let bytes = payload(MAX_PAYLOAD_SIZE * MAX_CHUNK_COUNT as usize + 1);
+        assert!(split_payload(&bytes).is_err());
+    }
+}
@@ -0,0 +1,299 @@
+//! Snapshot-only cold storage of documents on generic blob endpoints.
+//!
+//! Our latest snapshot of each document is encrypted and pushed as a "pin" to an S3-compatible
+//! bucket or a WebDAV folder. This gives users durability beyond their own devices without
+//! running an always-on node, the endpoint only ever sees opaque blobs.
+//!
+//! The encryption key and the object name are derived from the document id, everyone who knows
+//! the document id can restore the document from the pin but the storage provider can't.
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::time::Duration;
+
+use anyhow::{Context, Result, anyhow, bail};
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{Key, XChaCha20Poly1305, XNonce};
+use hmac::{Hmac, Mac};
+use p2panda_core::{Extension, Hash, PublicKey};
+use p2panda_store::LogStore;
+use reqwest::{StatusCode, Url};
+use sha2::{Digest, Sha256};
+use tracing::{debug, warn};
+
+use crate::compression::PayloadFormat;
+use crate::document::DocumentId;
+use crate::operation::LogType;
+use crate::store::{DocumentStore, LogId, OperationStore};
+
+/// Identifies the format of a pinned snapshot bundle.
+const BUNDLE_MAGIC: &[u8] = b"aardvark-pin\x01";
+/// How long pre-signed S3 requests stay valid.
+const PRESIGN_DURATION: Duration = Duration::from_secs(60);
+const KEY_CONTEXT: &[u8] = b"aardvark cold storage snapshot key";
+const NAME_CONTEXT: &[u8] = b"aardvark cold storage object name";
+
+/// Blob endpoint used to store pinned snapshots.
+#[derive(Clone, Debug)]
+pub enum ColdStorageBackend {
+    /// Any S3-compatible object storage, requests are signed with the given access key.
+    S3 {
+        endpoint: String,
+        bucket: String,
+        region: String,
+        access_key: String,
+        secret_key: String,
+    },
+    /// A folder on a WebDAV server, e.g. Nextcloud.
+    WebDav {
+        url: String,
+        username: Option<String>,
+        password: Option<String>,
+    },
+}
+
+#[derive(Debug)]
+pub(crate) struct ColdStorage {
+    client: reqwest::Client,
+    backend: ColdStorageBackend,
+}
+
+impl ColdStorage {
+    /// Cold storage on `backend`, reached through the SOCKS5 `proxy` if there is one.
+    pub fn new(backend: ColdStorageBackend, proxy: Option<&str>) -> Result<Self> {
+        let mut client = reqwest::Client::builder();
+        if let Some(proxy) = proxy {
+            // Resolve host names through the proxy as well, DNS requests would reveal us
+            client = client.proxy(reqwest::Proxy::all(format!("socks5h://{proxy}"))?);
+        }
+        Ok(Self {
+            client: client.build()?,
+            backend,
+        })
+    }
+
+    /// Encrypt and upload a snapshot, replacing the previous pin of the document.
+    pub async fn put(&self, document_id: &DocumentId, snapshot_bytes: &[u8]) -> Result<()> {
+        let bundle = encrypt(document_id, snapshot_bytes)?;
+        let request = match &self.backend {
+            ColdStorageBackend::S3 { .. } => {
+                let url = self.presign_s3("PUT", document_id)?;
+                self.client.put(url)
+            }
+            ColdStorageBackend::WebDav { .. } => {
+                self.webdav_request(reqwest::Method::PUT, document_id)?
+            }
+        };
+
+        request
+            .body(bundle)
+            .send()
+            .await?
+            .error_for_status()
+            .context("uploading snapshot failed")?;
+
+        Ok(())
+    }
+
+    /// Download and decrypt the pinned snapshot of a document, if there is one.
+    pub async fn get(&self, document_id: &DocumentId) -> Result<Option<Vec<u8>>> {
+        let request = match &self.backend {
+            ColdStorageBackend::S3 { .. } => {
+                let url = self.presign_s3("GET", document_id)?;
+                self.client.get(url)
+            }
+            ColdStorageBackend::WebDav { .. } => {
+                self.webdav_request(reqwest::Method::GET, document_id)?
+            }
+        };
+
+        let response = request.send().await?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        let bundle = response
+            .error_for_status()
+            .context("downloading snapshot failed")?
+            .bytes()
+            .await?;
+
+        decrypt(document_id, &bundle).map(Some)
+    }
+
+    /// Pre-signed URL of the object of a document, using path-style addressing and AWS
+    /// Signature Version 4 in the query string.
+    fn presign_s3(&self, method: &str, document_id: &DocumentId) -> Result<Url> {
+        let ColdStorageBackend::S3 {
+            endpoint,
+            bucket,
+            region,
+            access_key,
+            secret_key,
+        } = &self.backend
+        else {
+            unreachable!("backend is not S3");
+        };
+
+        let mut url: Url = endpoint.parse()?;
+        let host = match (url.host_str(), url.port()) {
+            (Some(host), Some(port)) => format!("{host}:{port}"),
+            (Some(host), None) => host.to_owned(),
+            (None, _) => bail!("S3 endpoint {endpoint} has no host"),
+        };
+        let path = format!(
+            "{}/{}/{}",
+            url.path().trim_end_matches('/'),
+            uri_encode(bucket),
+            object_name(document_id)
+        );
+
+        let now = chrono::Utc::now();
+        let date = now.format("%Y%m%d").to_string();
+        let timestamp = now.format("%Y%m%dT%H%M%SZ").to_string();
+        let scope = format!("{date}/{region}/s3/aws4_request");
+        // Parameters have to be sorted by name
+        let query = format!(
+            "X-Amz-Algorithm=AWS4-HMAC-SHA256&X-Amz-Credential={}&X-Amz-Date={timestamp}\
+             &X-Amz-Expires={}&X-Amz-SignedHeaders=host",
+            uri_encode(&format!("{access_key}/{scope}")),
+            PRESIGN_DURATION.as_secs(),
+        );
+
+        let canonical_request =
+            format!("{method}\n{path}\n{query}\nhost:{host}\n\nhost\nUNSIGNED-PAYLOAD");
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{timestamp}\n{scope}\n{}",
+            hex::encode(Sha256::digest(canonical_request))
+        );
+        let signing_key = [region.as_bytes(), b"s3", b"aws4_request"].iter().fold(
+            hmac_sha256(format!("AWS4{secret_key}").as_bytes(), date.as_bytes()),
+            |key, part| hmac_sha256(&key, part),
+        );
+        let signature = hex::encode(hmac_sha256(&signing_key, string_to_sign.as_bytes()));
+
+        url.set_path(&path);
+        url.set_query(Some(&format!("{query}&X-Amz-Signature={signature}")));
+        Ok(url)
+    }
+
+    fn webdav_request(
+        &self,
+        method: reqwest::Method,
+        document_id: &DocumentId,
+    ) -> Result<reqwest::RequestBuilder> {
+        let ColdStorageBackend::WebDav {
+            url,
+            username,
+            password,
+        } = &self.backend
+        else {
+            unreachable!("backend is not WebDAV");
+        };
+
+        let url = format!("{}/{}", url.trim_end_matches('/'), object_name(document_id));
+        let request = self.client.request(method, url);
+        Ok(match username {
+            Some(username) => request.basic_auth(username, password.as_ref()),
+            None => request,
+        })
+    }
+}
+
+/// Pin our latest snapshot of every document which changed since it was pinned last.
+///
+/// `pinned` keeps track of the snapshot operations which were already uploaded.
+pub(crate) async fn pin_documents(
+    cold_storage: &ColdStorage,
+    operation_store: &OperationStore,
+    document_store: &DocumentStore,
+    public_key: &PublicKey,
+    pinned: &mut HashMap<DocumentId, Hash>,
+) -> Result<()> {
+    for document in document_store.documents().await? {
+        let log_id = LogId::new(LogType::Snapshot, &document.id);
+        let Some((header, Some(body))) = operation_store
+            .latest_operation(public_key, &log_id)
+            .await?
+        else {
+            // We never created a snapshot of this document
+            continue;
+        };
+
+        let hash = header.hash();
+        if pinned.get(&document.id) == Some(&hash) {
+            continue;
+        }
+
+        // Pins are read without the operation header, so they're stored decompressed
+        let format: PayloadFormat = header.extension().unwrap_or_default();
+        let snapshot_bytes = match format.decode(body.to_bytes()) {
+            Ok(snapshot_bytes) => snapshot_bytes,
+            Err(error) => {
+                warn!(document_id = %document.id, "failed to decompress snapshot: {error}");
+                continue;
+            }
+        };
+        match cold_storage.put(&document.id, &snapshot_bytes).await {
+            Ok(()) => {
+                debug!(document_id = %document.id, "pinned snapshot");
+                pinned.insert(document.id, hash);
+            }
+            Err(error) => warn!(document_id = %document.id, "failed to pin snapshot: {error}"),
+        }
+    }
+
+    Ok(())
+}
+
+fn derive(context: &[u8], document_id: &DocumentId) -> Hash {
+    Hash::new([context, document_id.as_bytes()].concat())
+}
+
+fn hmac_sha256(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC takes keys of any size");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// Percent-encode everything but unreserved characters, as required for signing S3 requests.
+fn uri_encode(value: &str) -> String {
+    value.bytes().fold(String::new(), |mut encoded, byte| {
+        if byte.is_ascii_alphanumeric() || b"-_.~".contains(&byte) {
+            encoded.push(byte as char);
+        } else {
+            let _ = write!(encoded, "%{byte:02X}");
+        }
+        encoded
+    })
+}
+
+fn object_name(document_id: &DocumentId) -> String {
+    derive(NAME_CONTEXT, document_id).to_hex()
+}
+
+fn cipher(document_id: &DocumentId) -> XChaCha20Poly1305 {
+    let key = derive(KEY_CONTEXT, document_id);
+    XChaCha20Poly1305::new(Key::from_slice(key.as_bytes()))
+}
+
+fn encrypt(document_id: &DocumentId, snapshot_bytes: &[u8]) -> Result<Vec<u8>> {
+    let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let ciphertext = cipher(document_id)
+        .encrypt(&nonce, snapshot_bytes)
+        .map_err(|_| anyhow!("encrypting snapshot failed"))?;
+
+    Ok([BUNDLE_MAGIC, nonce.as_slice(), &ciphertext].concat())
+}
+
+fn decrypt(document_id: &DocumentId, bundle: &[u8]) -> Result<Vec<u8>> {
+    let Some(bundle) = bundle.strip_prefix(BUNDLE_MAGIC) else {
+        bail!("unknown snapshot bundle format");
+    };
+    if bundle.len() < 24 {
+        bail!("snapshot bundle is truncated");
+    }
+
+    let (nonce, ciphertext) = bundle.split_at(24);
+    cipher(document_id)
+        .decrypt(XNonce::from_slice(nonce), ciphertext)
+        .map_err(|_| anyhow!("decrypting snapshot failed, the bundle belongs to another document"))
+}
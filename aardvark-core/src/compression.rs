@@ -0,0 +1,83 @@
+//! Compressing snapshots before they are persisted and synced.
+//!
+//! Snapshots contain the whole history of a document and compress very well, compressing them
+//! with zstd cuts both the size of the store and what new peers need to sync. Payloads are
+//! compressed before they are split into chunks, the [`PayloadFormat`] in the header of every
+//! operation tells peers how to read the reassembled payload. Deltas are too small to gain much
+//! and stay uncompressed, as do operations created before compression was introduced.
+use std::io::Read;
+
+use anyhow::{Result, bail};
+use serde::{Deserialize, Serialize};
+
+/// Trade-off between speed and size, zstd's default.
+const COMPRESSION_LEVEL: i32 = 3;
+
+/// Maximum size of a decompressed payload in bytes.
+///
+/// A few bytes of compressed payload can expand to gigabytes, this keeps malicious peers from
+/// exhausting our memory.
+pub const MAX_DECOMPRESSED_SIZE: u64 = 512 * 1024 * 1024;
+
+/// Encoding of the payload of an operation.
+#[derive(Copy, Clone, Default, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PayloadFormat {
+    /// The payload as the application passed it.
+    #[default]
+    Plain,
+    /// The payload compressed with zstd.
+    Zstd,
+}
+
+impl PayloadFormat {
+    pub fn is_plain(&self) -> bool {
+        *self == Self::Plain
+    }
+
+    /// Encode `payload` in this format.
+    pub fn encode(self, payload: &[u8]) -> Result<Vec<u8>> {
+        match self {
+            Self::Plain => Ok(payload.to_vec()),
+            Self::Zstd => Ok(zstd::bulk::compress(payload, COMPRESSION_LEVEL)?),
+        }
+    }
+
+    /// Decode a `payload` in this format, see [`Self::encode`].
+    pub fn decode(self, payload: Vec<u8>) -> Result<Vec<u8>> {
+        match self {
+            Self::Plain => Ok(payload),
+            Self::Zstd => {
+                let mut decoded = Vec::new();
+                zstd::Decoder::new(payload.as_slice())?
+                    .take(MAX_DECOMPRESSED_SIZE + 1)
+                    .read_to_end(&mut decoded)?;
+                if decoded.len() as u64 > MAX_DECOMPRESSED_SIZE {
+                    bail!("decompressed payload exceeds {MAX_DECOMPRESSED_SIZE} bytes");
+                }
+                Ok(decoded)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compress_and_decompress() {
+        let payload = b"Hello World! ".repeat(1000);
+
+        let compressed = PayloadFormat::Zstd.encode(&payload).unwrap();
+        assert!(compressed.len() < payload.len() / 10);
+        assert_eq!(PayloadFormat::Zstd.decode(compressed).unwrap(), payload);
+
+        let plain = PayloadFormat::Plain.encode(&payload).unwrap();
+        assert_eq!(PayloadFormat::Plain.decode(plain).unwrap(), payload);
+    }
+
+    #[test]
+    fn invalid_payload() {
+        assert!(PayloadFormat::Zstd.decode(b"not zstd".to_vec()).is_err());
+    }
+}
@@ -0,0 +1,121 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+use p2panda_core::{Hash, PublicKey};
+
+use crate::bandwidth::BandwidthLimits;
+use crate::document::DocumentId;
+use crate::operation::LogType;
+
+/// Summary of a stored operation, used for introspection and debugging.
+#[derive(Clone, Debug)]
+pub struct OperationInfo {
+    pub hash: Hash,
+    pub public_key: PublicKey,
+    pub log_type: LogType,
+    pub seq_num: u64,
+    pub timestamp: u64,
+    pub payload_size: u64,
+    pub prune_flag: bool,
+}
+
+/// A snapshot of a document we've stored, see `Node::list_snapshots`.
+#[derive(Clone, Debug)]
+pub struct SnapshotInfo {
+    /// Hash of the (first) operation holding the snapshot.
+    pub hash: Hash,
+    /// Author who created the snapshot.
+    pub public_key: PublicKey,
+    pub timestamp: u64,
+    /// Size of the whole snapshot in bytes.
+    pub size: u64,
+}
+
+/// Sizes of the local database.
+#[derive(Clone, Debug, Default)]
+pub struct StoreStats {
+    pub documents: u64,
+    pub authors: u64,
+    pub operations: u64,
+    pub database_size: u64,
+}
+
+/// Gossip overlay state of a document as known by this node.
+#[derive(Clone, Debug)]
+pub struct TopicInfo {
+    /// Peers we are directly connected with on the gossip overlay of this document.
+    pub neighbors: Vec<PublicKey>,
+    /// Authors we know of for this document and when we last saw them.
+    pub authors: Vec<(PublicKey, Option<DateTime<Utc>>)>,
+    pub gossip: GossipStats,
+    pub ingest: IngestStats,
+}
+
+/// Gossip traffic of a document since it was subscribed.
+#[derive(Clone, Debug, Default)]
+pub struct GossipStats {
+    pub messages_sent: u64,
+    pub bytes_sent: u64,
+    pub messages_received: u64,
+    pub bytes_received: u64,
+    /// Received messages which were invalid or expired.
+    pub messages_dropped: u64,
+}
+
+/// Operations of a document we refused to ingest since it was subscribed.
+#[derive(Clone, Debug, Default)]
+pub struct IngestStats {
+    /// Received via gossip from authors sending too many operations too fast.
+    pub rate_limited: u64,
+    /// Payload too large to be plausible.
+    pub invalid_payload: u64,
+    /// Authors we didn't know before who already wrote to too many documents.
+    pub unknown_author: u64,
+    /// Authors we blocked.
+    pub blocked: u64,
+    /// Invalid signature or failed validation, most likely sent by an incompatible version.
+    pub rejected: u64,
+    /// Who sent the rejected operations and why, see [`RejectedOperations`].
+    pub rejections: Vec<RejectedOperations>,
+}
+
+/// Operations of one author rejected for the same reason.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RejectedOperations {
+    pub public_key: PublicKey,
+    pub reason: String,
+    pub count: u64,
+}
+
+/// Which side started a sync session.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SyncDirection {
+    /// We connected to the peer to sync a document.
+    Initiated,
+    /// The peer connected to us.
+    Accepted,
+}
+
+/// A currently running sync session.
+#[derive(Clone, Debug)]
+pub struct SyncSessionInfo {
+    pub id: u64,
+    /// Only known for sessions we initiated.
+    pub document: Option<DocumentId>,
+    pub direction: SyncDirection,
+    pub started: DateTime<Utc>,
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    pub paused: bool,
+}
+
+/// Sync traffic of the node, in bytes per second averaged over the last few seconds.
+#[derive(Clone, Debug, Default)]
+pub struct BandwidthInfo {
+    /// All sync sessions together.
+    pub upload_rate: u64,
+    pub download_rate: u64,
+    pub limits: BandwidthLimits,
+    /// Gossip and sync traffic received from every peer, peers which were quiet are missing.
+    pub peer_download_rates: HashMap<PublicKey, u64>,
+}
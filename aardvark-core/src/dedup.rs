@@ -0,0 +1,57 @@
+//! Forwarding every operation to the application only once.
+//!
+//! The same operation can arrive via gossip and sync at about the same time and make it through
+//! ingest twice before it was persisted. Applying a delta twice to the CRDT is harmless but
+//! wasteful, so we remember the hashes of recently forwarded operations and skip repeated ones.
+use std::collections::{HashSet, VecDeque};
+
+use p2panda_core::Hash;
+
+/// Number of operation hashes remembered per subscribed document.
+const CAPACITY: usize = 4096;
+
+#[derive(Debug, Default)]
+pub(crate) struct RecentOperations {
+    hashes: HashSet<Hash>,
+    /// Remembered hashes, the oldest first.
+    order: VecDeque<Hash>,
+}
+
+impl RecentOperations {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Remember the operation `hash`, returns false if it was seen recently.
+    pub fn insert(&mut self, hash: Hash) -> bool {
+        if !self.hashes.insert(hash) {
+            return false;
+        }
+        self.order.push_back(hash);
+        while self.order.len() > CAPACITY {
+            let Some(oldest) = self.order.pop_front() else {
+                break;
+            };
+            self.hashes.remove(&oldest);
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn forget_oldest() {
+        let mut recent = RecentOperations::new();
+        let first = Hash::new(b"first");
+        assert!(recent.insert(first));
+        assert!(!recent.insert(first));
+
+        for i in 0..CAPACITY {
+            assert!(recent.insert(Hash::new(i.to_be_bytes())));
+        }
+        assert!(recent.insert(first));
+    }
+}
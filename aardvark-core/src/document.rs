@@ -1,6 +1,8 @@
+use std::collections::HashMap;
 use std::fmt;
 use std::hash::Hash as StdHash;
 use std::str::FromStr;
+use std::time::Duration;
 
 use chrono::{DateTime, Utc};
 use p2panda_core::{Hash, HashError, PublicKey};
@@ -111,8 +113,33 @@ pub struct Document {
     #[sqlx(default)]
     pub name: Option<String>,
     pub last_accessed: Option<DateTime<Utc>>,
+    /// When the document was added to this device.
+    #[sqlx(default)]
+    pub created_at: Option<DateTime<Utc>>,
+    /// When we or another author last changed the text.
+    #[sqlx(default)]
+    pub last_edited: Option<DateTime<Utc>>,
+    /// Whether we advertise the document to peers on the local network, see `crate::nearby`.
+    #[sqlx(default)]
+    pub discoverable: bool,
+    /// Archived documents are kept but not synced anymore.
+    #[sqlx(default)]
+    pub archived: bool,
+    /// When the document was moved to the trash, see `crate::trash`.
+    #[sqlx(default)]
+    pub trashed_at: Option<DateTime<Utc>>,
+    /// Pinned documents are listed above all others.
+    #[sqlx(default)]
+    pub pinned: bool,
+    /// Position in the document list after the user reordered it, `None` for documents added
+    /// since.
+    #[sqlx(default)]
+    pub position: Option<i64>,
     #[sqlx(skip)]
     pub authors: Vec<Author>,
+    /// Names the user gave authors of this document, only shown on this device.
+    #[sqlx(skip)]
+    pub author_aliases: HashMap<PublicKey, String>,
 }
 
 #[derive(Debug)]
@@ -121,8 +148,43 @@ pub struct Author {
     pub last_seen: Option<DateTime<Utc>>,
 }
 
+/// Progress of downloading a document from peers after subscribing to it.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SyncProgress {
+    /// Operations received via sync since subscribing.
+    pub operations_received: u64,
+    /// Operations we know of in total, including the received ones, if we know of operations we
+    /// didn't receive yet.
+    pub operations_expected: Option<u64>,
+    /// Size of the received operations.
+    pub bytes_received: u64,
+    /// Sync with at least one peer completed and no other session is running.
+    pub done: bool,
+}
+
+/// Progress of receiving the chunks of large payloads, usually snapshots, see `crate::chunk`.
+///
+/// Deltas are forwarded as soon as they arrive, so the most recent changes are shown before the
+/// full history they build on arrived with the snapshots.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct HistoryProgress {
+    /// Chunks received of payloads which aren't complete yet.
+    pub chunks_received: u32,
+    /// Chunks of these payloads in total.
+    pub chunks_expected: u32,
+}
+
 pub trait SubscribableDocument: Sync + Send {
     fn bytes_received(&self, author: PublicKey, data: Vec<u8>);
+    fn ephemeral_bytes_received(&self, author: PublicKey, data: Vec<u8>);
+    /// Data of `author` was rejected because it doesn't match our protocol or extension schema.
+    fn author_incompatible(&self, author: PublicKey, reason: String);
     fn authors_joined(&self, authors: Vec<PublicKey>);
     fn author_set_online(&self, author: PublicKey, is_online: bool);
+    /// We lost all peers of the document and join it again in `delay`, for the `attempt`th time.
+    fn reconnecting(&self, attempt: u32, delay: Duration);
+    /// The initial download of the document made progress.
+    fn sync_progress(&self, progress: SyncProgress);
+    /// Chunks of large payloads arrived, `None` once none are missing anymore.
+    fn history_progress(&self, progress: Option<HistoryProgress>);
 }
@@ -0,0 +1,58 @@
+use std::time::SystemTime;
+
+use anyhow::{Result, bail};
+use p2panda_core::{PrivateKey, PublicKey, Signature};
+use serde::{Deserialize, Serialize};
+
+/// Signed message which is broadcast on the gossip overlay of a document but never persisted.
+///
+/// Ephemeral messages are used for state which is only interesting while peers are online, like
+/// presence, typing indicators or cursor positions. They are not part of any append-only log and
+/// therefore never synced to peers who join later.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct EphemeralMessage {
+    #[serde(rename = "k")]
+    pub public_key: PublicKey,
+    /// UNIX timestamp in seconds of when this message was created.
+    #[serde(rename = "t")]
+    pub timestamp: u64,
+    #[serde(rename = "p")]
+    pub payload: Vec<u8>,
+    #[serde(rename = "s")]
+    signature: Signature,
+}
+
+impl EphemeralMessage {
+    pub fn new(private_key: &PrivateKey, payload: Vec<u8>) -> Result<Self> {
+        let public_key = private_key.public_key();
+        let timestamp = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)?
+            .as_secs();
+        let signature = private_key.sign(&Self::signing_bytes(&public_key, timestamp, &payload));
+
+        Ok(Self {
+            public_key,
+            timestamp,
+            payload,
+            signature,
+        })
+    }
+
+    /// Check that the message was signed by the claimed author.
+    pub fn verify(&self) -> Result<()> {
+        let bytes = Self::signing_bytes(&self.public_key, self.timestamp, &self.payload);
+        if !self.public_key.verify(&bytes, &self.signature) {
+            bail!("invalid signature for ephemeral message");
+        }
+
+        Ok(())
+    }
+
+    fn signing_bytes(public_key: &PublicKey, timestamp: u64, payload: &[u8]) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(32 + 8 + payload.len());
+        bytes.extend_from_slice(public_key.as_bytes());
+        bytes.extend_from_slice(&timestamp.to_be_bytes());
+        bytes.extend_from_slice(payload);
+        bytes
+    }
+}
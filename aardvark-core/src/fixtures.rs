@@ -0,0 +1,127 @@
+//! Write generated documents directly into a store without running a node.
+//!
+//! This is used by the fixture generator to create large, realistic documents for benchmarks,
+//! UI stress testing and screenshots. Operations are signed with the keys of the given authors,
+//! so the resulting store looks exactly like one filled by syncing with those peers.
+
+use std::path::Path;
+
+use anyhow::Result;
+use p2panda_core::{Extension, PrivateKey};
+use tokio::runtime::{Builder, Runtime};
+
+use crate::compression::PayloadFormat;
+use crate::document::DocumentId;
+use crate::operation::{LogType, create_operation, create_operations};
+use crate::store::{DocumentStore, OperationStore, connect_database};
+
+pub struct FixtureWriter {
+    runtime: Runtime,
+    operation_store: OperationStore,
+    document_store: DocumentStore,
+}
+
+impl FixtureWriter {
+    /// Open (or create) the database inside of `db_location`.
+    pub fn open(db_location: &Path) -> Result<Self> {
+        let runtime = Builder::new_current_thread().enable_all().build()?;
+        let pool = runtime.block_on(connect_database(Some(db_location)))?;
+
+        Ok(Self {
+            runtime,
+            operation_store: OperationStore::new(pool.clone()),
+            document_store: DocumentStore::new(pool),
+        })
+    }
+
+    /// Create a new document owned by `author`.
+    pub fn create_document(&self, author: &PrivateKey, name: Option<String>) -> Result<DocumentId> {
+        self.runtime.block_on(async {
+            let operation = create_operation(
+                &mut self.operation_store.clone(),
+                author,
+                LogType::Snapshot,
+                None,
+                None,
+                false,
+            )
+            .await?;
+            let document_id: DocumentId = operation
+                .header
+                .extension()
+                .expect("document id from our own logs");
+
+            self.document_store.add_document(&document_id).await?;
+            self.document_store
+                .add_author(&document_id, &author.public_key())
+                .await?;
+            self.document_store
+                .set_name_for_document(&document_id, name)
+                .await?;
+
+            Ok(document_id)
+        })
+    }
+
+    /// Append a snapshot to the log of `author`, pruning all previous operations of the author.
+    pub fn add_snapshot(
+        &self,
+        author: &PrivateKey,
+        document_id: &DocumentId,
+        snapshot_bytes: &[u8],
+    ) -> Result<()> {
+        self.add_operation(
+            author,
+            document_id,
+            LogType::Snapshot,
+            snapshot_bytes,
+            true,
+            PayloadFormat::Zstd,
+        )
+    }
+
+    /// Append a delta to the log of `author`.
+    pub fn add_delta(
+        &self,
+        author: &PrivateKey,
+        document_id: &DocumentId,
+        delta_bytes: &[u8],
+    ) -> Result<()> {
+        self.add_operation(
+            author,
+            document_id,
+            LogType::Delta,
+            delta_bytes,
+            false,
+            PayloadFormat::Plain,
+        )
+    }
+
+    fn add_operation(
+        &self,
+        author: &PrivateKey,
+        document_id: &DocumentId,
+        log_type: LogType,
+        bytes: &[u8],
+        prune_flag: bool,
+        format: PayloadFormat,
+    ) -> Result<()> {
+        self.runtime.block_on(async {
+            self.document_store
+                .add_author(document_id, &author.public_key())
+                .await?;
+            create_operations(
+                &mut self.operation_store.clone(),
+                author,
+                log_type,
+                *document_id,
+                bytes,
+                prune_flag,
+                format,
+            )
+            .await?;
+
+            Ok(())
+        })
+    }
+}
@@ -0,0 +1,90 @@
+//! Presets for the expected number of peers.
+//!
+//! The overlay topology (active and passive views, fanout) and its heartbeat are managed by
+//! p2panda-net, whose `GossipConfig` only takes the maximum message size. Presets therefore
+//! don't tune the overlay itself, they adjust what we control around it: how large gossip
+//! messages may be, how often we sync again and how long ephemeral messages are relayed. A
+//! preset applies to the whole node, all documents use the same one.
+
+use std::str::FromStr;
+use std::time::Duration;
+
+use anyhow::{Error, bail};
+
+/// Presets for different numbers of peers, see [`GossipPreset::tuning`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum GossipPreset {
+    /// A handful of peers editing together, the default.
+    #[default]
+    SmallGroup,
+    /// Dozens of peers, usually on the same local network.
+    Classroom,
+    /// Hundreds of peers, many of them only reading along.
+    LargeEvent,
+}
+
+impl GossipPreset {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            GossipPreset::SmallGroup => "small-group",
+            GossipPreset::Classroom => "classroom",
+            GossipPreset::LargeEvent => "large-event",
+        }
+    }
+
+    pub fn tuning(&self) -> GossipTuning {
+        match self {
+            GossipPreset::SmallGroup => GossipTuning {
+                // FIXME: This is a temporary workaround to account for larger delta patches (for
+                // example when the user Copy & Pastes a big chunk of text).
+                //
+                // Related issue: https://github.com/p2panda/aardvark/issues/24
+                max_message_size: 512_000,
+                resync_interval: Duration::from_secs(60),
+                message_ttl: Duration::from_secs(30),
+            },
+            GossipPreset::Classroom => GossipTuning {
+                max_message_size: 256_000,
+                resync_interval: Duration::from_secs(120),
+                message_ttl: Duration::from_secs(15),
+            },
+            // Every message is relayed by many peers, keep them small and short-lived
+            GossipPreset::LargeEvent => GossipTuning {
+                max_message_size: 64_000,
+                resync_interval: Duration::from_secs(300),
+                message_ttl: Duration::from_secs(10),
+            },
+        }
+    }
+}
+
+impl FromStr for GossipPreset {
+    type Err = Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        Ok(match value {
+            "small-group" => GossipPreset::SmallGroup,
+            "classroom" => GossipPreset::Classroom,
+            "large-event" => GossipPreset::LargeEvent,
+            _ => bail!("unknown gossip preset \"{value}\""),
+        })
+    }
+}
+
+/// Effective settings of a preset.
+#[derive(Clone, Debug)]
+pub struct GossipTuning {
+    /// Maximum size of a single gossip message in bytes, larger deltas are only synced.
+    pub max_message_size: usize,
+    /// How often we sync again with peers we already synced with, this catches up on gossip
+    /// messages we missed.
+    pub resync_interval: Duration,
+    /// Ephemeral messages older than this are dropped on arrival.
+    pub message_ttl: Duration,
+}
+
+impl Default for GossipTuning {
+    fn default() -> Self {
+        GossipPreset::default().tuning()
+    }
+}
@@ -0,0 +1,241 @@
+//! Protection against peers flooding the store.
+//!
+//! Operations are checked before they are ingested, so a misbehaving or malicious peer can't fill
+//! the database. Only operations received via gossip are rate limited: sync sessions are started
+//! by us and legitimately deliver whole logs at once.
+use std::collections::{HashMap, HashSet};
+use std::time::Instant;
+
+use p2panda_core::{Header, PublicKey};
+
+use crate::chunk::MAX_PAYLOAD_SIZE;
+use crate::debug::{IngestStats, RejectedOperations};
+use crate::document::DocumentId;
+use crate::operation::AardvarkExtensions;
+
+/// Operations per second an author can send via gossip, on average.
+const OPERATIONS_PER_SECOND: f64 = 50.0;
+/// Operations an author can send via gossip at once, e.g. when a large paste is split into chunks.
+const OPERATIONS_BURST: f64 = 500.0;
+/// Larger payloads are dropped before they are persisted.
+///
+/// This is well above [`MAX_PAYLOAD_SIZE`], so operations of older versions without that limit
+/// still reach validation and their authors are told to update.
+const MAX_INGEST_PAYLOAD_SIZE: u64 = 16 * MAX_PAYLOAD_SIZE as u64;
+/// Documents an author we never stored before can write to until we restart.
+const MAX_DOCUMENTS_PER_UNKNOWN_AUTHOR: usize = 8;
+/// Different authors and reasons of rejected operations remembered per document, later ones are
+/// only counted.
+const MAX_REJECTIONS: usize = 32;
+
+/// Why an operation wasn't ingested.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Rejection {
+    RateLimited,
+    InvalidPayload,
+    UnknownAuthor,
+    Blocked,
+}
+
+#[derive(Debug)]
+pub(crate) struct IngestLimiter {
+    /// Authors of documents in the store when the node started, and ourselves.
+    known_authors: HashSet<PublicKey>,
+    /// Authors we don't accept anything from, see [`crate::Node::block_author`].
+    blocked_authors: HashSet<PublicKey>,
+    buckets: HashMap<PublicKey, TokenBucket>,
+    /// Documents unknown authors wrote to since the node started.
+    unknown_author_documents: HashMap<PublicKey, HashSet<DocumentId>>,
+    stats: HashMap<DocumentId, IngestStats>,
+}
+
+impl IngestLimiter {
+    pub fn new(known_authors: HashSet<PublicKey>, blocked_authors: HashSet<PublicKey>) -> Self {
+        Self {
+            known_authors,
+            blocked_authors,
+            buckets: HashMap::new(),
+            unknown_author_documents: HashMap::new(),
+            stats: HashMap::new(),
+        }
+    }
+
+    pub fn set_blocked(&mut self, author: PublicKey, blocked: bool) {
+        if blocked {
+            self.blocked_authors.insert(author);
+        } else {
+            self.blocked_authors.remove(&author);
+        }
+    }
+
+    pub fn is_blocked(&self, author: &PublicKey) -> bool {
+        self.blocked_authors.contains(author)
+    }
+
+    /// Whether `author` can send another operation via gossip right now.
+    pub fn check_gossip(&mut self, document: DocumentId, author: PublicKey) -> bool {
+        let rejection = if self.is_blocked(&author) {
+            Some(Rejection::Blocked)
+        } else if !self
+            .buckets
+            .entry(author)
+            .or_insert_with(TokenBucket::new)
+            .take()
+        {
+            Some(Rejection::RateLimited)
+        } else {
+            None
+        };
+
+        if let Some(rejection) = rejection {
+            self.record(document, rejection);
+        }
+        rejection.is_none()
+    }
+
+    /// Check an operation for `document` before it's persisted.
+    pub fn check(
+        &mut self,
+        document: DocumentId,
+        header: &Header<AardvarkExtensions>,
+    ) -> Result<(), Rejection> {
+        let result = self.check_inner(document, header);
+        if let Err(rejection) = result {
+            self.record(document, rejection);
+        }
+        result
+    }
+
+    fn check_inner(
+        &mut self,
+        document: DocumentId,
+        header: &Header<AardvarkExtensions>,
+    ) -> Result<(), Rejection> {
+        if self.is_blocked(&header.public_key) {
+            return Err(Rejection::Blocked);
+        }
+        if header.payload_size > MAX_INGEST_PAYLOAD_SIZE {
+            return Err(Rejection::InvalidPayload);
+        }
+
+        if self.known_authors.contains(&header.public_key) {
+            return Ok(());
+        }
+        let documents = self
+            .unknown_author_documents
+            .entry(header.public_key)
+            .or_default();
+        if !documents.contains(&document) && documents.len() >= MAX_DOCUMENTS_PER_UNKNOWN_AUTHOR {
+            return Err(Rejection::UnknownAuthor);
+        }
+        documents.insert(document);
+
+        Ok(())
+    }
+
+    /// Reset the counters of a document, e.g. when it's subscribed again.
+    pub fn reset_stats(&mut self, document: DocumentId) {
+        self.stats.insert(document, IngestStats::default());
+    }
+
+    pub fn stats(&self, document: &DocumentId) -> IngestStats {
+        self.stats.get(document).cloned().unwrap_or_default()
+    }
+
+    /// Count an operation of `author` with an invalid signature or which failed validation.
+    pub fn record_invalid(&mut self, document: DocumentId, author: PublicKey, reason: String) {
+        let stats = self.stats.entry(document).or_default();
+        stats.rejected += 1;
+        if let Some(rejection) = stats
+            .rejections
+            .iter_mut()
+            .find(|rejection| rejection.public_key == author && rejection.reason == reason)
+        {
+            rejection.count += 1;
+        } else if stats.rejections.len() < MAX_REJECTIONS {
+            stats.rejections.push(RejectedOperations {
+                public_key: author,
+                reason,
+                count: 1,
+            });
+        }
+    }
+
+    fn record(&mut self, document: DocumentId, rejection: Rejection) {
+        let stats = self.stats.entry(document).or_default();
+        match rejection {
+            Rejection::RateLimited => stats.rate_limited += 1,
+            Rejection::InvalidPayload => stats.invalid_payload += 1,
+            Rejection::UnknownAuthor => stats.unknown_author += 1,
+            Rejection::Blocked => stats.blocked += 1,
+        }
+    }
+}
+
+/// Refills with [`OPERATIONS_PER_SECOND`] up to [`OPERATIONS_BURST`], every operation takes one.
+#[derive(Debug)]
+struct TokenBucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl TokenBucket {
+    fn new() -> Self {
+        Self {
+            tokens: OPERATIONS_BURST,
+            updated: Instant::now(),
+        }
+    }
+
+    fn take(&mut self) -> bool {
+        let now = Instant::now();
+        let refill = now.duration_since(self.updated).as_secs_f64() * OPERATIONS_PER_SECOND;
+        self.tokens = (self.tokens + refill).min(OPERATIONS_BURST);
+        self.updated = now;
+
+        if self.tokens < 1.0 {
+            return false;
+        }
+        self.tokens -= 1.0;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use p2panda_core::{Hash, PrivateKey};
+
+    use super::*;
+
+    #[test]
+    fn record_invalid_operations() {
+        let document = DocumentId::from(Hash::new(b"document"));
+        let author = PrivateKey::new().public_key();
+        let mut limiter = IngestLimiter::new(HashSet::new(), HashSet::new());
+
+        limiter.record_invalid(document, author, "invalid signature".to_owned());
+        limiter.record_invalid(document, author, "invalid signature".to_owned());
+        limiter.record_invalid(document, author, "log type missing".to_owned());
+
+        let stats = limiter.stats(&document);
+        assert_eq!(stats.rejected, 3);
+        assert_eq!(
+            stats.rejections,
+            vec![
+                RejectedOperations {
+                    public_key: author,
+                    reason: "invalid signature".to_owned(),
+                    count: 2,
+                },
+                RejectedOperations {
+                    public_key: author,
+                    reason: "log type missing".to_owned(),
+                    count: 1,
+                },
+            ]
+        );
+
+        limiter.reset_stats(document);
+        assert!(limiter.stats(&document).rejections.is_empty());
+    }
+}
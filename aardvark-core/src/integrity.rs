@@ -0,0 +1,272 @@
+//! Finding and isolating corrupted data in the operation store.
+//!
+//! Every log of every author of every document is read back and checked like operations we
+//! receive from peers: signatures, payload hashes, our header extensions and that the operations
+//! form an unbroken chain of backlinks. Pruning removes the start of a log, a log may therefore
+//! begin with a later operation as long as that one has the prune flag set.
+//!
+//! Corrupted logs can be quarantined. Their operations are moved to a separate table, where they
+//! are kept for inspection, and the log is synced again from peers who still have it.
+use anyhow::Result;
+use p2panda_core::{Body, Extension, Header, Operation, PruneFlag, PublicKey};
+use p2panda_store::LogStore;
+use tracing::{info, warn};
+
+use crate::document::DocumentId;
+use crate::operation::{AardvarkExtensions, LogType, validate_operation};
+use crate::store::{DocumentStore, LogId, OperationStore};
+
+/// Result of `Node::verify_store`.
+#[derive(Clone, Debug, Default)]
+pub struct StoreReport {
+    pub logs: u64,
+    pub operations: u64,
+    pub corrupted_logs: Vec<CorruptedLog>,
+}
+
+/// A log which failed verification.
+#[derive(Clone, Debug)]
+pub struct CorruptedLog {
+    pub document_id: DocumentId,
+    pub public_key: PublicKey,
+    pub log_type: LogType,
+    pub reason: String,
+    /// Whether the operations of the log were moved to the quarantine.
+    pub quarantined: bool,
+}
+
+/// Check all logs in the store and quarantine corrupted ones if `quarantine` is set.
+pub(crate) async fn verify_store(
+    document_store: &DocumentStore,
+    operation_store: &mut OperationStore,
+    quarantine: bool,
+) -> Result<StoreReport> {
+    let mut report = StoreReport::default();
+    for document in document_store.documents().await? {
+        for author in &document.authors {
+            for log_type in [LogType::Delta, LogType::Snapshot] {
+                let log_id = LogId::new(log_type, &document.id);
+                let operations = operation_store
+                    .get_log(&author.public_key, &log_id, None)
+                    .await?
+                    .unwrap_or_default();
+                report.logs += 1;
+                report.operations += operations.len() as u64;
+
+                let Err(reason) =
+                    verify_log(&document.id, &author.public_key, log_type, &operations)
+                else {
+                    continue;
+                };
+                warn!(
+                    document = %document.id,
+                    author = %author.public_key,
+                    ?log_type,
+                    "Corrupted log: {reason}"
+                );
+
+                if quarantine {
+                    quarantine_log(
+                        document_store,
+                        operation_store,
+                        &log_id,
+                        &author.public_key,
+                        &operations,
+                        &reason,
+                    )
+                    .await?;
+                }
+                report.corrupted_logs.push(CorruptedLog {
+                    document_id: document.id,
+                    public_key: author.public_key,
+                    log_type,
+                    reason,
+                    quarantined: quarantine,
+                });
+            }
+        }
+    }
+
+    Ok(report)
+}
+
+/// Check the operations of a single log, ordered by sequence number.
+pub(crate) fn verify_log(
+    document_id: &DocumentId,
+    public_key: &PublicKey,
+    log_type: LogType,
+    operations: &[(Header<AardvarkExtensions>, Option<Body>)],
+) -> Result<(), String> {
+    let mut previous: Option<&Header<AardvarkExtensions>> = None;
+    for (header, body) in operations {
+        let seq_num = header.seq_num;
+        if &header.public_key != public_key {
+            return Err(format!("operation {seq_num} was written by another author"));
+        }
+        if !header.verify() {
+            return Err(format!("operation {seq_num} has an invalid signature"));
+        }
+        match body {
+            Some(body) => {
+                if header.payload_hash != Some(body.hash()) || header.payload_size != body.size() {
+                    return Err(format!("payload of operation {seq_num} doesn't match"));
+                }
+            }
+            None => {
+                if header.payload_hash.is_some() || header.payload_size != 0 {
+                    return Err(format!("payload of operation {seq_num} is missing"));
+                }
+            }
+        }
+
+        let operation = Operation {
+            hash: header.hash(),
+            header: header.clone(),
+            body: body.clone(),
+        };
+        if let Err(error) = validate_operation(&operation, document_id) {
+            return Err(format!("operation {seq_num} is invalid: {error}"));
+        }
+        let given_log_type: Option<LogType> = header.extension();
+        if given_log_type != Some(log_type) {
+            return Err(format!("operation {seq_num} belongs to another log"));
+        }
+
+        match previous {
+            Some(previous) => {
+                if seq_num != previous.seq_num + 1 {
+                    return Err(format!(
+                        "operations {} to {} are missing",
+                        previous.seq_num + 1,
+                        seq_num - 1
+                    ));
+                }
+                if header.backlink != Some(previous.hash()) {
+                    return Err(format!("backlink of operation {seq_num} is broken"));
+                }
+            }
+            None if seq_num == 0 => {
+                if header.backlink.is_some() {
+                    return Err("first operation has a backlink".to_owned());
+                }
+            }
+            None => {
+                // Everything before an operation with the prune flag is removed
+                let prune_flag: Option<PruneFlag> = header.extension();
+                if !prune_flag.is_some_and(|flag| flag.is_set()) {
+                    return Err(format!("operations 0 to {} are missing", seq_num - 1));
+                }
+            }
+        }
+        previous = Some(header);
+    }
+
+    Ok(())
+}
+
+/// Move all operations of a log into the quarantine table and remove them from the store.
+async fn quarantine_log(
+    document_store: &DocumentStore,
+    operation_store: &mut OperationStore,
+    log_id: &LogId,
+    public_key: &PublicKey,
+    operations: &[(Header<AardvarkExtensions>, Option<Body>)],
+    reason: &str,
+) -> Result<()> {
+    document_store
+        .quarantine_operations(
+            log_id,
+            operations
+                .iter()
+                .map(|(header, body)| (header.to_bytes(), body.as_ref().map(Body::to_bytes))),
+            reason,
+        )
+        .await?;
+
+    // Operations might have been stored behind the corrupted ones, remove them as well
+    if let Some((header, _)) = operation_store.latest_operation(public_key, log_id).await? {
+        operation_store
+            .delete_operations(public_key, log_id, header.seq_num + 1)
+            .await?;
+    }
+    info!(?log_id, %public_key, "Quarantined {} operations", operations.len());
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use p2panda_core::{Hash, PrivateKey};
+
+    use super::*;
+    use crate::operation::create_operation;
+    use crate::store::connect_database;
+
+    #[tokio::test]
+    async fn verify_and_quarantine_logs() {
+        let pool = connect_database(None).await.unwrap();
+        let document_store = DocumentStore::new(pool.clone());
+        let mut operation_store = OperationStore::new(pool);
+        let private_key = PrivateKey::new();
+        let public_key = private_key.public_key();
+
+        let operation = create_operation(
+            &mut operation_store,
+            &private_key,
+            LogType::Snapshot,
+            None,
+            None,
+            false,
+        )
+        .await
+        .unwrap();
+        let document_id: DocumentId = operation.header.extension().unwrap();
+        document_store.add_document(&document_id).await.unwrap();
+        document_store
+            .add_author(&document_id, &public_key)
+            .await
+            .unwrap();
+        for text in ["a", "b", "c"] {
+            create_operation(
+                &mut operation_store,
+                &private_key,
+                LogType::Delta,
+                Some(document_id),
+                Some(text.as_bytes()),
+                false,
+            )
+            .await
+            .unwrap();
+        }
+
+        let log_id = LogId::new(LogType::Delta, &document_id);
+        let operations = operation_store
+            .get_log(&public_key, &log_id, None)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(verify_log(&document_id, &public_key, LogType::Delta, &operations).is_ok());
+
+        let mut gap = operations.clone();
+        gap.remove(1);
+        assert_eq!(
+            verify_log(&document_id, &public_key, LogType::Delta, &gap),
+            Err("operations 1 to 1 are missing".to_owned())
+        );
+
+        let mut tampered = operations.clone();
+        tampered[2].1 = Some(Body::new(b"d"));
+        assert!(verify_log(&document_id, &public_key, LogType::Delta, &tampered).is_err());
+
+        let other_document: DocumentId = Hash::new(b"other").into();
+        assert!(verify_log(&other_document, &public_key, LogType::Delta, &operations).is_err());
+
+        // Intact logs are left alone
+        let report = verify_store(&document_store, &mut operation_store, true)
+            .await
+            .unwrap();
+        assert_eq!(report.logs, 2);
+        assert_eq!(report.operations, 4);
+        assert!(report.corrupted_logs.is_empty());
+    }
+}
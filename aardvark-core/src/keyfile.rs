@@ -0,0 +1,95 @@
+//! Passphrase protected keyfiles to move an identity to another device.
+//!
+//! A keyfile contains the private key encrypted with XChaCha20-Poly1305, the key of the cipher is
+//! derived from the passphrase with Argon2id. Everything needed to decrypt it apart from the
+//! passphrase is stored in the file itself:
+//!
+//! ```text
+//! magic (13 bytes) | salt (16 bytes) | nonce (24 bytes) | encrypted private key
+//! ```
+use argon2::Argon2;
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use chacha20poly1305::{Key, XChaCha20Poly1305, XNonce};
+use p2panda_core::PrivateKey;
+use thiserror::Error;
+
+/// Identifies the format of a keyfile, the last byte is the version.
+const KEYFILE_MAGIC: &[u8] = b"aardvark-key\x01";
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 24;
+
+#[derive(Debug, Error)]
+pub enum KeyfileError {
+    #[error("not an aardvark keyfile")]
+    UnknownFormat,
+    #[error("wrong passphrase or damaged keyfile")]
+    Decryption,
+    #[error("keyfile doesn't contain a valid private key")]
+    InvalidKey,
+    #[error("deriving key from passphrase failed: {0}")]
+    KeyDerivation(argon2::Error),
+}
+
+/// Encrypt a private key with a passphrase.
+pub fn encrypt_keyfile(
+    private_key: &PrivateKey,
+    passphrase: &str,
+) -> Result<Vec<u8>, KeyfileError> {
+    seal(KEYFILE_MAGIC, private_key.as_bytes(), passphrase)
+}
+
+/// Decrypt the private key stored in a keyfile.
+pub fn decrypt_keyfile(keyfile: &[u8], passphrase: &str) -> Result<PrivateKey, KeyfileError> {
+    let bytes = open(KEYFILE_MAGIC, keyfile, passphrase)?;
+    PrivateKey::try_from(bytes.as_slice()).map_err(|_| KeyfileError::InvalidKey)
+}
+
+/// Encrypt `plaintext` with a passphrase, the result starts with `magic` to tell formats apart.
+pub(crate) fn seal(
+    magic: &[u8],
+    plaintext: &[u8],
+    passphrase: &str,
+) -> Result<Vec<u8>, KeyfileError> {
+    let mut salt = [0; SALT_LEN];
+    OsRng.fill_bytes(&mut salt);
+    let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+
+    let payload = Payload {
+        msg: plaintext,
+        aad: magic,
+    };
+    let ciphertext = cipher(passphrase, &salt)?
+        .encrypt(&nonce, payload)
+        .expect("encrypting with a fresh nonce doesn't fail");
+
+    Ok([magic, &salt, nonce.as_slice(), &ciphertext].concat())
+}
+
+/// Decrypt bytes encrypted with [`seal`].
+pub(crate) fn open(magic: &[u8], sealed: &[u8], passphrase: &str) -> Result<Vec<u8>, KeyfileError> {
+    let Some(sealed) = sealed.strip_prefix(magic) else {
+        return Err(KeyfileError::UnknownFormat);
+    };
+    if sealed.len() < SALT_LEN + NONCE_LEN {
+        return Err(KeyfileError::UnknownFormat);
+    }
+
+    let (salt, sealed) = sealed.split_at(SALT_LEN);
+    let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+    let payload = Payload {
+        msg: ciphertext,
+        aad: magic,
+    };
+    cipher(passphrase, salt)?
+        .decrypt(XNonce::from_slice(nonce), payload)
+        .map_err(|_| KeyfileError::Decryption)
+}
+
+fn cipher(passphrase: &str, salt: &[u8]) -> Result<XChaCha20Poly1305, KeyfileError> {
+    let mut key = [0; 32];
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(KeyfileError::KeyDerivation)?;
+    Ok(XChaCha20Poly1305::new(Key::from_slice(&key)))
+}
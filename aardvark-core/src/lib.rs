@@ -0,0 +1,59 @@
+//! The core of Aardvark, without any dependency on GTK.
+//!
+//! Everything needed to write, store and sync documents with other peers lives here, so other
+//! frontends, e.g. a TUI or the headless `aardvark-pin-server`, can reuse it:
+//!
+//! - [`Node`] runs the p2panda network and the store, documents are joined with
+//!   [`Node::subscribe`] and receive operations of other authors through [`SubscribableDocument`].
+//!   The running node is changed with [`NodeCommand`].
+//! - Operations carry the extensions of [`LogType`] and the document they belong to, payloads are
+//!   opaque to the node. What they contain is up to the frontend, Aardvark stores Loro updates.
+//! - [`anonymity`], [`assists`], [`presence`] and [`receipts`] are helpers of the document model
+//!   which don't need a node.
+//!
+//! The GObject wrappers used by the GTK application are in `aardvark-doc`.
+
+pub mod anonymity;
+pub mod assists;
+pub mod backup;
+mod bandwidth;
+mod chunk;
+mod cold_storage;
+mod compression;
+pub mod debug;
+mod dedup;
+pub mod document;
+mod ephemeral;
+#[cfg(feature = "fixtures")]
+pub mod fixtures;
+mod gossip;
+mod ingest_limit;
+pub mod integrity;
+pub mod keyfile;
+mod metrics;
+pub mod nearby;
+mod network;
+mod node;
+mod operation;
+pub mod pairing;
+pub mod presence;
+pub mod receipts;
+mod reconnect;
+mod reorder;
+pub mod replica;
+pub mod self_test;
+mod store;
+mod sync;
+mod trash;
+mod utils;
+mod wal;
+
+pub use bandwidth::BandwidthLimits;
+pub use cold_storage::ColdStorageBackend;
+pub use document::{HistoryProgress, SubscribableDocument, SyncProgress};
+pub use ephemeral::EphemeralMessage;
+pub use gossip::{GossipPreset, GossipTuning};
+pub use node::{DEFAULT_NETWORK_ID, Node, NodeCommand, SubscriptionHandle};
+pub use operation::LogType;
+pub use sync::NetworkConfig;
+pub use trash::TRASH_RETENTION;
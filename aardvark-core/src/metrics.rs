@@ -0,0 +1,279 @@
+//! Health of a node in the Prometheus text format, for people running always-on replicas.
+//!
+//! The node only collects counters which are cheap to keep up to date. Everything else, like the
+//! size of the store or the number of peers, is read when the metrics are scraped. See
+//! `Node::serve_metrics`.
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use p2panda_core::Hash;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tracing::{debug, error};
+
+use crate::debug::StoreStats;
+use crate::document::DocumentId;
+
+/// Upper bounds of the ingest latency histogram, in seconds.
+const LATENCY_BUCKETS: [f64; 8] = [0.001, 0.005, 0.01, 0.05, 0.1, 0.5, 1.0, 5.0];
+/// Operations which didn't come out of ingest after this long were dropped, e.g. as duplicates.
+const INGEST_TIMEOUT: Duration = Duration::from_secs(60);
+/// Number of operations waiting in ingest after which we look for dropped ones.
+const MAX_PENDING: usize = 4096;
+/// How long we wait for the request of a client which connected to the metrics endpoint.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Histogram of how long operations took from arriving at the node until they were ingested.
+#[derive(Clone, Debug, Default)]
+pub struct IngestLatency {
+    /// Number of observations in each of `LATENCY_BUCKETS`, not cumulative.
+    buckets: [u64; LATENCY_BUCKETS.len()],
+    sum: f64,
+    count: u64,
+}
+
+impl IngestLatency {
+    fn observe(&mut self, latency: Duration) {
+        let seconds = latency.as_secs_f64();
+        if let Some(index) = LATENCY_BUCKETS.iter().position(|bound| seconds <= *bound) {
+            self.buckets[index] += 1;
+        }
+        self.sum += seconds;
+        self.count += 1;
+    }
+}
+
+/// Measures the ingest latency of operations, identified by their hash.
+#[derive(Debug, Default)]
+pub(crate) struct IngestTimer {
+    pending: HashMap<Hash, Instant>,
+    latency: IngestLatency,
+}
+
+impl IngestTimer {
+    pub fn started(&mut self, hash: Hash) {
+        if self.pending.len() >= MAX_PENDING {
+            self.pending
+                .retain(|_, started| started.elapsed() < INGEST_TIMEOUT);
+        }
+        self.pending.entry(hash).or_insert_with(Instant::now);
+    }
+
+    pub fn finished(&mut self, hash: &Hash) {
+        if let Some(started) = self.pending.remove(hash) {
+            self.latency.observe(started.elapsed());
+        }
+    }
+
+    pub fn latency(&self) -> IngestLatency {
+        self.latency.clone()
+    }
+}
+
+/// Everything reported when the metrics are scraped.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct MetricsSnapshot {
+    pub store: StoreStats,
+    /// Number of directly connected peers for every subscribed document.
+    pub peers: Vec<(DocumentId, usize)>,
+    pub sync_sessions: usize,
+    pub sync_completed: u64,
+    pub sync_failed: u64,
+    pub ingest: IngestLatency,
+}
+
+impl MetricsSnapshot {
+    pub fn render(&self) -> String {
+        let mut output = String::new();
+        let mut metric = |name: &str, kind: &str, help: &str, samples: &[(String, String)]| {
+            let _ = writeln!(output, "# HELP aardvark_{name} {help}");
+            let _ = writeln!(output, "# TYPE aardvark_{name} {kind}");
+            for (labels, value) in samples {
+                let _ = writeln!(output, "aardvark_{name}{labels} {value}");
+            }
+        };
+        let value = |value: u64| vec![(String::new(), value.to_string())];
+
+        metric(
+            "store_documents",
+            "gauge",
+            "Documents in the store.",
+            &value(self.store.documents),
+        );
+        metric(
+            "store_authors",
+            "gauge",
+            "Authors of all documents in the store.",
+            &value(self.store.authors),
+        );
+        metric(
+            "store_operations",
+            "gauge",
+            "Operations in the store.",
+            &value(self.store.operations),
+        );
+        metric(
+            "store_size_bytes",
+            "gauge",
+            "Size of the database.",
+            &value(self.store.database_size),
+        );
+        metric(
+            "peers",
+            "gauge",
+            "Peers directly connected on the gossip overlay of a document.",
+            &self
+                .peers
+                .iter()
+                .map(|(document, peers)| {
+                    (format!("{{document=\"{document}\"}}"), peers.to_string())
+                })
+                .collect::<Vec<_>>(),
+        );
+        metric(
+            "sync_sessions",
+            "gauge",
+            "Currently running sync sessions.",
+            &value(self.sync_sessions as u64),
+        );
+        metric(
+            "sync_sessions_completed_total",
+            "counter",
+            "Sync sessions which completed successfully.",
+            &value(self.sync_completed),
+        );
+        metric(
+            "sync_sessions_failed_total",
+            "counter",
+            "Sync sessions which failed or were cancelled.",
+            &value(self.sync_failed),
+        );
+
+        let mut cumulative = 0;
+        let mut samples: Vec<(String, String)> = LATENCY_BUCKETS
+            .iter()
+            .zip(self.ingest.buckets)
+            .map(|(bound, count)| {
+                cumulative += count;
+                (format!("_bucket{{le=\"{bound}\"}}"), cumulative.to_string())
+            })
+            .collect();
+        samples.push((
+            "_bucket{le=\"+Inf\"}".to_owned(),
+            self.ingest.count.to_string(),
+        ));
+        samples.push(("_sum".to_owned(), self.ingest.sum.to_string()));
+        samples.push(("_count".to_owned(), self.ingest.count.to_string()));
+        metric(
+            "ingest_latency_seconds",
+            "histogram",
+            "Time from receiving an operation until it was validated and stored.",
+            &samples,
+        );
+
+        output
+    }
+}
+
+/// Answer HTTP requests for `/metrics` on `listener` with the output of `collect`.
+///
+/// This is not a general purpose HTTP server, it reads only the request line and closes every
+/// connection after the response.
+pub(crate) async fn serve<F, Fut>(listener: TcpListener, collect: F)
+where
+    F: Fn() -> Fut,
+    Fut: Future<Output = Result<String>>,
+{
+    loop {
+        let (mut stream, address) = match listener.accept().await {
+            Ok(connection) => connection,
+            Err(error) => {
+                error!("Failed to accept metrics connection: {error}");
+                continue;
+            }
+        };
+
+        let mut request = [0; 1024];
+        let len = match tokio::time::timeout(REQUEST_TIMEOUT, stream.read(&mut request)).await {
+            Ok(Ok(len)) => len,
+            Ok(Err(error)) => {
+                debug!(%address, "Failed to read metrics request: {error}");
+                continue;
+            }
+            Err(_) => {
+                debug!(%address, "Metrics request timed out");
+                continue;
+            }
+        };
+
+        let response = if request[..len].starts_with(b"GET /metrics ") {
+            match collect().await {
+                Ok(body) => http_response("200 OK", &body),
+                Err(error) => {
+                    error!("Failed to collect metrics: {error}");
+                    http_response("500 Internal Server Error", "")
+                }
+            }
+        } else {
+            http_response("404 Not Found", "")
+        };
+        if let Err(error) = stream.write_all(response.as_bytes()).await {
+            debug!(%address, "Failed to send metrics: {error}");
+        }
+        let _ = stream.shutdown().await;
+    }
+}
+
+fn http_response(status: &str, body: &str) -> String {
+    format!(
+        "HTTP/1.1 {status}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_metrics() {
+        let mut timer = IngestTimer::default();
+        let hash = Hash::new(b"operation");
+        timer.started(hash);
+        timer.finished(&hash);
+        // Operations dropped by ingest are never observed
+        timer.started(Hash::new(b"duplicate"));
+
+        let snapshot = MetricsSnapshot {
+            store: StoreStats {
+                documents: 2,
+                authors: 3,
+                operations: 42,
+                database_size: 4096,
+            },
+            peers: vec![(Hash::new(b"document").into(), 5)],
+            sync_sessions: 1,
+            sync_completed: 7,
+            sync_failed: 2,
+            ingest: timer.latency(),
+        };
+        let output = snapshot.render();
+
+        assert!(
+            output
+                .contains("# TYPE aardvark_store_operations gauge\naardvark_store_operations 42\n")
+        );
+        assert!(output.contains("aardvark_store_size_bytes 4096\n"));
+        assert!(output.contains("aardvark_sync_sessions_failed_total 2\n"));
+        assert!(output.contains(&format!(
+            "aardvark_peers{{document=\"{}\"}} 5\n",
+            DocumentId::from(Hash::new(b"document"))
+        )));
+        assert!(output.contains("aardvark_ingest_latency_seconds_bucket{le=\"5\"} 1\n"));
+        assert!(output.contains("aardvark_ingest_latency_seconds_bucket{le=\"+Inf\"} 1\n"));
+        assert!(output.contains("aardvark_ingest_latency_seconds_count 1\n"));
+    }
+}
@@ -0,0 +1,199 @@
+//! Telling peers on the local network who we are, before any document is synced.
+//!
+//! mDNS discovery only announces node ids and addresses and has no room for anything else. Next
+//! to it every node joins a topic derived from the network id and periodically broadcasts a small
+//! signed announcement with metadata about itself, similar to the TXT record of an mDNS service.
+//! All fields are optional, the display name is only announced when the user set one and
+//! documents are only advertised after the user made them discoverable.
+//!
+//! Announcements are ephemeral messages, peers forget about nodes which stopped announcing.
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime};
+
+use anyhow::{Result, bail};
+use p2panda_core::cbor::{decode_cbor, encode_cbor};
+use p2panda_core::{Hash, PublicKey};
+use serde::{Deserialize, Serialize};
+
+use crate::document::DocumentId;
+use crate::ephemeral::EphemeralMessage;
+
+/// How often we announce ourselves to nearby peers.
+pub const ANNOUNCE_INTERVAL: Duration = Duration::from_secs(30);
+/// Peers which didn't announce themselves for this long are considered gone.
+pub const ANNOUNCE_TTL: Duration = Duration::from_secs(90);
+/// Longest text value of an announcement in bytes, the limit of a TXT record string.
+const MAX_VALUE_LEN: usize = 255;
+/// Most documents a single announcement advertises, keeps announcements small.
+const MAX_ADVERTISED_DOCUMENTS: usize = 16;
+const TOPIC_CONTEXT: &[u8] = b"aardvark nearby peers";
+
+/// Gossip topic all nodes of `network_id` announce themselves on.
+pub(crate) fn topic(network_id: &Hash) -> DocumentId {
+    Hash::new([TOPIC_CONTEXT, network_id.as_bytes()].concat()).into()
+}
+
+/// What we tell nearby peers about ourselves.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PeerMetadata {
+    #[serde(rename = "n", default, skip_serializing_if = "Option::is_none")]
+    pub display_name: Option<String>,
+    #[serde(rename = "v", default, skip_serializing_if = "Option::is_none")]
+    pub app_version: Option<String>,
+    /// Number of documents the peer knows about.
+    #[serde(rename = "d", default)]
+    pub document_count: u32,
+    /// Documents the peer made discoverable, anyone nearby can join them.
+    #[serde(rename = "a", default, skip_serializing_if = "Vec::is_empty")]
+    pub documents: Vec<AdvertisedDocument>,
+}
+
+/// A document advertised to nearby peers.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AdvertisedDocument {
+    #[serde(rename = "i")]
+    pub id: DocumentId,
+    #[serde(rename = "n", default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+}
+
+impl PeerMetadata {
+    pub(crate) fn encode(&self) -> Result<Vec<u8>> {
+        let mut metadata = self.clone();
+        truncate(&mut metadata.display_name);
+        truncate(&mut metadata.app_version);
+        metadata.documents.truncate(MAX_ADVERTISED_DOCUMENTS);
+        for document in &mut metadata.documents {
+            truncate(&mut document.name);
+        }
+        Ok(encode_cbor(&metadata)?)
+    }
+
+    pub(crate) fn decode(bytes: &[u8]) -> Result<Self> {
+        let metadata: Self = decode_cbor(bytes)?;
+        let too_long = |value: &Option<String>| {
+            value
+                .as_ref()
+                .is_some_and(|value| value.len() > MAX_VALUE_LEN)
+        };
+        if too_long(&metadata.display_name)
+            || too_long(&metadata.app_version)
+            || metadata
+                .documents
+                .iter()
+                .any(|document| too_long(&document.name))
+        {
+            bail!("announcement value exceeds {MAX_VALUE_LEN} bytes");
+        }
+        if metadata.documents.len() > MAX_ADVERTISED_DOCUMENTS {
+            bail!("announcement advertises more than {MAX_ADVERTISED_DOCUMENTS} documents");
+        }
+        Ok(metadata)
+    }
+}
+
+/// Cut `value` to at most `MAX_VALUE_LEN` bytes, without splitting a character.
+fn truncate(value: &mut Option<String>) {
+    if let Some(value) = value {
+        let mut len = value.len().min(MAX_VALUE_LEN);
+        while !value.is_char_boundary(len) {
+            len -= 1;
+        }
+        value.truncate(len);
+    }
+}
+
+/// A peer on the local network which announced itself recently.
+#[derive(Clone, Debug)]
+pub struct NearbyPeer {
+    pub public_key: PublicKey,
+    pub metadata: PeerMetadata,
+    pub last_seen: SystemTime,
+}
+
+/// Latest announcement of every nearby peer.
+#[derive(Debug, Default)]
+pub(crate) struct NearbyPeers {
+    peers: HashMap<PublicKey, NearbyPeer>,
+}
+
+impl NearbyPeers {
+    /// Remember the metadata announced in `message`, the message needs to be verified already.
+    pub fn insert(&mut self, message: &EphemeralMessage) -> Result<()> {
+        let metadata = PeerMetadata::decode(&message.payload)?;
+        self.peers.insert(
+            message.public_key,
+            NearbyPeer {
+                public_key: message.public_key,
+                metadata,
+                last_seen: SystemTime::now(),
+            },
+        );
+        Ok(())
+    }
+
+    /// Peers which announced themselves within `ANNOUNCE_TTL`, most recently seen first.
+    pub fn list(&mut self) -> Vec<NearbyPeer> {
+        let now = SystemTime::now();
+        self.peers.retain(|_, peer| {
+            now.duration_since(peer.last_seen).unwrap_or_default() <= ANNOUNCE_TTL
+        });
+
+        let mut peers: Vec<NearbyPeer> = self.peers.values().cloned().collect();
+        peers.sort_by(|a, b| b.last_seen.cmp(&a.last_seen));
+        peers
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use p2panda_core::PrivateKey;
+
+    use super::*;
+
+    #[test]
+    fn announce_metadata() {
+        let private_key = PrivateKey::new();
+        let metadata = PeerMetadata {
+            display_name: Some("ö".repeat(200)),
+            app_version: Some("0.1.0".to_owned()),
+            document_count: 3,
+            documents: (0..20)
+                .map(|index| AdvertisedDocument {
+                    id: Hash::new([index]).into(),
+                    name: Some(format!("Notes {index}")),
+                })
+                .collect(),
+        };
+        let message = EphemeralMessage::new(&private_key, metadata.encode().unwrap()).unwrap();
+
+        let mut nearby = NearbyPeers::default();
+        nearby.insert(&message).unwrap();
+        let peers = nearby.list();
+        assert_eq!(peers.len(), 1);
+        assert_eq!(peers[0].public_key, private_key.public_key());
+
+        // Long names are cut without splitting characters
+        let display_name = peers[0].metadata.display_name.as_ref().unwrap();
+        assert_eq!(display_name, &"ö".repeat(127));
+        assert_eq!(peers[0].metadata.app_version.as_deref(), Some("0.1.0"));
+        assert_eq!(peers[0].metadata.document_count, 3);
+        assert_eq!(peers[0].metadata.documents.len(), MAX_ADVERTISED_DOCUMENTS);
+        assert_eq!(
+            peers[0].metadata.documents[0].name.as_deref(),
+            Some("Notes 0")
+        );
+
+        // Nothing but the document count is announced by default
+        let empty = PeerMetadata::default().encode().unwrap();
+        assert_eq!(
+            PeerMetadata::decode(&empty).unwrap(),
+            PeerMetadata::default()
+        );
+        assert!(
+            nearby
+                .insert(&EphemeralMessage::new(&private_key, vec![1, 2, 3]).unwrap())
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,770 @@
+use crate::bandwidth::Throughput;
+use crate::debug::{GossipStats, IngestStats};
+use crate::dedup::RecentOperations;
+use crate::document::{DocumentId, SyncProgress};
+use crate::ephemeral::EphemeralMessage;
+use crate::gossip::GossipTuning;
+use crate::ingest_limit::IngestLimiter;
+use crate::metrics::{IngestLatency, IngestTimer};
+use crate::nearby::{self, ANNOUNCE_INTERVAL, NearbyPeer, NearbyPeers, PeerMetadata};
+use crate::operation::{
+    AardvarkExtensions, GossipMessage, LogType, decode_gossip_message, encode_gossip_ephemeral,
+    encode_gossip_operation,
+};
+use crate::reorder::{RawOperation, ReorderBuffer};
+use crate::store::{LogId, OperationStore};
+use crate::sync::{NetworkConfig, SyncSessions};
+use anyhow::{Result, bail};
+use p2panda_core::cbor::decode_cbor;
+use p2panda_core::{Extension, Hash, Header, Operation, PrivateKey, PublicKey};
+use p2panda_discovery::mdns::LocalDiscovery;
+use p2panda_net::config::GossipConfig;
+use p2panda_net::{FromNetwork, NetworkBuilder, SyncConfiguration, SystemEvent, ToNetwork};
+use p2panda_store::LogStore;
+use p2panda_stream::{DecodeExt, IngestExt};
+use std::collections::{HashMap, HashSet};
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+use tokio::sync::mpsc;
+use tokio::sync::{Notify, RwLock};
+use tokio::task::JoinHandle;
+use tokio_stream::wrappers::{ReceiverStream, UnboundedReceiverStream};
+use tokio_stream::{Stream, StreamExt};
+use tracing::{debug, error, warn};
+
+/// Ingest does multiple things for us:
+///
+/// - Validate operation- and log integrity and authenticity
+/// - De-duplicate already known operations
+/// - Out-of-order buffering
+/// - Pruning when flag is set
+/// - Persist operation in store
+fn ingest(
+    stream: impl Stream<Item = RawOperation> + Send + 'static,
+    operation_store: OperationStore,
+) -> impl Stream<Item = Operation<AardvarkExtensions>> + Send + 'static {
+    stream
+        // NOTE(adz): The persisting part should happen later, we want to check the payload on
+        // application layer first. In general "ingest" does too much at once and is
+        // inflexible. Related issue: https://github.com/p2panda/p2panda/issues/696
+        .ingest(operation_store, 128)
+        .filter_map(|result| match result {
+            Ok(operation) => Some(operation),
+            Err(err) => {
+                error!("ingesting operation failed: {err}");
+                None
+            }
+        })
+}
+
+/// How often the progress of downloading a document is reported while it changes.
+const SYNC_PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Counters for the progress of downloading a document, see `SyncProgress`.
+#[derive(Debug, Default)]
+struct SyncCounts {
+    operations_received: u64,
+    bytes_received: u64,
+    /// Operations we know of but didn't receive yet, see `ReorderBuffer::missing`.
+    operations_missing: u64,
+}
+
+/// Returns true if a message created at `timestamp` (UNIX seconds) is older than `ttl`.
+fn is_expired(timestamp: u64, ttl: Duration) -> bool {
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    now.saturating_sub(timestamp) > ttl.as_secs()
+}
+
+/// Everything running for a subscribed topic, dropping it leaves the topic.
+#[derive(Debug)]
+struct Subscription {
+    /// Ephemeral messages and announcements, sent after pending operations.
+    tx: mpsc::Sender<ToNetwork>,
+    /// Operations we wrote, they go out ahead of everything else.
+    operations_tx: mpsc::Sender<ToNetwork>,
+    /// Send from both lanes, forward ephemeral messages, route, ingest and forward operations and
+    /// report the sync progress.
+    tasks: Vec<JoinHandle<()>>,
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        for task in &self.tasks {
+            task.abort();
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Network {
+    operation_store: OperationStore,
+    network: p2panda_net::Network<DocumentId>,
+    subscriptions: RwLock<HashMap<DocumentId, Subscription>>,
+    /// In seconds, can be changed while topics are subscribed, see [`Self::set_message_ttl`].
+    message_ttl: Arc<AtomicU64>,
+    gossip_stats: Arc<Mutex<HashMap<DocumentId, GossipStats>>>,
+    /// Bytes received from every peer via gossip and sync, across documents.
+    peer_throughput: Arc<Mutex<HashMap<PublicKey, Throughput>>>,
+    ingest_limiter: Arc<Mutex<IngestLimiter>>,
+    ingest_timer: Arc<Mutex<IngestTimer>>,
+    sync_sessions: SyncSessions,
+    /// What we announce to nearby peers, see `crate::nearby`.
+    metadata: Arc<Mutex<PeerMetadata>>,
+    metadata_changed: Arc<Notify>,
+    nearby_peers: Arc<Mutex<NearbyPeers>>,
+    announce_task: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl Network {
+    #[allow(clippy::too_many_arguments)]
+    pub async fn spawn(
+        network_id: Hash,
+        private_key: PrivateKey,
+        sync_config: SyncConfiguration<DocumentId>,
+        sync_sessions: SyncSessions,
+        operation_store: OperationStore,
+        tuning: &GossipTuning,
+        network_config: &NetworkConfig,
+        known_authors: HashSet<PublicKey>,
+        blocked_authors: HashSet<PublicKey>,
+        metadata: PeerMetadata,
+    ) -> Result<Self> {
+        if !network_config.ipv4 && !network_config.ipv6 {
+            bail!("can't disable both IPv4 and IPv6");
+        }
+        // We always listen on both, a disabled one only on the loopback interface so no traffic
+        // leaves the device through it
+        let bind_ip_v4 = if network_config.ipv4 {
+            network_config.bind_ip_v4.unwrap_or(Ipv4Addr::UNSPECIFIED)
+        } else {
+            Ipv4Addr::LOCALHOST
+        };
+        let bind_ip_v6 = if network_config.ipv6 {
+            network_config.bind_ip_v6.unwrap_or(Ipv6Addr::UNSPECIFIED)
+        } else {
+            Ipv6Addr::LOCALHOST
+        };
+
+        // mDNS only finds the addresses of nearby peers, they tell us more about themselves on
+        // the announcement topic joined below
+        //
+        // NOTE: Routing sync through a SOCKS5 proxy like Tor isn't possible yet. Peers connect
+        // directly via QUIC, which proxies don't carry, and there is no relay whose TCP
+        // connections could be proxied instead. Once a relay is configured, its connections are
+        // what needs to go through the proxy, with mDNS and the announcements disabled.
+        let network = NetworkBuilder::new(network_id.into())
+            .private_key(private_key.clone())
+            .bind_ip_v4(bind_ip_v4)
+            .bind_port_v4(network_config.port)
+            .bind_ip_v6(bind_ip_v6)
+            .bind_port_v6(network_config.port)
+            .discovery(LocalDiscovery::new())
+            .gossip(GossipConfig {
+                max_message_size: tuning.max_message_size,
+            })
+            .sync(sync_config)
+            .build()
+            .await?;
+
+        let network = Self {
+            operation_store,
+            network,
+            subscriptions: RwLock::new(HashMap::new()),
+            message_ttl: Arc::new(AtomicU64::new(tuning.message_ttl.as_secs())),
+            gossip_stats: Arc::new(Mutex::new(HashMap::new())),
+            peer_throughput: Arc::new(Mutex::new(HashMap::new())),
+            ingest_limiter: Arc::new(Mutex::new(IngestLimiter::new(
+                known_authors,
+                blocked_authors,
+            ))),
+            ingest_timer: Arc::new(Mutex::new(IngestTimer::default())),
+            sync_sessions,
+            metadata: Arc::new(Mutex::new(metadata)),
+            metadata_changed: Arc::new(Notify::new()),
+            nearby_peers: Arc::new(Mutex::new(NearbyPeers::default())),
+            announce_task: Mutex::new(None),
+        };
+        network
+            .announce(nearby::topic(&network_id), private_key)
+            .await?;
+
+        Ok(network)
+    }
+
+    /// Periodically broadcast our metadata on the announcement `topic` and collect the
+    /// announcements of nearby peers.
+    async fn announce(&self, topic: DocumentId, private_key: PrivateKey) -> Result<()> {
+        let mut messages = self.subscribe_ephemeral(topic).await?;
+        let document_tx = self
+            .sender(&topic)
+            .await
+            .expect("Announcement topic was just subscribed");
+
+        let metadata = self.metadata.clone();
+        let metadata_changed = self.metadata_changed.clone();
+        let nearby_peers = self.nearby_peers.clone();
+        let task = tokio::task::spawn(async move {
+            let mut interval = tokio::time::interval(ANNOUNCE_INTERVAL);
+            loop {
+                tokio::select! {
+                    _ = interval.tick() => {}
+                    _ = metadata_changed.notified() => {}
+                    message = messages.recv() => {
+                        let Some(message) = message else {
+                            break;
+                        };
+                        if message.public_key == private_key.public_key() {
+                            continue;
+                        }
+                        if let Err(error) = nearby_peers.lock().unwrap().insert(&message) {
+                            debug!(public_key = %message.public_key, "ignoring invalid announcement: {error}");
+                        }
+                        continue;
+                    }
+                }
+
+                let announcement = metadata
+                    .lock()
+                    .unwrap()
+                    .encode()
+                    .and_then(|payload| EphemeralMessage::new(&private_key, payload))
+                    .and_then(encode_gossip_ephemeral);
+                let bytes = match announcement {
+                    Ok(bytes) => bytes,
+                    Err(error) => {
+                        error!("failed to create announcement: {error}");
+                        continue;
+                    }
+                };
+                if document_tx
+                    .send(ToNetwork::Message { bytes })
+                    .await
+                    .is_err()
+                {
+                    break;
+                }
+            }
+        });
+        *self.announce_task.lock().unwrap() = Some(task);
+
+        Ok(())
+    }
+
+    /// Change what we announce to nearby peers, changed metadata is sent right away.
+    pub fn update_metadata(&self, f: impl FnOnce(&mut PeerMetadata)) {
+        let mut metadata = self.metadata.lock().unwrap();
+        let previous = metadata.clone();
+        f(&mut metadata);
+        if *metadata != previous {
+            self.metadata_changed.notify_one();
+        }
+    }
+
+    pub fn message_ttl(&self) -> Duration {
+        Duration::from_secs(self.message_ttl.load(Ordering::Relaxed))
+    }
+
+    /// Drop ephemeral messages older than `ttl` from now on, on all subscribed topics.
+    pub fn set_message_ttl(&self, ttl: Duration) {
+        self.message_ttl.store(ttl.as_secs(), Ordering::Relaxed);
+    }
+
+    /// Bytes per second received recently from every peer which sent us anything.
+    pub fn peer_download_rates(&self) -> HashMap<PublicKey, u64> {
+        let now = Instant::now();
+        let mut peer_throughput = self.peer_throughput.lock().unwrap();
+        peer_throughput.retain(|_, throughput| throughput.rate(now) > 0);
+        peer_throughput
+            .iter_mut()
+            .map(|(peer, throughput)| (*peer, throughput.rate(now)))
+            .collect()
+    }
+
+    /// Peers on the local network which announced themselves recently.
+    pub fn nearby_peers(&self) -> Vec<NearbyPeer> {
+        self.nearby_peers.lock().unwrap().list()
+    }
+
+    /// Gossip traffic of a document since it was last subscribed.
+    pub fn gossip_stats(&self, document: &DocumentId) -> GossipStats {
+        self.gossip_stats
+            .lock()
+            .unwrap()
+            .get(document)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Operations of a document we refused to ingest since it was last subscribed.
+    pub fn ingest_stats(&self, document: &DocumentId) -> IngestStats {
+        self.ingest_limiter.lock().unwrap().stats(document)
+    }
+
+    /// Count an operation of `author` which failed validation, see [`IngestStats::rejected`].
+    pub fn record_invalid_operation(
+        &self,
+        document: DocumentId,
+        author: PublicKey,
+        reason: String,
+    ) {
+        self.ingest_limiter
+            .lock()
+            .unwrap()
+            .record_invalid(document, author, reason);
+    }
+
+    /// Drop everything `author` sends us from now on, or accept it again.
+    pub fn set_author_blocked(&self, author: PublicKey, blocked: bool) {
+        self.ingest_limiter
+            .lock()
+            .unwrap()
+            .set_blocked(author, blocked);
+    }
+
+    pub fn is_author_blocked(&self, author: &PublicKey) -> bool {
+        self.ingest_limiter.lock().unwrap().is_blocked(author)
+    }
+
+    /// How long ingesting operations took since the node started.
+    pub fn ingest_latency(&self) -> IngestLatency {
+        self.ingest_timer.lock().unwrap().latency()
+    }
+
+    fn record_sent(&self, document: &DocumentId, len: usize) {
+        let mut gossip_stats = self.gossip_stats.lock().unwrap();
+        let stats = gossip_stats.entry(*document).or_default();
+        stats.messages_sent += 1;
+        stats.bytes_sent += len as u64;
+    }
+
+    pub async fn shutdown(&self) -> Result<()> {
+        if let Some(task) = self.announce_task.lock().unwrap().take() {
+            task.abort();
+        }
+        self.network.clone().shutdown().await?;
+        Ok(())
+    }
+
+    pub async fn subscribe<Fut, EphemeralFut>(
+        &self,
+        document: DocumentId,
+        f: impl Fn(Operation<AardvarkExtensions>) -> Fut + Send + 'static,
+        on_ephemeral: impl Fn(EphemeralMessage) -> EphemeralFut + Send + 'static,
+        on_sync_progress: impl Fn(SyncProgress) + Send + 'static,
+    ) -> Result<()>
+    where
+        Fut: Future<Output = ()> + Send,
+        EphemeralFut: Future<Output = ()> + Send,
+    {
+        // Join a gossip overlay with peers who are interested in the same document and start sync
+        // with them.
+        let (document_tx, document_rx, _gossip_ready) = self.network.subscribe(document).await?;
+
+        self.gossip_stats
+            .lock()
+            .unwrap()
+            .insert(document, GossipStats::default());
+        self.ingest_limiter.lock().unwrap().reset_stats(document);
+
+        let stream = ReceiverStream::new(document_rx);
+
+        // Deltas are what peers are waiting for while we type, they never queue up behind
+        // ephemeral messages like read receipts or announcements. Snapshots aren't sent on the
+        // gossip overlay at all, sync sessions transfer them over their own connections.
+        let (operations_tx, mut operations_rx) = mpsc::channel::<ToNetwork>(128);
+        let (messages_tx, mut messages_rx) = mpsc::channel::<ToNetwork>(128);
+        let send_task = tokio::task::spawn(async move {
+            loop {
+                let message = tokio::select! {
+                    biased;
+                    Some(message) = operations_rx.recv() => message,
+                    Some(message) = messages_rx.recv() => message,
+                    else => break,
+                };
+                if document_tx.send(message).await.is_err() {
+                    break;
+                }
+            }
+        });
+
+        // Ephemeral messages are never persisted, they bypass ingest and are directly forwarded to
+        // the application layer.
+        let (ephemeral_tx, mut ephemeral_rx) = mpsc::unbounded_channel();
+        let ephemeral_task = tokio::task::spawn(async move {
+            while let Some(message) = ephemeral_rx.recv().await {
+                on_ephemeral(message).await;
+            }
+        });
+
+        // Incoming gossip payloads have a slightly different shape than sync. We convert them
+        // here to follow the p2panda operation tuple of a "header" and separate "body".
+        let gossip_stats = self.gossip_stats.clone();
+        let ingest_limiter = self.ingest_limiter.clone();
+        let message_ttl = self.message_ttl.clone();
+        let peer_throughput = self.peer_throughput.clone();
+        let sync_counts = Arc::new(Mutex::new(SyncCounts::default()));
+        let sync_counts_clone = sync_counts.clone();
+        let stream = stream.filter_map(move |event| match event {
+            FromNetwork::GossipMessage {
+                bytes,
+                delivered_from,
+            } => {
+                peer_throughput
+                    .lock()
+                    .unwrap()
+                    .entry(delivered_from)
+                    .or_default()
+                    .record(bytes.len(), Instant::now());
+                let mut gossip_stats = gossip_stats.lock().unwrap();
+                let stats = gossip_stats.entry(document).or_default();
+                stats.messages_received += 1;
+                stats.bytes_received += bytes.len() as u64;
+
+                match decode_gossip_message(&bytes) {
+                    Ok(GossipMessage::Operation(header, body)) => {
+                        // Invalid headers are dropped when decoding the operation below
+                        let rate_limited =
+                            decode_cbor::<Header<AardvarkExtensions>, _>(header.as_slice())
+                                .is_ok_and(|decoded| {
+                                    !ingest_limiter
+                                        .lock()
+                                        .unwrap()
+                                        .check_gossip(document, decoded.public_key)
+                                });
+                        if rate_limited {
+                            debug!("dropping operation, author is blocked or exceeded rate limit");
+                            stats.messages_dropped += 1;
+                            return None;
+                        }
+                        Some((header, body))
+                    }
+                    Ok(GossipMessage::Ephemeral(message)) => {
+                        if let Err(err) = message.verify() {
+                            error!("invalid ephemeral message: {err}");
+                            stats.messages_dropped += 1;
+                        } else if ingest_limiter
+                            .lock()
+                            .unwrap()
+                            .is_blocked(&message.public_key)
+                        {
+                            debug!("dropping ephemeral message of blocked author");
+                            stats.messages_dropped += 1;
+                        } else if is_expired(
+                            message.timestamp,
+                            Duration::from_secs(message_ttl.load(Ordering::Relaxed)),
+                        ) {
+                            debug!("dropping expired ephemeral message");
+                            stats.messages_dropped += 1;
+                        } else {
+                            let _ = ephemeral_tx.send(message);
+                        }
+                        None
+                    }
+                    Err(err) => {
+                        error!("decoding gossip message failed: {err}");
+                        stats.messages_dropped += 1;
+                        None
+                    }
+                }
+            }
+            FromNetwork::SyncMessage {
+                header,
+                payload,
+                delivered_from,
+            } => {
+                let len = header.len() + payload.as_ref().map_or(0, Vec::len);
+                peer_throughput
+                    .lock()
+                    .unwrap()
+                    .entry(delivered_from)
+                    .or_default()
+                    .record(len, Instant::now());
+                let mut sync_counts = sync_counts_clone.lock().unwrap();
+                sync_counts.operations_received += 1;
+                sync_counts.bytes_received += len as u64;
+                Some((header, payload))
+            }
+        });
+
+        // Decode p2panda operations (they are encoded in CBOR).
+        let mut stream = stream.decode().filter_map(|result| match result {
+            Ok(operation) => Some(operation),
+            Err(err) => {
+                error!("decoding operation failed: {err}");
+                None
+            }
+        });
+
+        // Route operations into two lanes: Deltas are small and need to reach the application
+        // quickly while a peer is typing, snapshots can be large and are processed in the
+        // background. Every log ends up in exactly one lane, this keeps ingesting each log
+        // sequential.
+        let (interactive_tx, interactive_rx) = mpsc::unbounded_channel::<RawOperation>();
+        let (bulk_tx, bulk_rx) = mpsc::unbounded_channel::<RawOperation>();
+        let ingest_limiter = self.ingest_limiter.clone();
+        let ingest_timer = self.ingest_timer.clone();
+        let operation_store = self.operation_store.clone();
+        let sync_counts_clone = sync_counts.clone();
+        let route_task = tokio::task::spawn(async move {
+            // Operations arriving via gossip before their predecessors arrived via sync wait
+            // here instead of failing to ingest.
+            let mut reorder_buffer = ReorderBuffer::new();
+            while let Some(operation) = stream.next().await {
+                // Ingesting verifies the signature as well, but doesn't tell us who sent it
+                if !operation.0.verify() {
+                    warn!(
+                        public_key = %operation.0.public_key,
+                        seq_num = %operation.0.seq_num,
+                        "not ingesting operation: invalid signature"
+                    );
+                    ingest_limiter.lock().unwrap().record_invalid(
+                        document,
+                        operation.0.public_key,
+                        "invalid signature".to_owned(),
+                    );
+                    continue;
+                }
+
+                let check = ingest_limiter.lock().unwrap().check(document, &operation.0);
+                if let Err(rejection) = check {
+                    warn!(
+                        public_key = %operation.0.public_key,
+                        seq_num = %operation.0.seq_num,
+                        "not ingesting operation: {rejection:?}"
+                    );
+                    continue;
+                }
+
+                let log = ReorderBuffer::log_key(&operation.0);
+                if !reorder_buffer.knows(&log) {
+                    let (public_key, log_type) = log;
+                    let log_id = LogId::new(log_type, &document);
+                    let next_seq_num =
+                        match operation_store.latest_operation(&public_key, &log_id).await {
+                            Ok(Some((header, _))) => header.seq_num + 1,
+                            Ok(None) => 0,
+                            Err(err) => {
+                                error!("looking up latest operation failed: {err}");
+                                0
+                            }
+                        };
+                    reorder_buffer.set_next_seq_num(log, next_seq_num);
+                }
+
+                let seq_num = operation.0.seq_num;
+                let ready = reorder_buffer.push(operation, Instant::now());
+                sync_counts_clone.lock().unwrap().operations_missing = reorder_buffer.missing();
+                if ready.is_empty() {
+                    debug!(
+                        %seq_num,
+                        pending = reorder_buffer.len(),
+                        "holding back operation until its predecessors arrived"
+                    );
+                }
+                for operation in ready {
+                    let log_type: Option<LogType> = operation.0.extension();
+                    let lane = match log_type {
+                        Some(LogType::Snapshot) => &bulk_tx,
+                        _ => &interactive_tx,
+                    };
+                    ingest_timer.lock().unwrap().started(operation.0.hash());
+                    if lane.send(operation).is_err() {
+                        return;
+                    }
+                }
+            }
+        });
+
+        let mut interactive = Box::pin(ingest(
+            UnboundedReceiverStream::new(interactive_rx),
+            self.operation_store.clone(),
+        ));
+        let mut bulk = Box::pin(ingest(
+            UnboundedReceiverStream::new(bulk_rx),
+            self.operation_store.clone(),
+        ));
+
+        // Send checked and ingested operations for this document to application layer, deltas
+        // always take precedence over snapshots. Operations which arrived via gossip and sync
+        // are only sent once.
+        let ingest_timer = self.ingest_timer.clone();
+        let forward_task = tokio::task::spawn(async move {
+            let mut recent_operations = RecentOperations::new();
+            loop {
+                let operation = tokio::select! {
+                    biased;
+                    Some(operation) = interactive.next() => operation,
+                    Some(operation) = bulk.next() => operation,
+                    else => break,
+                };
+                ingest_timer.lock().unwrap().finished(&operation.hash);
+                if !recent_operations.insert(operation.hash) {
+                    debug!(hash = %operation.hash, "not forwarding duplicate operation");
+                    continue;
+                }
+                f(operation).await;
+            }
+        });
+
+        // Report how far we got downloading the document until the first sync with a peer is done
+        let sync_sessions = self.sync_sessions.clone();
+        let completed_before = sync_sessions.completed(&document);
+        let progress_task = tokio::task::spawn(async move {
+            let mut interval = tokio::time::interval(SYNC_PROGRESS_INTERVAL);
+            let mut reported = SyncProgress::default();
+            loop {
+                interval.tick().await;
+                let progress = {
+                    let sync_counts = sync_counts.lock().unwrap();
+                    SyncProgress {
+                        operations_received: sync_counts.operations_received,
+                        operations_expected: (sync_counts.operations_missing > 0).then_some(
+                            sync_counts.operations_received + sync_counts.operations_missing,
+                        ),
+                        bytes_received: sync_counts.bytes_received,
+                        done: sync_sessions.completed(&document) > completed_before
+                            && !sync_sessions.is_syncing(&document),
+                    }
+                };
+                if progress != reported {
+                    on_sync_progress(progress);
+                    reported = progress;
+                }
+                if progress.done {
+                    break;
+                }
+            }
+        });
+
+        // Subscribing again replaces the previous subscription and stops its tasks
+        self.subscriptions.write().await.insert(
+            document,
+            Subscription {
+                tx: messages_tx,
+                operations_tx,
+                tasks: vec![
+                    send_task,
+                    ephemeral_task,
+                    route_task,
+                    forward_task,
+                    progress_task,
+                ],
+            },
+        );
+
+        Ok(())
+    }
+
+    /// Join a topic only used for ephemeral messages, e.g. to pair devices.
+    ///
+    /// Returns the ephemeral messages received on it, until it's unsubscribed.
+    pub async fn subscribe_ephemeral(
+        &self,
+        topic: DocumentId,
+    ) -> Result<mpsc::UnboundedReceiver<EphemeralMessage>> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        self.subscribe(
+            topic,
+            // Nobody writes operations for these topics
+            |_| async {},
+            move |message| {
+                let _ = tx.send(message);
+                async {}
+            },
+            |_| {},
+        )
+        .await?;
+        Ok(rx)
+    }
+
+    /// Leave the topic and stop forwarding anything received on it.
+    pub async fn unsubscribe(&self, document_id: &DocumentId) -> Result<()> {
+        self.subscriptions.write().await.remove(document_id);
+
+        Ok(())
+    }
+
+    async fn sender(&self, document: &DocumentId) -> Option<mpsc::Sender<ToNetwork>> {
+        self.subscriptions
+            .read()
+            .await
+            .get(document)
+            .map(|subscription| subscription.tx.clone())
+    }
+
+    /// The lane of operations, see [`Self::subscribe`].
+    async fn operation_sender(&self, document: &DocumentId) -> Option<mpsc::Sender<ToNetwork>> {
+        self.subscriptions
+            .read()
+            .await
+            .get(document)
+            .map(|subscription| subscription.operations_tx.clone())
+    }
+
+    pub async fn subscribe_events<Fut>(
+        &self,
+        f: impl Fn(SystemEvent<DocumentId>) -> Fut + Send + 'static,
+    ) -> Result<()>
+    where
+        Fut: Future<Output = ()> + Send,
+    {
+        let mut events = self.network.events().await?;
+
+        tokio::task::spawn(async move {
+            while let Ok(event) = events.recv().await {
+                f(event).await;
+            }
+        });
+
+        Ok(())
+    }
+
+    /// Send operations to the gossip overlay for `document`.
+    ///
+    /// This will panic if the `document` wasn't subscribed to.
+    pub async fn send_operation(
+        &self,
+        document: &DocumentId,
+        operation: Operation<AardvarkExtensions>,
+    ) -> Result<()> {
+        let document_tx = self
+            .operation_sender(document)
+            .await
+            .expect("Not subscribed to document with id {document_id}");
+
+        let encoded_gossip_operation = encode_gossip_operation(operation.header, operation.body)?;
+        self.record_sent(document, encoded_gossip_operation.len());
+        document_tx
+            .send(ToNetwork::Message {
+                bytes: encoded_gossip_operation,
+            })
+            .await?;
+
+        Ok(())
+    }
+
+    /// Send an ephemeral message to the gossip overlay for `document`.
+    ///
+    /// Fails if the `document` isn't subscribed to, e.g. when a timer sending presence fires
+    /// after the document was closed.
+    pub async fn send_ephemeral(
+        &self,
+        document: &DocumentId,
+        message: EphemeralMessage,
+    ) -> Result<()> {
+        let Some(document_tx) = self.sender(document).await else {
+            bail!("not subscribed to document {document}");
+        };
+
+        let bytes = encode_gossip_ephemeral(message)?;
+        self.record_sent(document, bytes.len());
+        document_tx.send(ToNetwork::Message { bytes }).await?;
+
+        Ok(())
+    }
+}
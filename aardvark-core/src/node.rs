@@ -0,0 +1,1826 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::{Arc, Mutex, OnceLock, Weak};
+use std::time::Duration;
+
+use anyhow::{Result, bail};
+use chrono::Utc;
+use p2panda_core::{Extension, Hash, Operation, PrivateKey, PruneFlag, PublicKey};
+use p2panda_net::{ResyncConfiguration, SyncConfiguration, SystemEvent};
+use p2panda_sync::log_sync::LogSyncProtocol;
+use tokio::net::TcpListener;
+use tokio::runtime::{Builder, Runtime};
+use tokio::sync::{Notify, RwLock, Semaphore, oneshot};
+use tokio::task::JoinHandle;
+use tracing::{debug, error, info, instrument, warn};
+
+use crate::backup::{self, BackupDocument};
+use crate::chunk::{Chunk, ChunkBuffer};
+use crate::cold_storage::{ColdStorage, ColdStorageBackend, pin_documents};
+use crate::compression::PayloadFormat;
+use crate::debug::{
+    BandwidthInfo, OperationInfo, SnapshotInfo, StoreStats, SyncSessionInfo, TopicInfo,
+};
+use crate::document::{Document, DocumentId, HistoryProgress, SubscribableDocument};
+use crate::ephemeral::EphemeralMessage;
+use crate::gossip::GossipTuning;
+use crate::integrity::{self, StoreReport};
+use crate::metrics::{self, MetricsSnapshot};
+use crate::nearby::{AdvertisedDocument, NearbyPeer, PeerMetadata};
+use crate::network::Network;
+use crate::operation::{
+    AardvarkExtensions, LogType, create_operation, create_operations, validate_operation,
+    validate_payload_size,
+};
+use crate::pairing::{self, LinkedDocument, PairingBundle, PairingCode};
+use crate::reconnect::{Backoff, WAKE_CHECK_INTERVAL, WakeDetector};
+use crate::self_test::{self, CheckResult};
+use crate::store::{DocumentStore, OperationStore, connect_database};
+use crate::sync::{ControlledSync, NetworkConfig, SyncSessions};
+use crate::trash::{self, CLEANUP_INTERVAL};
+use crate::wal::WriteAheadLog;
+
+/// Network the app runs on, nodes only find each other when they use the same network id.
+pub const DEFAULT_NETWORK_ID: &str = "aardvark <3";
+
+type Documents = Arc<RwLock<HashMap<DocumentId, Arc<dyn SubscribableDocument>>>>;
+type Neighbors = Arc<RwLock<HashMap<DocumentId, HashSet<PublicKey>>>>;
+/// Documents we're trying to join again after losing all peers, see `crate::reconnect`.
+type Reconnects = Arc<Mutex<HashMap<DocumentId, Backoff>>>;
+
+pub struct Node {
+    inner: OnceLock<Arc<NodeInner>>,
+    ready_notify: Arc<Notify>,
+    documents: Documents,
+    neighbors: Neighbors,
+    reconnects: Reconnects,
+    semaphore_operation_store: Semaphore,
+    cold_storage: RwLock<Option<Arc<ColdStorage>>>,
+    /// Periodically refreshes the pins in cold storage.
+    cold_storage_task: Mutex<Option<JoinHandle<()>>>,
+    /// Joins all documents again when the device woke up from suspend.
+    wake_task: Mutex<Option<JoinHandle<()>>>,
+    /// Answers requests for metrics, see `crate::metrics`.
+    metrics_task: Mutex<Option<JoinHandle<()>>>,
+    /// Deletes documents which were in the trash for long enough, see `crate::trash`.
+    trash_task: Mutex<Option<JoinHandle<()>>>,
+}
+
+/// Changes to a node while it's running, see [`Node::command`].
+#[derive(Clone, Debug)]
+pub enum NodeCommand {
+    /// Apply new settings of the network layer, subscribed documents stay subscribed.
+    ///
+    /// The message TTL and the sync session timeout apply right away. Everything else is part of
+    /// the transport and the sync manager p2panda-net sets up once, it takes effect after a
+    /// restart.
+    Reconfigure {
+        tuning: GossipTuning,
+        network_config: NetworkConfig,
+    },
+}
+
+/// Keeps a document subscribed, see [`Node::subscribe`].
+///
+/// Dropping the handle unsubscribes the document in the background: the gossip overlay is left
+/// and nothing received on it is forwarded to the document anymore. Use [`Self::unsubscribe`] to
+/// wait until that's done.
+#[must_use = "the document is unsubscribed when the handle is dropped"]
+pub struct SubscriptionHandle {
+    document_id: DocumentId,
+    subscription: Option<Subscription>,
+}
+
+/// What's needed to tear down a subscription, without keeping the node alive.
+struct Subscription {
+    inner: Weak<NodeInner>,
+    document: Arc<dyn SubscribableDocument>,
+    documents: Documents,
+    neighbors: Neighbors,
+    reconnects: Reconnects,
+}
+
+impl SubscriptionHandle {
+    pub fn document_id(&self) -> DocumentId {
+        self.document_id
+    }
+
+    /// Unsubscribe the document and wait until we left the gossip overlay.
+    pub async fn unsubscribe(mut self) -> Result<()> {
+        match self.teardown() {
+            Some(task) => task.await?,
+            None => Ok(()),
+        }
+    }
+
+    fn teardown(&mut self) -> Option<JoinHandle<Result<()>>> {
+        let subscription = self.subscription.take()?;
+        // Nothing is left to tear down once the node is gone
+        let inner = subscription.inner.upgrade()?;
+        let document_id = self.document_id;
+        let runtime = inner.runtime.handle().clone();
+        Some(runtime.spawn(async move {
+            let result = subscription.teardown(inner, document_id).await;
+            if let Err(error) = &result {
+                error!(%document_id, "Failed to unsubscribe document: {error}");
+            }
+            result
+        }))
+    }
+}
+
+impl Drop for SubscriptionHandle {
+    fn drop(&mut self) {
+        let _ = self.teardown();
+    }
+}
+
+impl fmt::Debug for SubscriptionHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SubscriptionHandle")
+            .field("document_id", &self.document_id)
+            .field("subscribed", &self.subscription.is_some())
+            .finish()
+    }
+}
+
+impl Subscription {
+    async fn teardown(self, inner: Arc<NodeInner>, document_id: DocumentId) -> Result<()> {
+        {
+            let mut documents = self.documents.write().await;
+            // The document was subscribed again in the meantime, that subscription stays
+            if !documents
+                .get(&document_id)
+                .is_some_and(|document| Arc::ptr_eq(document, &self.document))
+            {
+                return Ok(());
+            }
+            documents.remove(&document_id);
+        }
+        self.neighbors.write().await.remove(&document_id);
+        self.reconnects.lock().unwrap().remove(&document_id);
+
+        inner
+            .document_store
+            .set_last_accessed_for_document(&document_id, Some(Utc::now()))
+            .await?;
+        inner.network.unsubscribe(&document_id).await
+    }
+}
+
+impl Default for Node {
+    fn default() -> Self {
+        Node::new()
+    }
+}
+
+#[derive(Debug)]
+struct NodeInner {
+    runtime: Runtime,
+    operation_store: OperationStore,
+    document_store: DocumentStore,
+    network: Network,
+    private_key: PrivateKey,
+    sync_sessions: SyncSessions,
+    /// What the node was started with, parts of it can be changed with [`NodeCommand`].
+    tuning: GossipTuning,
+    network_config: NetworkConfig,
+    /// Local deltas which aren't stored yet, see `crate::wal`.
+    wal: WriteAheadLog,
+}
+
+impl Node {
+    pub fn new() -> Self {
+        Self {
+            inner: OnceLock::new(),
+            ready_notify: Arc::new(Notify::new()),
+            documents: Arc::new(RwLock::new(HashMap::new())),
+            neighbors: Arc::new(RwLock::new(HashMap::new())),
+            reconnects: Arc::new(Mutex::new(HashMap::new())),
+            // FIXME: This makes sure we only create one operation at the time and not in parallel
+            // Since we would mess up the sequence of operations
+            semaphore_operation_store: Semaphore::new(1),
+            cold_storage: RwLock::new(None),
+            cold_storage_task: Mutex::new(None),
+            wake_task: Mutex::new(None),
+            metrics_task: Mutex::new(None),
+            trash_task: Mutex::new(None),
+        }
+    }
+
+    async fn inner(&self) -> &Arc<NodeInner> {
+        if let Some(inner) = self.inner.get() {
+            inner
+        } else {
+            self.ready_notify.notified().await;
+            self.inner
+                .get()
+                .expect("Inner should always be set at this point")
+        }
+    }
+
+    pub async fn run(
+        &self,
+        private_key: PrivateKey,
+        network_id: Hash,
+        db_location: Option<&Path>,
+        tuning: GossipTuning,
+        network_config: NetworkConfig,
+    ) -> Result<()> {
+        let runtime = Builder::new_multi_thread()
+            .worker_threads(1)
+            .enable_all()
+            .build()?;
+
+        let _guard = runtime.enter();
+
+        let pool = connect_database(db_location).await?;
+
+        let operation_store = OperationStore::new(pool.clone());
+        let document_store = DocumentStore::new(pool);
+
+        let sync_sessions = SyncSessions::default();
+        let sync_config = {
+            let sync = LogSyncProtocol::new(document_store.clone(), operation_store.clone());
+            SyncConfiguration::<DocumentId>::new(ControlledSync::new(
+                sync,
+                sync_sessions.clone(),
+                &network_config,
+            ))
+            .max_concurrent_sync_sessions(network_config.max_concurrent_sessions)
+            .max_retry_attempts(network_config.max_retry_attempts)
+            .resync(ResyncConfiguration::new().interval(tuning.resync_interval.as_secs()))
+        };
+
+        // Edits which didn't make it into the store before a crash, before documents are loaded
+        let wal = WriteAheadLog::new(db_location.map(|location| location.join("wal")));
+        for (document_id, deltas) in wal.recover()? {
+            for delta in &deltas {
+                create_operations(
+                    &mut operation_store.clone(),
+                    &private_key,
+                    LogType::Delta,
+                    document_id,
+                    delta,
+                    false,
+                    PayloadFormat::Plain,
+                )
+                .await?;
+            }
+            wal.clear(&document_id)?;
+            info!(document = %document_id, "Recovered {} unsaved edits", deltas.len());
+        }
+
+        // Before anything else sees the documents
+        if let Err(error) =
+            trash::delete_expired(&document_store, &mut operation_store.clone(), &[]).await
+        {
+            error!("Failed to empty the trash: {error}");
+        }
+
+        let mut known_authors = document_store.all_authors().await?;
+        known_authors.insert(private_key.public_key());
+        let blocked_authors = document_store.blocked_authors().await?;
+        let mut metadata = PeerMetadata::default();
+        update_announcement(&mut metadata, document_store.documents().await?);
+        let network = Network::spawn(
+            network_id,
+            private_key.clone(),
+            sync_config,
+            sync_sessions.clone(),
+            operation_store.clone(),
+            &tuning,
+            &network_config,
+            known_authors,
+            blocked_authors,
+            metadata,
+        )
+        .await?;
+        let inner = Arc::new(NodeInner {
+            runtime,
+            operation_store,
+            document_store,
+            network,
+            private_key,
+            sync_sessions,
+            tuning,
+            network_config,
+            wal,
+        });
+
+        let documents = self.documents.clone();
+        let neighbors = self.neighbors.clone();
+        let reconnects = self.reconnects.clone();
+
+        let inner_clone = inner.clone();
+        inner
+            .network
+            .subscribe_events(move |system_event| {
+                let documents = documents.clone();
+                let neighbors = neighbors.clone();
+                let reconnects = reconnects.clone();
+                let inner_clone = inner_clone.clone();
+                async move {
+                    match system_event {
+                        SystemEvent::GossipJoined {
+                            topic_id,
+                            mut peers,
+                        } => {
+                            peers.retain(|peer| !inner_clone.network.is_author_blocked(peer));
+                            neighbors
+                                .write()
+                                .await
+                                .entry(topic_id.into())
+                                .or_default()
+                                .extend(peers.iter().cloned());
+                            if !peers.is_empty() {
+                                reconnects.lock().unwrap().remove(&topic_id.into());
+                            }
+                            if let Some(document) = documents.read().await.get(&topic_id.into()) {
+                                document.authors_joined(peers);
+                            }
+                        }
+                        SystemEvent::GossipNeighborUp { peer, .. }
+                            if inner_clone.network.is_author_blocked(&peer) => {}
+                        SystemEvent::GossipNeighborUp { topic_id, peer } => {
+                            neighbors
+                                .write()
+                                .await
+                                .entry(topic_id.into())
+                                .or_default()
+                                .insert(peer);
+                            reconnects.lock().unwrap().remove(&topic_id.into());
+                            if let Some(document) = documents.read().await.get(&topic_id.into()) {
+                                document.author_set_online(peer, true);
+                            }
+                        }
+                        SystemEvent::GossipNeighborDown { topic_id, peer } => {
+                            let mut lost_all_peers = false;
+                            if let Some(peers) = neighbors.write().await.get_mut(&topic_id.into()) {
+                                lost_all_peers = peers.remove(&peer) && peers.is_empty();
+                            }
+                            if let Err(error) = inner_clone
+                                .document_store
+                                .set_last_seen_for_author(peer, Some(Utc::now()))
+                                .await
+                            {
+                                error!("Failed to set last seen for author {peer}: {error}");
+                            }
+                            if let Some(document) = documents.read().await.get(&topic_id.into()) {
+                                document.author_set_online(peer, false);
+                            }
+                            // Usually the network changed or broke down, join the overlay again
+                            // until we find peers
+                            if lost_all_peers {
+                                reconnect_document(
+                                    inner_clone,
+                                    documents,
+                                    reconnects,
+                                    topic_id.into(),
+                                );
+                            }
+                        }
+                        _ => {}
+                    };
+                }
+            })
+            .await?;
+
+        // Connections don't survive suspend, but we might not notice they broke until much later
+        let inner_clone = inner.clone();
+        let documents = self.documents.clone();
+        let reconnects = self.reconnects.clone();
+        let wake_task = inner.runtime.spawn(async move {
+            let mut wake_detector = WakeDetector::default();
+            loop {
+                tokio::time::sleep(WAKE_CHECK_INTERVAL).await;
+                if !wake_detector.check(WAKE_CHECK_INTERVAL) {
+                    continue;
+                }
+
+                info!("Woke up from suspend, joining documents again");
+                let document_ids: Vec<DocumentId> =
+                    documents.read().await.keys().copied().collect();
+                for document_id in document_ids {
+                    reconnect_document(
+                        inner_clone.clone(),
+                        documents.clone(),
+                        reconnects.clone(),
+                        document_id,
+                    );
+                }
+            }
+        });
+        *self.wake_task.lock().unwrap() = Some(wake_task);
+
+        let inner_clone = inner.clone();
+        let documents = self.documents.clone();
+        let trash_task = inner.runtime.spawn(async move {
+            loop {
+                tokio::time::sleep(CLEANUP_INTERVAL).await;
+                // Subscribed documents are still shown somewhere, they're deleted next time
+                let in_use: Vec<DocumentId> = documents.read().await.keys().copied().collect();
+                match trash::delete_expired(
+                    &inner_clone.document_store,
+                    &mut inner_clone.operation_store.clone(),
+                    &in_use,
+                )
+                .await
+                {
+                    Ok(deleted) if !deleted.is_empty() => {
+                        if let Err(error) = announce_documents(&inner_clone).await {
+                            error!("Failed to announce documents: {error}");
+                        }
+                    }
+                    Ok(_) => {}
+                    Err(error) => error!("Failed to empty the trash: {error}"),
+                }
+            }
+        });
+        *self.trash_task.lock().unwrap() = Some(trash_task);
+
+        self.inner.set(inner).expect("Node can be run only once");
+        self.ready_notify.notify_waiters();
+
+        Ok(())
+    }
+
+    pub async fn shutdown(&self) -> Result<()> {
+        let inner = self.inner().await;
+        let _guard = inner.runtime.enter();
+
+        if let Some(task) = self.cold_storage_task.lock().unwrap().take() {
+            task.abort();
+        }
+        if let Some(task) = self.wake_task.lock().unwrap().take() {
+            task.abort();
+        }
+        if let Some(task) = self.metrics_task.lock().unwrap().take() {
+            task.abort();
+        }
+        if let Some(task) = self.trash_task.lock().unwrap().take() {
+            task.abort();
+        }
+        inner.network.shutdown().await?;
+
+        Ok(())
+    }
+
+    pub async fn documents(&self) -> Result<Vec<Document>> {
+        let inner = self.inner().await;
+
+        let inner_clone = inner.clone();
+        Ok(inner
+            .runtime
+            .spawn(async move { inner_clone.document_store.documents().await })
+            .await??)
+    }
+
+    #[instrument(skip(self))]
+    pub async fn create_document(&self) -> Result<DocumentId> {
+        let inner = self.inner().await;
+        let _permit = self.semaphore_operation_store.acquire().await.unwrap();
+
+        let inner_clone = inner.clone();
+        let operation = inner.runtime.block_on(async {
+            create_operation(
+                &mut inner_clone.operation_store.clone(),
+                &inner_clone.private_key,
+                LogType::Snapshot,
+                None,
+                None,
+                false,
+            )
+            .await
+        })?;
+
+        let document_id: DocumentId = operation
+            .header
+            .extension()
+            .expect("document id from our own logs");
+
+        Ok(document_id)
+    }
+
+    /// Set the name for a given document
+    ///
+    /// This information will be written to the database
+    pub async fn set_name_for_document(
+        &self,
+        document_id: &DocumentId,
+        name: Option<String>,
+    ) -> Result<()> {
+        let inner = self.inner().await;
+
+        let inner_clone = inner.clone();
+        let document_id = *document_id;
+        inner
+            .runtime
+            .spawn(async move {
+                inner_clone
+                    .document_store
+                    .set_name_for_document(&document_id, name)
+                    .await
+            })
+            .await??;
+        // Discoverable documents are advertised with their name
+        announce_documents(inner).await?;
+
+        Ok(())
+    }
+
+    /// Advertise a document to peers on the local network, who can then join it without an
+    /// invite code. This is stored in the database and stays until it's turned off again.
+    pub async fn set_document_discoverable(
+        &self,
+        document_id: &DocumentId,
+        discoverable: bool,
+    ) -> Result<()> {
+        let inner = self.inner().await;
+
+        let inner_clone = inner.clone();
+        let document_id = *document_id;
+        inner
+            .runtime
+            .spawn(async move {
+                inner_clone
+                    .document_store
+                    .set_discoverable_for_document(&document_id, discoverable)
+                    .await
+            })
+            .await??;
+        announce_documents(inner).await?;
+
+        Ok(())
+    }
+
+    /// Archive a document or restore it from the archive.
+    ///
+    /// Archived documents stay in the store but aren't advertised to nearby peers anymore. The
+    /// document should be unsubscribed when archiving it, to stop syncing it.
+    pub async fn set_document_archived(
+        &self,
+        document_id: &DocumentId,
+        archived: bool,
+    ) -> Result<()> {
+        let inner = self.inner().await;
+
+        let inner_clone = inner.clone();
+        let document_id = *document_id;
+        inner
+            .runtime
+            .spawn(async move {
+                inner_clone
+                    .document_store
+                    .set_archived_for_document(&document_id, archived)
+                    .await
+            })
+            .await??;
+        announce_documents(inner).await?;
+
+        Ok(())
+    }
+
+    /// Pin a document to the top of the document list or unpin it.
+    pub async fn set_document_pinned(&self, document_id: &DocumentId, pinned: bool) -> Result<()> {
+        let inner = self.inner().await;
+
+        let inner_clone = inner.clone();
+        let document_id = *document_id;
+        inner
+            .runtime
+            .spawn(async move {
+                inner_clone
+                    .document_store
+                    .set_pinned_for_document(&document_id, pinned)
+                    .await
+            })
+            .await??;
+
+        Ok(())
+    }
+
+    /// Show an author of a document as `alias` on this device, or with their regular name again
+    /// if it's `None`. Nothing of it is sent to peers.
+    pub async fn set_author_alias(
+        &self,
+        document_id: &DocumentId,
+        public_key: PublicKey,
+        alias: Option<String>,
+    ) -> Result<()> {
+        let inner = self.inner().await;
+
+        let inner_clone = inner.clone();
+        let document_id = *document_id;
+        inner
+            .runtime
+            .spawn(async move {
+                inner_clone
+                    .document_store
+                    .set_author_alias(&document_id, &public_key, alias.as_deref())
+                    .await
+            })
+            .await??;
+
+        Ok(())
+    }
+
+    /// Store the order of the document list after the user reordered it.
+    pub async fn set_document_positions(&self, document_ids: Vec<DocumentId>) -> Result<()> {
+        let inner = self.inner().await;
+
+        let inner_clone = inner.clone();
+        inner
+            .runtime
+            .spawn(async move {
+                inner_clone
+                    .document_store
+                    .set_positions_for_documents(&document_ids)
+                    .await
+            })
+            .await??;
+
+        Ok(())
+    }
+
+    /// Move a document to the trash or restore it from the trash.
+    ///
+    /// Trashed documents are deleted after `TRASH_RETENTION`, unless they're subscribed at that
+    /// time. Like archived documents they should be unsubscribed when moving them to the trash.
+    pub async fn set_document_trashed(
+        &self,
+        document_id: &DocumentId,
+        trashed: bool,
+    ) -> Result<()> {
+        let inner = self.inner().await;
+
+        let inner_clone = inner.clone();
+        let document_id = *document_id;
+        let trashed_at = trashed.then(Utc::now);
+        inner
+            .runtime
+            .spawn(async move {
+                inner_clone
+                    .document_store
+                    .set_trashed_at_for_document(&document_id, trashed_at)
+                    .await
+            })
+            .await??;
+        announce_documents(inner).await?;
+
+        Ok(())
+    }
+
+    /// Delete a document with all its operations right away, e.g. when emptying the trash.
+    #[instrument(skip(self), fields(%document_id))]
+    pub async fn delete_document(&self, document_id: &DocumentId) -> Result<()> {
+        if self.documents.read().await.contains_key(document_id) {
+            bail!("can't delete subscribed document {document_id}");
+        }
+
+        let inner = self.inner().await;
+        let _permit = self.semaphore_operation_store.acquire().await.unwrap();
+
+        let inner_clone = inner.clone();
+        let document_id = *document_id;
+        inner
+            .runtime
+            .spawn(async move {
+                trash::delete_document(
+                    &inner_clone.document_store,
+                    &mut inner_clone.operation_store.clone(),
+                    &document_id,
+                )
+                .await
+            })
+            .await??;
+        announce_documents(inner).await?;
+
+        Ok(())
+    }
+
+    /// Check all stored operations for corruption.
+    ///
+    /// With `quarantine` set, the operations of corrupted logs are moved out of the store and the
+    /// logs are synced again from peers who still have them.
+    pub async fn verify_store(&self, quarantine: bool) -> Result<StoreReport> {
+        let inner = self.inner().await;
+        // Nothing is written while we look at the logs
+        let _permit = self.semaphore_operation_store.acquire().await.unwrap();
+
+        let inner_clone = inner.clone();
+        let report = inner
+            .runtime
+            .spawn(async move {
+                integrity::verify_store(
+                    &inner_clone.document_store,
+                    &mut inner_clone.operation_store.clone(),
+                    quarantine,
+                )
+                .await
+            })
+            .await??;
+        info!(
+            logs = report.logs,
+            operations = report.operations,
+            corrupted = report.corrupted_logs.len(),
+            "Verified store"
+        );
+
+        Ok(report)
+    }
+
+    /// Bundle our identity, all documents and their operations into a backup encrypted with
+    /// `passphrase`.
+    #[instrument(skip_all)]
+    pub async fn export_backup(&self, passphrase: &str) -> Result<Vec<u8>> {
+        let inner = self.inner().await;
+        // A consistent state of all logs, nothing is written while we read them
+        let _permit = self.semaphore_operation_store.acquire().await.unwrap();
+
+        let inner_clone = inner.clone();
+        let passphrase = passphrase.to_owned();
+        inner
+            .runtime
+            .spawn(async move {
+                let backup = backup::collect(
+                    &inner_clone.document_store,
+                    &inner_clone.operation_store,
+                    &inner_clone.private_key,
+                )
+                .await?;
+                // Deriving the key from the passphrase is deliberately slow
+                tokio::task::spawn_blocking(move || backup.seal(&passphrase)).await?
+            })
+            .await?
+    }
+
+    /// Add the documents of a backup to the store, see [`crate::backup::Backup::decode_documents`].
+    ///
+    /// This has to happen before the documents are subscribed, subscribed documents don't learn
+    /// about the restored operations.
+    #[instrument(skip_all)]
+    pub async fn restore_backup(&self, documents: Vec<BackupDocument>) -> Result<()> {
+        let subscribed = self.documents.read().await;
+        if let Some(document) = documents
+            .iter()
+            .find(|document| subscribed.contains_key(&document.id))
+        {
+            bail!("can't restore subscribed document {}", document.id);
+        }
+        drop(subscribed);
+
+        let inner = self.inner().await;
+        let _permit = self.semaphore_operation_store.acquire().await.unwrap();
+
+        let inner_clone = inner.clone();
+        inner
+            .runtime
+            .spawn(async move {
+                backup::restore(
+                    &inner_clone.document_store,
+                    &mut inner_clone.operation_store.clone(),
+                    &documents,
+                )
+                .await
+            })
+            .await??;
+        announce_documents(inner).await?;
+
+        Ok(())
+    }
+
+    /// Join the gossip overlay of a document and forward everything we receive on it to
+    /// `document`, until the returned handle is dropped.
+    // TODO: check if peers are online and call SubscribableDocument::author_set_online().
+    // This requires system events tracking
+    #[instrument(skip(self, document), fields(%document_id))]
+    pub async fn subscribe<T: SubscribableDocument + 'static>(
+        &self,
+        document_id: DocumentId,
+        document: T,
+    ) -> Result<SubscriptionHandle> {
+        let document: Arc<dyn SubscribableDocument> = Arc::new(document);
+        let inner = self.inner().await;
+        let _permit = self.semaphore_operation_store.acquire().await.unwrap();
+
+        let inner_clone = inner.clone();
+        let stored_operations = inner
+            .runtime
+            .spawn(async move {
+                inner_clone
+                    .document_store
+                    .add_document(&document_id)
+                    .await?;
+                // Add ourselves as an author to the document store.
+                inner_clone
+                    .document_store
+                    .add_author(&document_id, &inner_clone.private_key.public_key())
+                    .await?;
+                inner_clone
+                    .document_store
+                    .operations_for_document(&inner_clone.operation_store, &document_id)
+                    .await
+            })
+            .await??;
+        if let Err(error) = announce_documents(inner).await {
+            warn!("Failed to announce documents: {error}");
+        }
+
+        // Chunks of large payloads are buffered until all of them arrived, stored chunks of
+        // payloads we don't have completely yet wait there for the rest to come in
+        let chunk_buffer = Arc::new(Mutex::new(ChunkBuffer::default()));
+        for operation in stored_operations {
+            // Send all stored operation bytes to the app,
+            // it doesn't matter if the app already knows some or all of them
+            let public_key = operation.header.public_key;
+            match reassemble_payload(&chunk_buffer, operation) {
+                Ok(Some(payload)) => document.bytes_received(public_key, payload),
+                Ok(None) => {}
+                Err(error) => warn!(%public_key, "Failed to reassemble stored payload: {error}"),
+            }
+        }
+        report_history_progress(&chunk_buffer, &*document);
+
+        inner
+            .runtime
+            .spawn(join_document(
+                inner.clone(),
+                document_id,
+                document.clone(),
+                chunk_buffer,
+            ))
+            .await??;
+
+        self.documents
+            .write()
+            .await
+            .insert(document_id, document.clone());
+
+        Ok(SubscriptionHandle {
+            document_id,
+            subscription: Some(Subscription {
+                inner: Arc::downgrade(inner),
+                document,
+                documents: self.documents.clone(),
+                neighbors: self.neighbors.clone(),
+                reconnects: self.reconnects.clone(),
+            }),
+        })
+    }
+
+    /// Returns the most recent `limit` operations we've stored for a document, newest last.
+    pub async fn operations(
+        &self,
+        document_id: &DocumentId,
+        limit: usize,
+    ) -> Result<Vec<OperationInfo>> {
+        let inner = self.inner().await;
+
+        let inner_clone = inner.clone();
+        let document_id = *document_id;
+        let mut operations = inner
+            .runtime
+            .spawn(async move {
+                inner_clone
+                    .document_store
+                    .operations_for_document(&inner_clone.operation_store, &document_id)
+                    .await
+            })
+            .await??;
+
+        operations.sort_by_key(|operation| operation.header.timestamp);
+        let skip = operations.len().saturating_sub(limit);
+
+        Ok(operations
+            .into_iter()
+            .skip(skip)
+            .map(|operation| {
+                let log_type: Option<LogType> = operation.header.extension();
+                let prune_flag: Option<PruneFlag> = operation.header.extension();
+                OperationInfo {
+                    hash: operation.hash,
+                    public_key: operation.header.public_key,
+                    log_type: log_type.unwrap_or_default(),
+                    seq_num: operation.header.seq_num,
+                    timestamp: operation.header.timestamp,
+                    payload_size: operation.header.payload_size,
+                    prune_flag: prune_flag.is_some_and(|flag| flag.is_set()),
+                }
+            })
+            .collect())
+    }
+
+    /// Returns the snapshots we've stored for a document, newest first.
+    ///
+    /// Every author only keeps their latest snapshot, creating a new one prunes the previous one.
+    /// This still allows going back to the state of the document another author had, e.g. if a
+    /// peer trashed the document.
+    pub async fn list_snapshots(&self, document_id: &DocumentId) -> Result<Vec<SnapshotInfo>> {
+        let mut snapshots: Vec<SnapshotInfo> = self
+            .stored_snapshots(document_id)
+            .await?
+            .into_iter()
+            .map(|(snapshot, _)| snapshot)
+            .collect();
+        snapshots.sort_by(|a, b| b.timestamp.cmp(&a.timestamp));
+
+        Ok(snapshots)
+    }
+
+    /// Returns the payload of the snapshot with `hash`, see [`Self::list_snapshots`].
+    pub async fn snapshot_payload(&self, document_id: &DocumentId, hash: &Hash) -> Result<Vec<u8>> {
+        let Some((_, payload)) = self
+            .stored_snapshots(document_id)
+            .await?
+            .into_iter()
+            .find(|(snapshot, _)| &snapshot.hash == hash)
+        else {
+            bail!("no snapshot {hash} stored for document {document_id}");
+        };
+
+        Ok(payload)
+    }
+
+    /// Read all snapshots of a document from the store, chunked snapshots are put back together.
+    async fn stored_snapshots(
+        &self,
+        document_id: &DocumentId,
+    ) -> Result<Vec<(SnapshotInfo, Vec<u8>)>> {
+        let inner = self.inner().await;
+
+        let inner_clone = inner.clone();
+        let document_id = *document_id;
+        let mut operations = inner
+            .runtime
+            .spawn(async move {
+                inner_clone
+                    .document_store
+                    .operations_for_document(&inner_clone.operation_store, &document_id)
+                    .await
+            })
+            .await??;
+        operations.sort_by_key(|operation| (operation.header.timestamp, operation.header.seq_num));
+
+        let chunk_buffer = Mutex::new(ChunkBuffer::default());
+        // Chunked snapshots are listed by their first operation
+        let mut first_chunks: HashMap<Hash, SnapshotInfo> = HashMap::new();
+        let mut snapshots = Vec::new();
+        for operation in operations {
+            let log_type: Option<LogType> = operation.header.extension();
+            if log_type != Some(LogType::Snapshot) {
+                continue;
+            }
+
+            let mut snapshot = SnapshotInfo {
+                hash: operation.hash,
+                public_key: operation.header.public_key,
+                timestamp: operation.header.timestamp,
+                size: 0,
+            };
+            let chunk: Option<Chunk> = operation.header.extension();
+            if let Some(chunk) = chunk {
+                snapshot = first_chunks
+                    .entry(chunk.payload_hash)
+                    .or_insert(snapshot)
+                    .clone();
+            }
+
+            match reassemble_payload(&chunk_buffer, operation) {
+                Ok(Some(payload)) => {
+                    snapshot.size = payload.len() as u64;
+                    snapshots.push((snapshot, payload));
+                }
+                Ok(None) => {}
+                Err(error) => warn!("Failed to read stored snapshot: {error}"),
+            }
+        }
+
+        Ok(snapshots)
+    }
+
+    /// Returns the gossip neighbors and known authors of a document.
+    pub async fn topic_info(&self, document_id: &DocumentId) -> Result<TopicInfo> {
+        let inner = self.inner().await;
+
+        let inner_clone = inner.clone();
+        let document_id = *document_id;
+        let authors = inner
+            .runtime
+            .spawn(async move {
+                inner_clone
+                    .document_store
+                    .authors_with_last_seen(&document_id)
+                    .await
+            })
+            .await??;
+
+        let neighbors = self
+            .neighbors
+            .read()
+            .await
+            .get(&document_id)
+            .map(|peers| peers.iter().cloned().collect())
+            .unwrap_or_default();
+
+        Ok(TopicInfo {
+            neighbors,
+            authors: authors
+                .into_iter()
+                .map(|author| (author.public_key, author.last_seen))
+                .collect(),
+            gossip: inner.network.gossip_stats(&document_id),
+            ingest: inner.network.ingest_stats(&document_id),
+        })
+    }
+
+    /// Returns sizes of the local database.
+    pub async fn store_stats(&self) -> Result<StoreStats> {
+        let inner = self.inner().await;
+
+        let inner_clone = inner.clone();
+        Ok(inner
+            .runtime
+            .spawn(async move { inner_clone.document_store.stats().await })
+            .await??)
+    }
+
+    /// Serve metrics about the health of this node in the Prometheus text format on
+    /// `http://<address>/metrics`, until the node shuts down.
+    ///
+    /// Returns the address the metrics are served on, useful when binding to port 0.
+    pub async fn serve_metrics(&self, address: SocketAddr) -> Result<SocketAddr> {
+        let inner = self.inner().await;
+
+        let listener = inner
+            .runtime
+            .spawn(async move { TcpListener::bind(address).await })
+            .await??;
+        let address = listener.local_addr()?;
+
+        let inner_clone = inner.clone();
+        let documents = self.documents.clone();
+        let neighbors = self.neighbors.clone();
+        let task = inner.runtime.spawn(async move {
+            metrics::serve(listener, || {
+                collect_metrics(inner_clone.clone(), documents.clone(), neighbors.clone())
+            })
+            .await
+        });
+        if let Some(previous) = self.metrics_task.lock().unwrap().replace(task) {
+            previous.abort();
+        }
+        info!(%address, "Serving metrics");
+
+        Ok(address)
+    }
+
+    /// Settings the gossip overlay is running with.
+    pub async fn gossip_tuning(&self) -> GossipTuning {
+        let inner = self.inner().await;
+        GossipTuning {
+            message_ttl: inner.network.message_ttl(),
+            ..inner.tuning.clone()
+        }
+    }
+
+    /// Change the running node, without restarting it.
+    ///
+    /// Returns whether parts of the change only take effect after a restart.
+    #[instrument(skip(self))]
+    pub async fn command(&self, command: NodeCommand) -> Result<bool> {
+        let inner = self.inner().await;
+        match command {
+            NodeCommand::Reconfigure {
+                tuning,
+                network_config,
+            } => {
+                inner.network.set_message_ttl(tuning.message_ttl);
+                inner
+                    .sync_sessions
+                    .set_session_timeout(network_config.session_timeout);
+                inner
+                    .sync_sessions
+                    .set_bandwidth_limits(network_config.bandwidth);
+
+                // Compared with what we started with, changing a setting back doesn't need a
+                // restart anymore
+                let restart_required = tuning.max_message_size != inner.tuning.max_message_size
+                    || tuning.resync_interval != inner.tuning.resync_interval
+                    || NetworkConfig {
+                        session_timeout: inner.network_config.session_timeout,
+                        bandwidth: inner.network_config.bandwidth,
+                        ..network_config
+                    } != inner.network_config;
+                if restart_required {
+                    info!("Some network settings take effect after a restart");
+                }
+                Ok(restart_required)
+            }
+        }
+    }
+
+    /// Returns all currently running sync sessions.
+    pub async fn sync_sessions(&self) -> Vec<SyncSessionInfo> {
+        self.inner().await.sync_sessions.list()
+    }
+
+    /// Current sync traffic and its limits.
+    pub async fn bandwidth(&self) -> BandwidthInfo {
+        let inner = self.inner().await;
+        let (upload_rate, download_rate) = inner.sync_sessions.throughput();
+        BandwidthInfo {
+            upload_rate,
+            download_rate,
+            limits: inner.sync_sessions.bandwidth_limits(),
+            peer_download_rates: inner.network.peer_download_rates(),
+        }
+    }
+
+    /// Pause or resume a running sync session.
+    ///
+    /// A paused session neither sends nor receives data, the connection is kept open.
+    #[instrument(skip(self))]
+    pub async fn set_sync_session_paused(&self, id: u64, paused: bool) -> Result<()> {
+        self.inner().await.sync_sessions.set_paused(id, paused)
+    }
+
+    /// Abort a running sync session, the document is synced again with the next session.
+    #[instrument(skip(self))]
+    pub async fn cancel_sync_session(&self, id: u64) -> Result<()> {
+        self.inner().await.sync_sessions.cancel(id)
+    }
+
+    /// Pin our snapshots of all documents to the given blob endpoint and keep them up to date.
+    ///
+    /// The endpoint is reached through the SOCKS5 `proxy` if there is one. Pins are refreshed
+    /// every `refresh_interval`, only documents with a new snapshot are uploaded again. Passing
+    /// `None` stops pinning, existing pins are kept on the endpoint.
+    #[instrument(skip(self, backend))]
+    pub async fn set_cold_storage(
+        &self,
+        backend: Option<ColdStorageBackend>,
+        proxy: Option<String>,
+        refresh_interval: Duration,
+    ) {
+        let inner = self.inner().await;
+
+        if let Some(task) = self.cold_storage_task.lock().unwrap().take() {
+            task.abort();
+        }
+
+        let proxy = proxy.as_deref();
+        let cold_storage = match backend.map(|backend| ColdStorage::new(backend, proxy)) {
+            Some(Ok(cold_storage)) => Some(Arc::new(cold_storage)),
+            Some(Err(error)) => {
+                // Never fall back to connecting without the proxy
+                warn!("Failed to set up cold storage: {error}");
+                None
+            }
+            None => None,
+        };
+        *self.cold_storage.write().await = cold_storage.clone();
+        let Some(cold_storage) = cold_storage else {
+            return;
+        };
+
+        let inner_clone = inner.clone();
+        let task = inner.runtime.spawn(async move {
+            let public_key = inner_clone.private_key.public_key();
+            let mut pinned = HashMap::new();
+            let mut interval = tokio::time::interval(refresh_interval);
+            loop {
+                interval.tick().await;
+                if let Err(error) = pin_documents(
+                    &cold_storage,
+                    &inner_clone.operation_store,
+                    &inner_clone.document_store,
+                    &public_key,
+                    &mut pinned,
+                )
+                .await
+                {
+                    warn!("Failed to pin documents to cold storage: {error}");
+                }
+            }
+        });
+        *self.cold_storage_task.lock().unwrap() = Some(task);
+    }
+
+    /// Download the pinned snapshot of a document from cold storage.
+    ///
+    /// Returns `None` if the document was never pinned.
+    #[instrument(skip(self), fields(%document_id))]
+    pub async fn restore_from_cold_storage(
+        &self,
+        document_id: DocumentId,
+    ) -> Result<Option<Vec<u8>>> {
+        let inner = self.inner().await;
+        let Some(cold_storage) = self.cold_storage.read().await.clone() else {
+            bail!("no cold storage configured");
+        };
+
+        Ok(inner
+            .runtime
+            .spawn(async move { cold_storage.get(&document_id).await })
+            .await??)
+    }
+
+    /// Stop accepting operations and messages from `public_key` and stop syncing their logs.
+    ///
+    /// Operations of the author which are already in the store are kept. The blocklist is
+    /// persisted and applies to all documents.
+    #[instrument(skip(self))]
+    pub async fn block_author(&self, public_key: PublicKey) -> Result<()> {
+        self.set_author_blocked(public_key, true).await?;
+
+        let documents = self.documents.read().await;
+        for (document_id, peers) in self.neighbors.write().await.iter_mut() {
+            if !peers.remove(&public_key) {
+                continue;
+            }
+            if let Some(document) = documents.get(document_id) {
+                document.author_set_online(public_key, false);
+            }
+        }
+
+        Ok(())
+    }
+
+    #[instrument(skip(self))]
+    pub async fn unblock_author(&self, public_key: PublicKey) -> Result<()> {
+        self.set_author_blocked(public_key, false).await
+    }
+
+    async fn set_author_blocked(&self, public_key: PublicKey, blocked: bool) -> Result<()> {
+        let inner = self.inner().await;
+
+        let inner_clone = inner.clone();
+        inner
+            .runtime
+            .spawn(async move {
+                inner_clone
+                    .document_store
+                    .set_author_blocked(&public_key, blocked)
+                    .await?;
+                inner_clone.network.set_author_blocked(public_key, blocked);
+                Ok(())
+            })
+            .await?
+    }
+
+    pub async fn blocked_authors(&self) -> Result<HashSet<PublicKey>> {
+        let inner = self.inner().await;
+
+        let inner_clone = inner.clone();
+        inner
+            .runtime
+            .spawn(async move { Ok(inner_clone.document_store.blocked_authors().await?) })
+            .await?
+    }
+
+    /// Hand our identity and all known documents to another device of ours joining with `code`.
+    ///
+    /// Returns once the other device confirmed it received them, fails when the code expired.
+    /// Dropping the returned future stops offering them.
+    #[instrument(skip(self))]
+    pub async fn link_device(&self, code: &PairingCode) -> Result<()> {
+        let inner = self.inner().await;
+
+        let (_cancel_tx, cancel_rx) = oneshot::channel();
+        let inner_clone = inner.clone();
+        let code = code.clone();
+        inner
+            .runtime
+            .spawn(async move {
+                let documents = inner_clone
+                    .document_store
+                    .documents()
+                    .await?
+                    .into_iter()
+                    .map(|document| LinkedDocument {
+                        id: document.id,
+                        name: document.name,
+                    })
+                    .collect();
+                let bundle = PairingBundle::new(&inner_clone.private_key, documents);
+                pairing::offer(
+                    &inner_clone.network,
+                    &inner_clone.private_key,
+                    &code,
+                    bundle,
+                    cancel_rx,
+                )
+                .await
+            })
+            .await?
+    }
+
+    /// Receive the identity and documents another device of ours offers for `code`.
+    ///
+    /// Dropping the returned future stops waiting for them.
+    #[instrument(skip(self))]
+    pub async fn accept_device_link(&self, code: &PairingCode) -> Result<PairingBundle> {
+        let inner = self.inner().await;
+
+        let (_cancel_tx, cancel_rx) = oneshot::channel();
+        let inner_clone = inner.clone();
+        let code = code.clone();
+        inner
+            .runtime
+            .spawn(async move {
+                pairing::accept(
+                    &inner_clone.network,
+                    &inner_clone.private_key,
+                    &code,
+                    cancel_rx,
+                )
+                .await
+            })
+            .await?
+    }
+
+    /// Check the connection to other devices, `report` is called with the result of every check
+    /// as soon as it's done.
+    ///
+    /// Dropping the returned future stops the checks.
+    #[instrument(skip_all)]
+    pub async fn self_test(&self, report: impl Fn(CheckResult) + Send + 'static) -> Result<()> {
+        let inner = self.inner().await;
+        let neighbors = self
+            .neighbors
+            .read()
+            .await
+            .values()
+            .flatten()
+            .copied()
+            .collect();
+
+        let (_cancel_tx, cancel_rx) = oneshot::channel();
+        let inner_clone = inner.clone();
+        inner
+            .runtime
+            .spawn(async move {
+                self_test::run(
+                    &inner_clone.network,
+                    &inner_clone.private_key,
+                    neighbors,
+                    report,
+                    cancel_rx,
+                )
+                .await
+            })
+            .await?
+    }
+
+    /// Add a document to the store without subscribing to it, e.g. when it was handed over by
+    /// another device of ours. Its content is synced once it's subscribed.
+    pub async fn add_document(&self, document_id: &DocumentId, name: Option<String>) -> Result<()> {
+        let inner = self.inner().await;
+
+        let inner_clone = inner.clone();
+        let document_id = *document_id;
+        inner
+            .runtime
+            .spawn(async move {
+                inner_clone
+                    .document_store
+                    .add_document(&document_id)
+                    .await?;
+                inner_clone
+                    .document_store
+                    .set_name_for_document(&document_id, name)
+                    .await
+            })
+            .await??;
+        announce_documents(inner).await?;
+
+        Ok(())
+    }
+
+    /// Change the name and app version we announce to peers on the local network, `None` doesn't
+    /// announce them at all.
+    pub async fn set_peer_metadata(
+        &self,
+        display_name: Option<String>,
+        app_version: Option<String>,
+    ) {
+        self.inner().await.network.update_metadata(|metadata| {
+            metadata.display_name = display_name;
+            metadata.app_version = app_version;
+        });
+    }
+
+    /// Peers on the local network which announced themselves recently.
+    pub async fn nearby_peers(&self) -> Vec<NearbyPeer> {
+        self.inner().await.network.nearby_peers()
+    }
+
+    /// Broadcast a "text delta" on the gossip overlay.
+    ///
+    /// This should be used to inform all subscribed peers about small changes to the text
+    /// document (Delta-Based CRDT).
+    #[instrument(skip(self, bytes), fields(%document_id, len = bytes.len()))]
+    pub async fn delta(&self, document_id: DocumentId, bytes: Vec<u8>) -> Result<()> {
+        let inner = self.inner().await;
+        // On disk before waiting for other writes, the delta only exists in memory until then
+        let entry = inner
+            .wal
+            .append(&document_id, &bytes)
+            .inspect_err(|error| warn!("Failed to write delta to the write-ahead log: {error}"))
+            .ok();
+        let _permit = self.semaphore_operation_store.acquire().await.unwrap();
+
+        let inner_clone = inner.clone();
+        inner
+            .runtime
+            .spawn(async move {
+                let mut operation_store = inner_clone.operation_store.clone();
+                // Append one operation to our "ephemeral" delta log, or multiple if the delta
+                // needs to be chunked.
+                let operations = create_operations(
+                    &mut operation_store,
+                    &inner_clone.private_key,
+                    LogType::Delta,
+                    document_id,
+                    &bytes,
+                    false,
+                    PayloadFormat::Plain,
+                )
+                .await?;
+                if let Some(entry) = entry {
+                    if let Err(error) = inner_clone.wal.remove(&document_id, entry) {
+                        warn!("Failed to remove delta from the write-ahead log: {error}");
+                    }
+                }
+                inner_clone
+                    .document_store
+                    .set_last_edited_for_document(&document_id, Utc::now())
+                    .await?;
+
+                // Broadcast operations on gossip overlay.
+                for operation in operations {
+                    inner_clone
+                        .network
+                        .send_operation(&document_id, operation)
+                        .await?;
+                }
+
+                Ok::<_, anyhow::Error>(())
+            })
+            .await??;
+
+        debug!("Delta operation sent");
+
+        Ok(())
+    }
+
+    /// Broadcast an ephemeral message on the gossip overlay.
+    ///
+    /// The message is signed but neither persisted nor synced later, peers who are not online
+    /// right now will never receive it.
+    #[instrument(skip(self, bytes), fields(%document_id, len = bytes.len()))]
+    pub async fn ephemeral(&self, document_id: DocumentId, bytes: Vec<u8>) -> Result<()> {
+        let inner = self.inner().await;
+
+        let inner_clone = inner.clone();
+        inner
+            .runtime
+            .spawn(async move {
+                let message = EphemeralMessage::new(&inner_clone.private_key, bytes)?;
+                inner_clone
+                    .network
+                    .send_ephemeral(&document_id, message)
+                    .await
+            })
+            .await??;
+
+        Ok(())
+    }
+
+    /// Same as [`Self::Delta`] next to persisting a whole snapshot and pruning.
+    ///
+    /// Snapshots contain the whole text document history and are much larger than deltas. This
+    /// data will only be sent to newly incoming peers via the sync protocol.
+    ///
+    /// Since a snapshot contains all data we need to reliably reconcile documents (it is a
+    /// State-Based CRDT) this command prunes all our logs and removes past snapshot- and delta
+    /// operations.
+    #[instrument(skip(self, snapshot_bytes), fields(%document_id, len = snapshot_bytes.len()))]
+    pub async fn snapshot(&self, document_id: DocumentId, snapshot_bytes: Vec<u8>) -> Result<()> {
+        let inner = self.inner().await;
+
+        // Deltas must wait until the prune operation below is written as well, otherwise deltas
+        // stored in between would be pruned without being part of this snapshot.
+        let _permit = self.semaphore_operation_store.acquire().await.unwrap();
+        let inner_clone = inner.clone();
+        inner
+            .runtime
+            .spawn(async move {
+                let mut operation_store = inner_clone.operation_store.clone();
+
+                // Append an operation to our "snapshot" log and set the prune flag to
+                // true. This will remove previous snapshots.
+                //
+                // Snapshots are not broadcasted on the gossip overlay as they would be
+                // too large. Peers will sync them up when they join the document.
+                //
+                // Snapshots are compressed, large ones are then split over multiple operations.
+                // Peers put them back together after syncing all chunks.
+                create_operations(
+                    &mut operation_store,
+                    &inner_clone.private_key,
+                    LogType::Snapshot,
+                    document_id,
+                    &snapshot_bytes,
+                    true,
+                    PayloadFormat::Zstd,
+                )
+                .await
+            })
+            .await??;
+
+        let inner_clone = inner.clone();
+        inner
+            .runtime
+            .spawn(async move {
+                let mut operation_store = inner_clone.operation_store.clone();
+
+                // Append an operation to our "ephemeral" delta log and set the prune
+                // flag to true.
+                //
+                // This signals removing all previous "delta" operations now. This is
+                // some sort of garbage collection whenever we snapshot. Snapshots
+                // already contain all history, there is no need to keep duplicate
+                // "delta" data around.
+                let operation = create_operation(
+                    &mut operation_store,
+                    &inner_clone.private_key,
+                    LogType::Delta,
+                    Some(document_id),
+                    None,
+                    true,
+                )
+                .await?;
+
+                // Broadcast operation on gossip overlay.
+                inner_clone
+                    .network
+                    .send_operation(&document_id, operation)
+                    .await
+            })
+            .await??;
+
+        info!("Snapshot saved");
+
+        Ok(())
+    }
+}
+
+async fn collect_metrics(
+    inner: Arc<NodeInner>,
+    documents: Documents,
+    neighbors: Neighbors,
+) -> Result<String> {
+    let store = inner.document_store.stats().await?;
+    let peers = {
+        let neighbors = neighbors.read().await;
+        documents
+            .read()
+            .await
+            .keys()
+            .map(|document_id| {
+                let peers = neighbors.get(document_id).map_or(0, HashSet::len);
+                (*document_id, peers)
+            })
+            .collect()
+    };
+    let (sync_completed, sync_failed) = inner.sync_sessions.totals();
+
+    let snapshot = MetricsSnapshot {
+        store,
+        peers,
+        sync_sessions: inner.sync_sessions.list().len(),
+        sync_completed,
+        sync_failed,
+        ingest: inner.network.ingest_latency(),
+    };
+    Ok(snapshot.render())
+}
+
+/// Tell nearby peers how many documents we know and which ones they can join, see
+/// `crate::nearby`.
+async fn announce_documents(inner: &Arc<NodeInner>) -> Result<()> {
+    let inner_clone = inner.clone();
+    let documents = inner
+        .runtime
+        .spawn(async move { inner_clone.document_store.documents().await })
+        .await??;
+    inner
+        .network
+        .update_metadata(|metadata| update_announcement(metadata, documents));
+
+    Ok(())
+}
+
+fn update_announcement(metadata: &mut PeerMetadata, documents: Vec<Document>) {
+    metadata.document_count = documents.len() as u32;
+    metadata.documents = documents
+        .into_iter()
+        .filter(|document| {
+            document.discoverable && !document.archived && document.trashed_at.is_none()
+        })
+        .map(|document| AdvertisedDocument {
+            id: document.id,
+            name: document.name,
+        })
+        .collect();
+}
+
+/// Join the overlay of a document again until we find peers, waiting longer after every attempt.
+///
+/// Does nothing if we're already reconnecting the document. Stops once a peer joined us or the
+/// document was unsubscribed.
+fn reconnect_document(
+    inner: Arc<NodeInner>,
+    documents: Documents,
+    reconnects: Reconnects,
+    document_id: DocumentId,
+) {
+    {
+        let mut reconnects = reconnects.lock().unwrap();
+        if reconnects.contains_key(&document_id) {
+            return;
+        }
+        reconnects.insert(document_id, Backoff::default());
+    }
+
+    inner.runtime.spawn(async move {
+        loop {
+            let Some(document) = documents.read().await.get(&document_id).cloned() else {
+                reconnects.lock().unwrap().remove(&document_id);
+                break;
+            };
+            // The entry is removed as soon as a peer joined us
+            let Some((attempt, delay)) =
+                reconnects
+                    .lock()
+                    .unwrap()
+                    .get_mut(&document_id)
+                    .map(|backoff| {
+                        let delay = backoff.next_delay();
+                        (backoff.attempt(), delay)
+                    })
+            else {
+                break;
+            };
+
+            document.reconnecting(attempt, delay);
+            tokio::time::sleep(delay).await;
+            if !reconnects.lock().unwrap().contains_key(&document_id) {
+                break;
+            }
+
+            info!(%document_id, attempt, "Joining document again");
+            let chunk_buffer = Arc::new(Mutex::new(ChunkBuffer::default()));
+            if let Err(error) =
+                join_document(inner.clone(), document_id, document, chunk_buffer).await
+            {
+                warn!(%document_id, "Failed to join document again: {error}");
+            }
+        }
+    });
+}
+
+/// Join the gossip overlay of a document and forward everything we receive on it to `document`.
+///
+/// Joining an overlay again replaces the previous subscription, e.g. when reconnecting.
+async fn join_document(
+    inner: Arc<NodeInner>,
+    document_id: DocumentId,
+    document: Arc<dyn SubscribableDocument>,
+    chunk_buffer: Arc<Mutex<ChunkBuffer>>,
+) -> Result<()> {
+    let inner_clone = inner.clone();
+    let document_clone = document.clone();
+    let document_ephemeral = document.clone();
+    let document_sync = document.clone();
+    inner
+        .network
+        .subscribe(
+            document_id,
+            move |operation| {
+                let inner_clone = inner_clone.clone();
+                let document_clone = document_clone.clone();
+                let chunk_buffer = chunk_buffer.clone();
+                async move {
+                    // Process the operations and forward application messages to app layer. This is where
+                    // we "materialize" our application state from incoming "application events".
+                    // Validation for our custom "document" extension.
+                    if let Err(err) = validate_operation(&operation, &document_id)
+                        .and_then(|_| validate_payload_size(&operation))
+                    {
+                        warn!(
+                            public_key = %operation.header.public_key,
+                            seq_num = %operation.header.seq_num,
+                            "{err}"
+                        );
+                        inner_clone.network.record_invalid_operation(
+                            document_id,
+                            operation.header.public_key,
+                            err.to_string(),
+                        );
+                        document_clone
+                            .author_incompatible(operation.header.public_key, err.to_string());
+                        return;
+                    }
+
+                    // When we discover a new author we need to add them to our document store.
+                    if let Err(error) = inner_clone
+                        .document_store
+                        .add_author(&document_id, &operation.header.public_key)
+                        .await
+                    {
+                        error!("Can't store author to database: {error}");
+                    }
+
+                    // Forward the payload up to the app, once all chunks of it arrived.
+                    let public_key = operation.header.public_key;
+                    let chunk: Option<Chunk> = operation.header.extension();
+                    let payload = reassemble_payload(&chunk_buffer, operation);
+                    if chunk.is_some() {
+                        report_history_progress(&chunk_buffer, &*document_clone);
+                    }
+                    let payload = match payload {
+                        Ok(payload) => payload,
+                        Err(error) => {
+                            warn!(%public_key, "Failed to reassemble payload: {error}");
+                            inner_clone.network.record_invalid_operation(
+                                document_id,
+                                public_key,
+                                error.to_string(),
+                            );
+                            document_clone.author_incompatible(public_key, error.to_string());
+                            return;
+                        }
+                    };
+                    if let Some(payload) = payload {
+                        if let Err(error) = inner_clone
+                            .document_store
+                            .set_last_edited_for_document(&document_id, Utc::now())
+                            .await
+                        {
+                            error!("Can't store last edit of document: {error}");
+                        }
+
+                        document_clone.bytes_received(public_key, payload);
+                    }
+                }
+            },
+            move |message| {
+                let document_ephemeral = document_ephemeral.clone();
+                async move {
+                    document_ephemeral
+                        .ephemeral_bytes_received(message.public_key, message.payload);
+                }
+            },
+            move |progress| document_sync.sync_progress(progress),
+        )
+        .await
+}
+
+/// Let the app know how many chunks of large payloads are still missing.
+fn report_history_progress(chunk_buffer: &Mutex<ChunkBuffer>, document: &dyn SubscribableDocument) {
+    let progress = chunk_buffer.lock().unwrap().progress();
+    document.history_progress(
+        progress.map(|(chunks_received, chunks_expected)| HistoryProgress {
+            chunks_received,
+            chunks_expected,
+        }),
+    );
+}
+
+/// Returns the decoded payload of an operation, or `None` if it has no body or is a chunk of a
+/// payload we didn't receive completely yet.
+fn reassemble_payload(
+    chunk_buffer: &Mutex<ChunkBuffer>,
+    operation: Operation<AardvarkExtensions>,
+) -> Result<Option<Vec<u8>>> {
+    let Some(body) = operation.body else {
+        return Ok(None);
+    };
+
+    let chunk: Option<Chunk> = operation.header.extension();
+    let payload = match chunk {
+        Some(chunk) => chunk_buffer.lock().unwrap().insert(
+            operation.header.public_key,
+            &chunk,
+            body.to_bytes(),
+        )?,
+        None => Some(body.to_bytes()),
+    };
+    let format: PayloadFormat = operation.header.extension().unwrap_or_default();
+    payload.map(|payload| format.decode(payload)).transpose()
+}
@@ -8,7 +8,10 @@ use p2panda_store::LogStore;
 use p2panda_store::OperationStore as TraitOperationStore;
 use serde::{Deserialize, Serialize};
 
+use crate::chunk::{Chunk, MAX_PAYLOAD_SIZE, split_payload};
+use crate::compression::PayloadFormat;
 use crate::document::DocumentId;
+use crate::ephemeral::EphemeralMessage;
 use crate::store::{LogId, OperationStore};
 
 /// Custom extensions for p2panda header.
@@ -43,6 +46,20 @@ pub struct AardvarkExtensions {
     /// we take the hash of the header itself to derive the document id.
     #[serde(rename = "d")]
     pub document: Option<DocumentId>,
+
+    /// Set if the body of this operation is only a part of a larger payload.
+    ///
+    /// Operation bodies are limited to `MAX_PAYLOAD_SIZE` bytes, larger payloads (usually
+    /// snapshots) are split over multiple consecutive operations in the same log.
+    #[serde(rename = "c", skip_serializing_if = "Option::is_none", default)]
+    pub chunk: Option<Chunk>,
+
+    /// Encoding of the payload, the same for all chunks of it.
+    ///
+    /// Peers which don't know about this field read payloads as plain, so it's only set for
+    /// compressed ones.
+    #[serde(rename = "f", skip_serializing_if = "PayloadFormat::is_plain", default)]
+    pub format: PayloadFormat,
 }
 
 #[derive(Copy, Clone, Default, Debug, PartialEq, Eq, StdHash, Serialize, Deserialize)]
@@ -70,6 +87,24 @@ impl Extension<LogType> for AardvarkExtensions {
     }
 }
 
+impl Extension<Chunk> for AardvarkExtensions {
+    fn extract(header: &Header<Self>) -> Option<Chunk> {
+        header
+            .extensions
+            .as_ref()
+            .and_then(|extensions| extensions.chunk.clone())
+    }
+}
+
+impl Extension<PayloadFormat> for AardvarkExtensions {
+    fn extract(header: &Header<Self>) -> Option<PayloadFormat> {
+        header
+            .extensions
+            .as_ref()
+            .map(|extensions| extensions.format)
+    }
+}
+
 impl Extension<DocumentId> for AardvarkExtensions {
     fn extract(header: &Header<Self>) -> Option<DocumentId> {
         // Check if header mentions an document.
@@ -116,6 +151,9 @@ impl Extension<LogId> for AardvarkExtensions {
 ///
 /// If no document is specified we create a new operation in a new log. The resulting hash of the
 /// header can be used to identify that new document.
+///
+/// The body may be at most `MAX_PAYLOAD_SIZE` bytes, use [`create_operations`] for payloads of
+/// any size.
 pub async fn create_operation(
     store: &mut OperationStore,
     private_key: &PrivateKey,
@@ -123,6 +161,70 @@ pub async fn create_operation(
     document: Option<DocumentId>,
     body: Option<&[u8]>,
     prune_flag: bool,
+) -> Result<Operation<AardvarkExtensions>> {
+    if let Some(body) = body {
+        if body.len() > MAX_PAYLOAD_SIZE {
+            bail!("payload of {} bytes needs to be chunked", body.len());
+        }
+    }
+
+    append_operation(
+        store,
+        private_key,
+        log_type,
+        document,
+        body,
+        prune_flag,
+        None,
+        PayloadFormat::Plain,
+    )
+    .await
+}
+
+/// Same as [`create_operation`] but encodes the payload in `format` and splits it over multiple
+/// operations if it's larger than `MAX_PAYLOAD_SIZE` after that.
+///
+/// Only the first operation carries the prune flag, it would otherwise remove the previous chunks
+/// of the same payload.
+pub async fn create_operations(
+    store: &mut OperationStore,
+    private_key: &PrivateKey,
+    log_type: LogType,
+    document: DocumentId,
+    payload: &[u8],
+    prune_flag: bool,
+    format: PayloadFormat,
+) -> Result<Vec<Operation<AardvarkExtensions>>> {
+    let payload = format.encode(payload)?;
+    let mut operations = Vec::new();
+    for (chunk, body) in split_payload(&payload)? {
+        let prune_flag = prune_flag && operations.is_empty();
+        let operation = append_operation(
+            store,
+            private_key,
+            log_type,
+            Some(document),
+            Some(body),
+            prune_flag,
+            chunk,
+            format,
+        )
+        .await?;
+        operations.push(operation);
+    }
+
+    Ok(operations)
+}
+
+async fn append_operation(
+    store: &mut OperationStore,
+    private_key: &PrivateKey,
+    log_type: LogType,
+    document: Option<DocumentId>,
+    body: Option<&[u8]>,
+    prune_flag: bool,
+    chunk: Option<Chunk>,
+    format: PayloadFormat,
 ) -> Result<Operation<AardvarkExtensions>> {
     let body = body.map(Body::new);
     let public_key = private_key.public_key();
@@ -148,6 +250,8 @@ pub async fn create_operation(
         prune_flag: PruneFlag::new(prune_flag),
         log_type,
         document,
+        chunk,
+        format,
     };
 
     let mut header = Header {
@@ -201,6 +305,11 @@ pub fn validate_operation(
     operation: &Operation<AardvarkExtensions>,
     expected_document: &DocumentId,
 ) -> Result<()> {
+    let log_type: Option<LogType> = operation.header.extension();
+    if log_type.is_none() {
+        bail!("log type missing");
+    }
+
     let given_document: Option<DocumentId> = operation.header.extension();
     match given_document {
         Some(given_document) => {
@@ -216,9 +325,50 @@ pub fn validate_operation(
             bail!("document id missing (expected: {})", expected_document);
         }
     }
+
+    let chunk: Option<Chunk> = operation.header.extension();
+    if let Some(chunk) = chunk {
+        chunk.validate()?;
+        if operation.body.is_none() {
+            bail!(
+                "chunk {} of payload {} has no body",
+                chunk.index,
+                chunk.payload_hash
+            );
+        }
+    }
     Ok(())
 }
 
+/// Reject operations with bodies larger than `MAX_PAYLOAD_SIZE`.
+///
+/// This is checked separately from [`validate_operation`] so operations we stored before the
+/// limit was introduced can still be loaded.
+pub fn validate_payload_size(operation: &Operation<AardvarkExtensions>) -> Result<()> {
+    if operation.header.payload_size > MAX_PAYLOAD_SIZE as u64 {
+        bail!(
+            "payload of {} bytes exceeds the maximum size of {MAX_PAYLOAD_SIZE} bytes",
+            operation.header.payload_size
+        );
+    }
+    Ok(())
+}
+
+/// Messages broadcast on the gossip overlay of a document.
+///
+/// Operations are sent as the plain `(header, body)` tuple peers used before there were
+/// ephemeral messages, so peers running older versions keep receiving our changes.
+#[derive(Debug, Serialize, Deserialize)]
+pub enum GossipMessage {
+    /// Encoded header and optional body of an operation, these get ingested and persisted.
+    #[serde(rename = "o")]
+    Operation(Vec<u8>, Option<Vec<u8>>),
+
+    /// Message which is only forwarded to the application and never persisted.
+    #[serde(rename = "e")]
+    Ephemeral(EphemeralMessage),
+}
+
 pub fn encode_gossip_operation<E>(header: Header<E>, body: Option<Body>) -> Result<Vec<u8>>
 where
     E: Extensions + Serialize,
@@ -227,7 +377,42 @@ where
     Ok(bytes)
 }
 
-pub fn decode_gossip_message(bytes: &[u8]) -> Result<(Vec<u8>, Option<Vec<u8>>)> {
+pub fn encode_gossip_ephemeral(message: EphemeralMessage) -> Result<Vec<u8>> {
+    let bytes = encode_cbor(&GossipMessage::Ephemeral(message))?;
+    Ok(bytes)
+}
+
+pub fn decode_gossip_message(bytes: &[u8]) -> Result<GossipMessage> {
+    if let Ok((header, body)) = decode_cbor::<(Vec<u8>, Option<Vec<u8>>), _>(bytes) {
+        return Ok(GossipMessage::Operation(header, body));
+    }
     let result = decode_cbor(bytes)?;
     Ok(result)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_gossip_messages() {
+        // Operations of older versions were sent as plain tuple
+        let legacy = encode_cbor(&(vec![1, 2, 3], Some(vec![4]))).unwrap();
+        assert!(matches!(
+            decode_gossip_message(&legacy).unwrap(),
+            GossipMessage::Operation(header, Some(body)) if header == [1, 2, 3] && body == [4]
+        ));
+        let tagged = encode_cbor(&GossipMessage::Operation(vec![1], None)).unwrap();
+        assert!(matches!(
+            decode_gossip_message(&tagged).unwrap(),
+            GossipMessage::Operation(header, None) if header == [1]
+        ));
+
+        let message = EphemeralMessage::new(&PrivateKey::new(), vec![5]).unwrap();
+        let ephemeral = encode_gossip_ephemeral(message).unwrap();
+        assert!(matches!(
+            decode_gossip_message(&ephemeral).unwrap(),
+            GossipMessage::Ephemeral(message) if message.payload == [5]
+        ));
+    }
+}
@@ -0,0 +1,292 @@
+//! Linking two devices of the same person to one identity.
+//!
+//! The device which already has the identity shows a short-lived pairing code, the new device
+//! asks the user to type it in. The first group of the code is a random rendezvous id, both
+//! devices join a gossip topic derived from it alone. The existing device broadcasts its private
+//! key and the list of known documents encrypted with the whole code until the new device
+//! confirms it received them.
+//!
+//! The topic is visible to other peers, so it must not reveal anything about the secret part of
+//! the code. Whoever joins the topic still receives the encrypted offer, deriving the encryption
+//! key from the code is deliberately slow so every guess of the secret costs an Argon2 run.
+//!
+//! The new device confirms by sending a hash over the offer and the private key it contained,
+//! only someone who could decrypt the offer is able to end it.
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+use anyhow::{Context, Result, anyhow, bail};
+use chacha20poly1305::aead::OsRng;
+use chacha20poly1305::aead::rand_core::RngCore;
+use p2panda_core::cbor::{decode_cbor, encode_cbor};
+use p2panda_core::{Hash, PrivateKey};
+use serde::{Deserialize, Serialize};
+use tokio::sync::oneshot;
+use tracing::{debug, warn};
+
+use crate::document::DocumentId;
+use crate::ephemeral::EphemeralMessage;
+use crate::keyfile;
+use crate::network::Network;
+
+/// How long a pairing code can be used.
+pub const PAIRING_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+/// How often the existing device repeats its offer while waiting for the new device.
+const OFFER_INTERVAL: Duration = Duration::from_secs(2);
+/// Identifies the format of an encrypted pairing bundle, the last byte is the version.
+const BUNDLE_MAGIC: &[u8] = b"aardvark-pair\x01";
+const TOPIC_CONTEXT: &[u8] = b"aardvark device pairing";
+const LINKED_CONTEXT: &[u8] = b"aardvark device pairing linked";
+
+/// Characters used in pairing codes, without ones which are easily confused like `0` and `O`.
+const CODE_ALPHABET: &[u8] = b"ABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+/// Length of the public rendezvous id at the start of a code.
+const RENDEZVOUS_LEN: usize = 4;
+/// Length of the secret part of a code, following the rendezvous id.
+const SECRET_LEN: usize = 8;
+const CODE_LEN: usize = RENDEZVOUS_LEN + SECRET_LEN;
+/// Characters shown together when displaying a code.
+const GROUP_LEN: usize = 4;
+
+/// Short code the user types on the new device, e.g. `R2WT-K7QD-4MXA`.
+#[derive(Clone, PartialEq, Eq)]
+pub struct PairingCode(String);
+
+impl PairingCode {
+    pub fn generate() -> Self {
+        let code = (0..CODE_LEN)
+            .map(|_| CODE_ALPHABET[OsRng.next_u32() as usize % CODE_ALPHABET.len()] as char)
+            .collect();
+        Self(code)
+    }
+
+    /// Gossip topic both devices meet on, derived from the rendezvous id only.
+    fn topic(&self) -> DocumentId {
+        let rendezvous = &self.0.as_bytes()[..RENDEZVOUS_LEN];
+        Hash::new([TOPIC_CONTEXT, rendezvous].concat()).into()
+    }
+}
+
+impl fmt::Display for PairingCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let groups: Vec<&str> = self
+            .0
+            .as_bytes()
+            .chunks(GROUP_LEN)
+            .map(|group| std::str::from_utf8(group).expect("codes are ASCII"))
+            .collect();
+        f.write_str(&groups.join("-"))
+    }
+}
+
+impl fmt::Debug for PairingCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // The code protects the private key, keep it out of logs
+        f.write_str("PairingCode(..)")
+    }
+}
+
+impl FromStr for PairingCode {
+    type Err = anyhow::Error;
+
+    /// Parse a code typed by the user, ignoring case, spaces and dashes.
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let code: String = value
+            .chars()
+            .filter(|char| !char.is_whitespace() && *char != '-')
+            .map(|char| char.to_ascii_uppercase())
+            .collect();
+        if code.len() != CODE_LEN || !code.bytes().all(|byte| CODE_ALPHABET.contains(&byte)) {
+            bail!("invalid pairing code");
+        }
+        Ok(Self(code))
+    }
+}
+
+/// A document known to the existing device.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LinkedDocument {
+    #[serde(rename = "i")]
+    pub id: DocumentId,
+    #[serde(rename = "n")]
+    pub name: Option<String>,
+}
+
+/// Everything the new device needs to continue where the existing device is.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PairingBundle {
+    #[serde(rename = "k")]
+    private_key: Vec<u8>,
+    #[serde(rename = "d")]
+    pub documents: Vec<LinkedDocument>,
+}
+
+impl PairingBundle {
+    pub fn new(private_key: &PrivateKey, documents: Vec<LinkedDocument>) -> Self {
+        Self {
+            private_key: private_key.as_bytes().to_vec(),
+            documents,
+        }
+    }
+
+    pub fn private_key(&self) -> Result<PrivateKey> {
+        Ok(PrivateKey::try_from(self.private_key.as_slice())?)
+    }
+
+    fn seal(&self, code: &PairingCode) -> Result<Vec<u8>> {
+        let bytes = encode_cbor(self)?;
+        Ok(keyfile::seal(BUNDLE_MAGIC, &bytes, &code.0)?)
+    }
+
+    fn open(sealed: &[u8], code: &PairingCode) -> Result<Self> {
+        let bytes = keyfile::open(BUNDLE_MAGIC, sealed, &code.0)?;
+        let bundle: Self = decode_cbor(bytes.as_slice())?;
+        bundle
+            .private_key()
+            .context("pairing bundle contains invalid private key")?;
+        Ok(bundle)
+    }
+}
+
+/// Messages sent on the pairing topic.
+#[derive(Debug, Serialize, Deserialize)]
+enum PairingMessage {
+    /// Encrypted [`PairingBundle`] broadcast by the existing device.
+    Offer(Vec<u8>),
+    /// The new device received and decrypted the bundle, see [`linked_proof`].
+    Linked(Hash),
+}
+
+/// Proof that the offer `sealed` was decrypted, only the existing device and whoever knows the
+/// code are able to compute it.
+fn linked_proof(private_key: &[u8], sealed: &[u8]) -> Hash {
+    Hash::new([LINKED_CONTEXT, private_key, sealed].concat())
+}
+
+/// Broadcast the bundle until the new device confirms it, or until `cancel` resolves.
+pub(crate) async fn offer(
+    network: &Network,
+    private_key: &PrivateKey,
+    code: &PairingCode,
+    bundle: PairingBundle,
+    cancel: oneshot::Receiver<()>,
+) -> Result<()> {
+    let code_clone = code.clone();
+    let sealed = tokio::task::spawn_blocking(move || bundle.seal(&code_clone)).await??;
+    let proof = linked_proof(private_key.as_bytes(), &sealed);
+    let offer = encode_cbor(&PairingMessage::Offer(sealed))?;
+
+    let topic = code.topic();
+    let mut messages = network.subscribe_ephemeral(topic).await?;
+    let result = tokio::select! {
+        result = tokio::time::timeout(PAIRING_TIMEOUT, async {
+            let mut interval = tokio::time::interval(OFFER_INTERVAL);
+            loop {
+                tokio::select! {
+                    _ = interval.tick() => {
+                        let message = EphemeralMessage::new(private_key, offer.clone())?;
+                        network.send_ephemeral(&topic, message).await?;
+                    }
+                    Some(message) = messages.recv() => {
+                        let Ok(PairingMessage::Linked(linked)) = decode_cbor::<PairingMessage, _>(message.payload.as_slice()) else {
+                            continue;
+                        };
+                        if linked == proof {
+                            debug!(public_key = %message.public_key, "device linked");
+                            return Ok::<_, anyhow::Error>(());
+                        }
+                        warn!(public_key = %message.public_key, "Ignoring confirmation without valid proof");
+                    }
+                }
+            }
+        }) => result.unwrap_or_else(|_| Err(anyhow!("pairing code expired"))),
+        _ = cancel => Err(anyhow!("pairing cancelled")),
+    };
+    network.unsubscribe(&topic).await?;
+
+    result
+}
+
+/// Wait for the existing device to offer its bundle, or until `cancel` resolves.
+pub(crate) async fn accept(
+    network: &Network,
+    private_key: &PrivateKey,
+    code: &PairingCode,
+    cancel: oneshot::Receiver<()>,
+) -> Result<PairingBundle> {
+    let topic = code.topic();
+    let mut messages = network.subscribe_ephemeral(topic).await?;
+    let result = tokio::select! {
+        result = tokio::time::timeout(PAIRING_TIMEOUT, async {
+            while let Some(message) = messages.recv().await {
+                let Ok(PairingMessage::Offer(sealed)) = decode_cbor::<PairingMessage, _>(message.payload.as_slice()) else {
+                    continue;
+                };
+                let code = code.clone();
+                let (bundle, sealed) = tokio::task::spawn_blocking(move || {
+                    (PairingBundle::open(&sealed, &code), sealed)
+                })
+                .await?;
+                match bundle {
+                    Ok(bundle) => {
+                        let proof = linked_proof(&bundle.private_key, &sealed);
+                        let linked = encode_cbor(&PairingMessage::Linked(proof))?;
+                        let message = EphemeralMessage::new(private_key, linked)?;
+                        network.send_ephemeral(&topic, message).await?;
+                        return Ok(bundle);
+                    }
+                    Err(error) => {
+                        warn!(public_key = %message.public_key, "Ignoring invalid pairing offer: {error}");
+                    }
+                }
+            }
+            Err(anyhow!("pairing topic closed"))
+        }) => result.unwrap_or_else(|_| Err(anyhow!("pairing code expired"))),
+        _ = cancel => Err(anyhow!("pairing cancelled")),
+    };
+    network.unsubscribe(&topic).await?;
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_and_display_code() {
+        let code = PairingCode::generate();
+        let displayed = code.to_string();
+        assert_eq!(displayed.len(), CODE_LEN + 2);
+        assert_eq!(PairingCode::from_str(&displayed).unwrap(), code);
+        assert_eq!(
+            PairingCode::from_str(&displayed.to_lowercase().replace('-', " ")).unwrap(),
+            code
+        );
+        assert!(PairingCode::from_str("K7QD-4MXA").is_err());
+    }
+
+    #[test]
+    fn topic_only_depends_on_rendezvous() {
+        let code = PairingCode::from_str("R2WT-K7QD-4MXA").unwrap();
+        let other_secret = PairingCode::from_str("R2WT-AAAA-AAAA").unwrap();
+        let other_rendezvous = PairingCode::from_str("R2WU-K7QD-4MXA").unwrap();
+        assert_eq!(code.topic(), other_secret.topic());
+        assert_ne!(code.topic(), other_rendezvous.topic());
+    }
+
+    #[test]
+    fn linked_proof_needs_bundle() {
+        let private_key = PrivateKey::new();
+        let code = PairingCode::generate();
+        let sealed = PairingBundle::new(&private_key, Vec::new())
+            .seal(&code)
+            .unwrap();
+        let proof = linked_proof(private_key.as_bytes(), &sealed);
+
+        let bundle = PairingBundle::open(&sealed, &code).unwrap();
+        assert_eq!(linked_proof(&bundle.private_key, &sealed), proof);
+        assert_ne!(linked_proof(PrivateKey::new().as_bytes(), &sealed), proof);
+    }
+}
@@ -0,0 +1,55 @@
+//! Presence, telling other authors whether we're at the device.
+//!
+//! The frontend decides when we're away, e.g. after a while without focus or typing. While a
+//! document is shown, we broadcast changes of our presence as ephemeral message, and whether
+//! we're away when peers join. Authors who never sent their presence are considered active, so
+//! not sharing it at all looks the same as being active all the time.
+
+/// Marks ephemeral messages which are presence updates, in front of the state.
+const PRESENCE_PREFIX: &[u8] = b"aardvark-presence:";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Presence {
+    Active,
+    Away,
+}
+
+impl Presence {
+    fn as_byte(self) -> u8 {
+        match self {
+            Presence::Active => b'a',
+            Presence::Away => b'w',
+        }
+    }
+}
+
+pub fn encode_presence(presence: Presence) -> Vec<u8> {
+    [PRESENCE_PREFIX, &[presence.as_byte()]].concat()
+}
+
+/// The state in a presence update, `None` if the message isn't one.
+pub fn decode_presence(bytes: &[u8]) -> Option<Presence> {
+    match bytes.strip_prefix(PRESENCE_PREFIX)? {
+        b"a" => Some(Presence::Active),
+        b"w" => Some(Presence::Away),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::receipts::encode_receipt;
+
+    #[test]
+    fn presence_roundtrip() {
+        for presence in [Presence::Active, Presence::Away] {
+            assert_eq!(decode_presence(&encode_presence(presence)), Some(presence));
+        }
+
+        // Other ephemeral messages aren't mistaken for presence updates
+        assert_eq!(decode_presence(&encode_receipt(&Default::default())), None);
+        assert_eq!(decode_presence(b"aardvark-presence:x"), None);
+        assert_eq!(decode_presence(b""), None);
+    }
+}
@@ -0,0 +1,44 @@
+//! Read receipts, telling other authors which version of a document we have seen.
+//!
+//! While a document is shown, we broadcast the version vector of our copy as ephemeral message
+//! shortly after it changed. Comparing the version of other authors with ours tells how many
+//! changes they're missing, see `Author::changes-behind`. Receipts are never persisted, authors
+//! who aren't online don't receive them.
+
+use loro::{LoroDoc, VersionVector};
+
+/// Marks ephemeral messages which are read receipts, in front of the encoded version vector.
+const RECEIPT_PREFIX: &[u8] = b"aardvark-seen:";
+
+pub fn encode_receipt(version: &VersionVector) -> Vec<u8> {
+    [RECEIPT_PREFIX, &version.encode()].concat()
+}
+
+/// The version in a read receipt, `None` if the message isn't a read receipt.
+pub fn decode_receipt(bytes: &[u8]) -> Option<VersionVector> {
+    let version = bytes.strip_prefix(RECEIPT_PREFIX)?;
+    VersionVector::decode(version).ok()
+}
+
+/// Number of changes in `doc` which aren't part of `version`.
+pub fn changes_missing(doc: &LoroDoc, version: &VersionVector) -> u32 {
+    let mut missing = 0;
+    for (peer, end) in doc.oplog_vv().iter() {
+        let mut counter = version.get(peer).copied().unwrap_or(0);
+        while counter < *end {
+            let Some(change) = doc.get_change(loro::ID::new(*peer, counter)) else {
+                break;
+            };
+            missing += 1;
+            counter = (change.id.counter + change.len as i32).max(counter + 1);
+        }
+    }
+    missing
+}
+
+/// Whether `version` contains all of our own changes in `doc`.
+pub fn has_own_changes(doc: &LoroDoc, version: &VersionVector) -> bool {
+    let peer = doc.peer_id();
+    let ours = doc.oplog_vv().get(&peer).copied().unwrap_or(0);
+    version.get(&peer).copied().unwrap_or(0) >= ours
+}
@@ -0,0 +1,115 @@
+//! Joining the gossip overlay of documents again after we lost all peers.
+//!
+//! When the network changes or the device wakes up from suspend, connections to peers break and
+//! the overlay of a document can end up empty. The node then joins the overlay again, waiting a
+//! bit longer after every attempt which didn't bring back any peer.
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::{Duration, SystemTime};
+
+/// Delay before the first attempt to join again.
+pub const INITIAL_DELAY: Duration = Duration::from_secs(1);
+
+/// Longest delay between two attempts.
+pub const MAX_DELAY: Duration = Duration::from_secs(300);
+
+/// How often we check whether the device was suspended.
+pub const WAKE_CHECK_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Exponentially growing delays between attempts, with random jitter so peers which lost their
+/// connections at the same time don't all retry at the same time.
+#[derive(Clone, Debug)]
+pub struct Backoff {
+    attempt: u32,
+    initial: Duration,
+    max: Duration,
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Self::new(INITIAL_DELAY, MAX_DELAY)
+    }
+}
+
+impl Backoff {
+    pub fn new(initial: Duration, max: Duration) -> Self {
+        Self {
+            attempt: 0,
+            initial,
+            max,
+        }
+    }
+
+    /// Number of attempts made so far.
+    pub fn attempt(&self) -> u32 {
+        self.attempt
+    }
+
+    /// Delay before the next attempt, between 80% and 120% of twice the previous one.
+    pub fn next_delay(&mut self) -> Duration {
+        let factor = 2u32.saturating_pow(self.attempt);
+        self.attempt += 1;
+
+        let delay = self.initial.saturating_mul(factor).min(self.max);
+        let jitter = 0.8 + 0.4 * random_fraction();
+        delay.mul_f64(jitter)
+    }
+}
+
+/// A random number between 0 and 1, good enough for jitter.
+fn random_fraction() -> f64 {
+    let random = RandomState::new().build_hasher().finish();
+    (random >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// Detects that the device was suspended, by comparing wall clock time with the time the
+/// monotonic clock says has passed. The monotonic clock doesn't advance during suspend.
+#[derive(Debug)]
+pub struct WakeDetector {
+    last_check: SystemTime,
+}
+
+impl Default for WakeDetector {
+    fn default() -> Self {
+        Self {
+            last_check: SystemTime::now(),
+        }
+    }
+}
+
+impl WakeDetector {
+    /// Returns `true` if a lot more wall clock time than `elapsed` passed since the last check.
+    pub fn check(&mut self, elapsed: Duration) -> bool {
+        let now = SystemTime::now();
+        let wall_clock = now.duration_since(self.last_check).unwrap_or_default();
+        self.last_check = now;
+        wall_clock > elapsed + WAKE_CHECK_INTERVAL
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exponential_backoff() {
+        let mut backoff = Backoff::new(Duration::from_secs(1), Duration::from_secs(10));
+        let delays: Vec<Duration> = (0..6).map(|_| backoff.next_delay()).collect();
+        assert_eq!(backoff.attempt(), 6);
+
+        let expected = [1, 2, 4, 8, 10, 10];
+        for (delay, expected) in delays.iter().zip(expected) {
+            let expected = Duration::from_secs(expected);
+            assert!(*delay >= expected.mul_f64(0.8));
+            assert!(*delay <= expected.mul_f64(1.2));
+        }
+    }
+
+    #[test]
+    fn jitter_in_range() {
+        for _ in 0..100 {
+            let fraction = random_fraction();
+            assert!((0.0..1.0).contains(&fraction));
+        }
+    }
+}
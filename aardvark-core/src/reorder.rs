@@ -0,0 +1,252 @@
+//! Holding back operations which arrive before the operations they build on.
+//!
+//! Gossip delivers operations as soon as they are created, while the operations before them
+//! might still be on their way via sync. Ingesting an operation fails while its backlink is
+//! missing, so such operations wait here and are released in order once their predecessor
+//! arrived. The buffer is bounded, operations waiting too long or exceeding the capacity are
+//! dropped, sync delivers them again later.
+use std::collections::{BTreeMap, HashMap};
+use std::time::{Duration, Instant};
+
+use p2panda_core::{Body, Extension, Header, PruneFlag, PublicKey};
+use tracing::debug;
+
+use crate::operation::{AardvarkExtensions, LogType};
+
+/// Operations held back per subscribed document at most.
+const MAX_PENDING: usize = 1024;
+/// How long an operation waits for its predecessor at most.
+const PENDING_TTL: Duration = Duration::from_secs(120);
+
+/// Decoded operation header, body and encoded header bytes as expected by ingest.
+pub(crate) type RawOperation = (Header<AardvarkExtensions>, Option<Body>, Vec<u8>);
+
+/// Log of a document, the buffer is only used for a single document.
+pub(crate) type LogKey = (PublicKey, LogType);
+
+#[derive(Debug, Default)]
+pub(crate) struct ReorderBuffer {
+    /// Sequence number of the next operation we expect in every log we saw.
+    next_seq_num: HashMap<LogKey, u64>,
+    pending: HashMap<LogKey, BTreeMap<u64, (Instant, RawOperation)>>,
+    len: usize,
+}
+
+impl ReorderBuffer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn log_key(header: &Header<AardvarkExtensions>) -> LogKey {
+        let log_type: Option<LogType> = header.extension();
+        (header.public_key, log_type.unwrap_or_default())
+    }
+
+    /// Whether we know which operation to expect next in the log.
+    pub fn knows(&self, log: &LogKey) -> bool {
+        self.next_seq_num.contains_key(log)
+    }
+
+    /// Expect `seq_num` next in the log, usually the one after the latest stored operation.
+    pub fn set_next_seq_num(&mut self, log: LogKey, seq_num: u64) {
+        self.next_seq_num.insert(log, seq_num);
+    }
+
+    /// Add a received operation, returns the operations ready to be ingested in order.
+    ///
+    /// Operations we expect next, operations with the prune flag, which don't need their
+    /// predecessors, and operations we're already past are ready right away.
+    pub fn push(&mut self, operation: RawOperation, now: Instant) -> Vec<RawOperation> {
+        let log = Self::log_key(&operation.0);
+        let seq_num = operation.0.seq_num;
+        let next = self.next_seq_num.get(&log).copied().unwrap_or_default();
+        let prune_flag: Option<PruneFlag> = operation.0.extension();
+
+        if seq_num > next && !prune_flag.is_some_and(|flag| flag.is_set()) {
+            self.hold(log, seq_num, operation, now);
+            return Vec::new();
+        }
+
+        if seq_num >= next {
+            self.next_seq_num.insert(log, seq_num + 1);
+        }
+        let mut ready = vec![operation];
+        self.release(&log, &mut ready);
+        ready
+    }
+
+    /// Number of operations held back.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Number of operations we know of but didn't receive yet, the held back ones and the ones
+    /// they're waiting for.
+    pub fn missing(&self) -> u64 {
+        self.pending
+            .iter()
+            .filter_map(|(log, pending)| {
+                let (last, _) = pending.last_key_value()?;
+                let next = self.next_seq_num.get(log).copied().unwrap_or_default();
+                Some(last + 1 - next)
+            })
+            .sum()
+    }
+
+    fn hold(&mut self, log: LogKey, seq_num: u64, operation: RawOperation, now: Instant) {
+        self.expire(now);
+        if self.len >= MAX_PENDING {
+            self.drop_oldest();
+        }
+
+        let previous = self
+            .pending
+            .entry(log)
+            .or_default()
+            .insert(seq_num, (now, operation));
+        if previous.is_none() {
+            self.len += 1;
+        }
+    }
+
+    /// Move the operations of `log` which don't wait for anything anymore to `ready`.
+    fn release(&mut self, log: &LogKey, ready: &mut Vec<RawOperation>) {
+        let Some(pending) = self.pending.get_mut(log) else {
+            return;
+        };
+        let next = self.next_seq_num.entry(*log).or_default();
+        while let Some(entry) = pending.first_entry() {
+            if *entry.key() > *next {
+                break;
+            }
+            if *entry.key() == *next {
+                *next += 1;
+            }
+            let (_, operation) = entry.remove();
+            ready.push(operation);
+            self.len -= 1;
+        }
+        if pending.is_empty() {
+            self.pending.remove(log);
+        }
+    }
+
+    fn expire(&mut self, now: Instant) {
+        let mut expired = 0;
+        self.pending.retain(|_, pending| {
+            pending.retain(|_, (received, _)| {
+                let keep = now.duration_since(*received) < PENDING_TTL;
+                if !keep {
+                    expired += 1;
+                }
+                keep
+            });
+            !pending.is_empty()
+        });
+        if expired > 0 {
+            debug!(
+                expired,
+                "dropping operations still missing their predecessors"
+            );
+            self.len -= expired;
+        }
+    }
+
+    fn drop_oldest(&mut self) {
+        let oldest = self
+            .pending
+            .iter()
+            .flat_map(|(log, pending)| {
+                pending
+                    .iter()
+                    .map(move |(seq_num, (received, _))| (*received, *log, *seq_num))
+            })
+            .min_by_key(|(received, _, _)| *received);
+        let Some((_, log, seq_num)) = oldest else {
+            return;
+        };
+
+        debug!("too many operations missing their predecessors, dropping the oldest");
+        if let Some(pending) = self.pending.get_mut(&log) {
+            pending.remove(&seq_num);
+            if pending.is_empty() {
+                self.pending.remove(&log);
+            }
+            self.len -= 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use p2panda_core::PrivateKey;
+
+    use super::*;
+    use crate::document::DocumentId;
+    use crate::operation::create_operation;
+    use crate::store::{OperationStore, connect_database};
+
+    #[tokio::test]
+    async fn release_in_order() {
+        let pool = connect_database(None).await.unwrap();
+        let mut operation_store = OperationStore::new(pool);
+        let private_key = PrivateKey::new();
+
+        let operation = create_operation(
+            &mut operation_store,
+            &private_key,
+            LogType::Snapshot,
+            None,
+            None,
+            false,
+        )
+        .await
+        .unwrap();
+        let document_id: DocumentId = operation.header.extension().unwrap();
+        let mut operations = Vec::new();
+        for text in ["a", "b", "c", "d"] {
+            let operation = create_operation(
+                &mut operation_store,
+                &private_key,
+                LogType::Delta,
+                Some(document_id),
+                Some(text.as_bytes()),
+                false,
+            )
+            .await
+            .unwrap();
+            operations.push((
+                operation.header.clone(),
+                operation.body.clone(),
+                operation.header.to_bytes(),
+            ));
+        }
+        let seq_nums = |ready: Vec<RawOperation>| -> Vec<u64> {
+            ready.iter().map(|(header, _, _)| header.seq_num).collect()
+        };
+
+        let now = Instant::now();
+        let mut buffer = ReorderBuffer::new();
+        assert!(seq_nums(buffer.push(operations[2].clone(), now)).is_empty());
+        assert!(seq_nums(buffer.push(operations[1].clone(), now)).is_empty());
+        assert_eq!(buffer.len(), 2);
+        assert_eq!(buffer.missing(), 3);
+        assert_eq!(
+            seq_nums(buffer.push(operations[0].clone(), now)),
+            vec![0, 1, 2]
+        );
+        assert_eq!(buffer.len(), 0);
+        assert_eq!(buffer.missing(), 0);
+        assert_eq!(seq_nums(buffer.push(operations[3].clone(), now)), vec![3]);
+
+        // Operations waiting too long are dropped
+        let mut buffer = ReorderBuffer::new();
+        buffer.push(operations[2].clone(), now);
+        buffer.push(
+            operations[3].clone(),
+            now + PENDING_TTL + Duration::from_secs(1),
+        );
+        assert_eq!(buffer.len(), 1);
+        assert_eq!(seq_nums(buffer.push(operations[0].clone(), now)), vec![0]);
+    }
+}
@@ -0,0 +1,91 @@
+//! Keeping documents available for others while their authors are offline.
+//!
+//! A replica joins documents like any other peer but never edits them. Operations it receives are
+//! validated and stored by ingest as usual and served to peers who sync with it later, so a small
+//! always-on machine can bridge the time between laptops being awake. See the
+//! `aardvark-pin-server` binary.
+use std::time::Duration;
+
+use anyhow::Result;
+use p2panda_core::PublicKey;
+use tracing::{debug, info, warn};
+
+use crate::document::{DocumentId, HistoryProgress, SubscribableDocument, SyncProgress};
+use crate::node::{Node, SubscriptionHandle};
+
+/// Document which is only stored and served, nothing is done with the data it receives.
+#[derive(Debug)]
+struct ReplicaDocument {
+    id: DocumentId,
+}
+
+impl SubscribableDocument for ReplicaDocument {
+    fn bytes_received(&self, author: PublicKey, data: Vec<u8>) {
+        debug!(document = %self.id, %author, len = data.len(), "Stored operation");
+    }
+
+    fn ephemeral_bytes_received(&self, _author: PublicKey, _data: Vec<u8>) {}
+
+    fn author_incompatible(&self, author: PublicKey, reason: String) {
+        warn!(document = %self.id, %author, "Incompatible author: {reason}");
+    }
+
+    fn authors_joined(&self, authors: Vec<PublicKey>) {
+        for author in authors {
+            info!(document = %self.id, %author, "Peer joined");
+        }
+    }
+
+    fn author_set_online(&self, author: PublicKey, is_online: bool) {
+        info!(document = %self.id, %author, is_online, "Peer changed state");
+    }
+
+    fn reconnecting(&self, attempt: u32, delay: Duration) {
+        info!(document = %self.id, attempt, ?delay, "Lost all peers, joining again");
+    }
+
+    fn sync_progress(&self, progress: SyncProgress) {
+        if progress.done {
+            info!(
+                document = %self.id,
+                operations = progress.operations_received,
+                "Caught up with peers"
+            );
+        }
+    }
+
+    fn history_progress(&self, _progress: Option<HistoryProgress>) {}
+}
+
+/// Join `document_ids` and all documents replicated before.
+///
+/// Returns the subscriptions of all joined documents, they're replicated until the handles are
+/// dropped.
+pub async fn replicate(
+    node: &Node,
+    document_ids: &[DocumentId],
+) -> Result<Vec<SubscriptionHandle>> {
+    let mut replicated: Vec<DocumentId> = node
+        .documents()
+        .await?
+        .into_iter()
+        .map(|document| document.id)
+        .collect();
+    for document_id in document_ids {
+        if !replicated.contains(document_id) {
+            node.add_document(document_id, None).await?;
+            replicated.push(*document_id);
+        }
+    }
+
+    let mut subscriptions = Vec::with_capacity(replicated.len());
+    for document_id in replicated {
+        let subscription = node
+            .subscribe(document_id, ReplicaDocument { id: document_id })
+            .await?;
+        subscriptions.push(subscription);
+        info!(document = %document_id, "Replicating document");
+    }
+
+    Ok(subscriptions)
+}
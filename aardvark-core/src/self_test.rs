@@ -0,0 +1,275 @@
+//! Finding out why syncing with other devices doesn't work.
+//!
+//! The checks run one after another and each result is reported as soon as it's known. Another
+//! device running the test at the same time acts as test peer: both join a topic only used for the
+//! test, ping each other and compare their clocks.
+//!
+//! Ephemeral messages older than the message TTL are dropped by the network, a test peer whose
+//! clock is far behind ours therefore isn't found at all.
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, SystemTime};
+
+use anyhow::{Result, anyhow};
+use p2panda_core::cbor::{decode_cbor, encode_cbor};
+use p2panda_core::{Hash, PrivateKey, PublicKey};
+use serde::{Deserialize, Serialize};
+use tokio::sync::{mpsc, oneshot};
+use tokio::time::Instant;
+
+use crate::document::DocumentId;
+use crate::ephemeral::EphemeralMessage;
+use crate::network::Network;
+
+/// How long we look for other devices on the local network.
+const DISCOVERY_TIMEOUT: Duration = Duration::from_secs(10);
+/// How long we wait for a test peer to answer our pings.
+const PING_TIMEOUT: Duration = Duration::from_secs(10);
+const PING_INTERVAL: Duration = Duration::from_secs(1);
+/// Larger differences to the clock of the test peer are reported.
+const MAX_CLOCK_OFFSET: Duration = Duration::from_secs(30);
+/// No operation was written before 2025-01-01, a clock before that is certainly wrong.
+const EARLIEST_TIMESTAMP: u64 = 1_735_689_600;
+const TOPIC_CONTEXT: &[u8] = b"aardvark connection self-test";
+
+/// The checks of the self-test, in the order they run.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Check {
+    /// Other devices are found on the local network with mDNS.
+    LocalDiscovery,
+    /// A relay server can be reached, to connect to devices outside of the local network.
+    Relay,
+    /// A direct connection to a test peer can be established.
+    TestPeer,
+    /// The clock of this device is plausible and agrees with the test peer.
+    Clock,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Status {
+    Passed,
+    /// Syncing works but might be slower or less reliable than it could be.
+    Warning,
+    Failed,
+    /// The check couldn't run.
+    Skipped,
+}
+
+/// What a check found out.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Finding {
+    /// Number of other devices found on the local network.
+    PeersNearby(usize),
+    NoPeersNearby,
+    /// Only devices on the local network can be reached.
+    NoRelayConfigured,
+    TestPeerReached {
+        round_trip: Duration,
+    },
+    NoTestPeer,
+    ClockInSync,
+    /// Our clock is before any operation could have been written.
+    ClockInPast,
+    /// Seconds our clock is ahead of the test peer, negative when it's behind.
+    ClockOffset(i64),
+    /// Our clock is plausible, there was no test peer to compare it with.
+    ClockNotCompared,
+}
+
+impl Finding {
+    pub fn status(&self) -> Status {
+        match self {
+            Finding::PeersNearby(_)
+            | Finding::TestPeerReached { .. }
+            | Finding::ClockInSync
+            | Finding::ClockNotCompared => Status::Passed,
+            Finding::ClockOffset(_) => Status::Warning,
+            Finding::NoPeersNearby | Finding::NoTestPeer | Finding::ClockInPast => Status::Failed,
+            Finding::NoRelayConfigured => Status::Skipped,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct CheckResult {
+    pub check: Check,
+    pub finding: Finding,
+}
+
+/// Messages sent on the self-test topic.
+#[derive(Debug, Serialize, Deserialize)]
+enum SelfTestMessage {
+    Ping(u64),
+    /// Answer to the ping with the given number of `to`.
+    Pong {
+        to: PublicKey,
+        ping: u64,
+    },
+}
+
+/// Run all checks, or until `cancel` resolves.
+///
+/// `neighbors` are the peers we're already connected to for documents, they count as found on
+/// the local network.
+pub(crate) async fn run(
+    network: &Network,
+    private_key: &PrivateKey,
+    neighbors: HashSet<PublicKey>,
+    report: impl Fn(CheckResult) + Send,
+    cancel: oneshot::Receiver<()>,
+) -> Result<()> {
+    let topic: DocumentId = Hash::new(TOPIC_CONTEXT).into();
+    let messages = network.subscribe_ephemeral(topic).await?;
+    let mut session = Session {
+        network,
+        private_key,
+        topic,
+        messages,
+        pings: HashMap::new(),
+        peers: HashSet::new(),
+        round_trip: None,
+        clock_offset: None,
+    };
+
+    let result = tokio::select! {
+        result = session.run(neighbors, &report) => result,
+        _ = cancel => Err(anyhow!("self-test cancelled")),
+    };
+    network.unsubscribe(&topic).await?;
+
+    result
+}
+
+struct Session<'a> {
+    network: &'a Network,
+    private_key: &'a PrivateKey,
+    topic: DocumentId,
+    messages: mpsc::UnboundedReceiver<EphemeralMessage>,
+    /// When we sent our pings, by number.
+    pings: HashMap<u64, Instant>,
+    /// Other devices running the self-test at the same time.
+    peers: HashSet<PublicKey>,
+    round_trip: Option<Duration>,
+    clock_offset: Option<i64>,
+}
+
+impl Session<'_> {
+    async fn run(
+        &mut self,
+        mut neighbors: HashSet<PublicKey>,
+        report: &impl Fn(CheckResult),
+    ) -> Result<()> {
+        self.exchange(DISCOVERY_TIMEOUT, |session| !session.peers.is_empty())
+            .await?;
+        neighbors.extend(self.peers.iter().copied());
+        report(CheckResult {
+            check: Check::LocalDiscovery,
+            finding: if neighbors.is_empty() {
+                Finding::NoPeersNearby
+            } else {
+                Finding::PeersNearby(neighbors.len())
+            },
+        });
+
+        // The network is only configured with mDNS discovery, there is no relay to check yet
+        report(CheckResult {
+            check: Check::Relay,
+            finding: Finding::NoRelayConfigured,
+        });
+
+        if !self.peers.is_empty() {
+            self.exchange(PING_TIMEOUT, |session| session.round_trip.is_some())
+                .await?;
+        }
+        report(CheckResult {
+            check: Check::TestPeer,
+            finding: match self.round_trip {
+                Some(round_trip) => Finding::TestPeerReached { round_trip },
+                None => Finding::NoTestPeer,
+            },
+        });
+
+        report(CheckResult {
+            check: Check::Clock,
+            finding: self.clock_finding(),
+        });
+
+        Ok(())
+    }
+
+    /// Ping the test topic and answer pings of others until `done` or the timeout passed.
+    async fn exchange(&mut self, timeout: Duration, done: impl Fn(&Self) -> bool) -> Result<()> {
+        let deadline = Instant::now() + timeout;
+        let mut interval = tokio::time::interval(PING_INTERVAL);
+        while !done(self) {
+            tokio::select! {
+                _ = tokio::time::sleep_until(deadline) => break,
+                _ = interval.tick() => {
+                    let ping = self.pings.len() as u64;
+                    self.pings.insert(ping, Instant::now());
+                    self.send(&SelfTestMessage::Ping(ping)).await?;
+                }
+                Some(message) = self.messages.recv() => self.handle(message).await?,
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn handle(&mut self, message: EphemeralMessage) -> Result<()> {
+        let public_key = self.private_key.public_key();
+        if message.public_key == public_key {
+            return Ok(());
+        }
+        let Ok(payload) = decode_cbor::<SelfTestMessage, _>(message.payload.as_slice()) else {
+            return Ok(());
+        };
+
+        self.peers.insert(message.public_key);
+        // Timestamps only have a precision of seconds, the time the message took doesn't matter
+        self.clock_offset = Some(unix_timestamp() as i64 - message.timestamp as i64);
+
+        match payload {
+            SelfTestMessage::Ping(ping) => {
+                let pong = SelfTestMessage::Pong {
+                    to: message.public_key,
+                    ping,
+                };
+                self.send(&pong).await?;
+            }
+            SelfTestMessage::Pong { to, ping } if to == public_key => {
+                if let Some(sent) = self.pings.get(&ping) {
+                    self.round_trip.get_or_insert(sent.elapsed());
+                }
+            }
+            SelfTestMessage::Pong { .. } => {}
+        }
+
+        Ok(())
+    }
+
+    async fn send(&self, message: &SelfTestMessage) -> Result<()> {
+        let message = EphemeralMessage::new(self.private_key, encode_cbor(message)?)?;
+        self.network.send_ephemeral(&self.topic, message).await
+    }
+
+    fn clock_finding(&self) -> Finding {
+        if unix_timestamp() < EARLIEST_TIMESTAMP {
+            return Finding::ClockInPast;
+        }
+
+        match self.clock_offset {
+            Some(offset) if offset.unsigned_abs() > MAX_CLOCK_OFFSET.as_secs() => {
+                Finding::ClockOffset(offset)
+            }
+            Some(_) => Finding::ClockInSync,
+            None => Finding::ClockNotCompared,
+        }
+    }
+}
+
+fn unix_timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
@@ -0,0 +1,624 @@
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash as StdHash;
+use std::path::Path;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use p2panda_core::PublicKey;
+use p2panda_store::sqlite::store::migrations as operation_store_migrations;
+use p2panda_store::{LogStore, SqliteStore};
+use p2panda_sync::log_sync::TopicLogMap;
+use serde::{Deserialize, Serialize};
+use sqlx;
+use sqlx::Row;
+use sqlx::migrate::Migrator;
+use tracing::{error, info};
+
+use crate::debug::StoreStats;
+use crate::document::{Author, Document, DocumentId};
+use crate::operation::{AardvarkExtensions, LogType, validate_operation};
+use crate::utils::CombinedMigrationSource;
+
+/// Open the database in `db_location` or in memory and bring its schema up to date.
+pub async fn connect_database(db_location: Option<&Path>) -> anyhow::Result<sqlx::SqlitePool> {
+    let connection_options = sqlx::sqlite::SqliteConnectOptions::new()
+        .shared_cache(true)
+        .create_if_missing(true);
+    let connection_options = if let Some(db_location) = db_location {
+        let db_file = db_location.join("database.sqlite");
+        info!("Database file location: {db_file:?}");
+        connection_options.filename(db_file)
+    } else {
+        connection_options.in_memory(true)
+    };
+
+    let pool = if db_location.is_some() {
+        sqlx::sqlite::SqlitePool::connect_with(connection_options).await?
+    } else {
+        // FIXME: we need to set max connection to 1 for in memory sqlite DB.
+        // Probably has to do something with this issue: https://github.com/launchbadge/sqlx/issues/2510
+        let pool_options = sqlx::sqlite::SqlitePoolOptions::new().max_connections(1);
+        pool_options.connect_with(connection_options).await?
+    };
+
+    // Run migration for p2panda OperationStore and for the our DocumentStore
+    Migrator::new(CombinedMigrationSource::new(vec![
+        operation_store_migrations(),
+        sqlx::migrate!(),
+    ]))
+    .await?
+    .run(&pool)
+    .await?;
+
+    Ok(pool)
+}
+
+#[derive(Clone, Debug)]
+pub struct DocumentStore {
+    pool: sqlx::SqlitePool,
+}
+
+impl DocumentStore {
+    pub fn new(pool: sqlx::SqlitePool) -> Self {
+        Self { pool }
+    }
+
+    pub(crate) async fn authors(&self, document_id: &DocumentId) -> sqlx::Result<Vec<PublicKey>> {
+        let list = sqlx::query("SELECT public_key FROM authors WHERE document_id = ?")
+            .bind(document_id)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(list
+            .iter()
+            .filter_map(|row| PublicKey::try_from(row.get::<&[u8], _>("public_key")).ok())
+            .collect())
+    }
+
+    pub async fn documents(&self) -> sqlx::Result<Vec<Document>> {
+        let mut documents: Vec<Document> = sqlx::query_as(
+            "SELECT document_id, name, last_accessed, created_at, last_edited, discoverable, archived, trashed_at, pinned, position FROM documents",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        let authors = sqlx::query("SELECT public_key, document_id, last_seen FROM authors")
+            .fetch_all(&self.pool)
+            .await?;
+
+        let mut authors_per_document = authors.iter().fold(HashMap::new(), |mut acc, row| {
+            let Ok(document_id) = row.try_get::<DocumentId, _>("document_id") else {
+                return acc;
+            };
+            let Ok(public_key) = PublicKey::try_from(row.get::<&[u8], _>("public_key")) else {
+                return acc;
+            };
+            let Ok(last_seen) = row.try_get::<Option<DateTime<Utc>>, _>("last_seen") else {
+                return acc;
+            };
+            acc.entry(document_id)
+                .or_insert_with(|| Vec::new())
+                .push(Author {
+                    public_key,
+                    last_seen,
+                });
+            acc
+        });
+
+        let aliases = sqlx::query("SELECT public_key, document_id, alias FROM author_aliases")
+            .fetch_all(&self.pool)
+            .await?;
+        let mut aliases_per_document = aliases.iter().fold(HashMap::new(), |mut acc, row| {
+            let Ok(document_id) = row.try_get::<DocumentId, _>("document_id") else {
+                return acc;
+            };
+            let Ok(public_key) = PublicKey::try_from(row.get::<&[u8], _>("public_key")) else {
+                return acc;
+            };
+            let Ok(alias) = row.try_get::<String, _>("alias") else {
+                return acc;
+            };
+            acc.entry(document_id)
+                .or_insert_with(HashMap::new)
+                .insert(public_key, alias);
+            acc
+        });
+
+        for document in &mut documents {
+            document.authors = authors_per_document
+                .remove(&document.id)
+                .expect("Document does not exist");
+            document.author_aliases = aliases_per_document
+                .remove(&document.id)
+                .unwrap_or_default();
+        }
+
+        Ok(documents)
+    }
+
+    /// Count rows of our own and the p2panda operation tables and determine the size of the
+    /// database.
+    pub async fn stats(&self) -> sqlx::Result<StoreStats> {
+        let documents: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM documents")
+            .fetch_one(&self.pool)
+            .await?;
+        let authors: i64 = sqlx::query_scalar("SELECT COUNT(DISTINCT public_key) FROM authors")
+            .fetch_one(&self.pool)
+            .await?;
+        let operations: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM operations_v1")
+            .fetch_one(&self.pool)
+            .await?;
+        let page_count: i64 = sqlx::query_scalar("PRAGMA page_count")
+            .fetch_one(&self.pool)
+            .await?;
+        let page_size: i64 = sqlx::query_scalar("PRAGMA page_size")
+            .fetch_one(&self.pool)
+            .await?;
+
+        Ok(StoreStats {
+            documents: documents as u64,
+            authors: authors as u64,
+            operations: operations as u64,
+            database_size: (page_count * page_size) as u64,
+        })
+    }
+
+    /// Authors of all documents.
+    pub async fn all_authors(&self) -> sqlx::Result<HashSet<PublicKey>> {
+        let list = sqlx::query("SELECT DISTINCT public_key FROM authors")
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(list
+            .iter()
+            .filter_map(|row| PublicKey::try_from(row.get::<&[u8], _>("public_key")).ok())
+            .collect())
+    }
+
+    /// Authors we don't accept any data from anymore.
+    pub async fn blocked_authors(&self) -> sqlx::Result<HashSet<PublicKey>> {
+        let list = sqlx::query("SELECT public_key FROM blocked_authors")
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(list
+            .iter()
+            .filter_map(|row| PublicKey::try_from(row.get::<&[u8], _>("public_key")).ok())
+            .collect())
+    }
+
+    pub async fn set_author_blocked(
+        &self,
+        public_key: &PublicKey,
+        blocked: bool,
+    ) -> sqlx::Result<()> {
+        if blocked {
+            sqlx::query(
+                "
+                INSERT OR IGNORE INTO blocked_authors ( public_key, blocked_at )
+                VALUES ( ?, ? )
+                ",
+            )
+            .bind(public_key.as_bytes().as_slice())
+            .bind(Utc::now())
+            .execute(&self.pool)
+            .await?;
+        } else {
+            sqlx::query("DELETE FROM blocked_authors WHERE public_key = ?")
+                .bind(public_key.as_bytes().as_slice())
+                .execute(&self.pool)
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    pub async fn authors_with_last_seen(
+        &self,
+        document_id: &DocumentId,
+    ) -> sqlx::Result<Vec<Author>> {
+        let list = sqlx::query("SELECT public_key, last_seen FROM authors WHERE document_id = ?")
+            .bind(document_id)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(list
+            .iter()
+            .filter_map(|row| {
+                let public_key = PublicKey::try_from(row.get::<&[u8], _>("public_key")).ok()?;
+                let last_seen = row.try_get::<Option<DateTime<Utc>>, _>("last_seen").ok()?;
+                Some(Author {
+                    public_key,
+                    last_seen,
+                })
+            })
+            .collect())
+    }
+
+    pub async fn add_document(&self, document_id: &DocumentId) -> sqlx::Result<()> {
+        // The document_id is the primary key in the table therefore ignore insertion when the document exists already
+        sqlx::query(
+            "
+            INSERT OR IGNORE INTO documents ( document_id, created_at )
+            VALUES ( ?, ? )
+            ",
+        )
+        .bind(document_id)
+        .bind(Utc::now())
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn add_author(
+        &self,
+        document_id: &DocumentId,
+        public_key: &PublicKey,
+    ) -> sqlx::Result<()> {
+        // The author/document_id pair is required to be unique therefore ignore if the insertion fails
+        sqlx::query(
+            "
+            INSERT OR IGNORE INTO authors ( public_key, document_id )
+            VALUES ( ?, ? )
+            ",
+        )
+        .bind(public_key.as_bytes().as_slice())
+        .bind(document_id)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn set_last_seen_for_author(
+        &self,
+        public_key: PublicKey,
+        last_seen: Option<DateTime<Utc>>,
+    ) -> sqlx::Result<()> {
+        sqlx::query(
+            "
+            UPDATE authors
+            SET last_seen = ?
+            WHERE public_key = ?
+            ",
+        )
+        .bind(last_seen)
+        .bind(public_key.as_bytes().as_slice())
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Show `public_key` as `alias` in the document, or with their regular name if it's `None`.
+    pub async fn set_author_alias(
+        &self,
+        document_id: &DocumentId,
+        public_key: &PublicKey,
+        alias: Option<&str>,
+    ) -> sqlx::Result<()> {
+        if let Some(alias) = alias {
+            sqlx::query(
+                "
+                INSERT OR REPLACE INTO author_aliases ( public_key, document_id, alias )
+                VALUES ( ?, ?, ? )
+                ",
+            )
+            .bind(public_key.as_bytes().as_slice())
+            .bind(document_id)
+            .bind(alias)
+            .execute(&self.pool)
+            .await?;
+        } else {
+            sqlx::query("DELETE FROM author_aliases WHERE public_key = ? AND document_id = ?")
+                .bind(public_key.as_bytes().as_slice())
+                .bind(document_id)
+                .execute(&self.pool)
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    pub async fn set_name_for_document(
+        &self,
+        document_id: &DocumentId,
+        name: Option<String>,
+    ) -> sqlx::Result<()> {
+        sqlx::query(
+            "
+            UPDATE documents
+            SET name = ?
+            WHERE document_id = ?
+            ",
+        )
+        .bind(name)
+        .bind(document_id)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn set_last_accessed_for_document(
+        &self,
+        document_id: &DocumentId,
+        last_accessed: Option<DateTime<Utc>>,
+    ) -> sqlx::Result<()> {
+        sqlx::query(
+            "
+            UPDATE documents
+            SET last_accessed = ?
+            WHERE document_id = ?
+            ",
+        )
+        .bind(last_accessed)
+        .bind(document_id)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn set_discoverable_for_document(
+        &self,
+        document_id: &DocumentId,
+        discoverable: bool,
+    ) -> sqlx::Result<()> {
+        sqlx::query(
+            "
+            UPDATE documents
+            SET discoverable = ?
+            WHERE document_id = ?
+            ",
+        )
+        .bind(discoverable)
+        .bind(document_id)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn set_archived_for_document(
+        &self,
+        document_id: &DocumentId,
+        archived: bool,
+    ) -> sqlx::Result<()> {
+        sqlx::query(
+            "
+            UPDATE documents
+            SET archived = ?
+            WHERE document_id = ?
+            ",
+        )
+        .bind(archived)
+        .bind(document_id)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn set_pinned_for_document(
+        &self,
+        document_id: &DocumentId,
+        pinned: bool,
+    ) -> sqlx::Result<()> {
+        sqlx::query(
+            "
+            UPDATE documents
+            SET pinned = ?
+            WHERE document_id = ?
+            ",
+        )
+        .bind(pinned)
+        .bind(document_id)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Store the order of the document list, every document gets its index in `document_ids` as
+    /// position.
+    pub async fn set_positions_for_documents(
+        &self,
+        document_ids: &[DocumentId],
+    ) -> sqlx::Result<()> {
+        let mut transaction = self.pool.begin().await?;
+        for (position, document_id) in document_ids.iter().enumerate() {
+            sqlx::query(
+                "
+                UPDATE documents
+                SET position = ?
+                WHERE document_id = ?
+                ",
+            )
+            .bind(position as i64)
+            .bind(document_id)
+            .execute(&mut *transaction)
+            .await?;
+        }
+        transaction.commit().await?;
+
+        Ok(())
+    }
+
+    pub async fn set_trashed_at_for_document(
+        &self,
+        document_id: &DocumentId,
+        trashed_at: Option<DateTime<Utc>>,
+    ) -> sqlx::Result<()> {
+        sqlx::query(
+            "
+            UPDATE documents
+            SET trashed_at = ?
+            WHERE document_id = ?
+            ",
+        )
+        .bind(trashed_at)
+        .bind(document_id)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Remove the document and its authors, the operations are kept in the `OperationStore`.
+    pub async fn delete_document(&self, document_id: &DocumentId) -> sqlx::Result<()> {
+        let mut transaction = self.pool.begin().await?;
+        sqlx::query("DELETE FROM authors WHERE document_id = ?")
+            .bind(document_id)
+            .execute(&mut *transaction)
+            .await?;
+        sqlx::query("DELETE FROM author_aliases WHERE document_id = ?")
+            .bind(document_id)
+            .execute(&mut *transaction)
+            .await?;
+        sqlx::query("DELETE FROM documents WHERE document_id = ?")
+            .bind(document_id)
+            .execute(&mut *transaction)
+            .await?;
+        transaction.commit().await?;
+
+        Ok(())
+    }
+
+    /// Keep operations of a corrupted log for inspection, see `crate::integrity`.
+    pub async fn quarantine_operations(
+        &self,
+        log_id: &LogId,
+        operations: impl Iterator<Item = (Vec<u8>, Option<Vec<u8>>)>,
+        reason: &str,
+    ) -> sqlx::Result<()> {
+        let LogId(log_type, document_id) = log_id;
+        let log_type = format!("{log_type:?}");
+        let now = Utc::now();
+
+        let mut transaction = self.pool.begin().await?;
+        for (header, body) in operations {
+            sqlx::query(
+                "
+                INSERT INTO quarantined_operations
+                    ( document_id, log_type, header, body, reason, quarantined_at )
+                VALUES ( ?, ?, ?, ?, ?, ? )
+                ",
+            )
+            .bind(document_id)
+            .bind(&log_type)
+            .bind(header)
+            .bind(body)
+            .bind(reason)
+            .bind(now)
+            .execute(&mut *transaction)
+            .await?;
+        }
+        transaction.commit().await?;
+
+        Ok(())
+    }
+
+    pub async fn set_last_edited_for_document(
+        &self,
+        document_id: &DocumentId,
+        last_edited: DateTime<Utc>,
+    ) -> sqlx::Result<()> {
+        sqlx::query(
+            "
+            UPDATE documents
+            SET last_edited = ?
+            WHERE document_id = ?
+            ",
+        )
+        .bind(last_edited)
+        .bind(document_id)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn operations_for_document(
+        &self,
+        operation_store: &OperationStore,
+        document_id: &DocumentId,
+    ) -> sqlx::Result<Vec<p2panda_core::Operation<AardvarkExtensions>>> {
+        let authors = self.authors(document_id).await?;
+
+        let log_ids = [
+            LogId::new(LogType::Delta, document_id),
+            LogId::new(LogType::Snapshot, document_id),
+        ];
+
+        let mut result = Vec::new();
+
+        for author in authors.iter() {
+            for log_id in &log_ids {
+                let operations = match operation_store.get_log(author, log_id, None).await {
+                    Ok(Some(operations)) => {
+                        operations.into_iter().map(|(header, body)| {
+                            let operation = p2panda_core::Operation {
+                                hash: header.hash(),
+                                header,
+                                body,
+                            };
+
+                            // Stored operations are always valid
+                            assert!(validate_operation(&operation, &document_id).is_ok());
+                            operation
+                        })
+                    }
+                    Ok(None) => {
+                        continue;
+                    }
+                    Err(error) => {
+                        error!(
+                            "Failed to load operation for {author} with log type {log_id:?}: {error}"
+                        );
+                        continue;
+                    }
+                };
+
+                result.extend(operations);
+            }
+        }
+
+        Ok(result)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, StdHash, Serialize, Deserialize)]
+pub struct LogId(LogType, DocumentId);
+
+impl LogId {
+    pub fn new(log_type: LogType, document: &DocumentId) -> Self {
+        Self(log_type, *document)
+    }
+}
+
+#[async_trait]
+impl TopicLogMap<DocumentId, LogId> for DocumentStore {
+    async fn get(&self, topic: &DocumentId) -> Option<HashMap<PublicKey, Vec<LogId>>> {
+        let Ok(authors) = self.authors(topic).await else {
+            return None;
+        };
+        // Logs of blocked authors are neither offered to nor requested from other peers
+        let Ok(blocked_authors) = self.blocked_authors().await else {
+            return None;
+        };
+        let log_ids = [
+            LogId::new(LogType::Delta, topic),
+            LogId::new(LogType::Snapshot, topic),
+        ];
+        Some(
+            authors
+                .into_iter()
+                .filter(|author| !blocked_authors.contains(author))
+                .map(|author| (author, log_ids.to_vec()))
+                .collect(),
+        )
+    }
+}
+
+pub type OperationStore = SqliteStore<LogId, AardvarkExtensions>;
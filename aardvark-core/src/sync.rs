@@ -0,0 +1,568 @@
+//! Control over individual sync sessions.
+//!
+//! Every sync session started by p2panda's sync manager goes through [`ControlledSync`], which
+//! registers the session in [`SyncSessions`] for the time it is running. Through the registry a
+//! session can be paused (no more bytes are read or written until it is resumed) or cancelled
+//! (the session fails with an IO error and is retried later by the sync manager). Sessions taking
+//! longer than the configured timeout, not counting the time they were paused, are cancelled the
+//! same way, the timeout can be changed while the node is running. The registry also limits the bandwidth of sessions, see
+//! `crate::bandwidth`.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::io;
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker, ready};
+use std::time::{Duration, Instant};
+
+use anyhow::{Result, anyhow};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use futures::{AsyncRead, AsyncWrite, Sink};
+use p2panda_sync::{FromSync, SyncError, SyncProtocol};
+
+use crate::bandwidth::{BandwidthLimits, Throughput, TokenBucket};
+use crate::debug::{SyncDirection, SyncSessionInfo};
+use crate::document::DocumentId;
+
+/// Where the node listens for peers and limits of sync sessions, e.g. for peers on flaky networks
+/// or behind firewalls.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NetworkConfig {
+    /// Listen on IPv4 at all.
+    pub ipv4: bool,
+    /// Listen on IPv6 at all.
+    pub ipv6: bool,
+    /// Address of the interface to listen on for IPv4, e.g. of a VPN, all interfaces if `None`.
+    pub bind_ip_v4: Option<Ipv4Addr>,
+    /// Address of the interface to listen on for IPv6, all interfaces if `None`.
+    pub bind_ip_v6: Option<Ipv6Addr>,
+    /// Port to listen on for both IPv4 and IPv6, a random one if 0.
+    pub port: u16,
+    /// Sessions taking longer are cancelled and retried, the only one applied without a restart.
+    pub session_timeout: Duration,
+    /// Sessions running at the same time at most, further ones wait until others ended.
+    pub max_concurrent_sessions: usize,
+    /// How often a failed session is retried before we wait for the next resync.
+    pub max_retry_attempts: u8,
+    /// Caps of sync traffic, applied without a restart as well.
+    pub bandwidth: BandwidthLimits,
+}
+
+impl Default for NetworkConfig {
+    fn default() -> Self {
+        Self {
+            ipv4: true,
+            ipv6: true,
+            bind_ip_v4: None,
+            bind_ip_v6: None,
+            port: 0,
+            session_timeout: Duration::from_secs(60),
+            max_concurrent_sessions: 128,
+            max_retry_attempts: 5,
+            bandwidth: BandwidthLimits::default(),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct SessionState {
+    document: Option<DocumentId>,
+    direction: SyncDirection,
+    started: DateTime<Utc>,
+    paused: AtomicBool,
+    /// Time spent paused before the current pause, and since when it's paused right now.
+    paused_time: Mutex<(Duration, Option<Instant>)>,
+    cancelled: AtomicBool,
+    bytes_sent: AtomicU64,
+    bytes_received: AtomicU64,
+    /// Limits of this session alone, see [`BandwidthLimits::peer_upload`].
+    upload: Mutex<TokenBucket>,
+    download: Mutex<TokenBucket>,
+    /// Tasks waiting for the session to be resumed.
+    wakers: Mutex<Vec<Waker>>,
+}
+
+impl SessionState {
+    fn wake(&self) {
+        for waker in self.wakers.lock().unwrap().drain(..) {
+            waker.wake();
+        }
+    }
+
+    fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Release);
+        self.wake();
+    }
+
+    fn set_paused(&self, paused: bool) {
+        let now = Instant::now();
+        let mut paused_time = self.paused_time.lock().unwrap();
+        match (paused, paused_time.1) {
+            (true, None) => paused_time.1 = Some(now),
+            (false, Some(since)) => *paused_time = (paused_time.0 + (now - since), None),
+            _ => {}
+        }
+        self.paused.store(paused, Ordering::Release);
+    }
+
+    /// How long the session was paused in total, up to now.
+    fn paused_time(&self) -> Duration {
+        let (before, since) = *self.paused_time.lock().unwrap();
+        before + since.map_or(Duration::ZERO, |since| since.elapsed())
+    }
+
+    /// Returns `Pending` while the session is paused and an error once it got cancelled.
+    fn poll_gate(&self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        if self.cancelled.load(Ordering::Acquire) {
+            return Poll::Ready(Err(io::Error::new(
+                io::ErrorKind::ConnectionAborted,
+                "sync session cancelled",
+            )));
+        }
+
+        if self.paused.load(Ordering::Acquire) {
+            self.wakers.lock().unwrap().push(cx.waker().clone());
+            // The session might have been resumed while we registered the waker
+            if self.paused.load(Ordering::Acquire) {
+                return Poll::Pending;
+            }
+        }
+
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// Whether data is sent to or received from a peer.
+#[derive(Clone, Copy, Debug)]
+enum Transfer {
+    Upload,
+    Download,
+}
+
+/// Traffic of all sync sessions together.
+#[derive(Debug, Default)]
+struct Bandwidth {
+    limits: BandwidthLimits,
+    upload: TokenBucket,
+    download: TokenBucket,
+    uploaded: Throughput,
+    downloaded: Throughput,
+}
+
+/// Registry of all currently running sync sessions.
+#[derive(Clone, Debug, Default)]
+pub struct SyncSessions {
+    sessions: Arc<Mutex<HashMap<u64, Arc<SessionState>>>>,
+    next_id: Arc<AtomicU64>,
+    /// Sessions which ended successfully and ones which failed, since the node started.
+    completed: Arc<AtomicU64>,
+    failed: Arc<AtomicU64>,
+    /// Sessions we initiated for a document which ended successfully, since the node started.
+    completed_by_document: Arc<Mutex<HashMap<DocumentId, u64>>>,
+    /// Timeout of sessions starting from now on in milliseconds, see [`ControlledSync`].
+    session_timeout: Arc<AtomicU64>,
+    bandwidth: Arc<Mutex<Bandwidth>>,
+}
+
+impl SyncSessions {
+    fn start(&self, document: Option<DocumentId>, direction: SyncDirection) -> SessionGuard {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let limits = self.bandwidth.lock().unwrap().limits;
+        let state = Arc::new(SessionState {
+            document,
+            direction,
+            started: Utc::now(),
+            paused: AtomicBool::new(false),
+            paused_time: Mutex::new((Duration::ZERO, None)),
+            cancelled: AtomicBool::new(false),
+            bytes_sent: AtomicU64::new(0),
+            bytes_received: AtomicU64::new(0),
+            upload: Mutex::new(TokenBucket::new(limits.peer_upload)),
+            download: Mutex::new(TokenBucket::new(limits.peer_download)),
+            wakers: Mutex::new(Vec::new()),
+        });
+        self.sessions.lock().unwrap().insert(id, state.clone());
+
+        SessionGuard {
+            id,
+            state,
+            sessions: self.clone(),
+        }
+    }
+
+    fn get(&self, id: u64) -> Result<Arc<SessionState>> {
+        self.sessions
+            .lock()
+            .unwrap()
+            .get(&id)
+            .cloned()
+            .ok_or_else(|| anyhow!("Unknown sync session {id}"))
+    }
+
+    pub fn list(&self) -> Vec<SyncSessionInfo> {
+        let mut sessions: Vec<SyncSessionInfo> = self
+            .sessions
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(id, state)| SyncSessionInfo {
+                id: *id,
+                document: state.document,
+                direction: state.direction,
+                started: state.started,
+                bytes_sent: state.bytes_sent.load(Ordering::Relaxed),
+                bytes_received: state.bytes_received.load(Ordering::Relaxed),
+                paused: state.paused.load(Ordering::Acquire),
+            })
+            .collect();
+        sessions.sort_by_key(|session| session.id);
+        sessions
+    }
+
+    fn record<T>(&self, document: Option<DocumentId>, result: &Result<T, SyncError>) {
+        let counter = if result.is_ok() {
+            &self.completed
+        } else {
+            &self.failed
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+
+        if let (Some(document), Ok(_)) = (document, result) {
+            *self
+                .completed_by_document
+                .lock()
+                .unwrap()
+                .entry(document)
+                .or_default() += 1;
+        }
+    }
+
+    /// Number of sessions we initiated for `document` which completed since the node started.
+    pub fn completed(&self, document: &DocumentId) -> u64 {
+        self.completed_by_document
+            .lock()
+            .unwrap()
+            .get(document)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Whether a session we initiated for `document` is running.
+    pub fn is_syncing(&self, document: &DocumentId) -> bool {
+        self.sessions
+            .lock()
+            .unwrap()
+            .values()
+            .any(|state| state.document.as_ref() == Some(document))
+    }
+
+    /// Number of sessions which completed and which failed since the node started.
+    pub fn totals(&self) -> (u64, u64) {
+        (
+            self.completed.load(Ordering::Relaxed),
+            self.failed.load(Ordering::Relaxed),
+        )
+    }
+
+    pub fn session_timeout(&self) -> Duration {
+        Duration::from_millis(self.session_timeout.load(Ordering::Relaxed))
+    }
+
+    /// Change the timeout of sessions, running sessions keep the one they started with.
+    pub fn set_session_timeout(&self, timeout: Duration) {
+        self.session_timeout
+            .store(timeout.as_millis() as u64, Ordering::Relaxed);
+    }
+
+    /// Change the bandwidth limits, of running sessions too.
+    pub fn set_bandwidth_limits(&self, limits: BandwidthLimits) {
+        {
+            let mut bandwidth = self.bandwidth.lock().unwrap();
+            bandwidth.limits = limits;
+            bandwidth.upload.set_rate(limits.upload);
+            bandwidth.download.set_rate(limits.download);
+        }
+        for state in self.sessions.lock().unwrap().values() {
+            state.upload.lock().unwrap().set_rate(limits.peer_upload);
+            state
+                .download
+                .lock()
+                .unwrap()
+                .set_rate(limits.peer_download);
+        }
+    }
+
+    pub fn bandwidth_limits(&self) -> BandwidthLimits {
+        self.bandwidth.lock().unwrap().limits
+    }
+
+    /// Bytes per second uploaded and downloaded by all sessions recently.
+    pub fn throughput(&self) -> (u64, u64) {
+        let now = Instant::now();
+        let mut bandwidth = self.bandwidth.lock().unwrap();
+        (bandwidth.uploaded.rate(now), bandwidth.downloaded.rate(now))
+    }
+
+    /// How long the next transfer of the session with `state` has to wait for the limits.
+    fn transfer_delay(&self, state: &SessionState, transfer: Transfer) -> Option<Duration> {
+        let now = Instant::now();
+        let mut bandwidth = self.bandwidth.lock().unwrap();
+        let (all, session) = match transfer {
+            Transfer::Upload => (
+                bandwidth.upload.delay(now),
+                state.upload.lock().unwrap().delay(now),
+            ),
+            Transfer::Download => (
+                bandwidth.download.delay(now),
+                state.download.lock().unwrap().delay(now),
+            ),
+        };
+        all.max(session)
+    }
+
+    fn record_transfer(&self, state: &SessionState, transfer: Transfer, len: usize) {
+        let now = Instant::now();
+        let mut bandwidth = self.bandwidth.lock().unwrap();
+        match transfer {
+            Transfer::Upload => {
+                state.bytes_sent.fetch_add(len as u64, Ordering::Relaxed);
+                state.upload.lock().unwrap().consume(len, now);
+                bandwidth.upload.consume(len, now);
+                bandwidth.uploaded.record(len, now);
+            }
+            Transfer::Download => {
+                state
+                    .bytes_received
+                    .fetch_add(len as u64, Ordering::Relaxed);
+                state.download.lock().unwrap().consume(len, now);
+                bandwidth.download.consume(len, now);
+                bandwidth.downloaded.record(len, now);
+            }
+        }
+    }
+
+    pub fn set_paused(&self, id: u64, paused: bool) -> Result<()> {
+        let state = self.get(id)?;
+        state.set_paused(paused);
+        if !paused {
+            state.wake();
+        }
+
+        Ok(())
+    }
+
+    pub fn cancel(&self, id: u64) -> Result<()> {
+        self.get(id)?.cancel();
+
+        Ok(())
+    }
+}
+
+/// Removes the session from the registry once it ended.
+struct SessionGuard {
+    id: u64,
+    state: Arc<SessionState>,
+    sessions: SyncSessions,
+}
+
+impl Drop for SessionGuard {
+    fn drop(&mut self) {
+        self.sessions.sessions.lock().unwrap().remove(&self.id);
+    }
+}
+
+/// Runs `session`, cancelling it once it took longer than `timeout`.
+///
+/// Time the session spent paused doesn't count, e.g. a large download paused on a metered
+/// connection is continued where it stopped when resumed.
+async fn with_timeout(
+    state: &SessionState,
+    timeout: Duration,
+    session: impl Future<Output = Result<(), SyncError>>,
+) -> Result<(), SyncError> {
+    let started = tokio::time::Instant::now();
+    let deadline = tokio::time::sleep(timeout);
+    tokio::pin!(deadline, session);
+    let mut timed_out = false;
+    loop {
+        tokio::select! {
+            result = &mut session => return result,
+            // Polling the session again lets it fail with the cancellation error
+            _ = &mut deadline, if !timed_out => {
+                let now = tokio::time::Instant::now();
+                let active = (now - started).saturating_sub(state.paused_time());
+                if state.paused.load(Ordering::Acquire) {
+                    // Check again later, the rest of the timeout starts once it's resumed
+                    deadline.as_mut().reset(now + timeout);
+                } else if active < timeout {
+                    deadline.as_mut().reset(now + (timeout - active));
+                } else {
+                    timed_out = true;
+                    state.cancel();
+                }
+            }
+        }
+    }
+}
+
+/// Wraps the reading or writing half of a sync session to apply pausing, cancellation and the
+/// bandwidth limits.
+struct SessionIo<'a, S: ?Sized> {
+    inner: &'a mut S,
+    session: &'a SessionGuard,
+    /// Waiting for the bandwidth limits before the next transfer.
+    throttle: Option<Pin<Box<tokio::time::Sleep>>>,
+}
+
+impl<'a, S: ?Sized> SessionIo<'a, S> {
+    fn new(inner: &'a mut S, session: &'a SessionGuard) -> Self {
+        Self {
+            inner,
+            session,
+            throttle: None,
+        }
+    }
+
+    /// Returns `Pending` until the bandwidth limits allow the next transfer.
+    fn poll_throttle(&mut self, cx: &mut Context<'_>, transfer: Transfer) -> Poll<()> {
+        loop {
+            if let Some(throttle) = &mut self.throttle {
+                ready!(throttle.as_mut().poll(cx));
+                self.throttle = None;
+            }
+
+            let sessions = &self.session.sessions;
+            match sessions.transfer_delay(&self.session.state, transfer) {
+                Some(delay) => self.throttle = Some(Box::pin(tokio::time::sleep(delay))),
+                None => return Poll::Ready(()),
+            }
+        }
+    }
+}
+
+impl<S: AsyncRead + Unpin + ?Sized> AsyncRead for SessionIo<'_, S> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        ready!(this.session.state.poll_gate(cx))?;
+        ready!(this.poll_throttle(cx, Transfer::Download));
+        let len = ready!(Pin::new(&mut *this.inner).poll_read(cx, buf))?;
+        this.session
+            .sessions
+            .record_transfer(&this.session.state, Transfer::Download, len);
+        Poll::Ready(Ok(len))
+    }
+}
+
+impl<S: AsyncWrite + Unpin + ?Sized> AsyncWrite for SessionIo<'_, S> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        ready!(this.session.state.poll_gate(cx))?;
+        ready!(this.poll_throttle(cx, Transfer::Upload));
+        let len = ready!(Pin::new(&mut *this.inner).poll_write(cx, buf))?;
+        this.session
+            .sessions
+            .record_transfer(&this.session.state, Transfer::Upload, len);
+        Poll::Ready(Ok(len))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut *self.get_mut().inner).poll_flush(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut *self.get_mut().inner).poll_close(cx)
+    }
+}
+
+/// Sync protocol registering every session of the wrapped protocol in [`SyncSessions`].
+#[derive(Debug)]
+pub struct ControlledSync<P> {
+    inner: Arc<P>,
+    sessions: SyncSessions,
+}
+
+impl<P> ControlledSync<P> {
+    pub fn new(inner: P, sessions: SyncSessions, config: &NetworkConfig) -> Self {
+        sessions.set_session_timeout(config.session_timeout);
+        sessions.set_bandwidth_limits(config.bandwidth);
+        Self {
+            inner: Arc::new(inner),
+            sessions,
+        }
+    }
+}
+
+#[async_trait]
+impl<'a, P> SyncProtocol<'a, DocumentId> for ControlledSync<P>
+where
+    P: for<'b> SyncProtocol<'b, DocumentId> + 'static,
+{
+    fn name(&self) -> &'static str {
+        self.inner.name()
+    }
+
+    async fn initiate(
+        self: Arc<Self>,
+        topic_query: DocumentId,
+        tx: Box<&'a mut (dyn AsyncWrite + Send + Unpin)>,
+        rx: Box<&'a mut (dyn AsyncRead + Send + Unpin)>,
+        app_tx: Box<&'a mut (dyn Sink<FromSync<DocumentId>, Error = SyncError> + Send + Unpin)>,
+    ) -> Result<(), SyncError> {
+        let session = self
+            .sessions
+            .start(Some(topic_query), SyncDirection::Initiated);
+        let mut tx = SessionIo::new(*tx, &session);
+        let mut rx = SessionIo::new(*rx, &session);
+
+        let result = with_timeout(
+            &session.state,
+            self.sessions.session_timeout(),
+            self.inner.clone().initiate(
+                topic_query,
+                Box::new(&mut tx as &mut (dyn AsyncWrite + Send + Unpin)),
+                Box::new(&mut rx as &mut (dyn AsyncRead + Send + Unpin)),
+                app_tx,
+            ),
+        )
+        .await;
+        self.sessions.record(Some(topic_query), &result);
+        result
+    }
+
+    async fn accept(
+        self: Arc<Self>,
+        tx: Box<&'a mut (dyn AsyncWrite + Send + Unpin)>,
+        rx: Box<&'a mut (dyn AsyncRead + Send + Unpin)>,
+        app_tx: Box<&'a mut (dyn Sink<FromSync<DocumentId>, Error = SyncError> + Send + Unpin)>,
+    ) -> Result<(), SyncError> {
+        // The topic is only known to the wrapped protocol after the handshake
+        let session = self.sessions.start(None, SyncDirection::Accepted);
+        let mut tx = SessionIo::new(*tx, &session);
+        let mut rx = SessionIo::new(*rx, &session);
+
+        let result = with_timeout(
+            &session.state,
+            self.sessions.session_timeout(),
+            self.inner.clone().accept(
+                Box::new(&mut tx as &mut (dyn AsyncWrite + Send + Unpin)),
+                Box::new(&mut rx as &mut (dyn AsyncRead + Send + Unpin)),
+                app_tx,
+            ),
+        )
+        .await;
+        self.sessions.record(None, &result);
+        result
+    }
+}
@@ -0,0 +1,169 @@
+//! Documents moved to the trash are deleted for good after a while.
+//!
+//! Moving a document to the trash only marks it in the document store, it can be restored until
+//! it's deleted. The node deletes trashed documents together with their authors and all their
+//! operations once they were in the trash for `TRASH_RETENTION`, or earlier when the trash is
+//! emptied.
+use std::time::Duration;
+
+use anyhow::Result;
+use chrono::Utc;
+use p2panda_store::LogStore;
+use tracing::info;
+
+use crate::document::DocumentId;
+use crate::operation::LogType;
+use crate::store::{DocumentStore, LogId, OperationStore};
+
+/// How long documents stay in the trash before they're deleted.
+pub const TRASH_RETENTION: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+/// How often we look for documents which were in the trash for long enough.
+pub(crate) const CLEANUP_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Remove the document and everything we stored about it.
+pub(crate) async fn delete_document(
+    document_store: &DocumentStore,
+    operation_store: &mut OperationStore,
+    document_id: &DocumentId,
+) -> Result<()> {
+    for author in document_store.authors(document_id).await? {
+        for log_type in [LogType::Delta, LogType::Snapshot] {
+            let log_id = LogId::new(log_type, document_id);
+            let Some((header, _)) = operation_store.latest_operation(&author, &log_id).await?
+            else {
+                continue;
+            };
+            // Removes all operations before the given sequence number, including the latest one
+            operation_store
+                .delete_operations(&author, &log_id, header.seq_num + 1)
+                .await?;
+        }
+    }
+    document_store.delete_document(document_id).await?;
+
+    Ok(())
+}
+
+/// Delete all documents which were in the trash for longer than `TRASH_RETENTION`, except
+/// `in_use`.
+///
+/// Returns the ids of the deleted documents.
+pub(crate) async fn delete_expired(
+    document_store: &DocumentStore,
+    operation_store: &mut OperationStore,
+    in_use: &[DocumentId],
+) -> Result<Vec<DocumentId>> {
+    let now = Utc::now();
+    let expired: Vec<DocumentId> = document_store
+        .documents()
+        .await?
+        .into_iter()
+        .filter(|document| {
+            document.trashed_at.is_some_and(|trashed_at| {
+                (now - trashed_at).to_std().unwrap_or_default() >= TRASH_RETENTION
+            }) && !in_use.contains(&document.id)
+        })
+        .map(|document| document.id)
+        .collect();
+
+    for document_id in &expired {
+        delete_document(document_store, operation_store, document_id).await?;
+        info!(document = %document_id, "Deleted document from the trash");
+    }
+
+    Ok(expired)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeDelta;
+    use p2panda_core::{Extension, PrivateKey};
+
+    use super::*;
+    use crate::operation::create_operation;
+    use crate::store::connect_database;
+
+    async fn add_document(
+        document_store: &DocumentStore,
+        operation_store: &mut OperationStore,
+        private_key: &PrivateKey,
+    ) -> DocumentId {
+        let operation = create_operation(
+            operation_store,
+            private_key,
+            LogType::Snapshot,
+            None,
+            None,
+            false,
+        )
+        .await
+        .unwrap();
+        let document_id: DocumentId = operation.header.extension().unwrap();
+        create_operation(
+            operation_store,
+            private_key,
+            LogType::Delta,
+            Some(document_id),
+            Some(b"hello"),
+            false,
+        )
+        .await
+        .unwrap();
+        document_store.add_document(&document_id).await.unwrap();
+        document_store
+            .add_author(&document_id, &private_key.public_key())
+            .await
+            .unwrap();
+        document_id
+    }
+
+    #[tokio::test]
+    async fn delete_expired_documents() {
+        let pool = connect_database(None).await.unwrap();
+        let document_store = DocumentStore::new(pool.clone());
+        let mut operation_store = OperationStore::new(pool);
+        let private_key = PrivateKey::new();
+
+        let expired = add_document(&document_store, &mut operation_store, &private_key).await;
+        let recent = add_document(&document_store, &mut operation_store, &private_key).await;
+        let in_use = add_document(&document_store, &mut operation_store, &private_key).await;
+        let long_ago = Utc::now() - TimeDelta::days(31);
+        document_store
+            .set_trashed_at_for_document(&expired, Some(long_ago))
+            .await
+            .unwrap();
+        document_store
+            .set_trashed_at_for_document(&recent, Some(Utc::now()))
+            .await
+            .unwrap();
+        document_store
+            .set_trashed_at_for_document(&in_use, Some(long_ago))
+            .await
+            .unwrap();
+
+        let deleted = delete_expired(&document_store, &mut operation_store, &[in_use])
+            .await
+            .unwrap();
+        assert_eq!(deleted, vec![expired]);
+
+        let documents: Vec<DocumentId> = document_store
+            .documents()
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|document| document.id)
+            .collect();
+        assert!(!documents.contains(&expired));
+        assert!(documents.contains(&recent));
+        assert!(documents.contains(&in_use));
+
+        let log_id = LogId::new(LogType::Delta, &expired);
+        assert!(
+            operation_store
+                .latest_operation(&private_key.public_key(), &log_id)
+                .await
+                .unwrap()
+                .is_none()
+        );
+    }
+}
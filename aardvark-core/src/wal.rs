@@ -0,0 +1,225 @@
+//! Local edits survive a crash until they're stored as operations.
+//!
+//! Deltas are appended to a write-ahead log on disk as soon as the node receives them, before
+//! they wait for other writes to the operation store. Once their operations are stored the entries
+//! are removed again. On the next start, entries left behind by a crash are stored as operations
+//! before any document is loaded, so the CRDT picks them up like every other stored delta.
+//!
+//! Storing a delta twice is harmless, importing the same change again doesn't change a document.
+//! Every entry is framed by its length and the hash of its bytes, a torn write at the end of a log
+//! is ignored.
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::Mutex;
+
+use anyhow::Result;
+use p2panda_core::Hash;
+use tracing::warn;
+
+use crate::document::DocumentId;
+
+const EXTENSION: &str = "wal";
+/// Length prefix and hash in front of every entry.
+const HEADER_LEN: usize = 4 + 32;
+
+/// Deltas of every document which aren't stored as operations yet.
+#[derive(Debug)]
+pub(crate) struct WriteAheadLog {
+    /// Nothing is written to disk without a directory, e.g. for nodes with an in-memory store.
+    dir: Option<PathBuf>,
+    pending: Mutex<Pending>,
+}
+
+#[derive(Debug, Default)]
+struct Pending {
+    next_entry: u64,
+    entries: HashMap<DocumentId, Vec<(u64, Vec<u8>)>>,
+}
+
+impl WriteAheadLog {
+    pub fn new(dir: Option<PathBuf>) -> Self {
+        Self {
+            dir,
+            pending: Mutex::default(),
+        }
+    }
+
+    /// Append a delta to the log of `document_id` and wait until it's on disk.
+    ///
+    /// Returns the entry to remove once the delta is stored.
+    pub fn append(&self, document_id: &DocumentId, delta: &[u8]) -> Result<u64> {
+        let mut pending = self.pending.lock().unwrap();
+        let entry = pending.next_entry;
+        pending.next_entry += 1;
+        pending
+            .entries
+            .entry(*document_id)
+            .or_default()
+            .push((entry, delta.to_vec()));
+
+        if let Some(path) = self.path(document_id)? {
+            let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+            file.write_all(&encode_entry(delta))?;
+            file.sync_data()?;
+        }
+
+        Ok(entry)
+    }
+
+    /// Remove an entry whose delta is stored now.
+    pub fn remove(&self, document_id: &DocumentId, entry: u64) -> Result<()> {
+        let mut pending = self.pending.lock().unwrap();
+        let Some(entries) = pending.entries.get_mut(document_id) else {
+            return Ok(());
+        };
+        entries.retain(|(id, _)| *id != entry);
+        let remaining: Vec<u8> = entries
+            .iter()
+            .flat_map(|(_, delta)| encode_entry(delta))
+            .collect();
+        if entries.is_empty() {
+            pending.entries.remove(document_id);
+        }
+
+        let Some(path) = self.path(document_id)? else {
+            return Ok(());
+        };
+        if remaining.is_empty() {
+            match fs::remove_file(&path) {
+                Err(error) if error.kind() != ErrorKind::NotFound => return Err(error.into()),
+                _ => {}
+            }
+        } else {
+            // Replace the log at once, a crash never leaves a partially rewritten log behind
+            let tmp_path = path.with_extension("tmp");
+            let mut file = File::create(&tmp_path)?;
+            file.write_all(&remaining)?;
+            file.sync_data()?;
+            fs::rename(tmp_path, path)?;
+        }
+
+        Ok(())
+    }
+
+    /// Deltas left behind by the previous run, for every document.
+    ///
+    /// They stay in the log until [`Self::clear`] is called for their document.
+    pub fn recover(&self) -> Result<Vec<(DocumentId, Vec<Vec<u8>>)>> {
+        let Some(dir) = &self.dir else {
+            return Ok(Vec::new());
+        };
+
+        let mut recovered = Vec::new();
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(error) if error.kind() == ErrorKind::NotFound => return Ok(recovered),
+            Err(error) => return Err(error.into()),
+        };
+        for entry in entries {
+            let path = entry?.path();
+            if path.extension().and_then(|extension| extension.to_str()) != Some(EXTENSION) {
+                continue;
+            }
+            let Some(document_id) = path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .and_then(|stem| DocumentId::from_str(stem).ok())
+            else {
+                warn!("Ignoring unknown write-ahead log {}", path.display());
+                continue;
+            };
+
+            let deltas = decode_entries(&fs::read(&path)?);
+            if !deltas.is_empty() {
+                recovered.push((document_id, deltas));
+            }
+        }
+
+        Ok(recovered)
+    }
+
+    /// Remove the log of `document_id` after its recovered deltas were stored.
+    pub fn clear(&self, document_id: &DocumentId) -> Result<()> {
+        self.pending.lock().unwrap().entries.remove(document_id);
+        if let Some(path) = self.path(document_id)? {
+            match fs::remove_file(path) {
+                Err(error) if error.kind() != ErrorKind::NotFound => return Err(error.into()),
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+
+    fn path(&self, document_id: &DocumentId) -> Result<Option<PathBuf>> {
+        let Some(dir) = &self.dir else {
+            return Ok(None);
+        };
+        fs::create_dir_all(dir)?;
+        Ok(Some(dir.join(format!("{document_id}.{EXTENSION}"))))
+    }
+}
+
+fn encode_entry(delta: &[u8]) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(HEADER_LEN + delta.len());
+    bytes.extend_from_slice(&(delta.len() as u32).to_le_bytes());
+    bytes.extend_from_slice(Hash::new(delta).as_bytes());
+    bytes.extend_from_slice(delta);
+    bytes
+}
+
+/// All complete and intact entries, up to the first one which isn't.
+fn decode_entries(mut bytes: &[u8]) -> Vec<Vec<u8>> {
+    let mut deltas = Vec::new();
+    while bytes.len() >= HEADER_LEN {
+        let len = u32::from_le_bytes(bytes[..4].try_into().unwrap()) as usize;
+        let Some(delta) = bytes.get(HEADER_LEN..HEADER_LEN + len) else {
+            break;
+        };
+        if Hash::new(delta).as_bytes()[..] != bytes[4..HEADER_LEN] {
+            break;
+        }
+        deltas.push(delta.to_vec());
+        bytes = &bytes[HEADER_LEN + len..];
+    }
+    if !bytes.is_empty() {
+        warn!(
+            "Ignoring {} bytes of a torn write-ahead log entry",
+            bytes.len()
+        );
+    }
+    deltas
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recover_pending_deltas() {
+        let dir = std::env::temp_dir().join(format!("aardvark-wal-{}", std::process::id()));
+        let document_id: DocumentId = Hash::new(b"document").into();
+
+        let wal = WriteAheadLog::new(Some(dir.clone()));
+        let stored = wal.append(&document_id, b"stored").unwrap();
+        wal.append(&document_id, b"pending").unwrap();
+        wal.remove(&document_id, stored).unwrap();
+
+        // A crash in the middle of appending the next entry
+        let path = dir.join(format!("{document_id}.{EXTENSION}"));
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(&encode_entry(b"torn")[..HEADER_LEN + 2])
+            .unwrap();
+
+        let wal = WriteAheadLog::new(Some(dir.clone()));
+        let recovered = wal.recover().unwrap();
+        assert_eq!(recovered, vec![(document_id, vec![b"pending".to_vec()])]);
+
+        wal.clear(&document_id).unwrap();
+        assert!(wal.recover().unwrap().is_empty());
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,161 @@
+//! Compare the CRDTs behind `crdt::CrdtBackend` by typing the same text into each of them.
+//!
+//! ```sh
+//! cargo run -p aardvark-doc --features automerge --example compare_crdts -- \
+//!     --file README.md --chars-per-change 4
+//! ```
+//!
+//! One replica types the text while a second one receives every change as update, like a peer
+//! following along via gossip. Reported are the sizes of the updates and the snapshot, and how
+//! long typing, exporting and loading took. Automerge is only available with the `automerge`
+//! feature, `--crdt` selects the implementations to compare, all built in ones by default.
+
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::time::{Duration, Instant};
+
+use aardvark_doc::crdt::CrdtKind;
+
+struct Options {
+    kinds: Vec<CrdtKind>,
+    file: Option<PathBuf>,
+    chars_per_change: usize,
+}
+
+struct Report {
+    changes: usize,
+    update_bytes: usize,
+    typing: Duration,
+    snapshot_bytes: usize,
+    export_snapshot: Duration,
+    load_snapshot: Duration,
+}
+
+fn usage() -> &'static str {
+    "Usage: compare_crdts [--crdt loro|automerge]... [--file <PATH>] [--chars-per-change <N>]"
+}
+
+fn parse_options() -> Result<Options, String> {
+    let mut options = Options {
+        kinds: Vec::new(),
+        file: None,
+        chars_per_change: 1,
+    };
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .ok_or_else(|| format!("Missing value for {arg}"))
+        };
+        match arg.as_str() {
+            "--crdt" => options
+                .kinds
+                .push(value()?.parse().map_err(|error| format!("{error}"))?),
+            "--file" => options.file = Some(PathBuf::from(value()?)),
+            "--chars-per-change" => {
+                options.chars_per_change =
+                    value()?.parse().map_err(|_| "Invalid --chars-per-change")?
+            }
+            _ => return Err(format!("Unknown argument {arg}")),
+        }
+    }
+
+    if options.kinds.is_empty() {
+        options.kinds = CrdtKind::ALL.to_vec();
+    }
+    if options.chars_per_change == 0 {
+        return Err("--chars-per-change must be at least 1".to_string());
+    }
+
+    Ok(options)
+}
+
+fn type_text(kind: CrdtKind, text: &str, chars_per_change: usize) -> anyhow::Result<Report> {
+    let mut typist = kind.create();
+    let mut follower = kind.create();
+    let mut changes = 0;
+    let mut update_bytes = 0;
+
+    let chars: Vec<char> = text.chars().collect();
+    let started = Instant::now();
+    for (index, chunk) in chars.chunks(chars_per_change).enumerate() {
+        let chunk: String = chunk.iter().collect();
+        typist.insert(index * chars_per_change, &chunk)?;
+        typist.commit();
+
+        let since = follower.version();
+        let update = typist.export_updates(&since)?;
+        follower.import(&update)?;
+        changes += 1;
+        update_bytes += update.len();
+    }
+    let typing = started.elapsed();
+    anyhow::ensure!(follower.text() == text, "{kind} replicas diverged");
+
+    let started = Instant::now();
+    let snapshot = typist.export_snapshot()?;
+    let export_snapshot = started.elapsed();
+
+    let started = Instant::now();
+    let loaded = kind.load(&snapshot)?;
+    let load_snapshot = started.elapsed();
+    anyhow::ensure!(loaded.text() == text, "{kind} snapshot is incomplete");
+
+    Ok(Report {
+        changes,
+        update_bytes,
+        typing,
+        snapshot_bytes: snapshot.len(),
+        export_snapshot,
+        load_snapshot,
+    })
+}
+
+fn main() -> ExitCode {
+    let options = match parse_options() {
+        Ok(options) => options,
+        Err(error) => {
+            eprintln!("{error}\n{}", usage());
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let text = match &options.file {
+        Some(file) => match std::fs::read_to_string(file) {
+            Ok(text) => text,
+            Err(error) => {
+                eprintln!("Failed to read {}: {error}", file.display());
+                return ExitCode::FAILURE;
+            }
+        },
+        None => "Hello Aardvark 🐽, typing along with a peer.\n".repeat(100),
+    };
+
+    println!(
+        "Typing {} characters, {} per change\n",
+        text.chars().count(),
+        options.chars_per_change
+    );
+    for kind in options.kinds {
+        match type_text(kind, &text, options.chars_per_change) {
+            Ok(report) => println!(
+                "{kind}:\n  updates: {} changes, {} bytes, {} bytes per change\n  \
+                 typing: {:?}\n  snapshot: {} bytes, exported in {:?}, loaded in {:?}\n",
+                report.changes,
+                report.update_bytes,
+                report.update_bytes / report.changes.max(1),
+                report.typing,
+                report.snapshot_bytes,
+                report.export_snapshot,
+                report.load_snapshot,
+            ),
+            Err(error) => {
+                eprintln!("{kind} failed: {error}");
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+
+    ExitCode::SUCCESS
+}
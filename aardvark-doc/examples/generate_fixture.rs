@@ -0,0 +1,265 @@
+//! Generate a large, realistic multi-author document directly into a store.
+//!
+//! ```sh
+//! cargo run -p aardvark-doc --features fixtures --example generate_fixture -- \
+//!     --data-dir ~/.local/share/aardvark --authors 5 --paragraphs 2000
+//! ```
+//!
+//! The generated document shows up in the document list the next time Aardvark is started with
+//! the given data directory. Every author gets its own snapshot, like after syncing with them.
+
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use aardvark_core::fixtures::FixtureWriter;
+use loro::{ExportMode, LoroDoc};
+use p2panda_core::PrivateKey;
+
+const TEXT_CONTAINER_ID: &str = "document";
+
+const WORDS: &[&str] = &[
+    "lorem",
+    "ipsum",
+    "dolor",
+    "sit",
+    "amet",
+    "consectetur",
+    "adipiscing",
+    "elit",
+    "sed",
+    "do",
+    "eiusmod",
+    "tempor",
+    "incididunt",
+    "ut",
+    "labore",
+    "et",
+    "dolore",
+    "magna",
+    "aliqua",
+    "enim",
+    "ad",
+    "minim",
+    "veniam",
+    "quis",
+    "nostrud",
+    "exercitation",
+    "ullamco",
+    "laboris",
+    "nisi",
+    "aliquip",
+    "ex",
+    "ea",
+    "commodo",
+    "consequat",
+    "peer",
+    "sync",
+    "offline",
+    "local-first",
+];
+
+const EMOJIS: &[&str] = &["🦔", "🐼", "✨", "👍", "🎉", "🇩🇪", "👩‍💻", "🏳️‍🌈", "❤️", "🚀"];
+
+#[derive(Clone, Copy, Debug)]
+enum Pattern {
+    /// Every author appends paragraphs to the end of the document in turn.
+    Append,
+    /// Authors insert paragraphs at random positions.
+    Interleaved,
+    /// Authors insert paragraphs and delete parts of existing text.
+    Rewrite,
+}
+
+struct Options {
+    data_dir: PathBuf,
+    name: String,
+    authors: usize,
+    paragraphs: usize,
+    emoji_density: f64,
+    pattern: Pattern,
+    seed: u64,
+}
+
+/// Small xorshift generator, fixtures need to be reproducible with the same seed.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn below(&mut self, max: usize) -> usize {
+        (self.next() % max.max(1) as u64) as usize
+    }
+
+    fn chance(&mut self, probability: f64) -> bool {
+        (self.next() as f64 / u64::MAX as f64) < probability
+    }
+}
+
+fn usage() -> &'static str {
+    "Usage: generate_fixture --data-dir <DIR> [--name <NAME>] [--authors <N>] \
+     [--paragraphs <N>] [--emoji-density <0.0-1.0>] \
+     [--pattern append|interleaved|rewrite] [--seed <N>]"
+}
+
+fn parse_options() -> Result<Options, String> {
+    let mut options = Options {
+        data_dir: PathBuf::new(),
+        name: "Generated Fixture".to_string(),
+        authors: 3,
+        paragraphs: 500,
+        emoji_density: 0.02,
+        pattern: Pattern::Interleaved,
+        seed: 1,
+    };
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let mut value = || {
+            args.next()
+                .ok_or_else(|| format!("Missing value for {arg}"))
+        };
+        match arg.as_str() {
+            "--data-dir" => options.data_dir = PathBuf::from(value()?),
+            "--name" => options.name = value()?,
+            "--authors" => options.authors = value()?.parse().map_err(|_| "Invalid --authors")?,
+            "--paragraphs" => {
+                options.paragraphs = value()?.parse().map_err(|_| "Invalid --paragraphs")?
+            }
+            "--emoji-density" => {
+                options.emoji_density = value()?.parse().map_err(|_| "Invalid --emoji-density")?
+            }
+            "--pattern" => {
+                options.pattern = match value()?.as_str() {
+                    "append" => Pattern::Append,
+                    "interleaved" => Pattern::Interleaved,
+                    "rewrite" => Pattern::Rewrite,
+                    other => return Err(format!("Unknown pattern {other}")),
+                }
+            }
+            // Zero is not a valid xorshift state
+            "--seed" => {
+                options.seed = value()?
+                    .parse::<u64>()
+                    .map_err(|_| "Invalid --seed")?
+                    .max(1)
+            }
+            _ => return Err(format!("Unknown argument {arg}")),
+        }
+    }
+
+    if options.data_dir.as_os_str().is_empty() {
+        return Err("--data-dir is required".to_string());
+    }
+    if options.authors == 0 {
+        return Err("At least one author is required".to_string());
+    }
+
+    Ok(options)
+}
+
+fn paragraph(rng: &mut Rng, emoji_density: f64) -> String {
+    let mut text = match rng.below(10) {
+        0 => "# ".to_string(),
+        1 => "- ".to_string(),
+        _ => String::new(),
+    };
+
+    for index in 0..(5 + rng.below(60)) {
+        if index > 0 {
+            text.push(' ');
+        }
+        if rng.chance(emoji_density) {
+            text.push_str(EMOJIS[rng.below(EMOJIS.len())]);
+        } else {
+            text.push_str(WORDS[rng.below(WORDS.len())]);
+        }
+    }
+    text.push_str("\n\n");
+
+    text
+}
+
+/// Same mapping from public key to Loro peer id as used by `Document`.
+fn peer_id(private_key: &PrivateKey) -> u64 {
+    let mut buf = [0u8; 8];
+    buf.copy_from_slice(&private_key.public_key().as_bytes()[..8]);
+    u64::from_be_bytes(buf)
+}
+
+fn generate(options: &Options) -> anyhow::Result<()> {
+    let mut rng = Rng(options.seed);
+    let authors: Vec<PrivateKey> = (0..options.authors)
+        .map(|_| {
+            let mut bytes = [0u8; 32];
+            for chunk in bytes.chunks_mut(8) {
+                chunk.copy_from_slice(&rng.next().to_be_bytes());
+            }
+            PrivateKey::from_bytes(&bytes)
+        })
+        .collect();
+
+    let doc = LoroDoc::new();
+    let text = doc.get_text(TEXT_CONTAINER_ID);
+
+    for index in 0..options.paragraphs {
+        let author = match options.pattern {
+            Pattern::Append => &authors[index % authors.len()],
+            Pattern::Interleaved | Pattern::Rewrite => &authors[rng.below(authors.len())],
+        };
+        doc.set_peer_id(peer_id(author))?;
+
+        let paragraph = paragraph(&mut rng, options.emoji_density);
+        let len = text.len_unicode();
+        match options.pattern {
+            Pattern::Append => text.insert(len, &paragraph)?,
+            Pattern::Interleaved | Pattern::Rewrite => {
+                text.insert(rng.below(len + 1), &paragraph)?
+            }
+        }
+
+        if matches!(options.pattern, Pattern::Rewrite) && rng.chance(0.3) {
+            let len = text.len_unicode();
+            let start = rng.below(len);
+            text.delete(start, rng.below(40).min(len - start))?;
+        }
+
+        doc.commit();
+    }
+
+    let writer = FixtureWriter::open(&options.data_dir)?;
+    let document_id = writer.create_document(&authors[0], Some(options.name.clone()))?;
+    let snapshot = doc.export(ExportMode::Snapshot)?;
+    for author in &authors {
+        writer.add_snapshot(author, &document_id, &snapshot)?;
+    }
+
+    println!(
+        "Created document {document_id} with {} characters by {} authors",
+        text.len_unicode(),
+        authors.len()
+    );
+
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    let options = match parse_options() {
+        Ok(options) => options,
+        Err(error) => {
+            eprintln!("{error}\n{}", usage());
+            return ExitCode::FAILURE;
+        }
+    };
+
+    if let Err(error) = generate(&options) {
+        eprintln!("Failed to generate fixture: {error}");
+        return ExitCode::FAILURE;
+    }
+
+    ExitCode::SUCCESS
+}
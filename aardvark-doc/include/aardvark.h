@@ -0,0 +1,275 @@
+/* aardvark.h
+ *
+ * Copyright 2025 The Aardvark Developers
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ *
+ * SPDX-License-Identifier: GPL-3.0-or-later
+ */
+
+/* The C ABI of aardvark-doc, implemented in `src/capi.rs`. */
+
+#pragma once
+
+#include <gio/gio.h>
+
+G_BEGIN_DECLS
+
+#define AARDVARK_ERROR (aardvark_error_quark ())
+
+/**
+ * AardvarkError:
+ * @AARDVARK_ERROR_FAILED: The operation failed, see the message.
+ */
+typedef enum {
+  AARDVARK_ERROR_FAILED,
+} AardvarkError;
+
+GQuark aardvark_error_quark (void);
+
+/* Boxed types */
+
+typedef struct _AardvarkAnchor AardvarkAnchor;
+typedef struct _AardvarkDocumentId AardvarkDocumentId;
+typedef struct _AardvarkSyncProgress AardvarkSyncProgress;
+typedef struct _AardvarkFrontmatter AardvarkFrontmatter;
+typedef struct _AardvarkPrivateKey AardvarkPrivateKey;
+typedef struct _AardvarkPublicKey AardvarkPublicKey;
+
+GType aardvark_anchor_get_type (void);
+GType aardvark_document_id_get_type (void);
+GType aardvark_sync_progress_get_type (void);
+GType aardvark_frontmatter_get_type (void);
+GType aardvark_private_key_get_type (void);
+GType aardvark_public_key_get_type (void);
+
+/* Objects, their state is exposed as properties */
+
+#define AARDVARK_TYPE_AUTHOR (aardvark_author_get_type ())
+G_DECLARE_FINAL_TYPE (AardvarkAuthor, aardvark_author, AARDVARK, AUTHOR, GObject)
+
+/**
+ * AardvarkAuthors:
+ *
+ * A #GListModel of #AardvarkAuthor.
+ */
+#define AARDVARK_TYPE_AUTHORS (aardvark_authors_get_type ())
+G_DECLARE_FINAL_TYPE (AardvarkAuthors, aardvark_authors, AARDVARK, AUTHORS, GObject)
+
+/**
+ * AardvarkSortedAuthors:
+ *
+ * A #GListModel of #AardvarkAuthor, sorted by the `order` property.
+ */
+#define AARDVARK_TYPE_SORTED_AUTHORS (aardvark_sorted_authors_get_type ())
+G_DECLARE_FINAL_TYPE (AardvarkSortedAuthors, aardvark_sorted_authors, AARDVARK, SORTED_AUTHORS, GObject)
+
+/**
+ * AardvarkPeers:
+ *
+ * A #GListModel of the #AardvarkAuthor connected right now.
+ */
+#define AARDVARK_TYPE_PEERS (aardvark_peers_get_type ())
+G_DECLARE_FINAL_TYPE (AardvarkPeers, aardvark_peers, AARDVARK, PEERS, GObject)
+
+#define AARDVARK_TYPE_BOOKMARK (aardvark_bookmark_get_type ())
+G_DECLARE_FINAL_TYPE (AardvarkBookmark, aardvark_bookmark, AARDVARK, BOOKMARK, GObject)
+
+/**
+ * AardvarkBookmarks:
+ *
+ * A #GListModel of #AardvarkBookmark.
+ */
+#define AARDVARK_TYPE_BOOKMARKS (aardvark_bookmarks_get_type ())
+G_DECLARE_FINAL_TYPE (AardvarkBookmarks, aardvark_bookmarks, AARDVARK, BOOKMARKS, GObject)
+
+#define AARDVARK_TYPE_CHECKPOINT (aardvark_checkpoint_get_type ())
+G_DECLARE_FINAL_TYPE (AardvarkCheckpoint, aardvark_checkpoint, AARDVARK, CHECKPOINT, GObject)
+
+/**
+ * AardvarkCheckpoints:
+ *
+ * A #GListModel of #AardvarkCheckpoint.
+ */
+#define AARDVARK_TYPE_CHECKPOINTS (aardvark_checkpoints_get_type ())
+G_DECLARE_FINAL_TYPE (AardvarkCheckpoints, aardvark_checkpoints, AARDVARK, CHECKPOINTS, GObject)
+
+#define AARDVARK_TYPE_PROFILER (aardvark_profiler_get_type ())
+G_DECLARE_FINAL_TYPE (AardvarkProfiler, aardvark_profiler, AARDVARK, PROFILER, GObject)
+
+#define AARDVARK_TYPE_DOCUMENT (aardvark_document_get_type ())
+G_DECLARE_FINAL_TYPE (AardvarkDocument, aardvark_document, AARDVARK, DOCUMENT, GObject)
+
+/**
+ * AardvarkDocuments:
+ *
+ * A #GListModel of #AardvarkDocument.
+ */
+#define AARDVARK_TYPE_DOCUMENTS (aardvark_documents_get_type ())
+G_DECLARE_FINAL_TYPE (AardvarkDocuments, aardvark_documents, AARDVARK, DOCUMENTS, GObject)
+
+#define AARDVARK_TYPE_SERVICE (aardvark_service_get_type ())
+G_DECLARE_FINAL_TYPE (AardvarkService, aardvark_service, AARDVARK, SERVICE, GObject)
+
+/* Identity */
+
+/**
+ * aardvark_private_key_new:
+ *
+ * Returns: (transfer full): A new random private key.
+ */
+AardvarkPrivateKey *aardvark_private_key_new (void);
+
+/**
+ * aardvark_private_key_get_public_key:
+ * @private_key: A private key.
+ *
+ * Returns: (transfer full): The public key of @private_key.
+ */
+AardvarkPublicKey *aardvark_private_key_get_public_key (const AardvarkPrivateKey *private_key);
+
+/**
+ * aardvark_public_key_to_string:
+ * @public_key: A public key.
+ *
+ * Returns: (transfer full): The hex encoded key.
+ */
+char *aardvark_public_key_to_string (const AardvarkPublicKey *public_key);
+
+/* Documents */
+
+/**
+ * aardvark_document_id_new_from_string:
+ * @value: A hex encoded document id.
+ *
+ * Returns: (transfer full) (nullable): The id, or %NULL if @value isn't one.
+ */
+AardvarkDocumentId *aardvark_document_id_new_from_string (const char *value);
+
+/**
+ * aardvark_document_id_to_string:
+ * @id: A document id.
+ *
+ * Returns: (transfer full): The hex encoded id.
+ */
+char *aardvark_document_id_to_string (const AardvarkDocumentId *id);
+
+/**
+ * aardvark_service_new:
+ * @private_key: The identity of this device.
+ * @data_dir: Where documents are stored.
+ *
+ * Returns: (transfer full): A new service, start it with aardvark_service_startup().
+ */
+AardvarkService *aardvark_service_new (const AardvarkPrivateKey *private_key,
+                                       GFile                    *data_dir);
+
+/**
+ * aardvark_service_startup:
+ * @service: A service.
+ *
+ * Open the store and connect to the network, blocks until the node is running.
+ */
+void aardvark_service_startup (AardvarkService *service);
+
+/**
+ * aardvark_service_shutdown:
+ * @service: A service.
+ */
+void aardvark_service_shutdown (AardvarkService *service);
+
+/**
+ * aardvark_service_get_document:
+ * @service: A service.
+ * @id: The id of a document.
+ *
+ * Returns: (transfer full): The document with @id, created if it isn't known yet.
+ */
+AardvarkDocument *aardvark_service_get_document (AardvarkService          *service,
+                                                 const AardvarkDocumentId *id);
+
+/**
+ * aardvark_document_new:
+ * @service: A service.
+ * @id: (nullable): The id of an existing document, or %NULL to create a new one.
+ *
+ * Returns: (transfer full): The document.
+ */
+AardvarkDocument *aardvark_document_new (AardvarkService          *service,
+                                         const AardvarkDocumentId *id);
+
+/**
+ * aardvark_document_insert_text:
+ * @document: A document.
+ * @position: Position in characters.
+ * @text: The text to insert.
+ * @error: Return location for an error.
+ *
+ * Returns: Whether the text was inserted.
+ */
+gboolean aardvark_document_insert_text (AardvarkDocument  *document,
+                                        int                position,
+                                        const char        *text,
+                                        GError           **error);
+
+/**
+ * aardvark_document_delete_range:
+ * @document: A document.
+ * @start: Start position in characters.
+ * @end: End position in characters.
+ * @error: Return location for an error.
+ *
+ * Returns: Whether the text was deleted.
+ */
+gboolean aardvark_document_delete_range (AardvarkDocument  *document,
+                                         int                start,
+                                         int                end,
+                                         GError           **error);
+
+/**
+ * aardvark_document_anchor_at:
+ * @document: A document.
+ * @position: Position in characters.
+ *
+ * Returns: (transfer full) (nullable): An anchor in front of the character at @position, it stays
+ *   attached to it when text before it is edited, or %NULL if there's no text at @position.
+ */
+AardvarkAnchor *aardvark_document_anchor_at (AardvarkDocument *document,
+                                             int               position);
+
+/**
+ * aardvark_document_resolve:
+ * @document: A document.
+ * @anchor: An anchor of @document.
+ *
+ * Returns: The current position of @anchor in characters, or -1 if it isn't part of @document.
+ */
+int aardvark_document_resolve (AardvarkDocument     *document,
+                               const AardvarkAnchor *anchor);
+
+/**
+ * aardvark_document_add_view:
+ * @document: A document.
+ *
+ * Register a view showing @document, it's synced while at least one view is registered.
+ */
+void aardvark_document_add_view (AardvarkDocument *document);
+
+/**
+ * aardvark_document_remove_view:
+ * @document: A document.
+ */
+void aardvark_document_remove_view (AardvarkDocument *document);
+
+G_END_DECLS
@@ -1,10 +1,15 @@
 use std::sync::Mutex;
-use std::{cell::Cell, sync::OnceLock};
+use std::{
+    cell::{Cell, RefCell},
+    sync::OnceLock,
+};
 
 use glib::Properties;
 use glib::prelude::*;
 use glib::subclass::prelude::*;
 
+use crate::colors::preferred_color;
+use crate::identicon;
 use crate::identity::PublicKey;
 
 pub const COLORS: [(&str, &str); 15] = [
@@ -78,19 +83,56 @@ mod imp {
         #[property(name = "name", get = Self::name, type = String)]
         #[property(name = "emoji", get = Self::emoji, type = String)]
         #[property(name = "color", get = Self::color, type = String)]
+        #[property(name = "avatar", get = Self::avatar, type = gdk::Paintable)]
         #[property(get, set, construct_only, type = PublicKey)]
         public_key: OnceLock<PublicKey>,
+        /// Index in `COLORS` assigned within the document, see [`crate::colors`].
+        pub color: Cell<Option<usize>>,
+        /// Rendered on first use and again when the color changes.
+        pub avatar: RefCell<Option<gdk::Paintable>>,
+        /// When we last received something from this author or they disconnected.
         #[property(get, set, construct_only)]
         pub last_seen: Mutex<Option<glib::DateTime>>,
-        #[property(get, default = true)]
+        /// Connected to us right now, see [`crate::peers::Peers`].
+        #[property(get)]
         pub is_online: Cell<bool>,
+        /// Changes of this author are part of the document.
+        #[property(get)]
+        pub has_contributed: Cell<bool>,
         #[property(get)]
         pub is_this_device: Cell<bool>,
+        /// Set when data of this author couldn't be processed, most likely because they use a
+        /// different version of Aardvark.
+        #[property(get, nullable)]
+        pub incompatible_reason: Mutex<Option<String>>,
+        /// Operations and messages of blocked authors are dropped, see [`crate::service::Service::set_author_blocked`].
+        #[property(get)]
+        pub blocked: Cell<bool>,
+        /// Where in the text this author changed something last, `-1` if we don't know.
+        #[property(get = Self::last_edit_position, type = i32, minimum = -1)]
+        pub last_edit_position: Cell<Option<u32>>,
+        /// Changes of the document this author hasn't seen yet, according to their last read
+        /// receipt, see [`crate::receipts`].
+        #[property(get)]
+        pub changes_behind: Cell<u32>,
+        /// Bytes per second we received from this author recently, updated by
+        /// [`crate::service::Service::bandwidth`].
+        #[property(get)]
+        pub download_rate: Cell<u64>,
+        /// The author told us they're away from their device, see [`crate::presence`].
+        #[property(get)]
+        pub away: Cell<bool>,
+        /// The author uses a throwaway identity, see [`crate::anonymity`].
+        #[property(get)]
+        pub anonymous: Cell<bool>,
+        /// What the user renamed the author to, it's shown as their name on this device only.
+        #[property(get, nullable)]
+        pub alias: Mutex<Option<String>>,
     }
 
     #[glib::object_subclass]
     impl ObjectSubclass for Author {
-        const NAME: &'static str = "Author";
+        const NAME: &'static str = "AardvarkAuthor";
         type Type = super::Author;
     }
 
@@ -99,11 +141,16 @@ mod imp {
 
     impl Author {
         fn name(&self) -> String {
-            let bytes = self.public_key.get().unwrap().as_bytes();
-            let selector_color = bytes[..(bytes.len() / 2)]
-                .iter()
-                .fold(0u8, |acc, b| acc ^ b) as usize
-                % COLORS.len();
+            if let Some(alias) = self.alias.lock().unwrap().clone() {
+                return alias;
+            }
+            if self.anonymous.get() {
+                return format!("Anonymous {}", self.emoji());
+            }
+
+            let public_key = self.public_key.get().unwrap();
+            let selector_color = preferred_color(public_key);
+            let bytes = public_key.as_bytes();
             let selector_emoji = bytes[(bytes.len() / 2)..]
                 .iter()
                 .fold(0u8, |acc, b| acc ^ b) as usize
@@ -120,13 +167,35 @@ mod imp {
             EMOJIS[selector_emoji].0.to_string()
         }
 
+        /// The color assigned within the document, the one in the name until we know the
+        /// other authors.
         fn color(&self) -> String {
-            let bytes = self.public_key.get().unwrap().as_bytes();
-            let selector_color = bytes[..(bytes.len() / 2)]
-                .iter()
-                .fold(0u8, |acc, b| acc ^ b) as usize
-                % COLORS.len();
-            COLORS[selector_color].0.to_string()
+            COLORS[self.color_index()].0.to_string()
+        }
+
+        fn color_index(&self) -> usize {
+            self.color
+                .get()
+                .unwrap_or_else(|| preferred_color(self.public_key.get().unwrap()))
+        }
+
+        /// Identicon of the public key in the author's color, see [`crate::identicon`].
+        fn avatar(&self) -> gdk::Paintable {
+            self.avatar
+                .borrow_mut()
+                .get_or_insert_with(|| {
+                    identicon::paintable(
+                        self.public_key.get().unwrap(),
+                        COLORS[self.color_index()].1,
+                    )
+                })
+                .clone()
+        }
+
+        fn last_edit_position(&self) -> i32 {
+            self.last_edit_position
+                .get()
+                .map_or(-1, |position| position as i32)
         }
     }
 }
@@ -136,19 +205,20 @@ glib::wrapper! {
 }
 impl Author {
     pub fn new(public_key: &PublicKey) -> Self {
-        let obj: Self = glib::Object::builder()
+        glib::Object::builder()
             .property("public-key", public_key)
-            .build();
-
-        obj.imp().is_online.set(true);
-        obj
+            .build()
     }
 
+    /// An author loaded from the store, they contributed to the document.
     pub(crate) fn with_state(public_key: &PublicKey, last_seen: Option<&glib::DateTime>) -> Self {
-        glib::Object::builder()
+        let obj: Self = glib::Object::builder()
             .property("public-key", public_key)
             .property("last-seen", last_seen)
-            .build()
+            .build();
+
+        obj.imp().has_contributed.set(true);
+        obj
     }
 
     pub fn for_this_device(public_key: &PublicKey) -> Self {
@@ -156,6 +226,7 @@ impl Author {
 
         obj.imp().is_this_device.set(true);
         obj.imp().is_online.set(true);
+        obj.imp().has_contributed.set(true);
         obj
     }
 
@@ -163,9 +234,88 @@ impl Author {
         let was_online = self.imp().is_online.get();
         self.imp().is_online.set(is_online);
         if !is_online && was_online {
-            *self.imp().last_seen.lock().unwrap() = glib::DateTime::now_local().ok();
-            self.notify_last_seen();
+            self.set_seen_now();
+            // They tell us again when they're back
+            self.set_away(false);
         }
         self.notify_is_online();
     }
+
+    /// We just received something from this author.
+    pub(crate) fn set_seen_now(&self) {
+        *self.imp().last_seen.lock().unwrap() = glib::DateTime::now_local().ok();
+        self.notify_last_seen();
+    }
+
+    pub(crate) fn set_has_contributed(&self) {
+        if !self.imp().has_contributed.replace(true) {
+            self.notify_has_contributed();
+        }
+    }
+
+    pub(crate) fn set_last_edit_position(&self, position: Option<u32>) {
+        if self.imp().last_edit_position.replace(position) != position {
+            self.notify_last_edit_position();
+        }
+    }
+
+    pub(crate) fn set_changes_behind(&self, changes_behind: u32) {
+        if self.imp().changes_behind.replace(changes_behind) != changes_behind {
+            self.notify_changes_behind();
+        }
+    }
+
+    pub(crate) fn set_download_rate(&self, download_rate: u64) {
+        if self.imp().download_rate.replace(download_rate) != download_rate {
+            self.notify_download_rate();
+        }
+    }
+
+    pub(crate) fn set_away(&self, away: bool) {
+        if self.imp().away.replace(away) != away {
+            self.notify_away();
+        }
+    }
+
+    pub(crate) fn set_anonymous(&self, anonymous: bool) {
+        if self.imp().anonymous.replace(anonymous) != anonymous {
+            self.notify_anonymous();
+            self.notify_name();
+        }
+    }
+
+    pub(crate) fn set_alias(&self, alias: Option<String>) {
+        let mut current = self.imp().alias.lock().unwrap();
+        if *current == alias {
+            return;
+        }
+        *current = alias;
+        drop(current);
+        self.notify_alias();
+        self.notify_name();
+    }
+
+    pub(crate) fn set_color(&self, color: usize) {
+        if self.imp().color.replace(Some(color)) != Some(color) {
+            self.imp().avatar.take();
+            self.notify_color();
+            self.notify_avatar();
+        }
+    }
+
+    pub(crate) fn set_blocked(&self, blocked: bool) {
+        if self.imp().blocked.replace(blocked) != blocked {
+            self.notify_blocked();
+        }
+    }
+
+    pub(crate) fn set_incompatible_reason(&self, reason: String) {
+        let mut incompatible_reason = self.imp().incompatible_reason.lock().unwrap();
+        if incompatible_reason.as_ref() == Some(&reason) {
+            return;
+        }
+        *incompatible_reason = Some(reason);
+        drop(incompatible_reason);
+        self.notify_incompatible_reason();
+    }
 }
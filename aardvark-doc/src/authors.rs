@@ -1,3 +1,4 @@
+use std::collections::HashMap;
 use std::sync::Mutex;
 
 use gio::prelude::*;
@@ -5,6 +6,7 @@ use gio::subclass::prelude::ListModelImpl;
 use glib::subclass::prelude::*;
 
 use crate::author::Author;
+use crate::colors::assign_colors;
 use crate::identity::PublicKey;
 
 mod imp {
@@ -13,11 +15,13 @@ mod imp {
     #[derive(Default)]
     pub struct Authors {
         pub list: Mutex<Vec<Author>>,
+        /// Names the user gave authors, also those who aren't in the list yet.
+        pub aliases: Mutex<HashMap<p2panda_core::PublicKey, String>>,
     }
 
     #[glib::object_subclass]
     impl ObjectSubclass for Authors {
-        const NAME: &'static str = "Authors";
+        const NAME: &'static str = "AardvarkAuthors";
         type Type = super::Authors;
         type Interfaces = (gio::ListModel,);
     }
@@ -49,9 +53,6 @@ glib::wrapper! {
     @implements gio::ListModel;
 }
 
-unsafe impl Send for Authors {}
-unsafe impl Sync for Authors {}
-
 impl Default for Authors {
     fn default() -> Self {
         Self::new()
@@ -66,9 +67,19 @@ impl Authors {
     pub(crate) fn from_vec(authors: Vec<Author>) -> Self {
         let obj: Self = glib::Object::new();
         *obj.imp().list.lock().unwrap() = authors;
+        obj.update_colors();
         obj
     }
 
+    /// Assign the authors distinguishable colors, after authors were added.
+    fn update_colors(&self) {
+        let authors = self.imp().list.lock().unwrap().clone();
+        let public_keys: Vec<PublicKey> = authors.iter().map(Author::public_key).collect();
+        for (author, color) in authors.iter().zip(assign_colors(&public_keys)) {
+            author.set_color(color);
+        }
+    }
+
     pub(crate) fn add_this_device(&self, author_key: PublicKey) {
         let mut list = self.imp().list.lock().unwrap();
         let pos = list.len() as u32;
@@ -76,6 +87,7 @@ impl Authors {
         let author = Author::for_this_device(&author_key);
         list.push(author);
         drop(list);
+        self.update_colors();
         self.items_changed(pos, 0, 1);
     }
 
@@ -86,28 +98,73 @@ impl Authors {
             let pos = list.len() as u32;
 
             let author = Author::new(&author_key);
+            author.set_alias(self.alias(&author_key));
 
             list.push(author);
             drop(list);
 
+            self.update_colors();
             self.items_changed(pos, 0, 1);
         }
     }
 
-    pub(crate) fn add_or_update(&self, author_key: PublicKey, is_online: bool) {
-        let mut list = self.imp().list.lock().unwrap();
+    fn alias(&self, author_key: &PublicKey) -> Option<String> {
+        self.imp()
+            .aliases
+            .lock()
+            .unwrap()
+            .get(&author_key.0)
+            .cloned()
+    }
 
-        if let Some(author) = list.iter().find(|author| author.public_key() == author_key) {
-            author.set_is_online(is_online);
-        } else {
-            let pos = list.len() as u32;
+    /// Show authors with the names in `aliases` instead of their regular ones.
+    pub(crate) fn set_aliases(&self, aliases: HashMap<p2panda_core::PublicKey, String>) {
+        *self.imp().aliases.lock().unwrap() = aliases;
+        let authors = self.imp().list.lock().unwrap().clone();
+        for author in authors {
+            author.set_alias(self.alias(&author.public_key()));
+        }
+    }
 
-            let author = Author::new(&author_key);
+    /// Show an author as `alias`, or with their regular name if it's `None`.
+    pub(crate) fn set_alias(&self, author_key: &PublicKey, alias: Option<String>) {
+        let mut aliases = self.imp().aliases.lock().unwrap();
+        match alias.clone() {
+            Some(alias) => aliases.insert(author_key.0, alias),
+            None => aliases.remove(&author_key.0),
+        };
+        drop(aliases);
+
+        if let Some(author) = self.by_public_key(author_key) {
+            author.set_alias(alias);
+        }
+    }
 
-            list.push(author);
-            drop(list);
+    pub fn by_public_key(&self, author_key: &PublicKey) -> Option<Author> {
+        self.imp()
+            .list
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|author| &author.public_key() == author_key)
+            .cloned()
+    }
 
-            self.items_changed(pos, 0, 1);
+    /// Mark an author as incompatible, they are added to the list if they are not known yet.
+    pub(crate) fn set_incompatible(&self, author_key: PublicKey, reason: String) {
+        self.ensure_author(author_key.clone());
+
+        if let Some(author) = self.by_public_key(&author_key) {
+            author.set_incompatible_reason(reason);
+        }
+    }
+
+    /// Mark an author as contributor, they are added to the list if they are not known yet.
+    pub(crate) fn set_contributed(&self, author_key: PublicKey) {
+        self.ensure_author(author_key.clone());
+
+        if let Some(author) = self.by_public_key(&author_key) {
+            author.set_has_contributed();
         }
     }
 }
@@ -0,0 +1,165 @@
+//! Named places in a document to jump back to.
+//!
+//! Bookmarks are shared with all authors. They are stored next to the text CRDT as anchors, so a
+//! bookmark stays at the same place in the text when it's edited, even concurrently. See
+//! [`crate::document::Document::add_bookmark`].
+use std::cell::{Cell, OnceCell, RefCell};
+
+use gio::prelude::*;
+use gio::subclass::prelude::ListModelImpl;
+use glib::Properties;
+use glib::subclass::prelude::*;
+
+mod imp {
+    use super::*;
+
+    #[derive(Debug, Default, Properties)]
+    #[properties(wrapper_type = super::Bookmark)]
+    pub struct Bookmark {
+        #[property(get, construct_only)]
+        id: OnceCell<String>,
+        #[property(get)]
+        pub name: RefCell<String>,
+        /// Position of the bookmark in the text, in characters.
+        #[property(get)]
+        pub position: Cell<i32>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for Bookmark {
+        const NAME: &'static str = "AardvarkBookmark";
+        type Type = super::Bookmark;
+    }
+
+    #[glib::derived_properties]
+    impl ObjectImpl for Bookmark {}
+
+    #[derive(Default)]
+    pub struct Bookmarks {
+        pub list: RefCell<Vec<super::Bookmark>>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for Bookmarks {
+        const NAME: &'static str = "AardvarkBookmarks";
+        type Type = super::Bookmarks;
+        type Interfaces = (gio::ListModel,);
+    }
+
+    impl ObjectImpl for Bookmarks {}
+
+    impl ListModelImpl for Bookmarks {
+        fn item_type(&self) -> glib::Type {
+            super::Bookmark::static_type()
+        }
+
+        fn n_items(&self) -> u32 {
+            self.list.borrow().len() as u32
+        }
+
+        fn item(&self, index: u32) -> Option<glib::Object> {
+            self.list
+                .borrow()
+                .get(index as usize)
+                .cloned()
+                .map(Cast::upcast)
+        }
+    }
+}
+
+glib::wrapper! {
+    pub struct Bookmark(ObjectSubclass<imp::Bookmark>);
+}
+
+impl Bookmark {
+    fn new(id: &str) -> Self {
+        glib::Object::builder().property("id", id).build()
+    }
+
+    fn set_name(&self, name: &str) {
+        if *self.imp().name.borrow() != name {
+            self.imp().name.replace(name.to_owned());
+            self.notify_name();
+        }
+    }
+
+    fn set_position(&self, position: i32) {
+        if self.imp().position.replace(position) != position {
+            self.notify_position();
+        }
+    }
+}
+
+glib::wrapper! {
+    /// Bookmarks of a document, in the order they appear in the text.
+    pub struct Bookmarks(ObjectSubclass<imp::Bookmarks>)
+    @implements gio::ListModel;
+}
+
+impl Default for Bookmarks {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Bookmarks {
+    pub fn new() -> Self {
+        glib::Object::new()
+    }
+
+    pub fn by_id(&self, id: &str) -> Option<Bookmark> {
+        self.imp()
+            .list
+            .borrow()
+            .iter()
+            .find(|bookmark| bookmark.id() == id)
+            .cloned()
+    }
+
+    /// The first bookmark after `position`, wrapping around at the end of the text.
+    pub fn next(&self, position: i32) -> Option<Bookmark> {
+        let list = self.imp().list.borrow();
+        list.iter()
+            .find(|bookmark| bookmark.position() > position)
+            .or_else(|| list.first())
+            .cloned()
+    }
+
+    /// The last bookmark before `position`, wrapping around at the start of the text.
+    pub fn previous(&self, position: i32) -> Option<Bookmark> {
+        let list = self.imp().list.borrow();
+        list.iter()
+            .rev()
+            .find(|bookmark| bookmark.position() < position)
+            .or_else(|| list.last())
+            .cloned()
+    }
+
+    /// Replace the bookmarks by `bookmarks`, given as id, name and position.
+    ///
+    /// Bookmarks which are still there keep their object, so views only need to follow their
+    /// properties.
+    pub(crate) fn update(&self, mut bookmarks: Vec<(String, String, i32)>) {
+        bookmarks.sort_by(|(a_id, _, a), (b_id, _, b)| a.cmp(b).then_with(|| a_id.cmp(b_id)));
+
+        let list: Vec<Bookmark> = bookmarks
+            .iter()
+            .map(|(id, name, position)| {
+                let bookmark = self.by_id(id).unwrap_or_else(|| Bookmark::new(id));
+                bookmark.set_name(name);
+                bookmark.set_position(*position);
+                bookmark
+            })
+            .collect();
+
+        let removed = {
+            let mut old = self.imp().list.borrow_mut();
+            if *old == list {
+                return;
+            }
+            std::mem::replace(&mut *old, list).len() as u32
+        };
+        let added = self.imp().list.borrow().len() as u32;
+        self.items_changed(0, removed, added);
+    }
+}
@@ -0,0 +1,236 @@
+//! C ABI of the document layer, for GObject introspection.
+//!
+//! Built as shared library with `cargo rustc -p aardvark-doc --features capi --crate-type cdylib`,
+//! `include/aardvark.h` declares what's exported here and `meson.build` generates the
+//! `Aardvark-0.1` typelib from both. Python or JavaScript apps then use the types like any other
+//! GObject library: state is read through properties and their notifications, lists are
+//! `GListModel`s.
+//!
+//! Only what can't be done through properties is exported as function. Async methods of the Rust
+//! API aren't exported yet, they need to be wrapped as `GAsyncReadyCallback` style functions.
+
+// Like for any C library, callers pass valid pointers as documented in the header
+#![allow(clippy::missing_safety_doc)]
+
+use std::ffi::{CStr, c_char};
+use std::ptr;
+use std::str::FromStr;
+
+use glib::ffi::{GError, GType, gboolean};
+use glib::translate::*;
+
+use crate::author::Author;
+use crate::authors::Authors;
+use crate::bookmarks::{Bookmark, Bookmarks};
+use crate::checkpoints::{Checkpoint, Checkpoints};
+use crate::document::{Anchor, Document, DocumentId, SyncProgress};
+use crate::documents::Documents;
+use crate::frontmatter::Frontmatter;
+use crate::identity::{PrivateKey, PublicKey};
+use crate::peers::Peers;
+use crate::profiler::Profiler;
+use crate::service::Service;
+use crate::sorted_authors::SortedAuthors;
+
+type ServicePtr = *mut <Service as glib::object::ObjectType>::GlibType;
+type DocumentPtr = *mut <Document as glib::object::ObjectType>::GlibType;
+
+/// Errors of functions taking a `GError`, the message tells what went wrong.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, glib::ErrorDomain)]
+#[error_domain(name = "aardvark-error-quark")]
+pub enum Error {
+    Failed,
+}
+
+/// Report `result` through `error`, returns whether it succeeded.
+unsafe fn propagate<T>(result: anyhow::Result<T>, error: *mut *mut GError) -> gboolean {
+    match result {
+        Ok(_) => glib::ffi::GTRUE,
+        Err(err) => {
+            if !error.is_null() {
+                let err = glib::Error::new(Error::Failed, &err.to_string());
+                unsafe { *error = err.into_glib_ptr() };
+            }
+            glib::ffi::GFALSE
+        }
+    }
+}
+
+/// Boxed types defined in Rust are boxes, freed with the free function of their type.
+fn into_boxed<T>(value: T) -> *mut T {
+    Box::into_raw(Box::new(value))
+}
+
+macro_rules! get_type {
+    ($($name:ident => $type:ty),* $(,)?) => {
+        $(
+            #[unsafe(no_mangle)]
+            pub extern "C" fn $name() -> GType {
+                <$type as glib::prelude::StaticType>::static_type().into_glib()
+            }
+        )*
+    };
+}
+
+get_type! {
+    aardvark_author_get_type => Author,
+    aardvark_authors_get_type => Authors,
+    aardvark_bookmark_get_type => Bookmark,
+    aardvark_bookmarks_get_type => Bookmarks,
+    aardvark_checkpoint_get_type => Checkpoint,
+    aardvark_checkpoints_get_type => Checkpoints,
+    aardvark_document_get_type => Document,
+    aardvark_documents_get_type => Documents,
+    aardvark_peers_get_type => Peers,
+    aardvark_profiler_get_type => Profiler,
+    aardvark_service_get_type => Service,
+    aardvark_sorted_authors_get_type => SortedAuthors,
+    aardvark_anchor_get_type => Anchor,
+    aardvark_document_id_get_type => DocumentId,
+    aardvark_sync_progress_get_type => SyncProgress,
+    aardvark_frontmatter_get_type => Frontmatter,
+    aardvark_private_key_get_type => PrivateKey,
+    aardvark_public_key_get_type => PublicKey,
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn aardvark_error_quark() -> glib::ffi::GQuark {
+    <Error as glib::error::ErrorDomain>::domain().into_glib()
+}
+
+#[unsafe(no_mangle)]
+pub extern "C" fn aardvark_private_key_new() -> *mut PrivateKey {
+    into_boxed(PrivateKey::new())
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn aardvark_private_key_get_public_key(
+    private_key: *const PrivateKey,
+) -> *mut PublicKey {
+    let private_key = unsafe { &*private_key };
+    into_boxed(private_key.public_key())
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn aardvark_public_key_to_string(
+    public_key: *const PublicKey,
+) -> *mut c_char {
+    let public_key = unsafe { &*public_key };
+    public_key.to_string().to_glib_full()
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn aardvark_document_id_new_from_string(
+    value: *const c_char,
+) -> *mut DocumentId {
+    let value = unsafe { CStr::from_ptr(value) };
+    value
+        .to_str()
+        .ok()
+        .and_then(|value| DocumentId::from_str(value).ok())
+        .map_or(ptr::null_mut(), into_boxed)
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn aardvark_document_id_to_string(id: *const DocumentId) -> *mut c_char {
+    let id = unsafe { &*id };
+    id.to_string().to_glib_full()
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn aardvark_service_new(
+    private_key: *const PrivateKey,
+    data_dir: *mut gio::ffi::GFile,
+) -> ServicePtr {
+    let private_key = unsafe { &*private_key };
+    let data_dir: gio::File = unsafe { from_glib_none(data_dir) };
+    Service::new(private_key, &data_dir).into_glib_ptr()
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn aardvark_service_startup(service: ServicePtr) {
+    let service: Borrowed<Service> = unsafe { from_glib_borrow(service) };
+    service.startup();
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn aardvark_service_shutdown(service: ServicePtr) {
+    let service: Borrowed<Service> = unsafe { from_glib_borrow(service) };
+    service.shutdown();
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn aardvark_service_get_document(
+    service: ServicePtr,
+    id: *const DocumentId,
+) -> DocumentPtr {
+    let service: Borrowed<Service> = unsafe { from_glib_borrow(service) };
+    let id = unsafe { &*id };
+    service.document(id).into_glib_ptr()
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn aardvark_document_new(
+    service: ServicePtr,
+    id: *const DocumentId,
+) -> DocumentPtr {
+    let service: Borrowed<Service> = unsafe { from_glib_borrow(service) };
+    let id = unsafe { id.as_ref() };
+    Document::new(&service, id).into_glib_ptr()
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn aardvark_document_insert_text(
+    document: DocumentPtr,
+    position: i32,
+    text: *const c_char,
+    error: *mut *mut GError,
+) -> gboolean {
+    let document: Borrowed<Document> = unsafe { from_glib_borrow(document) };
+    let text = unsafe { CStr::from_ptr(text) }.to_string_lossy();
+    unsafe { propagate(document.insert_text(position, &text), error) }
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn aardvark_document_delete_range(
+    document: DocumentPtr,
+    start: i32,
+    end: i32,
+    error: *mut *mut GError,
+) -> gboolean {
+    let document: Borrowed<Document> = unsafe { from_glib_borrow(document) };
+    unsafe { propagate(document.delete_range(start, end), error) }
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn aardvark_document_anchor_at(
+    document: DocumentPtr,
+    position: i32,
+) -> *mut Anchor {
+    let document: Borrowed<Document> = unsafe { from_glib_borrow(document) };
+    document
+        .anchor_at(position)
+        .map_or(ptr::null_mut(), into_boxed)
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn aardvark_document_resolve(
+    document: DocumentPtr,
+    anchor: *const Anchor,
+) -> i32 {
+    let document: Borrowed<Document> = unsafe { from_glib_borrow(document) };
+    let anchor = unsafe { &*anchor };
+    document.resolve(anchor).unwrap_or(-1)
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn aardvark_document_add_view(document: DocumentPtr) {
+    let document: Borrowed<Document> = unsafe { from_glib_borrow(document) };
+    document.add_view();
+}
+
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn aardvark_document_remove_view(document: DocumentPtr) {
+    let document: Borrowed<Document> = unsafe { from_glib_borrow(document) };
+    document.remove_view();
+}
@@ -0,0 +1,225 @@
+//! Named versions of a document, e.g. "Draft v1".
+//!
+//! Checkpoints are shared with all authors. They are stored next to the text CRDT with the
+//! version of the document at the time they were created, so the text at that version can be
+//! restored and compared with the current text. See
+//! [`crate::document::Document::create_checkpoint`].
+use std::cell::{OnceCell, RefCell};
+
+use gio::prelude::*;
+use gio::subclass::prelude::ListModelImpl;
+use glib::Properties;
+use glib::subclass::prelude::*;
+
+mod imp {
+    use super::*;
+
+    #[derive(Debug, Default, Properties)]
+    #[properties(wrapper_type = super::Checkpoint)]
+    pub struct Checkpoint {
+        #[property(get, construct_only)]
+        id: OnceCell<String>,
+        #[property(get)]
+        pub name: RefCell<String>,
+        #[property(get, nullable)]
+        pub created_at: RefCell<Option<glib::DateTime>>,
+        /// Encoded frontiers of the document when the checkpoint was created.
+        pub version: RefCell<Vec<u8>>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for Checkpoint {
+        const NAME: &'static str = "AardvarkCheckpoint";
+        type Type = super::Checkpoint;
+    }
+
+    #[glib::derived_properties]
+    impl ObjectImpl for Checkpoint {}
+
+    #[derive(Default)]
+    pub struct Checkpoints {
+        pub list: RefCell<Vec<super::Checkpoint>>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for Checkpoints {
+        const NAME: &'static str = "AardvarkCheckpoints";
+        type Type = super::Checkpoints;
+        type Interfaces = (gio::ListModel,);
+    }
+
+    impl ObjectImpl for Checkpoints {}
+
+    impl ListModelImpl for Checkpoints {
+        fn item_type(&self) -> glib::Type {
+            super::Checkpoint::static_type()
+        }
+
+        fn n_items(&self) -> u32 {
+            self.list.borrow().len() as u32
+        }
+
+        fn item(&self, index: u32) -> Option<glib::Object> {
+            self.list
+                .borrow()
+                .get(index as usize)
+                .cloned()
+                .map(Cast::upcast)
+        }
+    }
+}
+
+glib::wrapper! {
+    pub struct Checkpoint(ObjectSubclass<imp::Checkpoint>);
+}
+
+impl Checkpoint {
+    fn new(id: &str) -> Self {
+        glib::Object::builder().property("id", id).build()
+    }
+
+    fn set_name(&self, name: &str) {
+        if *self.imp().name.borrow() != name {
+            self.imp().name.replace(name.to_owned());
+            self.notify_name();
+        }
+    }
+
+    fn set_created_at(&self, created_at: Option<glib::DateTime>) {
+        if *self.imp().created_at.borrow() != created_at {
+            self.imp().created_at.replace(created_at);
+            self.notify_created_at();
+        }
+    }
+
+    pub(crate) fn version(&self) -> Vec<u8> {
+        self.imp().version.borrow().clone()
+    }
+}
+
+glib::wrapper! {
+    /// Checkpoints of a document, newest first.
+    pub struct Checkpoints(ObjectSubclass<imp::Checkpoints>)
+    @implements gio::ListModel;
+}
+
+impl Default for Checkpoints {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Checkpoints {
+    pub fn new() -> Self {
+        glib::Object::new()
+    }
+
+    pub fn by_id(&self, id: &str) -> Option<Checkpoint> {
+        self.imp()
+            .list
+            .borrow()
+            .iter()
+            .find(|checkpoint| checkpoint.id() == id)
+            .cloned()
+    }
+
+    /// Replace the checkpoints by `checkpoints`, given as id, name, creation time (as unix
+    /// timestamp) and encoded version.
+    pub(crate) fn update(&self, mut checkpoints: Vec<(String, String, i64, Vec<u8>)>) {
+        checkpoints
+            .sort_by(|(a_id, _, a, _), (b_id, _, b, _)| b.cmp(a).then_with(|| a_id.cmp(b_id)));
+
+        let list: Vec<Checkpoint> = checkpoints
+            .into_iter()
+            .map(|(id, name, created_at, version)| {
+                let checkpoint = self.by_id(&id).unwrap_or_else(|| Checkpoint::new(&id));
+                checkpoint.set_name(&name);
+                checkpoint.set_created_at(glib::DateTime::from_unix_utc(created_at).ok());
+                checkpoint.imp().version.replace(version);
+                checkpoint
+            })
+            .collect();
+
+        let removed = {
+            let mut old = self.imp().list.borrow_mut();
+            if *old == list {
+                return;
+            }
+            std::mem::replace(&mut *old, list).len() as u32
+        };
+        let added = self.imp().list.borrow().len() as u32;
+        self.items_changed(0, removed, added);
+    }
+}
+
+/// A line of the difference between two texts, see [`diff_lines`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DiffLine {
+    Unchanged(String),
+    Added(String),
+    Removed(String),
+}
+
+/// Compare `old` and `new` line by line, lines which weren't changed are part of the result too.
+pub fn diff_lines(old: &str, new: &str) -> Vec<DiffLine> {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+
+    // Most edits only touch a small part of the text, only compare what's in between the lines
+    // both have in common at the start and at the end
+    let prefix = old
+        .iter()
+        .zip(new.iter())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let old_changed = &old[prefix..old.len() - suffix];
+    let new_changed = &new[prefix..new.len() - suffix];
+
+    // Length of the longest common subsequence of the remaining lines, from each pair of
+    // positions to the end
+    let width = new_changed.len() + 1;
+    let mut common = vec![0usize; (old_changed.len() + 1) * width];
+    for i in (0..old_changed.len()).rev() {
+        for j in (0..new_changed.len()).rev() {
+            common[i * width + j] = if old_changed[i] == new_changed[j] {
+                common[(i + 1) * width + j + 1] + 1
+            } else {
+                common[(i + 1) * width + j].max(common[i * width + j + 1])
+            };
+        }
+    }
+
+    let mut diff: Vec<DiffLine> = old[..prefix]
+        .iter()
+        .map(|line| DiffLine::Unchanged(line.to_string()))
+        .collect();
+    let (mut i, mut j) = (0, 0);
+    while i < old_changed.len() || j < new_changed.len() {
+        if i < old_changed.len() && j < new_changed.len() && old_changed[i] == new_changed[j] {
+            diff.push(DiffLine::Unchanged(old_changed[i].to_string()));
+            i += 1;
+            j += 1;
+        } else if i < old_changed.len()
+            && (j == new_changed.len() || common[(i + 1) * width + j] >= common[i * width + j + 1])
+        {
+            diff.push(DiffLine::Removed(old_changed[i].to_string()));
+            i += 1;
+        } else {
+            diff.push(DiffLine::Added(new_changed[j].to_string()));
+            j += 1;
+        }
+    }
+    diff.extend(
+        old[old.len() - suffix..]
+            .iter()
+            .map(|line| DiffLine::Unchanged(line.to_string())),
+    );
+
+    diff
+}
@@ -0,0 +1,39 @@
+//! Colors of the authors of a document.
+//!
+//! Every author has a preferred color derived from their public key, the one in their name.
+//! Within a document two authors only share a color when there are more authors than colors,
+//! the others move to the next free one. Collisions are resolved in the order of the public keys,
+//! so all peers knowing the same authors assign the same colors.
+
+use crate::author::COLORS;
+use crate::identity::PublicKey;
+
+/// Index in [`COLORS`] of the color `public_key` prefers.
+pub fn preferred_color(public_key: &PublicKey) -> usize {
+    let bytes = public_key.as_bytes();
+    bytes[..(bytes.len() / 2)]
+        .iter()
+        .fold(0u8, |acc, b| acc ^ b) as usize
+        % COLORS.len()
+}
+
+/// Index in [`COLORS`] for each of `public_keys`, in the same order.
+pub fn assign_colors(public_keys: &[PublicKey]) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..public_keys.len()).collect();
+    order.sort_by(|a, b| public_keys[*a].as_bytes().cmp(public_keys[*b].as_bytes()));
+
+    let mut colors = vec![0; public_keys.len()];
+    let mut taken = [0usize; COLORS.len()];
+    for index in order {
+        let preferred = preferred_color(&public_keys[index]);
+        // The least used color, starting at the preferred one
+        let least_used = taken.iter().min().copied().unwrap_or_default();
+        let color = (0..COLORS.len())
+            .map(|offset| (preferred + offset) % COLORS.len())
+            .find(|color| taken[*color] == least_used)
+            .unwrap_or(preferred);
+        taken[color] += 1;
+        colors[index] = color;
+    }
+    colors
+}
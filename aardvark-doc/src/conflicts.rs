@@ -0,0 +1,47 @@
+//! Detect concurrent edits of the same region of the text.
+//!
+//! The CRDT merges concurrent edits without ever failing, but when two authors changed the same
+//! words at the same time the merged text is rarely what either of them meant. We remember where
+//! we edited the text recently and when changes of another author arrive which touch the same
+//! region, but were made without knowing about our edit, the document emits `conflict-detected`.
+use std::time::{Duration, Instant};
+
+use loro::{LoroDoc, VersionVector, cursor::Cursor};
+
+/// How long our own edits are considered for conflicts. Changes made concurrently arrive within
+/// seconds while both authors are online.
+pub(crate) const CONFLICT_WINDOW: Duration = Duration::from_secs(60);
+
+/// A region of the text we changed recently.
+#[derive(Debug)]
+pub(crate) struct LocalEdit {
+    pub start: Cursor,
+    pub end: Cursor,
+    /// Our operation counter right after the edit, anyone who saw the edit has a version at
+    /// least this high for us.
+    pub counter: i32,
+    pub made_at: Instant,
+}
+
+impl LocalEdit {
+    pub fn is_recent(&self) -> bool {
+        self.made_at.elapsed() < CONFLICT_WINDOW
+    }
+
+    /// Whether an author who had seen `version` knew about this edit.
+    pub fn seen_in(&self, version: &VersionVector, peer: u64) -> bool {
+        version.get(&peer).copied().unwrap_or(0) >= self.counter
+    }
+}
+
+/// Version of the document `peer` had seen when they made their latest change.
+pub(crate) fn seen_by(doc: &LoroDoc, peer: u64) -> Option<VersionVector> {
+    let end = doc.oplog_vv().get(&peer).copied()?;
+    let change = doc.get_change(loro::ID::new(peer, end - 1))?;
+    doc.frontiers_to_vv(&change.deps)
+}
+
+/// Whether the regions `a` and `b` overlap or touch, given as start and end in characters.
+pub(crate) fn overlaps(a: (usize, usize), b: (usize, usize)) -> bool {
+    a.0 <= b.1 && b.0 <= a.1
+}
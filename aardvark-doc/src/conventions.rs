@@ -0,0 +1,113 @@
+//! Editing conventions shared by all authors of a document.
+//!
+//! Assists which write markdown, like the meeting notes section, follow the conventions of the
+//! document instead of a personal preference, so the text doesn't flip between styles depending on
+//! who formatted it last.
+
+/// How headings are written.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, glib::Enum)]
+#[enum_type(name = "AardvarkHeadingStyle")]
+pub enum HeadingStyle {
+    /// `## Heading`
+    #[default]
+    Atx,
+    /// The heading underlined with `=` or `-`.
+    ///
+    /// Markdown only has setext headings for the first two levels, deeper ones are written in
+    /// the ATX style.
+    Setext,
+}
+
+impl HeadingStyle {
+    /// Format a heading, including the underline for setext headings.
+    pub fn format(self, level: usize, title: &str) -> String {
+        match (self, level) {
+            (HeadingStyle::Setext, 1) => format!("{title}\n{}", "=".repeat(underline_len(title))),
+            (HeadingStyle::Setext, 2) => format!("{title}\n{}", "-".repeat(underline_len(title))),
+            _ => format!("{} {title}", "#".repeat(level)),
+        }
+    }
+
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            HeadingStyle::Atx => "atx",
+            HeadingStyle::Setext => "setext",
+        }
+    }
+
+    pub(crate) fn from_name(value: &str) -> Self {
+        match value {
+            "setext" => HeadingStyle::Setext,
+            _ => HeadingStyle::Atx,
+        }
+    }
+}
+
+/// Character starting the items of unordered lists.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, glib::Enum)]
+#[enum_type(name = "AardvarkListMarker")]
+pub enum ListMarker {
+    #[default]
+    Dash,
+    Asterisk,
+    Plus,
+}
+
+impl ListMarker {
+    pub fn as_char(self) -> char {
+        match self {
+            ListMarker::Dash => '-',
+            ListMarker::Asterisk => '*',
+            ListMarker::Plus => '+',
+        }
+    }
+
+    /// Format an unordered list item.
+    pub fn format(self, text: &str) -> String {
+        format!("{} {text}", self.as_char())
+    }
+
+    pub(crate) fn from_char(value: char) -> Self {
+        match value {
+            '*' => ListMarker::Asterisk,
+            '+' => ListMarker::Plus,
+            _ => ListMarker::Dash,
+        }
+    }
+}
+
+/// Whether `text` contains a heading of `level` with `title`, in either style.
+///
+/// Authors might have changed the heading style after the heading was written, it's still the
+/// same heading.
+pub fn contains_heading(text: &str, level: usize, title: &str) -> bool {
+    let atx = HeadingStyle::Atx.format(level, title);
+    let underline = match level {
+        1 => Some('='),
+        2 => Some('-'),
+        _ => None,
+    };
+
+    let mut lines = text.lines().map(str::trim_end).peekable();
+    while let Some(line) = lines.next() {
+        if line == atx {
+            return true;
+        }
+        let Some(underline) = underline else {
+            continue;
+        };
+        if line == title
+            && lines
+                .peek()
+                .is_some_and(|next| !next.is_empty() && next.chars().all(|char| char == underline))
+        {
+            return true;
+        }
+    }
+
+    false
+}
+
+fn underline_len(title: &str) -> usize {
+    title.chars().count().max(3)
+}
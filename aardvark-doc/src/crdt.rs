@@ -0,0 +1,353 @@
+//! Text CRDTs behind a common interface, to compare Loro with Automerge.
+//!
+//! [`Document`](crate::document::Document) is built on Loro, its history, cursors and read
+//! receipts use Loro directly. [`CrdtBackend`] covers what every implementation needs to sync
+//! plain text: editing, versions and exchanging updates. Measuring another CRDT, e.g. for the
+//! size of updates or the time to import a snapshot, only needs an implementation of it.
+//!
+//! This is a harness for comparisons, not an abstraction `Document` goes through: swapping the
+//! CRDT of a document would also change its stored operations and break every existing peer.
+//! The `compare_crdts` example types the same text into each [`CrdtKind`], selected at run time
+//! with `--crdt`. Automerge is only built with the `automerge` feature.
+
+use std::fmt;
+use std::str::FromStr;
+
+use anyhow::{Result, anyhow};
+use loro::cursor::{Cursor, Side};
+use loro::{ExportMode, LoroDoc, LoroText, VersionVector};
+
+/// The container of the text, the same one [`Document`](crate::document::Document) uses so
+/// snapshots of documents can be loaded.
+const TEXT_CONTAINER_ID: &str = "document";
+
+/// A replica of a text CRDT. Positions and lengths are counted in Unicode code points.
+pub trait CrdtBackend {
+    fn text(&self) -> String;
+
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn insert(&mut self, index: usize, text: &str) -> Result<()>;
+
+    fn delete(&mut self, index: usize, len: usize) -> Result<()>;
+
+    /// Delete `len` characters at `index` and insert `text` in their place.
+    fn splice(&mut self, index: usize, len: usize, text: &str) -> Result<()> {
+        self.delete(index, len)?;
+        self.insert(index, text)
+    }
+
+    /// An anchor in front of the character at `index`, which stays attached to it through
+    /// edits of any replica. It's encoded, so it can be sent to other replicas.
+    fn anchor_at(&self, index: usize) -> Option<Vec<u8>>;
+
+    /// Current index of an anchor of this or another replica.
+    ///
+    /// If the character of the anchor was deleted, this is where it used to be.
+    fn resolve(&self, anchor: &[u8]) -> Option<usize>;
+
+    /// Finish the current change, edits before it are exported as one.
+    fn commit(&mut self);
+
+    /// The version of this replica, opaque to everyone but the same implementation.
+    fn version(&mut self) -> Vec<u8>;
+
+    /// Changes made after `since`, a [`Self::version`] of another replica or empty for all.
+    fn export_updates(&mut self, since: &[u8]) -> Result<Vec<u8>>;
+
+    /// The whole state, including its history.
+    fn export_snapshot(&mut self) -> Result<Vec<u8>>;
+
+    /// Apply updates or a snapshot of another replica.
+    fn import(&mut self, bytes: &[u8]) -> Result<()>;
+}
+
+/// Available implementations of [`CrdtBackend`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CrdtKind {
+    Loro,
+    #[cfg(feature = "automerge")]
+    Automerge,
+}
+
+impl CrdtKind {
+    /// All implementations built in.
+    pub const ALL: &[CrdtKind] = &[
+        CrdtKind::Loro,
+        #[cfg(feature = "automerge")]
+        CrdtKind::Automerge,
+    ];
+
+    /// An empty replica.
+    pub fn create(self) -> Box<dyn CrdtBackend> {
+        match self {
+            CrdtKind::Loro => Box::new(LoroBackend::new()),
+            #[cfg(feature = "automerge")]
+            CrdtKind::Automerge => Box::new(AutomergeBackend::new()),
+        }
+    }
+
+    /// A replica loaded from [`CrdtBackend::export_snapshot`] of the same implementation.
+    pub fn load(self, snapshot: &[u8]) -> Result<Box<dyn CrdtBackend>> {
+        Ok(match self {
+            CrdtKind::Loro => Box::new(LoroBackend::load(snapshot)?),
+            #[cfg(feature = "automerge")]
+            CrdtKind::Automerge => Box::new(AutomergeBackend::load(snapshot)?),
+        })
+    }
+}
+
+impl fmt::Display for CrdtKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CrdtKind::Loro => f.write_str("loro"),
+            #[cfg(feature = "automerge")]
+            CrdtKind::Automerge => f.write_str("automerge"),
+        }
+    }
+}
+
+impl FromStr for CrdtKind {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        CrdtKind::ALL
+            .iter()
+            .find(|kind| kind.to_string() == value)
+            .copied()
+            .ok_or_else(|| anyhow!("unknown or disabled CRDT: {value}"))
+    }
+}
+
+/// Replace the text of `to` with the text of `from`, as a single change.
+///
+/// The history isn't converted, the converted text starts a new one.
+pub fn convert(from: &dyn CrdtBackend, to: &mut dyn CrdtBackend) -> Result<()> {
+    let len = to.len();
+    to.splice(0, len, &from.text())?;
+    to.commit();
+    Ok(())
+}
+
+/// Loro, the CRDT of [`Document`](crate::document::Document).
+pub struct LoroBackend {
+    doc: LoroDoc,
+}
+
+impl LoroBackend {
+    pub fn new() -> Self {
+        Self {
+            doc: LoroDoc::new(),
+        }
+    }
+
+    pub fn load(snapshot: &[u8]) -> Result<Self> {
+        let doc = LoroDoc::from_snapshot(snapshot)?;
+        Ok(Self { doc })
+    }
+
+    fn loro_text(&self) -> LoroText {
+        self.doc.get_text(TEXT_CONTAINER_ID)
+    }
+}
+
+impl Default for LoroBackend {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CrdtBackend for LoroBackend {
+    fn text(&self) -> String {
+        self.loro_text().to_string()
+    }
+
+    fn len(&self) -> usize {
+        self.loro_text().len_unicode()
+    }
+
+    fn insert(&mut self, index: usize, text: &str) -> Result<()> {
+        Ok(self.loro_text().insert(index, text)?)
+    }
+
+    fn delete(&mut self, index: usize, len: usize) -> Result<()> {
+        Ok(self.loro_text().delete(index, len)?)
+    }
+
+    fn splice(&mut self, index: usize, len: usize, text: &str) -> Result<()> {
+        self.loro_text().splice(index, len, text)?;
+        Ok(())
+    }
+
+    fn anchor_at(&self, index: usize) -> Option<Vec<u8>> {
+        self.loro_text()
+            .get_cursor(index, Side::Middle)
+            .map(|cursor| cursor.encode())
+    }
+
+    fn resolve(&self, anchor: &[u8]) -> Option<usize> {
+        let cursor = Cursor::decode(anchor).ok()?;
+        let result = self.doc.get_cursor_pos(&cursor).ok()?;
+        Some(result.current.pos)
+    }
+
+    fn commit(&mut self) {
+        self.doc.commit();
+    }
+
+    fn version(&mut self) -> Vec<u8> {
+        self.doc.oplog_vv().encode()
+    }
+
+    fn export_updates(&mut self, since: &[u8]) -> Result<Vec<u8>> {
+        let since = if since.is_empty() {
+            VersionVector::default()
+        } else {
+            VersionVector::decode(since)?
+        };
+        Ok(self.doc.export(ExportMode::updates(&since))?)
+    }
+
+    fn export_snapshot(&mut self) -> Result<Vec<u8>> {
+        Ok(self.doc.export(ExportMode::Snapshot)?)
+    }
+
+    fn import(&mut self, bytes: &[u8]) -> Result<()> {
+        self.doc.import(bytes)?;
+        Ok(())
+    }
+}
+
+/// Automerge with the text in a text object at the root.
+///
+/// Replicas which create the text object independently have different objects, only one of them
+/// wins after syncing. New replicas of a document need to load a snapshot of it first.
+#[cfg(feature = "automerge")]
+pub struct AutomergeBackend {
+    doc: automerge::AutoCommit,
+    text: automerge::ObjId,
+}
+
+#[cfg(feature = "automerge")]
+impl AutomergeBackend {
+    pub fn new() -> Self {
+        use automerge::transaction::Transactable;
+
+        let mut doc = automerge::AutoCommit::new();
+        let text = doc
+            .put_object(automerge::ROOT, TEXT_CONTAINER_ID, automerge::ObjType::Text)
+            .expect("root to be a map");
+        doc.commit();
+        Self { doc, text }
+    }
+
+    pub fn load(snapshot: &[u8]) -> Result<Self> {
+        let doc = automerge::AutoCommit::load(snapshot)?;
+        let mut backend = Self {
+            doc,
+            text: automerge::ROOT,
+        };
+        backend.resolve_text()?;
+        Ok(backend)
+    }
+
+    /// Find the text object again, after importing changes of other replicas.
+    fn resolve_text(&mut self) -> Result<()> {
+        use automerge::ReadDoc;
+
+        match self.doc.get(automerge::ROOT, TEXT_CONTAINER_ID)? {
+            Some((automerge::Value::Object(automerge::ObjType::Text), text)) => {
+                self.text = text;
+                Ok(())
+            }
+            _ => Err(anyhow!("text object missing")),
+        }
+    }
+}
+
+#[cfg(feature = "automerge")]
+impl Default for AutomergeBackend {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "automerge")]
+impl CrdtBackend for AutomergeBackend {
+    fn text(&self) -> String {
+        use automerge::ReadDoc;
+
+        self.doc.text(&self.text).unwrap_or_default()
+    }
+
+    fn len(&self) -> usize {
+        use automerge::ReadDoc;
+
+        self.doc.length(&self.text)
+    }
+
+    fn insert(&mut self, index: usize, text: &str) -> Result<()> {
+        self.splice(index, 0, text)
+    }
+
+    fn delete(&mut self, index: usize, len: usize) -> Result<()> {
+        self.splice(index, len, "")
+    }
+
+    fn splice(&mut self, index: usize, len: usize, text: &str) -> Result<()> {
+        use automerge::transaction::Transactable;
+
+        self.doc
+            .splice_text(&self.text, index, len as isize, text)?;
+        Ok(())
+    }
+
+    fn anchor_at(&self, index: usize) -> Option<Vec<u8>> {
+        use automerge::ReadDoc;
+
+        self.doc
+            .get_cursor(&self.text, index, None)
+            .ok()
+            .map(|cursor| cursor.to_bytes())
+    }
+
+    fn resolve(&self, anchor: &[u8]) -> Option<usize> {
+        use automerge::ReadDoc;
+
+        let cursor = automerge::Cursor::try_from(anchor).ok()?;
+        self.doc.get_cursor_position(&self.text, &cursor, None).ok()
+    }
+
+    fn commit(&mut self) {
+        self.doc.commit();
+    }
+
+    fn version(&mut self) -> Vec<u8> {
+        self.doc
+            .get_heads()
+            .iter()
+            .flat_map(|head| head.0)
+            .collect()
+    }
+
+    fn export_updates(&mut self, since: &[u8]) -> Result<Vec<u8>> {
+        let heads = since
+            .chunks(32)
+            .map(automerge::ChangeHash::try_from)
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(self.doc.save_after(&heads))
+    }
+
+    fn export_snapshot(&mut self) -> Result<Vec<u8>> {
+        Ok(self.doc.save())
+    }
+
+    fn import(&mut self, bytes: &[u8]) -> Result<()> {
+        self.doc.load_incremental(bytes)?;
+        self.resolve_text()
+    }
+}
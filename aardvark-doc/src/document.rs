@@ -1,21 +1,48 @@
 use std::fmt;
 use std::str::FromStr;
-
-use aardvark_node::document::{DocumentId as DocumentIdNode, SubscribableDocument};
-use anyhow::Result;
-use gio::prelude::ApplicationExtManual;
+use std::time::{Duration, Instant};
+
+use aardvark_core::SubscriptionHandle;
+use aardvark_core::document::{
+    DocumentId as DocumentIdNode, HistoryProgress, SubscribableDocument,
+    SyncProgress as SyncProgressNode,
+};
+use anyhow::{Result, anyhow};
+use gio::prelude::{ApplicationExtManual, ListModelExt, ListModelExtManual};
 use glib::prelude::*;
 use glib::subclass::{Signal, prelude::*};
 use glib::{Properties, clone};
-use loro::{ExportMode, LoroDoc, LoroText, event::Diff};
-use p2panda_core::HashError;
-use tracing::error;
-
+use loro::{
+    ExpandType, ExportMode, Frontiers, LoroDoc, LoroError, LoroList, LoroMap, LoroText, LoroValue,
+    StyleConfig, StyleConfigMap, ValueOrContainer, VersionVector,
+    cursor::{Cursor, Side},
+    event::Diff,
+};
+use p2panda_core::{Hash, HashError};
+use tracing::{debug, error, warn};
+
+use crate::anonymity::{encode_anonymous_announcement, is_anonymous_announcement};
+use crate::author::Author;
 use crate::authors::Authors;
+use crate::bookmarks::{Bookmark, Bookmarks};
+use crate::checkpoints::{Checkpoint, Checkpoints, DiffLine, diff_lines};
+use crate::conflicts::{LocalEdit, overlaps, seen_by};
+use crate::conventions::{HeadingStyle, ListMarker, contains_heading};
+use crate::debug::{OperationInfo, SnapshotInfo, TopicInfo};
+use crate::frontmatter::{Frontmatter, format_entry, new_block, parse_frontmatter};
+use crate::graphemes::{CLUSTER_WINDOW, extend_to_clusters};
+use crate::history::{self, ChangeBatch, RevertEdit};
 use crate::identity::PublicKey;
+use crate::peers::Peers;
+use crate::polls::{Poll, extract_poll_ids, poll_marker};
+use crate::presence::{Presence, decode_presence, encode_presence};
+use crate::profiler::Profiler;
+use crate::receipts::{changes_missing, decode_receipt, encode_receipt, has_own_changes};
 use crate::service::Service;
+use crate::summary::ChangeSummary;
+use crate::tasks::{Task, extract_tasks, format_due_date, parse_due_date};
 
-#[derive(Clone, Debug, PartialEq, Eq, glib::Boxed)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash, glib::Boxed)]
 #[boxed_type(name = "AardvarkDocumentId", nullable)]
 pub struct DocumentId(pub(crate) DocumentIdNode);
 
@@ -33,18 +60,120 @@ impl fmt::Display for DocumentId {
     }
 }
 
+/// Progress of downloading the document from peers after subscribing to it.
+#[derive(Clone, Debug, PartialEq, glib::Boxed)]
+#[boxed_type(name = "AardvarkSyncProgress", nullable)]
+pub struct SyncProgress(SyncProgressNode);
+
+impl SyncProgress {
+    /// Number of changes received so far.
+    pub fn operations_received(&self) -> u64 {
+        self.0.operations_received
+    }
+
+    /// Number of changes we know of in total, if we know of some we didn't receive yet.
+    pub fn operations_expected(&self) -> Option<u64> {
+        self.0.operations_expected
+    }
+
+    pub fn bytes_received(&self) -> u64 {
+        self.0.bytes_received
+    }
+
+    /// We caught up with at least one peer.
+    pub fn is_done(&self) -> bool {
+        self.0.done
+    }
+
+    /// How much of the document was received, between 0 and 1, if we know how much there is.
+    pub fn fraction(&self) -> Option<f64> {
+        if self.0.done {
+            return Some(1.0);
+        }
+        let expected = self
+            .0
+            .operations_expected
+            .filter(|expected| *expected > 0)?;
+        Some((self.0.operations_received as f64 / expected as f64).min(1.0))
+    }
+}
+
+/// A position in the text which stays attached to the same character while the text around it
+/// is edited, locally or by other authors. See [`Document::anchor_at`].
+#[derive(Clone, Debug, glib::Boxed)]
+#[boxed_type(name = "AardvarkAnchor", nullable)]
+pub struct Anchor(Cursor);
+
+impl Anchor {
+    /// Encode the anchor, e.g. to store it or to send it to other authors of the document.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.0.encode()
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        Cursor::decode(bytes).ok().map(Anchor)
+    }
+}
+
+/// A change of the text waiting to be emitted as `text-inserted` or `range-deleted` signal.
+#[derive(Debug)]
+enum TextChange {
+    Inserted { pos: i32, text: String },
+    Deleted { start: i32, end: i32 },
+}
+
 mod imp {
     use super::*;
-    use std::cell::{Cell, OnceCell};
+    use std::cell::{Cell, OnceCell, RefCell};
+    use std::collections::{HashMap, VecDeque};
     use std::sync::{Arc, Mutex, OnceLock};
     use std::time::Duration;
 
     /// Identifier of container where we handle the text CRDT in a Loro document.
     ///
     /// Loro documents can contain multiple different CRDT types in one document.
-    const TEXT_CONTAINER_ID: &str = "document";
+    pub(super) const TEXT_CONTAINER_ID: &str = "document";
+    /// Key of the mark on a task description which holds the public key of the assignee.
+    pub(super) const ASSIGNEE_MARK: &str = "assignee";
+    /// Key of the mark on a task description which holds the due date.
+    pub(super) const DUE_DATE_MARK: &str = "due";
+    /// Identifier of the map container holding settings shared by all authors of a document.
+    const SETTINGS_CONTAINER_ID: &str = "settings";
+    const MEETING_NOTES_KEY: &str = "meeting-notes";
+    const HEADING_STYLE_KEY: &str = "heading-style";
+    const LIST_MARKER_KEY: &str = "list-marker";
+    const WRAP_COLUMN_KEY: &str = "wrap-column";
+    /// Identifier of the map container holding all polls of a document, by poll id.
+    pub(super) const POLLS_CONTAINER_ID: &str = "polls";
+    pub(super) const POLL_QUESTION_KEY: &str = "question";
+    pub(super) const POLL_OPTIONS_KEY: &str = "options";
+    /// Map from the public key of an author to the index of the option they voted for.
+    pub(super) const POLL_VOTES_KEY: &str = "votes";
+    /// Identifier of the text container holding the title of a document.
+    const TITLE_CONTAINER_ID: &str = "title";
+    /// Identifier of the map container holding all bookmarks of a document, by bookmark id.
+    pub(super) const BOOKMARKS_CONTAINER_ID: &str = "bookmarks";
+    pub(super) const BOOKMARK_NAME_KEY: &str = "name";
+    /// Encoded anchor in the text the bookmark points to.
+    pub(super) const BOOKMARK_ANCHOR_KEY: &str = "anchor";
+    /// Identifier of the map container holding all checkpoints of a document, by checkpoint id.
+    pub(super) const CHECKPOINTS_CONTAINER_ID: &str = "checkpoints";
+    pub(super) const CHECKPOINT_NAME_KEY: &str = "name";
+    /// Encoded frontiers of the document the checkpoint was created at.
+    pub(super) const CHECKPOINT_VERSION_KEY: &str = "version";
+    /// Unix timestamp of when the checkpoint was created.
+    pub(super) const CHECKPOINT_CREATED_AT_KEY: &str = "created-at";
+    /// Time to sync with other peers after opening a document before we add a section for today,
+    /// another peer might have added it already.
+    const MEETING_NOTES_DELAY: Duration = Duration::from_secs(10);
     const DOCUMENT_NAME_LENGTH: usize = 32;
     const SNAPSHOT_TIMEOUT: Duration = Duration::from_secs(5);
+    /// Time to wait after a change before broadcasting a read receipt, so typing doesn't send one
+    /// for every keystroke.
+    const RECEIPT_TIMEOUT: Duration = Duration::from_secs(2);
+    /// Inserts larger than this (in bytes) are committed in multiple steps, so every delta we
+    /// broadcast stays well below the gossip message size limit.
+    const INSERT_CHUNK_SIZE: usize = 16 * 1024;
 
     #[derive(Properties, Default)]
     #[properties(wrapper_type = super::Document)]
@@ -53,22 +182,140 @@ mod imp {
         name: Mutex<Option<String>>,
         #[property(get, construct_only, set)]
         last_accessed: Mutex<Option<glib::DateTime>>,
+        /// When the document was added to this device.
+        #[property(get, construct_only)]
+        created_at: Mutex<Option<glib::DateTime>>,
+        /// When we or another author last changed the text.
+        #[property(get, construct_only)]
+        pub(super) last_edited: Mutex<Option<glib::DateTime>>,
         #[property(name = "text", get = Self::text, type = String)]
+        /// Title shared by all authors, edited separately from the text. The document is named
+        /// after the first line of the text while the title is empty.
+        #[property(name = "title", get = Self::title, set = Self::set_title, type = String)]
+        /// Metadata from the YAML frontmatter at the top of the text.
+        #[property(name = "frontmatter", get = Self::frontmatter, type = Option<Frontmatter>)]
+        // Start a date-stamped section whenever the document is opened on a new day, shared by
+        // all authors of the document.
+        #[property(
+            name = "meeting-notes",
+            get = Self::meeting_notes,
+            set = Self::set_meeting_notes,
+            type = bool
+        )]
+        // Editing conventions shared by all authors, see `crate::conventions`.
+        #[property(
+            name = "heading-style",
+            get = Self::heading_style,
+            set = Self::set_heading_style,
+            type = HeadingStyle,
+            builder(HeadingStyle::default())
+        )]
+        #[property(
+            name = "list-marker",
+            get = Self::list_marker,
+            set = Self::set_list_marker,
+            type = ListMarker,
+            builder(ListMarker::default())
+        )]
+        // Column text is wrapped at when formatting it, 0 when lines aren't wrapped.
+        #[property(
+            name = "wrap-column",
+            get = Self::wrap_column,
+            set = Self::set_wrap_column,
+            type = u32
+        )]
         pub(super) crdt_doc: OnceCell<LoroDoc>,
         #[property(get, construct_only, set = Self::set_id)]
         id: OnceCell<DocumentId>,
         #[property(get, set = Self::set_subscribed)]
         subscribed: Cell<bool>,
+        /// Keeps us subscribed on the node, dropping it unsubscribes.
+        subscription: Mutex<Option<SubscriptionHandle>>,
+        /// Whether the document is advertised to peers on the local network, who can join it
+        /// without an invite code.
+        #[property(get, set = Self::set_discoverable)]
+        pub(super) discoverable: Cell<bool>,
+        /// Archived documents aren't synced anymore and are listed separately, until they're
+        /// restored.
+        #[property(get, set = Self::set_archived)]
+        pub(super) archived: Cell<bool>,
+        /// Pinned documents are listed above all others, see [`crate::documents::Documents`].
+        #[property(get, set = Self::set_pinned)]
+        pub(super) pinned: Cell<bool>,
+        /// Position in the document list after the user reordered it.
+        pub(super) position: Cell<Option<i64>>,
+        /// When the document was moved to the trash, it's deleted `TRASH_RETENTION` later.
+        #[property(get)]
+        pub(super) trashed_at: Mutex<Option<glib::DateTime>>,
+        /// Number of views (e.g. windows) currently showing this document.
+        views: Cell<u32>,
         #[property(get, construct_only)]
         service: OnceCell<Service>,
         #[property(get, set = Self::set_authors, construct_only)]
         authors: OnceCell<Authors>,
+        /// Authors currently connected to us while we're subscribed.
+        #[property(get)]
+        peers: Peers,
+        /// How often we tried to join the document again since we lost all peers, 0 while we're
+        /// connected or never were.
+        #[property(get)]
+        reconnect_attempt: Cell<u32>,
+        /// How far we got downloading the document since we subscribed, `None` until we
+        /// received something.
+        #[property(get, nullable)]
+        sync_progress: RefCell<Option<SyncProgress>>,
+        /// Chunks of large payloads, usually snapshots, we're still receiving.
+        history_progress: Cell<Option<HistoryProgress>>,
+        /// Changes we received which build on changes we don't have yet, by the peer who made
+        /// them with the counter they end at. The CRDT applies them once the rest arrived.
+        missing_history: RefCell<HashMap<u64, i32>>,
+        /// The full history of the document is still on its way, while the most recent changes
+        /// might already be shown.
+        #[property(get)]
+        loading_history: Cell<bool>,
         snapshot_task: Mutex<Option<glib::SourceId>>,
+        /// Where the last change to the text ended, in characters.
+        last_change: Cell<Option<usize>>,
+        /// Where other authors changed the text last, stays in place when the text around it
+        /// changes.
+        last_edits: Mutex<Vec<(Author, Cursor)>>,
+        /// Regions of the text we changed recently, see `crate::conflicts`.
+        recent_edits: Mutex<Vec<LocalEdit>>,
+        /// Regions of the text changed by the remote message being applied right now, in
+        /// characters.
+        remote_changes: RefCell<Option<Vec<(usize, usize)>>>,
+        #[property(get)]
+        bookmarks: Bookmarks,
+        #[property(get)]
+        checkpoints: Checkpoints,
+        /// Version of the document other authors told us they have, see `crate::receipts`.
+        seen_versions: Mutex<Vec<(Author, VersionVector)>>,
+        /// Local changes made while no peer was connected, until a peer acknowledged them with a
+        /// read receipt.
+        #[property(get)]
+        pending_local_changes: Cell<u32>,
+        receipt_task: Mutex<Option<glib::SourceId>>,
+        /// Latency of local edits and remote patches, see `crate::profiler`.
+        #[property(get)]
+        profiler: Profiler,
+        /// Changes of the text which are already part of `text` but weren't emitted yet, with
+        /// their version.
+        ///
+        /// Changes made by handlers of the signals are queued behind the ones still pending, so
+        /// they're observed in the order they were applied.
+        text_changes: RefCell<VecDeque<(u64, TextChange)>>,
+        /// Version of the last queued change of the text.
+        queued_text_version: Cell<u64>,
+        /// Version of the last change of the text which was emitted, every change increments it
+        /// by one.
+        #[property(get)]
+        text_version: Cell<u64>,
+        emitting_text_changes: Cell<bool>,
     }
 
     #[glib::object_subclass]
     impl ObjectSubclass for Document {
-        const NAME: &'static str = "Document";
+        const NAME: &'static str = "AardvarkDocument";
         type Type = super::Document;
     }
 
@@ -78,18 +325,20 @@ mod imp {
         }
 
         let mut name = String::with_capacity(DOCUMENT_NAME_LENGTH);
-        crdt_text.iter(|slice| {
-            for char in slice.chars() {
-                if char == '\n' {
-                    // Only use the first line as name for the document
-                    return false;
-                } else if char.is_whitespace() || char.is_alphanumeric() {
-                    name.push(char);
-                }
+        // Documents with frontmatter are named after their title, or the first line below it
+        if crdt_text.slice(0, 4).is_ok_and(|start| start == "---\n") {
+            let text = crdt_text.to_string();
+            if let Some(frontmatter) = parse_frontmatter(&text) {
+                let source = match frontmatter.get("title") {
+                    Some(title) => title.to_owned(),
+                    None => text.chars().skip(frontmatter.block_len()).collect(),
+                };
+                push_name_chars(&mut name, source.trim_start());
+                return (!name.trim().is_empty()).then_some(name);
             }
+        }
 
-            name.len() < DOCUMENT_NAME_LENGTH
-        });
+        crdt_text.iter(|slice| push_name_chars(&mut name, slice));
 
         if name.trim().len() > 0 {
             Some(name)
@@ -98,6 +347,20 @@ mod imp {
         }
     }
 
+    /// Add the characters of `slice` usable in a name, returns `false` once the name is complete.
+    fn push_name_chars(name: &mut String, slice: &str) -> bool {
+        for char in slice.chars() {
+            if char == '\n' {
+                // Only use the first line as name for the document
+                return false;
+            } else if char.is_whitespace() || char.is_alphanumeric() {
+                name.push(char);
+            }
+        }
+
+        name.len() < DOCUMENT_NAME_LENGTH
+    }
+
     impl Document {
         fn set_authors(&self, authors: Option<Authors>) {
             if let Some(authors) = authors {
@@ -113,14 +376,152 @@ mod imp {
                 .to_string()
         }
 
-        fn update_name(&self) {
-            let crdt_text = self
+        fn frontmatter(&self) -> Option<Frontmatter> {
+            parse_frontmatter(&self.text())
+        }
+
+        fn meeting_notes(&self) -> bool {
+            let settings = self
                 .crdt_doc
                 .get()
                 .expect("crdt_doc to be set")
-                .get_text(TEXT_CONTAINER_ID);
+                .get_map(SETTINGS_CONTAINER_ID);
+            matches!(
+                settings.get(MEETING_NOTES_KEY),
+                Some(ValueOrContainer::Value(LoroValue::Bool(true)))
+            )
+        }
+
+        fn set_meeting_notes(&self, meeting_notes: bool) {
+            if self.meeting_notes() == meeting_notes {
+                return;
+            }
+
+            let doc = self.crdt_doc.get().expect("crdt_doc to be set");
+            let settings = doc.get_map(SETTINGS_CONTAINER_ID);
+            if let Err(error) = settings.insert(MEETING_NOTES_KEY, meeting_notes) {
+                error!("Failed to change meeting notes setting: {error}");
+                return;
+            }
+            doc.commit();
+
+            if meeting_notes {
+                self.obj().insert_meeting_notes_section();
+            }
+        }
+
+        fn heading_style(&self) -> HeadingStyle {
+            match self.setting(HEADING_STYLE_KEY) {
+                Some(ValueOrContainer::Value(LoroValue::String(value))) => {
+                    HeadingStyle::from_name(&value)
+                }
+                _ => HeadingStyle::default(),
+            }
+        }
+
+        fn set_heading_style(&self, heading_style: HeadingStyle) {
+            if self.heading_style() != heading_style {
+                self.set_setting(HEADING_STYLE_KEY, heading_style.name());
+            }
+        }
+
+        fn list_marker(&self) -> ListMarker {
+            match self.setting(LIST_MARKER_KEY) {
+                Some(ValueOrContainer::Value(LoroValue::String(value))) => value
+                    .chars()
+                    .next()
+                    .map(ListMarker::from_char)
+                    .unwrap_or_default(),
+                _ => ListMarker::default(),
+            }
+        }
+
+        fn set_list_marker(&self, list_marker: ListMarker) {
+            if self.list_marker() != list_marker {
+                self.set_setting(LIST_MARKER_KEY, list_marker.as_char().to_string());
+            }
+        }
+
+        fn wrap_column(&self) -> u32 {
+            match self.setting(WRAP_COLUMN_KEY) {
+                Some(ValueOrContainer::Value(LoroValue::I64(value))) => {
+                    u32::try_from(value).unwrap_or_default()
+                }
+                _ => 0,
+            }
+        }
+
+        fn set_wrap_column(&self, wrap_column: u32) {
+            if self.wrap_column() != wrap_column {
+                self.set_setting(WRAP_COLUMN_KEY, wrap_column as i64);
+            }
+        }
+
+        fn title(&self) -> String {
+            self.crdt_doc
+                .get()
+                .expect("crdt_doc to be set")
+                .get_text(TITLE_CONTAINER_ID)
+                .to_string()
+        }
+
+        /// Change the title with as few edits as possible, so concurrent changes to different
+        /// parts of the title are merged instead of one replacing the other.
+        fn set_title(&self, title: String) {
+            let current: Vec<char> = self.title().chars().collect();
+            let new: Vec<char> = title.chars().collect();
+            if current == new {
+                return;
+            }
+
+            let prefix = current.iter().zip(&new).take_while(|(a, b)| a == b).count();
+            let suffix = current[prefix..]
+                .iter()
+                .rev()
+                .zip(new[prefix..].iter().rev())
+                .take_while(|(a, b)| a == b)
+                .count();
+            let deleted = current.len() - prefix - suffix;
+            let inserted: String = new[prefix..new.len() - suffix].iter().collect();
+
+            let doc = self.crdt_doc.get().expect("crdt_doc to be set");
+            let text = doc.get_text(TITLE_CONTAINER_ID);
+            let result = text
+                .delete(prefix, deleted)
+                .and_then(|_| text.insert(prefix, &inserted));
+            if let Err(error) = result {
+                error!("Failed to change title: {error}");
+                return;
+            }
+            doc.commit();
+        }
+
+        fn setting(&self, key: &str) -> Option<ValueOrContainer> {
+            self.crdt_doc
+                .get()
+                .expect("crdt_doc to be set")
+                .get_map(SETTINGS_CONTAINER_ID)
+                .get(key)
+        }
+
+        fn set_setting(&self, key: &str, value: impl Into<LoroValue>) {
+            let doc = self.crdt_doc.get().expect("crdt_doc to be set");
+            let settings = doc.get_map(SETTINGS_CONTAINER_ID);
+            if let Err(error) = settings.insert(key, value) {
+                error!("Failed to change {key} setting: {error}");
+                return;
+            }
+            doc.commit();
+        }
 
-            let name = extract_name(crdt_text);
+        fn update_name(&self) {
+            let doc = self.crdt_doc.get().expect("crdt_doc to be set");
+            let title = doc.get_text(TITLE_CONTAINER_ID).to_string();
+            let name = if title.trim().is_empty() {
+                extract_name(doc.get_text(TEXT_CONTAINER_ID))
+            } else {
+                Some(title.trim().to_owned())
+            };
 
             if name == self.obj().name() {
                 return;
@@ -130,7 +531,7 @@ mod imp {
             self.obj().notify_name();
 
             let obj = self.obj();
-            glib::spawn_future(clone!(
+            glib::spawn_future_local(clone!(
                 #[weak]
                 obj,
                 async move {
@@ -150,6 +551,120 @@ mod imp {
             ));
         }
 
+        fn set_discoverable(&self, discoverable: bool) {
+            if self.discoverable.get() == discoverable {
+                return;
+            }
+
+            self.discoverable.set(discoverable);
+            self.obj().notify_discoverable();
+
+            let obj = self.obj();
+            glib::spawn_future_local(clone!(
+                #[weak]
+                obj,
+                async move {
+                    let document_id = obj.id().0;
+                    if let Err(error) = obj
+                        .service()
+                        .node()
+                        .set_document_discoverable(&document_id, discoverable)
+                        .await
+                    {
+                        error!(
+                            "Failed to change discoverability of document {}: {}",
+                            document_id, error
+                        );
+                    }
+                }
+            ));
+        }
+
+        fn set_archived(&self, archived: bool) {
+            if self.archived.get() == archived {
+                return;
+            }
+
+            self.archived.set(archived);
+            self.obj().notify_archived();
+
+            let obj = self.obj();
+            glib::spawn_future_local(clone!(
+                #[weak]
+                obj,
+                async move {
+                    let document_id = obj.id().0;
+                    if let Err(error) = obj
+                        .service()
+                        .node()
+                        .set_document_archived(&document_id, archived)
+                        .await
+                    {
+                        error!("Failed to archive document {}: {}", document_id, error);
+                    }
+                }
+            ));
+        }
+
+        fn set_pinned(&self, pinned: bool) {
+            if self.pinned.get() == pinned {
+                return;
+            }
+
+            self.pinned.set(pinned);
+            self.obj().notify_pinned();
+
+            let obj = self.obj();
+            glib::spawn_future_local(clone!(
+                #[weak]
+                obj,
+                async move {
+                    let document_id = obj.id().0;
+                    if let Err(error) = obj
+                        .service()
+                        .node()
+                        .set_document_pinned(&document_id, pinned)
+                        .await
+                    {
+                        error!("Failed to pin document {}: {}", document_id, error);
+                    }
+                }
+            ));
+        }
+
+        fn set_trashed(&self, trashed: bool) {
+            if self.trashed_at.lock().unwrap().is_some() == trashed {
+                return;
+            }
+
+            *self.trashed_at.lock().unwrap() = if trashed {
+                glib::DateTime::now_utc().ok()
+            } else {
+                None
+            };
+            self.obj().notify_trashed_at();
+
+            let obj = self.obj();
+            glib::spawn_future_local(clone!(
+                #[weak]
+                obj,
+                async move {
+                    let document_id = obj.id().0;
+                    if let Err(error) = obj
+                        .service()
+                        .node()
+                        .set_document_trashed(&document_id, trashed)
+                        .await
+                    {
+                        error!(
+                            "Failed to move document {} to the trash: {}",
+                            document_id, error
+                        );
+                    }
+                }
+            ));
+        }
+
         fn set_id(&self, id: Option<DocumentId>) {
             if let Some(id) = id {
                 self.id.set(id).expect("Document id can only be set once");
@@ -160,29 +675,100 @@ mod imp {
             let doc = self.crdt_doc.get().expect("crdt_doc to be set");
             let text = doc.get_text(TEXT_CONTAINER_ID);
 
-            text.insert(index, chunk)?;
-            doc.commit();
+            // Every commit becomes its own delta operation
+            let start = index;
+            let mut index = index;
+            for chunk in split_into_chunks(chunk, INSERT_CHUNK_SIZE) {
+                let started = Instant::now();
+                text.insert(index, chunk)?;
+                self.profiler.add_crdt_apply(started.elapsed());
+                doc.commit();
+                index += chunk.chars().count();
+            }
+            self.add_recent_edit(start, index);
 
             Ok(())
         }
 
-        pub fn delete_text(&self, index: usize, len: usize) -> Result<()> {
+        pub fn insert_paragraphs(&self, index: usize, chunk: &str, origin: &str) -> Result<()> {
             let doc = self.crdt_doc.get().expect("crdt_doc to be set");
             let text = doc.get_text(TEXT_CONTAINER_ID);
 
-            text.delete(index, len)?;
-            doc.commit();
+            // Paragraphs are committed one after another, every commit depends on the previous
+            // one and keeps them in their original order
+            let start = index;
+            let mut index = index;
+            for (number, paragraph) in split_into_paragraphs(chunk).enumerate() {
+                for chunk in split_into_chunks(paragraph, INSERT_CHUNK_SIZE) {
+                    text.insert(index, chunk)?;
+                    doc.set_next_commit_message(&format!("{origin}, paragraph {}", number + 1));
+                    doc.commit();
+                    index += chunk.chars().count();
+                }
+            }
+            self.add_recent_edit(start, index);
 
             Ok(())
         }
 
+        /// Delete `len` characters at `index` and insert `chunk` in their place, as one change.
+        pub fn splice(&self, index: usize, len: usize, chunk: &str) -> Result<()> {
+            self.transaction(|transaction| transaction.splice(index as i32, len as i32, chunk))
+        }
+
+        /// Delete the characters from `start` to `end`, extended to whole grapheme clusters.
+        ///
+        /// Returns the range which was deleted.
+        pub fn delete_text(&self, start: usize, end: usize) -> Result<(usize, usize)> {
+            let doc = self.crdt_doc.get().expect("crdt_doc to be set");
+            let text = doc.get_text(TEXT_CONTAINER_ID);
+
+            let window_start = start.saturating_sub(CLUSTER_WINDOW);
+            let window_end = (end + CLUSTER_WINDOW).min(text.len_unicode());
+            let window = text.slice(window_start, window_end)?;
+            let (start, end) =
+                extend_to_clusters(&window, start - window_start, end - window_start);
+            let (start, end) = (window_start + start, window_start + end);
+
+            let started = Instant::now();
+            text.delete(start, end - start)?;
+            self.profiler.add_crdt_apply(started.elapsed());
+            doc.commit();
+            self.add_recent_edit(start, start);
+
+            Ok((start, end))
+        }
+
         /// Apply changes to the CRDT from a message received from another peer
-        pub fn on_remote_message(&self, bytes: Vec<u8>) {
+        pub fn on_remote_message(&self, bytes: Vec<u8>) -> Result<(), LoroError> {
             let doc = self.crdt_doc.get().expect("crdt_doc to be set");
 
-            if let Err(err) = doc.import_with(&bytes, "delta") {
-                eprintln!("received invalid message: {}", err);
+            self.last_change.set(None);
+            self.remote_changes.replace(Some(Vec::new()));
+            let status = match doc.import_with(&bytes, "delta") {
+                Ok(status) => status,
+                Err(err) => {
+                    self.remote_changes.take();
+                    warn!(document_id = %self.obj().id(), "received invalid message: {err}");
+                    return Err(err);
+                }
+            };
+
+            // Deltas arrive before the snapshots they might build on
+            let mut missing_history = self.missing_history.borrow_mut();
+            if let Some(pending) = status.pending.as_ref() {
+                for (peer, (_, end)) in pending.iter() {
+                    let missing_end = missing_history.entry(*peer).or_default();
+                    *missing_end = (*missing_end).max(*end);
+                }
             }
+            let version = doc.oplog_vv();
+            missing_history.retain(|peer, end| version.get(peer).copied().unwrap_or(0) < *end);
+            drop(missing_history);
+            self.update_loading_history();
+
+            debug!(document_id = %self.obj().id(), len = bytes.len(), "applied remote message");
+            Ok(())
         }
 
         pub fn set_subscribed(&self, subscribed: bool) {
@@ -196,114 +782,524 @@ mod imp {
                 *self.last_accessed.lock().unwrap() = None;
 
                 let obj = self.obj();
-                glib::spawn_future(clone!(
+                glib::spawn_future_local(clone!(
                     #[weak]
                     obj,
                     async move {
-                        let document_id = obj.id().0;
-                        let handle = DocumentHandle(obj.downgrade());
-                        if let Err(error) =
-                            obj.service().node().subscribe(document_id, handle).await
-                        {
-                            error!("Failed to subscribe to document: {}", error);
-                            obj.imp().set_subscribed(false);
-                        }
+                        glib::timeout_future(MEETING_NOTES_DELAY).await;
+                        obj.insert_meeting_notes_section();
                     }
                 ));
-            } else {
-                *self.last_accessed.lock().unwrap() = glib::DateTime::now_utc().ok();
 
-                let obj = self.obj();
-                // Keep the application alive till we completed the unsubscription task
-                let guard = gio::Application::default().and_then(|app| Some(app.hold()));
-                // Keep a strong reference to the document to ensure the document lives long enough
                 glib::spawn_future_local(clone!(
-                    #[strong]
+                    #[weak]
                     obj,
                     async move {
                         let document_id = obj.id().0;
-                        if let Err(error) = obj.service().node().unsubscribe(&document_id).await {
-                            error!("Failed to unsubscribe document {}: {}", document_id, error);
+                        let handle = DocumentHandle::new(&obj);
+                        match obj.service().node().subscribe(document_id, handle).await {
+                            // Dropping the subscription right away when we were unsubscribed in
+                            // the meantime
+                            Ok(subscription) if obj.subscribed() => {
+                                *obj.imp().subscription.lock().unwrap() = Some(subscription);
+                            }
+                            Ok(_) => {}
+                            Err(error) => {
+                                error!("Failed to subscribe to document: {}", error);
+                                obj.imp().set_subscribed(false);
+                            }
                         }
-                        drop(guard);
                     }
                 ));
+            } else {
+                *self.last_accessed.lock().unwrap() = glib::DateTime::now_utc().ok();
+                self.peers.clear();
+                self.set_reconnect_attempt(0);
+                self.set_sync_progress(None);
+                self.set_history_progress(None);
+
+                // Still subscribing otherwise, the subscription is dropped once it's done
+                if let Some(subscription) = self.subscription.lock().unwrap().take() {
+                    // Keep the application alive till we completed the unsubscription task
+                    let guard = gio::Application::default().map(|app| app.hold());
+                    // Doesn't need the document, it's also torn down while disposing it
+                    glib::spawn_future_local(async move {
+                        // Errors are logged by the node
+                        let _ = subscription.unsubscribe().await;
+                        drop(guard);
+                    });
+                }
             }
             self.obj().notify_last_accessed();
             self.obj().notify_subscribed();
         }
 
-        fn emit_text_inserted(&self, pos: i32, text: String) {
-            if pos <= DOCUMENT_NAME_LENGTH as i32 {
-                self.update_name();
+        pub(super) fn set_reconnect_attempt(&self, attempt: u32) {
+            if self.reconnect_attempt.replace(attempt) != attempt {
+                self.obj().notify_reconnect_attempt();
             }
-
-            self.obj()
-                .emit_by_name::<()>("text-inserted", &[&pos, &text]);
         }
 
-        fn emit_range_deleted(&self, start: i32, end: i32) {
-            if start <= DOCUMENT_NAME_LENGTH as i32 || end <= DOCUMENT_NAME_LENGTH as i32 {
-                self.update_name();
+        pub(super) fn set_sync_progress(&self, progress: Option<SyncProgress>) {
+            if *self.sync_progress.borrow() == progress {
+                return;
             }
-
-            self.obj()
-                .emit_by_name::<()>("range-deleted", &[&start, &end]);
+            self.sync_progress.replace(progress);
+            self.obj().notify_sync_progress();
         }
 
-        fn mark_for_snapshot(&self) {
-            let mut snapshot_task = self.snapshot_task.lock().unwrap();
-            if snapshot_task.is_none() {
-                let obj = self.obj();
-                let ctx = glib::MainContext::ref_thread_default();
-                let handle = ctx.spawn_with_priority(
-                    glib::source::Priority::LOW,
-                    clone!(
-                        #[weak]
-                        obj,
-                        async move {
-                            glib::timeout_future_with_priority(
-                                glib::source::Priority::LOW,
-                                SNAPSHOT_TIMEOUT,
-                            )
-                            .await;
-                            obj.store_snapshot().await;
-                            obj.imp().snapshot_task.lock().unwrap().take();
-                        }
-                    ),
-                );
+        pub(super) fn set_history_progress(&self, progress: Option<HistoryProgress>) {
+            self.history_progress.set(progress);
+            self.update_loading_history();
+        }
 
-                *snapshot_task = handle.into_source_id().ok();
+        fn update_loading_history(&self) {
+            let loading =
+                self.history_progress.get().is_some() || !self.missing_history.borrow().is_empty();
+            if self.loading_history.replace(loading) != loading {
+                self.obj().notify_loading_history();
             }
         }
 
-        fn setup_loro_document(&self) {
-            let public_key = self.obj().service().private_key().public_key();
-            let obj = self.obj();
-            let doc = LoroDoc::new();
-            // The peer id represents the identity of the author applying local changes (that's
-            // essentially us), it needs be strictly unique.
-            doc.set_peer_id({
-                // Take first 8 bytes of public key (32 bytes) to determine a unique "peer id"
-                // which is used to keep authors apart inside the text crdt.
-                //
-                // TODO(adz): This is strictly speaking not collision-resistant but we're limited
-                // here by the 8 bytes / 64 bit from the u64 `PeerId` type from Loro. In practice
-                // this should not really be a problem, but it would be nice if the Loro API would
-                // change some day.
-                let mut buf = [0u8; 8];
-                buf[..8].copy_from_slice(&public_key.0.as_bytes()[..8]);
-                u64::from_be_bytes(buf)
-            })
-            .expect("set peer id for new document");
+        /// Remember the last change to the text as the last edit of `author`.
+        pub(super) fn set_last_edit(&self, author: &Author) {
+            let Some(position) = self.last_change.take() else {
+                return;
+            };
+            let Some(cursor) = self.anchor_at(position) else {
+                return;
+            };
 
-            let text = doc.get_text(TEXT_CONTAINER_ID);
+            let mut last_edits = self.last_edits.lock().unwrap();
+            last_edits.retain(|(other, _)| other != author);
+            last_edits.push((author.clone(), cursor));
+            drop(last_edits);
+            author.set_last_edit_position(Some(position as u32));
+        }
+
+        fn add_remote_change(&self, start: usize, end: usize) {
+            if let Some(remote_changes) = self.remote_changes.borrow_mut().as_mut() {
+                remote_changes.push((start, end));
+            }
+        }
+
+        /// Remember that we just changed the text between `start` and `end`, in characters.
+        fn add_recent_edit(&self, start: usize, end: usize) {
+            let (Some(start), Some(end)) = (self.anchor_at(start), self.anchor_at(end)) else {
+                return;
+            };
+            self.add_recent_edits(vec![(start, end)]);
+        }
+
+        /// Remember the regions of the text changed by the commit we just made.
+        fn add_recent_edits(&self, edits: Vec<(Cursor, Cursor)>) {
+            let doc = self.crdt_doc.get().expect("crdt_doc to be set");
+            let counter = doc.oplog_vv().get(&doc.peer_id()).copied().unwrap_or(0);
+
+            let mut recent_edits = self.recent_edits.lock().unwrap();
+            recent_edits.retain(LocalEdit::is_recent);
+            for (start, end) in edits {
+                recent_edits.push(LocalEdit {
+                    start,
+                    end,
+                    counter,
+                    made_at: Instant::now(),
+                });
+            }
+        }
+
+        /// Apply all changes `edit` makes to the text with a single commit.
+        ///
+        /// Changes made before `edit` fails are committed all the same, the CRDT can't take them
+        /// back.
+        pub(super) fn transaction<T>(
+            &self,
+            edit: impl FnOnce(&mut TextTransaction) -> Result<T>,
+        ) -> Result<T> {
+            let doc = self.crdt_doc.get().expect("crdt_doc to be set");
+            let mut transaction = TextTransaction {
+                document: self,
+                text: doc.get_text(TEXT_CONTAINER_ID),
+                edits: Vec::new(),
+            };
+            let result = edit(&mut transaction);
+            doc.commit();
+            self.add_recent_edits(transaction.edits);
+
+            result
+        }
+
+        /// Emit `conflict-detected` if the remote message just applied changed text we edited
+        /// recently, without `author` having seen our edit.
+        pub(super) fn detect_conflicts(&self, author: &Author) {
+            let Some(remote_changes) = self.remote_changes.take() else {
+                return;
+            };
+            let doc = self.crdt_doc.get().expect("crdt_doc to be set");
+            let Some(seen) = seen_by(doc, peer_id(&author.public_key())) else {
+                return;
+            };
+
+            let mut conflict: Option<(usize, usize)> = None;
+            let mut recent_edits = self.recent_edits.lock().unwrap();
+            recent_edits.retain(LocalEdit::is_recent);
+            for edit in recent_edits.iter() {
+                if edit.seen_in(&seen, doc.peer_id()) {
+                    continue;
+                }
+                let (Some(start), Some(end)) = (
+                    self.anchor_position(&edit.start),
+                    self.anchor_position(&edit.end),
+                ) else {
+                    continue;
+                };
+                let local = (start.min(end), start.max(end));
+                for remote in remote_changes
+                    .iter()
+                    .filter(|remote| overlaps(local, **remote))
+                {
+                    let (start, end) = conflict.unwrap_or(local);
+                    conflict = Some((
+                        start.min(local.0).min(remote.0),
+                        end.max(local.1).max(remote.1),
+                    ));
+                }
+            }
+            drop(recent_edits);
+
+            if let Some((start, end)) = conflict {
+                let len = doc.get_text(TEXT_CONTAINER_ID).len_unicode();
+                self.obj().emit_by_name::<()>(
+                    "conflict-detected",
+                    &[&(start.min(len) as i32), &(end.min(len) as i32), author],
+                );
+            }
+        }
+
+        /// Move the last edits of authors along with the text changed around them.
+        fn update_last_edit_positions(&self) {
+            for (author, cursor) in self.last_edits.lock().unwrap().iter() {
+                let position = self.anchor_position(cursor);
+                author.set_last_edit_position(position.map(|position| position as u32));
+            }
+        }
+
+        /// An anchor at `position` (in characters) in the text, which stays in front of the same
+        /// character when text before it is inserted or deleted.
+        pub(super) fn anchor_at(&self, position: usize) -> Option<Cursor> {
+            self.crdt_doc
+                .get()
+                .expect("crdt_doc to be set")
+                .get_text(TEXT_CONTAINER_ID)
+                .get_cursor(position, Side::Middle)
+        }
+
+        /// Current position of an anchor in the text, in characters.
+        ///
+        /// If the character the anchor was placed at was deleted, this is where it used to be.
+        pub(super) fn anchor_position(&self, anchor: &Cursor) -> Option<usize> {
+            let doc = self.crdt_doc.get().expect("crdt_doc to be set");
+            doc.get_cursor_pos(anchor)
+                .ok()
+                .map(|result| result.current.pos)
+        }
+
+        /// Read the bookmarks from the CRDT again, e.g. after they or the text changed.
+        fn update_bookmarks(&self) {
+            let doc = self.crdt_doc.get().expect("crdt_doc to be set");
+            let LoroValue::Map(bookmarks) = doc.get_map(BOOKMARKS_CONTAINER_ID).get_deep_value()
+            else {
+                return;
+            };
+
+            let bookmarks = bookmarks
+                .iter()
+                .filter_map(|(id, bookmark)| {
+                    let LoroValue::Map(bookmark) = bookmark else {
+                        return None;
+                    };
+                    let Some(LoroValue::Binary(anchor)) = bookmark.get(BOOKMARK_ANCHOR_KEY) else {
+                        return None;
+                    };
+                    let position = self.anchor_position(&Cursor::decode(anchor).ok()?)?;
+                    let name = match bookmark.get(BOOKMARK_NAME_KEY) {
+                        Some(LoroValue::String(name)) => name.to_string(),
+                        _ => String::new(),
+                    };
+                    Some((id.to_string(), name, position as i32))
+                })
+                .collect();
+            self.bookmarks.update(bookmarks);
+        }
+
+        /// Read the checkpoints from the CRDT again, e.g. after one was created.
+        fn update_checkpoints(&self) {
+            let doc = self.crdt_doc.get().expect("crdt_doc to be set");
+            let LoroValue::Map(checkpoints) =
+                doc.get_map(CHECKPOINTS_CONTAINER_ID).get_deep_value()
+            else {
+                return;
+            };
+
+            let checkpoints = checkpoints
+                .iter()
+                .filter_map(|(id, checkpoint)| {
+                    let LoroValue::Map(checkpoint) = checkpoint else {
+                        return None;
+                    };
+                    let Some(LoroValue::Binary(version)) = checkpoint.get(CHECKPOINT_VERSION_KEY)
+                    else {
+                        return None;
+                    };
+                    let name = match checkpoint.get(CHECKPOINT_NAME_KEY) {
+                        Some(LoroValue::String(name)) => name.to_string(),
+                        _ => String::new(),
+                    };
+                    let created_at = match checkpoint.get(CHECKPOINT_CREATED_AT_KEY) {
+                        Some(LoroValue::I64(created_at)) => *created_at,
+                        _ => 0,
+                    };
+                    Some((id.to_string(), name, created_at, version.to_vec()))
+                })
+                .collect();
+            self.checkpoints.update(checkpoints);
+        }
+
+        fn set_last_edited_now(&self) {
+            *self.last_edited.lock().unwrap() = glib::DateTime::now_utc().ok();
+            self.obj().notify_last_edited();
+        }
+
+        /// Whether a change at `pos` might change the name of the document.
+        fn affects_name(&self, pos: i32) -> bool {
+            if pos <= DOCUMENT_NAME_LENGTH as i32 {
+                return true;
+            }
+
+            // The name comes from the title in the frontmatter or the line following it
+            let text = self
+                .crdt_doc
+                .get()
+                .expect("crdt_doc to be set")
+                .get_text(TEXT_CONTAINER_ID);
+            if !text.slice(0, 4).is_ok_and(|start| start == "---\n") {
+                return false;
+            }
+            parse_frontmatter(&text.to_string()).is_some_and(|frontmatter| {
+                pos as usize <= frontmatter.block_len() + DOCUMENT_NAME_LENGTH
+            })
+        }
+
+        fn queue_text_change(&self, change: TextChange) {
+            let version = self.queued_text_version.get() + 1;
+            self.queued_text_version.set(version);
+            self.text_changes.borrow_mut().push_back((version, change));
+        }
+
+        /// Emit the queued changes of the text in order, followed by a notification of `text`
+        /// once all of them were emitted.
+        ///
+        /// Does nothing when called from a handler of the signals, the queue is already being
+        /// emitted then.
+        fn emit_text_changes(&self) {
+            if self.emitting_text_changes.replace(true) {
+                return;
+            }
+
+            loop {
+                let Some((version, change)) = self.text_changes.borrow_mut().pop_front() else {
+                    break;
+                };
+                self.text_version.set(version);
+                match change {
+                    TextChange::Inserted { pos, text } => {
+                        if self.affects_name(pos) {
+                            self.update_name();
+                        }
+                        self.obj()
+                            .emit_by_name::<()>("text-inserted", &[&pos, &text, &version]);
+                    }
+                    TextChange::Deleted { start, end } => {
+                        if self.affects_name(start) {
+                            self.update_name();
+                        }
+                        self.obj()
+                            .emit_by_name::<()>("range-deleted", &[&start, &end, &version]);
+                    }
+                }
+            }
+
+            self.emitting_text_changes.set(false);
+            self.obj().notify_text_version();
+            self.obj().notify_text();
+            self.obj().notify_frontmatter();
+        }
+
+        /// The text and the version of the last change it contains.
+        ///
+        /// The version might be ahead of `text-version` while changes are still being emitted,
+        /// signals with a version up to this one are already contained in the text.
+        pub(super) fn text_with_version(&self) -> (String, u64) {
+            (self.text(), self.queued_text_version.get())
+        }
+
+        /// Remember which version of the document `author` has seen.
+        pub(super) fn set_seen_version(&self, author: &Author, version: VersionVector) {
+            let doc = self.crdt_doc.get().expect("crdt_doc to be set");
+            author.set_changes_behind(changes_missing(doc, &version));
+            if self.pending_local_changes.get() > 0 && has_own_changes(doc, &version) {
+                self.pending_local_changes.set(0);
+                self.obj().notify_pending_local_changes();
+            }
+
+            let mut seen_versions = self.seen_versions.lock().unwrap();
+            seen_versions.retain(|(other, _)| other != author);
+            seen_versions.push((author.clone(), version));
+        }
+
+        /// Our copy of the document changed, update how far behind other authors are and tell
+        /// them about it.
+        pub(super) fn version_changed(&self) {
+            let doc = self.crdt_doc.get().expect("crdt_doc to be set");
+            for (author, version) in self.seen_versions.lock().unwrap().iter() {
+                author.set_changes_behind(changes_missing(doc, version));
+            }
+
+            self.schedule_receipt();
+        }
+
+        /// Broadcast a read receipt soon, if the document is shown.
+        pub(super) fn schedule_receipt(&self) {
+            if self.views.get() == 0 || !self.subscribed.get() {
+                return;
+            }
+
+            let mut receipt_task = self.receipt_task.lock().unwrap();
+            if receipt_task.is_none() {
+                let obj = self.obj();
+                let handle = glib::spawn_future_local(clone!(
+                    #[weak]
+                    obj,
+                    async move {
+                        glib::timeout_future(RECEIPT_TIMEOUT).await;
+                        obj.imp().receipt_task.lock().unwrap().take();
+
+                        let doc = obj.imp().crdt_doc.get().expect("crdt_doc to be set");
+                        let receipt = encode_receipt(&doc.oplog_vv());
+                        if let Err(error) = obj.send_ephemeral_message(receipt).await {
+                            warn!("Failed to send read receipt: {error}");
+                        }
+                    }
+                ));
+
+                *receipt_task = handle.into_source_id().ok();
+            }
+        }
+
+        /// Tell the peers of the document whether we're away, if it's shown.
+        pub(super) fn send_presence(&self, presence: Presence) {
+            if self.views.get() == 0 || !self.subscribed.get() {
+                return;
+            }
+
+            let obj = self.obj();
+            glib::spawn_future_local(clone!(
+                #[weak]
+                obj,
+                async move {
+                    if let Err(error) = obj.send_ephemeral_message(encode_presence(presence)).await
+                    {
+                        warn!("Failed to send presence: {error}");
+                    }
+                }
+            ));
+        }
+
+        /// Peers joined, let them know if we're away, they assume we're active otherwise.
+        ///
+        /// Anonymous sessions are announced as well, before anything else.
+        pub(super) fn announce_presence(&self) {
+            let service = self.obj().service();
+            if service.anonymous() && self.views.get() > 0 && self.subscribed.get() {
+                let obj = self.obj();
+                glib::spawn_future_local(clone!(
+                    #[weak]
+                    obj,
+                    async move {
+                        let announcement = encode_anonymous_announcement();
+                        if let Err(error) = obj.send_ephemeral_message(announcement).await {
+                            warn!("Failed to announce anonymous session: {error}");
+                        }
+                    }
+                ));
+            }
+            if service.presence() == Some(Presence::Away) {
+                self.send_presence(Presence::Away);
+            }
+        }
+
+        pub(super) fn mark_for_snapshot(&self) {
+            let mut snapshot_task = self.snapshot_task.lock().unwrap();
+            if snapshot_task.is_none() {
+                let obj = self.obj();
+                let ctx = glib::MainContext::ref_thread_default();
+                let handle = ctx.spawn_with_priority(
+                    glib::source::Priority::LOW,
+                    clone!(
+                        #[weak]
+                        obj,
+                        async move {
+                            glib::timeout_future_with_priority(
+                                glib::source::Priority::LOW,
+                                SNAPSHOT_TIMEOUT,
+                            )
+                            .await;
+                            obj.store_snapshot().await;
+                            obj.imp().snapshot_task.lock().unwrap().take();
+                        }
+                    ),
+                );
+
+                *snapshot_task = handle.into_source_id().ok();
+            }
+        }
+
+        fn setup_loro_document(&self) {
+            let public_key = self.obj().service().private_key().public_key();
+            let obj = self.obj();
+            let doc = LoroDoc::new();
+            // The peer id represents the identity of the author applying local changes (that's
+            // essentially us), it needs be strictly unique.
+            doc.set_peer_id(peer_id(&public_key))
+                .expect("set peer id for new document");
+            // Shown as time of the changes in the exported history
+            doc.set_record_timestamp(true);
+            // Metadata of tasks shouldn't spread to text typed right before or after a task
+            let mut text_styles = StyleConfigMap::new();
+            for key in [ASSIGNEE_MARK, DUE_DATE_MARK] {
+                text_styles.insert(
+                    key.into(),
+                    StyleConfig {
+                        expand: ExpandType::None,
+                    },
+                );
+            }
+            doc.config_text_style(text_styles);
+
+            // Loro requires its callbacks to be `Send`, but only calls them on this thread while
+            // we change the document
+            let weak = glib::SendWeakRef::from(obj.downgrade());
+
+            let text = doc.get_text(TEXT_CONTAINER_ID);
             doc.subscribe(
                 &text.id(),
                 Arc::new(clone!(
-                    #[weak]
-                    obj,
+                    #[strong]
+                    weak,
                     move |loro_event| {
+                        let Some(obj) = weak.upgrade() else {
+                            return;
+                        };
                         let text_deltas = loro_event.events.into_iter().filter_map(|event| {
                             if event.is_unknown {
                                 return None;
@@ -328,42 +1324,149 @@ mod imp {
                                         index += retain;
                                     }
                                     loro::TextDelta::Insert { insert, .. } => {
-                                        let len = insert.len();
-                                        obj.imp().emit_text_inserted(index as i32, insert);
+                                        // Positions are in characters, not bytes
+                                        let len = insert.chars().count();
+                                        obj.imp().queue_text_change(TextChange::Inserted {
+                                            pos: index as i32,
+                                            text: insert,
+                                        });
+                                        obj.imp().add_remote_change(index, index + len);
                                         index += len;
+                                        obj.imp().last_change.set(Some(index));
                                     }
                                     loro::TextDelta::Delete { delete } => {
-                                        obj.imp().emit_range_deleted(
-                                            index as i32,
-                                            (index + delete) as i32,
-                                        );
+                                        obj.imp().queue_text_change(TextChange::Deleted {
+                                            start: index as i32,
+                                            end: (index + delete) as i32,
+                                        });
+                                        obj.imp().add_remote_change(index, index);
+                                        obj.imp().last_change.set(Some(index));
                                     }
                                 }
                             }
                         }
-                        obj.notify_text();
+                        obj.imp().update_last_edit_positions();
+                        obj.imp().update_bookmarks();
+                        obj.imp().emit_text_changes();
+                    }
+                )),
+            )
+            .detach();
+
+            let settings = doc.get_map(SETTINGS_CONTAINER_ID);
+            doc.subscribe(
+                &settings.id(),
+                Arc::new(clone!(
+                    #[strong]
+                    weak,
+                    move |_| {
+                        let Some(obj) = weak.upgrade() else {
+                            return;
+                        };
+                        obj.notify_meeting_notes();
+                        obj.notify_heading_style();
+                        obj.notify_list_marker();
+                        obj.notify_wrap_column();
+                    }
+                )),
+            )
+            .detach();
+
+            // Events of nested containers, e.g. votes, are delivered to the parent as well
+            let polls = doc.get_map(POLLS_CONTAINER_ID);
+            doc.subscribe(
+                &polls.id(),
+                Arc::new(clone!(
+                    #[strong]
+                    weak,
+                    move |_| {
+                        let Some(obj) = weak.upgrade() else {
+                            return;
+                        };
+                        obj.emit_by_name::<()>("polls-changed", &[]);
+                    }
+                )),
+            )
+            .detach();
+
+            let title = doc.get_text(TITLE_CONTAINER_ID);
+            doc.subscribe(
+                &title.id(),
+                Arc::new(clone!(
+                    #[strong]
+                    weak,
+                    move |_| {
+                        let Some(obj) = weak.upgrade() else {
+                            return;
+                        };
+                        obj.imp().update_name();
+                        obj.notify_title();
+                    }
+                )),
+            )
+            .detach();
+
+            let bookmarks = doc.get_map(BOOKMARKS_CONTAINER_ID);
+            doc.subscribe(
+                &bookmarks.id(),
+                Arc::new(clone!(
+                    #[strong]
+                    weak,
+                    move |_| {
+                        if let Some(obj) = weak.upgrade() {
+                            obj.imp().update_bookmarks();
+                        }
+                    }
+                )),
+            )
+            .detach();
+
+            let checkpoints = doc.get_map(CHECKPOINTS_CONTAINER_ID);
+            doc.subscribe(
+                &checkpoints.id(),
+                Arc::new(clone!(
+                    #[strong]
+                    weak,
+                    move |_| {
+                        if let Some(obj) = weak.upgrade() {
+                            obj.imp().update_checkpoints();
+                        }
                     }
                 )),
             )
             .detach();
 
             doc.subscribe_local_update(Box::new(clone!(
-                #[weak]
-                obj,
-                #[upgrade_or]
-                false,
+                #[strong]
+                weak,
                 move |delta_bytes| {
+                    let Some(obj) = weak.upgrade() else {
+                        return false;
+                    };
                     let delta_bytes = delta_bytes.to_vec();
+                    let edit = obj.profiler().delta_encoded();
+                    // The node stores when remote changes arrive, we only know about ours here
+                    obj.imp().set_last_edited_now();
                     obj.imp().mark_for_snapshot();
+                    obj.imp().version_changed();
+                    if obj.peers().n_items() == 0 {
+                        let pending = obj.imp().pending_local_changes.get();
+                        obj.imp().pending_local_changes.set(pending + 1);
+                        obj.notify_pending_local_changes();
+                    }
                     // Move a strong reference to the Document into the spawn,
                     // to ensure changes are always propagated to the network
-                    glib::spawn_future(async move {
+                    glib::spawn_future_local(async move {
                         // Broadcast a "text delta" to all peers
+                        let started = Instant::now();
                         if let Err(error) =
                             obj.service().node().delta(obj.id().0, delta_bytes).await
                         {
                             error!("Failed to send delta of document to the network: {}", error);
                         }
+                        if let Some(edit) = edit {
+                            obj.profiler().delta_sent(&edit, started.elapsed());
+                        }
                     });
 
                     true
@@ -381,12 +1484,53 @@ mod imp {
             static SIGNALS: OnceLock<Vec<Signal>> = OnceLock::new();
             SIGNALS.get_or_init(|| {
                 vec![
+                    // Text was inserted at a position, the last parameter is the version of the
+                    // change, see `Document::text_with_version()`.
                     Signal::builder("text-inserted")
-                        .param_types([glib::types::Type::I32, glib::types::Type::STRING])
+                        .param_types([
+                            glib::types::Type::I32,
+                            glib::types::Type::STRING,
+                            glib::types::Type::U64,
+                        ])
                         .build(),
+                    // Text was deleted between a start and end position, the last parameter is
+                    // the version of the change.
                     Signal::builder("range-deleted")
-                        .param_types([glib::types::Type::I32, glib::types::Type::I32])
+                        .param_types([
+                            glib::types::Type::I32,
+                            glib::types::Type::I32,
+                            glib::types::Type::U64,
+                        ])
+                        .build(),
+                    // Changes of another author were applied to the document.
+                    Signal::builder("remote-change")
+                        .param_types([Author::static_type()])
+                        .build(),
+                    // Another author checked or unchecked a task we created or are assigned to.
+                    Signal::builder("task-toggled")
+                        .param_types([
+                            Author::static_type(),
+                            glib::types::Type::STRING,
+                            glib::types::Type::BOOL,
+                        ])
+                        .build(),
+                    // Another author changed the same region of the text as we did, at the same
+                    // time. The region the merged edits cover is given as start and end.
+                    Signal::builder("conflict-detected")
+                        .param_types([
+                            glib::types::Type::I32,
+                            glib::types::Type::I32,
+                            Author::static_type(),
+                        ])
                         .build(),
+                    // A poll was added or someone voted.
+                    Signal::builder("polls-changed").build(),
+                    // An ephemeral message was received from another peer.
+                    Signal::builder("ephemeral-message")
+                        .param_types([PublicKey::static_type(), glib::Bytes::static_type()])
+                        .build(),
+                    // The current state was persisted as snapshot.
+                    Signal::builder("snapshot-stored").build(),
                 ]
             })
         }
@@ -415,7 +1559,14 @@ mod imp {
                 let authors = Authors::new();
 
                 // Add ourself to the list of authors
-                authors.add_this_device(self.obj().service().private_key().public_key());
+                let service = self.obj().service();
+                authors.add_this_device(service.private_key().public_key());
+                if service.anonymous() {
+                    if let Some(author) = authors.by_public_key(&service.private_key().public_key())
+                    {
+                        author.set_anonymous(true);
+                    }
+                }
                 authors
             });
 
@@ -432,93 +1583,1193 @@ impl Document {
         glib::Object::builder()
             .property("service", service)
             .property("id", id)
+            .property("created-at", glib::DateTime::now_utc().ok())
             .build()
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub(crate) fn with_state(
         service: &Service,
         id: Option<&DocumentId>,
         name: Option<&str>,
         last_accessed: Option<&glib::DateTime>,
+        created_at: Option<&glib::DateTime>,
+        last_edited: Option<&glib::DateTime>,
+        discoverable: bool,
+        archived: bool,
+        trashed_at: Option<&glib::DateTime>,
+        pinned: bool,
+        position: Option<i64>,
         authors: &Authors,
     ) -> Self {
-        glib::Object::builder()
+        let document: Self = glib::Object::builder()
             .property("service", service)
             .property("id", id)
             .property("authors", authors)
             .property("name", name)
             .property("last-accessed", last_accessed)
-            .build()
+            .property("created-at", created_at)
+            .property("last-edited", last_edited)
+            .build();
+        // Already stored, going through the setter would store it again
+        document.imp().discoverable.set(discoverable);
+        document.imp().archived.set(archived);
+        *document.imp().trashed_at.lock().unwrap() = trashed_at.cloned();
+        document.imp().pinned.set(pinned);
+        document.imp().position.set(position);
+        service.documents().sort(&document);
+        document
     }
 
-    pub fn insert_text(&self, pos: i32, text: &str) -> Result<()> {
-        self.imp().insert_text(pos as usize, text)
+    /// Register a view showing this document.
+    ///
+    /// The document is subscribed as long as at least one view is registered, so closing one of
+    /// multiple windows showing the same document doesn't stop syncing it.
+    pub fn add_view(&self) {
+        let imp = self.imp();
+        imp.views.set(imp.views.get() + 1);
+        self.set_subscribed(true);
     }
 
-    pub fn delete_range(&self, start_pos: i32, end_pos: i32) -> Result<()> {
-        self.imp()
-            .delete_text(start_pos as usize, (end_pos - start_pos) as usize)
-    }
+    /// Unregister a view added with [`Self::add_view`].
+    pub fn remove_view(&self) {
+        let imp = self.imp();
+        let views = imp.views.get().saturating_sub(1);
+        imp.views.set(views);
 
-    /// Persist the snapshot.
-    pub(crate) async fn store_snapshot(&self) {
-        // FIXME: only store a new snapshot if it changed since the previous snapshot
-        let snapshot_bytes = self
-            .imp()
-            .crdt_doc
-            .get()
-            .expect("crdt_doc to be set")
-            .export(ExportMode::Snapshot)
-            .expect("encoded crdt snapshot");
-        if let Err(error) = self
-            .service()
-            .node()
-            .snapshot(self.id().0, snapshot_bytes)
-            .await
-        {
-            error!(
-                "Failed to send snapshot of document to the network: {}",
-                error
-            );
+        if views == 0 {
+            self.set_subscribed(false);
         }
     }
-}
 
-unsafe impl Send for Document {}
-unsafe impl Sync for Document {}
+    /// Position in the document list after the user reordered it, `None` for documents added
+    /// since.
+    pub(crate) fn position(&self) -> Option<i64> {
+        self.imp().position.get()
+    }
 
-struct DocumentHandle(glib::WeakRef<Document>);
+    pub(crate) fn set_position(&self, position: Option<i64>) {
+        self.imp().position.set(position);
+    }
 
-impl SubscribableDocument for DocumentHandle {
-    fn bytes_received(&self, author: p2panda_core::PublicKey, data: Vec<u8>) {
-        if let Some(document) = self.0.upgrade() {
-            let context = glib::MainContext::ref_thread_default();
-            context.invoke(move || {
-                document.imp().on_remote_message(data);
-                document.authors().ensure_author(PublicKey(author));
-            });
-        }
+    /// Move the document to the trash, or restore it from the trash.
+    ///
+    /// Views should be closed when the document is moved to the trash, it isn't synced anymore.
+    pub fn set_trashed(&self, trashed: bool) {
+        self.imp().set_trashed(trashed);
     }
 
-    fn authors_joined(&self, authors: Vec<p2panda_core::PublicKey>) {
-        if let Some(document) = self.0.upgrade() {
-            let context = glib::MainContext::ref_thread_default();
-            context.invoke(move || {
+    /// When the document in the trash is deleted for good.
+    pub fn deletion_date(&self) -> Option<glib::DateTime> {
+        self.trashed_at()?
+            .add_seconds(aardvark_core::TRASH_RETENTION.as_secs_f64())
+            .ok()
+    }
+
+    pub fn insert_text(&self, pos: i32, text: &str) -> Result<()> {
+        self.imp().insert_text(pos as usize, text)
+    }
+
+    /// Insert a large text, e.g. the contents of an imported file, one paragraph at a time.
+    ///
+    /// Every paragraph becomes its own change with `origin` in the commit message, instead of a
+    /// single giant insert. The history stays as granular as if the text was typed and the
+    /// operations stay small in the store and when syncing.
+    pub fn insert_paragraphs(&self, pos: i32, text: &str, origin: &str) -> Result<()> {
+        self.imp().insert_paragraphs(pos as usize, text, origin)
+    }
+
+    /// Delete the text from `start_pos` to `end_pos`.
+    ///
+    /// The range is extended to whole grapheme clusters, so no part of an emoji or a character
+    /// with combining marks is left behind. Returns the range which was actually deleted.
+    pub fn delete_range(&self, start_pos: i32, end_pos: i32) -> Result<(i32, i32)> {
+        let (start, end) = self
+            .imp()
+            .delete_text(start_pos as usize, end_pos as usize)?;
+        Ok((start as i32, end as i32))
+    }
+
+    /// Replace `delete_len` characters at `pos` with `text`.
+    ///
+    /// Other authors receive a single change instead of a deletion followed by an insertion.
+    pub fn splice(&self, pos: i32, delete_len: i32, text: &str) -> Result<()> {
+        self.imp().splice(pos as usize, delete_len as usize, text)
+    }
+
+    /// Make several changes to the text which are committed and sent to other authors as one
+    /// change, e.g. when replacing all occurrences of a word.
+    ///
+    /// Positions passed to the transaction refer to the text including the changes made so far
+    /// in the same transaction. The `text-inserted` and `range-deleted` signals are only emitted
+    /// once the transaction is committed. Large texts should be inserted with
+    /// [`Self::insert_text()`] instead, which splits them into several changes.
+    pub fn transaction<T>(
+        &self,
+        edit: impl FnOnce(&mut TextTransaction) -> Result<T>,
+    ) -> Result<T> {
+        self.imp().transaction(edit)
+    }
+
+    /// Broadcast a message to all peers currently subscribed to this document.
+    ///
+    /// The message is not persisted, peers which are not online will never receive it.
+    pub async fn send_ephemeral_message(&self, bytes: Vec<u8>) -> Result<()> {
+        self.service()
+            .send_ephemeral_message(&self.id(), bytes)
+            .await
+    }
+
+    /// Broadcast our presence to the peers of the document, if it's shown.
+    pub(crate) fn send_presence(&self, presence: Presence) {
+        self.imp().send_presence(presence);
+    }
+
+    /// The text together with the version of the last change it contains.
+    ///
+    /// Handlers of `text-inserted` and `range-deleted` may read the text while later changes
+    /// are still queued, the text is ahead of the signals then. Changes with a version up to the
+    /// returned one are already part of the text and must not be applied to a copy of it again.
+    pub fn text_with_version(&self) -> (String, u64) {
+        self.imp().text_with_version()
+    }
+
+    /// Connect to the signal emitted when an ephemeral message from another peer was received.
+    pub fn connect_ephemeral_message<F: Fn(&Self, &PublicKey, &glib::Bytes) + 'static>(
+        &self,
+        f: F,
+    ) -> glib::SignalHandlerId {
+        self.connect_closure(
+            "ephemeral-message",
+            false,
+            glib::closure_local!(move |obj: Self, author: PublicKey, bytes: glib::Bytes| {
+                f(&obj, &author, &bytes);
+            }),
+        )
+    }
+
+    /// All checkbox items in the text of this document.
+    pub fn tasks(&self) -> Vec<Task> {
+        let doc = self.imp().crdt_doc.get().expect("crdt_doc to be set");
+        let text = doc.get_text(imp::TEXT_CONTAINER_ID);
+        let mut tasks = extract_tasks(&text.to_string());
+
+        // Collect the ranges of marked text, a mark applies to the task it overlaps with
+        let mut marks = Vec::new();
+        let mut offset = 0;
+        for delta in text.to_delta() {
+            let loro::TextDelta::Insert { insert, attributes } = delta else {
+                continue;
+            };
+            let end = offset + insert.chars().count();
+            if let Some(attributes) = attributes {
+                marks.push((offset..end, attributes));
+            }
+            offset = end;
+        }
+
+        for task in &mut tasks {
+            let range = task.text_range();
+            let mark = |key: &str| {
+                marks
+                    .iter()
+                    .filter(|(marked, _)| marked.start < range.end && range.start < marked.end)
+                    .find_map(|(_, attributes)| match attributes.get(key) {
+                        Some(LoroValue::String(value)) => Some(value.to_string()),
+                        _ => None,
+                    })
+            };
+            task.assignee = mark(imp::ASSIGNEE_MARK).and_then(|value| value.parse().ok());
+            task.due_date = mark(imp::DUE_DATE_MARK).and_then(|value| parse_due_date(&value));
+        }
+
+        tasks
+    }
+
+    /// Assign a task to an author, or remove the assignee if `assignee` is `None`.
+    pub fn assign_task(&self, task: &Task, assignee: Option<&PublicKey>) -> Result<()> {
+        self.set_task_mark(
+            task,
+            imp::ASSIGNEE_MARK,
+            assignee.map(|assignee| assignee.to_string()),
+        )
+    }
+
+    /// Set the day a task should be done by, or remove it if `due_date` is `None`.
+    pub fn set_task_due_date(&self, task: &Task, due_date: Option<&glib::DateTime>) -> Result<()> {
+        self.set_task_mark(task, imp::DUE_DATE_MARK, due_date.map(format_due_date))
+    }
+
+    fn set_task_mark(&self, task: &Task, key: &str, value: Option<String>) -> Result<()> {
+        let doc = self.imp().crdt_doc.get().expect("crdt_doc to be set");
+        let text = doc.get_text(imp::TEXT_CONTAINER_ID);
+
+        match value {
+            Some(value) => text.mark(task.text_range(), key, value)?,
+            None => text.unmark(task.text_range(), key)?,
+        }
+        doc.commit();
+
+        Ok(())
+    }
+
+    /// Check or uncheck a task.
+    pub fn set_task_checked(&self, task: &Task, checked: bool) -> Result<()> {
+        if task.checked == checked {
+            return Ok(());
+        }
+
+        let marker_offset = task.marker_offset as i32;
+        self.splice(marker_offset, 1, if checked { "x" } else { " " })
+    }
+
+    /// Connect to the signal emitted when changes of another author were applied.
+    pub fn connect_remote_change<F: Fn(&Self, &Author) + 'static>(
+        &self,
+        f: F,
+    ) -> glib::SignalHandlerId {
+        self.connect_closure(
+            "remote-change",
+            false,
+            glib::closure_local!(move |obj: Self, author: Author| {
+                f(&obj, &author);
+            }),
+        )
+    }
+
+    /// Connect to the signal emitted when another author toggled a task we created or which is
+    /// assigned to us.
+    pub fn connect_task_toggled<F: Fn(&Self, &Author, &str, bool) + 'static>(
+        &self,
+        f: F,
+    ) -> glib::SignalHandlerId {
+        self.connect_closure(
+            "task-toggled",
+            false,
+            glib::closure_local!(
+                move |obj: Self, author: Author, text: String, checked: bool| {
+                    f(&obj, &author, &text, checked);
+                }
+            ),
+        )
+    }
+
+    /// Connect to the signal emitted when another author edited the same region of the text as
+    /// we did, concurrently. The CRDT merged both edits, the user might want to review the result.
+    pub fn connect_conflict_detected<F: Fn(&Self, i32, i32, &Author) + 'static>(
+        &self,
+        f: F,
+    ) -> glib::SignalHandlerId {
+        self.connect_closure(
+            "conflict-detected",
+            false,
+            glib::closure_local!(move |obj: Self, start: i32, end: i32, author: Author| {
+                f(&obj, start, end, &author);
+            }),
+        )
+    }
+
+    /// Apply an event the node sent for this document.
+    fn handle_node_event(&self, event: NodeEvent) {
+        match event {
+            NodeEvent::BytesReceived {
+                author,
+                data,
+                received,
+            } => {
+                let started = Instant::now();
+                let result = self.imp().on_remote_message(data);
+                self.profiler()
+                    .patch_applied(started - received, started.elapsed());
+                // Loro rejects updates encoded with an unknown version of its format
+                if let Err(error) = result {
+                    self.authors().set_incompatible(author, error.to_string());
+                } else {
+                    let remote_changes = self
+                        .imp()
+                        .remote_changes
+                        .borrow()
+                        .clone()
+                        .unwrap_or_default();
+                    self.authors().set_contributed(author.clone());
+                    if let Some(author) = self.authors().by_public_key(&author) {
+                        author.set_seen_now();
+                        author.set_away(false);
+                        self.imp().set_last_edit(&author);
+                        self.imp().detect_conflicts(&author);
+                        self.imp().version_changed();
+                        self.emit_by_name::<()>("remote-change", &[&author]);
+                    }
+                    self.emit_toggled_tasks(&remote_changes);
+                }
+            }
+            NodeEvent::EphemeralBytesReceived { author, data } => {
+                let sender = self.authors().by_public_key(&author);
+                if let Some(sender) = sender.as_ref() {
+                    sender.set_seen_now();
+                }
+                if let Some(version) = decode_receipt(&data) {
+                    if let Some(sender) = sender.as_ref() {
+                        self.imp().set_seen_version(sender, version);
+                    }
+                    return;
+                }
+                if is_anonymous_announcement(&data) {
+                    if let Some(sender) = sender.as_ref() {
+                        sender.set_anonymous(true);
+                    }
+                    return;
+                }
+                if let Some(presence) = decode_presence(&data) {
+                    if let Some(sender) = sender.as_ref() {
+                        sender.set_away(presence == Presence::Away);
+                    }
+                    return;
+                }
+
+                self.emit_by_name::<()>(
+                    "ephemeral-message",
+                    &[&author, &glib::Bytes::from_owned(data)],
+                );
+            }
+            NodeEvent::AuthorIncompatible { author, reason } => {
+                self.authors().set_incompatible(author, reason);
+            }
+            NodeEvent::AuthorsJoined(authors) => {
+                if !authors.is_empty() {
+                    self.imp().set_reconnect_attempt(0);
+                }
                 for author in authors.into_iter() {
-                    document.authors().add_or_update(PublicKey(author), true);
+                    self.peers().set_connected(&self.authors(), author, true);
                 }
-            });
+                // Let them know how far we are and whether we're around
+                self.imp().schedule_receipt();
+                self.imp().announce_presence();
+            }
+            NodeEvent::AuthorSetOnline { author, is_online } => {
+                self.peers()
+                    .set_connected(&self.authors(), author, is_online);
+                if is_online {
+                    self.imp().set_reconnect_attempt(0);
+                    self.imp().schedule_receipt();
+                    self.imp().announce_presence();
+                }
+            }
+            NodeEvent::Reconnecting { attempt, delay } => {
+                debug!(document_id = %self.id(), attempt, ?delay, "reconnecting");
+                self.imp().set_reconnect_attempt(attempt);
+            }
+            NodeEvent::SyncProgress(progress) => {
+                self.imp().set_sync_progress(Some(progress));
+            }
+            NodeEvent::HistoryProgress(progress) => {
+                self.imp().set_history_progress(progress);
+            }
         }
     }
 
-    fn author_set_online(&self, author: p2panda_core::PublicKey, is_online: bool) {
-        if let Some(document) = self.0.upgrade() {
-            let context = glib::MainContext::ref_thread_default();
-            context.invoke(move || {
-                document
-                    .authors()
-                    .add_or_update(PublicKey(author), is_online);
+    /// Notify about tasks we created or are assigned to which someone else toggled with the
+    /// remote message just applied, `remote_changes` are the regions of the text it changed.
+    ///
+    /// Toggling replaces the character between the brackets, so a task was toggled if the message
+    /// inserted that character but neither the brackets nor the description, otherwise the task
+    /// was written just now. Most messages don't touch a checkbox at all, the text is only
+    /// searched for tasks if one of them does.
+    ///
+    /// The CRDT knows who inserted every character, so the author of the checkbox marker is the
+    /// one who toggled the task, the author of the description is the one who created it.
+    fn emit_toggled_tasks(&self, remote_changes: &[(usize, usize)]) {
+        let doc = self.imp().crdt_doc.get().expect("crdt_doc to be set");
+        let text = doc.get_text(imp::TEXT_CONTAINER_ID);
+        let inserted = |pos: usize| {
+            remote_changes
+                .iter()
+                .any(|(start, end)| (*start..*end).contains(&pos))
+        };
+        let toggled = |marker_offset: usize| {
+            inserted(marker_offset) && !inserted(marker_offset - 1) && !inserted(marker_offset + 1)
+        };
+
+        let len = text.len_unicode();
+        let touches_checkbox = remote_changes
+            .iter()
+            .filter(|(start, end)| start < end)
+            .any(|&(start, end)| {
+                let from = start.saturating_sub(1);
+                text.slice(from, (end + 1).min(len)).is_ok_and(|slice| {
+                    let chars: Vec<char> = slice.chars().collect();
+                    chars.windows(3).enumerate().any(|(index, window)| {
+                        matches!(window, ['[', ' ' | 'x' | 'X', ']']) && toggled(from + index + 1)
+                    })
+                })
             });
+        if !touches_checkbox {
+            return;
+        }
+
+        let own_peer_id = doc.peer_id();
+        let own_public_key = self.service().private_key().public_key();
+        for task in self.tasks() {
+            if !toggled(task.marker_offset) || inserted(task.text_offset) {
+                continue;
+            }
+
+            let created_by_us =
+                text.get_editor_at_unicode_pos(task.text_offset) == Some(own_peer_id);
+            let assigned_to_us = task.assignee.as_ref() == Some(&own_public_key);
+            let Some(toggled_by) = text.get_editor_at_unicode_pos(task.marker_offset) else {
+                continue;
+            };
+            if !(created_by_us || assigned_to_us) || toggled_by == own_peer_id {
+                continue;
+            }
+
+            let author = self
+                .authors()
+                .iter::<Author>()
+                .filter_map(Result::ok)
+                .find(|author| peer_id(&author.public_key()) == toggled_by);
+            if let Some(author) = author {
+                self.emit_by_name::<()>("task-toggled", &[&author, &task.text, &task.checked]);
+            }
         }
     }
+
+    /// Set a top-level key of the frontmatter, or remove it if `value` is `None`.
+    ///
+    /// A frontmatter block is added to the top of the text if there is none yet. Only the line
+    /// of the key is changed, the rest of the block stays as it is.
+    pub fn set_frontmatter_value(&self, key: &str, value: Option<&str>) -> Result<()> {
+        let frontmatter = self.frontmatter();
+        let range = frontmatter
+            .as_ref()
+            .and_then(|frontmatter| frontmatter.entry_range(key));
+
+        self.transaction(|transaction| {
+            match (frontmatter, range, value) {
+                (_, Some(range), value) => {
+                    transaction.delete_range(range.start as i32, range.end as i32)?;
+                    if let Some(value) = value {
+                        transaction.insert_text(range.start as i32, &format_entry(key, value))?;
+                    }
+                }
+                (Some(frontmatter), None, Some(value)) => {
+                    let offset = frontmatter.closing_offset() as i32;
+                    transaction.insert_text(offset, &format_entry(key, value))?;
+                }
+                (None, None, Some(value)) => transaction.insert_text(0, &new_block(key, value))?,
+                (_, None, None) => {}
+            }
+
+            Ok(())
+        })
+    }
+
+    /// All polls referenced in the text of this document, in the order they appear.
+    pub fn polls(&self) -> Vec<Poll> {
+        extract_poll_ids(&self.text())
+            .into_iter()
+            .filter_map(|id| self.poll(id))
+            .collect()
+    }
+
+    /// The poll with the given id, if we know about it already.
+    pub fn poll(&self, id: &str) -> Option<Poll> {
+        let doc = self.imp().crdt_doc.get().expect("crdt_doc to be set");
+        let LoroValue::Map(polls) = doc.get_map(imp::POLLS_CONTAINER_ID).get_deep_value() else {
+            return None;
+        };
+        let LoroValue::Map(poll) = polls.get(id)? else {
+            return None;
+        };
+
+        let question = match poll.get(imp::POLL_QUESTION_KEY) {
+            Some(LoroValue::String(question)) => question.to_string(),
+            _ => String::new(),
+        };
+        let options = match poll.get(imp::POLL_OPTIONS_KEY) {
+            Some(LoroValue::List(options)) => options
+                .iter()
+                .filter_map(|option| match option {
+                    LoroValue::String(option) => Some(option.to_string()),
+                    _ => None,
+                })
+                .collect(),
+            _ => Vec::new(),
+        };
+        let votes = match poll.get(imp::POLL_VOTES_KEY) {
+            Some(LoroValue::Map(votes)) => votes
+                .iter()
+                .filter_map(|(author, option)| {
+                    let LoroValue::I64(option) = option else {
+                        return None;
+                    };
+                    Some((author.parse().ok()?, usize::try_from(*option).ok()?))
+                })
+                .collect(),
+            _ => Vec::new(),
+        };
+
+        Some(Poll {
+            id: id.to_owned(),
+            question,
+            options,
+            votes,
+        })
+    }
+
+    /// Create a poll and insert a line referencing it at `pos`, which should be the start of a
+    /// line.
+    ///
+    /// Returns the id of the new poll.
+    pub fn create_poll(&self, pos: i32, question: &str, options: &[String]) -> Result<String> {
+        let doc = self.imp().crdt_doc.get().expect("crdt_doc to be set");
+        let id = glib::uuid_string_random().replace('-', "");
+
+        let poll = doc
+            .get_map(imp::POLLS_CONTAINER_ID)
+            .insert_container(&id, LoroMap::new())?;
+        poll.insert(imp::POLL_QUESTION_KEY, question)?;
+        let list = poll.insert_container(imp::POLL_OPTIONS_KEY, LoroList::new())?;
+        for option in options {
+            list.push(option.as_str())?;
+        }
+        poll.insert_container(imp::POLL_VOTES_KEY, LoroMap::new())?;
+        doc.commit();
+
+        self.insert_text(pos, &format!("{}\n", poll_marker(&id)))?;
+
+        Ok(id)
+    }
+
+    /// Vote for an option of a poll, or withdraw our vote if `option` is `None`.
+    pub fn vote(&self, poll: &Poll, option: Option<usize>) -> Result<()> {
+        let doc = self.imp().crdt_doc.get().expect("crdt_doc to be set");
+        let votes = doc
+            .get_map(imp::POLLS_CONTAINER_ID)
+            .get_or_create_container(&poll.id, LoroMap::new())?
+            .get_or_create_container(imp::POLL_VOTES_KEY, LoroMap::new())?;
+
+        let public_key = self.service().private_key().public_key().to_string();
+        match option {
+            Some(option) => votes.insert(&public_key, option as i64)?,
+            None => votes.delete(&public_key)?,
+        }
+        doc.commit();
+
+        Ok(())
+    }
+
+    /// Connect to the signal emitted when a poll was added or someone voted.
+    pub fn connect_polls_changed<F: Fn(&Self) + 'static>(&self, f: F) -> glib::SignalHandlerId {
+        self.connect_closure(
+            "polls-changed",
+            false,
+            glib::closure_local!(move |obj: Self| {
+                f(&obj);
+            }),
+        )
+    }
+
+    /// An anchor in front of the character at `pos`.
+    ///
+    /// Unlike a position it isn't moved by inserting or deleting text before it, so comments,
+    /// search results or cursors of other authors stay where they belong.
+    pub fn anchor_at(&self, pos: i32) -> Option<Anchor> {
+        self.imp().anchor_at(pos as usize).map(Anchor)
+    }
+
+    /// Current position of `anchor` in characters, if it belongs to this document.
+    ///
+    /// If the character the anchor was placed at was deleted, this is where it used to be.
+    pub fn resolve(&self, anchor: &Anchor) -> Option<i32> {
+        self.imp()
+            .anchor_position(&anchor.0)
+            .map(|position| position as i32)
+    }
+
+    /// Add a bookmark named `name` at `pos`, it's shared with all authors.
+    ///
+    /// Returns the id of the new bookmark.
+    pub fn add_bookmark(&self, pos: i32, name: &str) -> Result<String> {
+        let doc = self.imp().crdt_doc.get().expect("crdt_doc to be set");
+        let id = glib::uuid_string_random().replace('-', "");
+        let anchor = self
+            .imp()
+            .anchor_at(pos as usize)
+            .ok_or_else(|| anyhow!("no text at position {pos}"))?;
+
+        let bookmark = doc
+            .get_map(imp::BOOKMARKS_CONTAINER_ID)
+            .insert_container(&id, LoroMap::new())?;
+        bookmark.insert(imp::BOOKMARK_NAME_KEY, name)?;
+        bookmark.insert(imp::BOOKMARK_ANCHOR_KEY, anchor.encode())?;
+        doc.commit();
+
+        Ok(id)
+    }
+
+    pub fn remove_bookmark(&self, bookmark: &Bookmark) -> Result<()> {
+        let doc = self.imp().crdt_doc.get().expect("crdt_doc to be set");
+        doc.get_map(imp::BOOKMARKS_CONTAINER_ID)
+            .delete(&bookmark.id())?;
+        doc.commit();
+
+        Ok(())
+    }
+
+    /// Remember the current version of the document as checkpoint called `name`, for all authors.
+    pub fn create_checkpoint(&self, name: &str) -> Result<String> {
+        let doc = self.imp().crdt_doc.get().expect("crdt_doc to be set");
+        let id = glib::uuid_string_random().replace('-', "");
+        let created_at = glib::DateTime::now_utc()?.to_unix();
+        let version = doc.oplog_frontiers().encode();
+
+        let checkpoint = doc
+            .get_map(imp::CHECKPOINTS_CONTAINER_ID)
+            .insert_container(&id, LoroMap::new())?;
+        checkpoint.insert(imp::CHECKPOINT_NAME_KEY, name)?;
+        checkpoint.insert(imp::CHECKPOINT_VERSION_KEY, version)?;
+        checkpoint.insert(imp::CHECKPOINT_CREATED_AT_KEY, created_at)?;
+        doc.commit();
+
+        Ok(id)
+    }
+
+    /// The text of the document at `checkpoint`.
+    pub fn checkpoint_text(&self, checkpoint: &Checkpoint) -> Result<String> {
+        let doc = self.imp().crdt_doc.get().expect("crdt_doc to be set");
+        let version = Frontiers::decode(&checkpoint.version())?;
+
+        // Check out the old version on a copy, the document itself keeps following all changes
+        let fork = doc.fork();
+        fork.checkout(&version)?;
+        Ok(fork.get_text(imp::TEXT_CONTAINER_ID).to_string())
+    }
+
+    /// Compare the text at `checkpoint` with the current text, line by line.
+    pub fn diff_against(&self, checkpoint: &Checkpoint) -> Result<Vec<DiffLine>> {
+        let old = self.checkpoint_text(checkpoint)?;
+        Ok(diff_lines(&old, &self.text()))
+    }
+
+    /// Append a section for today if meeting notes are enabled and it doesn't exist yet.
+    ///
+    /// Only the online author with the lowest public key adds the section, so peers opening the
+    /// document at the same time don't add it twice.
+    pub(crate) fn insert_meeting_notes_section(&self) {
+        if !self.meeting_notes() || !self.subscribed() {
+            return;
+        }
+
+        let public_key = self.service().private_key().public_key();
+        let elected = self
+            .authors()
+            .iter::<Author>()
+            .filter_map(Result::ok)
+            .filter(|author| author.is_online() || author.is_this_device())
+            .map(|author| author.public_key())
+            .min_by(|a, b| a.as_bytes().cmp(b.as_bytes()));
+        if elected != Some(public_key) {
+            return;
+        }
+
+        // The date format doesn't depend on the locale, every peer looks for the same header
+        let Ok(today) = glib::DateTime::now_local().and_then(|now| now.format("%F")) else {
+            return;
+        };
+        let text = self.text();
+        if contains_heading(&text, 2, &today) {
+            return;
+        }
+        let header = self.heading_style().format(2, &today);
+
+        let separator = if text.is_empty() || text.ends_with("\n\n") {
+            ""
+        } else if text.ends_with('\n') {
+            "\n"
+        } else {
+            "\n\n"
+        };
+        let end = text.chars().count() as i32;
+        if let Err(error) = self.insert_text(end, &format!("{separator}{header}\n\n")) {
+            error!("Failed to add meeting notes section: {error}");
+        }
+    }
+
+    /// The current version of the document, encoded, see [`Self::authors_changed_since`].
+    pub fn version(&self) -> Vec<u8> {
+        let doc = self.imp().crdt_doc.get().expect("crdt_doc to be set");
+        doc.oplog_vv().encode()
+    }
+
+    /// Authors who changed the document since the encoded `version`, all authors who changed
+    /// it if the version can't be decoded.
+    pub fn authors_changed_since(&self, version: &[u8]) -> Vec<Author> {
+        let doc = self.imp().crdt_doc.get().expect("crdt_doc to be set");
+        let version = VersionVector::decode(version).unwrap_or_default();
+        let peers: Vec<u64> = doc
+            .oplog_vv()
+            .iter()
+            .filter(|(peer, end)| version.get(peer).copied().unwrap_or(0) < **end)
+            .map(|(peer, _)| *peer)
+            .collect();
+
+        self.authors()
+            .iter::<Author>()
+            .filter_map(Result::ok)
+            .filter(|author| peers.contains(&peer_id(&author.public_key())))
+            .collect()
+    }
+
+    /// Number of changes in the history of this document, grows with every edit of any author.
+    pub fn changes(&self) -> u64 {
+        self.imp()
+            .crdt_doc
+            .get()
+            .expect("crdt_doc to be set")
+            .len_changes() as u64
+    }
+
+    /// Human readable dump of the CRDT state for debugging.
+    pub fn crdt_state(&self) -> String {
+        let doc = self.imp().crdt_doc.get().expect("crdt_doc to be set");
+
+        format!(
+            "peer id: {}\nchanges: {}\noperations: {}\noplog version: {:?}\nstate version: {:?}\n\n{:#?}",
+            doc.peer_id(),
+            doc.len_changes(),
+            doc.len_ops(),
+            doc.oplog_vv(),
+            doc.state_vv(),
+            doc.get_deep_value(),
+        )
+    }
+
+    /// Show `author` as `alias` in this document, or with their regular name again if it's
+    /// `None` or blank.
+    ///
+    /// The alias is stored on this device only, peers keep seeing the author as before.
+    pub async fn set_author_alias(&self, author: &PublicKey, alias: Option<&str>) -> Result<()> {
+        let alias = alias
+            .map(str::trim)
+            .filter(|alias| !alias.is_empty())
+            .map(str::to_owned);
+        self.service()
+            .node()
+            .set_author_alias(&self.id().0, author.0, alias.clone())
+            .await?;
+        self.authors().set_alias(author, alias);
+        Ok(())
+    }
+
+    /// The most recent `limit` operations stored for this document, newest last.
+    pub async fn operations(&self, limit: usize) -> Result<Vec<OperationInfo>> {
+        self.service().node().operations(&self.id().0, limit).await
+    }
+
+    /// Gossip neighbors and known authors of this document as seen by the node.
+    pub async fn topic_info(&self) -> Result<TopicInfo> {
+        self.service().node().topic_info(&self.id().0).await
+    }
+
+    /// Merge the snapshot pinned in cold storage into this document.
+    ///
+    /// Returns `false` if there is no pin of this document.
+    pub async fn restore_from_cold_storage(&self) -> Result<bool> {
+        let Some(snapshot_bytes) = self
+            .service()
+            .node()
+            .restore_from_cold_storage(self.id().0)
+            .await?
+        else {
+            return Ok(false);
+        };
+
+        let doc = self.imp().crdt_doc.get().expect("crdt_doc to be set");
+        doc.import_with(&snapshot_bytes, "snapshot")?;
+        // Imported changes don't cause local updates, make sure they are persisted and synced
+        self.imp().mark_for_snapshot();
+
+        Ok(true)
+    }
+
+    /// Snapshots of this document stored on this device, newest first.
+    ///
+    /// Next to our own there is the latest snapshot of every other author we synced with.
+    pub async fn snapshots(&self) -> Result<Vec<SnapshotInfo>> {
+        self.service().node().list_snapshots(&self.id().0).await
+    }
+
+    /// Every change of the text as patch series for `git am`, see [`crate::history`].
+    pub fn export_history(&self) -> Result<String> {
+        let doc = self.imp().crdt_doc.get().expect("crdt_doc to be set");
+        let changes = history::changes(doc, imp::TEXT_CONTAINER_ID, &self.authors())?;
+        Ok(history::format_patches(
+            &changes,
+            &history::file_name(self.name().as_deref()),
+        ))
+    }
+
+    /// Changes of the text grouped into batches of consecutive changes by the same author, in
+    /// causal order.
+    pub fn change_batches(&self) -> Result<Vec<ChangeBatch>> {
+        let doc = self.imp().crdt_doc.get().expect("crdt_doc to be set");
+        history::change_batches(doc, imp::TEXT_CONTAINER_ID, &self.authors())
+    }
+
+    /// What `batch` changed, e.g. "+120 chars in §Introduction, −2 lines".
+    pub fn change_summary(&self, batch: &ChangeBatch) -> Result<ChangeSummary> {
+        let doc = self.imp().crdt_doc.get().expect("crdt_doc to be set");
+        history::summarize_batch(doc, imp::TEXT_CONTAINER_ID, batch)
+    }
+
+    /// Compare the current text with the text after reverting `batch`, line by line.
+    pub fn revert_preview(&self, batch: &ChangeBatch) -> Result<Vec<DiffLine>> {
+        let doc = self.imp().crdt_doc.get().expect("crdt_doc to be set");
+        let edits = history::revert_edits(doc, imp::TEXT_CONTAINER_ID, batch)?;
+
+        let fork = doc.fork();
+        let text = fork.get_text(imp::TEXT_CONTAINER_ID);
+        for edit in edits {
+            match edit {
+                RevertEdit::Delete { start, end } => text.delete(start, end - start)?,
+                RevertEdit::Insert {
+                    pos,
+                    text: inserted,
+                } => text.insert(pos, &inserted)?,
+            }
+        }
+        Ok(diff_lines(&self.text(), &text.to_string()))
+    }
+
+    /// Undo the changes of `batch` with a new change, changes made after it are kept.
+    pub fn revert_changes(&self, batch: &ChangeBatch) -> Result<()> {
+        let doc = self.imp().crdt_doc.get().expect("crdt_doc to be set");
+        let edits = history::revert_edits(doc, imp::TEXT_CONTAINER_ID, batch)?;
+        if edits.is_empty() {
+            return Ok(());
+        }
+
+        let name = batch
+            .author
+            .as_ref()
+            .map(Author::name)
+            .unwrap_or_else(|| "unknown author".to_string());
+        doc.set_next_commit_message(&format!("Revert changes by {name}"));
+        self.transaction(|transaction| {
+            for edit in edits {
+                match edit {
+                    RevertEdit::Delete { start, end } => {
+                        transaction.delete_range(start as i32, end as i32)?
+                    }
+                    RevertEdit::Insert { pos, text } => {
+                        transaction.insert_text(pos as i32, &text)?
+                    }
+                }
+            }
+            Ok(())
+        })
+    }
+
+    /// Like [`Self::export_history`], but reads the history from the stored snapshots, the
+    /// document doesn't need to be subscribed.
+    pub async fn export_stored_history(&self) -> Result<String> {
+        let doc = LoroDoc::new();
+        for snapshot in self.snapshots().await? {
+            let snapshot_bytes = self
+                .service()
+                .node()
+                .snapshot_payload(&self.id().0, &snapshot.hash)
+                .await?;
+            doc.import_with(&snapshot_bytes, "snapshot")?;
+        }
+        let changes = history::changes(&doc, imp::TEXT_CONTAINER_ID, &self.authors())?;
+        Ok(history::format_patches(
+            &changes,
+            &history::file_name(self.name().as_deref()),
+        ))
+    }
+
+    /// Load the state of the stored snapshot with `hash` into a new document.
+    ///
+    /// The new document has its own id, this document isn't changed. This allows recovering the
+    /// text e.g. after another author deleted it.
+    pub async fn restore_snapshot(&self, hash: &Hash) -> Result<Document> {
+        let snapshot_bytes = self
+            .service()
+            .node()
+            .snapshot_payload(&self.id().0, hash)
+            .await?;
+
+        let document = Document::new(&self.service(), None);
+        let doc = document.imp().crdt_doc.get().expect("crdt_doc to be set");
+        doc.import_with(&snapshot_bytes, "snapshot")?;
+        // Imported changes don't cause local updates, make sure they are persisted and synced
+        document.imp().mark_for_snapshot();
+
+        Ok(document)
+    }
+
+    /// Persist the snapshot.
+    pub(crate) async fn store_snapshot(&self) {
+        // FIXME: only store a new snapshot if it changed since the previous snapshot
+        let snapshot_bytes = self
+            .imp()
+            .crdt_doc
+            .get()
+            .expect("crdt_doc to be set")
+            .export(ExportMode::Snapshot)
+            .expect("encoded crdt snapshot");
+        if let Err(error) = self
+            .service()
+            .node()
+            .snapshot(self.id().0, snapshot_bytes)
+            .await
+        {
+            error!(
+                "Failed to send snapshot of document to the network: {}",
+                error
+            );
+            return;
+        }
+        self.emit_by_name::<()>("snapshot-stored", &[]);
+    }
+
+    pub fn connect_snapshot_stored<F: Fn(&Self) + 'static>(&self, f: F) -> glib::SignalHandlerId {
+        self.connect_closure(
+            "snapshot-stored",
+            false,
+            glib::closure_local!(move |obj: Self| {
+                f(&obj);
+            }),
+        )
+    }
+}
+
+/// Changes of the text which are committed together, see [`Document::transaction()`].
+pub struct TextTransaction<'a> {
+    document: &'a imp::Document,
+    text: LoroText,
+    /// Anchors around every change, to detect conflicts with them.
+    edits: Vec<(Cursor, Cursor)>,
+}
+
+impl TextTransaction<'_> {
+    /// The text including the changes made so far.
+    pub fn text(&self) -> String {
+        self.text.to_string()
+    }
+
+    pub fn insert_text(&mut self, pos: i32, text: &str) -> Result<()> {
+        self.splice(pos, 0, text)
+    }
+
+    pub fn delete_range(&mut self, start_pos: i32, end_pos: i32) -> Result<()> {
+        self.splice(start_pos, end_pos - start_pos, "")
+    }
+
+    /// Replace `delete_len` characters at `pos` with `text`.
+    pub fn splice(&mut self, pos: i32, delete_len: i32, text: &str) -> Result<()> {
+        let started = Instant::now();
+        self.text.splice(pos as usize, delete_len as usize, text)?;
+        self.document
+            .obj()
+            .profiler()
+            .add_crdt_apply(started.elapsed());
+
+        let start = pos as usize;
+        let end = start + text.chars().count();
+        if let (Some(start), Some(end)) =
+            (self.document.anchor_at(start), self.document.anchor_at(end))
+        {
+            self.edits.push((start, end));
+        }
+
+        Ok(())
+    }
+}
+
+/// Events of the node about a subscribed document.
+enum NodeEvent {
+    BytesReceived {
+        author: PublicKey,
+        data: Vec<u8>,
+        received: Instant,
+    },
+    EphemeralBytesReceived {
+        author: PublicKey,
+        data: Vec<u8>,
+    },
+    AuthorIncompatible {
+        author: PublicKey,
+        reason: String,
+    },
+    AuthorsJoined(Vec<PublicKey>),
+    AuthorSetOnline {
+        author: PublicKey,
+        is_online: bool,
+    },
+    Reconnecting {
+        attempt: u32,
+        delay: Duration,
+    },
+    SyncProgress(SyncProgress),
+    HistoryProgress(Option<HistoryProgress>),
+}
+
+/// Handed to the node, which calls it from its own threads.
+///
+/// The document itself never leaves the main thread, events are forwarded to it instead.
+struct DocumentHandle {
+    sender: async_channel::Sender<NodeEvent>,
+}
+
+impl DocumentHandle {
+    /// Create a handle whose events are applied to `document` on the main context until the
+    /// handle is dropped.
+    fn new(document: &Document) -> Self {
+        let (sender, receiver) = async_channel::unbounded();
+        glib::spawn_future_local(clone!(
+            #[weak]
+            document,
+            async move {
+                while let Ok(event) = receiver.recv().await {
+                    document.handle_node_event(event);
+                }
+            }
+        ));
+
+        Self { sender }
+    }
+
+    fn send(&self, event: NodeEvent) {
+        // The document is gone when nobody receives anymore
+        let _ = self.sender.try_send(event);
+    }
+}
+
+/// Loro identifies authors by a "peer id", we take the first 8 bytes of the public key (32 bytes)
+/// to keep authors apart inside the text crdt.
+///
+/// TODO(adz): This is strictly speaking not collision-resistant but we're limited here by the 8
+/// bytes / 64 bit from the u64 `PeerId` type from Loro. In practice this should not really be a
+/// problem, but it would be nice if the Loro API would change some day.
+pub(crate) fn peer_id(public_key: &PublicKey) -> u64 {
+    let mut buf = [0u8; 8];
+    buf[..8].copy_from_slice(&public_key.0.as_bytes()[..8]);
+    u64::from_be_bytes(buf)
+}
+
+/// Splits `text` into paragraphs, each including the blank lines following it.
+pub(crate) fn split_into_paragraphs(text: &str) -> impl Iterator<Item = &str> {
+    let mut rest = text;
+    std::iter::from_fn(move || {
+        if rest.is_empty() {
+            return None;
+        }
+
+        let mut end = 0;
+        let mut has_content = false;
+        let mut after_blank_line = false;
+        for line in rest.split_inclusive('\n') {
+            if line.trim().is_empty() {
+                after_blank_line = has_content;
+            } else if after_blank_line {
+                break;
+            } else {
+                has_content = true;
+            }
+            end += line.len();
+        }
+
+        let (paragraph, remaining) = rest.split_at(end);
+        rest = remaining;
+        Some(paragraph)
+    })
+}
+
+/// Splits `text` into slices of at most `max_len` bytes.
+///
+/// Slices end after a line break when possible and never in the middle of a character.
+fn split_into_chunks(text: &str, max_len: usize) -> impl Iterator<Item = &str> {
+    let mut rest = text;
+    std::iter::from_fn(move || {
+        if rest.is_empty() {
+            return None;
+        }
+        if rest.len() <= max_len {
+            return Some(std::mem::take(&mut rest));
+        }
+
+        let mut end = max_len;
+        while !rest.is_char_boundary(end) {
+            end -= 1;
+        }
+        if let Some(newline) = rest[..end].rfind('\n') {
+            end = newline + 1;
+        }
+
+        let (chunk, remaining) = rest.split_at(end);
+        rest = remaining;
+        Some(chunk)
+    })
+}
+
+/// Turns arbitrary bytes, e.g. from the clipboard or a file, into text suitable for a document.
+///
+/// Invalid UTF-8 sequences are replaced with U+FFFD and all line endings are converted to `\n`.
+pub fn normalize_text(bytes: &[u8]) -> String {
+    String::from_utf8_lossy(bytes)
+        .replace("\r\n", "\n")
+        .replace('\r', "\n")
+}
+
+impl SubscribableDocument for DocumentHandle {
+    fn bytes_received(&self, author: p2panda_core::PublicKey, data: Vec<u8>) {
+        self.send(NodeEvent::BytesReceived {
+            author: PublicKey(author),
+            data,
+            received: Instant::now(),
+        });
+    }
+
+    fn ephemeral_bytes_received(&self, author: p2panda_core::PublicKey, data: Vec<u8>) {
+        self.send(NodeEvent::EphemeralBytesReceived {
+            author: PublicKey(author),
+            data,
+        });
+    }
+
+    fn author_incompatible(&self, author: p2panda_core::PublicKey, reason: String) {
+        self.send(NodeEvent::AuthorIncompatible {
+            author: PublicKey(author),
+            reason,
+        });
+    }
+
+    fn authors_joined(&self, authors: Vec<p2panda_core::PublicKey>) {
+        self.send(NodeEvent::AuthorsJoined(
+            authors.into_iter().map(PublicKey).collect(),
+        ));
+    }
+
+    fn author_set_online(&self, author: p2panda_core::PublicKey, is_online: bool) {
+        self.send(NodeEvent::AuthorSetOnline {
+            author: PublicKey(author),
+            is_online,
+        });
+    }
+
+    fn reconnecting(&self, attempt: u32, delay: Duration) {
+        self.send(NodeEvent::Reconnecting { attempt, delay });
+    }
+
+    fn sync_progress(&self, progress: SyncProgressNode) {
+        self.send(NodeEvent::SyncProgress(SyncProgress(progress)));
+    }
+
+    fn history_progress(&self, progress: Option<HistoryProgress>) {
+        self.send(NodeEvent::HistoryProgress(progress));
+    }
 }
@@ -1,8 +1,10 @@
+use std::collections::HashMap;
 use std::sync::Mutex;
 
 use gio::prelude::*;
 use gio::subclass::prelude::ListModelImpl;
 use glib::subclass::prelude::*;
+use tracing::error;
 
 use crate::document::{Document, DocumentId};
 
@@ -12,11 +14,13 @@ mod imp {
     #[derive(Default)]
     pub struct Documents {
         pub(super) list: Mutex<Vec<Document>>,
+        /// Lookup of the single shared instance for each document id.
+        pub(super) instances: Mutex<HashMap<DocumentId, glib::WeakRef<Document>>>,
     }
 
     #[glib::object_subclass]
     impl ObjectSubclass for Documents {
-        const NAME: &'static str = "Documents";
+        const NAME: &'static str = "AardvarkDocuments";
         type Type = super::Documents;
         type Interfaces = (gio::ListModel,);
     }
@@ -48,9 +52,6 @@ glib::wrapper! {
     @implements gio::ListModel;
 }
 
-unsafe impl Send for Documents {}
-unsafe impl Sync for Documents {}
-
 impl Default for Documents {
     fn default() -> Self {
         Self::new()
@@ -63,19 +64,137 @@ impl Documents {
     }
 
     pub(crate) fn add(&self, document: Document) {
+        self.imp()
+            .instances
+            .lock()
+            .unwrap()
+            .insert(document.id(), document.downgrade());
+
+        // Let filtered views of the list move the document between their sections
+        let refilter = glib::clone!(
+            #[weak(rename_to = this)]
+            self,
+            move |document: &Document| {
+                let index = this
+                    .imp()
+                    .list
+                    .lock()
+                    .unwrap()
+                    .iter()
+                    .position(|item| item == document);
+                if let Some(index) = index {
+                    this.items_changed(index as u32, 1, 1);
+                }
+            }
+        );
+        document.connect_archived_notify(refilter.clone());
+        document.connect_trashed_at_notify(refilter);
+        document.connect_pinned_notify(glib::clone!(
+            #[weak(rename_to = this)]
+            self,
+            move |document| this.sort(document)
+        ));
+
         let mut list = self.imp().list.lock().unwrap();
 
         // FIXME: Inserting a new document at the top of the list is quite inefficient
-        list.insert(0, document.clone());
+        let index = insertion_index(&list, &document);
+        list.insert(index, document);
         drop(list);
-        self.items_changed(0, 0, 1);
+        self.items_changed(index as u32, 0, 1);
     }
 
-    pub fn by_id(&self, document_id: &DocumentId) -> Option<Document> {
-        let list = self.imp().list.lock().unwrap();
+    /// Move `document` to its place in the list after it was pinned or its stored position was
+    /// restored.
+    pub(crate) fn sort(&self, document: &Document) {
+        let mut list = self.imp().list.lock().unwrap();
+        let Some(old_index) = list.iter().position(|item| item == document) else {
+            return;
+        };
+        let document = list.remove(old_index);
+        let index = insertion_index(&list, &document);
+        list.insert(index, document);
+        drop(list);
+
+        if index != old_index {
+            self.items_changed(old_index as u32, 1, 0);
+            self.items_changed(index as u32, 0, 1);
+        }
+    }
+
+    /// Move `document` to the place of `target`, e.g. when the user drags it there.
+    ///
+    /// The document is pinned or unpinned like `target`, and the new order of all documents is
+    /// stored.
+    pub fn move_document(&self, document: &Document, target: &Document) {
+        if document == target {
+            return;
+        }
+        document.set_pinned(target.pinned());
+
+        let mut list = self.imp().list.lock().unwrap();
+        let (Some(old_index), Some(index)) = (
+            list.iter().position(|item| item == document),
+            list.iter().position(|item| item == target),
+        ) else {
+            return;
+        };
+        let document = list.remove(old_index);
+        list.insert(index, document.clone());
+
+        for (position, item) in list.iter().enumerate() {
+            item.set_position(Some(position as i64));
+        }
+        let document_ids = list.iter().map(|item| item.id().0).collect();
+        drop(list);
+
+        let start = old_index.min(index);
+        let changed = old_index.max(index) - start + 1;
+        self.items_changed(start as u32, changed as u32, changed as u32);
+
+        glib::spawn_future_local(async move {
+            if let Err(error) = document
+                .service()
+                .node()
+                .set_document_positions(document_ids)
+                .await
+            {
+                error!("Failed to store the order of documents: {}", error);
+            }
+        });
+    }
+
+    /// Remove a document which was deleted from the store.
+    pub(crate) fn remove(&self, document: &Document) {
+        self.imp().instances.lock().unwrap().remove(&document.id());
+
+        let mut list = self.imp().list.lock().unwrap();
+        let Some(index) = list.iter().position(|item| item == document) else {
+            return;
+        };
+        list.remove(index);
+        drop(list);
+        self.items_changed(index as u32, 1, 0);
+    }
 
-        list.iter()
-            .find(|document| &document.id() == document_id)
-            .cloned()
+    /// Returns the instance of the document with the given id if it exists.
+    ///
+    /// There is at most one `Document` per id, all windows showing the same document share it.
+    pub fn by_id(&self, document_id: &DocumentId) -> Option<Document> {
+        self.imp()
+            .instances
+            .lock()
+            .unwrap()
+            .get(document_id)
+            .and_then(|document| document.upgrade())
     }
 }
+
+/// Pinned documents come first, then documents added since the user reordered the list with the
+/// newest first, then all others by their position.
+fn insertion_index(list: &[Document], document: &Document) -> usize {
+    let key = |document: &Document| (!document.pinned(), document.position());
+    list.iter()
+        .position(|item| key(item) >= key(document))
+        .unwrap_or(list.len())
+}
@@ -0,0 +1,181 @@
+//! YAML frontmatter at the top of a document, as used by static site generators and Obsidian.
+//!
+//! ```text
+//! ---
+//! title: Meeting notes
+//! tags: [planning]
+//! ---
+//! ```
+//!
+//! Only top-level `key: value` pairs are exposed as metadata, everything else in the block
+//! (nested values, comments, lists) is kept as it is in the text.
+
+use std::ops::Range;
+
+const DELIMITER: &str = "---";
+/// YAML also allows closing a document with `...`.
+const CLOSING_DELIMITERS: [&str; 2] = ["---", "..."];
+
+#[derive(Clone, Debug, Default, PartialEq, glib::Boxed)]
+#[boxed_type(name = "AardvarkFrontmatter", nullable)]
+pub struct Frontmatter {
+    entries: Vec<Entry>,
+    /// Position of the closing delimiter, in unicode characters.
+    closing_offset: usize,
+    /// Length of the whole block including the closing delimiter, in unicode characters.
+    block_len: usize,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Entry {
+    key: String,
+    value: String,
+    /// Range of the line including its newline, in unicode characters.
+    range: Range<usize>,
+}
+
+impl Frontmatter {
+    /// Value of a top-level key.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.entries
+            .iter()
+            .find(|entry| entry.key == key)
+            .map(|entry| entry.value.as_str())
+    }
+
+    /// All top-level keys and their values, in the order they appear.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.entries
+            .iter()
+            .map(|entry| (entry.key.as_str(), entry.value.as_str()))
+    }
+
+    /// Length of the whole block including both delimiters, in unicode characters.
+    pub fn block_len(&self) -> usize {
+        self.block_len
+    }
+
+    /// Range of the line defining `key`, in unicode characters.
+    pub(crate) fn entry_range(&self, key: &str) -> Option<Range<usize>> {
+        self.entries
+            .iter()
+            .find(|entry| entry.key == key)
+            .map(|entry| entry.range.clone())
+    }
+
+    /// Position new entries are inserted at, right before the closing delimiter.
+    pub(crate) fn closing_offset(&self) -> usize {
+        self.closing_offset
+    }
+}
+
+/// Parse the frontmatter block at the very start of `text`, if there is one.
+pub fn parse_frontmatter(text: &str) -> Option<Frontmatter> {
+    let mut lines = text.split_inclusive('\n');
+    let first = lines.next()?;
+    if first.trim_end() != DELIMITER || !first.ends_with('\n') {
+        return None;
+    }
+
+    let mut entries = Vec::new();
+    let mut offset = first.chars().count();
+    for line in lines {
+        let line_len = line.chars().count();
+        let content = line.trim_end_matches(['\n', '\r']);
+
+        if CLOSING_DELIMITERS.contains(&content.trim_end()) {
+            return Some(Frontmatter {
+                entries,
+                closing_offset: offset,
+                block_len: offset + line_len,
+            });
+        }
+
+        if let Some((key, value)) = parse_entry(content) {
+            entries.push(Entry {
+                key,
+                value,
+                range: offset..offset + line_len,
+            });
+        }
+        offset += line_len;
+    }
+
+    // The block was never closed, it's just text starting with a horizontal rule
+    None
+}
+
+/// A `key: value` line, indented lines belong to the value of a previous key.
+fn parse_entry(line: &str) -> Option<(String, String)> {
+    if line.starts_with(char::is_whitespace) || line.starts_with(['#', '-']) {
+        return None;
+    }
+
+    let (key, value) = line.split_once(':')?;
+    let key = key.trim();
+    if key.is_empty() {
+        return None;
+    }
+    let value = strip_comment(value.trim());
+
+    Some((key.to_owned(), unquote(value)))
+}
+
+fn strip_comment(value: &str) -> &str {
+    if value.starts_with(['"', '\'']) {
+        return value;
+    }
+    match value.find(" #") {
+        Some(index) => value[..index].trim_end(),
+        None => value,
+    }
+}
+
+fn unquote(value: &str) -> String {
+    if value.len() >= 2 && value.starts_with('"') && value.ends_with('"') {
+        let mut unescaped = String::with_capacity(value.len());
+        let mut chars = value[1..value.len() - 1].chars();
+        while let Some(char) = chars.next() {
+            if char != '\\' {
+                unescaped.push(char);
+                continue;
+            }
+            match chars.next() {
+                Some('n') => unescaped.push('\n'),
+                Some('t') => unescaped.push('\t'),
+                Some(escaped) => unescaped.push(escaped),
+                None => {}
+            }
+        }
+        unescaped
+    } else if value.len() >= 2 && value.starts_with('\'') && value.ends_with('\'') {
+        value[1..value.len() - 1].replace("''", "'")
+    } else {
+        value.to_owned()
+    }
+}
+
+/// Line defining `key` as `value`, quoting the value when YAML would read it differently.
+pub(crate) fn format_entry(key: &str, value: &str) -> String {
+    let needs_quotes = value.is_empty()
+        || value != value.trim()
+        || value.contains([':', '#', '\n'])
+        || value.starts_with([
+            '"', '\'', '[', '{', '&', '*', '!', '|', '>', '%', '@', '`', '-',
+        ]);
+
+    if needs_quotes {
+        let escaped = value
+            .replace('\\', "\\\\")
+            .replace('"', "\\\"")
+            .replace('\n', "\\n");
+        format!("{key}: \"{escaped}\"\n")
+    } else {
+        format!("{key}: {value}\n")
+    }
+}
+
+/// A new frontmatter block containing a single entry.
+pub(crate) fn new_block(key: &str, value: &str) -> String {
+    format!("{DELIMITER}\n{}{DELIMITER}\n", format_entry(key, value))
+}
@@ -0,0 +1,79 @@
+//! Boundaries of grapheme clusters, the characters as perceived by the user.
+//!
+//! An emoji with a skin tone, a family joined with zero width joiners, a flag or a letter with a
+//! combining accent consist of several code points but are edited as one character. Deleting
+//! only a part of them leaves broken characters behind, e.g. a lone regional indicator.
+//!
+//! This follows the rules of UAX #29 which matter for text typed by people, it doesn't know
+//! about every combining mark or Hangul syllable composition.
+
+/// Number of characters around a range looked at when extending it to whole clusters.
+pub(crate) const CLUSTER_WINDOW: usize = 32;
+
+const ZERO_WIDTH_JOINER: char = '\u{200D}';
+
+/// Whether `c` belongs to the cluster of the character before it.
+fn is_extend(c: char) -> bool {
+    matches!(c,
+        ZERO_WIDTH_JOINER
+        // Combining diacritical marks
+        | '\u{0300}'..='\u{036F}'
+        | '\u{1AB0}'..='\u{1AFF}'
+        | '\u{1DC0}'..='\u{1DFF}'
+        // Combining marks for symbols, including the keycap
+        | '\u{20D0}'..='\u{20FF}'
+        | '\u{FE20}'..='\u{FE2F}'
+        // Variation selectors, e.g. to show a symbol as emoji
+        | '\u{FE00}'..='\u{FE0F}'
+        | '\u{E0100}'..='\u{E01EF}'
+        // Skin tones
+        | '\u{1F3FB}'..='\u{1F3FF}'
+        // Tags of subdivision flags
+        | '\u{E0020}'..='\u{E007F}'
+    )
+}
+
+fn is_regional_indicator(c: char) -> bool {
+    matches!(c, '\u{1F1E6}'..='\u{1F1FF}')
+}
+
+/// Whether a cluster starts at `pos` of `chars`.
+fn is_boundary(chars: &[char], pos: usize) -> bool {
+    if pos == 0 || pos >= chars.len() {
+        return true;
+    }
+
+    let (before, after) = (chars[pos - 1], chars[pos]);
+    if before == '\r' && after == '\n' {
+        return false;
+    }
+    if is_extend(after) || before == ZERO_WIDTH_JOINER {
+        return false;
+    }
+    if is_regional_indicator(before) && is_regional_indicator(after) {
+        // Flags are pairs of regional indicators
+        let preceding = chars[..pos]
+            .iter()
+            .rev()
+            .take_while(|c| is_regional_indicator(**c))
+            .count();
+        return preceding % 2 == 0;
+    }
+
+    true
+}
+
+/// Extend the range from `start` to `end` of `text`, in characters, to whole clusters.
+pub(crate) fn extend_to_clusters(text: &str, start: usize, end: usize) -> (usize, usize) {
+    let chars: Vec<char> = text.chars().collect();
+    let mut start = start.min(chars.len());
+    let mut end = end.clamp(start, chars.len());
+    while !is_boundary(&chars, start) {
+        start -= 1;
+    }
+    while !is_boundary(&chars, end) {
+        end += 1;
+    }
+
+    (start, end)
+}
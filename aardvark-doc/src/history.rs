@@ -0,0 +1,393 @@
+//! The history of a document as a series of patches.
+//!
+//! Every change of the text CRDT becomes a patch in the format of `git format-patch`, with the
+//! author and time of the change, so the history can be audited or applied to a git repository
+//! with `git am`. Changes are put in causal order, each patch shows the difference to the text
+//! with all previous patches applied.
+//!
+//! Changes can also be reverted in batches of consecutive changes by the same author, e.g.
+//! after someone deleted half of the text by mistake. Reverting is a new change on top of the
+//! current text, everyone else's edits made since stay in place.
+use std::cmp::Reverse;
+use std::fmt::Write;
+
+use anyhow::Result;
+use gio::prelude::ListModelExtManual;
+use loro::cursor::Side;
+use loro::event::Diff;
+use loro::{ChangeMeta, LoroDoc, LoroText, TextDelta, VersionVector};
+
+use crate::author::Author;
+use crate::authors::Authors;
+use crate::checkpoints::{DiffLine, diff_lines};
+use crate::document::peer_id;
+use crate::summary::{ChangeSummary, summarize};
+
+/// Lines around the changed ones in every hunk.
+const CONTEXT_LINES: usize = 3;
+
+/// A change of the text, see [`changes`].
+pub(crate) struct HistoryEntry {
+    pub author: Option<Author>,
+    /// Unix timestamp, if the author recorded one.
+    pub timestamp: Option<i64>,
+    pub message: Option<String>,
+    pub old: String,
+    pub new: String,
+}
+
+/// Consecutive changes of the text by the same author, which are reverted together.
+#[derive(Clone, Debug)]
+pub struct ChangeBatch {
+    pub author: Option<Author>,
+    /// Unix timestamp of the last change, if the author recorded one.
+    pub timestamp: Option<i64>,
+    /// Number of changes in the batch.
+    pub changes: u32,
+    peer: u64,
+    /// Version of the document right before the first change.
+    before: VersionVector,
+    /// Version of the document right after the last change.
+    after: VersionVector,
+}
+
+/// An edit of the current text undoing part of a [`ChangeBatch`], positions are in characters.
+#[derive(Debug, PartialEq)]
+pub(crate) enum RevertEdit {
+    Delete { start: usize, end: usize },
+    Insert { pos: usize, text: String },
+}
+
+/// All changes of `doc` in causal order.
+fn sorted_changes(doc: &LoroDoc) -> Vec<ChangeMeta> {
+    let mut metas = Vec::new();
+    for (peer, end) in doc.oplog_vv().iter() {
+        let mut counter = 0;
+        while counter < *end {
+            let Some(change) = doc.get_change(loro::ID::new(*peer, counter)) else {
+                break;
+            };
+            counter = (change.id.counter + change.len as i32).max(counter + 1);
+            metas.push(change);
+        }
+    }
+    // Lamport timestamps grow along causal dependencies, so every prefix is a valid version
+    metas.sort_by_key(|change| (change.lamport, change.id.peer));
+    metas
+}
+
+fn author_of(authors: &Authors, peer: u64) -> Option<Author> {
+    authors
+        .iter::<Author>()
+        .filter_map(Result::ok)
+        .find(|author| peer_id(&author.public_key()) == peer)
+}
+
+/// The changes of the text in `doc` in causal order, changes of anything else are skipped.
+pub(crate) fn changes(
+    doc: &LoroDoc,
+    text_container: &str,
+    authors: &Authors,
+) -> Result<Vec<HistoryEntry>> {
+    let fork = doc.fork();
+    let mut version = VersionVector::new();
+    let mut old = String::new();
+    let mut changes = Vec::new();
+    for change in sorted_changes(doc) {
+        version.insert(change.id.peer, change.id.counter + change.len as i32);
+        fork.checkout(&doc.vv_to_frontiers(&version))?;
+        let new = fork.get_text(text_container).to_string();
+        if new == old {
+            continue;
+        }
+
+        changes.push(HistoryEntry {
+            author: author_of(authors, change.id.peer),
+            timestamp: (change.timestamp > 0).then_some(change.timestamp),
+            message: change.message.map(|message| message.to_string()),
+            old: std::mem::replace(&mut old, new.clone()),
+            new,
+        });
+    }
+    Ok(changes)
+}
+
+/// The changes of the text in `doc` in causal order, consecutive changes of the same author are
+/// grouped into one batch.
+pub(crate) fn change_batches(
+    doc: &LoroDoc,
+    text_container: &str,
+    authors: &Authors,
+) -> Result<Vec<ChangeBatch>> {
+    let fork = doc.fork();
+    let mut version = VersionVector::new();
+    let mut old = String::new();
+    let mut batches: Vec<ChangeBatch> = Vec::new();
+    for change in sorted_changes(doc) {
+        let before = version.clone();
+        version.insert(change.id.peer, change.id.counter + change.len as i32);
+        fork.checkout(&doc.vv_to_frontiers(&version))?;
+        let new = fork.get_text(text_container).to_string();
+        if new == old {
+            continue;
+        }
+        old = new;
+
+        let timestamp = (change.timestamp > 0).then_some(change.timestamp);
+        match batches.last_mut() {
+            Some(batch) if batch.peer == change.id.peer => {
+                batch.after = version.clone();
+                batch.changes += 1;
+                batch.timestamp = timestamp.or(batch.timestamp);
+            }
+            _ => batches.push(ChangeBatch {
+                author: author_of(authors, change.id.peer),
+                timestamp,
+                changes: 1,
+                peer: change.id.peer,
+                before,
+                after: version.clone(),
+            }),
+        }
+    }
+    Ok(batches)
+}
+
+/// Edits of the current text in `doc` undoing `batch`, ordered from the end of the text to its
+/// start so they can be applied one after another.
+///
+/// Text the batch inserted is deleted unless it was deleted already, text it deleted is
+/// inserted again where it used to be. Changes made since are kept.
+pub(crate) fn revert_edits(
+    doc: &LoroDoc,
+    text_container: &str,
+    batch: &ChangeBatch,
+) -> Result<Vec<RevertEdit>> {
+    let before = doc.vv_to_frontiers(&batch.before);
+    let after = doc.vv_to_frontiers(&batch.after);
+    let text_id = doc.get_text(text_container).id();
+    let diff = doc.diff(&before, &after)?;
+    let Some((_, Diff::Text(deltas))) = diff.iter().find(|(id, _)| **id == text_id) else {
+        return Ok(Vec::new());
+    };
+
+    // Anchors are taken from the versions around the batch and resolved in the current text
+    let before_doc = doc.fork();
+    before_doc.checkout(&before)?;
+    let before_text = before_doc.get_text(text_container);
+    let after_doc = doc.fork();
+    after_doc.checkout(&after)?;
+    let after_text = after_doc.get_text(text_container);
+    // Current position of the character at `index` of `text` if it still exists, where it used to
+    // be otherwise
+    let position = |text: &LoroText, index: usize| {
+        let cursor = text.get_cursor(index, Side::Middle)?;
+        let result = doc.get_cursor_pos(&cursor).ok()?;
+        Some((result.current.pos, result.update.is_none()))
+    };
+
+    let mut edits = Vec::new();
+    let (mut before_index, mut after_index) = (0, 0);
+    for delta in deltas {
+        match delta {
+            TextDelta::Retain { retain, .. } => {
+                before_index += retain;
+                after_index += retain;
+            }
+            TextDelta::Delete { delete } => {
+                let text = before_text.slice(before_index, before_index + delete)?;
+                if let Some((pos, _)) = position(&before_text, before_index) {
+                    edits.push(RevertEdit::Insert { pos, text });
+                }
+                before_index += delete;
+            }
+            TextDelta::Insert { insert, .. } => {
+                let len = insert.chars().count();
+                for index in after_index..after_index + len {
+                    if let Some((pos, true)) = position(&after_text, index) {
+                        edits.push(RevertEdit::Delete {
+                            start: pos,
+                            end: pos + 1,
+                        });
+                    }
+                }
+                after_index += len;
+            }
+        }
+    }
+
+    // Deleting before inserting at the same position keeps the inserted text
+    edits.sort_by_key(|edit| match edit {
+        RevertEdit::Delete { start, .. } => (Reverse(*start), false),
+        RevertEdit::Insert { pos, .. } => (Reverse(*pos), true),
+    });
+    // Deletions of adjacent characters are merged, unless text is inserted in between
+    let mut merged: Vec<RevertEdit> = Vec::with_capacity(edits.len());
+    for edit in edits {
+        match (merged.last_mut(), &edit) {
+            (Some(RevertEdit::Delete { start, .. }), RevertEdit::Delete { end, .. })
+                if *start == *end =>
+            {
+                *start -= 1;
+            }
+            _ => merged.push(edit),
+        }
+    }
+    Ok(merged)
+}
+
+/// Summarize what `batch` changed in the text of `doc`.
+pub(crate) fn summarize_batch(
+    doc: &LoroDoc,
+    text_container: &str,
+    batch: &ChangeBatch,
+) -> Result<ChangeSummary> {
+    let before = doc.vv_to_frontiers(&batch.before);
+    let after = doc.vv_to_frontiers(&batch.after);
+    let text_id = doc.get_text(text_container).id();
+    let diff = doc.diff(&before, &after)?;
+    let Some((_, Diff::Text(deltas))) = diff.iter().find(|(id, _)| **id == text_id) else {
+        return Ok(ChangeSummary::default());
+    };
+
+    let before_doc = doc.fork();
+    before_doc.checkout(&before)?;
+    let text = before_doc.get_text(text_container).to_string();
+    Ok(summarize(&text, deltas))
+}
+
+/// File name of a document called `name` in patches.
+pub fn file_name(name: Option<&str>) -> String {
+    let name = name
+        .map(|name| name.trim().replace('/', "-"))
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| "Untitled Document".to_string());
+    format!("{name}.md")
+}
+
+/// Format `changes` of the file `file_name` as patch series, see the module documentation.
+pub(crate) fn format_patches(changes: &[HistoryEntry], file_name: &str) -> String {
+    let mut patches = String::new();
+    for (index, change) in changes.iter().enumerate() {
+        let (name, email) = signature(change.author.as_ref());
+        let subject = change
+            .message
+            .clone()
+            .unwrap_or_else(|| format!("Edit by {name}"));
+        // Fixed like in `git format-patch`, marks the start of a patch
+        let _ = writeln!(
+            patches,
+            "From 0000000000000000000000000000000000000000 Mon Sep 17 00:00:00 2001"
+        );
+        let _ = writeln!(patches, "From: {name} <{email}>");
+        if let Some(date) = change
+            .timestamp
+            .and_then(|timestamp| glib::DateTime::from_unix_utc(timestamp).ok())
+            .and_then(|date| date.format_iso8601().ok())
+        {
+            let _ = writeln!(patches, "Date: {date}");
+        }
+        let _ = writeln!(
+            patches,
+            "Subject: [PATCH {}/{}] {subject}\n\n---",
+            index + 1,
+            changes.len()
+        );
+
+        let _ = writeln!(patches, "diff --git a/{file_name} b/{file_name}");
+        if change.old.is_empty() {
+            let _ = writeln!(patches, "new file mode 100644\n--- /dev/null");
+        } else {
+            let _ = writeln!(patches, "--- a/{file_name}");
+        }
+        let _ = writeln!(patches, "+++ b/{file_name}");
+        patches.push_str(&unified_diff(&diff_lines(&change.old, &change.new)));
+        patches.push_str("-- \n\n");
+    }
+    patches
+}
+
+/// Name and email address of `author` for patches and commits.
+///
+/// Authors don't have email addresses, the public key takes their place so authors with the same
+/// name can be told apart.
+pub fn signature(author: Option<&Author>) -> (String, String) {
+    match author {
+        Some(author) => (
+            author.name(),
+            format!("{}@aardvark.invalid", author.public_key()),
+        ),
+        None => (
+            "Unknown Author".to_string(),
+            "unknown@aardvark.invalid".to_string(),
+        ),
+    }
+}
+
+/// The hunks of `diff` in unified diff format, with [`CONTEXT_LINES`] unchanged lines around
+/// every change.
+pub(crate) fn unified_diff(diff: &[DiffLine]) -> String {
+    let changed: Vec<usize> = diff
+        .iter()
+        .enumerate()
+        .filter(|(_, line)| !matches!(line, DiffLine::Unchanged(_)))
+        .map(|(index, _)| index)
+        .collect();
+
+    // Ranges of `diff` shown in a hunk, changes close to each other share one
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    for index in changed {
+        let start = index.saturating_sub(CONTEXT_LINES);
+        let end = (index + 1 + CONTEXT_LINES).min(diff.len());
+        match ranges.last_mut() {
+            Some((_, last_end)) if start <= *last_end => *last_end = end,
+            _ => ranges.push((start, end)),
+        }
+    }
+
+    let mut output = String::new();
+    for (start, end) in ranges {
+        // Lines of the old and new text before the hunk
+        let old_before = diff[..start]
+            .iter()
+            .filter(|line| !matches!(line, DiffLine::Added(_)))
+            .count();
+        let new_before = diff[..start]
+            .iter()
+            .filter(|line| !matches!(line, DiffLine::Removed(_)))
+            .count();
+        let hunk = &diff[start..end];
+        let old_len = hunk
+            .iter()
+            .filter(|line| !matches!(line, DiffLine::Added(_)))
+            .count();
+        let new_len = hunk
+            .iter()
+            .filter(|line| !matches!(line, DiffLine::Removed(_)))
+            .count();
+        // Empty ranges start at the line before them
+        let old_start = if old_len == 0 {
+            old_before
+        } else {
+            old_before + 1
+        };
+        let new_start = if new_len == 0 {
+            new_before
+        } else {
+            new_before + 1
+        };
+
+        let _ = writeln!(
+            output,
+            "@@ -{old_start},{old_len} +{new_start},{new_len} @@"
+        );
+        for line in hunk {
+            let _ = match line {
+                DiffLine::Unchanged(line) => writeln!(output, " {line}"),
+                DiffLine::Added(line) => writeln!(output, "+{line}"),
+                DiffLine::Removed(line) => writeln!(output, "-{line}"),
+            };
+        }
+    }
+    output
+}
@@ -0,0 +1,78 @@
+//! Avatar images generated from public keys.
+//!
+//! An identicon is a grid of cells, mirrored along its vertical axis, where every cell is filled
+//! depending on a bit of the hash of the public key. Cells are drawn in a darker shade of the
+//! author's color on a transparent background, so the avatar's background color shows through.
+
+use gdk::prelude::*;
+
+use crate::identity::PublicKey;
+
+/// Cells per row and column.
+pub const GRID: usize = 5;
+/// Edge length in pixels of the rendered images, large enough for all avatars we show.
+pub const SIZE: usize = 64;
+
+/// Filled cells of the identicon of `public_key`, by row.
+pub fn pattern(public_key: &PublicKey) -> [[bool; GRID]; GRID] {
+    let hash = p2panda_core::Hash::new(public_key.as_bytes());
+    let bytes = hash.as_bytes();
+    let filled = |bit: usize| bytes[bit / 8] & (1 << (bit % 8)) != 0;
+    let mut pattern = [[false; GRID]; GRID];
+    for (y, row) in pattern.iter_mut().enumerate() {
+        for (x, cell) in row.iter_mut().enumerate() {
+            // Mirrored, so only the left half and the middle column use bits of their own
+            let column = x.min(GRID - 1 - x);
+            *cell = filled(y * GRID.div_ceil(2) + column);
+        }
+    }
+    pattern
+}
+
+/// RGBA pixels of `pattern` drawn `size` pixels wide and high in `color`.
+///
+/// The grid only covers the middle half of the image, so it fits into round avatars.
+pub fn render(pattern: &[[bool; GRID]; GRID], color: [u8; 3], size: usize) -> Vec<u8> {
+    let inset = size / 4;
+    let cell = ((size - 2 * inset) / GRID).max(1);
+    let offset = (size - cell * GRID) / 2;
+    let mut pixels = vec![0; size * size * 4];
+    for (y, pixel_row) in pixels.chunks_exact_mut(size * 4).enumerate() {
+        let Some(row) = y
+            .checked_sub(offset)
+            .map(|y| y / cell)
+            .filter(|row| *row < GRID)
+        else {
+            continue;
+        };
+        for (x, pixel) in pixel_row.chunks_exact_mut(4).enumerate() {
+            let Some(column) = x.checked_sub(offset).map(|x| x / cell) else {
+                continue;
+            };
+            if column < GRID && pattern[row][column] {
+                pixel.copy_from_slice(&[color[0], color[1], color[2], 0xff]);
+            }
+        }
+    }
+    pixels
+}
+
+/// The identicon of `public_key` in a darker shade of `color`, a hex color like `#faf387`.
+pub fn paintable(public_key: &PublicKey, color: &str) -> gdk::Paintable {
+    let shade = |index: usize| {
+        let value = color
+            .get(1 + index * 2..3 + index * 2)
+            .and_then(|value| u8::from_str_radix(value, 16).ok())
+            .unwrap_or_default();
+        (value as u16 * 11 / 20) as u8
+    };
+    let pixels = render(&pattern(public_key), [shade(0), shade(1), shade(2)], SIZE);
+    gdk::MemoryTexture::new(
+        SIZE as i32,
+        SIZE as i32,
+        gdk::MemoryFormat::R8g8b8a8,
+        &glib::Bytes::from_owned(pixels),
+        SIZE * 4,
+    )
+    .upcast()
+}
@@ -0,0 +1,118 @@
+//! Invites to documents which can be shared over any channel, e.g. pasted into a chat.
+//!
+//! An invite is a single line starting with [`PREFIX`], followed by base64 of a version byte,
+//! the document id, an optional invite token and the public keys of peers which have the
+//! document. Peers are only hints, discovery still only finds them on the local network.
+use std::fmt;
+use std::str::FromStr;
+
+use thiserror::Error;
+
+use crate::document::DocumentId;
+use crate::identity::PublicKey;
+
+pub const PREFIX: &str = "aardvark-invite:";
+const VERSION: u8 = 1;
+/// Peers included in an invite at most, to keep it short.
+pub const MAX_PEERS: usize = 4;
+
+#[derive(Debug, Error, PartialEq)]
+pub enum InviteError {
+    #[error("Not an invite")]
+    NotAnInvite,
+    #[error("Invite of unsupported version {0}, please update Aardvark")]
+    UnsupportedVersion(u8),
+    #[error("Invite is incomplete")]
+    Truncated,
+    #[error("Invite contains an invalid peer")]
+    InvalidPeer,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Invite {
+    pub document_id: DocumentId,
+    pub token: Option<Vec<u8>>,
+    pub peers: Vec<PublicKey>,
+}
+
+impl Invite {
+    pub fn new(document_id: &DocumentId, peers: &[PublicKey]) -> Self {
+        Self {
+            document_id: document_id.clone(),
+            token: None,
+            peers: peers.iter().take(MAX_PEERS).cloned().collect(),
+        }
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let token = self.token.as_deref().unwrap_or_default();
+        let mut bytes = vec![VERSION];
+        bytes.extend_from_slice(self.document_id.0.as_bytes());
+        bytes.push(token.len().min(u8::MAX as usize) as u8);
+        bytes.extend_from_slice(&token[..token.len().min(u8::MAX as usize)]);
+        bytes.push(self.peers.len() as u8);
+        for peer in &self.peers {
+            bytes.extend_from_slice(peer.as_bytes());
+        }
+        bytes
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Result<Self, InviteError> {
+        let mut rest = bytes;
+        let version = split_off(&mut rest, 1)?[0];
+        if version != VERSION {
+            return Err(InviteError::UnsupportedVersion(version));
+        }
+        let document_id: [u8; 32] = split_off(&mut rest, 32)?.try_into().expect("32 bytes");
+        let token_len = split_off(&mut rest, 1)?[0] as usize;
+        let token = split_off(&mut rest, token_len)?;
+        let n_peers = split_off(&mut rest, 1)?[0] as usize;
+        let peers = (0..n_peers)
+            .map(|_| {
+                let bytes = split_off(&mut rest, 32)?;
+                p2panda_core::PublicKey::try_from(bytes)
+                    .map(PublicKey)
+                    .map_err(|_| InviteError::InvalidPeer)
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self {
+            document_id: DocumentId(document_id.into()),
+            token: (!token.is_empty()).then(|| token.to_vec()),
+            peers,
+        })
+    }
+}
+
+impl fmt::Display for Invite {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{PREFIX}{}", glib::base64_encode(&self.to_bytes()))
+    }
+}
+
+impl FromStr for Invite {
+    type Err = InviteError;
+
+    /// Surrounding text is ignored, e.g. when a whole chat message was copied.
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let start = value.find(PREFIX).ok_or(InviteError::NotAnInvite)?;
+        let encoded: String = value[start + PREFIX.len()..]
+            .chars()
+            .take_while(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '/' | '='))
+            .collect();
+        if encoded.is_empty() {
+            return Err(InviteError::Truncated);
+        }
+        Self::from_bytes(&glib::base64_decode(&encoded))
+    }
+}
+
+/// Split off the first `len` bytes of `rest`.
+fn split_off<'a>(rest: &mut &'a [u8], len: usize) -> Result<&'a [u8], InviteError> {
+    if rest.len() < len {
+        return Err(InviteError::Truncated);
+    }
+    let (taken, remaining) = rest.split_at(len);
+    *rest = remaining;
+    Ok(taken)
+}
@@ -1,12 +1,36 @@
 pub mod author;
 pub mod authors;
+pub mod bookmarks;
+#[cfg(feature = "capi")]
+pub mod capi;
+pub mod checkpoints;
+pub mod colors;
+mod conflicts;
+pub mod conventions;
+pub mod crdt;
 pub mod document;
 pub mod documents;
+pub mod frontmatter;
+mod graphemes;
+pub mod history;
+pub mod identicon;
+pub mod invite;
+pub mod peers;
+pub mod polls;
+pub mod profiler;
 pub mod service;
+pub mod sorted_authors;
+pub mod summary;
+pub mod tasks;
+
+pub use aardvark_core::{anonymity, assists, debug, presence, receipts};
 
 pub mod identity {
+    pub use aardvark_core::keyfile::KeyfileError;
+    use aardvark_core::keyfile::{decrypt_keyfile, encrypt_keyfile};
     pub use p2panda_core::identity::IdentityError;
     use std::fmt;
+    use std::str::FromStr;
 
     #[derive(Clone, Debug, glib::Boxed)]
     #[boxed_type(name = "AardvarkPrivateKey", nullable)]
@@ -24,6 +48,16 @@ pub mod identity {
         pub fn as_bytes(&self) -> &[u8] {
             self.0.as_bytes().as_slice()
         }
+
+        /// Encrypt the key with a passphrase, to move it to another device.
+        pub fn to_keyfile(&self, passphrase: &str) -> Result<Vec<u8>, KeyfileError> {
+            encrypt_keyfile(&self.0, passphrase)
+        }
+
+        /// Decrypt a key exported with [`PrivateKey::to_keyfile`].
+        pub fn from_keyfile(keyfile: &[u8], passphrase: &str) -> Result<Self, KeyfileError> {
+            Ok(PrivateKey(decrypt_keyfile(keyfile, passphrase)?))
+        }
     }
 
     impl TryFrom<&[u8]> for PrivateKey {
@@ -56,6 +90,14 @@ pub mod identity {
         }
     }
 
+    impl FromStr for PublicKey {
+        type Err = IdentityError;
+
+        fn from_str(value: &str) -> Result<Self, Self::Err> {
+            Ok(PublicKey(p2panda_core::PublicKey::from_str(value)?))
+        }
+    }
+
     impl<'a> From<&'a PublicKey> for &'a [u8] {
         fn from(value: &PublicKey) -> &[u8] {
             value.0.as_bytes().as_slice()
@@ -71,12 +113,29 @@ pub mod identity {
 
 #[cfg(test)]
 mod tests {
-    use crate::document::Document;
-    use crate::identity::PrivateKey;
-    use crate::service::Service;
-    use gio::prelude::FileExt;
-    use glib::object::ObjectExt;
+    use crate::assists::{Continuation, closing_char, continuation, should_close_before};
+    use crate::author::Author;
+    use crate::author::COLORS;
+    use crate::authors::Authors;
+    use crate::checkpoints::{Checkpoint, DiffLine, diff_lines};
+    use crate::colors::{assign_colors, preferred_color};
+    use crate::conflicts::{overlaps, seen_by};
+    use crate::conventions::{HeadingStyle, ListMarker, contains_heading};
+    use crate::crdt::{CrdtKind, convert};
+    use crate::document::{Anchor, Document, DocumentId};
+    use crate::history::{RevertEdit, change_batches, revert_edits, summarize_batch, unified_diff};
+    use crate::identicon::{GRID, pattern, render};
+    use crate::identity::{KeyfileError, PrivateKey};
+    use crate::invite::{Invite, InviteError};
+    use crate::presence::Presence;
+    use crate::receipts::{changes_missing, decode_receipt, encode_receipt};
+    use crate::service::{NetworkConfig, Service};
+    use crate::sorted_authors::{AuthorOrder, SortedAuthors};
+    use crate::summary::summarize;
+    use gio::prelude::{FileExt, ListModelExt, ListModelExtManual};
+    use glib::object::{CastNone, ObjectExt};
     use std::fs;
+    use std::str::FromStr;
 
     struct TestResource {
         service: Service,
@@ -99,8 +158,10 @@ mod tests {
             fs::create_dir_all(&data_path).expect("Able to create data dir");
             let data_dir = gio::File::for_path(data_path);
 
+            // Stay apart from the app and from tests running on other machines on the network
+            let network_id = format!("aardvark tests {}", std::process::id());
             TestResource {
-                service: Service::new(&private_key, &data_dir),
+                service: Service::with_network_id(&private_key, &data_dir, &network_id),
             }
         }
 
@@ -124,6 +185,34 @@ mod tests {
         assert_eq!(document.text(), test_string);
     }
 
+    #[test]
+    fn independent_services() {
+        let context = glib::MainContext::default();
+
+        let resource = TestResource::new();
+        let service = resource.service();
+        service.startup();
+        let resource2 = TestResource::new();
+        let service2 = resource2.service();
+        service2.startup();
+        assert_ne!(
+            service.private_key().public_key(),
+            service2.private_key().public_key()
+        );
+
+        let document = Document::new(&service, None);
+        document.set_subscribed(true);
+        context.iteration(false);
+        assert!(document.insert_text(0, "Only here").is_ok());
+
+        assert_eq!(service.documents().n_items(), 1);
+        assert_eq!(service2.documents().n_items(), 0);
+        assert!(service2.documents().by_id(&document.id()).is_none());
+
+        service.shutdown();
+        service2.shutdown();
+    }
+
     #[test]
     fn basic_sync() {
         let main_loop = glib::MainLoop::new(None, false);
@@ -244,4 +333,1115 @@ mod tests {
         service.shutdown();
         service2.shutdown();
     }
+
+    #[test]
+    fn insert_large_text() {
+        let context = glib::MainContext::default();
+
+        let resource = TestResource::new();
+        let service = resource.service();
+        service.startup();
+        let document = Document::new(&service, None);
+        document.set_subscribed(true);
+        context.iteration(false);
+
+        // Multi-byte characters make sure chunks are never split inside a character
+        let test_string = "Große Einfügung 🦔\n".repeat(4096);
+        assert!(document.insert_text(0, "[]").is_ok());
+        assert!(document.insert_text(1, &test_string).is_ok());
+        assert_eq!(document.text(), format!("[{test_string}]"));
+    }
+
+    #[test]
+    fn text_versions() {
+        let context = glib::MainContext::default();
+
+        let resource = TestResource::new();
+        let service = resource.service();
+        service.startup();
+        let document = Document::new(&service, None);
+        document.set_subscribed(true);
+        context.iteration(false);
+
+        let changes = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+        for signal in ["text-inserted", "range-deleted"] {
+            let changes = changes.clone();
+            document.connect_local(signal, false, move |values| {
+                let document: Document = values[0].get().unwrap();
+                let version: u64 = values[3].get().unwrap();
+                // The text is never behind the signal
+                let (_, text_version) = document.text_with_version();
+                assert!(text_version >= version);
+                changes.borrow_mut().push(version);
+                None
+            });
+        }
+
+        let (_, start) = document.text_with_version();
+        assert!(document.insert_text(0, "Hello World").is_ok());
+        assert!(document.delete_range(5, 11).is_ok());
+        assert_eq!(*changes.borrow(), vec![start + 1, start + 2]);
+        assert_eq!(document.text_version(), start + 2);
+        assert_eq!(
+            document.text_with_version(),
+            ("Hello".to_owned(), start + 2)
+        );
+    }
+
+    #[test]
+    fn transaction() {
+        let context = glib::MainContext::default();
+
+        let resource = TestResource::new();
+        let service = resource.service();
+        service.startup();
+        let document = Document::new(&service, None);
+        document.set_subscribed(true);
+        context.iteration(false);
+        assert!(document.insert_text(0, "cat and cat").is_ok());
+
+        let commits = std::rc::Rc::new(std::cell::Cell::new(0));
+        document.connect_notify_local(
+            Some("text"),
+            glib::clone!(
+                #[strong]
+                commits,
+                move |_, _| commits.set(commits.get() + 1)
+            ),
+        );
+
+        assert!(document.splice(0, 3, "dog").is_ok());
+        assert_eq!(document.text(), "dog and cat");
+        assert_eq!(commits.get(), 1);
+
+        let result = document.transaction(|transaction| {
+            transaction.splice(8, 3, "bird")?;
+            transaction.insert_text(0, "A ")?;
+            assert_eq!(transaction.text(), "A dog and bird");
+            transaction.delete_range(5, 9)?;
+            Ok(transaction.text())
+        });
+        assert_eq!(result.unwrap(), "A dog bird");
+        assert_eq!(document.text(), "A dog bird");
+        assert_eq!(commits.get(), 2);
+    }
+
+    #[test]
+    fn grapheme_clusters() {
+        let context = glib::MainContext::default();
+
+        let resource = TestResource::new();
+        let service = resource.service();
+        service.startup();
+        let document = Document::new(&service, None);
+        document.set_subscribed(true);
+        context.iteration(false);
+
+        let inserted = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+        document.connect_local(
+            "text-inserted",
+            false,
+            glib::clone!(
+                #[strong]
+                inserted,
+                move |values| {
+                    let pos: i32 = values[1].get().unwrap();
+                    let text: String = values[2].get().unwrap();
+                    inserted.borrow_mut().push((pos, text));
+                    None
+                }
+            ),
+        );
+
+        // Positions of signals are in characters, also after multi-byte characters
+        assert!(document.insert_text(0, "漢字").is_ok());
+        assert!(
+            document
+                .transaction(|transaction| {
+                    transaction.insert_text(0, "🦔")?;
+                    transaction.insert_text(3, "!")
+                })
+                .is_ok()
+        );
+        assert_eq!(document.text(), "🦔漢字!");
+        assert_eq!(
+            *inserted.borrow(),
+            vec![
+                (0, "漢字".to_owned()),
+                (0, "🦔".to_owned()),
+                (3, "!".to_owned())
+            ]
+        );
+
+        // Family of three joined with zero width joiners
+        let family = "👩\u{200D}👩\u{200D}👦";
+        // Thumbs up with a skin tone, a flag and a letter with a combining accent
+        let text = format!("a{family}b👍🏽🇩🇪e\u{301}");
+        assert!(document.delete_range(0, 4).is_ok());
+        assert!(document.insert_text(0, &text).is_ok());
+
+        // Deleting the last code point removes the whole family
+        assert_eq!(document.delete_range(5, 6).unwrap(), (1, 6));
+        assert_eq!(document.text(), "ab👍🏽🇩🇪e\u{301}");
+        // Deleting the skin tone removes the thumb too
+        assert_eq!(document.delete_range(3, 4).unwrap(), (2, 4));
+        // Only half of the flag
+        assert_eq!(document.delete_range(2, 3).unwrap(), (2, 4));
+        assert_eq!(document.delete_range(3, 4).unwrap(), (2, 4));
+        assert_eq!(document.text(), "ab");
+    }
+
+    #[test]
+    fn insert_paragraphs() {
+        let context = glib::MainContext::default();
+
+        let resource = TestResource::new();
+        let service = resource.service();
+        service.startup();
+        let document = Document::new(&service, None);
+        document.set_subscribed(true);
+        context.iteration(false);
+
+        let text = "# Notes\n\nFirst paragraph\nstill the first\n\n\nSecond paragraph\n";
+        assert_eq!(
+            crate::document::split_into_paragraphs(text).collect::<Vec<_>>(),
+            [
+                "# Notes\n\n",
+                "First paragraph\nstill the first\n\n\n",
+                "Second paragraph\n"
+            ]
+        );
+
+        let changes = document.changes();
+        assert!(document.insert_paragraphs(0, text, "notes.md").is_ok());
+        assert_eq!(document.text(), text);
+        assert!(document.changes() > changes);
+    }
+
+    #[test]
+    fn normalize_text() {
+        assert_eq!(
+            crate::document::normalize_text(b"one\r\ntwo\rthree\n"),
+            "one\ntwo\nthree\n"
+        );
+        assert_eq!(
+            crate::document::normalize_text(b"invalid \xff byte"),
+            "invalid \u{FFFD} byte"
+        );
+    }
+
+    #[test]
+    fn assists() {
+        assert_eq!(closing_char('('), Some(')'));
+        assert_eq!(closing_char('a'), None);
+        assert!(should_close_before(None));
+        assert!(should_close_before(Some(' ')));
+        assert!(should_close_before(Some(')')));
+        assert!(!should_close_before(Some('w')));
+
+        let prefix = |prefix: &str| Some(Continuation::Prefix(prefix.to_owned()));
+        assert_eq!(continuation("- Milk"), prefix("- "));
+        assert_eq!(continuation("  * Eggs"), prefix("  * "));
+        assert_eq!(continuation("- [x] Done"), prefix("- [ ] "));
+        assert_eq!(continuation("9. Ninth"), prefix("10. "));
+        assert_eq!(continuation("> Quote"), prefix("> "));
+        assert_eq!(continuation("Plain text"), None);
+        assert_eq!(continuation("-dash"), None);
+        assert_eq!(continuation(". Dot"), None);
+
+        // Empty items end the list
+        assert_eq!(continuation("- "), Some(Continuation::End(2)));
+        assert_eq!(continuation("  - [ ] "), Some(Continuation::End(8)));
+        assert_eq!(continuation("3. "), Some(Continuation::End(3)));
+        assert_eq!(continuation("> "), Some(Continuation::End(2)));
+    }
+
+    #[test]
+    fn extract_tasks() {
+        let tasks =
+            crate::tasks::extract_tasks("# Party\n- [ ] Book venue\n  * [x] Buy 🎈\n- [] Nope\n");
+        assert_eq!(tasks.len(), 2);
+        assert_eq!(tasks[0].text, "Book venue");
+        assert!(!tasks[0].checked);
+        assert_eq!(tasks[0].marker_offset, 11);
+        assert_eq!(tasks[1].text, "Buy 🎈");
+        assert!(tasks[1].checked);
+        assert_eq!(tasks[1].marker_offset, 30);
+        assert_eq!(tasks[1].text_offset, 33);
+    }
+
+    #[test]
+    fn assign_task() {
+        let context = glib::MainContext::default();
+
+        let resource = TestResource::new();
+        let service = resource.service();
+        service.startup();
+        let document = Document::new(&service, None);
+        document.set_subscribed(true);
+        context.iteration(false);
+
+        let assignee = PrivateKey::new().public_key();
+        assert!(document.insert_text(0, "- [ ] Book venue\n").is_ok());
+        let task = document.tasks().remove(0);
+        assert!(document.assign_task(&task, Some(&assignee)).is_ok());
+
+        // Typing right after the task doesn't extend the assignment to a new task
+        assert!(document.insert_text(17, "- [ ] Buy balloons").is_ok());
+        let tasks = document.tasks();
+        assert_eq!(tasks[0].assignee, Some(assignee));
+        assert_eq!(tasks[1].assignee, None);
+
+        assert!(document.set_task_checked(&tasks[0], true).is_ok());
+        assert!(document.assign_task(&tasks[0], None).is_ok());
+        let task = document.tasks().remove(0);
+        assert!(task.checked);
+        assert_eq!(task.assignee, None);
+    }
+
+    #[test]
+    fn task_due_date() {
+        let context = glib::MainContext::default();
+
+        let resource = TestResource::new();
+        let service = resource.service();
+        service.startup();
+        let document = Document::new(&service, None);
+        document.set_subscribed(true);
+        context.iteration(false);
+
+        assert!(document.insert_text(0, "- [ ] Book venue").is_ok());
+        let task = document.tasks().remove(0);
+        assert_eq!(task.due_date, None);
+        assert!(!task.is_overdue());
+
+        let yesterday = crate::tasks::today().add_days(-1).unwrap();
+        assert!(document.set_task_due_date(&task, Some(&yesterday)).is_ok());
+        let task = document.tasks().remove(0);
+        assert_eq!(task.due_date, Some(yesterday));
+        assert!(task.is_overdue());
+
+        // Done tasks are never overdue
+        assert!(document.set_task_checked(&task, true).is_ok());
+        assert!(!document.tasks()[0].is_overdue());
+    }
+
+    #[test]
+    fn meeting_notes_section() {
+        let context = glib::MainContext::default();
+
+        let resource = TestResource::new();
+        let service = resource.service();
+        service.startup();
+        let document = Document::new(&service, None);
+        document.set_subscribed(true);
+        context.iteration(false);
+
+        assert!(document.insert_text(0, "# Standup").is_ok());
+        document.set_meeting_notes(true);
+        let today = glib::DateTime::now_local().unwrap().format("%F").unwrap();
+        assert_eq!(document.text(), format!("# Standup\n\n## {today}\n\n"));
+
+        // Opening the document again on the same day doesn't add another section
+        document.insert_meeting_notes_section();
+        assert_eq!(document.text().matches(&format!("## {today}")).count(), 1);
+    }
+
+    #[test]
+    fn editing_conventions() {
+        let context = glib::MainContext::default();
+
+        let resource = TestResource::new();
+        let service = resource.service();
+        service.startup();
+        let document = Document::new(&service, None);
+        document.set_subscribed(true);
+        context.iteration(false);
+
+        assert_eq!(document.heading_style(), HeadingStyle::Atx);
+        assert_eq!(document.list_marker(), ListMarker::Dash);
+        assert_eq!(document.wrap_column(), 0);
+
+        document.set_heading_style(HeadingStyle::Setext);
+        document.set_list_marker(ListMarker::Plus);
+        document.set_wrap_column(72);
+        assert_eq!(document.heading_style(), HeadingStyle::Setext);
+        assert_eq!(document.list_marker(), ListMarker::Plus);
+        assert_eq!(document.wrap_column(), 72);
+
+        // Meeting notes sections follow the heading style of the document
+        document.set_meeting_notes(true);
+        let today = glib::DateTime::now_local().unwrap().format("%F").unwrap();
+        assert_eq!(document.text(), format!("{today}\n----------\n\n"));
+
+        // A section written in the other style counts as well
+        document.set_heading_style(HeadingStyle::Atx);
+        document.insert_meeting_notes_section();
+        assert_eq!(document.text().matches(today.as_str()).count(), 1);
+
+        assert!(contains_heading(&format!("## {today}\n"), 2, &today));
+        assert!(!contains_heading(&format!("{today}\n===\n"), 2, &today));
+        assert!(!contains_heading(&format!("{today}\n\n---\n"), 2, &today));
+    }
+
+    #[test]
+    fn frontmatter() {
+        let resource = TestResource::new();
+        let service = resource.service();
+        let document = Document::new(&service, None);
+        assert!(document.frontmatter().is_none());
+
+        assert!(document.insert_text(0, "Notes\n").is_ok());
+        assert!(
+            document
+                .set_frontmatter_value("title", Some("Weekly sync"))
+                .is_ok()
+        );
+        assert_eq!(document.text(), "---\ntitle: Weekly sync\n---\nNotes\n");
+        assert_eq!(document.name().as_deref(), Some("Weekly sync"));
+
+        assert!(
+            document
+                .set_frontmatter_value("draft", Some("true"))
+                .is_ok()
+        );
+        assert!(
+            document
+                .set_frontmatter_value("title", Some("Sync: weekly"))
+                .is_ok()
+        );
+        assert_eq!(
+            document.text(),
+            "---\ntitle: \"Sync: weekly\"\ndraft: true\n---\nNotes\n"
+        );
+        let frontmatter = document.frontmatter().unwrap();
+        assert_eq!(frontmatter.get("title"), Some("Sync: weekly"));
+        assert_eq!(
+            frontmatter.iter().collect::<Vec<_>>(),
+            vec![("title", "Sync: weekly"), ("draft", "true")]
+        );
+
+        // Removing all keys keeps the block and anything else in it
+        assert!(document.set_frontmatter_value("title", None).is_ok());
+        assert!(document.set_frontmatter_value("draft", None).is_ok());
+        assert_eq!(document.text(), "---\n---\nNotes\n");
+        assert_eq!(document.name().as_deref(), Some("Notes"));
+    }
+
+    #[test]
+    fn polls() {
+        let resource = TestResource::new();
+        let service = resource.service();
+        let document = Document::new(&service, None);
+        let public_key = service.private_key().public_key();
+
+        assert!(document.insert_text(0, "# Agenda\n").is_ok());
+        let options = ["Monday".to_string(), "Friday".to_string()];
+        let id = document
+            .create_poll(9, "When do we meet?", &options)
+            .expect("Able to create poll");
+        assert_eq!(
+            document.text(),
+            format!("# Agenda\n{}\n", crate::polls::poll_marker(&id))
+        );
+
+        let polls = document.polls();
+        assert_eq!(polls.len(), 1);
+        assert_eq!(polls[0].question, "When do we meet?");
+        assert_eq!(polls[0].options, options);
+        assert!(polls[0].votes.is_empty());
+
+        assert!(document.vote(&polls[0], Some(1)).is_ok());
+        let poll = document.poll(&id).unwrap();
+        assert_eq!(poll.vote_of(&public_key), Some(1));
+        assert_eq!(poll.results(), vec![0, 1]);
+
+        // Voting again replaces the previous vote
+        assert!(document.vote(&poll, Some(0)).is_ok());
+        assert_eq!(document.poll(&id).unwrap().results(), vec![1, 0]);
+
+        assert!(document.vote(&poll, None).is_ok());
+        assert_eq!(document.poll(&id).unwrap().vote_of(&public_key), None);
+    }
+
+    #[test]
+    fn keyfile() {
+        let private_key = PrivateKey::new();
+        let keyfile = private_key
+            .to_keyfile("correct horse battery staple")
+            .expect("Able to export private key");
+
+        let imported = PrivateKey::from_keyfile(&keyfile, "correct horse battery staple")
+            .expect("Able to import private key");
+        assert_eq!(imported.public_key(), private_key.public_key());
+
+        assert!(matches!(
+            PrivateKey::from_keyfile(&keyfile, "wrong passphrase"),
+            Err(KeyfileError::Decryption)
+        ));
+        assert!(matches!(
+            PrivateKey::from_keyfile(private_key.as_bytes(), "correct horse battery staple"),
+            Err(KeyfileError::UnknownFormat)
+        ));
+    }
+
+    #[test]
+    fn author_colors() {
+        let public_keys: Vec<_> = (0..COLORS.len() + 1)
+            .map(|_| PrivateKey::new().public_key())
+            .collect();
+
+        // Everyone gets a color of their own while there are enough
+        let colors = assign_colors(&public_keys[..COLORS.len()]);
+        let mut distinct = colors.clone();
+        distinct.sort();
+        distinct.dedup();
+        assert_eq!(distinct.len(), COLORS.len());
+
+        // Peers knowing the authors in a different order assign the same colors
+        let mut reversed = public_keys.clone();
+        reversed.reverse();
+        let mut reversed_colors = assign_colors(&reversed);
+        reversed_colors.reverse();
+        assert_eq!(assign_colors(&public_keys), reversed_colors);
+
+        // A single author keeps the color of their name
+        assert_eq!(
+            assign_colors(&public_keys[..1]),
+            vec![preferred_color(&public_keys[0])]
+        );
+        let authors = Authors::from_vec(vec![Author::new(&public_keys[0])]);
+        let author = authors.item(0).and_downcast::<Author>().unwrap();
+        assert!(author.name().starts_with(&author.color()));
+    }
+
+    #[test]
+    fn invites() {
+        let document_id = DocumentId::from_str(&"ab".repeat(32)).unwrap();
+        let peers: Vec<_> = (0..6).map(|_| PrivateKey::new().public_key()).collect();
+        let invite = Invite::new(&document_id, &peers);
+        assert_eq!(invite.peers, peers[..4]);
+
+        // Surrounding text of a chat message is ignored
+        let text = format!("Join my notes: {invite}, see you there");
+        assert_eq!(Invite::from_str(&text), Ok(invite));
+
+        let invite = Invite {
+            document_id: document_id.clone(),
+            token: Some(b"secret".to_vec()),
+            peers: Vec::new(),
+        };
+        assert_eq!(Invite::from_str(&invite.to_string()), Ok(invite));
+
+        assert_eq!(
+            Invite::from_str(&document_id.to_string()),
+            Err(InviteError::NotAnInvite)
+        );
+        assert_eq!(
+            Invite::from_str("aardvark-invite:AQ=="),
+            Err(InviteError::Truncated)
+        );
+    }
+
+    #[test]
+    fn identicons() {
+        let public_key = PrivateKey::new().public_key();
+        let identicon = pattern(&public_key);
+        assert_eq!(identicon, pattern(&public_key));
+        for row in identicon {
+            let mut mirrored = row;
+            mirrored.reverse();
+            assert_eq!(row, mirrored);
+        }
+
+        let mut filled = [[false; GRID]; GRID];
+        filled[0][0] = true;
+        let pixels = render(&filled, [1, 2, 3], 40);
+        assert_eq!(pixels.len(), 40 * 40 * 4);
+        // The grid covers the middle half, 4 pixels wide cells start 10 pixels in
+        let pixel = |x: usize, y: usize| &pixels[(y * 40 + x) * 4..(y * 40 + x + 1) * 4];
+        assert_eq!(pixel(10, 10), &[1, 2, 3, 0xff]);
+        assert_eq!(pixel(13, 13), &[1, 2, 3, 0xff]);
+        assert_eq!(pixel(9, 10), &[0, 0, 0, 0]);
+        assert_eq!(pixel(14, 10), &[0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn sorted_authors() {
+        let authors: Vec<Author> = (0..4)
+            .map(|_| Author::new(&PrivateKey::new().public_key()))
+            .collect();
+        authors[2].set_is_online(true);
+        let sorted = SortedAuthors::new(&Authors::from_vec(authors.clone()));
+        let names = |model: &SortedAuthors| -> Vec<String> {
+            model.iter::<Author>().flatten().map(|a| a.name()).collect()
+        };
+
+        assert_eq!(sorted.n_items(), 4);
+        assert_eq!(sorted.item(0).and_downcast::<Author>().unwrap(), authors[2]);
+
+        sorted.set_order(AuthorOrder::Alphabetical);
+        let mut expected: Vec<String> = authors.iter().map(|a| a.name()).collect();
+        expected.sort_by_key(|name| name.to_lowercase());
+        assert_eq!(names(&sorted), expected);
+
+        sorted.set_online_only(true);
+        assert_eq!(names(&sorted), vec![authors[2].name()]);
+
+        // The list follows authors coming online
+        authors[0].set_is_online(true);
+        assert_eq!(sorted.n_items(), 2);
+    }
+
+    #[test]
+    fn bookmarks() {
+        let context = glib::MainContext::default();
+
+        let resource = TestResource::new();
+        let service = resource.service();
+        service.startup();
+        let document = Document::new(&service, None);
+        document.set_subscribed(true);
+        context.iteration(false);
+        assert!(document.insert_text(0, "Hello World").is_ok());
+
+        let id = document.add_bookmark(6, "World").unwrap();
+        let bookmark = document.bookmarks().by_id(&id).unwrap();
+        assert_eq!(bookmark.name(), "World");
+        assert_eq!(bookmark.position(), 6);
+
+        // Bookmarks stay in front of the same text
+        assert!(document.insert_text(0, "Oh, ").is_ok());
+        assert_eq!(bookmark.position(), 10);
+        assert!(document.delete_range(0, 4).is_ok());
+        assert_eq!(bookmark.position(), 6);
+
+        document.add_bookmark(0, "Hello").unwrap();
+        let bookmarks = document.bookmarks();
+        assert_eq!(bookmarks.n_items(), 2);
+        assert_eq!(bookmarks.next(0).unwrap(), bookmark);
+        assert_eq!(bookmarks.next(6).unwrap().name(), "Hello");
+        assert_eq!(bookmarks.previous(6).unwrap().name(), "Hello");
+
+        assert!(document.remove_bookmark(&bookmark).is_ok());
+        assert_eq!(bookmarks.n_items(), 1);
+    }
+
+    #[test]
+    fn document_order() {
+        let resource = TestResource::new();
+        let service = resource.service();
+        service.startup();
+        let first = Document::new(&service, None);
+        let second = Document::new(&service, None);
+        let third = Document::new(&service, None);
+        let documents = service.documents();
+        let order = || -> Vec<Document> {
+            documents
+                .iter::<Document>()
+                .filter_map(Result::ok)
+                .collect()
+        };
+
+        // New documents are listed first
+        assert_eq!(order(), [third.clone(), second.clone(), first.clone()]);
+
+        first.set_pinned(true);
+        assert_eq!(order(), [first.clone(), third.clone(), second.clone()]);
+
+        documents.move_document(&second, &third);
+        assert_eq!(order(), [first.clone(), second.clone(), third.clone()]);
+
+        // Dragging a document to a pinned one pins it
+        documents.move_document(&third, &first);
+        assert!(third.pinned());
+        assert_eq!(order(), [third.clone(), first.clone(), second.clone()]);
+
+        // Documents added after reordering the list are listed first among unpinned ones
+        let fourth = Document::new(&service, None);
+        assert_eq!(order(), [third, first, fourth, second]);
+    }
+
+    #[test]
+    fn read_receipts() {
+        // Changes of different peers are never merged into one
+        let edit = |peer: u64, text: &str| {
+            let doc = loro::LoroDoc::new();
+            doc.set_peer_id(peer).unwrap();
+            doc.get_text("document").insert(0, text).unwrap();
+            doc.commit();
+            doc.export(loro::ExportMode::all_updates()).unwrap()
+        };
+        let doc = loro::LoroDoc::new();
+        doc.import(&edit(1, "Hello")).unwrap();
+        let seen = doc.oplog_vv();
+        doc.import(&edit(2, " World")).unwrap();
+        doc.import(&edit(3, "!")).unwrap();
+
+        let receipt = encode_receipt(&seen);
+        assert_eq!(decode_receipt(&receipt), Some(seen.clone()));
+        assert_eq!(decode_receipt(b"not a receipt"), None);
+
+        assert_eq!(changes_missing(&doc, &seen), 2);
+        assert_eq!(changes_missing(&doc, &Default::default()), 3);
+        assert_eq!(changes_missing(&doc, &doc.oplog_vv()), 0);
+    }
+
+    #[test]
+    fn conflicts() {
+        let ours = loro::LoroDoc::new();
+        ours.set_peer_id(1).unwrap();
+        ours.get_text("document").insert(0, "Hello World").unwrap();
+        ours.commit();
+        let theirs = loro::LoroDoc::new();
+        theirs.set_peer_id(2).unwrap();
+        theirs
+            .import(&ours.export(loro::ExportMode::all_updates()).unwrap())
+            .unwrap();
+
+        // Both change the same word concurrently
+        ours.get_text("document").insert(6, "big ").unwrap();
+        ours.commit();
+        theirs.get_text("document").delete(6, 5).unwrap();
+        theirs.commit();
+        let our_counter = ours.oplog_vv().get(&1).copied().unwrap();
+        ours.import(&theirs.export(loro::ExportMode::all_updates()).unwrap())
+            .unwrap();
+        let seen = seen_by(&ours, 2).unwrap();
+        assert!(seen.get(&1).copied().unwrap_or(0) < our_counter);
+
+        // A change made after receiving ours isn't concurrent
+        theirs
+            .import(&ours.export(loro::ExportMode::all_updates()).unwrap())
+            .unwrap();
+        theirs.get_text("document").insert(0, "Oh, ").unwrap();
+        theirs.commit();
+        ours.import(&theirs.export(loro::ExportMode::all_updates()).unwrap())
+            .unwrap();
+        let seen = seen_by(&ours, 2).unwrap();
+        assert!(seen.get(&1).copied().unwrap_or(0) >= our_counter);
+        assert!(seen_by(&ours, 3).is_none());
+
+        assert!(overlaps((6, 10), (6, 6)));
+        assert!(overlaps((0, 5), (5, 8)));
+        assert!(!overlaps((0, 5), (6, 8)));
+    }
+
+    #[test]
+    fn revert_author_changes() {
+        let ours = loro::LoroDoc::new();
+        ours.set_peer_id(1).unwrap();
+        ours.get_text("document").insert(0, "Hello World").unwrap();
+        ours.commit();
+        let theirs = loro::LoroDoc::new();
+        theirs.set_peer_id(2).unwrap();
+        theirs
+            .import(&ours.export(loro::ExportMode::all_updates()).unwrap())
+            .unwrap();
+
+        // They replace a word with two changes, we keep editing afterwards
+        theirs.get_text("document").delete(5, 6).unwrap();
+        theirs.commit();
+        theirs.get_text("document").insert(5, " everyone").unwrap();
+        theirs.commit();
+        ours.import(&theirs.export(loro::ExportMode::all_updates()).unwrap())
+            .unwrap();
+        ours.get_text("document").insert(14, "!").unwrap();
+        ours.commit();
+        assert_eq!(ours.get_text("document").to_string(), "Hello everyone!");
+
+        let batches = change_batches(&ours, "document", &Authors::new()).unwrap();
+        assert_eq!(
+            batches
+                .iter()
+                .map(|batch| batch.changes)
+                .collect::<Vec<_>>(),
+            [1, 2, 1]
+        );
+
+        // Only their changes are undone
+        let edits = revert_edits(&ours, "document", &batches[1]).unwrap();
+        let text = ours.get_text("document");
+        for edit in edits {
+            match edit {
+                RevertEdit::Delete { start, end } => text.delete(start, end - start).unwrap(),
+                RevertEdit::Insert {
+                    pos,
+                    text: inserted,
+                } => text.insert(pos, &inserted).unwrap(),
+            }
+        }
+        assert_eq!(text.to_string(), "Hello World!");
+
+        // Reverting again deletes nothing, their text is gone already
+        ours.commit();
+        let edits = revert_edits(&ours, "document", &batches[1]).unwrap();
+        assert!(
+            edits
+                .iter()
+                .all(|edit| !matches!(edit, RevertEdit::Delete { .. }))
+        );
+    }
+
+    #[test]
+    fn summarize_changes() {
+        let ours = loro::LoroDoc::new();
+        ours.set_peer_id(1).unwrap();
+        ours.get_text("document")
+            .insert(0, "# Intro\nHello\n# Plan\n- a\n- b\n")
+            .unwrap();
+        ours.commit();
+        let theirs = loro::LoroDoc::new();
+        theirs.set_peer_id(2).unwrap();
+        theirs
+            .import(&ours.export(loro::ExportMode::all_updates()).unwrap())
+            .unwrap();
+        theirs
+            .get_text("document")
+            .insert(13, " world,\nnew line")
+            .unwrap();
+        theirs.commit();
+
+        let batches = change_batches(&theirs, "document", &Authors::new()).unwrap();
+        let summary = summarize_batch(&theirs, "document", &batches[1]).unwrap();
+        assert_eq!(summary.inserted, 16);
+        assert_eq!(summary.section(), Some("Intro"));
+        assert_eq!(summary.to_string(), "+16 chars in §Intro, +1 line");
+
+        // Deletions in two sections
+        let deltas = [
+            loro::TextDelta::Retain {
+                retain: 8,
+                attributes: None,
+            },
+            loro::TextDelta::Delete { delete: 6 },
+            loro::TextDelta::Retain {
+                retain: 11,
+                attributes: None,
+            },
+            loro::TextDelta::Delete { delete: 4 },
+        ];
+        let summary = summarize("# Intro\nHello\n# Plan\n- a\n- b\n", &deltas);
+        assert_eq!(summary.sections, ["Intro", "Plan"]);
+        assert_eq!(summary.to_string(), "−10 chars in 2 sections, −2 lines");
+
+        // Setext headings, a single character
+        let deltas = [
+            loro::TextDelta::Retain {
+                retain: 12,
+                attributes: None,
+            },
+            loro::TextDelta::Insert {
+                insert: "🐽".to_string(),
+                attributes: None,
+            },
+        ];
+        let summary = summarize("Notes\n=====\nText", &deltas);
+        assert_eq!(summary.to_string(), "+1 char in §Notes");
+        assert!(summarize("Text", &[]).is_empty());
+    }
+
+    #[test]
+    fn checkpoints() {
+        let context = glib::MainContext::default();
+
+        let resource = TestResource::new();
+        let service = resource.service();
+        service.startup();
+        let document = Document::new(&service, None);
+        document.set_subscribed(true);
+        context.iteration(false);
+        assert!(
+            document
+                .insert_text(0, "Title\nFirst draft\nThe end")
+                .is_ok()
+        );
+
+        let id = document.create_checkpoint("Draft v1").unwrap();
+        assert_eq!(document.checkpoints().n_items(), 1);
+        let checkpoint = document.checkpoints().by_id(&id).unwrap();
+        assert_eq!(checkpoint.name(), "Draft v1");
+        assert!(checkpoint.created_at().is_some());
+
+        assert!(document.delete_range(6, 17).is_ok());
+        assert!(document.insert_text(6, "Second draft").is_ok());
+        assert_eq!(
+            document.checkpoint_text(&checkpoint).unwrap(),
+            "Title\nFirst draft\nThe end"
+        );
+        assert_eq!(
+            document.diff_against(&checkpoint).unwrap(),
+            vec![
+                DiffLine::Unchanged("Title".into()),
+                DiffLine::Removed("First draft".into()),
+                DiffLine::Added("Second draft".into()),
+                DiffLine::Unchanged("The end".into()),
+            ]
+        );
+        let checkpoints: Vec<Checkpoint> =
+            document.checkpoints().iter().map(Result::unwrap).collect();
+        assert_eq!(checkpoints, vec![checkpoint]);
+
+        assert_eq!(
+            diff_lines("a\nb\nc", "a\nc\nd"),
+            vec![
+                DiffLine::Unchanged("a".into()),
+                DiffLine::Removed("b".into()),
+                DiffLine::Unchanged("c".into()),
+                DiffLine::Added("d".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn export_history() {
+        let context = glib::MainContext::default();
+
+        let resource = TestResource::new();
+        let service = resource.service();
+        service.startup();
+        let document = Document::new(&service, None);
+        document.set_subscribed(true);
+        context.iteration(false);
+        assert!(document.insert_text(0, "Title\nBody").is_ok());
+
+        let patches = document.export_history().unwrap();
+        assert!(patches.starts_with("From 0000000000000000000000000000000000000000"));
+        assert!(patches.contains("Subject: [PATCH 1/1] "));
+        assert!(patches.contains("new file mode 100644\n--- /dev/null\n+++ b/"));
+        assert!(patches.contains("@@ -0,0 +1,2 @@\n+Title\n+Body\n"));
+
+        let old: Vec<String> = (1..=10).map(|line| line.to_string()).collect();
+        let mut new = old.clone();
+        new[1] = "changed".into();
+        new.push("11".into());
+        assert_eq!(
+            unified_diff(&diff_lines(&old.join("\n"), &new.join("\n"))),
+            "@@ -1,5 +1,5 @@\n 1\n-2\n+changed\n 3\n 4\n 5\n\
+             @@ -8,3 +8,4 @@\n 8\n 9\n 10\n+11\n"
+        );
+    }
+
+    #[test]
+    fn anchors() {
+        let context = glib::MainContext::default();
+
+        let resource = TestResource::new();
+        let service = resource.service();
+        service.startup();
+        let document = Document::new(&service, None);
+        document.set_subscribed(true);
+        context.iteration(false);
+        assert!(document.insert_text(0, "Hello World").is_ok());
+
+        let anchor = document.anchor_at(6).unwrap();
+        assert!(document.insert_text(0, "Oh, ").is_ok());
+        assert_eq!(document.resolve(&anchor), Some(10));
+        assert!(document.delete_range(0, 4).is_ok());
+        assert_eq!(document.resolve(&anchor), Some(6));
+
+        let decoded = Anchor::from_bytes(&anchor.to_bytes()).unwrap();
+        assert!(document.delete_range(6, 11).is_ok());
+        assert_eq!(document.resolve(&decoded), Some(6));
+        assert!(Anchor::from_bytes(b"not an anchor").is_none());
+    }
+
+    #[test]
+    fn anonymous_author() {
+        let author = Author::new(&PrivateKey::new().public_key());
+        let name = author.name();
+        assert!(!name.starts_with("Anonymous"));
+
+        author.set_anonymous(true);
+        assert_eq!(author.name(), format!("Anonymous {}", author.emoji()));
+        author.set_anonymous(false);
+        assert_eq!(author.name(), name);
+    }
+
+    #[test]
+    fn author_aliases() {
+        let authors = Authors::new();
+        let fox = PrivateKey::new().public_key();
+        let owl = PrivateKey::new().public_key();
+        authors.ensure_author(fox.clone());
+        let fox_author = authors.by_public_key(&fox).unwrap();
+        let name = fox_author.name();
+
+        authors.set_alias(&fox, Some("Ana".to_owned()));
+        assert_eq!(fox_author.name(), "Ana");
+        assert_eq!(fox_author.alias().as_deref(), Some("Ana"));
+
+        // Aliases of authors we don't know yet apply once they show up
+        authors.set_aliases([(owl.0, "Bo".to_owned())].into());
+        assert_eq!(fox_author.name(), name);
+        authors.ensure_author(owl.clone());
+        assert_eq!(authors.by_public_key(&owl).unwrap().name(), "Bo");
+
+        // They win over the name of anonymous sessions
+        fox_author.set_anonymous(true);
+        authors.set_alias(&fox, Some("Ana".to_owned()));
+        assert_eq!(fox_author.name(), "Ana");
+        authors.set_alias(&fox, None);
+        assert_eq!(
+            fox_author.name(),
+            format!("Anonymous {}", fox_author.emoji())
+        );
+    }
+
+    #[test]
+    fn presence() {
+        let resource = TestResource::new();
+        let service = resource.service();
+
+        // Nothing is shared unless enabled
+        service.set_away(true);
+        assert_eq!(service.presence(), None);
+        service.set_share_presence(true);
+        assert_eq!(service.presence(), Some(Presence::Away));
+        service.set_away(false);
+        assert_eq!(service.presence(), Some(Presence::Active));
+    }
+
+    #[test]
+    fn title() {
+        let context = glib::MainContext::default();
+
+        let resource = TestResource::new();
+        let service = resource.service();
+        service.startup();
+        let document = Document::new(&service, None);
+        document.set_subscribed(true);
+        context.iteration(false);
+        assert!(document.insert_text(0, "First line\nSecond line").is_ok());
+        assert_eq!(document.name().as_deref(), Some("First line"));
+
+        // The title takes precedence over the first line
+        document.set_title("Plan");
+        assert_eq!(document.title(), "Plan");
+        assert_eq!(document.name().as_deref(), Some("Plan"));
+        assert_eq!(document.text(), "First line\nSecond line");
+
+        document.set_title("Meeting Plan 2");
+        assert_eq!(document.title(), "Meeting Plan 2");
+        assert_eq!(document.name().as_deref(), Some("Meeting Plan 2"));
+
+        document.set_title("");
+        assert_eq!(document.name().as_deref(), Some("First line"));
+    }
+
+    #[test]
+    fn reconfigure_running_service() {
+        let context = glib::MainContext::default();
+        let apply = |service: &Service, config: NetworkConfig| {
+            service.set_network_config(config);
+            while context.pending() {
+                context.iteration(false);
+            }
+        };
+
+        let resource = TestResource::new();
+        let service = resource.service();
+        service.startup();
+        let started_with = service.network_config();
+
+        // The session timeout is applied right away
+        apply(
+            &service,
+            NetworkConfig {
+                session_timeout: started_with.session_timeout * 2,
+                ..started_with.clone()
+            },
+        );
+        assert!(!service.restart_required());
+
+        // The port only after a restart, unless it's changed back
+        apply(
+            &service,
+            NetworkConfig {
+                port: started_with.port.wrapping_add(1),
+                ..started_with.clone()
+            },
+        );
+        assert!(service.restart_required());
+        apply(&service, started_with);
+        assert!(!service.restart_required());
+
+        service.shutdown();
+    }
+
+    #[test]
+    fn crdt_backends_converge() {
+        for kind in CrdtKind::ALL {
+            let mut alice = kind.create();
+            alice.insert(0, "Hello World").unwrap();
+            alice.commit();
+            let mut bob = kind.load(&alice.export_snapshot().unwrap()).unwrap();
+            assert_eq!(bob.text(), "Hello World", "{kind}");
+
+            // Concurrent edits
+            let alice_version = alice.version();
+            let bob_version = bob.version();
+            alice.insert(5, ",").unwrap();
+            alice.commit();
+            bob.splice(6, 5, "Aardvark 🐽").unwrap();
+            bob.commit();
+
+            let from_alice = alice.export_updates(&bob_version).unwrap();
+            let from_bob = bob.export_updates(&alice_version).unwrap();
+            alice.import(&from_bob).unwrap();
+            bob.import(&from_alice).unwrap();
+            assert_eq!(alice.text(), "Hello, Aardvark 🐽", "{kind}");
+            assert_eq!(bob.text(), alice.text(), "{kind}");
+            assert_eq!(bob.len(), 17, "{kind}");
+        }
+    }
+
+    #[test]
+    fn crdt_anchors_follow_concurrent_edits() {
+        for kind in CrdtKind::ALL {
+            let mut alice = kind.create();
+            alice.insert(0, "Hello World").unwrap();
+            alice.commit();
+            let mut bob = kind.load(&alice.export_snapshot().unwrap()).unwrap();
+
+            // Bob anchors "World", Alice edits in front of it at the same time
+            let anchor = bob.anchor_at(6).unwrap();
+            let bob_version = bob.version();
+            alice.splice(0, 5, "Oh hi").unwrap();
+            alice.insert(0, "🐽 ").unwrap();
+            alice.commit();
+            bob.import(&alice.export_updates(&bob_version).unwrap())
+                .unwrap();
+            assert_eq!(bob.text(), "🐽 Oh hi World", "{kind}");
+            assert_eq!(bob.resolve(&anchor), Some(8), "{kind}");
+            assert_eq!(alice.resolve(&anchor), Some(8), "{kind}");
+
+            // The anchor stays where its character was
+            bob.delete(7, 3).unwrap();
+            assert_eq!(bob.resolve(&anchor), Some(7), "{kind}");
+        }
+    }
+
+    #[test]
+    fn convert_between_crdt_backends() {
+        for from_kind in CrdtKind::ALL {
+            let mut from = from_kind.create();
+            from.insert(0, "# Agenda\n- 🥕\n").unwrap();
+            from.delete(0, 2).unwrap();
+            from.commit();
+
+            for to_kind in CrdtKind::ALL {
+                let mut to = to_kind.create();
+                to.insert(0, "replaced").unwrap();
+                convert(from.as_ref(), to.as_mut()).unwrap();
+                assert_eq!(to.text(), "Agenda\n- 🥕\n", "{from_kind} to {to_kind}");
+
+                let loaded = to_kind.load(&to.export_snapshot().unwrap()).unwrap();
+                assert_eq!(loaded.text(), to.text(), "{from_kind} to {to_kind}");
+            }
+        }
+
+        assert_eq!("loro".parse::<CrdtKind>().unwrap(), CrdtKind::Loro);
+        assert!("yjs".parse::<CrdtKind>().is_err());
+    }
 }
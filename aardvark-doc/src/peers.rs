@@ -0,0 +1,123 @@
+//! Peers currently connected to a document, as opposed to its authors.
+//!
+//! [`Authors`] lists everyone who ever contributed to a document or connected to it while it was
+//! open, [`Peers`] only who we're connected to right now. It follows the gossip overlay of the
+//! document: peers are added when they join or become our neighbors, removed when they leave and
+//! all of them are removed when we unsubscribe. Both lists share the same [`Author`] objects.
+use std::sync::Mutex;
+
+use gio::prelude::*;
+use gio::subclass::prelude::ListModelImpl;
+use glib::subclass::prelude::*;
+
+use crate::author::Author;
+use crate::authors::Authors;
+use crate::identity::PublicKey;
+
+mod imp {
+    use super::*;
+
+    #[derive(Default)]
+    pub struct Peers {
+        pub list: Mutex<Vec<Author>>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for Peers {
+        const NAME: &'static str = "AardvarkPeers";
+        type Type = super::Peers;
+        type Interfaces = (gio::ListModel,);
+    }
+
+    impl ObjectImpl for Peers {}
+
+    impl ListModelImpl for Peers {
+        fn item_type(&self) -> glib::Type {
+            Author::static_type()
+        }
+
+        fn n_items(&self) -> u32 {
+            self.list.lock().unwrap().len() as u32
+        }
+
+        fn item(&self, index: u32) -> Option<glib::Object> {
+            self.list
+                .lock()
+                .unwrap()
+                .get(index as usize)
+                .cloned()
+                .map(Cast::upcast)
+        }
+    }
+}
+
+glib::wrapper! {
+    pub struct Peers(ObjectSubclass<imp::Peers>)
+    @implements gio::ListModel;
+}
+
+impl Default for Peers {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Peers {
+    pub fn new() -> Self {
+        glib::Object::new()
+    }
+
+    pub fn by_public_key(&self, author_key: &PublicKey) -> Option<Author> {
+        self.imp()
+            .list
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|author| &author.public_key() == author_key)
+            .cloned()
+    }
+
+    /// Add or remove a peer, it's added to `authors` as well if it's not known yet.
+    pub(crate) fn set_connected(&self, authors: &Authors, author_key: PublicKey, connected: bool) {
+        let mut list = self.imp().list.lock().unwrap();
+        let position = list
+            .iter()
+            .position(|author| author.public_key() == author_key);
+
+        match (position, connected) {
+            (None, true) => {
+                authors.ensure_author(author_key.clone());
+                let Some(author) = authors.by_public_key(&author_key) else {
+                    return;
+                };
+                let position = list.len() as u32;
+                list.push(author.clone());
+                drop(list);
+
+                author.set_is_online(true);
+                self.items_changed(position, 0, 1);
+            }
+            (Some(position), false) => {
+                let author = list.remove(position);
+                drop(list);
+
+                author.set_is_online(false);
+                self.items_changed(position as u32, 1, 0);
+            }
+            _ => {}
+        }
+    }
+
+    /// Remove all peers, e.g. when we unsubscribed from the document.
+    pub(crate) fn clear(&self) {
+        let peers = std::mem::take(&mut *self.imp().list.lock().unwrap());
+        if peers.is_empty() {
+            return;
+        }
+
+        for author in &peers {
+            author.set_is_online(false);
+        }
+        self.items_changed(0, peers.len() as u32, 0);
+    }
+}
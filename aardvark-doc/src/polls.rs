@@ -0,0 +1,59 @@
+//! Polls embedded in a document, for quick decisions while writing together.
+//!
+//! The text only contains a marker line, e.g. `<!-- poll:4f1c… -->`, so published documents
+//! don't show it. Question, options and votes are stored in a map next to the text CRDT.
+
+use crate::identity::PublicKey;
+
+const MARKER_PREFIX: &str = "<!-- poll:";
+const MARKER_SUFFIX: &str = " -->";
+
+/// A poll and the votes of all authors.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Poll {
+    pub id: String,
+    pub question: String,
+    pub options: Vec<String>,
+    /// The option every author voted for, by index. Authors have at most one vote.
+    pub votes: Vec<(PublicKey, usize)>,
+}
+
+impl Poll {
+    /// Number of votes for every option, in the order of `options`.
+    pub fn results(&self) -> Vec<usize> {
+        let mut results = vec![0; self.options.len()];
+        for (_, option) in &self.votes {
+            if let Some(count) = results.get_mut(*option) {
+                *count += 1;
+            }
+        }
+        results
+    }
+
+    /// The option the given author voted for.
+    pub fn vote_of(&self, author: &PublicKey) -> Option<usize> {
+        self.votes
+            .iter()
+            .find(|(voter, _)| voter == author)
+            .map(|(_, option)| *option)
+    }
+}
+
+/// Line referencing the poll with the given id.
+pub fn poll_marker(id: &str) -> String {
+    format!("{MARKER_PREFIX}{id}{MARKER_SUFFIX}")
+}
+
+/// Id of the poll referenced by a line, if the line is a poll marker.
+pub fn parse_poll_marker(line: &str) -> Option<&str> {
+    let id = line
+        .trim()
+        .strip_prefix(MARKER_PREFIX)?
+        .strip_suffix(MARKER_SUFFIX)?;
+    (!id.is_empty() && !id.contains(char::is_whitespace)).then_some(id)
+}
+
+/// Ids of all polls referenced in a text, in the order they appear.
+pub fn extract_poll_ids(text: &str) -> Vec<&str> {
+    text.lines().filter_map(parse_poll_marker).collect()
+}
@@ -0,0 +1,257 @@
+//! Latency of the editing pipeline, to spot performance regressions while working on it.
+//!
+//! Local edits are timed from the text buffer hook until their deltas were handed to the
+//! network, remote patches from the node receiving them until the next frame showed them. Nothing
+//! is recorded while the profiler is disabled.
+use std::cell::{Cell, RefCell};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use glib::Properties;
+use glib::prelude::*;
+use glib::subclass::{Signal, prelude::*};
+
+/// Samples kept for every kind of latency.
+const MAX_SAMPLES: usize = 100;
+
+/// Where the time of a single local edit, e.g. a keystroke, went.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct EditLatency {
+    /// Handling the change in the text buffer, without the time spent in the CRDT.
+    pub buffer_hook: Duration,
+    /// Inserting or deleting the text in the CRDT.
+    pub crdt_apply: Duration,
+    /// Committing the change and encoding it as delta.
+    pub delta_encode: Duration,
+    /// Creating operations for the delta and queuing them for the network.
+    pub channel_send: Duration,
+}
+
+impl EditLatency {
+    pub fn total(&self) -> Duration {
+        self.buffer_hook + self.crdt_apply + self.delta_encode + self.channel_send
+    }
+}
+
+/// Where the time of a patch received from another author went.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PatchLatency {
+    /// From the node receiving the patch until the main loop picked it up.
+    pub receive: Duration,
+    /// Importing the patch into the CRDT, including updating the text buffer.
+    pub apply: Duration,
+    /// Until the next frame was drawn.
+    pub render: Duration,
+}
+
+impl PatchLatency {
+    pub fn total(&self) -> Duration {
+        self.receive + self.apply + self.render
+    }
+}
+
+/// A local edit on its way through the pipeline.
+///
+/// The buffer hook is done before the deltas are sent, the edit is complete once both happened.
+#[derive(Debug)]
+pub(crate) struct PendingEdit {
+    started: Instant,
+    latency: Mutex<EditLatency>,
+    /// Deltas of this edit which weren't sent yet.
+    sending: Mutex<usize>,
+    hook_done: Mutex<bool>,
+}
+
+mod imp {
+    use super::*;
+
+    #[derive(Debug, Default, Properties)]
+    #[properties(wrapper_type = super::Profiler)]
+    pub struct Profiler {
+        /// Enabled while the profiler overlay is shown.
+        #[property(get, set = Self::set_enabled, explicit_notify)]
+        enabled: Cell<bool>,
+        pub(super) edits: RefCell<VecDeque<EditLatency>>,
+        pub(super) patches: RefCell<VecDeque<PatchLatency>>,
+        pub(super) current_edit: RefCell<Option<Arc<PendingEdit>>>,
+        /// When the CRDT started committing the current edit.
+        pub(super) commit_started: Cell<Option<Instant>>,
+        /// Applied patches waiting for the next frame, with when they were applied.
+        pub(super) rendering: RefCell<Vec<(Instant, PatchLatency)>>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for Profiler {
+        const NAME: &'static str = "AardvarkProfiler";
+        type Type = super::Profiler;
+    }
+
+    #[glib::derived_properties]
+    impl ObjectImpl for Profiler {
+        fn signals() -> &'static [Signal] {
+            static SIGNALS: OnceLock<Vec<Signal>> = OnceLock::new();
+            SIGNALS.get_or_init(|| {
+                vec![
+                    // A new sample was recorded.
+                    Signal::builder("updated").build(),
+                ]
+            })
+        }
+    }
+
+    impl Profiler {
+        fn set_enabled(&self, enabled: bool) {
+            if self.enabled.replace(enabled) == enabled {
+                return;
+            }
+
+            self.current_edit.take();
+            self.commit_started.take();
+            self.rendering.borrow_mut().clear();
+            self.obj().notify_enabled();
+        }
+    }
+}
+
+glib::wrapper! {
+    pub struct Profiler(ObjectSubclass<imp::Profiler>);
+}
+
+impl Default for Profiler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Profiler {
+    pub fn new() -> Self {
+        glib::Object::new()
+    }
+
+    /// The most recent local edits, oldest first.
+    pub fn edits(&self) -> Vec<EditLatency> {
+        self.imp().edits.borrow().iter().copied().collect()
+    }
+
+    /// The most recent remote patches, oldest first.
+    pub fn patches(&self) -> Vec<PatchLatency> {
+        self.imp().patches.borrow().iter().copied().collect()
+    }
+
+    /// Called by the text buffer when the user changed the text.
+    pub fn begin_edit(&self) {
+        if !self.enabled() {
+            return;
+        }
+
+        self.imp().current_edit.replace(Some(Arc::new(PendingEdit {
+            started: Instant::now(),
+            latency: Mutex::new(EditLatency::default()),
+            sending: Mutex::new(0),
+            hook_done: Mutex::new(false),
+        })));
+    }
+
+    /// Called by the text buffer once it handled the change.
+    pub fn end_edit(&self) {
+        let Some(edit) = self.imp().current_edit.take() else {
+            return;
+        };
+
+        {
+            let mut latency = edit.latency.lock().unwrap();
+            latency.buffer_hook = edit
+                .started
+                .elapsed()
+                .saturating_sub(latency.crdt_apply + latency.delta_encode);
+        }
+        *edit.hook_done.lock().unwrap() = true;
+        self.finish_edit(&edit);
+    }
+
+    pub(crate) fn add_crdt_apply(&self, duration: Duration) {
+        if let Some(edit) = self.imp().current_edit.borrow().as_ref() {
+            edit.latency.lock().unwrap().crdt_apply += duration;
+            self.imp().commit_started.set(Some(Instant::now()));
+        }
+    }
+
+    /// Called when the CRDT emitted the delta of the current edit, returns the edit to pass to
+    /// [`Self::delta_sent`] once the delta was sent.
+    pub(crate) fn delta_encoded(&self) -> Option<Arc<PendingEdit>> {
+        let started = self.imp().commit_started.take()?;
+        let edit = self.imp().current_edit.borrow().clone()?;
+
+        edit.latency.lock().unwrap().delta_encode += started.elapsed();
+        *edit.sending.lock().unwrap() += 1;
+        Some(edit)
+    }
+
+    pub(crate) fn delta_sent(&self, edit: &PendingEdit, duration: Duration) {
+        edit.latency.lock().unwrap().channel_send += duration;
+        *edit.sending.lock().unwrap() -= 1;
+        self.finish_edit(edit);
+    }
+
+    fn finish_edit(&self, edit: &PendingEdit) {
+        if !*edit.hook_done.lock().unwrap() || *edit.sending.lock().unwrap() > 0 {
+            return;
+        }
+
+        let latency = *edit.latency.lock().unwrap();
+        push_sample(&mut self.imp().edits.borrow_mut(), latency);
+        self.emit_by_name::<()>("updated", &[]);
+    }
+
+    pub(crate) fn patch_applied(&self, receive: Duration, apply: Duration) {
+        if !self.enabled() {
+            return;
+        }
+
+        let latency = PatchLatency {
+            receive,
+            apply,
+            render: Duration::ZERO,
+        };
+        self.imp()
+            .rendering
+            .borrow_mut()
+            .push((Instant::now(), latency));
+    }
+
+    /// Called by the view showing the document for every frame, completes the timing of patches
+    /// applied before it.
+    pub fn frame_drawn(&self) {
+        let rendering: Vec<_> = self.imp().rendering.borrow_mut().drain(..).collect();
+        if rendering.is_empty() {
+            return;
+        }
+
+        let mut patches = self.imp().patches.borrow_mut();
+        for (applied, mut latency) in rendering {
+            latency.render = applied.elapsed();
+            push_sample(&mut patches, latency);
+        }
+        drop(patches);
+        self.emit_by_name::<()>("updated", &[]);
+    }
+
+    /// Connect to the signal emitted when a new sample was recorded.
+    pub fn connect_updated<F: Fn(&Self) + 'static>(&self, f: F) -> glib::SignalHandlerId {
+        self.connect_closure(
+            "updated",
+            false,
+            glib::closure_local!(move |obj: Self| {
+                f(&obj);
+            }),
+        )
+    }
+}
+
+fn push_sample<T>(samples: &mut VecDeque<T>, sample: T) {
+    if samples.len() == MAX_SAMPLES {
+        samples.pop_front();
+    }
+    samples.push_back(sample);
+}
@@ -1,19 +1,105 @@
-use gio::prelude::FileExt;
+use gio::prelude::{FileExt, ListModelExtManual};
 use glib::Properties;
+use glib::clone;
 use glib::object::ObjectExt;
 use glib::subclass::prelude::*;
 use p2panda_core::Hash;
+use std::cell::{Cell, RefCell};
+use std::collections::HashSet;
 use std::sync::OnceLock;
+use std::time::Duration;
 use tracing::error;
 
+use crate::debug::{BandwidthInfo, StoreStats, SyncSessionInfo};
 use crate::identity::{PrivateKey, PublicKey};
+use crate::presence::Presence;
 use crate::{
     author::Author,
     authors::Authors,
     document::{Document, DocumentId},
     documents::Documents,
 };
-use aardvark_node::Node;
+use aardvark_core::backup::Backup;
+use aardvark_core::{DEFAULT_NETWORK_ID, GossipTuning, Node, NodeCommand};
+
+pub use aardvark_core::BandwidthLimits;
+pub use aardvark_core::ColdStorageBackend;
+pub use aardvark_core::NetworkConfig;
+pub use aardvark_core::integrity::{CorruptedLog, StoreReport};
+pub use aardvark_core::nearby::NearbyPeer;
+pub use aardvark_core::pairing::PairingCode;
+pub use aardvark_core::self_test::{Check, CheckResult, Finding, Status};
+
+/// How often pins in cold storage are refreshed with newer snapshots.
+const COLD_STORAGE_REFRESH_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Presets for different numbers of peers, they apply to all documents.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, glib::Enum)]
+#[enum_type(name = "AardvarkGossipPreset")]
+pub enum GossipPreset {
+    #[default]
+    SmallGroup,
+    Classroom,
+    LargeEvent,
+}
+
+impl From<GossipPreset> for aardvark_core::GossipPreset {
+    fn from(value: GossipPreset) -> Self {
+        match value {
+            GossipPreset::SmallGroup => aardvark_core::GossipPreset::SmallGroup,
+            GossipPreset::Classroom => aardvark_core::GossipPreset::Classroom,
+            GossipPreset::LargeEvent => aardvark_core::GossipPreset::LargeEvent,
+        }
+    }
+}
+
+/// A document another peer on the local network made discoverable, see
+/// [`Service::nearby_documents`].
+#[derive(Clone, Debug)]
+pub struct NearbyDocument {
+    pub id: DocumentId,
+    pub name: Option<String>,
+    /// Peer advertising the document, with the display name they announced.
+    pub public_key: PublicKey,
+    pub display_name: Option<String>,
+}
+
+/// Identity and documents another device of ours handed over, see [`Service::accept_device_link`].
+#[derive(Clone, Debug)]
+pub struct DeviceLink {
+    pub private_key: PrivateKey,
+    pub documents: Vec<(DocumentId, Option<String>)>,
+}
+
+/// Identity and documents read from a backup, see [`Service::export_backup`].
+#[derive(Clone, Debug)]
+pub struct AccountBackup {
+    pub private_key: PrivateKey,
+    backup: Backup,
+}
+
+impl AccountBackup {
+    /// Decrypt a backup with the passphrase it was exported with.
+    ///
+    /// Deriving the key from the passphrase is deliberately slow, don't call this on the main
+    /// thread.
+    pub fn open(bytes: &[u8], passphrase: &str) -> anyhow::Result<Self> {
+        let backup = Backup::open(bytes, passphrase)?;
+        Ok(Self {
+            private_key: PrivateKey(backup.private_key()?),
+            backup,
+        })
+    }
+
+    pub fn n_documents(&self) -> usize {
+        self.backup.documents.len()
+    }
+
+    /// The documents without the identity, see [`Service::restore_documents`].
+    pub fn documents_to_bytes(&self) -> anyhow::Result<Vec<u8>> {
+        self.backup.encode_documents()
+    }
+}
 
 mod imp {
     use super::*;
@@ -26,16 +112,72 @@ mod imp {
         pub private_key: OnceLock<PrivateKey>,
         #[property(get, set, construct_only, type = gio::File)]
         pub data_dir: OnceLock<gio::File>,
+        /// Services only find each other when they use the same network id, e.g. to keep tests
+        /// apart from the app.
+        #[property(get, set, construct_only)]
+        network_id: RefCell<String>,
         #[property(get)]
         documents: Documents,
+        /// Applied to the running node, see `restart-required`.
+        #[property(get, set = Self::set_gossip_preset, builder(GossipPreset::default()))]
+        gossip_preset: Cell<GossipPreset>,
+        pub network_config: RefCell<NetworkConfig>,
+        /// Whether the gossip preset or network config changed in ways which only take effect
+        /// after a restart.
+        #[property(get)]
+        restart_required: Cell<bool>,
+        pub running: Cell<bool>,
+        /// We're away from the device, set by the frontend following the user's activity.
+        #[property(get, set = Self::set_away)]
+        away: Cell<bool>,
+        /// The identity is a throwaway one, peers are told to call us "Anonymous", see
+        /// [`crate::anonymity`]. Nothing is stored beyond the data directory.
+        #[property(get, set, construct_only)]
+        anonymous: Cell<bool>,
+        /// Tell other authors whether we're away, see [`crate::presence`].
+        #[property(get, set = Self::set_share_presence)]
+        share_presence: Cell<bool>,
+        pub blocked_authors: RefCell<HashSet<p2panda_core::PublicKey>>,
     }
 
     #[glib::derived_properties]
     impl ObjectImpl for Service {}
 
+    impl Service {
+        fn set_gossip_preset(&self, preset: GossipPreset) {
+            if self.gossip_preset.replace(preset) != preset {
+                self.obj().notify_gossip_preset();
+                self.obj().reconfigure();
+            }
+        }
+
+        fn set_away(&self, away: bool) {
+            if self.away.replace(away) != away {
+                self.obj().notify_away();
+                if let Some(presence) = self.obj().presence() {
+                    self.obj().broadcast_presence(presence);
+                }
+            }
+        }
+
+        fn set_share_presence(&self, share_presence: bool) {
+            if self.share_presence.replace(share_presence) != share_presence {
+                self.obj().notify_share_presence();
+                // Nobody should keep seeing us as away after we stopped sharing it
+                if self.away.get() {
+                    self.obj().broadcast_presence(if share_presence {
+                        Presence::Away
+                    } else {
+                        Presence::Active
+                    });
+                }
+            }
+        }
+    }
+
     #[glib::object_subclass]
     impl ObjectSubclass for Service {
-        const NAME: &'static str = "Service";
+        const NAME: &'static str = "AardvarkService";
         type Type = super::Service;
     }
 }
@@ -46,9 +188,33 @@ glib::wrapper! {
 
 impl Service {
     pub fn new(private_key: &PrivateKey, data_dir: &gio::File) -> Self {
+        Self::with_network_id(private_key, data_dir, DEFAULT_NETWORK_ID)
+    }
+
+    /// Create a service on its own network, it doesn't see services on other networks.
+    ///
+    /// Any number of services can run in the same process as long as they use different data
+    /// directories, e.g. to test syncing between peers.
+    pub fn with_network_id(
+        private_key: &PrivateKey,
+        data_dir: &gio::File,
+        network_id: &str,
+    ) -> Self {
         glib::Object::builder()
             .property("private-key", private_key)
             .property("data-dir", data_dir)
+            .property("network-id", network_id)
+            .build()
+    }
+
+    /// Create a service for an anonymous session, `private_key` is expected to be a throwaway
+    /// one and `data_dir` to be removed after the session.
+    pub fn new_anonymous(private_key: &PrivateKey, data_dir: &gio::File) -> Self {
+        glib::Object::builder()
+            .property("private-key", private_key)
+            .property("data-dir", data_dir)
+            .property("network-id", DEFAULT_NETWORK_ID)
+            .property("anonymous", true)
             .build()
     }
 
@@ -56,22 +222,43 @@ impl Service {
         glib::MainContext::new().block_on(async move {
             let private_key = self.private_key().0.clone();
             let public_key = private_key.public_key();
-            let network_id = Hash::new(b"aardvark <3");
+            let network_id = Hash::new(self.network_id().as_bytes());
             let path = self.data_dir().path().expect("Valid file path");
-            if let Err(error) = self
+            let result = self
                 .imp()
                 .node
-                .run(private_key.clone(), network_id, Some(path.as_ref()))
-                .await
-            {
-                error!("Running node failed: {error}");
+                .run(
+                    private_key.clone(),
+                    network_id,
+                    Some(path.as_ref()),
+                    aardvark_core::GossipPreset::from(self.gossip_preset()).tuning(),
+                    self.network_config(),
+                )
+                .await;
+            match result {
+                Ok(()) => self.imp().running.set(true),
+                Err(error) => error!("Running node failed: {error}"),
+            }
+
+            match self.imp().node.blocked_authors().await {
+                Ok(blocked_authors) => {
+                    self.imp().blocked_authors.replace(blocked_authors);
+                }
+                Err(error) => error!("Failed to load blocked authors: {error}"),
             }
 
             if let Ok(documents) = self.imp().node.documents().await {
                 for document in documents {
-                    let last_accessed = document.last_accessed.and_then(|last_accessed| {
-                        glib::DateTime::from_unix_utc(last_accessed.timestamp()).ok()
-                    });
+                    let to_date_time = |timestamp: Option<i64>| {
+                        timestamp
+                            .and_then(|timestamp| glib::DateTime::from_unix_utc(timestamp).ok())
+                    };
+                    let last_accessed =
+                        to_date_time(document.last_accessed.map(|date| date.timestamp()));
+                    let created_at = to_date_time(document.created_at.map(|date| date.timestamp()));
+                    let last_edited =
+                        to_date_time(document.last_edited.map(|date| date.timestamp()));
+                    let trashed_at = to_date_time(document.trashed_at.map(|date| date.timestamp()));
 
                     let authors: Vec<Author> = document
                         .authors
@@ -90,14 +277,25 @@ impl Service {
                             }
                         })
                         .collect();
+                    for author in &authors {
+                        author.set_blocked(self.is_author_blocked(&author.public_key()));
+                    }
 
                     let authors = Authors::from_vec(authors);
+                    authors.set_aliases(document.author_aliases);
                     // The document is inserted automatically in the document list
                     let _document = Document::with_state(
                         self,
                         Some(&DocumentId(document.id)),
                         document.name.as_deref(),
                         last_accessed.as_ref(),
+                        created_at.as_ref(),
+                        last_edited.as_ref(),
+                        document.discoverable,
+                        document.archived,
+                        trashed_at.as_ref(),
+                        document.pinned,
+                        document.position,
                         &authors,
                     );
                 }
@@ -113,6 +311,376 @@ impl Service {
         });
     }
 
+    /// Returns the shared instance of the document with the given id.
+    ///
+    /// The document is created if it isn't known yet, e.g. when joining a document of another peer.
+    pub fn document(&self, document_id: &DocumentId) -> Document {
+        self.documents()
+            .by_id(document_id)
+            .unwrap_or_else(|| Document::new(self, Some(document_id)))
+    }
+
+    /// Sizes of the local database.
+    pub async fn store_stats(&self) -> anyhow::Result<StoreStats> {
+        self.imp().node.store_stats().await
+    }
+
+    /// Settings the gossip overlay is running with.
+    pub async fn gossip_tuning(&self) -> GossipTuning {
+        self.imp().node.gossip_tuning().await
+    }
+
+    /// Limits of sync sessions the node runs with.
+    pub fn network_config(&self) -> NetworkConfig {
+        self.imp().network_config.borrow().clone()
+    }
+
+    /// Change the limits of sync sessions, applied to the running node, see `restart-required`.
+    pub fn set_network_config(&self, config: NetworkConfig) {
+        if self.imp().network_config.replace(config.clone()) != config {
+            self.reconfigure();
+        }
+    }
+
+    /// Apply the gossip preset and network config to the running node.
+    fn reconfigure(&self) {
+        if !self.imp().running.get() {
+            return;
+        }
+
+        let command = NodeCommand::Reconfigure {
+            tuning: aardvark_core::GossipPreset::from(self.gossip_preset()).tuning(),
+            network_config: self.network_config(),
+        };
+        glib::spawn_future_local(clone!(
+            #[weak(rename_to = this)]
+            self,
+            async move {
+                match this.imp().node.command(command).await {
+                    Ok(restart_required) => {
+                        let previous = this.imp().restart_required.replace(restart_required);
+                        if previous != restart_required {
+                            this.notify_restart_required();
+                        }
+                    }
+                    Err(error) => error!("Failed to reconfigure node: {error}"),
+                }
+            }
+        ));
+    }
+
+    /// Currently running sync sessions with other peers.
+    pub async fn sync_sessions(&self) -> Vec<SyncSessionInfo> {
+        self.imp().node.sync_sessions().await
+    }
+
+    /// Current sync traffic and its limits, see [`NetworkConfig::bandwidth`].
+    ///
+    /// Updates `download-rate` of the authors of all documents as well.
+    pub async fn bandwidth(&self) -> BandwidthInfo {
+        let info = self.imp().node.bandwidth().await;
+        for document in self.documents().iter::<Document>().filter_map(Result::ok) {
+            for author in document.authors().iter::<Author>().filter_map(Result::ok) {
+                let rate = info
+                    .peer_download_rates
+                    .get(&author.public_key().0)
+                    .copied()
+                    .unwrap_or_default();
+                author.set_download_rate(rate);
+            }
+        }
+        info
+    }
+
+    /// Pause or resume a running sync session, see [`Self::sync_sessions`].
+    pub async fn set_sync_session_paused(&self, id: u64, paused: bool) -> anyhow::Result<()> {
+        self.imp().node.set_sync_session_paused(id, paused).await
+    }
+
+    /// Abort a running sync session, see [`Self::sync_sessions`].
+    pub async fn cancel_sync_session(&self, id: u64) -> anyhow::Result<()> {
+        self.imp().node.cancel_sync_session(id).await
+    }
+
+    /// Keep encrypted snapshots of all documents pinned on a blob endpoint, `None` stops pinning.
+    ///
+    /// The endpoint is reached through the SOCKS5 `proxy` as `host:port` if there is one.
+    /// Snapshots are uploaded right away and refreshed periodically afterwards.
+    pub async fn set_cold_storage(
+        &self,
+        backend: Option<ColdStorageBackend>,
+        proxy: Option<String>,
+    ) {
+        self.imp()
+            .node
+            .set_cold_storage(backend, proxy, COLD_STORAGE_REFRESH_INTERVAL)
+            .await
+    }
+
+    /// Change what we tell peers on the local network about ourselves, before any document is
+    /// synced with them. Nothing is announced for fields which are `None`.
+    pub async fn set_peer_metadata(
+        &self,
+        display_name: Option<String>,
+        app_version: Option<String>,
+    ) {
+        self.imp()
+            .node
+            .set_peer_metadata(display_name, app_version)
+            .await
+    }
+
+    /// Peers on the local network which announced themselves recently.
+    pub async fn nearby_peers(&self) -> Vec<NearbyPeer> {
+        self.imp().node.nearby_peers().await
+    }
+
+    /// Documents nearby peers advertise which we don't know yet, most recently seen first.
+    pub async fn nearby_documents(&self) -> Vec<NearbyDocument> {
+        let mut nearby_documents: Vec<NearbyDocument> = Vec::new();
+        for peer in self.nearby_peers().await {
+            for document in peer.metadata.documents {
+                let id = DocumentId(document.id);
+                if self.documents().by_id(&id).is_some()
+                    || nearby_documents.iter().any(|nearby| nearby.id == id)
+                {
+                    continue;
+                }
+                nearby_documents.push(NearbyDocument {
+                    id,
+                    name: document.name,
+                    public_key: PublicKey(peer.public_key),
+                    display_name: peer.metadata.display_name.clone(),
+                });
+            }
+        }
+        nearby_documents
+    }
+
+    /// Hand our identity and documents to another device of ours which joins with `code`.
+    ///
+    /// Returns once the other device received them. Dropping the future cancels linking.
+    pub async fn link_device(&self, code: &PairingCode) -> anyhow::Result<()> {
+        self.imp().node.link_device(code).await
+    }
+
+    /// Receive the identity and documents of another device of ours showing `code`.
+    ///
+    /// This doesn't change the identity of this service, it has to be restarted with the new
+    /// private key. Dropping the future cancels linking.
+    pub async fn accept_device_link(&self, code: &PairingCode) -> anyhow::Result<DeviceLink> {
+        let bundle = self.imp().node.accept_device_link(code).await?;
+        Ok(DeviceLink {
+            private_key: PrivateKey(bundle.private_key()?),
+            documents: bundle
+                .documents
+                .into_iter()
+                .map(|document| (DocumentId(document.id), document.name))
+                .collect(),
+        })
+    }
+
+    /// Add documents handed over by another device of ours, see [`Self::accept_device_link`].
+    ///
+    /// Their content is synced from other peers once they are opened.
+    pub async fn add_linked_documents(
+        &self,
+        documents: &[(DocumentId, Option<String>)],
+    ) -> anyhow::Result<()> {
+        let public_key = self.private_key().public_key();
+        for (document_id, name) in documents {
+            self.imp()
+                .node
+                .add_document(&document_id.0, name.clone())
+                .await?;
+            if self.documents().by_id(document_id).is_some() {
+                continue;
+            }
+
+            let authors = Authors::from_vec(vec![Author::for_this_device(&public_key)]);
+            // The document is inserted automatically in the document list
+            let _document = Document::with_state(
+                self,
+                Some(document_id),
+                name.as_deref(),
+                None,
+                glib::DateTime::now_utc().ok().as_ref(),
+                None,
+                false,
+                false,
+                None,
+                &authors,
+            );
+        }
+
+        Ok(())
+    }
+
+    pub fn is_author_blocked(&self, public_key: &PublicKey) -> bool {
+        self.imp().blocked_authors.borrow().contains(&public_key.0)
+    }
+
+    /// Block or unblock an author in all documents.
+    ///
+    /// Operations and messages of blocked authors are dropped and their logs aren't synced
+    /// anymore, what they wrote before stays in the documents.
+    pub async fn set_author_blocked(
+        &self,
+        public_key: &PublicKey,
+        blocked: bool,
+    ) -> anyhow::Result<()> {
+        if blocked {
+            self.imp().node.block_author(public_key.0).await?;
+            self.imp().blocked_authors.borrow_mut().insert(public_key.0);
+        } else {
+            self.imp().node.unblock_author(public_key.0).await?;
+            self.imp()
+                .blocked_authors
+                .borrow_mut()
+                .remove(&public_key.0);
+        }
+
+        for document in self.documents().iter::<Document>().filter_map(Result::ok) {
+            if let Some(author) = document.authors().by_public_key(public_key) {
+                author.set_blocked(blocked);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Check the store for corrupted data, and move it out of the way if `repair` is set.
+    pub async fn verify_store(&self, repair: bool) -> anyhow::Result<StoreReport> {
+        self.imp().node.verify_store(repair).await
+    }
+
+    /// Bundle the identity and all documents with their full history into a backup encrypted with
+    /// `passphrase`.
+    pub async fn export_backup(&self, passphrase: &str) -> anyhow::Result<Vec<u8>> {
+        self.imp().node.export_backup(passphrase).await
+    }
+
+    /// Add the documents of a backup, see [`AccountBackup::documents_to_bytes`].
+    ///
+    /// The service has to use the identity of the backup. Documents which are already known keep
+    /// their state, only missing operations are added to them.
+    pub async fn restore_documents(&self, bytes: &[u8]) -> anyhow::Result<()> {
+        let documents = Backup::decode_documents(bytes)?;
+        self.imp().node.restore_backup(documents.clone()).await?;
+
+        let public_key = self.private_key().public_key();
+        for document in documents {
+            let document_id = DocumentId(document.id);
+            if self.documents().by_id(&document_id).is_some() {
+                continue;
+            }
+
+            let authors: Vec<Author> = document
+                .authors
+                .into_iter()
+                .map(|author| {
+                    let author = PublicKey(author);
+                    if author == public_key {
+                        Author::for_this_device(&author)
+                    } else {
+                        Author::with_state(&author, None)
+                    }
+                })
+                .collect();
+            for author in &authors {
+                author.set_blocked(self.is_author_blocked(&author.public_key()));
+            }
+            // The document is inserted automatically in the document list
+            let _document = Document::with_state(
+                self,
+                Some(&document_id),
+                document.name.as_deref(),
+                None,
+                glib::DateTime::now_utc().ok().as_ref(),
+                None,
+                false,
+                document.archived,
+                None,
+                document.pinned,
+                None,
+                &Authors::from_vec(authors),
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Delete all documents in the trash right away.
+    ///
+    /// Documents which are still open somewhere stay in the trash.
+    pub async fn empty_trash(&self) -> anyhow::Result<()> {
+        let trashed: Vec<Document> = self
+            .documents()
+            .iter::<Document>()
+            .filter_map(Result::ok)
+            .filter(|document| document.trashed_at().is_some() && !document.subscribed())
+            .collect();
+        for document in trashed {
+            self.imp().node.delete_document(&document.id().0).await?;
+            self.documents().remove(&document);
+        }
+
+        Ok(())
+    }
+
+    /// Check the connection to other devices, `report` is called on the main context with the
+    /// result of every check as soon as it's done.
+    ///
+    /// Another device running the test at the same time is used as test peer. Dropping the future
+    /// stops the checks.
+    pub async fn self_test(&self, report: impl Fn(CheckResult) + 'static) -> anyhow::Result<()> {
+        let (sender, receiver) = async_channel::unbounded();
+        let reporting = glib::spawn_future_local(async move {
+            while let Ok(result) = receiver.recv().await {
+                report(result);
+            }
+        });
+
+        let result = self
+            .imp()
+            .node
+            .self_test(move |result| {
+                let _ = sender.try_send(result);
+            })
+            .await;
+        // The sender is gone once the test finished, wait until every result was reported
+        let _ = reporting.await;
+
+        result
+    }
+
+    /// Broadcast a message which is not persisted to all peers subscribed to a document.
+    pub async fn send_ephemeral_message(
+        &self,
+        document_id: &DocumentId,
+        bytes: Vec<u8>,
+    ) -> anyhow::Result<()> {
+        self.imp().node.ephemeral(document_id.0, bytes).await
+    }
+
+    /// Our presence as we tell it to other authors, `None` if we don't share it.
+    pub fn presence(&self) -> Option<Presence> {
+        if !self.share_presence() {
+            return None;
+        }
+        Some(if self.away() {
+            Presence::Away
+        } else {
+            Presence::Active
+        })
+    }
+
+    fn broadcast_presence(&self, presence: Presence) {
+        for document in self.documents().iter::<Document>().filter_map(Result::ok) {
+            document.send_presence(presence);
+        }
+    }
+
     pub(crate) fn node(&self) -> &Node {
         &self.imp().node
     }
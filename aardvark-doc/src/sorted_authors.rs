@@ -0,0 +1,211 @@
+//! The authors of a document in the order chosen by the user.
+//!
+//! [`SortedAuthors`] wraps the [`Authors`] of a document, it keeps the authors sorted and
+//! optionally hides those who aren't connected, so every list of authors shows them the same way.
+//! This device is always listed first.
+use std::cell::{Cell, OnceCell, RefCell};
+use std::cmp::Ordering;
+
+use gio::prelude::*;
+use gio::subclass::prelude::ListModelImpl;
+use glib::Properties;
+use glib::clone;
+use glib::subclass::prelude::*;
+
+use crate::author::Author;
+use crate::authors::Authors;
+
+/// Author properties the order depends on.
+const SORT_PROPERTIES: [&str; 4] = ["is-online", "last-seen", "blocked", "name"];
+
+/// How authors are ordered.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, glib::Enum)]
+#[enum_type(name = "AardvarkAuthorOrder")]
+pub enum AuthorOrder {
+    /// Connected authors before the others, each group alphabetically.
+    #[default]
+    OnlineFirst,
+    /// Most recently seen authors first, connected authors count as seen right now.
+    RecentActivity,
+    Alphabetical,
+}
+
+impl AuthorOrder {
+    fn compare(self, a: &Author, b: &Author) -> Ordering {
+        let by_name = || {
+            a.name()
+                .to_lowercase()
+                .cmp(&b.name().to_lowercase())
+                .then_with(|| a.public_key().to_string().cmp(&b.public_key().to_string()))
+        };
+
+        b.is_this_device()
+            .cmp(&a.is_this_device())
+            .then_with(|| match self {
+                AuthorOrder::OnlineFirst => b.is_online().cmp(&a.is_online()),
+                AuthorOrder::RecentActivity => last_active(b).cmp(&last_active(a)),
+                AuthorOrder::Alphabetical => Ordering::Equal,
+            })
+            .then_with(by_name)
+    }
+}
+
+/// When the author was last active, `None` if they were never seen.
+fn last_active(author: &Author) -> Option<i64> {
+    if author.is_online() {
+        Some(i64::MAX)
+    } else {
+        author.last_seen().map(|last_seen| last_seen.to_unix())
+    }
+}
+
+mod imp {
+    use super::*;
+
+    #[derive(Default, Properties)]
+    #[properties(wrapper_type = super::SortedAuthors)]
+    pub struct SortedAuthors {
+        #[property(get, set = Self::set_model, construct_only)]
+        model: OnceCell<Authors>,
+        #[property(get, set = Self::set_order, explicit_notify, builder(AuthorOrder::default()))]
+        order: Cell<AuthorOrder>,
+        /// Hide authors who aren't connected right now.
+        #[property(get, set = Self::set_online_only, explicit_notify)]
+        online_only: Cell<bool>,
+        list: RefCell<Vec<Author>>,
+        handlers: RefCell<Vec<(Author, glib::SignalHandlerId)>>,
+        items_changed_handler: RefCell<Option<glib::SignalHandlerId>>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for SortedAuthors {
+        const NAME: &'static str = "AardvarkSortedAuthors";
+        type Type = super::SortedAuthors;
+        type Interfaces = (gio::ListModel,);
+    }
+
+    #[glib::derived_properties]
+    impl ObjectImpl for SortedAuthors {
+        fn dispose(&self) {
+            for (author, handler) in self.handlers.take() {
+                author.disconnect(handler);
+            }
+            if let (Some(model), Some(handler)) =
+                (self.model.get(), self.items_changed_handler.take())
+            {
+                model.disconnect(handler);
+            }
+        }
+    }
+
+    impl ListModelImpl for SortedAuthors {
+        fn item_type(&self) -> glib::Type {
+            Author::static_type()
+        }
+
+        fn n_items(&self) -> u32 {
+            self.list.borrow().len() as u32
+        }
+
+        fn item(&self, index: u32) -> Option<glib::Object> {
+            self.list
+                .borrow()
+                .get(index as usize)
+                .cloned()
+                .map(Cast::upcast)
+        }
+    }
+
+    impl SortedAuthors {
+        fn set_model(&self, model: Authors) {
+            for author in model.iter::<Author>().flatten() {
+                self.watch_author(&author);
+            }
+            let handler = model.connect_items_changed(clone!(
+                #[weak(rename_to = this)]
+                self,
+                move |model, position, _, added| {
+                    for author in (position..position + added)
+                        .filter_map(|index| model.item(index).and_downcast::<Author>())
+                    {
+                        this.watch_author(&author);
+                    }
+                    this.update();
+                }
+            ));
+            self.items_changed_handler.replace(Some(handler));
+            self.model.set(model).unwrap();
+            self.update();
+        }
+
+        fn set_order(&self, order: AuthorOrder) {
+            if self.order.replace(order) != order {
+                self.update();
+                self.obj().notify_order();
+            }
+        }
+
+        fn set_online_only(&self, online_only: bool) {
+            if self.online_only.replace(online_only) != online_only {
+                self.update();
+                self.obj().notify_online_only();
+            }
+        }
+
+        fn watch_author(&self, author: &Author) {
+            let mut handlers = self.handlers.borrow_mut();
+            if handlers.iter().any(|(watched, _)| watched == author) {
+                return;
+            }
+
+            for property in SORT_PROPERTIES {
+                let handler = author.connect_notify_local(
+                    Some(property),
+                    clone!(
+                        #[weak(rename_to = this)]
+                        self,
+                        move |_, _| this.update()
+                    ),
+                );
+                handlers.push((author.clone(), handler));
+            }
+        }
+
+        /// Sort and filter the authors again, the list is replaced as a whole if it changed.
+        fn update(&self) {
+            let Some(model) = self.model.get() else {
+                return;
+            };
+
+            let online_only = self.online_only.get();
+            let mut authors: Vec<Author> = model
+                .iter::<Author>()
+                .flatten()
+                .filter(|author| !online_only || author.is_online())
+                .collect();
+            let order = self.order.get();
+            authors.sort_by(|a, b| order.compare(a, b));
+
+            let removed = {
+                let mut list = self.list.borrow_mut();
+                if *list == authors {
+                    return;
+                }
+                std::mem::replace(&mut *list, authors).len() as u32
+            };
+            let added = self.list.borrow().len() as u32;
+            self.obj().items_changed(0, removed, added);
+        }
+    }
+}
+
+glib::wrapper! {
+    pub struct SortedAuthors(ObjectSubclass<imp::SortedAuthors>)
+    @implements gio::ListModel;
+}
+
+impl SortedAuthors {
+    pub fn new(model: &Authors) -> Self {
+        glib::Object::builder().property("model", model).build()
+    }
+}
@@ -0,0 +1,147 @@
+//! Short summaries of changes to the text, like "+120 chars in §Introduction, −2 lines".
+//!
+//! The history sidebar shows them next to every batch of changes, so it's clear what a change
+//! was about without opening a diff. Sections are the markdown headings above the changed text.
+
+use std::fmt;
+
+use loro::TextDelta;
+
+/// What a batch of deltas changed, see [`summarize`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ChangeSummary {
+    /// Characters inserted.
+    pub inserted: usize,
+    /// Characters deleted.
+    pub deleted: usize,
+    /// Lines added, i.e. line breaks inserted.
+    pub lines_added: usize,
+    /// Lines removed, i.e. line breaks deleted.
+    pub lines_removed: usize,
+    /// Titles of the sections containing changes, in the order of the text.
+    pub sections: Vec<String>,
+}
+
+impl ChangeSummary {
+    pub fn is_empty(&self) -> bool {
+        self.inserted == 0 && self.deleted == 0
+    }
+
+    /// How many lines the text grew by, negative if it shrank.
+    pub fn lines(&self) -> isize {
+        self.lines_added as isize - self.lines_removed as isize
+    }
+
+    /// The section all changes are in, if they are in a single one.
+    pub fn section(&self) -> Option<&str> {
+        match self.sections.as_slice() {
+            [section] => Some(section),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ChangeSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return f.write_str("no changes");
+        }
+
+        let plural = |count: usize, unit: &str| {
+            if count == 1 {
+                format!("{count} {unit}")
+            } else {
+                format!("{count} {unit}s")
+            }
+        };
+        let mut chars = Vec::new();
+        if self.inserted > 0 {
+            chars.push(format!("+{}", plural(self.inserted, "char")));
+        }
+        if self.deleted > 0 {
+            chars.push(format!("−{}", plural(self.deleted, "char")));
+        }
+        f.write_str(&chars.join(" "))?;
+
+        match self.sections.len() {
+            0 => {}
+            1 => write!(f, " in §{}", self.sections[0])?,
+            count => write!(f, " in {count} sections")?,
+        }
+
+        match self.lines() {
+            0 => Ok(()),
+            lines if lines > 0 => write!(f, ", +{}", plural(lines as usize, "line")),
+            lines => write!(f, ", −{}", plural(lines.unsigned_abs(), "line")),
+        }
+    }
+}
+
+/// Summarize `deltas` applied to `text`, the text before the change.
+pub fn summarize(text: &str, deltas: &[TextDelta]) -> ChangeSummary {
+    let headings = headings(text);
+    let mut summary = ChangeSummary::default();
+    let mut sections: Vec<String> = Vec::new();
+    let mut add_section = |index: usize| {
+        let Some((_, title)) = headings.iter().rev().find(|(start, _)| *start <= index) else {
+            return;
+        };
+        if !sections.contains(title) {
+            sections.push(title.clone());
+        }
+    };
+
+    let mut chars = text.chars();
+    let mut index = 0;
+    for delta in deltas {
+        match delta {
+            TextDelta::Retain { retain, .. } => {
+                chars.by_ref().take(*retain).for_each(drop);
+                index += retain;
+            }
+            TextDelta::Delete { delete } => {
+                add_section(index);
+                summary.deleted += delete;
+                summary.lines_removed += chars
+                    .by_ref()
+                    .take(*delete)
+                    .filter(|char| *char == '\n')
+                    .count();
+                index += delete;
+            }
+            TextDelta::Insert { insert, .. } => {
+                add_section(index);
+                summary.inserted += insert.chars().count();
+                summary.lines_added += insert.matches('\n').count();
+            }
+        }
+    }
+
+    summary.sections = sections;
+    summary
+}
+
+/// The headings of `text` with the position of their first character, in both styles.
+fn headings(text: &str) -> Vec<(usize, String)> {
+    let mut headings = Vec::new();
+    let mut lines = text.split('\n').peekable();
+    let mut start = 0;
+    while let Some(line) = lines.next() {
+        let title = line.trim();
+        let hashes = title.chars().take_while(|char| *char == '#').count();
+        if (1..=6).contains(&hashes) && title[hashes..].starts_with([' ', '\t']) {
+            let title = title[hashes..].trim().trim_end_matches('#').trim_end();
+            headings.push((start, title.to_string()));
+        } else if !title.is_empty()
+            && lines.peek().is_some_and(|next| {
+                let next = next.trim_end();
+                !next.is_empty()
+                    && (next.chars().all(|c| c == '=') || next.chars().all(|c| c == '-'))
+            })
+        {
+            headings.push((start, title.to_string()));
+        }
+        start += line.chars().count() + 1;
+    }
+    headings
+}
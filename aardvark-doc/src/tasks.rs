@@ -0,0 +1,115 @@
+//! Tasks written as markdown checkboxes, e.g. `- [ ] Book venue`.
+
+use crate::identity::PublicKey;
+
+/// A checkbox item found in the text of a document.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Task {
+    /// Description of the task, without the checkbox.
+    pub text: String,
+    pub checked: bool,
+    /// Position of the character inside the brackets, in unicode characters.
+    pub marker_offset: usize,
+    /// Position of the first character of the description, in unicode characters.
+    pub text_offset: usize,
+    /// Author responsible for this task.
+    ///
+    /// This isn't part of the markdown, it's stored as a mark on the description in the text CRDT.
+    pub assignee: Option<PublicKey>,
+    /// Day the task should be done by, at local midnight.
+    ///
+    /// Stored as a mark on the description like the assignee.
+    pub due_date: Option<glib::DateTime>,
+}
+
+impl Task {
+    /// Range of the description, in unicode characters.
+    pub fn text_range(&self) -> std::ops::Range<usize> {
+        self.text_offset..self.text_offset + self.text.chars().count()
+    }
+
+    /// Whether the task is still open after its due date passed.
+    pub fn is_overdue(&self) -> bool {
+        !self.checked
+            && self
+                .due_date
+                .as_ref()
+                .is_some_and(|due_date| due_date < &today())
+    }
+}
+
+/// Start of the current day in the local time zone.
+pub fn today() -> glib::DateTime {
+    let now = glib::DateTime::now_local().expect("current time");
+    glib::DateTime::from_local(now.year(), now.month(), now.day_of_month(), 0, 0, 0.0)
+        .expect("constructing GDateTime works")
+}
+
+/// Dates are stored as `YYYY-MM-DD`, independent of the time zone of the author.
+pub(crate) fn format_due_date(due_date: &glib::DateTime) -> String {
+    format!(
+        "{:04}-{:02}-{:02}",
+        due_date.year(),
+        due_date.month(),
+        due_date.day_of_month()
+    )
+}
+
+pub(crate) fn parse_due_date(value: &str) -> Option<glib::DateTime> {
+    let mut parts = value.splitn(3, '-').map(|part| part.parse().ok());
+    let (Some(Some(year)), Some(Some(month)), Some(Some(day))) =
+        (parts.next(), parts.next(), parts.next())
+    else {
+        return None;
+    };
+
+    glib::DateTime::from_local(year, month, day, 0, 0, 0.0).ok()
+}
+
+/// Find all tasks in a markdown text.
+///
+/// A task is a list item starting with `[ ]` or `[x]`, it may be indented.
+pub fn extract_tasks(text: &str) -> Vec<Task> {
+    let mut tasks = Vec::new();
+    let mut line_offset = 0;
+
+    for line in text.split('\n') {
+        if let Some(task) = parse_task(line, line_offset) {
+            tasks.push(task);
+        }
+        line_offset += line.chars().count() + 1;
+    }
+
+    tasks
+}
+
+fn parse_task(line: &str, line_offset: usize) -> Option<Task> {
+    let trimmed = line.trim_start();
+    let indent = line.chars().count() - trimmed.chars().count();
+
+    let rest = trimmed
+        .strip_prefix("- ")
+        .or_else(|| trimmed.strip_prefix("* "))
+        .or_else(|| trimmed.strip_prefix("+ "))?;
+    let checked = match rest.get(..4)? {
+        "[ ] " => false,
+        "[x] " | "[X] " => true,
+        _ => return None,
+    };
+
+    let description = rest[4..].trim_end();
+    if description.is_empty() {
+        return None;
+    }
+
+    // List marker and space are two characters, followed by the opening bracket
+    let marker_offset = line_offset + indent + 3;
+    Some(Task {
+        text: description.to_owned(),
+        checked,
+        marker_offset,
+        text_offset: marker_offset + 3,
+        assignee: None,
+        due_date: None,
+    })
+}
@@ -1,9 +0,0 @@
-pub mod document;
-mod network;
-mod node;
-mod operation;
-mod store;
-mod utils;
-
-pub use document::SubscribableDocument;
-pub use node::Node;